@@ -33,3 +33,8 @@ $FOREST_CLI_PATH snapshot export --tipset 0
 
 echo "Testing genesis snapshot validity"
 zstd --test forest_snapshot_calibnet_2022-11-01_height_0.forest.car.zst
+
+echo "Exporting zstd compressed snapshot at the genesis tipset key"
+GENESIS_KEY=$($FOREST_CLI_PATH chain genesis | jq -r '[.Cids[]["/"]] | join(",")')
+$FOREST_CLI_PATH snapshot export --tipset "@$GENESIS_KEY" --skip-checksum -o genesis_by_key.forest.car.zst
+cmp genesis_by_key.forest.car.zst forest_snapshot_calibnet_2022-11-01_height_0.forest.car.zst
@@ -26,7 +26,7 @@ snapshot="$("$FOREST_TOOL_PATH" snapshot fetch --chain calibnet)"
 # Start Offline RPC servers on ports
 for i in "${!PORTS[@]}"; do
   port=${PORTS[$i]}
-  data_dir="offline-rpc-db-$((i + 1))"
+  data_dir="$PWD/offline-rpc-db-$((i + 1))"
   "$FOREST_TOOL_PATH" api serve "$snapshot" "$old_snapshot" --chain calibnet --port "$port" --data-dir "$data_dir" &
 done
 
@@ -48,10 +48,14 @@ for port in "${PORTS[@]}"; do
   diff "$parent_path/test_data/calibnet_block_3000.json" "$temp_dir/block.json"
 done
 
+# The offline servers write their admin token to their data directory
+forest_token=$(cat "$PWD/offline-rpc-db-1/admin_token")
+lotus_token=$(cat "$PWD/offline-rpc-db-2/admin_token")
+
 # TODO(aatifsyed): https://github.com/ChainSafe/forest/pull/4096
 #                  `--filter` logic should be commonised
 # Compare the http endpoints
-$FOREST_TOOL_PATH api compare "$snapshot" --forest /ip4/127.0.0.1/tcp/8080/http --lotus /ip4/127.0.0.1/tcp/8081/http --n-tipsets 5 '--filter=!Filecoin.StateWaitMsg'
+$FOREST_TOOL_PATH api compare "$snapshot" --forest "$forest_token:/ip4/127.0.0.1/tcp/8080/http" --lotus "$lotus_token:/ip4/127.0.0.1/tcp/8081/http" --n-tipsets 5 '--filter=!Filecoin.StateWaitMsg'
 
 # Compare the ws endpoints
-$FOREST_TOOL_PATH api compare "$snapshot" --forest /ip4/127.0.0.1/tcp/8080/ws --lotus /ip4/127.0.0.1/tcp/8081/ws --n-tipsets 5 '--filter=!Filecoin.StateWaitMsg'
+$FOREST_TOOL_PATH api compare "$snapshot" --forest "$forest_token:/ip4/127.0.0.1/tcp/8080/ws" --lotus "$lotus_token:/ip4/127.0.0.1/tcp/8081/ws" --n-tipsets 5 '--filter=!Filecoin.StateWaitMsg'
@@ -18,11 +18,18 @@ $FOREST_CLI_PATH attach --exec 'showPeers()'
 
 echo "Test dev commands (which could brick the node/cause subsequent snapshots to fail)"
 
+echo "Test subcommand: chain head --format json"
+$FOREST_CLI_PATH chain head --format json | jq -e '.Height > 0'
+
 echo "Test subcommand: chain set-head"
 $FOREST_CLI_PATH chain set-head --epoch -10 --force
 
+echo "Test subcommand: chain prune"
+$FOREST_CLI_PATH chain prune --no-wait
+
 echo "Test subcommand: info show"
 $FOREST_CLI_PATH info show
+$FOREST_CLI_PATH info show --output json | jq -e '.Epoch > 0 and .Network != null'
 
 echo "Test subcommand: net info"
 $FOREST_CLI_PATH net info
@@ -57,7 +57,7 @@ $FOREST_WALLET_PATH set-default "$ADDR_ONE"
 
 $FOREST_WALLET_PATH list
 
-MSG=$($FOREST_CLI_PATH send "$ADDR_TWO" "$FIL_AMT")
+MSG=$($FOREST_CLI_PATH send --yes "$ADDR_TWO" "$FIL_AMT")
 : "$MSG"
 
 ADDR_TWO_BALANCE=0
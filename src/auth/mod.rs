@@ -3,12 +3,15 @@
 
 use crate::key_management::KeyInfo;
 use crate::shim::crypto::SignatureType;
-use chrono::{Duration, Utc};
+use chrono::{DateTime, Duration, Utc};
 use jsonwebtoken::{decode, encode, errors::Result as JWTResult, DecodingKey, EncodingKey, Header};
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+mod tokens;
+pub use tokens::{TokenInfo, TokenRegistry};
+
 /// constant string that is used to identify the JWT secret key in `KeyStore`
 pub const JWT_IDENTIFIER: &str = "auth-jwt-private";
 /// Admin permissions
@@ -41,25 +44,81 @@ pub enum Error {
 struct Claims {
     #[serde(rename = "Allow")]
     allow: Vec<String>,
-    // Expiration time (as UTC timestamp)
-    exp: usize,
+    // Expiration time (as UTC timestamp), the token never expires if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    exp: Option<usize>,
+    // Token identifier, used to revoke the token
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    jti: Option<String>,
 }
 
 /// Create a new JWT Token
 pub fn create_token(perms: Vec<String>, key: &[u8], token_exp: Duration) -> JWTResult<String> {
-    let exp_time = Utc::now() + token_exp;
+    encode_token(perms, key, Some(Utc::now() + token_exp), None)
+}
+
+/// Create a new JWT Token that can be revoked through its identifier, and
+/// never expires if `expires_at` is `None`
+pub fn create_token_with_id(
+    perms: Vec<String>,
+    key: &[u8],
+    expires_at: Option<DateTime<Utc>>,
+    id: String,
+) -> JWTResult<String> {
+    encode_token(perms, key, expires_at, Some(id))
+}
+
+fn encode_token(
+    perms: Vec<String>,
+    key: &[u8],
+    expires_at: Option<DateTime<Utc>>,
+    jti: Option<String>,
+) -> JWTResult<String> {
     let payload = Claims {
         allow: perms,
-        exp: exp_time.timestamp() as usize,
+        exp: expires_at.map(|time| time.timestamp() as usize),
+        jti,
     };
     encode(&Header::default(), &payload, &EncodingKey::from_secret(key))
 }
 
 /// Verify JWT Token and return the allowed permissions from token
 pub fn verify_token(token: &str, key: &[u8]) -> JWTResult<Vec<String>> {
-    let validation = jsonwebtoken::Validation::new(jsonwebtoken::Algorithm::default());
+    verify_token_with_id(token, key).map(|(perms, _)| perms)
+}
+
+/// Verify JWT Token and return the allowed permissions and the identifier
+/// from token
+pub fn verify_token_with_id(token: &str, key: &[u8]) -> JWTResult<(Vec<String>, Option<String>)> {
+    verify_token_claims(token, key).map(|claims| (claims.perms, claims.id))
+}
+
+/// Claims of a verified JWT Token
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VerifiedClaims {
+    pub perms: Vec<String>,
+    /// Identifier used to revoke the token
+    pub id: Option<String>,
+    /// The token never expires if unset
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Verify JWT Token and return all of its claims
+pub fn verify_token_claims(token: &str, key: &[u8]) -> JWTResult<VerifiedClaims> {
+    let mut validation = jsonwebtoken::Validation::new(jsonwebtoken::Algorithm::default());
+    // Tokens are issued and verified by the same node, so there's no clock
+    // drift to account for. Tokens without an expiration time never expire.
+    validation.leeway = 0;
+    validation.required_spec_claims.clear();
     let token = decode::<Claims>(token, &DecodingKey::from_secret(key), &validation)?;
-    Ok(token.claims.allow)
+    Ok(VerifiedClaims {
+        perms: token.claims.allow,
+        id: token.claims.jti,
+        expires_at: token
+            .claims
+            .exp
+            .and_then(|exp| DateTime::from_timestamp(exp as i64, 0)),
+    })
 }
 
 pub fn generate_priv_key() -> KeyInfo {
@@ -100,15 +159,30 @@ mod tests {
         .unwrap();
         assert!(verify_token(&token, key.private_key()).is_err());
 
-        // Token duration of -10 seconds (already expired, slightly). There is leeway of 60 seconds
-        // by default, so validation must pass.
+        // Token duration of -10 seconds (already expired, slightly). There is no leeway, so
+        // validation must fail.
         let token = create_token(
             perms_expected.clone(),
             key.private_key(),
             -Duration::try_seconds(10).expect("Infallible"),
         )
         .unwrap();
-        let perms = verify_token(&token, key.private_key()).unwrap();
+        let err = verify_token(&token, key.private_key()).unwrap_err();
+        assert_eq!(
+            err.kind(),
+            &jsonwebtoken::errors::ErrorKind::ExpiredSignature
+        );
+
+        // Token without expiration time. Validation must pass.
+        let token = create_token_with_id(
+            perms_expected.clone(),
+            key.private_key(),
+            None,
+            "kiosk".into(),
+        )
+        .unwrap();
+        let (perms, id) = verify_token_with_id(&token, key.private_key()).unwrap();
         assert_eq!(perms_expected, perms);
+        assert_eq!(id.as_deref(), Some("kiosk"));
     }
 }
@@ -0,0 +1,202 @@
+// Copyright 2019-2024 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::sync::Arc;
+
+use ahash::HashMap;
+use chrono::Duration;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::db::{setting_keys::AUTH_TOKENS_KEY, SettingsStore, SettingsStoreExt as _};
+use crate::lotus_json::lotus_json_with_self;
+
+/// Metadata of a JWT token issued through the `Filecoin.AuthNew` RPC method.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct TokenInfo {
+    /// Identifier of the token, matching its `jti` claim.
+    pub id: String,
+    pub perms: Vec<String>,
+    /// Unix timestamp of when the token was issued.
+    pub created: i64,
+    /// Unix timestamp of when the token expires, if ever.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(default)]
+    pub revoked: bool,
+}
+lotus_json_with_self!(TokenInfo);
+
+/// Thread-safe registry of the issued tokens, consulted on every RPC request
+/// so that revoked tokens are rejected even though their signature is valid.
+#[derive(Default)]
+pub struct TokenRegistry {
+    tokens: RwLock<HashMap<String, TokenInfo>>,
+    /// Where the registry is persisted to, if anywhere.
+    settings: Option<Arc<dyn SettingsStore + Sync + Send>>,
+    /// Longest lifetime of the non-admin tokens, unlimited if unset.
+    max_exp: Option<Duration>,
+}
+
+impl std::fmt::Debug for TokenRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TokenRegistry")
+            .field("tokens", &self.tokens)
+            .field("max_exp", &self.max_exp)
+            .finish_non_exhaustive()
+    }
+}
+
+impl TokenRegistry {
+    /// Creates a registry that is persisted in the settings store, loading the
+    /// previously issued tokens.
+    pub fn load(settings: Arc<dyn SettingsStore + Sync + Send>, max_exp: Option<Duration>) -> Self {
+        let tokens = match settings.read_obj::<Vec<TokenInfo>>(AUTH_TOKENS_KEY) {
+            Ok(tokens) => tokens
+                .into_iter()
+                .flatten()
+                .map(|token| (token.id.clone(), token))
+                .collect(),
+            Err(e) => {
+                warn!("Failed to load the issued tokens: {e}");
+                HashMap::default()
+            }
+        };
+        Self {
+            tokens: RwLock::new(tokens),
+            settings: Some(settings),
+            max_exp,
+        }
+    }
+
+    /// Checks that a token with the given permissions may be issued for the
+    /// given duration, `None` meaning that it never expires. Admin tokens
+    /// aren't limited.
+    pub fn check_expiration(
+        &self,
+        perms: &[String],
+        token_exp: Option<Duration>,
+    ) -> anyhow::Result<()> {
+        let Some(max_exp) = self.max_exp else {
+            return Ok(());
+        };
+        if perms.iter().any(|perm| perm == "admin") {
+            return Ok(());
+        }
+        match token_exp {
+            Some(token_exp) if token_exp <= max_exp => Ok(()),
+            _ => anyhow::bail!(
+                "non-admin tokens must expire within {} seconds",
+                max_exp.num_seconds()
+            ),
+        }
+    }
+
+    /// Records a newly issued token.
+    pub fn register(&self, token: TokenInfo) -> anyhow::Result<()> {
+        let mut tokens = self.tokens.write();
+        tokens.insert(token.id.clone(), token);
+        self.persist(&tokens)
+    }
+
+    /// Returns the issued tokens, oldest first.
+    pub fn list(&self) -> Vec<TokenInfo> {
+        let mut tokens = self.tokens.read().values().cloned().collect::<Vec<_>>();
+        tokens.sort_by(|a, b| a.created.cmp(&b.created).then_with(|| a.id.cmp(&b.id)));
+        tokens
+    }
+
+    /// Revokes the token with the given identifier.
+    pub fn revoke(&self, id: &str) -> anyhow::Result<()> {
+        let mut tokens = self.tokens.write();
+        let token = tokens
+            .get_mut(id)
+            .ok_or_else(|| anyhow::anyhow!("unknown token {id}"))?;
+        token.revoked = true;
+        self.persist(&tokens)
+    }
+
+    /// Returns `true` if the token with the given identifier has been revoked.
+    pub fn is_revoked(&self, id: &str) -> bool {
+        self.tokens
+            .read()
+            .get(id)
+            .is_some_and(|token| token.revoked)
+    }
+
+    fn persist(&self, tokens: &HashMap<String, TokenInfo>) -> anyhow::Result<()> {
+        match &self.settings {
+            Some(settings) => {
+                settings.write_obj(AUTH_TOKENS_KEY, &tokens.values().collect::<Vec<_>>())
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::MemoryDB;
+
+    fn token(id: &str) -> TokenInfo {
+        TokenInfo {
+            id: id.into(),
+            perms: vec!["read".into()],
+            created: 0,
+            expires: Some(60),
+            label: None,
+            revoked: false,
+        }
+    }
+
+    #[test]
+    fn revocation_survives_reload() {
+        let settings = Arc::new(MemoryDB::default());
+        let registry = TokenRegistry::load(settings.clone(), None);
+        registry.register(token("a")).unwrap();
+        registry.register(token("b")).unwrap();
+        registry.revoke("a").unwrap();
+        assert!(registry.revoke("c").is_err());
+
+        let registry = TokenRegistry::load(settings, None);
+        assert!(registry.is_revoked("a"));
+        assert!(!registry.is_revoked("b"));
+        assert!(!registry.is_revoked("c"));
+        assert_eq!(
+            registry.list(),
+            vec![
+                TokenInfo {
+                    revoked: true,
+                    ..token("a")
+                },
+                token("b")
+            ]
+        );
+    }
+
+    #[test]
+    fn expiration_limited_for_non_admin_tokens() {
+        let registry = TokenRegistry {
+            max_exp: Some(Duration::try_hours(1).expect("Infallible")),
+            ..Default::default()
+        };
+        let read = ["read".to_owned()];
+        let admin = ["read".to_owned(), "admin".to_owned()];
+        let minute = Some(Duration::try_minutes(1).expect("Infallible"));
+        let day = Some(Duration::try_days(1).expect("Infallible"));
+
+        assert!(registry.check_expiration(&read, minute).is_ok());
+        assert!(registry.check_expiration(&read, day).is_err());
+        assert!(registry.check_expiration(&read, None).is_err());
+        assert!(registry.check_expiration(&admin, day).is_ok());
+        assert!(registry.check_expiration(&admin, None).is_ok());
+        assert!(TokenRegistry::default()
+            .check_expiration(&read, None)
+            .is_ok());
+    }
+}
@@ -1,7 +1,7 @@
 // Copyright 2019-2024 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use std::{borrow::Cow, num::NonZeroUsize};
 
 use super::{
@@ -26,9 +26,14 @@ use url::Url;
 /// `LOTUS_IGNORE_DRAND`
 pub const IGNORE_DRAND_VAR: &str = "IGNORE_DRAND";
 
+/// Time after a round is produced within which its entry is expected to be
+/// published by the `drand` servers.
+const ENTRY_PUBLISH_GRACE: Duration = Duration::from_secs(30);
+
 /// Type of the `drand` network. `mainnet` is chained and `quicknet` is unchained.
 /// For the details, see <https://github.com/filecoin-project/FIPs/blob/1bd887028ac1b50b6f2f94913e07ede73583da5b/FIPS/fip-0063.md#specification>
-#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+#[derive(PartialEq, Eq, Copy, Clone, Debug, SerdeDeserialize, SerdeSerialize)]
+#[serde(rename_all = "lowercase")]
 pub enum DrandNetwork {
     Mainnet,
     Quicknet,
@@ -45,7 +50,7 @@ impl DrandNetwork {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq, Eq, SerdeDeserialize, SerdeSerialize)]
 /// Configuration used when initializing a `Drand` beacon.
 pub struct DrandConfig<'a> {
     /// Public endpoints of the `Drand` service.
@@ -171,6 +176,46 @@ where
         network_version: NetworkVersion,
         fil_epoch: ChainEpoch,
     ) -> u64;
+
+    /// Returns the UNIX timestamp in seconds at which a round is produced.
+    fn round_time(&self, round: u64) -> u64;
+
+    /// Returns the latest round produced by a UNIX timestamp in seconds.
+    fn round_at(&self, time: u64) -> u64;
+
+    /// Returns the entry of a round, waiting for the round to be produced if
+    /// it is due within `max_wait`. Fails for rounds due later, and for
+    /// rounds not published within [`ENTRY_PUBLISH_GRACE`] of being produced.
+    async fn entry_when_produced(
+        &self,
+        round: u64,
+        max_wait: Duration,
+    ) -> anyhow::Result<BeaconEntry> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?;
+        let due = Duration::from_secs(self.round_time(round));
+        let wait = due.saturating_sub(now);
+        anyhow::ensure!(
+            wait <= max_wait,
+            "beacon round {round} is produced in {}, later than the {} waited for at most",
+            humantime::format_duration(wait),
+            humantime::format_duration(max_wait)
+        );
+        let published_by = (due + ENTRY_PUBLISH_GRACE).saturating_sub(now);
+        if published_by.is_zero() {
+            return self.entry(round).await;
+        }
+        tokio::time::timeout(published_by, async {
+            tokio::time::sleep(wait).await;
+            self.entry(round).await
+        })
+        .await
+        .with_context(|| {
+            format!(
+                "beacon round {round} isn't published {} after being produced",
+                humantime::format_duration(ENTRY_PUBLISH_GRACE)
+            )
+        })?
+    }
 }
 
 #[async_trait]
@@ -199,6 +244,14 @@ impl Beacon for Box<dyn Beacon> {
         self.as_ref()
             .max_beacon_round_for_epoch(network_version, fil_epoch)
     }
+
+    fn round_time(&self, round: u64) -> u64 {
+        self.as_ref().round_time(round)
+    }
+
+    fn round_at(&self, time: u64) -> u64 {
+        self.as_ref().round_at(time)
+    }
 }
 
 #[derive(SerdeDeserialize, SerdeSerialize, Debug, Clone, PartialEq, Eq, Default)]
@@ -264,6 +317,34 @@ impl DrandBeacon {
     }
 }
 
+impl DrandBeacon {
+    /// Verifies an entry fetched from the `drand` servers, on its own if the
+    /// network is unchained, or else against the signature of the previous
+    /// round.
+    fn verify_fetched_entry(
+        &self,
+        entry: &BeaconEntry,
+        previous_signature: Option<&[u8]>,
+    ) -> anyhow::Result<bool> {
+        if self.network.is_unchained() {
+            let pk = PublicKeyOnG2::from_bytes(&self.public_key)?;
+            let signature = SignatureOnG1::from_bytes(entry.signature())?;
+            Ok(pk.verify(BeaconEntry::message_unchained(entry.round()), &signature))
+        } else {
+            let Some(previous_signature) = previous_signature else {
+                return Ok(false);
+            };
+            let pk = PublicKeyOnG1::from_bytes(&self.public_key)?;
+            let message = BeaconEntry::message_chained(entry.round(), previous_signature);
+            Ok(verify_messages_chained(
+                &pk,
+                &[message.as_ref()],
+                &[SignatureOnG2::from_bytes(entry.signature())?],
+            ))
+        }
+    }
+}
+
 #[async_trait]
 impl Beacon for DrandBeacon {
     fn network(&self) -> DrandNetwork {
@@ -340,7 +421,7 @@ impl Beacon for DrandBeacon {
             None => {
                 async fn fetch_entry_from_url(
                     url: impl reqwest::IntoUrl,
-                ) -> anyhow::Result<BeaconEntry> {
+                ) -> anyhow::Result<BeaconEntryJson> {
                     let resp: BeaconEntryJson = global_http_client()
                         .get(url)
                         // More tolerance on slow networks
@@ -350,12 +431,12 @@ impl Beacon for DrandBeacon {
                         .error_for_status()?
                         .json()
                         .await?;
-                    anyhow::Ok(BeaconEntry::new(resp.round, hex::decode(resp.signature)?))
+                    anyhow::Ok(resp)
                 }
 
                 async fn fetch_entry(
                     urls: impl Iterator<Item = impl reqwest::IntoUrl>,
-                ) -> anyhow::Result<BeaconEntry> {
+                ) -> anyhow::Result<BeaconEntryJson> {
                     let mut errors = vec![];
                     for url in urls {
                         match fetch_entry_from_url(url).await {
@@ -376,12 +457,24 @@ impl Beacon for DrandBeacon {
                         anyhow::Ok(server.join(&format!("{}/public/{round}", self.hash))?)
                     })
                     .try_collect()?;
-                Ok(
+                let resp =
                     backoff::future::retry(backoff::ExponentialBackoff::default(), || async {
                         Ok(fetch_entry(urls.iter().cloned()).await?)
                     })
-                    .await?,
-                )
+                    .await?;
+                anyhow::ensure!(
+                    resp.round == round,
+                    "drand servers returned round {} for round {round}",
+                    resp.round
+                );
+                let entry = BeaconEntry::new(resp.round, hex::decode(resp.signature)?);
+                let previous_signature = resp.previous_signature.map(hex::decode).transpose()?;
+                anyhow::ensure!(
+                    self.verify_fetched_entry(&entry, previous_signature.as_deref())?,
+                    "invalid signature of the beacon entry of round {round}"
+                );
+                self.verified_beacons.write().put(round, entry.clone());
+                Ok(entry)
             }
         }
     }
@@ -409,4 +502,16 @@ impl Beacon for DrandBeacon {
             from_genesis / self.interval + 1
         }
     }
+
+    fn round_time(&self, round: u64) -> u64 {
+        // Round 1 is produced at genesis
+        self.drand_gen_time + round.saturating_sub(1) * self.interval
+    }
+
+    fn round_at(&self, time: u64) -> u64 {
+        match time.checked_sub(self.drand_gen_time) {
+            Some(from_genesis) => from_genesis / self.interval + 1,
+            None => 0,
+        }
+    }
 }
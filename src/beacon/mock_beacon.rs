@@ -50,4 +50,13 @@ impl Beacon for MockBeacon {
     fn max_beacon_round_for_epoch(&self, _network_version: NetworkVersion, fil_epoch: i64) -> u64 {
         fil_epoch as u64
     }
+
+    // A round every second since the UNIX epoch
+    fn round_time(&self, round: u64) -> u64 {
+        round
+    }
+
+    fn round_at(&self, time: u64) -> u64 {
+        time
+    }
 }
@@ -2,11 +2,18 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use crate::{
-    beacon::{Beacon, ChainInfo, DrandBeacon, DrandConfig, DrandNetwork},
+    beacon::{
+        mock_beacon::MockBeacon, Beacon, BeaconEntry, BeaconPoint, BeaconSchedule, ChainInfo,
+        DrandBeacon, DrandConfig, DrandNetwork,
+    },
+    networks::{ChainConfig, DrandPoint},
     shim::version::NetworkVersion,
 };
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 fn new_beacon_mainnet() -> DrandBeacon {
     DrandBeacon::new(
@@ -35,29 +42,29 @@ fn new_beacon_mainnet() -> DrandBeacon {
 }
 
 fn new_beacon_quicknet() -> DrandBeacon {
-    DrandBeacon::new(
-        1598306400,
-        30,
-        &DrandConfig {
-            // https://drand.love/developer/http-api/#public-endpoints
-            servers: vec![
-                "https://api.drand.sh".try_into().unwrap(),
-                "https://api2.drand.sh".try_into().unwrap(),
-                "https://api3.drand.sh".try_into().unwrap(),
-                "https://drand.cloudflare.com".try_into().unwrap(),
-                "https://api.drand.secureweb3.com:6875".try_into().unwrap(),
-            ],
-            // https://api.drand.sh/52db9ba70e0cc0f6eaf7803dd07447a1f5477735fd3f661792ba94600c84e971/info
-            chain_info:  ChainInfo {
-                public_key: Cow::Borrowed("83cf0f2896adee7eb8b5f01fcad3912212c437e0073e911fb90022d3e760183c8c4b450b6a0a6c3ac6a5776a2d1064510d1fec758c921cc22b0e17e63aaf4bcb5ed66304de9cf809bd274ca73bab4af5a6e9c76a4bc09e76eae8991ef5ece45a"),
-                period: 3,
-                genesis_time: 1692803367,
-                hash: Cow::Borrowed("52db9ba70e0cc0f6eaf7803dd07447a1f5477735fd3f661792ba94600c84e971"),
-                group_hash: Cow::Borrowed("f477d5c89f21a17c863a7f937c6a6d15859414d2be09cd448d4279af331c5d3e"),
-            },
-            network_type: DrandNetwork::Quicknet,
+    DrandBeacon::new(1598306400, 30, &new_beacon_quicknet_config())
+}
+
+fn new_beacon_quicknet_config() -> DrandConfig<'static> {
+    DrandConfig {
+        // https://drand.love/developer/http-api/#public-endpoints
+        servers: vec![
+            "https://api.drand.sh".try_into().unwrap(),
+            "https://api2.drand.sh".try_into().unwrap(),
+            "https://api3.drand.sh".try_into().unwrap(),
+            "https://drand.cloudflare.com".try_into().unwrap(),
+            "https://api.drand.secureweb3.com:6875".try_into().unwrap(),
+        ],
+        // https://api.drand.sh/52db9ba70e0cc0f6eaf7803dd07447a1f5477735fd3f661792ba94600c84e971/info
+        chain_info:  ChainInfo {
+            public_key: Cow::Borrowed("83cf0f2896adee7eb8b5f01fcad3912212c437e0073e911fb90022d3e760183c8c4b450b6a0a6c3ac6a5776a2d1064510d1fec758c921cc22b0e17e63aaf4bcb5ed66304de9cf809bd274ca73bab4af5a6e9c76a4bc09e76eae8991ef5ece45a"),
+            period: 3,
+            genesis_time: 1692803367,
+            hash: Cow::Borrowed("52db9ba70e0cc0f6eaf7803dd07447a1f5477735fd3f661792ba94600c84e971"),
+            group_hash: Cow::Borrowed("f477d5c89f21a17c863a7f937c6a6d15859414d2be09cd448d4279af331c5d3e"),
         },
-    )
+        network_type: DrandNetwork::Quicknet,
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -128,3 +135,209 @@ fn test_max_beacon_round_for_epoch_quicknet() {
         ((1598306400 + 3547000 * 30) - 1692803367 - 30) / 3 + 1
     );
 }
+
+// https://api.drand.sh/52db9ba70e0cc0f6eaf7803dd07447a1f5477735fd3f661792ba94600c84e971/public/{2,3}
+async fn mock_quicknet_round(
+    axum::extract::Path((hash, round)): axum::extract::Path<(String, u64)>,
+) -> Result<axum::Json<serde_json::Value>, http::StatusCode> {
+    let signature = match round {
+        2 => "b6b6a585449b66eb12e875b64fcbab3799861a00e4dbf092d99e969a5eac57dd3f798acf61e705fe4f093db926626807",
+        3 => "b3fab6df720b68cc47175f2c777e86d84187caab5770906f515ff1099cb01e4deaa027075d860823e49477b93c72bd64",
+        _ => return Err(http::StatusCode::NOT_FOUND),
+    };
+    if hash != "52db9ba70e0cc0f6eaf7803dd07447a1f5477735fd3f661792ba94600c84e971" {
+        return Err(http::StatusCode::NOT_FOUND);
+    }
+    Ok(axum::Json(serde_json::json!({
+        "round": round,
+        "randomness": "",
+        "signature": signature,
+    })))
+}
+
+#[tokio::test]
+async fn ask_and_verify_beacon_entry_of_custom_schedule() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let server = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let app = axum::Router::new().route(
+            "/:hash/public/:round",
+            axum::routing::get(mock_quicknet_round),
+        );
+        axum::serve(listener, app).await.unwrap()
+    });
+
+    let quicknet = new_beacon_quicknet_config();
+    let config = ChainConfig {
+        drand_schedule: vec![DrandPoint {
+            height: 0,
+            config: DrandConfig {
+                servers: vec![server.parse().unwrap()],
+                ..quicknet
+            },
+        }],
+        ..ChainConfig::devnet()
+    };
+    let schedule = config.get_beacon_schedule(1598306400);
+    let [point] = schedule.0.as_slice() else {
+        panic!("the schedule has a single beacon");
+    };
+    assert_eq!(point.beacon.network(), DrandNetwork::Quicknet);
+
+    let e2 = point.beacon.entry(2).await.unwrap();
+    let e3 = point.beacon.entry(3).await.unwrap();
+    assert!(point
+        .beacon
+        .verify_entries(&[e2.clone(), e3.clone()], &e2)
+        .unwrap());
+    // Not one of the verified, cached entries
+    let forged = BeaconEntry::new(4, e3.signature().to_vec());
+    assert!(!point.beacon.verify_entries(&[forged], &e2).unwrap());
+}
+
+/// State of a mock `drand` quicknet server, which counts the requests for
+/// entries and doesn't publish them before `published_from`.
+#[derive(Clone, Default)]
+struct MockQuicknet {
+    requests: Arc<AtomicUsize>,
+    published_from: Option<SystemTime>,
+}
+
+async fn mock_published_quicknet_round(
+    axum::extract::State(state): axum::extract::State<MockQuicknet>,
+    path: axum::extract::Path<(String, u64)>,
+) -> Result<axum::Json<serde_json::Value>, http::StatusCode> {
+    state.requests.fetch_add(1, Ordering::Relaxed);
+    match state.published_from {
+        Some(published_from) if SystemTime::now() < published_from => {
+            Err(http::StatusCode::NOT_FOUND)
+        }
+        _ => mock_quicknet_round(path).await,
+    }
+}
+
+/// Serves the quicknet entries of rounds 2 and 3, with a beacon whose genesis
+/// is at `genesis_time`.
+async fn mock_quicknet_beacon(genesis_time: i32, state: MockQuicknet) -> DrandBeacon {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let server = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let app = axum::Router::new()
+            .route(
+                "/:hash/public/:round",
+                axum::routing::get(mock_published_quicknet_round),
+            )
+            .with_state(state);
+        axum::serve(listener, app).await.unwrap()
+    });
+
+    let quicknet = new_beacon_quicknet_config();
+    DrandBeacon::new(
+        1598306400,
+        30,
+        &DrandConfig {
+            servers: vec![server.parse().unwrap()],
+            chain_info: ChainInfo {
+                genesis_time,
+                ..quicknet.chain_info
+            },
+            ..quicknet
+        },
+    )
+}
+
+fn unix_now() -> i32 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+        .try_into()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn fetched_entries_are_verified_and_cached() {
+    let state = MockQuicknet::default();
+    let beacon = mock_quicknet_beacon(1692803367, state.clone()).await;
+
+    let e2 = beacon.entry(2).await.unwrap();
+    assert_eq!(state.requests.load(Ordering::Relaxed), 1);
+    assert_eq!(beacon.entry(2).await.unwrap(), e2);
+    assert_eq!(state.requests.load(Ordering::Relaxed), 1);
+    beacon.entry(3).await.unwrap();
+    assert_eq!(state.requests.load(Ordering::Relaxed), 2);
+    // Cached entries are verified already
+    assert!(beacon
+        .verify_entries(&[beacon.entry(3).await.unwrap()], &e2)
+        .unwrap());
+    assert_eq!(state.requests.load(Ordering::Relaxed), 2);
+}
+
+#[tokio::test]
+async fn entries_produced_soon_are_waited_for() {
+    // Round 3 is produced 2 seconds from now, with a period of 3 seconds, and
+    // published a second later
+    let genesis_time = unix_now() - 4;
+    let state = MockQuicknet {
+        published_from: Some(UNIX_EPOCH + Duration::from_secs(genesis_time as u64 + 7)),
+        ..Default::default()
+    };
+    let beacon = mock_quicknet_beacon(genesis_time, state.clone()).await;
+
+    let e3 = beacon
+        .entry_when_produced(3, Duration::from_secs(10))
+        .await
+        .unwrap();
+    assert_eq!(e3.round(), 3);
+    assert!(SystemTime::now() >= state.published_from.unwrap());
+}
+
+#[tokio::test]
+async fn entries_produced_far_ahead_are_refused() {
+    let state = MockQuicknet::default();
+    let beacon = mock_quicknet_beacon(unix_now(), state.clone()).await;
+
+    let error = beacon
+        .entry_when_produced(1000, Duration::from_secs(60))
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("later than"), "{error}");
+    assert_eq!(state.requests.load(Ordering::Relaxed), 0);
+}
+
+#[test]
+fn round_time_and_round_at_agree() {
+    let beacon = new_beacon_quicknet();
+    assert_eq!(beacon.round_time(1), 1692803367);
+    assert_eq!(beacon.round_at(1692803366), 0);
+    for round in 1..10 {
+        let time = beacon.round_time(round);
+        assert_eq!(beacon.round_at(time), round);
+        assert_eq!(beacon.round_at(time + 2), round);
+    }
+}
+
+#[tokio::test]
+async fn entries_for_block_after_null_rounds_are_newer_than_prev() {
+    let schedule = BeaconSchedule(vec![BeaconPoint {
+        height: 0,
+        beacon: Box::<MockBeacon>::default(),
+    }]);
+    // The mock beacon produces a round per epoch
+    let prev = MockBeacon::default().entry(5).await.unwrap();
+    let rounds =
+        |entries: Vec<BeaconEntry>| entries.iter().map(BeaconEntry::round).collect::<Vec<_>>();
+
+    // Epochs 6 to 8 are null rounds
+    let entries = schedule
+        .beacon_entries_for_block(NetworkVersion::V21, 9, 5, &prev)
+        .await
+        .unwrap();
+    assert_eq!(rounds(entries), [6, 7, 8, 9]);
+    // No entry newer than the previous one
+    let entries = schedule
+        .beacon_entries_for_block(NetworkVersion::V21, 5, 4, &prev)
+        .await
+        .unwrap();
+    assert!(entries.is_empty());
+}
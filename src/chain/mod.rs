@@ -6,31 +6,95 @@ use crate::blocks::Tipset;
 use crate::cid_collections::CidHashSet;
 use crate::db::car::forest;
 use crate::ipld::stream_chain;
+use crate::shim::clock::ChainEpoch;
 use crate::utils::io::{AsyncWriterWithChecksum, Checksum};
 use crate::utils::stream::par_buffer;
 use anyhow::Context as _;
 use digest::Digest;
+use futures::TryStreamExt as _;
 use fvm_ipld_blockstore::Blockstore;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
 use std::sync::Arc;
 use tokio::io::{AsyncWrite, AsyncWriteExt, BufWriter};
 
 pub use self::{store::*, weight::*};
 
+#[derive(Debug, Default)]
+pub struct ExportOptions {
+    pub skip_checksum: bool,
+    /// Include the messages of all tipsets down to genesis, not only those of
+    /// the tipsets whose state is exported.
+    pub include_old_messages: bool,
+    pub seen: CidHashSet,
+    pub progress: Option<Arc<ExportProgress>>,
+}
+
+/// Progress of an [`export`], which walks the chain from its tipset down to
+/// genesis.
+#[derive(Debug)]
+pub struct ExportProgress {
+    start_epoch: ChainEpoch,
+    epoch: AtomicI64,
+    blocks: AtomicU64,
+}
+
+impl ExportProgress {
+    pub fn new(start_epoch: ChainEpoch) -> Self {
+        Self {
+            start_epoch,
+            epoch: AtomicI64::new(start_epoch),
+            blocks: AtomicU64::new(0),
+        }
+    }
+
+    pub fn start_epoch(&self) -> ChainEpoch {
+        self.start_epoch
+    }
+
+    /// Epoch of the tipset being exported.
+    pub fn epoch(&self) -> ChainEpoch {
+        self.epoch.load(Ordering::Relaxed)
+    }
+
+    /// Number of blocks exported so far.
+    pub fn blocks(&self) -> u64 {
+        self.blocks.load(Ordering::Relaxed)
+    }
+}
+
 pub async fn export<D: Digest>(
     db: impl Blockstore + Send + Sync + 'static,
     tipset: &Tipset,
     lookup_depth: ChainEpochDelta,
     writer: impl AsyncWrite + Unpin,
-    seen: CidHashSet,
-    skip_checksum: bool,
+    options: ExportOptions,
 ) -> anyhow::Result<Option<digest::Output<D>>, Error> {
+    let ExportOptions {
+        skip_checksum,
+        include_old_messages,
+        seen,
+        progress,
+    } = options;
     let db = Arc::new(db);
     let stateroot_lookup_limit = tipset.epoch() - lookup_depth;
+    let message_limit = match include_old_messages {
+        true => -1,
+        false => stateroot_lookup_limit,
+    };
     let roots = tipset.key().to_cids();
 
     // Wrap writer in optional checksum calculator
     let mut writer = AsyncWriterWithChecksum::<D, _>::new(BufWriter::new(writer), !skip_checksum);
 
+    let tipsets = tipset.clone().chain(Arc::clone(&db)).inspect({
+        let progress = progress.clone();
+        move |tipset| {
+            if let Some(progress) = &progress {
+                progress.epoch.store(tipset.epoch(), Ordering::Relaxed);
+            }
+        }
+    });
+
     // Stream stateroots in range stateroot_lookup_limit..=tipset.epoch(). Also
     // stream all block headers until genesis.
     let blocks = par_buffer(
@@ -38,12 +102,14 @@ pub async fn export<D: Digest>(
         // are small enough that keeping 1k in memory isn't a problem. Average
         // block size is between 1kb and 2kb.
         1024,
-        stream_chain(
-            Arc::clone(&db),
-            tipset.clone().chain(Arc::clone(&db)),
-            stateroot_lookup_limit,
-        )
-        .with_seen(seen),
+        stream_chain(Arc::clone(&db), tipsets, stateroot_lookup_limit)
+            .with_seen(seen)
+            .with_message_limit(message_limit)
+            .inspect_ok(move |_| {
+                if let Some(progress) = &progress {
+                    progress.blocks.fetch_add(1, Ordering::Relaxed);
+                }
+            }),
     );
 
     // Encode Ipld key-value pairs in zstd frames
@@ -56,11 +56,9 @@ fn compute_next_base_fee(
     next_base_fee
 }
 
-pub fn compute_base_fee<DB>(
-    db: &DB,
-    ts: &Tipset,
-    smoke_height: ChainEpoch,
-) -> Result<TokenAmount, crate::chain::Error>
+/// Returns the total gas limit of the unique messages of a tipset, which the
+/// base fee of its children is computed from.
+pub fn tipset_gas_limit<DB>(db: &DB, ts: &Tipset) -> Result<u64, crate::chain::Error>
 where
     DB: Blockstore,
 {
@@ -85,6 +83,18 @@ where
             }
         }
     }
+    Ok(total_limit)
+}
+
+pub fn compute_base_fee<DB>(
+    db: &DB,
+    ts: &Tipset,
+    smoke_height: ChainEpoch,
+) -> Result<TokenAmount, crate::chain::Error>
+where
+    DB: Blockstore,
+{
+    let total_limit = tipset_gas_limit(db, ts)?;
 
     // Compute next base fee based on the current gas limit and parent base fee.
     let parent_base_fee = &ts.block_headers().first().parent_base_fee;
@@ -97,6 +107,38 @@ where
     ))
 }
 
+/// Projects the base fee `epochs` tipsets after a tipset paying `base_fee`.
+///
+/// `gas_usage` holds the gas limit used and the number of blocks of the recent
+/// tipsets, from the oldest. The gas used per block is assumed to keep
+/// following the linear trend between the oldest and the newest of them, and
+/// each projected tipset updates the base fee the way the chain does.
+pub fn project_base_fee(
+    base_fee: &TokenAmount,
+    gas_usage: &[(u64, usize)],
+    epochs: u64,
+) -> TokenAmount {
+    let per_block = |(gas_limit_used, no_of_blocks): &(u64, usize)| {
+        *gas_limit_used as f64 / (*no_of_blocks).max(1) as f64
+    };
+    let (Some(oldest), Some(newest)) = (gas_usage.first(), gas_usage.last()) else {
+        return base_fee.clone();
+    };
+    let (oldest, newest) = (per_block(oldest), per_block(newest));
+    let slope = match gas_usage.len() {
+        1 => 0.0,
+        len => (newest - oldest) / (len - 1) as f64,
+    };
+
+    let mut projected = base_fee.clone();
+    for epoch in 1..=epochs {
+        let gas_limit_used = (newest + slope * epoch as f64).clamp(0.0, BLOCK_GAS_LIMIT as f64);
+        // The projected tipsets are all past the smoke upgrade.
+        projected = compute_next_base_fee(&projected, gas_limit_used as u64, 1, 1, 0);
+    }
+    projected
+}
+
 #[cfg(test)]
 mod tests {
     use crate::blocks::RawBlockHeader;
@@ -165,6 +207,63 @@ mod tests {
         }
     }
 
+    #[test]
+    fn project_base_fee_follows_the_gas_usage_trend() {
+        let base_fee = TokenAmount::from_atto(100_000_000);
+        // (gas usage from the oldest tipset, epochs, projected base fee)
+        let cases = [
+            // No usage to project from
+            (vec![], 10, 100_000_000),
+            // Steady usage at the target
+            (vec![(BLOCK_GAS_TARGET, 1); 5], 10, 100_000_000),
+            // Steady full blocks, the base fee rises by 12.5% each epoch
+            (vec![(BLOCK_GAS_LIMIT * 2, 2); 3], 2, 126_562_500),
+            // Rising usage reaches full blocks and stays capped there
+            (
+                vec![(BLOCK_GAS_TARGET, 1), (BLOCK_GAS_TARGET * 3 / 2, 1)],
+                2,
+                126_562_500,
+            ),
+            // Rising usage still below the target
+            (
+                vec![(0, 1), (BLOCK_GAS_TARGET / 4, 1), (BLOCK_GAS_TARGET / 2, 1)],
+                2,
+                // 0.75 then 1.0 of the target
+                96_875_000,
+            ),
+            // Falling usage from full blocks
+            (
+                vec![(BLOCK_GAS_LIMIT, 1), (BLOCK_GAS_TARGET * 3 / 2, 1)],
+                2,
+                // 1.0 then 0.5 of the target
+                93_750_000,
+            ),
+            // Falling usage bottoms out at empty blocks
+            (
+                vec![(BLOCK_GAS_TARGET, 1), (0, 1)],
+                3,
+                // 100_000_000 * 0.875^3
+                66_992_187,
+            ),
+        ];
+
+        for (gas_usage, epochs, expected) in cases {
+            assert_eq!(
+                project_base_fee(&base_fee, &gas_usage, epochs),
+                TokenAmount::from_atto(expected),
+                "{gas_usage:?} over {epochs} epochs"
+            );
+        }
+    }
+
+    #[test]
+    fn project_base_fee_does_not_fall_below_the_minimum() {
+        assert_eq!(
+            project_base_fee(&TokenAmount::from_atto(MINIMUM_BASE_FEE), &[(0, 1)], 100),
+            TokenAmount::from_atto(MINIMUM_BASE_FEE)
+        );
+    }
+
     #[test]
     fn compute_base_fee_shouldnt_panic_on_bad_input() {
         let blockstore = MemoryDB::default();
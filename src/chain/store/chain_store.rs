@@ -34,7 +34,7 @@ use super::{
     Error,
 };
 use crate::db::setting_keys::HEAD_KEY;
-use crate::db::{SettingsStore, SettingsStoreExt};
+use crate::db::{IndicesStore, SettingsStore, SettingsStoreExt};
 
 // A cap on the size of the future_sink
 const SINK_CAP: usize = 200;
@@ -63,12 +63,17 @@ pub struct ChainStore<DB> {
     /// Settings store
     settings: Arc<dyn SettingsStore + Sync + Send>,
 
+    /// Indices store, e.g. for the message index
+    indices: Arc<dyn IndicesStore + Sync + Send>,
+
     /// Used as a cache for tipset `lookbacks`.
     pub chain_index: Arc<ChainIndex<Arc<DB>>>,
 
     /// Tracks blocks for the purpose of forming tipsets.
     tipset_tracker: TipsetTracker<DB>,
 
+    chain_config: Arc<ChainConfig>,
+
     genesis_block_header: CachingBlockHeader,
 
     /// validated blocks
@@ -106,11 +111,13 @@ where
     pub fn new(
         db: Arc<DB>,
         settings: Arc<dyn SettingsStore + Sync + Send>,
+        indices: Arc<dyn IndicesStore + Sync + Send>,
         chain_config: Arc<ChainConfig>,
         genesis_block_header: CachingBlockHeader,
     ) -> anyhow::Result<Self> {
         let (publisher, _) = broadcast::channel(SINK_CAP);
-        let chain_index = Arc::new(ChainIndex::new(Arc::clone(&db)));
+        let chain_index =
+            Arc::new(ChainIndex::new(Arc::clone(&db)).with_epoch_samples(indices.clone()));
 
         if !settings
             .read_obj::<TipsetKey>(HEAD_KEY)?
@@ -125,9 +132,11 @@ where
         let cs = Self {
             publisher,
             chain_index,
-            tipset_tracker: TipsetTracker::new(Arc::clone(&db), chain_config),
+            tipset_tracker: TipsetTracker::new(Arc::clone(&db), chain_config.clone()),
+            chain_config,
             db,
             settings,
+            indices,
             genesis_block_header,
             validated_blocks,
         };
@@ -139,12 +148,27 @@ where
     /// the settings store under the [`crate::db::setting_keys::HEAD_KEY`] key.
     pub fn set_heaviest_tipset(&self, ts: Arc<Tipset>) -> Result<(), Error> {
         self.settings.write_obj(HEAD_KEY, ts.key())?;
+        if let Err(e) = self
+            .chain_index
+            .sample_final_epoch(ts.clone(), self.chain_config.policy.chain_finality)
+        {
+            warn!("Failed to sample the epochs of the chain: {e}");
+        }
         if self.publisher.send(HeadChange::Apply(ts)).is_err() {
             debug!("did not publish head change, no active receivers");
         }
         Ok(())
     }
 
+    /// Writes the key of the heaviest tipset to the settings store again, and
+    /// returns the heaviest tipset as read back from the store.
+    pub fn flush_head(&self) -> anyhow::Result<Arc<Tipset>> {
+        let head = self.heaviest_tipset();
+        self.settings.write_obj(HEAD_KEY, head.key())?;
+        let key = self.settings.require_obj::<TipsetKey>(HEAD_KEY)?;
+        Ok(self.chain_index.load_required_tipset(&key)?)
+    }
+
     /// Adds a block header to the tipset tracker, which tracks valid headers.
     pub fn add_to_tipset_tracker(&self, header: &CachingBlockHeader) {
         self.tipset_tracker.add(header);
@@ -171,6 +195,16 @@ where
         &self.genesis_block_header
     }
 
+    /// Returns the settings store backing this chain store.
+    pub fn settings(&self) -> Arc<dyn SettingsStore + Sync + Send> {
+        self.settings.clone()
+    }
+
+    /// Returns the indices store backing this chain store.
+    pub fn indices(&self) -> &Arc<dyn IndicesStore + Sync + Send> {
+        &self.indices
+    }
+
     /// Returns the currently tracked heaviest tipset.
     pub fn heaviest_tipset(&self) -> Arc<Tipset> {
         self.chain_index
@@ -548,7 +582,8 @@ mod tests {
             message_receipts: Cid::new_v1(DAG_CBOR, Identity.digest(&[])),
             ..Default::default()
         });
-        let cs = ChainStore::new(db.clone(), db, chain_config, gen_block.clone()).unwrap();
+        let cs =
+            ChainStore::new(db.clone(), db.clone(), db, chain_config, gen_block.clone()).unwrap();
 
         assert_eq!(cs.genesis_block_header(), &gen_block);
     }
@@ -562,7 +597,7 @@ mod tests {
             ..Default::default()
         });
 
-        let cs = ChainStore::new(db.clone(), db, chain_config, gen_block).unwrap();
+        let cs = ChainStore::new(db.clone(), db.clone(), db, chain_config, gen_block).unwrap();
 
         let cid = Cid::new_v1(DAG_CBOR, Blake2b256.digest(&[1, 2, 3]));
         assert!(!cs.is_block_validated(&cid));
@@ -570,4 +605,59 @@ mod tests {
         cs.mark_block_as_validated(&cid);
         assert!(cs.is_block_validated(&cid));
     }
+
+    #[test]
+    fn lookback_of_null_rounds_is_the_first_non_null_ancestor() {
+        let db = Arc::new(crate::db::MemoryDB::default());
+        let mut chain_config = ChainConfig::devnet();
+        chain_config.policy.chain_finality = 4;
+        let chain_config = Arc::new(chain_config);
+        let state_root = |epoch: ChainEpoch| Cid::new_v1(DAG_CBOR, Identity.digest(&[epoch as u8]));
+
+        // Epochs 3 and 4 are null rounds
+        let mut tipsets = HashMap::new();
+        let mut head: Option<Tipset> = None;
+        for epoch in [0, 1, 2, 5, 6, 7, 8, 9] {
+            let mut header = RawBlockHeader {
+                miner_address: Address::new_id(0),
+                state_root: state_root(head.as_ref().map_or(-1, Tipset::epoch)),
+                epoch,
+                ..Default::default()
+            };
+            if let Some(head) = &head {
+                header.parents = head.key().clone();
+            }
+            let header = CachingBlockHeader::new(header);
+            db.put_cbor_default(&header).unwrap();
+            let tipset = Tipset::from(header);
+            tipsets.insert(epoch, tipset.clone());
+            head = Some(tipset);
+        }
+        let head = Arc::new(head.unwrap());
+
+        // (round, lookback tipset, state the lookback tipset leads to)
+        let cases = [
+            // The lookback epoch is the one after a non-null round
+            (6, 2, 2),
+            // The lookback epoch is a null round
+            (7, 2, 2),
+            (8, 2, 2),
+            // Non-null lookback epochs
+            (9, 5, 5),
+            (10, 6, 6),
+        ];
+        let chain_index = Arc::new(ChainIndex::new(db));
+        for (round, lookback, state) in cases {
+            let (tipset, state_root_) =
+                ChainStore::<crate::db::MemoryDB>::get_lookback_tipset_for_round(
+                    chain_index.clone(),
+                    chain_config.clone(),
+                    head.clone(),
+                    round,
+                )
+                .unwrap();
+            assert_eq!(tipset.key(), tipsets[&lookback].key(), "round {round}");
+            assert_eq!(state_root_, state_root(state), "round {round}");
+        }
+    }
 }
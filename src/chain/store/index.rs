@@ -5,18 +5,40 @@ use std::{num::NonZeroUsize, sync::Arc};
 
 use crate::beacon::{BeaconEntry, IGNORE_DRAND_VAR};
 use crate::blocks::{Tipset, TipsetKey};
+use crate::db::IndicesStore;
 use crate::metrics;
 use crate::shim::clock::ChainEpoch;
 use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::{from_slice, to_vec};
 use itertools::Itertools;
 use lru::LruCache;
 use nonzero_ext::nonzero;
 use parking_lot::Mutex;
+use tracing::debug;
 
-use crate::chain::Error;
+use crate::chain::{ChainEpochDelta, Error};
 
 const DEFAULT_TIPSET_CACHE_SIZE: NonZeroUsize = nonzero!(131072_usize);
 
+/// Interval between the epochs whose tipsets are sampled in the epoch index.
+/// Looking up an old tipset walks back about this many parents from the
+/// nearest sample, rather than from the head.
+pub const EPOCH_SAMPLE_INTERVAL: ChainEpoch = 100;
+
+/// Key of the epoch up to which all the samples have been written by
+/// [`ChainIndex::backfill_epoch_samples`].
+const EPOCH_SAMPLES_COMPLETE_KEY: &[u8] = b"/epoch/complete";
+
+/// Key of the sample of `epoch` in the indices store.
+fn epoch_sample_key(epoch: ChainEpoch) -> Vec<u8> {
+    [b"/epoch/".as_slice(), &epoch.to_be_bytes()].concat()
+}
+
+/// Rounds `epoch` down to a sampled epoch.
+fn sampled_epoch_at_or_below(epoch: ChainEpoch) -> ChainEpoch {
+    epoch.div_euclid(EPOCH_SAMPLE_INTERVAL) * EPOCH_SAMPLE_INTERVAL
+}
+
 type TipsetCache = Mutex<LruCache<TipsetKey, Arc<Tipset>>>;
 
 /// Keeps look-back tipsets in cache at a given interval `skip_length` and can
@@ -27,6 +49,10 @@ pub struct ChainIndex<DB> {
 
     /// `Blockstore` pointer needed to load tipsets from cold storage.
     pub db: DB,
+
+    /// Keys of the tipsets at every [`EPOCH_SAMPLE_INTERVAL`] epochs deeper
+    /// than finality, or of the next tipset after null rounds.
+    epoch_samples: Option<Arc<dyn IndicesStore + Sync + Send>>,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -41,7 +67,18 @@ pub enum ResolveNullTipset {
 impl<DB: Blockstore> ChainIndex<DB> {
     pub fn new(db: DB) -> Self {
         let ts_cache = Mutex::new(LruCache::new(DEFAULT_TIPSET_CACHE_SIZE));
-        Self { ts_cache, db }
+        Self {
+            ts_cache,
+            db,
+            epoch_samples: None,
+        }
+    }
+
+    /// Stores samples of the epochs of the chain in `indices`, and uses them to
+    /// look tipsets up by height.
+    pub fn with_epoch_samples(mut self, indices: Arc<dyn IndicesStore + Sync + Send>) -> Self {
+        self.epoch_samples = Some(indices);
+        self
     }
 
     /// Loads a tipset from memory given the tipset keys and cache. Semantically
@@ -112,6 +149,15 @@ impl<DB: Blockstore> ChainIndex<DB> {
     /// Requesting epoch 2 with [`ResolveNullTipset::TakeNewer`] will return
     /// epoch 3. Requesting with [`ResolveNullTipset::TakeOlder`] will return
     /// epoch 1.
+    ///
+    /// # How far back is walked?
+    ///
+    /// If the epochs are sampled, the walk from `from` stops at the first
+    /// sampled tipset it reaches, which shows that `from` descends from the
+    /// sampled chain, and carries on from the nearest sample at or above the
+    /// requested epoch. From the head, about finality plus twice
+    /// [`EPOCH_SAMPLE_INTERVAL`] parents are loaded. If `from` is on a fork
+    /// which left the sampled chain, the walk carries on until it joins it.
     pub fn tipset_by_height(
         &self,
         to: ChainEpoch,
@@ -128,6 +174,7 @@ impl<DB: Blockstore> ChainIndex<DB> {
             )));
         }
 
+        let from = self.sampled_ancestor(to, &from).unwrap_or(from);
         for (child, parent) in self.chain(from).tuple_windows() {
             if to == child.epoch() {
                 return Ok(child);
@@ -145,6 +192,112 @@ impl<DB: Blockstore> ChainIndex<DB> {
         )))
     }
 
+    /// Walks from `from` down to the first sampled tipset, and returns the
+    /// sampled ancestor of `from` nearest to `to` at or above it. Returns
+    /// `None` if `to` is reached first, as walking on is then as cheap.
+    fn sampled_ancestor(&self, to: ChainEpoch, from: &Arc<Tipset>) -> Option<Arc<Tipset>> {
+        self.epoch_samples.as_ref()?;
+        let nearest = sampled_epoch_at_or_below(to + EPOCH_SAMPLE_INTERVAL - 1);
+        for (child, parent) in self.chain(from.clone()).tuple_windows() {
+            if child.epoch() <= nearest {
+                return None;
+            }
+            // The child is the tipset of a sampled epoch if it follows it
+            let sampled = sampled_epoch_at_or_below(child.epoch());
+            if sampled > parent.epoch()
+                && self
+                    .epoch_sample(sampled)
+                    .is_some_and(|sample| sample.key() == child.key())
+            {
+                // Samples are written from a single chain, so the ones below
+                // are ancestors of the child too
+                return self.epoch_sample(nearest);
+            }
+        }
+        None
+    }
+
+    /// Loads the tipset sampled at the `sampled` epoch, if any.
+    fn epoch_sample(&self, sampled: ChainEpoch) -> Option<Arc<Tipset>> {
+        let indices = self.epoch_samples.as_ref()?;
+        let load = || -> anyhow::Result<Option<Arc<Tipset>>> {
+            match indices.read_index(&epoch_sample_key(sampled))? {
+                Some(bytes) => Ok(self.load_tipset(&from_slice(&bytes)?)?),
+                None => Ok(None),
+            }
+        };
+        load().unwrap_or_else(|e| {
+            debug!("Failed to load the tipset sampled at epoch {sampled}: {e}");
+            None
+        })
+    }
+
+    /// Samples the newest epoch of the chain of `head` deeper than `finality`,
+    /// which re-orgs can no longer replace. Walks back at most `finality` plus
+    /// [`EPOCH_SAMPLE_INTERVAL`] parents, and only when a new epoch becomes
+    /// final. Older epochs are sampled by [`ChainIndex::backfill_epoch_samples`].
+    pub fn sample_final_epoch(
+        &self,
+        head: Arc<Tipset>,
+        finality: ChainEpochDelta,
+    ) -> anyhow::Result<()> {
+        let Some(indices) = &self.epoch_samples else {
+            return Ok(());
+        };
+        let final_epoch = head.epoch() - finality;
+        let sampled = sampled_epoch_at_or_below(final_epoch);
+        if sampled <= 0 || indices.read_index(&epoch_sample_key(sampled))?.is_some() {
+            return Ok(());
+        }
+        let tipset = self.tipset_by_height(sampled, head, ResolveNullTipset::TakeNewer)?;
+        // Null rounds up to the head leave nothing final to sample
+        if tipset.epoch() <= final_epoch {
+            indices.write_indices(vec![(epoch_sample_key(sampled), to_vec(tipset.key())?)])?;
+        }
+        Ok(())
+    }
+
+    /// Samples the epochs of the chain of `head` deeper than `finality`, down to
+    /// where a previous backfill got to, or to the start of the chain. This
+    /// walks every tipset in between, so is meant to run in the background,
+    /// e.g. after importing a snapshot. Returns the number of written samples.
+    pub fn backfill_epoch_samples(
+        &self,
+        head: Arc<Tipset>,
+        finality: ChainEpochDelta,
+    ) -> anyhow::Result<usize> {
+        let Some(indices) = &self.epoch_samples else {
+            return Ok(0);
+        };
+        let complete: ChainEpoch = match indices.read_index(EPOCH_SAMPLES_COMPLETE_KEY)? {
+            Some(bytes) => from_slice(&bytes)?,
+            None => 0,
+        };
+        let final_epoch = head.epoch() - finality;
+        let mut samples = vec![];
+        for (child, parent) in self.chain(head).tuple_windows() {
+            if child.epoch() <= complete {
+                break;
+            }
+            if child.epoch() > final_epoch {
+                continue;
+            }
+            // The child is the sample of every sampled epoch down to its parent
+            let mut sampled = sampled_epoch_at_or_below(child.epoch());
+            while sampled > parent.epoch().max(complete) {
+                samples.push((epoch_sample_key(sampled), to_vec(child.key())?));
+                sampled -= EPOCH_SAMPLE_INTERVAL;
+            }
+        }
+        let written = samples.len();
+        samples.push((
+            EPOCH_SAMPLES_COMPLETE_KEY.to_vec(),
+            to_vec(&sampled_epoch_at_or_below(final_epoch).max(complete))?,
+        ));
+        indices.write_indices(samples)?;
+        Ok(written)
+    }
+
     /// Iterate from the given tipset to genesis. Missing tipsets cut the chain
     /// short. Semantically identical to [`Tipset::chain`] but the results are
     /// cached.
@@ -203,6 +356,7 @@ mod tests {
     use crate::blocks::CachingBlockHeader;
     use crate::blocks::RawBlockHeader;
     use crate::db::MemoryDB;
+    use crate::test_utils::{persist_chain, CountingStore};
     use crate::utils::db::CborStoreExt;
 
     fn persist_tipset(tipset: &Tipset, db: &impl Blockstore) {
@@ -216,15 +370,21 @@ mod tests {
     }
 
     fn tipset_child(parent: &Tipset, epoch: ChainEpoch) -> Tipset {
-        // Use a static counter to give all tipsets a unique timestamp
-        static COUNTER: AtomicU64 = AtomicU64::new(0);
-        let n = COUNTER.fetch_add(1, Ordering::Relaxed);
         Tipset::from(CachingBlockHeader::new(RawBlockHeader {
             parents: parent.key().clone(),
             epoch,
+            ..unique_header()
+        }))
+    }
+
+    fn unique_header() -> RawBlockHeader {
+        // Use a static counter to give all tipsets a unique timestamp
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+        RawBlockHeader {
             timestamp: n,
             ..Default::default()
-        }))
+        }
     }
 
     #[test]
@@ -295,4 +455,149 @@ mod tests {
             &epoch2b
         );
     }
+
+    #[test]
+    fn sampled_lookups_bound_parent_hops() {
+        let db = Arc::new(CountingStore::default());
+        let indices = Arc::new(MemoryDB::default());
+        let gen = genesis_tipset();
+        persist_tipset(&gen, &db);
+        // Null rounds at every 7th epoch, and across the sampled epoch 500
+        let epochs = (1..=2000).filter(|epoch| epoch % 7 != 0 && !(495..=505).contains(epoch));
+        let chain = persist_chain(&db, &gen, epochs, |_, _| unique_header());
+        let head = Arc::new(chain.last().unwrap().clone());
+        let finality = 50;
+
+        let samples = ChainIndex::new(db.clone())
+            .with_epoch_samples(indices.clone())
+            .backfill_epoch_samples(head.clone(), finality)
+            .unwrap();
+        assert_eq!(samples, 19);
+        // Nothing is left to backfill
+        let samples = ChainIndex::new(db.clone())
+            .with_epoch_samples(indices.clone())
+            .backfill_epoch_samples(head.clone(), finality)
+            .unwrap();
+        assert_eq!(samples, 0);
+
+        let unsampled = ChainIndex::new(db.clone());
+        for to in [1, 99, 100, 101, 497, 500, 777, 1234, 1900, 1949, 1950, 1999] {
+            for resolve in [ResolveNullTipset::TakeOlder, ResolveNullTipset::TakeNewer] {
+                let expected = unsampled
+                    .tipset_by_height(to, head.clone(), resolve)
+                    .unwrap();
+                // Start with a cold cache
+                let index = ChainIndex::new(db.clone()).with_epoch_samples(indices.clone());
+                db.take_reads();
+                let actual = index.tipset_by_height(to, head.clone(), resolve).unwrap();
+                assert_eq!(actual, expected, "epoch {to} {resolve:?}");
+                let reads = db.take_reads();
+                // The head is walked back to the newest sample, from which
+                // deeper epochs are reached in a bounded number of parents
+                if to < head.epoch() - finality - EPOCH_SAMPLE_INTERVAL {
+                    assert!(
+                        reads <= (finality + 2 * EPOCH_SAMPLE_INTERVAL) as usize + 1,
+                        "{reads} blocks read for epoch {to}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn samples_survive_reorgs() {
+        let db = Arc::new(MemoryDB::default());
+        let indices = Arc::new(MemoryDB::default());
+        let index = ChainIndex::new(db.clone()).with_epoch_samples(indices.clone());
+        let gen = genesis_tipset();
+        persist_tipset(&gen, &db);
+        let finality = 10;
+
+        // The sampled epoch 200 is not final yet at epoch 205
+        let chain_a = persist_chain(&db, &gen, 1..=205, |_, _| unique_header());
+        for tipset in &chain_a {
+            index
+                .sample_final_epoch(Arc::new(tipset.clone()), finality)
+                .unwrap();
+        }
+        assert!(indices
+            .read_index(&epoch_sample_key(200))
+            .unwrap()
+            .is_none());
+
+        // A heavier fork branching off at epoch 198 replaces it
+        let chain_b = persist_chain(&db, &chain_a[197], 199..=220, |_, _| unique_header());
+        for tipset in &chain_b {
+            index
+                .sample_final_epoch(Arc::new(tipset.clone()), finality)
+                .unwrap();
+        }
+        let head_b = Arc::new(chain_b.last().unwrap().clone());
+        let b_200 = &chain_b[1];
+        assert_eq!(b_200.epoch(), 200);
+        assert_eq!(
+            indices.read_index(&epoch_sample_key(200)).unwrap(),
+            Some(to_vec(b_200.key()).unwrap())
+        );
+        for to in [150, 200, 203] {
+            assert_eq!(
+                index
+                    .tipset_by_height(to, head_b.clone(), ResolveNullTipset::TakeOlder)
+                    .unwrap()
+                    .key(),
+                chain_b
+                    .iter()
+                    .chain(&chain_a)
+                    .find(|ts| ts.epoch() == to && (to < 198 || chain_b.contains(ts)))
+                    .unwrap()
+                    .key()
+            );
+        }
+        // The head of the dropped chain still finds its own tipsets, as the
+        // fork is shallower than finality
+        assert_eq!(
+            index
+                .tipset_by_height(
+                    203,
+                    Arc::new(chain_a.last().unwrap().clone()),
+                    ResolveNullTipset::TakeOlder
+                )
+                .unwrap()
+                .as_ref(),
+            &chain_a[202]
+        );
+    }
+
+    #[test]
+    fn samples_are_skipped_on_forks_of_the_sampled_chain() {
+        let db = Arc::new(MemoryDB::default());
+        let indices = Arc::new(MemoryDB::default());
+        let index = ChainIndex::new(db.clone()).with_epoch_samples(indices.clone());
+        let gen = genesis_tipset();
+        persist_tipset(&gen, &db);
+        let finality = 50;
+
+        let chain_a = persist_chain(&db, &gen, 1..=1000, |_, _| unique_header());
+        let head_a = Arc::new(chain_a.last().unwrap().clone());
+        index.backfill_epoch_samples(head_a, finality).unwrap();
+        // A fork branching off at epoch 250, deeper than finality, hasn't been
+        // sampled
+        let chain_b = persist_chain(&db, &chain_a[249], 251..=800, |_, _| unique_header());
+        let head_b = Arc::new(chain_b.last().unwrap().clone());
+
+        for to in [99, 200, 250, 251, 300, 420, 700] {
+            let expected = ChainIndex::new(db.clone())
+                .tipset_by_height(to, head_b.clone(), ResolveNullTipset::TakeOlder)
+                .unwrap();
+            assert_eq!(
+                index
+                    .tipset_by_height(to, head_b.clone(), ResolveNullTipset::TakeOlder)
+                    .unwrap(),
+                expected,
+                "epoch {to}"
+            );
+            let chain = if to <= 250 { &chain_a } else { &chain_b };
+            assert!(chain.contains(&expected), "epoch {to}");
+        }
+    }
 }
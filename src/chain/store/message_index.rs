@@ -0,0 +1,76 @@
+// Copyright 2019-2024 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! An index mapping the CIDs of the messages on chain to the tipsets which
+//! executed them, so that looking up an old message doesn't require walking the
+//! chain back to it.
+
+use crate::blocks::{Tipset, TipsetKey};
+use crate::db::IndicesStore;
+use crate::interpreter::BlockMessages;
+use crate::shim::clock::ChainEpoch;
+use cid::Cid;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::{from_slice, to_vec};
+use serde_tuple::{Deserialize_tuple, Serialize_tuple};
+
+/// Key of the entry of `message` in the indices store.
+fn message_index_key(message: &Cid) -> Vec<u8> {
+    [b"/message/".as_slice(), &message.to_bytes()].concat()
+}
+
+/// Where a message was executed on chain.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize_tuple, Deserialize_tuple)]
+pub struct MessageIndexEntry {
+    /// Epoch of the tipset which executed the message.
+    pub epoch: ChainEpoch,
+    /// Key of the tipset which executed the message, i.e. whose parent
+    /// included it.
+    pub tipset: TipsetKey,
+    /// Index of the message receipt in the parent message receipts of
+    /// `tipset`.
+    pub receipt_index: u64,
+}
+
+impl MessageIndexEntry {
+    /// Loads the entry of `message`, if it has been indexed.
+    pub fn load(
+        indices: &(impl IndicesStore + ?Sized),
+        message: &Cid,
+    ) -> anyhow::Result<Option<Self>> {
+        match indices.read_index(&message_index_key(message))? {
+            Some(bytes) => Ok(Some(from_slice(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Indexes the messages executed by `tipset`, that is those included in its
+/// parent, replacing the entries of any fork which executed them before.
+/// Returns the number of indexed messages.
+pub fn index_messages(
+    db: &impl Blockstore,
+    indices: &(impl IndicesStore + ?Sized),
+    tipset: &Tipset,
+) -> anyhow::Result<usize> {
+    if tipset.epoch() == 0 {
+        return Ok(0);
+    }
+    let parent = Tipset::load_required(db, tipset.parents())?;
+    let entries = BlockMessages::for_tipset(db, &parent)?
+        .into_iter()
+        .flat_map(|block| block.messages)
+        .enumerate()
+        .map(|(receipt_index, message)| {
+            let entry = MessageIndexEntry {
+                epoch: tipset.epoch(),
+                tipset: tipset.key().clone(),
+                receipt_index: receipt_index as u64,
+            };
+            Ok((message_index_key(&message.cid()?), to_vec(&entry)?))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let count = entries.len();
+    indices.write_indices(entries)?;
+    Ok(count)
+}
@@ -5,6 +5,7 @@ pub mod base_fee;
 mod chain_store;
 mod errors;
 pub mod index;
+pub mod message_index;
 mod tipset_tracker;
 
 pub use self::{base_fee::*, chain_store::*, errors::*};
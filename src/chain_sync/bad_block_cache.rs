@@ -1,24 +1,46 @@
 // Copyright 2019-2024 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use std::num::NonZeroUsize;
+use std::{num::NonZeroUsize, sync::Arc};
 
 use cid::Cid;
 use lru::LruCache;
 use nonzero_ext::nonzero;
 use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::db::{setting_keys::BAD_BLOCKS_KEY, SettingsStore, SettingsStoreExt as _};
+
+const DEFAULT_CAPACITY: NonZeroUsize = nonzero!(1usize << 15);
 
 /// Thread-safe cache for tracking bad blocks.
 /// This cache is checked before validating a block, to ensure no duplicate
 /// work.
-#[derive(Debug)]
 pub struct BadBlockCache {
-    cache: Mutex<LruCache<Cid, String>>,
+    cache: Mutex<LruCache<Cid, BadBlock>>,
+    /// Where the cache is persisted to, if anywhere.
+    settings: Option<Arc<dyn SettingsStore + Sync + Send>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct BadBlock {
+    reason: String,
+    /// Unix timestamp of when the block was marked bad.
+    timestamp: i64,
+}
+
+impl std::fmt::Debug for BadBlockCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BadBlockCache")
+            .field("cache", &self.cache)
+            .finish_non_exhaustive()
+    }
 }
 
 impl Default for BadBlockCache {
     fn default() -> Self {
-        Self::new(nonzero!(1usize << 15))
+        Self::new(DEFAULT_CAPACITY)
     }
 }
 
@@ -26,23 +48,186 @@ impl BadBlockCache {
     pub fn new(cap: NonZeroUsize) -> Self {
         Self {
             cache: Mutex::new(LruCache::new(cap)),
+            settings: None,
+        }
+    }
+
+    /// Creates a cache that is persisted in the settings store, loading the
+    /// blocks previously marked bad.
+    pub fn load(settings: Arc<dyn SettingsStore + Sync + Send>) -> Self {
+        Self::load_with_capacity(settings, DEFAULT_CAPACITY)
+    }
+
+    fn load_with_capacity(
+        settings: Arc<dyn SettingsStore + Sync + Send>,
+        cap: NonZeroUsize,
+    ) -> Self {
+        let cache = Self {
+            cache: Mutex::new(LruCache::new(cap)),
+            settings: Some(settings),
+        };
+        match cache.load_persisted() {
+            // Replayed from the least to the most recently marked, so that the
+            // most recent ones are kept if there are more than fit.
+            Ok(mut entries) => {
+                entries.sort_by_key(|(_, block)| block.timestamp);
+                let mut lru = cache.cache.lock();
+                for (cid, block) in entries {
+                    if let Some((evicted, _)) = lru.push(cid, block) {
+                        cache.unpersist(&evicted);
+                    }
+                }
+            }
+            Err(e) => warn!("Failed to load the bad block cache: {e}"),
+        }
+        cache
+    }
+
+    fn load_persisted(&self) -> anyhow::Result<Vec<(Cid, BadBlock)>> {
+        let Some(settings) = &self.settings else {
+            return Ok(vec![]);
+        };
+        let prefix = format!("{BAD_BLOCKS_KEY}/");
+        let mut entries = vec![];
+        for key in settings.setting_keys()? {
+            if let Some(cid) = key.strip_prefix(&prefix) {
+                if let Some(block) = settings.read_obj(&key)? {
+                    entries.push((cid.parse()?, block));
+                }
+            }
         }
+        Ok(entries)
     }
 
     /// Puts a bad block `Cid` in the cache with a given reason.
     pub fn put(&self, c: Cid, reason: String) -> Option<String> {
-        self.cache.lock().put(c, reason)
+        let block = BadBlock {
+            reason,
+            timestamp: chrono::Utc::now().timestamp(),
+        };
+        let mut cache = self.cache.lock();
+        self.persist(&c, &block);
+        match cache.push(c, block) {
+            Some((previous, block)) if previous == c => Some(block.reason),
+            Some((evicted, _)) => {
+                self.unpersist(&evicted);
+                None
+            }
+            None => None,
+        }
     }
 
     /// Returns `Some` with the reason if the block CID is in bad block cache.
     /// This also updates the key to the head of the cache.
     pub fn get(&self, c: &Cid) -> Option<String> {
-        self.cache.lock().get(c).cloned()
+        self.cache.lock().get(c).map(|block| block.reason.clone())
     }
 
     /// Returns `Some` with the reason if the block CID is in bad block cache.
     /// This function does not update the head position of the `Cid` key.
     pub fn peek(&self, c: &Cid) -> Option<String> {
-        self.cache.lock().peek(c).cloned()
+        self.cache.lock().peek(c).map(|block| block.reason.clone())
+    }
+
+    /// Removes a block `Cid` from the cache, returning the reason it was
+    /// marked bad for.
+    pub fn remove(&self, c: &Cid) -> Option<String> {
+        let mut cache = self.cache.lock();
+        let removed = cache.pop(c);
+        if removed.is_some() {
+            self.unpersist(c);
+        }
+        removed.map(|block| block.reason)
+    }
+
+    /// Removes all blocks from the cache.
+    pub fn clear(&self) {
+        let mut cache = self.cache.lock();
+        for (cid, _) in cache.iter() {
+            self.unpersist(cid);
+        }
+        cache.clear();
+    }
+
+    fn persist(&self, c: &Cid, block: &BadBlock) {
+        if let Some(settings) = &self.settings {
+            if let Err(e) = settings.write_obj(&key(c), block) {
+                warn!("Failed to persist bad block {c}: {e}");
+            }
+        }
+    }
+
+    fn unpersist(&self, c: &Cid) {
+        if let Some(settings) = &self.settings {
+            if let Err(e) = settings.remove(&key(c)) {
+                warn!("Failed to remove persisted bad block {c}: {e}");
+            }
+        }
+    }
+}
+
+/// The settings key a bad block is persisted under.
+fn key(c: &Cid) -> String {
+    format!("{BAD_BLOCKS_KEY}/{c}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::MemoryDB;
+    use cid::multihash::{Code::Identity, MultihashDigest};
+    use fvm_ipld_encoding::DAG_CBOR;
+
+    fn cid(n: u8) -> Cid {
+        Cid::new_v1(DAG_CBOR, Identity.digest(&[n]))
+    }
+
+    #[test]
+    fn reason_survives_reload() {
+        let settings = Arc::new(MemoryDB::default());
+        BadBlockCache::load(settings.clone()).put(cid(0), "bad".into());
+
+        let cache = BadBlockCache::load(settings.clone());
+        assert_eq!(cache.peek(&cid(0)), Some("bad".into()));
+
+        cache.remove(&cid(0));
+        assert_eq!(BadBlockCache::load(settings).peek(&cid(0)), None);
+    }
+
+    #[test]
+    fn reload_keeps_most_recent_entries() {
+        let settings = Arc::new(MemoryDB::default());
+        let cache = BadBlockCache::load_with_capacity(settings.clone(), nonzero!(2usize));
+        for n in 0..3 {
+            cache.put(cid(n), n.to_string());
+        }
+
+        let cache = BadBlockCache::load_with_capacity(settings, nonzero!(2usize));
+        assert_eq!(cache.peek(&cid(0)), None);
+        assert_eq!(cache.peek(&cid(1)), Some("1".into()));
+        assert_eq!(cache.peek(&cid(2)), Some("2".into()));
+    }
+
+    #[test]
+    fn each_block_is_persisted_under_its_own_key() {
+        let settings = Arc::new(MemoryDB::default());
+        let persisted = || {
+            let mut keys = settings.setting_keys().unwrap();
+            keys.sort();
+            keys
+        };
+        let cache = BadBlockCache::load_with_capacity(settings.clone(), nonzero!(2usize));
+        cache.put(cid(0), "0".into());
+        cache.put(cid(1), "1".into());
+        assert_eq!(persisted(), [key(&cid(0)), key(&cid(1))]);
+
+        // Evicted blocks are no longer persisted
+        cache.put(cid(2), "2".into());
+        assert_eq!(persisted(), [key(&cid(1)), key(&cid(2))]);
+
+        cache.remove(&cid(1));
+        assert_eq!(persisted(), [key(&cid(2))]);
+        cache.clear();
+        assert!(persisted().is_empty());
     }
 }
@@ -8,7 +8,9 @@ use std::{
     time::SystemTime,
 };
 
-use crate::blocks::{Block, CreateTipsetError, FullTipset, GossipBlock, Tipset, TipsetKey};
+use crate::blocks::{
+    Block, CachingBlockHeader, CreateTipsetError, FullTipset, GossipBlock, Tipset, TipsetKey,
+};
 use crate::chain::{ChainStore, Error as ChainStoreError};
 use crate::libp2p::{
     hello::HelloRequest, NetworkEvent, NetworkMessage, PeerId, PeerManager, PubsubMessage,
@@ -27,6 +29,7 @@ use fvm_ipld_blockstore::Blockstore;
 use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use tokio::sync::broadcast;
 use tracing::{debug, error, info, trace, warn};
 
 use crate::chain_sync::{
@@ -45,6 +48,11 @@ use crate::chain_sync::{
 const DEFAULT_REQUEST_WINDOW: usize = 8;
 const DEFAULT_TIPSET_SAMPLE_SIZE: usize = 5;
 const DEFAULT_RECENT_STATE_ROOTS: i64 = 2000;
+// Lotus starts pre-migrations 120 epochs before the upgrades
+const DEFAULT_PRE_MIGRATION_EPOCHS: i64 = 120;
+/// How many block headers a slow `Filecoin.SyncIncomingBlocks` subscriber can
+/// lag behind before it starts missing some.
+const INCOMING_BLOCKS_CAPACITY: usize = 100;
 
 pub(in crate::chain_sync) type WorkerState = Arc<RwLock<SyncState>>;
 
@@ -88,6 +96,15 @@ pub struct SyncConfig {
     /// head is
     #[cfg_attr(test, arbitrary(gen(|g| u32::arbitrary(g) as _)))]
     pub tipset_sample_size: usize,
+    /// Number of epochs before an upgrade to start migrating the state for it
+    /// in the background, so that the migration at the upgrade epoch only
+    /// migrates what changed since. Zero disables pre-migrations.
+    #[serde(default = "default_pre_migration_epochs")]
+    pub pre_migration_epochs: i64,
+}
+
+fn default_pre_migration_epochs() -> i64 {
+    DEFAULT_PRE_MIGRATION_EPOCHS
 }
 
 impl Default for SyncConfig {
@@ -96,6 +113,7 @@ impl Default for SyncConfig {
             request_window: DEFAULT_REQUEST_WINDOW,
             recent_state_roots: DEFAULT_RECENT_STATE_ROOTS,
             tipset_sample_size: DEFAULT_TIPSET_SAMPLE_SIZE,
+            pre_migration_epochs: DEFAULT_PRE_MIGRATION_EPOCHS,
         }
     }
 }
@@ -147,6 +165,9 @@ pub struct ChainMuxer<DB, M> {
     /// cache
     bad_blocks: Arc<BadBlockCache>,
 
+    /// Headers of the blocks received over GossipSub, for `Filecoin.SyncIncomingBlocks`
+    incoming_blocks: broadcast::Sender<CachingBlockHeader>,
+
     /// Incoming network events to be handled by synchronizer
     net_handler: flume::Receiver<NetworkEvent>,
 
@@ -188,7 +209,8 @@ where
             worker_state: Default::default(),
             network,
             genesis,
-            bad_blocks: Arc::new(BadBlockCache::default()),
+            bad_blocks: Arc::new(BadBlockCache::load(state_manager.chain_store().settings())),
+            incoming_blocks: broadcast::channel(INCOMING_BLOCKS_CAPACITY).0,
             net_handler: network_rx,
             mpool,
             tipset_sender,
@@ -204,6 +226,12 @@ where
         self.bad_blocks.clone()
     }
 
+    /// Returns a sender of the headers of blocks received over GossipSub, to
+    /// subscribe to.
+    pub fn incoming_blocks_cloned(&self) -> broadcast::Sender<CachingBlockHeader> {
+        self.incoming_blocks.clone()
+    }
+
     /// Returns a cloned `Arc` of the sync worker state.
     pub fn sync_state_cloned(&self) -> WorkerState {
         self.worker_state.clone()
@@ -356,6 +384,7 @@ where
         network: SyncNetworkContext<DB>,
         chain_store: Arc<ChainStore<DB>>,
         bad_block_cache: Arc<BadBlockCache>,
+        incoming_blocks: broadcast::Sender<CachingBlockHeader>,
         mem_pool: Arc<MessagePool<M>>,
         genesis: Arc<Tipset>,
         message_processing_strategy: PubsubMessageProcessingStrategy,
@@ -435,6 +464,8 @@ where
                     metrics::LIBP2P_MESSAGE_TOTAL
                         .get_or_create(&metrics::values::PUBSUB_BLOCK)
                         .inc();
+                    // This only fails when nobody is subscribed
+                    let _ = incoming_blocks.send(b.header.clone());
                     // Assemble full tipset from block
                     let tipset =
                         Self::gossipsub_block_to_full_tipset(b, source, network.clone()).await?;
@@ -523,6 +554,7 @@ where
         let network = self.network.clone();
         let genesis = self.genesis.clone();
         let bad_block_cache = self.bad_blocks.clone();
+        let incoming_blocks = self.incoming_blocks.clone();
         let mem_pool = self.mpool.clone();
         let block_delay = self.state_manager.chain_config().block_delay_secs as u64;
 
@@ -541,6 +573,7 @@ where
                     network.clone(),
                     chain_store.clone(),
                     bad_block_cache.clone(),
+                    incoming_blocks.clone(),
                     mem_pool.clone(),
                     genesis.clone(),
                     PubsubMessageProcessingStrategy::DoNotProcess,
@@ -565,6 +598,7 @@ where
         let network = self.network.clone();
         let genesis = self.genesis.clone();
         let bad_block_cache = self.bad_blocks.clone();
+        let incoming_blocks = self.incoming_blocks.clone();
         let mem_pool = self.mpool.clone();
         let tipset_sample_size = self.state_manager.sync_config().tipset_sample_size;
         let block_delay = self.state_manager.chain_config().block_delay_secs as u64;
@@ -585,6 +619,7 @@ where
                     network.clone(),
                     chain_store.clone(),
                     bad_block_cache.clone(),
+                    incoming_blocks.clone(),
                     mem_pool.clone(),
                     genesis.clone(),
                     PubsubMessageProcessingStrategy::Process,
@@ -707,6 +742,7 @@ where
         let network = self.network.clone();
         let genesis = self.genesis.clone();
         let bad_block_cache = self.bad_blocks.clone();
+        let incoming_blocks = self.incoming_blocks.clone();
         let mem_pool = self.mpool.clone();
         let block_delay = self.state_manager.chain_config().block_delay_secs as u64;
         let stream_processor: ChainMuxerFuture<(), ChainMuxerError> = Box::pin(async move {
@@ -724,6 +760,7 @@ where
                     network.clone(),
                     chain_store.clone(),
                     bad_block_cache.clone(),
+                    incoming_blocks.clone(),
                     mem_pool.clone(),
                     genesis.clone(),
                     PubsubMessageProcessingStrategy::DoNotProcess,
@@ -797,6 +834,7 @@ where
         let network = self.network.clone();
         let genesis = self.genesis.clone();
         let bad_block_cache = self.bad_blocks.clone();
+        let incoming_blocks = self.incoming_blocks.clone();
         let mem_pool = self.mpool.clone();
         let tipset_sender = self.tipset_sender.clone();
         let block_delay = self.state_manager.chain_config().block_delay_secs as u64;
@@ -826,6 +864,7 @@ where
                         network.clone(),
                         chain_store.clone(),
                         bad_block_cache.clone(),
+                        incoming_blocks.clone(),
                         mem_pool.clone(),
                         genesis.clone(),
                         PubsubMessageProcessingStrategy::Process,
@@ -0,0 +1,135 @@
+// Copyright 2019-2024 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::sync::Arc;
+
+use crate::blocks::{Tipset, TipsetKey};
+use crate::chain::index::{ChainIndex, ResolveNullTipset};
+use crate::db::{setting_keys::SYNC_CHECKPOINT_KEY, SettingsStore, SettingsStoreExt as _};
+use crate::shim::clock::ChainEpoch;
+use fvm_ipld_blockstore::Blockstore;
+use serde::{Deserialize, Serialize};
+
+/// A tipset the node operator trusts to be valid, e.g. the head of a trusted
+/// snapshot. Its ancestors are not fully validated when syncing.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    epoch: ChainEpoch,
+    key: TipsetKey,
+}
+
+impl From<&Tipset> for Checkpoint {
+    fn from(tipset: &Tipset) -> Self {
+        Self {
+            epoch: tipset.epoch(),
+            key: tipset.key().clone(),
+        }
+    }
+}
+
+impl Checkpoint {
+    /// Loads the checkpoint from the settings store, if one was set.
+    pub fn load(settings: &dyn SettingsStore) -> anyhow::Result<Option<Self>> {
+        settings.read_obj(SYNC_CHECKPOINT_KEY)
+    }
+
+    /// Persists the checkpoint in the settings store, replacing the previous
+    /// one.
+    pub fn save(&self, settings: &dyn SettingsStore) -> anyhow::Result<()> {
+        settings.write_obj(SYNC_CHECKPOINT_KEY, self)
+    }
+
+    pub fn epoch(&self) -> ChainEpoch {
+        self.epoch
+    }
+
+    pub fn key(&self) -> &TipsetKey {
+        &self.key
+    }
+
+    /// Returns `true` if `tipset` is the checkpointed tipset, one of its
+    /// ancestors or one of its descendants, i.e. if the checkpoint vouches for
+    /// the history of `tipset`.
+    pub fn is_on_chain_of<DB: Blockstore>(
+        &self,
+        chain_index: &ChainIndex<DB>,
+        tipset: Arc<Tipset>,
+    ) -> anyhow::Result<bool> {
+        let (ancestor_epoch, ancestor_key, descendant) = if tipset.epoch() >= self.epoch {
+            (self.epoch, &self.key, tipset.clone())
+        } else {
+            let Some(checkpointed) = chain_index.load_tipset(&self.key)? else {
+                return Ok(false);
+            };
+            (tipset.epoch(), tipset.key(), checkpointed)
+        };
+        let ancestor = chain_index.tipset_by_height(
+            ancestor_epoch,
+            descendant,
+            ResolveNullTipset::TakeOlder,
+        )?;
+        Ok(ancestor.key() == ancestor_key)
+    }
+}
+
+/// Returns `true` if a tipset at the given epoch has to be fully validated,
+/// i.e. it isn't covered by the checkpoint. The tipset must be on the chain of
+/// the checkpoint, see [`Checkpoint::is_on_chain_of`].
+pub(in crate::chain_sync) fn needs_validation(
+    checkpoint: Option<&Checkpoint>,
+    epoch: ChainEpoch,
+) -> bool {
+    checkpoint.map_or(true, |checkpoint| epoch > checkpoint.epoch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::{chain4u, CachingBlockHeader, Chain4U, RawBlockHeader};
+    use crate::db::MemoryDB;
+
+    #[test]
+    fn validation_skipped_up_to_checkpoint() {
+        let settings = MemoryDB::default();
+        assert_eq!(Checkpoint::load(&settings).unwrap(), None);
+        assert!(needs_validation(None, 0));
+
+        let tipset = Tipset::from(CachingBlockHeader::new(RawBlockHeader {
+            epoch: 10,
+            ..Default::default()
+        }));
+        Checkpoint::from(&tipset).save(&settings).unwrap();
+
+        let checkpoint = Checkpoint::load(&settings).unwrap().unwrap();
+        assert_eq!(checkpoint.key(), tipset.key());
+        assert!(!needs_validation(Some(&checkpoint), 9));
+        assert!(!needs_validation(Some(&checkpoint), 10));
+        assert!(needs_validation(Some(&checkpoint), 11));
+    }
+
+    #[test]
+    fn forks_are_not_on_the_chain_of_the_checkpoint() {
+        let c4u = Chain4U::new();
+        chain4u! {
+            in c4u;
+            [_genesis] -> below @ [_main_1] -> checkpointed @ [_main_2] -> above @ [_main_3]
+        };
+        chain4u! {
+            from [_genesis] in c4u;
+            fork_below @ [_fork_1] -> fork_at @ [_fork_2] -> fork_above @ [_fork_3]
+        };
+        let chain_index = ChainIndex::new(Arc::new(c4u));
+        let checkpoint = Checkpoint::from(checkpointed);
+
+        for tipset in [below, checkpointed, above] {
+            assert!(checkpoint
+                .is_on_chain_of(&chain_index, Arc::new(tipset.clone()))
+                .unwrap());
+        }
+        for tipset in [fork_below, fork_at, fork_above] {
+            assert!(!checkpoint
+                .is_on_chain_of(&chain_index, Arc::new(tipset.clone()))
+                .unwrap());
+        }
+    }
+}
@@ -37,6 +37,15 @@ pub static BLOCK_VALIDATION_TASKS_TIME: Lazy<Family<TypeLabel, Histogram>> = Laz
     );
     metric
 });
+pub static STATE_PREFETCH_TIME: Lazy<Histogram> = Lazy::new(|| {
+    let metric = crate::metrics::default_histogram();
+    crate::metrics::default_registry().register(
+        "state_prefetch_time",
+        "Duration of routine which reads the state of the actors of the messages of a tipset before they are executed",
+        metric.clone(),
+    );
+    metric
+});
 pub static LIBP2P_MESSAGE_TOTAL: Lazy<Family<Libp2pMessageKindLabel, Counter>> = Lazy::new(|| {
     let metric = Family::default();
     crate::metrics::default_registry().register(
@@ -202,4 +211,5 @@ pub mod values {
     pub const BASE_FEE_CHECK: TypeLabel = TypeLabel::new("base_fee_check");
     pub const PARENT_WEIGHT_CAL: TypeLabel = TypeLabel::new("parent_weight_check");
     pub const BLOCK_SIGNATURE_CHECK: TypeLabel = TypeLabel::new("block_signature_check");
+    pub const MESSAGE_SIGNATURE_CHECK: TypeLabel = TypeLabel::new("message_signature_check");
 }
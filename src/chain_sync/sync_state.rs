@@ -3,7 +3,7 @@
 
 use std::sync::Arc;
 
-use crate::blocks::Tipset;
+use crate::blocks::{Tipset, TipsetKey};
 use crate::shim::clock::ChainEpoch;
 #[cfg(test)]
 use chrono::TimeZone;
@@ -55,7 +55,10 @@ pub struct SyncState {
     start: Option<DateTime<Utc>>,
     #[cfg_attr(test, arbitrary(gen(maybe_epoch0)))]
     end: Option<DateTime<Utc>>,
-    message: String,
+    /// Why the sync failed, if it did.
+    message: Option<String>,
+    /// The epoch and key of the last tipset that passed validation.
+    last_validated: Option<(ChainEpoch, TipsetKey)>,
 }
 
 #[cfg(test)]
@@ -98,6 +101,16 @@ impl SyncState {
         self.epoch
     }
 
+    /// Returns why the sync failed, if it did.
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+
+    /// Returns the epoch and key of the last validated [`Tipset`]
+    pub fn last_validated(&self) -> Option<&(ChainEpoch, TipsetKey)> {
+        self.last_validated.as_ref()
+    }
+
     /// Get the elapsed time of the current syncing process.
     /// Returns `None` if syncing has not started
     pub fn get_elapsed_time(&self) -> Option<Duration> {
@@ -123,9 +136,14 @@ impl SyncState {
         self.epoch = epoch;
     }
 
+    /// Records the last [`Tipset`] that passed validation.
+    pub fn set_last_validated(&mut self, epoch: ChainEpoch, key: TipsetKey) {
+        self.last_validated = Some((epoch, key));
+    }
+
     /// Sets error for the sync.
     pub fn error(&mut self, err: String) {
-        self.message = err;
+        self.message = Some(err);
         self.stage = SyncStage::Error;
         self.end = Some(Utc::now());
     }
@@ -133,7 +151,12 @@ impl SyncState {
 
 mod lotus_json {
     use super::SyncState;
-    use crate::{blocks::Tipset, chain_sync::SyncStage, lotus_json::*};
+    use crate::{
+        blocks::{Tipset, TipsetKey},
+        chain_sync::SyncStage,
+        lotus_json::*,
+        shim::clock::ChainEpoch,
+    };
     use chrono::{DateTime, Utc};
     use std::sync::Arc;
 
@@ -156,7 +179,10 @@ mod lotus_json {
         start: LotusJson<Option<DateTime<Utc>>>,
         #[serde(skip_serializing_if = "LotusJson::is_none", default)]
         end: LotusJson<Option<DateTime<Utc>>>,
-        message: LotusJson<String>,
+        #[serde(skip_serializing_if = "LotusJson::is_none", default)]
+        message: LotusJson<Option<String>>,
+        #[serde(skip_serializing_if = "LotusJson::is_none", default)]
+        last_validated: LotusJson<Option<(ChainEpoch, TipsetKey)>>,
     }
 
     impl HasLotusJson for SyncState {
@@ -164,14 +190,32 @@ mod lotus_json {
 
         #[cfg(test)]
         fn snapshots() -> Vec<(serde_json::Value, Self)> {
-            vec![(
-                json!({
-                    "Epoch": 0,
-                    "Message": "",
-                    "Stage": "header sync",
-                }),
-                Self::default(),
-            )]
+            vec![
+                (
+                    json!({
+                        "Epoch": 0,
+                        "Stage": "header sync",
+                    }),
+                    Self::default(),
+                ),
+                (
+                    json!({
+                        "Epoch": 0,
+                        "Stage": "error",
+                        "Message": "invalid block",
+                        "LastValidated": [1, [{"/": "baeaaaaa"}]],
+                    }),
+                    Self {
+                        stage: SyncStage::Error,
+                        message: Some("invalid block".into()),
+                        last_validated: Some((
+                            1,
+                            ::nonempty::nonempty![::cid::Cid::default()].into(),
+                        )),
+                        ..Default::default()
+                    },
+                ),
+            ]
         }
 
         fn into_lotus_json(self) -> Self::LotusJson {
@@ -183,6 +227,7 @@ mod lotus_json {
                 start,
                 end,
                 message,
+                last_validated,
             } = self;
             Self::LotusJson {
                 base: base.as_deref().cloned().into(),
@@ -192,6 +237,7 @@ mod lotus_json {
                 start: start.into(),
                 end: end.into(),
                 message: message.into(),
+                last_validated: last_validated.into(),
             }
         }
 
@@ -204,6 +250,7 @@ mod lotus_json {
                 start,
                 end,
                 message,
+                last_validated,
             } = lotus_json;
             Self {
                 base: base.into_inner().map(Arc::new),
@@ -213,6 +260,7 @@ mod lotus_json {
                 start: start.into_inner(),
                 end: end.into_inner(),
                 message: message.into_inner(),
+                last_validated: last_validated.into_inner(),
             }
         }
     }
@@ -11,7 +11,7 @@ use std::{
 };
 
 use crate::libp2p::chain_exchange::TipsetBundle;
-use crate::message::{valid_for_block_inclusion, Message as MessageTrait};
+use crate::message::{valid_for_block_inclusion, Message as MessageTrait, SignedMessage};
 use crate::networks::Height;
 use crate::shim::clock::ALLOWABLE_CLOCK_DRIFT;
 use crate::shim::{
@@ -25,7 +25,10 @@ use crate::{
     fil_cns::{self, FilecoinConsensus, FilecoinConsensusError},
 };
 use crate::{
-    chain::{persist_objects, ChainStore, Error as ChainStoreError},
+    chain::{
+        index::ChainIndex, message_index::index_messages, persist_objects, ChainStore,
+        Error as ChainStoreError,
+    },
     metrics::HistogramTimerExt,
 };
 use ahash::{HashMap, HashMapExt, HashSet};
@@ -36,13 +39,19 @@ use fvm_ipld_blockstore::Blockstore;
 use fvm_ipld_encoding::to_vec;
 use itertools::Itertools;
 use nonempty::{nonempty, NonEmpty};
+use rayon::prelude::*;
 use thiserror::Error;
 use tokio::task::JoinSet;
 use tracing::{debug, error, info, trace, warn};
 
 use crate::chain_sync::{
-    bad_block_cache::BadBlockCache, consensus::collect_errs, metrics,
-    network_context::SyncNetworkContext, sync_state::SyncStage, validation::TipsetValidator,
+    bad_block_cache::BadBlockCache,
+    checkpoint::{needs_validation, Checkpoint},
+    consensus::collect_errs,
+    metrics,
+    network_context::SyncNetworkContext,
+    sync_state::SyncStage,
+    validation::TipsetValidator,
 };
 
 const MAX_TIPSETS_TO_REQUEST: u64 = 100;
@@ -1081,6 +1090,11 @@ async fn sync_messages_check_state<DB: Blockstore + Send + Sync + 'static>(
 ) -> Result<(), TipsetRangeSyncerError> {
     let request_window = state_manager.sync_config().request_window;
     let db = chainstore.blockstore();
+    let checkpoint = tipsets
+        .first()
+        .and_then(|head| load_checkpoint(&chainstore, head.clone()));
+
+    let prefetch = parking_lot::Mutex::new(None::<tokio::task::JoinHandle<()>>);
 
     // Stream through the tipsets from lowest epoch to highest epoch
     stream::iter(tipsets.into_iter().rev())
@@ -1094,6 +1108,24 @@ async fn sync_messages_check_state<DB: Blockstore + Send + Sync + 'static>(
         .try_for_each(|batch| async {
             for full_tipset in batch {
                 let current_epoch = full_tipset.epoch();
+                // The messages of the tipset are executed when validating its
+                // child, so read the state they touch while validating it. One
+                // prefetch at a time keeps it from falling behind.
+                if needs_validation(checkpoint.as_ref(), current_epoch) {
+                    let mut prefetch = prefetch.lock();
+                    if prefetch.as_ref().map_or(true, |it| it.is_finished()) {
+                        let chain_index = chainstore.chain_index.clone();
+                        let full_tipset = full_tipset.clone();
+                        *prefetch = Some(tokio::task::spawn_blocking(move || {
+                            let _timer = metrics::STATE_PREFETCH_TIME.start_timer();
+                            if let Err(e) = prefetch_message_actors(&chain_index, &full_tipset) {
+                                debug!(
+                                    "Failed to prefetch the state for epoch {current_epoch}: {e}"
+                                );
+                            }
+                        }));
+                    }
+                }
                 let timer = metrics::TIPSET_PROCESSING_TIME.start_timer();
                 validate_tipset(
                     state_manager.clone(),
@@ -1101,12 +1133,24 @@ async fn sync_messages_check_state<DB: Blockstore + Send + Sync + 'static>(
                     bad_block_cache,
                     full_tipset.clone(),
                     genesis,
+                    checkpoint.as_ref(),
                     invalid_block_strategy,
                 )
                 .await?;
                 drop(timer);
-                chainstore.set_heaviest_tipset(Arc::new(full_tipset.into_tipset()))?;
-                tracker.write().set_epoch(current_epoch);
+                let key = full_tipset.key().clone();
+                let tipset = Arc::new(full_tipset.into_tipset());
+                chainstore.set_heaviest_tipset(tipset.clone())?;
+                // The message index only speeds up lookups, which fall back to
+                // scanning the chain, so failing to update it isn't fatal
+                if let Err(e) = index_messages(db, chainstore.indices().as_ref(), &tipset) {
+                    warn!("Failed to index the messages executed at epoch {current_epoch}: {e}");
+                }
+                {
+                    let mut tracker = tracker.write();
+                    tracker.set_epoch(current_epoch);
+                    tracker.set_last_validated(current_epoch, key);
+                }
                 metrics::LAST_VALIDATED_TIPSET_EPOCH.set(current_epoch);
             }
             Ok(())
@@ -1114,6 +1158,64 @@ async fn sync_messages_check_state<DB: Blockstore + Send + Sync + 'static>(
         .await
 }
 
+/// Reads the actors the messages of the tipset are sent from and to, and the
+/// heads of their states, in the state its parent was executed on. The messages
+/// are executed on the state the validation of the tipset computes, which
+/// shares most blocks with that one. Nothing is written, so the state roots
+/// computed afterwards are unchanged.
+fn prefetch_message_actors<DB: Blockstore>(
+    chain_index: &ChainIndex<Arc<DB>>,
+    full_tipset: &FullTipset,
+) -> anyhow::Result<()> {
+    let parent =
+        chain_index.load_required_tipset(&full_tipset.blocks().first().header().parents)?;
+    let tree = StateTree::new_from_root(chain_index.db.clone(), parent.parent_state())?;
+    let addresses: HashSet<Address> = full_tipset
+        .blocks()
+        .iter()
+        .flat_map(|block| {
+            block
+                .bls_msgs()
+                .iter()
+                .chain(block.secp_msgs().iter().map(SignedMessage::message))
+        })
+        .flat_map(|msg| [msg.from, msg.to])
+        .collect();
+    for address in addresses {
+        if let Some(actor) = tree.get_actor(&address)? {
+            chain_index.db.get(&actor.state)?;
+        }
+    }
+    Ok(())
+}
+
+/// Loads the sync checkpoint, unless `head` is on a fork of the checkpointed
+/// chain, in which case none of the tipsets leading to it can be trusted.
+fn load_checkpoint<DB: Blockstore>(
+    chainstore: &ChainStore<DB>,
+    head: Arc<Tipset>,
+) -> Option<Checkpoint> {
+    let checkpoint = Checkpoint::load(chainstore.settings().as_ref()).unwrap_or_else(|e| {
+        warn!("Failed to load the sync checkpoint: {e}");
+        None
+    })?;
+    match checkpoint.is_on_chain_of(&chainstore.chain_index, head.clone()) {
+        Ok(true) => Some(checkpoint),
+        Ok(false) => {
+            warn!(
+                "Tipset {} is not on the chain of the sync checkpoint {}, validating it fully",
+                head.key(),
+                checkpoint.key()
+            );
+            None
+        }
+        Err(e) => {
+            warn!("Failed to check the ancestry of the sync checkpoint: {e}");
+            None
+        }
+    }
+}
+
 /// Validates full blocks in the tipset in parallel (since the messages are not
 /// executed), adding the successful ones to the tipset tracker, and the failed
 /// ones to the bad block cache, depending on strategy. Any bad block fails
@@ -1124,6 +1226,7 @@ async fn validate_tipset<DB: Blockstore + Send + Sync + 'static>(
     bad_block_cache: &BadBlockCache,
     full_tipset: FullTipset,
     genesis: &Tipset,
+    checkpoint: Option<&Checkpoint>,
     invalid_block_strategy: InvalidBlockStrategy,
 ) -> Result<(), TipsetRangeSyncerError> {
     if full_tipset.key().eq(genesis.key()) {
@@ -1132,6 +1235,10 @@ async fn validate_tipset<DB: Blockstore + Send + Sync + 'static>(
     }
 
     let epoch = full_tipset.epoch();
+    if !needs_validation(checkpoint, epoch) {
+        trace!("Skipping validation of checkpointed tipset: EPOCH = {epoch}");
+        return Ok(());
+    }
     let full_tipset_key = full_tipset.key().clone();
 
     let mut validations = FuturesUnordered::new();
@@ -1497,6 +1604,7 @@ async fn check_block_messages<DB: Blockstore + Send + Sync + 'static>(
     }
 
     // Check validity for SECP messages
+    let mut key_addrs = Vec::with_capacity(block.secp_msgs().len());
     for (i, msg) in block.secp_msgs().iter().enumerate() {
         check_msg(msg.message(), &mut account_sequences, &tree).map_err(|e| {
             TipsetRangeSyncerError::Validation(format!(
@@ -1508,11 +1616,16 @@ async fn check_block_messages<DB: Blockstore + Send + Sync + 'static>(
             .resolve_to_key_addr(&msg.from(), &base_tipset)
             .await
             .map_err(|e| TipsetRangeSyncerError::ResolvingAddressFromMessage(e.to_string()))?;
-        // SecP256K1 Signature validation
-        msg.signature
-            .verify(&msg.message().cid().unwrap().to_bytes(), &key_addr)
-            .map_err(TipsetRangeSyncerError::MessageSignatureInvalid)?;
+        key_addrs.push(key_addr);
     }
+    let v_block = block.clone();
+    tokio::task::spawn_blocking(move || {
+        let metric = &*metrics::BLOCK_VALIDATION_TASKS_TIME
+            .get_or_create(&metrics::values::MESSAGE_SIGNATURE_CHECK);
+        let _timer = metric.start_timer();
+        verify_secp_signatures(v_block.secp_msgs(), &key_addrs)
+    })
+    .await??;
 
     // Validate message root from header matches message root
     let msg_root = TipsetValidator::compute_msg_root(
@@ -1531,6 +1644,33 @@ async fn check_block_messages<DB: Blockstore + Send + Sync + 'static>(
     Ok(())
 }
 
+/// Verifies the signatures of the SECP messages against the key addresses of
+/// their senders in parallel, failing with the first invalid one in order.
+/// Delegated signatures cover the RLP-encoded Ethereum transaction rather than
+/// the message CID.
+fn verify_secp_signatures(
+    msgs: &[SignedMessage],
+    key_addrs: &[Address],
+) -> Result<(), TipsetRangeSyncerError> {
+    msgs.par_iter()
+        .zip(key_addrs)
+        .map(|(msg, key_addr)| {
+            if msg.is_delegated() {
+                return Ok(());
+            }
+            let cid = msg
+                .message()
+                .cid()
+                .map_err(|e| TipsetRangeSyncerError::MessageSignatureInvalid(e.to_string()))?;
+            msg.signature
+                .verify(&cid.to_bytes(), key_addr)
+                .map_err(TipsetRangeSyncerError::MessageSignatureInvalid)
+        })
+        .collect::<Vec<_>>()
+        .into_iter()
+        .collect()
+}
+
 /// Checks optional values in header.
 ///
 /// It only looks for fields which are common to all consensus types.
@@ -1581,10 +1721,14 @@ fn validate_tipset_against_cache(
 
 #[cfg(test)]
 mod test {
-    use crate::blocks::RawBlockHeader;
     use crate::blocks::VRFProof;
+    use crate::blocks::{chain4u, RawBlockHeader};
     use crate::blocks::{CachingBlockHeader, ElectionProof, Ticket, Tipset};
-    use crate::shim::address::Address;
+    use crate::chain_sync::SyncConfig;
+    use crate::db::{car::ManyCar, GarbageCollectable as _, MemoryDB};
+    use crate::key_management::{generate_key, sign, Key};
+    use crate::networks::ChainConfig;
+    use crate::shim::{address::Address, crypto::SignatureType};
     use cid::Cid;
     use num_bigint::BigInt;
 
@@ -1636,4 +1780,183 @@ mod test {
         assert_eq!(ts, ts3);
         assert_eq!(ts.weight(), &BigInt::from(10));
     }
+
+    fn signed_message(sequence: u64, key: &Key) -> SignedMessage {
+        let message: Message = fvm_shared4::message::Message {
+            from: key.address.into(),
+            to: Address::new_id(100).into(),
+            sequence,
+            ..Default::default()
+        }
+        .into();
+        let signature = sign(
+            SignatureType::Secp256k1,
+            key.key_info.private_key(),
+            &message.cid().unwrap().to_bytes(),
+        )
+        .unwrap();
+        SignedMessage::new_unchecked(message, signature)
+    }
+
+    #[test]
+    fn parallel_signature_checks_match_sequential_ones() {
+        let keys = (0..3)
+            .map(|_| generate_key(SignatureType::Secp256k1).unwrap())
+            .collect_vec();
+        let mut msgs = (0..32)
+            .map(|sequence| signed_message(sequence, &keys[sequence as usize % keys.len()]))
+            .collect_vec();
+        let key_addrs = msgs.iter().map(|msg| msg.from()).collect_vec();
+        assert!(verify_secp_signatures(&msgs, &key_addrs).is_ok());
+
+        // Swap the signatures of two messages, so that both are invalid
+        let (first, second) = (5, 20);
+        let signature = msgs[first].signature.clone();
+        msgs[first].signature = msgs[second].signature.clone();
+        msgs[second].signature = signature;
+
+        let sequential = msgs
+            .iter()
+            .zip(&key_addrs)
+            .find_map(|(msg, key_addr)| {
+                msg.signature
+                    .verify(&msg.message().cid().unwrap().to_bytes(), key_addr)
+                    .err()
+            })
+            .unwrap();
+        for _ in 0..10 {
+            match verify_secp_signatures(&msgs, &key_addrs) {
+                Err(TipsetRangeSyncerError::MessageSignatureInvalid(e)) => {
+                    assert_eq!(e, sequential)
+                }
+                other => panic!("unexpected result {other:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn prefetching_does_not_alter_the_state() {
+        let db = Arc::new(ManyCar::new(MemoryDB::default()));
+        db.read_only_files(std::iter::once("test-snapshots/chain4.car".into()))
+            .unwrap();
+        // The snapshot only includes the state of its genesis
+        let parent = db.heaviest_tipset().unwrap().chain(&db).last().unwrap();
+        let actors = || {
+            let mut actors = vec![];
+            StateTree::new_from_root(db.clone(), parent.parent_state())
+                .unwrap()
+                .for_each(|address, actor| {
+                    actors.push((address, actor.clone()));
+                    Ok(())
+                })
+                .unwrap();
+            actors
+        };
+        let expected = actors();
+        assert!(expected.len() > 1);
+
+        // Each actor sends a message to the next one, so that the state of
+        // every actor is prefetched
+        let messages = expected
+            .iter()
+            .zip(expected.iter().cycle().skip(1))
+            .map(|((from, _), (to, _))| {
+                fvm_shared4::message::Message {
+                    from: (*from).into(),
+                    to: (*to).into(),
+                    ..Default::default()
+                }
+                .into()
+            })
+            .collect_vec();
+        let header = CachingBlockHeader::new(RawBlockHeader {
+            parents: parent.key().clone(),
+            epoch: parent.epoch() + 1,
+            ..mock_block(1, 1, 1).into_raw()
+        });
+        let full_tipset = FullTipset::new([Block {
+            header,
+            bls_messages: messages,
+            secp_messages: vec![],
+        }])
+        .unwrap();
+
+        let chain_index = ChainIndex::new(db.clone());
+        for _ in 0..2 {
+            prefetch_message_actors(&chain_index, &full_tipset).unwrap();
+            // Nothing is written, so the state the tipset is executed on is
+            // the same
+            assert_eq!(actors(), expected);
+            assert!(db.writer().get_keys().unwrap().is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn forks_below_the_checkpoint_are_validated() {
+        let chain_store = Arc::new(ChainStore::calibnet());
+        let state_manager = Arc::new(
+            StateManager::new(
+                chain_store.clone(),
+                Arc::new(ChainConfig::calibnet()),
+                Arc::new(SyncConfig::default()),
+            )
+            .unwrap(),
+        );
+        let genesis = Tipset::from(chain_store.genesis_block_header());
+        chain4u! {
+            in chain_store.blockstore();
+            [_genesis = chain_store.genesis_block_header()]
+            -> [main_1] -> main_2 @ [_main_2]
+        };
+        chain4u! {
+            from [_genesis] in chain_store.blockstore();
+            [fork_1] -> fork_2 @ [_fork_2]
+        };
+        Checkpoint::from(main_2)
+            .save(chain_store.settings().as_ref())
+            .unwrap();
+        // Neither block is signed, so validating them fails.
+        let full_tipset = |header: &RawBlockHeader| {
+            FullTipset::new([Block {
+                header: CachingBlockHeader::new(header.clone()),
+                bls_messages: vec![],
+                secp_messages: vec![],
+            }])
+            .unwrap()
+        };
+        let bad_block_cache = BadBlockCache::default();
+
+        let checkpoint = load_checkpoint(&chain_store, Arc::new(main_2.clone()));
+        assert!(checkpoint.is_some());
+        validate_tipset(
+            state_manager.clone(),
+            &chain_store,
+            &bad_block_cache,
+            full_tipset(main_1),
+            &genesis,
+            checkpoint.as_ref(),
+            InvalidBlockStrategy::Strict,
+        )
+        .await
+        .unwrap();
+
+        let checkpoint = load_checkpoint(&chain_store, Arc::new(fork_2.clone()));
+        assert_eq!(checkpoint, None);
+        let fork_1 = full_tipset(fork_1);
+        let fork_1_cid = *fork_1.blocks().first().cid();
+        assert!(matches!(
+            validate_tipset(
+                state_manager,
+                &chain_store,
+                &bad_block_cache,
+                fork_1,
+                &genesis,
+                checkpoint.as_ref(),
+                InvalidBlockStrategy::Strict,
+            )
+            .await,
+            Err(TipsetRangeSyncerError::BlockWithoutSignature)
+        ));
+        assert!(bad_block_cache.peek(&fork_1_cid).is_some());
+    }
 }
@@ -282,10 +282,8 @@ impl<V> IntoIterator for CidHashMap<V> {
 // Keys //
 //////////
 
-#[cfg(test)]
 use std::collections::hash_map::Keys as StdKeys;
 
-#[cfg(test)]
 impl<V> CidHashMap<V> {
     /// An iterator visiting all keys in arbitrary order.
     ///
@@ -303,13 +301,11 @@ impl<V> CidHashMap<V> {
 /// An iterator over the keys of a `HashMap`.
 ///
 /// See [`CidHashMap::keys`].
-#[cfg(test)]
 pub struct Keys<'a, V> {
     compact: StdKeys<'a, CidV1DagCborBlake2b256, V>,
     uncompact: StdKeys<'a, Uncompactable, V>,
 }
 
-#[cfg(test)]
 impl<'a, V> Iterator for Keys<'a, V> {
     type Item = Cid;
 
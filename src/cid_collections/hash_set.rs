@@ -31,6 +31,20 @@ impl CidHashSet {
     pub fn insert(&mut self, cid: Cid) -> bool {
         self.inner.insert(cid, ()).is_none()
     }
+
+    /// Returns `true` if the set contains a value.
+    ///
+    /// See also [`HashSet::contains`].
+    pub fn contains(&self, cid: &Cid) -> bool {
+        self.inner.contains_key(cid)
+    }
+
+    /// Returns the number of elements in the set.
+    ///
+    /// See also [`HashSet::len`].
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
 }
 
 ////////////////////
@@ -17,7 +17,7 @@ where
     ArgT: Into<OsString> + Clone,
 {
     // Capture Cli inputs
-    let Cli { token, cmd } = Cli::parse_from(args);
+    let Cli { token, output, cmd } = Cli::parse_from(args);
 
     let api = ApiInfo::from_env()?.set_token(token);
 
@@ -26,26 +26,33 @@ where
         .build()
         .unwrap()
         .block_on(async {
-            logger::setup_logger(&crate::cli_shared::cli::CliOpts::default());
-            if let Ok(name) = api.state_network_name().await {
-                if get_actual_chain_name(&name) != "mainnet" {
-                    CurrentNetwork::set_global(Network::Testnet);
+            // Logs go to stderr, so that stdout only has the output of the
+            // command
+            logger::setup_cli_logger();
+            // Probes only make the calls they check, so a hanging node fails
+            // them within their timeout
+            if !matches!(cmd, Subcommand::Healthcheck(_)) {
+                if let Ok(name) = api.state_network_name().await {
+                    if get_actual_chain_name(&name) != "mainnet" {
+                        CurrentNetwork::set_global(Network::Testnet);
+                    }
                 }
             }
             // Run command
             match cmd {
-                Subcommand::Chain(cmd) => cmd.run(api).await,
+                Subcommand::Chain(cmd) => cmd.run(api, output).await,
                 Subcommand::Auth(cmd) => cmd.run(api).await,
-                Subcommand::Net(cmd) => cmd.run(api).await,
-                Subcommand::Sync(cmd) => cmd.run(api).await,
-                Subcommand::Mpool(cmd) => cmd.run(api).await,
+                Subcommand::Net(cmd) => cmd.run(api, output).await,
+                Subcommand::Sync(cmd) => cmd.run(api, output).await,
+                Subcommand::Mpool(cmd) => cmd.run(api, output).await,
                 Subcommand::State(cmd) => cmd.run(api).await,
                 Subcommand::Config(cmd) => cmd.run(&mut std::io::stdout()),
                 Subcommand::Send(cmd) => cmd.run(api).await,
-                Subcommand::Info(cmd) => cmd.run(api).await,
+                Subcommand::Info(cmd) => cmd.run(api, output).await,
                 Subcommand::Snapshot(cmd) => cmd.run(api).await,
                 Subcommand::Attach(cmd) => cmd.run(api),
                 Subcommand::Shutdown(cmd) => cmd.run(api).await,
+                Subcommand::Healthcheck(cmd) => cmd.run(api).await,
             }
         })
 }
@@ -2,7 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use crate::auth::*;
-use crate::rpc_client::{ApiInfo, JsonRpcError};
+use crate::rpc_client::{ApiInfo, JsonRpcError, API_INFO_KEY};
 use chrono::Duration;
 use clap::Subcommand;
 use std::str::FromStr;
@@ -17,17 +17,39 @@ pub enum AuthCommands {
         #[arg(short, long)]
         perm: String,
         /// Token is revoked after this duration
-        #[arg(long, default_value_t = humantime::Duration::from_str("2 months").expect("infallible"))]
-        expire_in: humantime::Duration,
+        #[arg(long, alias = "expire-in", default_value_t = humantime::Duration::from_str("2 months").expect("infallible"))]
+        expires_in: humantime::Duration,
+        /// Create a token that never expires
+        #[arg(long)]
+        no_expiry: bool,
+        /// Label to identify the token with when listing tokens
+        #[arg(long)]
+        label: Option<String>,
     },
-    /// Get RPC API Information
+    /// Create a token with the given permission, and print it along with the
+    /// address of the node as `FULLNODE_API_INFO=<token>:<multiaddr>`
     ApiInfo {
         /// permission to assign the token, one of: read, write, sign, admin
         #[arg(short, long)]
         perm: String,
         /// Token is revoked after this duration
-        #[arg(long, default_value_t = humantime::Duration::from_str("2 months").expect("infallible"))]
-        expire_in: humantime::Duration,
+        #[arg(long, alias = "expire-in", default_value_t = humantime::Duration::from_str("2 months").expect("infallible"))]
+        expires_in: humantime::Duration,
+        /// Create a token that never expires
+        #[arg(long)]
+        no_expiry: bool,
+    },
+    /// List the tokens created through the API
+    List,
+    /// Revoke a token created through the API, rejecting any further request
+    /// using it
+    ///
+    /// Only the tokens created with `create-token` or `api-info` can be
+    /// revoked. Other tokens, such as the admin token printed by the daemon on
+    /// startup, carry no identifier and remain valid until they expire.
+    Revoke {
+        /// Identifier of the token to revoke, as shown by `auth list`
+        id: String,
     },
 }
 
@@ -44,25 +66,65 @@ fn process_perms(perm: String) -> Result<Vec<String>, JsonRpcError> {
     .collect())
 }
 
+fn token_exp(expires_in: humantime::Duration, no_expiry: bool) -> anyhow::Result<Option<Duration>> {
+    if no_expiry {
+        Ok(None)
+    } else {
+        Ok(Some(Duration::from_std(expires_in.into())?))
+    }
+}
+
 impl AuthCommands {
     pub async fn run(self, api: ApiInfo) -> anyhow::Result<()> {
         match self {
-            Self::CreateToken { perm, expire_in } => {
+            Self::CreateToken {
+                perm,
+                expires_in,
+                no_expiry,
+                label,
+            } => {
                 let perm: String = perm.parse()?;
                 let perms = process_perms(perm)?;
-                let token_exp = Duration::from_std(expire_in.into())?;
-                print_rpc_res_bytes(api.auth_new(perms, token_exp).await?)
+                let token_exp = token_exp(expires_in, no_expiry)?;
+                print_rpc_res_bytes(api.auth_new(perms, token_exp, label).await?)
             }
-            Self::ApiInfo { perm, expire_in } => {
+            Self::ApiInfo {
+                perm,
+                expires_in,
+                no_expiry,
+            } => {
                 let perm: String = perm.parse()?;
                 let perms = process_perms(perm)?;
-                let token_exp = Duration::from_std(expire_in.into())?;
-                let token = api.auth_new(perms, token_exp).await?;
+                let token_exp = token_exp(expires_in, no_expiry)?;
+                let token = api.auth_new(perms, token_exp, None).await?;
                 let new_api = ApiInfo {
                     token: Some(String::from_utf8(token)?),
                     ..api
                 };
-                println!("FULLNODE_API_INFO=\"{}\"", new_api);
+                // Same format as `lotus auth api-info`
+                println!("{API_INFO_KEY}={new_api}");
+                Ok(())
+            }
+            Self::List => {
+                for token in api.auth_list().await? {
+                    let expires = match token.expires {
+                        Some(expires) => chrono::DateTime::from_timestamp(expires, 0)
+                            .map(|time| format!("expires {}", time.to_rfc3339()))
+                            .unwrap_or_default(),
+                        None => "never expires".into(),
+                    };
+                    println!(
+                        "{}\t{}\t{expires}{}\t{}",
+                        token.id,
+                        token.perms.join(","),
+                        if token.revoked { " (revoked)" } else { "" },
+                        token.label.unwrap_or_default(),
+                    );
+                }
+                Ok(())
+            }
+            Self::Revoke { id } => {
+                api.auth_revoke(id).await?;
                 Ok(())
             }
         }
@@ -1,11 +1,16 @@
 // Copyright 2019-2024 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use std::time::Duration;
+
 use crate::blocks::{Tipset, TipsetKey};
+use crate::cli_shared::cli::OutputFormat;
+use crate::db::{GcProgress, GcStage};
 use crate::lotus_json::{HasLotusJson, LotusJson};
 use crate::message::ChainMessage;
-use crate::rpc_client::{ApiInfo, JsonRpcError};
-use anyhow::bail;
+use crate::rpc_client::ApiInfo;
+use crate::shim::clock::ChainEpoch;
+use anyhow::{bail, ensure};
 use cid::Cid;
 use clap::Subcommand;
 use nonempty::NonEmpty;
@@ -24,7 +29,12 @@ pub enum ChainCommands {
     Genesis,
 
     /// Prints out the canonical head of the chain
-    Head,
+    Head {
+        /// Output format, one block CID per line or the tipset key and epoch
+        /// as JSON. Defaults to the global `--output`
+        #[arg(long, value_enum)]
+        format: Option<OutputFormat>,
+    },
 
     /// Reads and prints out a message referenced by the specified CID from the
     /// chain block store
@@ -54,16 +64,40 @@ pub enum ChainCommands {
         #[arg(short, long, aliases = ["yes", "no-confirm"], short_alias = 'y')]
         force: bool,
     },
+
+    /// Runs the garbage collector of the node now, rather than at its next
+    /// scheduled run, and follows its progress
+    Prune {
+        /// Return once the run is requested
+        #[arg(long)]
+        no_wait: bool,
+    },
+
+    /// Writes the head of the chain to the database again, e.g. before a
+    /// reboot of the host, and prints its epoch
+    Flush,
 }
 
+/// How often `chain prune` polls the progress of the garbage collector.
+const PRUNE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
 impl ChainCommands {
-    pub async fn run(self, api: ApiInfo) -> anyhow::Result<()> {
+    pub async fn run(self, api: ApiInfo, output: OutputFormat) -> anyhow::Result<()> {
         match self {
             Self::Block { cid } => {
                 print_pretty_json(api.chain_get_block(cid).await?.into_lotus_json())
             }
             Self::Genesis => print_pretty_json(LotusJson(api.chain_get_genesis().await?)),
-            Self::Head => print_rpc_res_cids(api.chain_head().await?),
+            Self::Head { format } if format.unwrap_or(output) == OutputFormat::Text => {
+                print_rpc_res_cids(api.chain_head().await?)
+            }
+            Self::Head { .. } => {
+                let head = api.chain_head().await?;
+                print_pretty_json(serde_json::json!({
+                    "Cids": LotusJson(head.key().clone()),
+                    "Height": head.epoch(),
+                }))
+            }
             Self::Message { cid } => {
                 let bytes = api.chain_read_obj(cid).await?;
                 match fvm_ipld_encoding::from_slice::<ChainMessage>(&bytes)? {
@@ -83,9 +117,15 @@ impl ChainCommands {
                 epoch: Some(epoch),
                 force: no_confirm,
             } => {
-                maybe_confirm(no_confirm, SET_HEAD_CONFIRMATION_MESSAGE)?;
                 assert!(cids.is_empty(), "should be disallowed by clap");
                 let tipset = tipset_by_epoch_or_offset(&api, epoch).await?;
+                maybe_confirm(
+                    no_confirm,
+                    format!(
+                        "Setting the head to the tipset at epoch {}. {SET_HEAD_CONFIRMATION_MESSAGE}",
+                        tipset.epoch()
+                    ),
+                )?;
                 api.chain_set_head(tipset.key().clone()).await?;
                 Ok(())
             }
@@ -101,25 +141,74 @@ impl ChainCommands {
                 .await?;
                 Ok(())
             }
+            Self::Flush => {
+                let epoch = api.chain_flush().await?;
+                println!("Persisted head at epoch {epoch}");
+                Ok(())
+            }
+            Self::Prune { no_wait } => {
+                let runs = api.chain_prune_status().await?.runs;
+                api.chain_prune().await?;
+                println!("Garbage collection requested");
+                if no_wait {
+                    return Ok(());
+                }
+                let mut last_stage = None;
+                loop {
+                    let progress = api.chain_prune_status().await?;
+                    if progress.runs > runs {
+                        println!("Removed {} keys", progress.swept);
+                        return Ok(());
+                    }
+                    if last_stage != Some((progress.stage, progress.paused)) {
+                        last_stage = Some((progress.stage, progress.paused));
+                        println!("{}", describe_progress(&progress));
+                    }
+                    tokio::time::sleep(PRUNE_POLL_INTERVAL).await;
+                }
+            }
         }
     }
 }
 
+fn describe_progress(progress: &GcProgress) -> String {
+    if progress.paused {
+        return "Paused while the node catches up with the network".into();
+    }
+    match progress.stage {
+        GcStage::Idle => "Waiting for the garbage collector to start".into(),
+        GcStage::Marking => "Listing the keys of the database".into(),
+        GcStage::Waiting => format!(
+            "Marked {} keys at epoch {}, waiting for epoch {} to remove the unreachable ones",
+            progress.marked, progress.epoch_marked, progress.sweep_epoch
+        ),
+        GcStage::Filtering => format!("Finding the reachable keys among {}", progress.marked),
+        GcStage::Sweeping => "Removing the unreachable keys".into(),
+    }
+}
+
 /// If `epoch_or_offset` is negative, get the tipset that many blocks before the
 /// current head. Else treat `epoch_or_offset` as an epoch, and get that tipset.
-async fn tipset_by_epoch_or_offset(
-    api: &ApiInfo,
-    epoch_or_offset: i64,
-) -> Result<Tipset, JsonRpcError> {
+async fn tipset_by_epoch_or_offset(api: &ApiInfo, epoch_or_offset: i64) -> anyhow::Result<Tipset> {
     let current_head = api.chain_head().await?;
+    let target_epoch = target_epoch(current_head.epoch(), epoch_or_offset)?;
+    Ok(api
+        .chain_get_tipset_by_height(target_epoch, current_head.key().into())
+        .await?)
+}
 
+/// Resolves `epoch_or_offset` against the epoch of the current head, see
+/// [`tipset_by_epoch_or_offset`].
+fn target_epoch(head_epoch: ChainEpoch, epoch_or_offset: i64) -> anyhow::Result<ChainEpoch> {
     let target_epoch = match epoch_or_offset.is_negative() {
-        true => current_head.epoch() + epoch_or_offset, // adding negative number
+        true => head_epoch + epoch_or_offset, // adding negative number
         false => epoch_or_offset,
     };
-
-    api.chain_get_tipset_by_height(target_epoch, current_head.key().into())
-        .await
+    ensure!(
+        (0..=head_epoch).contains(&target_epoch),
+        "epoch {target_epoch} is not between genesis and the current head at epoch {head_epoch}"
+    );
+    Ok(target_epoch)
 }
 
 const SET_HEAD_CONFIRMATION_MESSAGE: &str =
@@ -139,3 +228,69 @@ fn maybe_confirm(no_confirm: bool, prompt: impl Into<String>) -> anyhow::Result<
         false => bail!("Operation cancelled by user"),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(subcommand)]
+        command: ChainCommands,
+    }
+
+    fn parse(args: &[&str]) -> Result<ChainCommands, clap::Error> {
+        Cli::try_parse_from(std::iter::once("chain").chain(args.iter().copied()))
+            .map(|cli| cli.command)
+    }
+
+    #[test]
+    fn set_head_arguments() {
+        assert!(matches!(
+            parse(&["set-head", "--epoch", "-10", "--force"]).unwrap(),
+            ChainCommands::SetHead {
+                epoch: Some(-10),
+                force: true,
+                ..
+            }
+        ));
+        assert!(matches!(
+            parse(&["set-head", "--epoch", "100"]).unwrap(),
+            ChainCommands::SetHead {
+                epoch: Some(100),
+                force: false,
+                ..
+            }
+        ));
+        let cid = Cid::default().to_string();
+        assert!(parse(&["set-head", &cid, "--epoch", "100"]).is_err());
+        assert!(parse(&["set-head"]).is_err());
+    }
+
+    #[test]
+    fn head_format() {
+        assert!(matches!(
+            parse(&["head"]).unwrap(),
+            ChainCommands::Head { format: None }
+        ));
+        assert!(matches!(
+            parse(&["head", "--format", "json"]).unwrap(),
+            ChainCommands::Head {
+                format: Some(OutputFormat::Json)
+            }
+        ));
+        assert!(parse(&["head", "--format", "yaml"]).is_err());
+    }
+
+    #[test]
+    fn epoch_resolution() {
+        assert_eq!(target_epoch(100, 40).unwrap(), 40);
+        assert_eq!(target_epoch(100, -40).unwrap(), 60);
+        assert_eq!(target_epoch(100, 100).unwrap(), 100);
+        assert_eq!(target_epoch(100, -100).unwrap(), 0);
+        assert!(target_epoch(100, 101).is_err());
+        assert!(target_epoch(100, -101).is_err());
+    }
+}
@@ -0,0 +1,197 @@
+// Copyright 2019-2024 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::future::Future;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::blocks::Tipset;
+use crate::chain_sync::{SyncStage, SyncState};
+use crate::rpc_client::{ApiInfo, JsonRpcError};
+use anyhow::{bail, Context as _};
+use clap::Subcommand;
+
+/// Probes meant for container liveness and readiness checks. They exit with 0
+/// if the node is healthy and 1 otherwise, with a single line saying why.
+#[derive(Debug, Subcommand)]
+pub enum HealthcheckCommand {
+    /// Succeeds if the node answers RPC calls
+    Live {
+        #[command(flatten)]
+        opts: HealthcheckOpts,
+    },
+    /// Succeeds if the node is live, done syncing and close to the head of the
+    /// network
+    Ready {
+        #[command(flatten)]
+        opts: HealthcheckOpts,
+        /// How many epochs the head may be behind the epoch expected from the
+        /// wall clock
+        #[arg(long, default_value_t = 5)]
+        max_lag_epochs: u64,
+    },
+}
+
+#[derive(Debug, clap::Args)]
+pub struct HealthcheckOpts {
+    /// How long, in seconds, each RPC call may take
+    #[arg(long, default_value_t = 5)]
+    timeout: u64,
+    /// Don't print the reason, only exit with the result
+    #[arg(short, long)]
+    quiet: bool,
+}
+
+impl HealthcheckCommand {
+    pub async fn run(self, api: ApiInfo) -> anyhow::Result<()> {
+        let (opts, result) = match self {
+            Self::Live { opts } => {
+                let result = live(&api, Duration::from_secs(opts.timeout)).await;
+                (opts, result)
+            }
+            Self::Ready {
+                opts,
+                max_lag_epochs,
+            } => {
+                let result = ready(&api, Duration::from_secs(opts.timeout), max_lag_epochs).await;
+                (opts, result)
+            }
+        };
+        match result {
+            Ok(reason) => {
+                if !opts.quiet {
+                    println!("ok: {reason}");
+                }
+                Ok(())
+            }
+            Err(reason) => {
+                if !opts.quiet {
+                    println!("unhealthy: {reason:#}");
+                }
+                std::process::exit(1)
+            }
+        }
+    }
+}
+
+/// Awaits a call, giving up after `timeout`.
+async fn timed<T>(
+    timeout: Duration,
+    call: impl Future<Output = Result<T, JsonRpcError>>,
+) -> anyhow::Result<T> {
+    Ok(tokio::time::timeout(timeout, call)
+        .await
+        .context("timed out")??)
+}
+
+async fn live(api: &ApiInfo, timeout: Duration) -> anyhow::Result<String> {
+    let version = timed(timeout, api.version())
+        .await
+        .context("RPC server not responding")?;
+    Ok(format!(
+        "RPC server responding, version {}",
+        version.version
+    ))
+}
+
+async fn ready(api: &ApiInfo, timeout: Duration, max_lag_epochs: u64) -> anyhow::Result<String> {
+    let version = timed(timeout, api.version())
+        .await
+        .context("RPC server not responding")?;
+    let sync = timed(timeout, api.sync_status())
+        .await
+        .context("failed to get the sync status")?;
+    let head = timed(timeout, api.chain_head())
+        .await
+        .context("failed to get the chain head")?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?;
+    check_ready(
+        sync.active_syncs.first(),
+        &head,
+        now,
+        version.block_delay,
+        max_lag_epochs,
+    )
+}
+
+/// Checks that the sync is complete, and that `head` is at most
+/// `max_lag_epochs` behind the epoch expected `now`.
+fn check_ready(
+    sync: &SyncState,
+    head: &Tipset,
+    now: Duration,
+    block_delay: u64,
+    max_lag_epochs: u64,
+) -> anyhow::Result<String> {
+    if sync.stage() != SyncStage::Complete {
+        match sync.message() {
+            Some(message) => bail!("sync stage is {}: {message}", sync.stage()),
+            None => bail!("sync stage is {}", sync.stage()),
+        }
+    }
+    let lag_secs = now.as_secs().saturating_sub(head.min_timestamp());
+    let lag_epochs = lag_secs / block_delay.max(1);
+    if lag_epochs > max_lag_epochs {
+        bail!(
+            "head at epoch {} is {lag_epochs} epochs behind the wall clock (max {max_lag_epochs})",
+            head.epoch()
+        );
+    }
+    Ok(format!(
+        "synced, head at epoch {} is {lag_epochs} epochs behind the wall clock",
+        head.epoch()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::RawBlockHeader;
+    use crate::shim::address::Address;
+
+    fn head(epoch: i64, timestamp: u64) -> Tipset {
+        Tipset::from(RawBlockHeader {
+            miner_address: Address::new_id(1000),
+            epoch,
+            timestamp,
+            ..Default::default()
+        })
+    }
+
+    fn sync_state(stage: SyncStage) -> SyncState {
+        let mut state = SyncState::default();
+        state.set_stage(stage);
+        state
+    }
+
+    #[test]
+    fn ready_when_synced_and_close_to_wall_clock() {
+        let head = head(100, 3000);
+        let now = Duration::from_secs(3000 + 4 * 30);
+        let sync = sync_state(SyncStage::Complete);
+        assert!(check_ready(&sync, &head, now, 30, 5).is_ok());
+        assert!(check_ready(&sync, &head, now, 30, 4).is_ok());
+        let err = check_ready(&sync, &head, now, 30, 3).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "head at epoch 100 is 4 epochs behind the wall clock (max 3)"
+        );
+        // A clock running behind the head is not lagging
+        assert!(check_ready(&sync, &head, Duration::from_secs(2000), 30, 0).is_ok());
+    }
+
+    #[test]
+    fn not_ready_while_syncing() {
+        let head = head(100, 3000);
+        let now = Duration::from_secs(3000);
+        for stage in [
+            SyncStage::Idle,
+            SyncStage::Headers,
+            SyncStage::PersistHeaders,
+            SyncStage::Messages,
+            SyncStage::Error,
+        ] {
+            let err = check_ready(&sync_state(stage), &head, now, 30, 5).unwrap_err();
+            assert_eq!(err.to_string(), format!("sync stage is {stage}"));
+        }
+    }
+}
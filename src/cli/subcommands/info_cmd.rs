@@ -1,11 +1,18 @@
 // Copyright 2019-2024 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use std::collections::BTreeMap;
+use std::future::Future;
+
 use crate::blocks::Tipset;
-use crate::rpc_client::ApiInfo;
+use crate::cli_shared::cli::OutputFormat;
+use crate::rpc_client::{ApiInfo, JsonRpcError};
 use crate::shim::econ::TokenAmount;
+use anyhow::Context as _;
 use chrono::{DateTime, Utc};
 use clap::Subcommand;
+use colored::Colorize as _;
+use serde::Serialize;
 
 use crate::shim::clock::{ChainEpoch, BLOCKS_PER_EPOCH, EPOCH_DURATION_SECONDS};
 use humantime::format_duration;
@@ -14,12 +21,19 @@ use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use crate::cli::humantoken::TokenAmountPretty;
 
+/// How long each of the calls the report is made of may take, so that a node
+/// hanging on one of them still gets a report.
+const CALL_TIMEOUT: Duration = Duration::from_secs(10);
+
 #[derive(Debug, Subcommand)]
 pub enum InfoCommand {
+    /// Summarize the health of the node, as a compact, colored summary, or
+    /// every field of it with `--output json`
     Show,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
 pub struct NodeStatusInfo {
     /// How far behind the node is with respect to syncing to head in seconds
     pub lag: i64,
@@ -27,20 +41,31 @@ pub struct NodeStatusInfo {
     /// an average of 5 blocks per tipset in the last couple of
     /// hours. The number of blocks per tipset is non-deterministic
     /// but averaging at 5 is considered healthy.
-    pub health: f64,
+    pub health: Option<f64>,
     /// epoch the node is currently at
     pub epoch: ChainEpoch,
+    /// Epoch the head is expected at by now, given the genesis timestamp and
+    /// the block delay of the network
+    pub expected_epoch: Option<ChainEpoch>,
     /// Base fee is the set price per unit of gas (measured in attoFIL/gas unit) to be burned (sent to an unrecoverable address) for every message execution
+    #[serde(with = "crate::lotus_json")]
     pub base_fee: TokenAmount,
     pub sync_status: SyncStatus,
+    /// Stage of the current sync, e.g. `message sync`
+    pub sync_stage: Option<String>,
     /// Start time of the node
-    pub start_time: DateTime<Utc>,
-    pub network: String,
+    pub start_time: Option<DateTime<Utc>>,
+    pub network: Option<String>,
+    pub peers: Option<usize>,
+    /// Number of messages waiting in the message pool
+    pub pending_messages: Option<usize>,
     pub default_wallet_address: Option<String>,
     pub default_wallet_address_balance: Option<String>,
+    /// Why the fields left empty could not be retrieved, by field
+    pub errors: BTreeMap<&'static str, String>,
 }
 
-#[derive(Debug, strum::Display, PartialEq)]
+#[derive(Debug, strum::Display, PartialEq, Serialize)]
 pub enum SyncStatus {
     Ok,
     Slow,
@@ -49,15 +74,9 @@ pub enum SyncStatus {
 }
 
 impl NodeStatusInfo {
-    pub fn new(
-        cur_duration: Duration,
-        blocks_per_tipset_last_finality: f64,
-        head: &Tipset,
-        start_time: DateTime<Utc>,
-        network: String,
-        default_wallet_address: Option<String>,
-        default_wallet_address_balance: Option<String>,
-    ) -> NodeStatusInfo {
+    /// The status of a node at `head`, to be completed with the results of
+    /// further calls.
+    pub fn new(cur_duration: Duration, head: &Tipset) -> NodeStatusInfo {
         let ts = head.min_timestamp() as i64;
         let cur_duration_secs = cur_duration.as_secs() as i64;
         let lag = cur_duration_secs - ts;
@@ -76,35 +95,70 @@ impl NodeStatusInfo {
 
         let base_fee = head.min_ticket_block().parent_base_fee.clone();
 
-        // blocks_per_tipset_last_finality = no of blocks till head / chain finality
-        let health = 100. * blocks_per_tipset_last_finality / BLOCKS_PER_EPOCH as f64;
-
         Self {
             lag,
-            health,
+            health: None,
             epoch: head.epoch(),
+            expected_epoch: None,
             base_fee,
             sync_status,
-            start_time,
-            network,
-            default_wallet_address,
-            default_wallet_address_balance,
+            sync_stage: None,
+            start_time: None,
+            network: None,
+            peers: None,
+            pending_messages: None,
+            default_wallet_address: None,
+            default_wallet_address_balance: None,
+            errors: BTreeMap::new(),
+        }
+    }
+
+    /// Keeps the value of a call, or why it failed under the name of the field
+    /// it was meant for.
+    fn field<T>(&mut self, name: &'static str, result: anyhow::Result<T>) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(e) => {
+                self.errors.insert(name, format!("{e:#}"));
+                None
+            }
+        }
+    }
+
+    /// How many epochs the head is behind the expected epoch, if known.
+    pub fn epoch_lag(&self) -> Option<ChainEpoch> {
+        self.expected_epoch
+            .map(|expected_epoch| expected_epoch - self.epoch)
+    }
+
+    /// The value of a field, or why it is missing.
+    fn or_unavailable(&self, name: &str, value: Option<String>) -> String {
+        match (value, self.errors.get(name)) {
+            (Some(value), _) => value,
+            (None, Some(e)) => format!("unavailable ({e})").red().to_string(),
+            (None, None) => "unavailable".red().to_string(),
         }
     }
 
     fn format(&self, now: DateTime<Utc>) -> String {
-        let network = format!("Network: {}", self.network);
+        let network = format!(
+            "Network: {}",
+            self.or_unavailable("Network", self.network.clone())
+        );
 
         let uptime = {
-            let uptime = (now - self.start_time)
-                .to_std()
-                .expect("failed converting to std duration");
-            let uptime = Duration::from_secs(uptime.as_secs());
-            let fmt_uptime = format_duration(uptime);
-            format!(
-                "Uptime: {fmt_uptime} (Started at: {})",
-                self.start_time.with_timezone(&chrono::offset::Local)
-            )
+            let uptime = self.start_time.map(|start_time| {
+                let uptime = (now - start_time)
+                    .to_std()
+                    .expect("failed converting to std duration");
+                let uptime = Duration::from_secs(uptime.as_secs());
+                let fmt_uptime = format_duration(uptime);
+                format!(
+                    "{fmt_uptime} (Started at: {})",
+                    start_time.with_timezone(&chrono::offset::Local)
+                )
+            });
+            format!("Uptime: {}", self.or_unavailable("StartTime", uptime))
         };
 
         let chain = {
@@ -115,20 +169,58 @@ impl NodeStatusInfo {
             } else {
                 format!("{} behind", lag_time)
             };
+            let sync_status = match self.sync_status {
+                SyncStatus::Ok | SyncStatus::Fast => self.sync_status.to_string().green(),
+                SyncStatus::Slow => self.sync_status.to_string().yellow(),
+                SyncStatus::Behind => self.sync_status.to_string().red(),
+            };
+
+            format!(
+                "Chain: [sync: {sync_status}! ({behind})] [basefee: {base_fee_fmt}] [epoch: {}]",
+                self.epoch
+            )
+        };
 
+        let sync = {
+            let lag = self.epoch_lag().map(|lag| {
+                format!(
+                    "{lag} epochs (expected epoch: {})",
+                    self.expected_epoch.unwrap_or_default()
+                )
+            });
             format!(
-                "Chain: [sync: {}! ({})] [basefee: {base_fee_fmt}] [epoch: {}]",
-                self.sync_status, behind, self.epoch
+                "Sync: [stage: {}] [lag: {}]",
+                self.or_unavailable("SyncStage", self.sync_stage.clone()),
+                self.or_unavailable("ExpectedEpoch", lag)
             )
         };
 
-        let chain_health = format!("Chain health: {:.2}%\n\n", self.health);
+        let peers = format!(
+            "Peers: {}",
+            self.or_unavailable("Peers", self.peers.map(|peers| peers.to_string()))
+        );
+
+        let pending_messages = format!(
+            "Pending messages: {}",
+            self.or_unavailable(
+                "PendingMessages",
+                self.pending_messages.map(|count| count.to_string())
+            )
+        );
+
+        let chain_health = format!(
+            "Chain health: {}\n\n",
+            self.or_unavailable("Health", self.health.map(|health| format!("{health:.2}%")))
+        );
 
         let wallet_info = {
-            let wallet_address = self
-                .default_wallet_address
-                .clone()
-                .unwrap_or("address not set".to_string());
+            let wallet_address = match self.errors.contains_key("DefaultWalletAddress") {
+                true => self.or_unavailable("DefaultWalletAddress", None),
+                false => self
+                    .default_wallet_address
+                    .clone()
+                    .unwrap_or("address not set".to_string()),
+            };
 
             let wallet_balance = match self
                 .default_wallet_address_balance
@@ -149,42 +241,105 @@ impl NodeStatusInfo {
             )
         };
 
-        [network, uptime, chain, chain_health, wallet_info].join("\n")
+        [
+            network,
+            uptime,
+            chain,
+            sync,
+            peers,
+            pending_messages,
+            chain_health,
+            wallet_info,
+        ]
+        .join("\n")
     }
 }
 
-impl InfoCommand {
-    pub async fn run(self, api: ApiInfo) -> anyhow::Result<()> {
-        let (node_status, head, network, start_time, default_wallet_address) = tokio::try_join!(
-            api.node_status(),
-            api.chain_head(),
-            api.state_network_name(),
-            api.start_time(),
-            api.wallet_default_address(),
-        )?;
+/// Epoch the head of a chain is expected at by `now`, given the timestamp of
+/// its genesis and its block delay in seconds.
+pub fn expected_epoch(now: Duration, genesis_timestamp: u64, block_delay: u64) -> ChainEpoch {
+    (now.as_secs().saturating_sub(genesis_timestamp) / block_delay.max(1)) as ChainEpoch
+}
 
-        let cur_duration: Duration = SystemTime::now().duration_since(UNIX_EPOCH)?;
-        let blocks_per_tipset_last_finality =
-            node_status.chain_status.blocks_per_tipset_last_finality;
+/// Awaits a call, giving up after [`CALL_TIMEOUT`].
+async fn timed<T>(call: impl Future<Output = Result<T, JsonRpcError>>) -> anyhow::Result<T> {
+    Ok(tokio::time::timeout(CALL_TIMEOUT, call)
+        .await
+        .context("timed out")??)
+}
 
-        let default_wallet_address_balance = if let Some(def_addr) = &default_wallet_address {
-            let balance = api.wallet_balance(def_addr.clone()).await?;
-            Some(balance)
-        } else {
-            None
+impl InfoCommand {
+    pub async fn run(self, api: ApiInfo, output: OutputFormat) -> anyhow::Result<()> {
+        let Self::Show = self;
+
+        let wallet = async {
+            let address = timed(api.wallet_default_address()).await?;
+            let balance = match &address {
+                Some(address) => Some(timed(api.wallet_balance(address.clone())).await?),
+                None => None,
+            };
+            anyhow::Ok((address, balance))
         };
-
-        let node_status_info = NodeStatusInfo::new(
-            cur_duration,
-            blocks_per_tipset_last_finality,
-            &head,
-            start_time,
+        let (
+            head,
+            node_status,
             network,
-            default_wallet_address.clone(),
-            default_wallet_address_balance,
+            start_time,
+            sync,
+            peers,
+            pending,
+            genesis,
+            version,
+            wallet,
+        ) = tokio::join!(
+            timed(api.chain_head()),
+            timed(api.node_status()),
+            timed(api.state_network_name()),
+            timed(api.start_time()),
+            timed(api.sync_status()),
+            timed(api.net_peers()),
+            timed(api.mpool_pending(vec![])),
+            timed(api.chain_get_genesis()),
+            timed(api.version()),
+            wallet,
         );
 
-        println!("{}", node_status_info.format(Utc::now()));
+        let cur_duration: Duration = SystemTime::now().duration_since(UNIX_EPOCH)?;
+        let head = head.context("could not get the head of the chain")?;
+        let mut info = NodeStatusInfo::new(cur_duration, &head);
+
+        // blocks_per_tipset_last_finality = no of blocks till head / chain finality
+        info.health = info.field("Health", node_status).map(|node_status| {
+            100. * node_status.chain_status.blocks_per_tipset_last_finality
+                / BLOCKS_PER_EPOCH as f64
+        });
+        info.network = info.field("Network", network);
+        info.start_time = info.field("StartTime", start_time);
+        info.sync_stage = info
+            .field("SyncStage", sync)
+            .map(|sync| sync.active_syncs.first().stage().to_string());
+        info.peers = info.field("Peers", peers).map(|peers| peers.len());
+        info.pending_messages = info
+            .field("PendingMessages", pending)
+            .map(|pending| pending.len());
+        let genesis = genesis.and_then(|genesis| genesis.context("the node has no genesis"));
+        info.expected_epoch = match (genesis, version) {
+            (Ok(genesis), Ok(version)) => Some(expected_epoch(
+                cur_duration,
+                genesis.min_timestamp(),
+                version.block_delay,
+            )),
+            (Err(e), _) | (_, Err(e)) => info.field("ExpectedEpoch", Err(e)),
+        };
+        if let Some((address, balance)) = info.field("DefaultWalletAddress", wallet) {
+            info.default_wallet_address = address;
+            info.default_wallet_address_balance = balance;
+        }
+
+        match output {
+            OutputFormat::Text => println!("{}", info.format(Utc::now())),
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&info)?),
+        }
 
         Ok(())
     }
@@ -205,7 +360,7 @@ mod tests {
     use quickcheck_macros::quickcheck;
     use std::{str::FromStr, sync::Arc, time::Duration};
 
-    use super::{NodeStatusInfo, SyncStatus};
+    use super::*;
 
     fn mock_tipset_at(seconds_since_unix_epoch: u64) -> Arc<Tipset> {
         let mock_header = CachingBlockHeader::new(RawBlockHeader {
@@ -221,27 +376,34 @@ mod tests {
     fn mock_node_status() -> NodeStatusInfo {
         NodeStatusInfo {
             lag: 0,
-            health: 90.,
+            health: Some(90.),
             epoch: i64::MAX,
+            expected_epoch: None,
             base_fee: TokenAmount::from_whole(1),
             sync_status: SyncStatus::Ok,
-            start_time: DateTime::<chrono::Utc>::MIN_UTC,
-            network: "calibnet".to_string(),
+            sync_stage: None,
+            start_time: Some(DateTime::<chrono::Utc>::MIN_UTC),
+            network: Some("calibnet".to_string()),
+            peers: None,
+            pending_messages: None,
             default_wallet_address: Some("-".to_string()),
             default_wallet_address_balance: None,
+            errors: BTreeMap::new(),
         }
     }
 
     fn node_status(duration: Duration, tipset: &Tipset) -> NodeStatusInfo {
-        NodeStatusInfo::new(
-            duration,
-            20.,
-            tipset,
-            DateTime::<chrono::Utc>::MIN_UTC,
-            "calibnet".to_string(),
-            None,
-            None,
-        )
+        NodeStatusInfo {
+            health: Some(100. * 20. / BLOCKS_PER_EPOCH as f64),
+            start_time: Some(DateTime::<chrono::Utc>::MIN_UTC),
+            network: Some("calibnet".to_string()),
+            ..NodeStatusInfo::new(duration, tipset)
+        }
+    }
+
+    fn format(status: &NodeStatusInfo) -> String {
+        colored::control::set_override(false);
+        status.format(DateTime::<chrono::Utc>::MIN_UTC)
     }
 
     #[quickcheck]
@@ -260,7 +422,7 @@ mod tests {
         let tipset = mock_tipset_at(duration.as_secs().saturating_sub(200));
         let status = node_status(duration, tipset.as_ref());
 
-        assert!(status.health.is_finite());
+        assert!(status.health.unwrap().is_finite());
         assert_ne!(status.sync_status, SyncStatus::Ok);
         assert_ne!(status.sync_status, SyncStatus::Slow);
     }
@@ -274,7 +436,7 @@ mod tests {
                 .saturating_sub(EPOCH_DURATION_SECONDS as u64 * 4),
         );
         let status = node_status(duration, tipset.as_ref());
-        assert!(status.health.is_finite());
+        assert!(status.health.unwrap().is_finite());
         assert_ne!(status.sync_status, SyncStatus::Behind);
         assert_ne!(status.sync_status, SyncStatus::Ok);
     }
@@ -285,18 +447,14 @@ mod tests {
         let tipset = mock_tipset_at(duration.as_secs() - 10);
         let status = node_status(duration, tipset.as_ref());
 
-        assert!(status
-            .format(DateTime::<chrono::Utc>::MIN_UTC)
-            .contains("10s behind"));
+        assert!(format(&status).contains("10s behind"));
     }
 
     #[test]
     fn test_lag_uptime_ahead() {
         let mut status = mock_node_status();
         status.lag = -360;
-        assert!(status
-            .format(DateTime::<chrono::Utc>::MIN_UTC)
-            .contains("6m ahead"));
+        assert!(format(&status).contains("6m ahead"));
     }
 
     #[test]
@@ -306,17 +464,80 @@ mod tests {
         let status = node_status(duration, tipset.as_ref());
         let expected_status_fmt =
             "[sync: Slow! (59s behind)] [basefee: 0 FIL] [epoch: 0]".to_string();
-        assert!(status
-            .format(DateTime::<chrono::Utc>::MIN_UTC)
-            .contains(&expected_status_fmt));
+        assert!(format(&status).contains(&expected_status_fmt));
 
         let tipset = mock_tipset_at(duration.as_secs() - 30000);
         let status = node_status(duration, tipset.as_ref());
 
         let expected_status_fmt =
             "[sync: Behind! (8h 20m behind)] [basefee: 0 FIL] [epoch: 0]".to_string();
-        assert!(status
-            .format(DateTime::<chrono::Utc>::MIN_UTC)
-            .contains(&expected_status_fmt));
+        assert!(format(&status).contains(&expected_status_fmt));
+    }
+
+    #[test]
+    fn epoch_lag() {
+        let genesis = 1_000_000;
+        for (block_delay, elapsed, expected) in [
+            (30, 0, 0),
+            (30, 29, 0),
+            (30, 30, 1),
+            (30, 3000, 100),
+            (4, 3000, 750),
+            (1, 3000, 3000),
+            // A zero block delay is not divided by
+            (0, 3000, 3000),
+        ] {
+            let now = Duration::from_secs(genesis + elapsed);
+            assert_eq!(
+                expected_epoch(now, genesis, block_delay),
+                expected,
+                "block delay {block_delay}, {elapsed}s after genesis"
+            );
+        }
+        // Clocks behind the genesis expect it
+        assert_eq!(expected_epoch(Duration::from_secs(10), genesis, 30), 0);
+
+        let mut status = node_status(Duration::from_secs(genesis + 3000), &mock_tipset_at(0));
+        status.epoch = 90;
+        status.expected_epoch = Some(expected_epoch(
+            Duration::from_secs(genesis + 3000),
+            genesis,
+            30,
+        ));
+        assert_eq!(status.epoch_lag(), Some(10));
+        assert!(format(&status).contains("[lag: 10 epochs (expected epoch: 100)]"));
+    }
+
+    #[test]
+    fn failed_calls_are_reported() {
+        let mut status = node_status(Duration::from_secs(60), &mock_tipset_at(50));
+        status.peers = status.field("Peers", Ok(3));
+        status.pending_messages = status.field(
+            "PendingMessages",
+            Err(anyhow::anyhow!("connection refused")),
+        );
+        status.sync_stage = status.field("SyncStage", Err(anyhow::anyhow!("timed out")));
+
+        let text = format(&status);
+        assert!(text.contains("Peers: 3"), "{text}");
+        assert!(
+            text.contains("Pending messages: unavailable (connection refused)"),
+            "{text}"
+        );
+        assert!(text.contains("[stage: unavailable (timed out)]"), "{text}");
+        // The rest of the report is unaffected
+        assert!(text.contains("10s behind"), "{text}");
+
+        let json = serde_json::to_value(&status).unwrap();
+        assert_eq!(json["Peers"], 3);
+        assert!(json["PendingMessages"].is_null());
+        assert_eq!(json["Errors"]["PendingMessages"], "connection refused");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn hung_calls_time_out() {
+        let hung = std::future::pending::<Result<(), JsonRpcError>>();
+        let e = timed(hung).await.unwrap_err();
+        assert_eq!(e.to_string(), "timed out");
     }
 }
@@ -10,6 +10,7 @@ mod attach_cmd;
 mod auth_cmd;
 mod chain_cmd;
 mod config_cmd;
+mod healthcheck_cmd;
 mod info_cmd;
 mod mpool_cmd;
 mod net_cmd;
@@ -23,7 +24,7 @@ use std::io::Write;
 
 use crate::blocks::Tipset;
 pub(crate) use crate::cli_shared::cli::Config;
-use crate::cli_shared::cli::HELP_MESSAGE;
+use crate::cli_shared::cli::{OutputFormat, HELP_MESSAGE};
 use crate::utils::version::FOREST_VERSION_STRING;
 use clap::Parser;
 use serde::Serialize;
@@ -31,9 +32,9 @@ use tracing::error;
 
 pub(super) use self::{
     attach_cmd::AttachCommand, auth_cmd::AuthCommands, chain_cmd::ChainCommands,
-    config_cmd::ConfigCommands, mpool_cmd::MpoolCommands, net_cmd::NetCommands,
-    send_cmd::SendCommand, shutdown_cmd::ShutdownCommand, snapshot_cmd::SnapshotCommands,
-    state_cmd::StateCommands, sync_cmd::SyncCommands,
+    config_cmd::ConfigCommands, healthcheck_cmd::HealthcheckCommand, mpool_cmd::MpoolCommands,
+    net_cmd::NetCommands, send_cmd::SendCommand, shutdown_cmd::ShutdownCommand,
+    snapshot_cmd::SnapshotCommands, state_cmd::StateCommands, sync_cmd::SyncCommands,
 };
 use crate::cli::subcommands::info_cmd::InfoCommand;
 
@@ -45,6 +46,9 @@ pub struct Cli {
     /// Client JWT token to use for JSON-RPC authentication
     #[arg(short, long)]
     pub token: Option<String>,
+    /// Output format of the commands that support it
+    #[arg(long, global = true, value_enum, default_value_t)]
+    pub output: OutputFormat,
     #[command(subcommand)]
     pub cmd: Subcommand,
 }
@@ -96,6 +100,10 @@ pub enum Subcommand {
 
     /// Shutdown Forest
     Shutdown(ShutdownCommand),
+
+    /// Probe the liveness or readiness of the node
+    #[command(subcommand)]
+    Healthcheck(HealthcheckCommand),
 }
 
 /// Format a vector to a prettified string
@@ -4,6 +4,8 @@
 use std::str::FromStr;
 
 use crate::blocks::Tipset;
+use crate::cli_shared::cli::OutputFormat;
+use crate::lotus_json::LotusJson;
 use crate::message::SignedMessage;
 use crate::rpc_client::ApiInfo;
 use crate::shim::address::StrictAddress;
@@ -16,7 +18,8 @@ use num::BigInt;
 
 #[derive(Debug, Subcommand)]
 pub enum MpoolCommands {
-    /// Get pending messages
+    /// Get pending messages. With `--output json`, prints a single array of
+    /// the messages, or of their CIDs
     Pending {
         /// Print pending messages for addresses in local wallet only
         #[arg(long)]
@@ -108,6 +111,9 @@ struct MpStat {
     below_current: u64,
     below_past: u64,
     gas_limit: BigInt,
+    /// Lowest pending sequence is above the on-chain sequence, so none of the
+    /// pending messages from this sender can be included until the gap is filled.
+    gapped: bool,
 }
 
 fn compute_stats(
@@ -136,6 +142,10 @@ fn compute_stats(
 
         let mut stat = MpStat {
             address: address.to_string(),
+            gapped: bucket
+                .keys()
+                .min()
+                .is_some_and(|lowest| *lowest > actor_sequence),
             ..Default::default()
         };
 
@@ -167,6 +177,7 @@ fn compute_stats(
 
 fn print_stats(stats: &[MpStat], basefee_lookback: u32) {
     let mut total = MpStat::default();
+    let mut gapped = 0;
 
     for stat in stats {
         total.past += stat.past;
@@ -175,9 +186,12 @@ fn print_stats(stats: &[MpStat], basefee_lookback: u32) {
         total.below_current += stat.below_current;
         total.below_past += stat.below_past;
         total.gas_limit += &stat.gas_limit;
+        if stat.gapped {
+            gapped += 1;
+        }
 
         println!(
-            "{}: Nonce past: {}, cur: {}, future: {}; FeeCap cur: {}, min-{}: {}, gasLimit: {}",
+            "{}: Nonce past: {}, cur: {}, future: {}; FeeCap cur: {}, min-{}: {}, gasLimit: {}{}",
             stat.address,
             stat.past,
             stat.current,
@@ -185,7 +199,8 @@ fn print_stats(stats: &[MpStat], basefee_lookback: u32) {
             stat.below_current,
             basefee_lookback,
             stat.below_past,
-            stat.gas_limit
+            stat.gas_limit,
+            if stat.gapped { " (gapped)" } else { "" }
         );
     }
 
@@ -200,10 +215,13 @@ fn print_stats(stats: &[MpStat], basefee_lookback: u32) {
         total.below_past,
         total.gas_limit
     );
+    if gapped > 0 {
+        println!("gapped senders: {gapped}");
+    }
 }
 
 impl MpoolCommands {
-    pub async fn run(self, api: ApiInfo) -> anyhow::Result<()> {
+    pub async fn run(self, api: ApiInfo, output: OutputFormat) -> anyhow::Result<()> {
         match self {
             Self::Pending {
                 local,
@@ -222,14 +240,25 @@ impl MpoolCommands {
 
                 let filtered_messages = filter_messages(messages, local_addrs, &to, &from)?;
 
+                if output == OutputFormat::Json {
+                    let json = if cids {
+                        let cids = filtered_messages
+                            .iter()
+                            .map(|msg| msg.cid())
+                            .collect::<Result<Vec<_>, _>>()?;
+                        serde_json::to_string_pretty(&LotusJson(cids))?
+                    } else {
+                        serde_json::to_string_pretty(&LotusJson(filtered_messages))?
+                    };
+                    println!("{json}");
+                    return Ok(());
+                }
+
                 for msg in filtered_messages {
                     if cids {
                         println!("{}", msg.cid().unwrap());
                     } else {
-                        println!(
-                            "{}",
-                            serde_json::to_string_pretty(&crate::lotus_json::LotusJson(msg))?
-                        );
+                        println!("{}", serde_json::to_string_pretty(&LotusJson(msg))?);
                     }
                 }
 
@@ -480,6 +509,7 @@ mod tests {
                 below_current: 0,
                 below_past: 0,
                 gas_limit: 25201703.into(),
+                gapped: false,
             },
             MpStat {
                 address: addr1.to_string(),
@@ -489,9 +519,45 @@ mod tests {
                 below_current: 0,
                 below_past: 0,
                 gas_limit: 133944296.into(),
+                gapped: false,
             },
         ];
 
         assert_eq!(stats, expected);
     }
+
+    #[test]
+    fn compute_statistics_gapped() {
+        use crate::shim::message::Message;
+
+        let addr = Address::new_id(1000);
+        let messages = [12, 13].map(|sequence| Message {
+            from: addr,
+            sequence,
+            gas_limit: 1000,
+            ..Default::default()
+        });
+        let actor_sequences = HashMap::from_iter([(addr, 10)]);
+
+        let stats = compute_stats(
+            &messages,
+            actor_sequences,
+            TokenAmount::default(),
+            TokenAmount::default(),
+        );
+
+        assert_eq!(
+            stats,
+            vec![MpStat {
+                address: addr.to_string(),
+                past: 0,
+                current: 0,
+                future: 2,
+                below_current: 0,
+                below_past: 0,
+                gas_limit: 2000.into(),
+                gapped: true,
+            }]
+        );
+    }
 }
@@ -1,6 +1,7 @@
 // Copyright 2019-2024 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use crate::cli_shared::cli::OutputFormat;
 use crate::libp2p::{Multiaddr, Protocol};
 use crate::rpc_api::data_types::AddrInfo;
 use crate::rpc_client::ApiInfo;
@@ -8,6 +9,8 @@ use ahash::{HashMap, HashSet};
 use cid::multibase;
 use clap::Subcommand;
 use itertools::Itertools;
+use serde::Serialize;
+use tabled::{builder::Builder, settings::Style};
 
 use crate::cli::subcommands::cli_error_and_die;
 
@@ -17,7 +20,8 @@ pub enum NetCommands {
     Listen,
     /// Lists `libp2p` swarm network info
     Info,
-    /// Lists `libp2p` swarm peers
+    /// Lists `libp2p` swarm peers. With `--output json`, prints the peers
+    /// as returned by `Filecoin.NetPeers`, with their `Agent` if requested
     Peers {
         /// Print agent name
         #[arg(short, long)]
@@ -30,15 +34,48 @@ pub enum NetCommands {
     },
     /// Disconnects from a peer by it's peer ID
     Disconnect {
-        /// Peer ID to disconnect from
+        /// Peer ID to disconnect from, or `all` to drop every connection
         id: String,
     },
     /// Print information about reachability from the internet
     Reachability,
+    /// Lists gossipsub peer scores, highest first
+    Scores,
+    /// Manages the bootstrap peers
+    Bootstrap {
+        #[command(subcommand)]
+        command: NetBootstrapCommands,
+    },
+}
+
+/// A peer as printed by `net peers` in JSON mode.
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct Peer {
+    #[serde(flatten)]
+    info: AddrInfo,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    agent: Option<String>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum NetBootstrapCommands {
+    /// Lists the bootstrap peers
+    List,
+    /// Adds a bootstrap peer, kept across restarts
+    Add {
+        /// Multi-address (with `/p2p/` protocol)
+        address: String,
+        /// Connect to the peer right away
+        #[arg(long)]
+        dial: bool,
+    },
+    /// Connects to all bootstrap peers
+    Dial,
 }
 
 impl NetCommands {
-    pub async fn run(self, api: ApiInfo) -> anyhow::Result<()> {
+    pub async fn run(self, api: ApiInfo, output: OutputFormat) -> anyhow::Result<()> {
         match self {
             Self::Listen => {
                 let info = api.net_addrs_listen().await?;
@@ -83,7 +120,19 @@ impl NetCommands {
                     HashMap::default()
                 };
 
-                let output: Vec<String> = addrs
+                if output == OutputFormat::Json {
+                    let peers = addrs
+                        .into_iter()
+                        .map(|info| Peer {
+                            agent: peer_to_agents.get(&info.id).cloned(),
+                            info,
+                        })
+                        .collect_vec();
+                    println!("{}", serde_json::to_string_pretty(&peers)?);
+                    return Ok(());
+                }
+
+                let lines: Vec<String> = addrs
                     .into_iter()
                     .filter_map(|info| {
                         let addresses: Vec<String> = info
@@ -119,36 +168,12 @@ impl NetCommands {
                         }
                     })
                     .collect();
-                println!("{}", output.join("\n"));
+                println!("{}", lines.join("\n"));
                 Ok(())
             }
             Self::Connect { address } => {
-                let addr: Multiaddr = address
-                    .parse()
-                    .map_err(|e| {
-                        cli_error_and_die(format!("Error parsing multiaddr. Error was: {e}"), 1);
-                    })
-                    .expect("Parse provided multiaddr from string");
-
-                let mut id = "".to_owned();
-
-                for protocol in addr.iter() {
-                    if let Protocol::P2p(p2p) = protocol {
-                        id = multibase::encode(multibase::Base::Base58Btc, p2p.to_bytes());
-                        id = id.split_off(1);
-                    }
-                }
-
-                if id.is_empty() {
-                    cli_error_and_die("Needs a /p2p/ protocol present in multiaddr", 1)
-                }
-
-                let addrs = HashSet::from_iter([addr]);
-                let addr_info = AddrInfo {
-                    id: id.clone(),
-                    addrs,
-                };
-
+                let addr_info = addr_info(&address);
+                let id = addr_info.id.clone();
                 api.net_connect(addr_info).await?;
                 println!("connect {id}: success");
                 Ok(())
@@ -170,6 +195,114 @@ impl NetCommands {
                 }
                 Ok(())
             }
+            Self::Scores => {
+                let mut scores = api.net_pubsub_scores().await?;
+                scores.sort_by(|a, b| b.score.score.total_cmp(&a.score.score));
+
+                let mut builder = Builder::default();
+                builder.push_record([
+                    "Peer",
+                    "Score",
+                    "Topic",
+                    "Time in mesh",
+                    "First deliveries",
+                    "Mesh deliveries",
+                    "Invalid deliveries",
+                ]);
+                for score in scores {
+                    let topics = score
+                        .score
+                        .topics
+                        .into_iter()
+                        .sorted_by(|a, b| a.0.cmp(&b.0))
+                        .collect_vec();
+                    if topics.is_empty() {
+                        builder.push_record([
+                            score.id.clone(),
+                            format!("{:.2}", score.score.score),
+                            String::new(),
+                            String::new(),
+                            String::new(),
+                            String::new(),
+                            String::new(),
+                        ]);
+                    }
+                    for (topic, stats) in topics {
+                        builder.push_record([
+                            score.id.clone(),
+                            format!("{:.2}", score.score.score),
+                            topic,
+                            humantime::format_duration(std::time::Duration::from_secs(
+                                (stats.time_in_mesh / 1_000_000_000).max(0) as u64,
+                            ))
+                            .to_string(),
+                            stats.first_message_deliveries.to_string(),
+                            stats.mesh_message_deliveries.to_string(),
+                            stats.invalid_message_deliveries.to_string(),
+                        ]);
+                    }
+                }
+                println!("{}", builder.build().with(Style::rounded()));
+                Ok(())
+            }
+            Self::Bootstrap { command } => command.run(api).await,
         }
     }
 }
+
+impl NetBootstrapCommands {
+    pub async fn run(self, api: ApiInfo) -> anyhow::Result<()> {
+        match self {
+            Self::List => {
+                println!("{}", api.net_bootstrap_list().await?.join("\n"));
+                Ok(())
+            }
+            Self::Add { address, dial } => {
+                let addr_info = addr_info(&address);
+                api.net_bootstrap_add(address).await?;
+                if dial {
+                    let id = addr_info.id.clone();
+                    api.net_connect(addr_info).await?;
+                    println!("connect {id}: success");
+                }
+                Ok(())
+            }
+            Self::Dial => {
+                let result = api.net_bootstrap().await?;
+                println!(
+                    "connected to {} of {} bootstrap peers",
+                    result.succeeded, result.attempted
+                );
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Parses a multi-address with a `/p2p/` protocol into the peer it points to.
+fn addr_info(address: &str) -> AddrInfo {
+    let addr: Multiaddr = address
+        .parse()
+        .map_err(|e| {
+            cli_error_and_die(format!("Error parsing multiaddr. Error was: {e}"), 1);
+        })
+        .expect("Parse provided multiaddr from string");
+
+    let mut id = "".to_owned();
+
+    for protocol in addr.iter() {
+        if let Protocol::P2p(p2p) = protocol {
+            id = multibase::encode(multibase::Base::Base58Btc, p2p.to_bytes());
+            id = id.split_off(1);
+        }
+    }
+
+    if id.is_empty() {
+        cli_error_and_die("Needs a /p2p/ protocol present in multiaddr", 1)
+    }
+
+    AddrInfo {
+        id,
+        addrs: HashSet::from_iter([addr]),
+    }
+}
@@ -3,11 +3,16 @@
 
 use std::str::FromStr as _;
 
+use crate::cli::humantoken::TokenAmountPretty as _;
+use crate::ipld::Ipld;
+use crate::lotus_json::HasLotusJson as _;
+use crate::message::Message as _;
 use crate::rpc_client::ApiInfo;
 use crate::shim::address::{Address, StrictAddress};
 use crate::shim::econ::TokenAmount;
-use crate::shim::message::{Message, METHOD_SEND};
-use anyhow::Context as _;
+use crate::shim::message::{Message, MethodNum, METHOD_SEND};
+use anyhow::{bail, Context as _};
+use fvm_ipld_encoding::RawBytes;
 use num::Zero as _;
 
 use crate::cli::humantoken;
@@ -19,15 +24,34 @@ pub struct SendCommand {
     #[arg(long)]
     from: Option<String>,
     target_address: String,
+    /// Amount to send, e.g. `1.5`, `10 milliFIL` or `100 attoFIL`, in FIL when
+    /// no unit is given
     #[arg(value_parser = humantoken::parse)]
     amount: TokenAmount,
+    /// Estimated when zero
     #[arg(long, value_parser = humantoken::parse, default_value_t = TokenAmount::zero())]
     gas_feecap: TokenAmount,
-    /// In milliGas
+    /// In milliGas, estimated when zero
     #[arg(long, default_value_t = 0)]
     gas_limit: i64,
+    /// Estimated when zero
     #[arg(long, value_parser = humantoken::parse, default_value_t = TokenAmount::zero())]
     gas_premium: TokenAmount,
+    /// Sign the message with this nonce instead of the next one of the sender
+    #[arg(long)]
+    nonce: Option<u64>,
+    /// Method to invoke on the target
+    #[arg(long, default_value_t = METHOD_SEND)]
+    method: MethodNum,
+    /// Parameters of the method, as IPLD JSON
+    #[arg(long)]
+    params_json: Option<String>,
+    /// Send without asking for confirmation
+    #[arg(short, long)]
+    yes: bool,
+    /// Wait for the message to land on chain and print its exit code
+    #[arg(long)]
+    wait: bool,
 }
 
 impl SendCommand {
@@ -41,11 +65,17 @@ impl SendCommand {
                 )?)?
             };
 
+        let params = match &self.params_json {
+            Some(json) => encode_params(json)?,
+            None => RawBytes::default(),
+        };
+
         let message = Message {
             from,
             to: StrictAddress::from_str(&self.target_address)?.into(),
             value: self.amount.clone(),
-            method_num: METHOD_SEND,
+            method_num: self.method,
+            params,
             gas_limit: self.gas_limit as u64,
             gas_fee_cap: self.gas_feecap.clone(),
             gas_premium: self.gas_premium.clone(),
@@ -53,10 +83,90 @@ impl SendCommand {
             ..Default::default()
         };
 
-        let signed_msg = api.mpool_push_message(message, None).await?;
+        let message = api
+            .gas_estimate_message_gas(message, None, Default::default())
+            .await?;
+
+        if !self.yes {
+            println!(
+                "Sending {} from {} to {}, with a total cost of at most {}",
+                message.value.pretty(),
+                message.from,
+                message.to,
+                message.required_funds().pretty()
+            );
+            if !crate::cli::subcommands::prompt_confirm() {
+                bail!("Operation cancelled by user");
+            }
+        }
+
+        let cid = match self.nonce {
+            Some(nonce) => {
+                let message = Message {
+                    sequence: nonce,
+                    ..message
+                };
+                let signed_msg = api.wallet_sign_message(Some(from), message).await?;
+                api.mpool_push(signed_msg).await?
+            }
+            None => api.mpool_push_message(message, None).await?.cid()?,
+        };
+
+        println!("{cid}");
 
-        println!("{}", signed_msg.cid().unwrap());
+        if self.wait {
+            let lookup = api
+                .state_wait_msg(cid, 1)
+                .await?
+                .context("message was not found")?;
+            println!(
+                "Landed at epoch {} with exit code {}",
+                lookup.height,
+                lookup.receipt.exit_code().value()
+            );
+        }
 
         Ok(())
     }
 }
+
+/// Encodes method parameters given as IPLD JSON, e.g. `{"/": {"bytes": ".."}}`
+/// for byte strings, to CBOR.
+fn encode_params(json: &str) -> anyhow::Result<RawBytes> {
+    let params = Ipld::from_lotus_json(
+        serde_json::from_str(json).context("method parameters are not valid IPLD JSON")?,
+    );
+    Ok(RawBytes::new(fvm_ipld_encoding::to_vec(&params)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn amounts() {
+        let attos = |amount: &str| humantoken::parse(amount).unwrap().atto().clone();
+        assert_eq!(attos("1"), TokenAmount::from_whole(1).atto().clone());
+        assert_eq!(attos("1.5 milliFIL"), 1_500_000_000_000_000u64.into());
+        assert_eq!(attos("10 attoFIL"), 10u64.into());
+        assert_eq!(attos("0.000000000000000001 FIL"), 1u64.into());
+        assert_eq!(attos("0.001 milliFIL"), 1_000_000_000_000u64.into());
+        assert_eq!(
+            attos("1.000000000000000001 FIL"),
+            1_000_000_000_000_000_001u64.into()
+        );
+        // Amounts are never rounded to the nearest attoFIL
+        assert!(humantoken::parse("0.0000000000000000001 FIL").is_err());
+        assert!(humantoken::parse("1.5 attoFIL").is_err());
+        assert!(humantoken::parse("0.0000000000000001 milliFIL").is_err());
+    }
+
+    #[test]
+    fn params() {
+        assert!(encode_params("").is_err());
+        assert_eq!(
+            encode_params("[1, \"a\"]").unwrap().to_vec(),
+            [0x82, 0x01, 0x61, b'a']
+        );
+    }
+}
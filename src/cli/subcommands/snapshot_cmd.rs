@@ -1,17 +1,22 @@
 // Copyright 2019-2024 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use super::*;
+use crate::blocks::TipsetKey;
 use crate::chain_sync::SyncConfig;
 use crate::cli_shared::snapshot::{self, TrustedVendor};
 use crate::rpc_api::chain_api::ChainExportParams;
 use crate::rpc_api::data_types::ApiTipsetKey;
 use crate::rpc_client::ApiInfo;
+use crate::shim::clock::ChainEpoch;
 use anyhow::Context as _;
 use chrono::DateTime;
+use cid::Cid;
 use clap::Subcommand;
 use human_repr::HumanCount;
+use indicatif::{ProgressBar, ProgressStyle};
+use nonempty::NonEmpty;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use tempfile::NamedTempFile;
 use tokio::io::AsyncWriteExt;
 
@@ -28,15 +33,49 @@ pub enum SnapshotCommands {
         /// Don't write the archive.
         #[arg(long)]
         dry_run: bool,
-        /// Tipset to start the export from, default is the chain head
+        /// Tipset to start the export from, either an epoch or a tipset key
+        /// as `@<cid>,<cid>...`. Default is the chain head.
         #[arg(short, long)]
-        tipset: Option<i64>,
+        tipset: Option<TipsetSelector>,
         /// How many state-roots to include. Lower limit is 900 for `calibnet` and `mainnet`.
         #[arg(short, long)]
         depth: Option<crate::chain::ChainEpochDelta>,
+        /// Only include the messages of the tipsets whose state-roots are
+        /// included. Set to `false` to include all messages down to genesis,
+        /// which requires a node with the full message history.
+        #[arg(long, default_value_t = true, action = clap::ArgAction::Set)]
+        skip_old_messages: bool,
     },
 }
 
+/// A tipset given by its epoch or by its key.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TipsetSelector {
+    Epoch(ChainEpoch),
+    Key(TipsetKey),
+}
+
+impl FromStr for TipsetSelector {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix('@') {
+            Some(cids) => {
+                let cids = cids
+                    .split(',')
+                    .map(Cid::from_str)
+                    .collect::<Result<Vec<_>, _>>()
+                    .context("invalid tipset key")?;
+                let cids = NonEmpty::from_vec(cids).context("empty tipset key")?;
+                Ok(Self::Key(cids.into()))
+            }
+            None => Ok(Self::Epoch(s.parse().context(
+                "expected an epoch or a tipset key as `@<cid>,<cid>...`",
+            )?)),
+        }
+    }
+}
+
 impl SnapshotCommands {
     pub async fn run(self, api: ApiInfo) -> anyhow::Result<()> {
         match self {
@@ -46,18 +85,21 @@ impl SnapshotCommands {
                 dry_run,
                 tipset,
                 depth,
+                skip_old_messages,
             } => {
-                let chain_head = api.chain_head().await?;
-
-                let epoch = tipset.unwrap_or(chain_head.epoch());
+                let tipset = match tipset {
+                    None => api.chain_head().await?,
+                    Some(TipsetSelector::Epoch(epoch)) => {
+                        api.chain_get_tipset_by_height(epoch, Default::default())
+                            .await?
+                    }
+                    Some(TipsetSelector::Key(tsk)) => api.chain_get_tipset(tsk).await?,
+                };
+                let epoch = tipset.epoch();
 
                 let raw_network_name = api.state_network_name().await?;
                 let chain_name = crate::daemon::get_actual_chain_name(&raw_network_name);
 
-                let tipset = api
-                    .chain_get_tipset_by_height(epoch, Default::default())
-                    .await?;
-
                 let output_path = match output_path.is_dir() {
                     true => output_path.join(snapshot::filename(
                         TrustedVendor::Forest,
@@ -79,44 +121,57 @@ impl SnapshotCommands {
                     epoch,
                     recent_roots: depth.unwrap_or(SyncConfig::default().recent_state_roots),
                     output_path: temp_path.to_path_buf(),
-                    tipset_keys: ApiTipsetKey(Some(chain_head.key().clone())),
+                    tipset_keys: ApiTipsetKey(Some(tipset.key().clone())),
                     skip_checksum,
                     dry_run,
+                    skip_old_messages,
                 };
 
+                // The export walks the chain down to genesis, one epoch at a time
+                let pb = ProgressBar::new(epoch as u64)
+                    .with_style(
+                        ProgressStyle::with_template(
+                            "{spinner} {msg} [{elapsed_precise}] {wide_bar} ETA {eta}",
+                        )
+                        .expect("indicatif template must be valid"),
+                    )
+                    .with_message("Getting ready to export...");
+                pb.enable_steady_tick(std::time::Duration::from_secs_f32(0.1));
                 let handle = tokio::spawn({
+                    let api = api.clone();
+                    let pb = pb.clone();
                     let tmp_file = temp_path.to_owned();
                     let output_path = output_path.clone();
                     async move {
                         let mut interval =
                             tokio::time::interval(tokio::time::Duration::from_secs_f32(0.25));
-                        println!("Getting ready to export...");
                         loop {
                             interval.tick().await;
+                            // Not available until the node has found the tipset
+                            let Ok(Some(status)) = api.chain_export_status().await else {
+                                continue;
+                            };
                             let snapshot_size = std::fs::metadata(&tmp_file)
                                 .map(|meta| meta.len())
                                 .unwrap_or(0);
-                            print!(
-                                "{}{}",
-                                anes::MoveCursorToPreviousLine(1),
-                                anes::ClearLine::All
-                            );
-                            println!(
-                                "{}: {}",
-                                &output_path.to_string_lossy(),
-                                snapshot_size.human_count_bytes()
-                            );
-                            let _ = std::io::stdout().flush();
+                            pb.set_position((status.start_epoch - status.epoch) as u64);
+                            pb.set_message(format!(
+                                "{}: {}, {} blocks",
+                                output_path.display(),
+                                snapshot_size.human_count_bytes(),
+                                status.blocks
+                            ));
                         }
                     }
                 });
 
-                let hash_result = api.chain_export(params).await?;
+                let hash_result = api.chain_export(params).await;
 
                 handle.abort();
                 let _ = handle.await;
+                pb.finish_and_clear();
 
-                if let Some(hash) = hash_result {
+                if let Some(hash) = hash_result? {
                     save_checksum(&output_path, hash).await?;
                 }
                 temp_path.persist(output_path)?;
@@ -148,3 +203,24 @@ async fn save_checksum(source: &Path, encoded_hash: String) -> anyhow::Result<()
     checksum_file.flush().await?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tipset_selector() {
+        assert_eq!(
+            "1000".parse::<TipsetSelector>().unwrap(),
+            TipsetSelector::Epoch(1000)
+        );
+        let cid = Cid::from_str("bafy2bzacea3wsdh6y3a36tb3skempjoxqpuyompjbmfeyf34fi3uy6uue42v4")
+            .unwrap();
+        assert_eq!(
+            format!("@{cid},{cid}").parse::<TipsetSelector>().unwrap(),
+            TipsetSelector::Key(NonEmpty::from((cid, vec![cid])).into())
+        );
+        assert!("@".parse::<TipsetSelector>().is_err());
+        assert!("head".parse::<TipsetSelector>().is_err());
+    }
+}
@@ -1,43 +0,0 @@
-// Copyright 2019-2024 ChainSafe Systems
-// SPDX-License-Identifier: Apache-2.0, MIT
-
-use std::path::PathBuf;
-
-use crate::rpc_client::ApiInfo;
-use crate::shim::clock::ChainEpoch;
-use crate::shim::econ::TokenAmount;
-use cid::Cid;
-use clap::Subcommand;
-use serde_tuple::{self, Deserialize_tuple, Serialize_tuple};
-
-#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug)]
-struct VestingSchedule {
-    entries: Vec<VestingScheduleEntry>,
-}
-
-#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug)]
-struct VestingScheduleEntry {
-    epoch: ChainEpoch,
-    amount: TokenAmount,
-}
-
-#[derive(Debug, Subcommand)]
-pub enum StateCommands {
-    Fetch {
-        root: Cid,
-        /// The `.car` file path to save the state root
-        #[arg(short, long)]
-        save_to_file: Option<PathBuf>,
-    },
-}
-
-impl StateCommands {
-    pub async fn run(self, api: ApiInfo) -> anyhow::Result<()> {
-        match self {
-            Self::Fetch { root, save_to_file } => {
-                println!("{}", api.state_fetch_root(root, save_to_file).await?);
-            }
-        }
-        Ok(())
-    }
-}
@@ -0,0 +1,125 @@
+// Copyright 2019-2024 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+mod render;
+
+use std::path::PathBuf;
+
+use crate::lotus_json::HasLotusJson;
+use crate::rpc_client::ApiInfo;
+use crate::shim::clock::ChainEpoch;
+use crate::shim::econ::TokenAmount;
+use cid::Cid;
+use clap::Subcommand;
+use serde_tuple::{self, Deserialize_tuple, Serialize_tuple};
+
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug)]
+struct VestingSchedule {
+    entries: Vec<VestingScheduleEntry>,
+}
+
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug)]
+struct VestingScheduleEntry {
+    epoch: ChainEpoch,
+    amount: TokenAmount,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum StateCommands {
+    Fetch {
+        root: Cid,
+        /// The `.car` file path to save the state root
+        #[arg(short, long)]
+        save_to_file: Option<PathBuf>,
+    },
+    /// Computes the state of a tipset and prints the invocation trees of its
+    /// messages
+    ComputeState {
+        /// Epoch of the tipset, the head by default
+        #[arg(long)]
+        epoch: Option<ChainEpoch>,
+        /// Print the state root and traces as JSON
+        #[arg(long, conflicts_with = "html")]
+        json: bool,
+        /// Print the traces as an HTML page, with collapsible calls
+        #[arg(long)]
+        html: bool,
+        /// Also apply the pending messages of the message pool
+        #[arg(long)]
+        apply_mpool_messages: bool,
+    },
+    /// Shows the progress of the state migration being run, if any
+    MigrationStatus,
+}
+
+impl StateCommands {
+    pub async fn run(self, api: ApiInfo) -> anyhow::Result<()> {
+        match self {
+            Self::Fetch { root, save_to_file } => {
+                println!("{}", api.state_fetch_root(root, save_to_file).await?);
+            }
+            Self::ComputeState {
+                epoch,
+                json,
+                html,
+                apply_mpool_messages,
+            } => {
+                let head = api.chain_head().await?;
+                let tipset = match epoch {
+                    Some(epoch) => {
+                        api.chain_get_tipset_by_height(epoch, head.key().into())
+                            .await?
+                    }
+                    None => head,
+                };
+                let messages = match apply_mpool_messages {
+                    true => api
+                        .mpool_pending(tipset.cids().into())
+                        .await?
+                        .into_iter()
+                        .map(|message| message.message)
+                        .collect(),
+                    false => vec![],
+                };
+                let output = api
+                    .state_compute(tipset.epoch(), messages, tipset.key().into())
+                    .await?;
+                if json {
+                    println!(
+                        "{}",
+                        serde_json::to_string_pretty(&output.into_lotus_json())?
+                    );
+                } else if html {
+                    print!("{}", render::render_html(tipset.epoch(), &output));
+                } else {
+                    print!("{}", render::render_text(tipset.epoch(), &output));
+                }
+            }
+            Self::MigrationStatus => match api.state_migration_status().await? {
+                Some(status) => {
+                    let kind = match status.pre_migration {
+                        true => "Pre-migrating",
+                        false => "Migrating",
+                    };
+                    print!(
+                        "{kind} the state for {} at epoch {}: {}/{} actors ({:.1}%)",
+                        status.height,
+                        status.epoch,
+                        status.migrated_actors,
+                        status.total_actors,
+                        status.percent_complete
+                    );
+                    match status.eta_seconds {
+                        Some(eta) => println!(
+                            ", about {} left",
+                            humantime::format_duration(std::time::Duration::from_secs(eta))
+                        ),
+                        None => println!(),
+                    }
+                }
+                None => println!("No state migration is running"),
+            },
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,252 @@
+// Copyright 2019-2024 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Renders the invocation trees of `forest-cli state compute-state`, as plain
+//! text or as an HTML page with collapsible calls.
+
+use std::fmt::Write as _;
+
+use crate::cli::humantoken::TokenAmountPretty as _;
+use crate::rpc_api::data_types::{ApiInvocResult, ComputeStateOutput, ExecutionTrace};
+use crate::shim::{econ::TokenAmount, error::ExitCode};
+use fvm_ipld_encoding::{RawBytes, CBOR, DAG_CBOR};
+use libipld_core::ipld::Ipld;
+
+/// Byte strings longer than this are truncated in decoded values.
+const MAX_BYTES_SHOWN: usize = 32;
+
+pub fn render_text(epoch: i64, output: &ComputeStateOutput) -> String {
+    let mut text = String::new();
+    let _ = writeln!(text, "Computed state at epoch {epoch}: {}", output.root);
+    for result in &output.trace {
+        let _ = writeln!(text);
+        let _ = writeln!(text, "{}", message_summary(result));
+        if !result.error.is_empty() {
+            let _ = writeln!(text, "  error: {}", result.error);
+        }
+        if let Some(trace) = &result.execution_trace {
+            text_call(&mut text, trace, 1);
+        }
+    }
+    text
+}
+
+fn text_call(text: &mut String, trace: &ExecutionTrace, depth: usize) {
+    let indent = "  ".repeat(depth);
+    let _ = writeln!(text, "{indent}{}", call_summary(trace));
+    if let Some(params) = decode(&trace.msg.params, trace.msg.params_codec) {
+        let _ = writeln!(text, "{indent}  params: {params}");
+    }
+    if let Some(ret) = decode(&trace.msg_rct.r#return, trace.msg_rct.return_codec) {
+        let _ = writeln!(text, "{indent}  return: {ret}");
+    }
+    for subcall in &trace.subcalls {
+        text_call(text, subcall, depth + 1);
+    }
+}
+
+pub fn render_html(epoch: i64, output: &ComputeStateOutput) -> String {
+    let mut html = String::new();
+    let _ = writeln!(
+        html,
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>State at epoch {epoch}</title>
+<style>
+body {{ font-family: monospace; }}
+details {{ margin-left: 1.5em; }}
+summary {{ cursor: pointer; }}
+th {{ text-align: left; padding-right: 1em; }}
+.failed {{ color: #c00; }}
+</style>
+</head>
+<body>
+<h1>Computed state at epoch {epoch}: {}</h1>"#,
+        output.root
+    );
+    for result in &output.trace {
+        let _ = writeln!(
+            html,
+            "<details>\n<summary{}>{}</summary>",
+            failed_class(
+                result
+                    .msg_rct
+                    .as_ref()
+                    .map(|receipt| receipt.exit_code().into())
+            ),
+            escape(&message_summary(result))
+        );
+        if !result.error.is_empty() {
+            let _ = writeln!(html, "<p class=\"failed\">{}</p>", escape(&result.error));
+        }
+        if let Some(trace) = &result.execution_trace {
+            html_call(&mut html, trace);
+        }
+        let _ = writeln!(html, "</details>");
+    }
+    let _ = writeln!(html, "</body>\n</html>");
+    html
+}
+
+fn html_call(html: &mut String, trace: &ExecutionTrace) {
+    let _ = writeln!(
+        html,
+        "<details>\n<summary{}>{}</summary>\n<table>",
+        failed_class(Some(trace.msg_rct.exit_code)),
+        escape(&call_summary(trace))
+    );
+    if let Some(params) = decode(&trace.msg.params, trace.msg.params_codec) {
+        let _ = writeln!(html, "<tr><th>Params</th><td>{}</td></tr>", escape(&params));
+    }
+    if let Some(ret) = decode(&trace.msg_rct.r#return, trace.msg_rct.return_codec) {
+        let _ = writeln!(html, "<tr><th>Return</th><td>{}</td></tr>", escape(&ret));
+    }
+    let _ = writeln!(html, "</table>");
+    if !trace.gas_charges.is_empty() {
+        let _ = writeln!(
+            html,
+            "<details>\n<summary>Gas charges ({})</summary>\n<table>\n<tr><th>Name</th><th>Total</th><th>Compute</th><th>Storage</th></tr>",
+            trace.gas_charges.len()
+        );
+        for charge in &trace.gas_charges {
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                escape(&charge.name),
+                charge.total_gas,
+                charge.compute_gas,
+                charge.storage_gas
+            );
+        }
+        let _ = writeln!(html, "</table>\n</details>");
+    }
+    for subcall in &trace.subcalls {
+        html_call(html, subcall);
+    }
+    let _ = writeln!(html, "</details>");
+}
+
+fn message_summary(result: &ApiInvocResult) -> String {
+    let exit_code = result
+        .msg_rct
+        .as_ref()
+        .map(|receipt| exit_code(receipt.exit_code().into()))
+        .unwrap_or_else(|| "no receipt".into());
+    format!(
+        "Message {}: {} -> {}, method {}, {exit_code}, gas used {}",
+        result.msg_cid,
+        result.msg.from,
+        result.msg.to,
+        result.msg.method_num,
+        result.gas_cost.gas_used.atto()
+    )
+}
+
+fn call_summary(trace: &ExecutionTrace) -> String {
+    let gas: u64 = trace
+        .gas_charges
+        .iter()
+        .map(|charge| charge.total_gas)
+        .sum();
+    let value = match trace.msg.value == TokenAmount::default() {
+        true => String::new(),
+        false => format!(", value {}", trace.msg.value.pretty()),
+    };
+    format!(
+        "{} -> {}, method {}{value}, {}, gas {gas}",
+        trace.msg.from,
+        trace.msg.to,
+        trace.msg.method,
+        exit_code(trace.msg_rct.exit_code)
+    )
+}
+
+fn exit_code(exit_code: ExitCode) -> String {
+    match exit_code.value() {
+        0 => "Ok".into(),
+        code => format!("exit code {code}"),
+    }
+}
+
+fn failed_class(exit_code: Option<ExitCode>) -> &'static str {
+    match exit_code.map(|exit_code| exit_code.value()) {
+        Some(0) => "",
+        _ => r#" class="failed""#,
+    }
+}
+
+/// Decodes CBOR parameters and return values, falling back to their raw bytes.
+fn decode(bytes: &RawBytes, codec: u64) -> Option<String> {
+    if bytes.is_empty() {
+        return None;
+    }
+    match codec {
+        CBOR | DAG_CBOR => match fvm_ipld_encoding::from_slice::<Ipld>(bytes) {
+            Ok(ipld) => Some(format_ipld(&ipld)),
+            Err(_) => Some(format_bytes(bytes)),
+        },
+        _ => Some(format_bytes(bytes)),
+    }
+}
+
+fn format_ipld(ipld: &Ipld) -> String {
+    match ipld {
+        Ipld::Null => "null".into(),
+        Ipld::Bool(b) => b.to_string(),
+        Ipld::Integer(i) => i.to_string(),
+        Ipld::Float(f) => f.to_string(),
+        Ipld::String(s) => format!("{s:?}"),
+        Ipld::Bytes(bytes) => format_bytes(bytes),
+        Ipld::List(list) => format!(
+            "[{}]",
+            list.iter().map(format_ipld).collect::<Vec<_>>().join(", ")
+        ),
+        Ipld::Map(map) => format!(
+            "{{{}}}",
+            map.iter()
+                .map(|(key, value)| format!("{key:?}: {}", format_ipld(value)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        Ipld::Link(cid) => cid.to_string(),
+    }
+}
+
+fn format_bytes(bytes: &[u8]) -> String {
+    match bytes.get(..MAX_BYTES_SHOWN) {
+        Some(shown) if bytes.len() > MAX_BYTES_SHOWN => {
+            format!("0x{}... ({} bytes)", hex::encode(shown), bytes.len())
+        }
+        _ => format!("0x{}", hex::encode(bytes)),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lotus_json::HasLotusJson;
+
+    fn output() -> ComputeStateOutput {
+        let json = include_str!("compute_state_output.json");
+        ComputeStateOutput::from_lotus_json(serde_json::from_str(json).unwrap())
+    }
+
+    #[test]
+    fn text() {
+        insta::assert_snapshot!(render_text(1000, &output()));
+    }
+
+    #[test]
+    fn html() {
+        insta::assert_snapshot!(render_html(1000, &output()));
+    }
+}
@@ -2,14 +2,19 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use std::{
+    collections::VecDeque,
     io::{stdout, Write},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use crate::chain_sync::SyncStage;
+use crate::cli_shared::cli::OutputFormat;
+use crate::rpc_api::data_types::RPCSyncState;
 use crate::rpc_client::*;
+use crate::shim::clock::ChainEpoch;
 use cid::Cid;
 use clap::Subcommand;
+use is_terminal::IsTerminal;
 use ticker::Ticker;
 
 use crate::cli::subcommands::format_vec_pretty;
@@ -21,6 +26,9 @@ pub enum SyncCommands {
         /// Don't exit after node is synced
         #[arg(short)]
         watch: bool,
+        /// Exit with an error if the node isn't synced after this many seconds
+        #[arg(long)]
+        timeout: Option<u64>,
     },
     /// Check sync status
     Status,
@@ -36,16 +44,87 @@ pub enum SyncCommands {
         #[arg(short)]
         cid: String,
     },
+    /// Unmark a given block as bad, or all of them
+    UnmarkBad {
+        /// The block CID to unmark
+        #[arg(short, long, required_unless_present = "all")]
+        cid: Option<String>,
+        /// Unmark all the bad blocks
+        #[arg(long, conflicts_with = "cid")]
+        all: bool,
+    },
+}
+
+/// How far back `sync wait` looks to estimate the catch-up rate.
+const ETA_WINDOW: Duration = Duration::from_secs(60);
+
+/// Every how many ticks `sync wait` prints a line when stdout isn't a terminal.
+const PLAIN_OUTPUT_INTERVAL: usize = 10;
+
+/// Estimates the time left to sync from how fast the number of epochs left to
+/// sync went down over a sliding window. The chain keeps growing while syncing,
+/// so this is the catch-up rate rather than the validation rate.
+struct EtaEstimator {
+    window: Duration,
+    /// Time since the start of the wait, and the number of epochs left then
+    samples: VecDeque<(Duration, ChainEpoch)>,
+}
+
+impl EtaEstimator {
+    fn new(window: Duration) -> Self {
+        Self {
+            window,
+            samples: VecDeque::new(),
+        }
+    }
+
+    fn push(&mut self, at: Duration, todo: ChainEpoch) {
+        self.samples.push_back((at, todo));
+        while let Some((oldest, _)) = self.samples.front() {
+            if at.saturating_sub(*oldest) <= self.window {
+                break;
+            }
+            self.samples.pop_front();
+        }
+    }
+
+    /// Time left to sync `todo` epochs, or [`None`] if the node isn't catching
+    /// up over the window.
+    fn eta(&self, todo: ChainEpoch) -> Option<Duration> {
+        let (first_at, first_todo) = self.samples.front()?;
+        let (last_at, last_todo) = self.samples.back()?;
+        let secs = last_at.saturating_sub(*first_at).as_secs_f64();
+        let rate = (first_todo - last_todo) as f64 / secs;
+        if !rate.is_finite() || rate <= 0.0 {
+            return None;
+        }
+        Some(Duration::from_secs_f64(todo.max(0) as f64 / rate))
+    }
 }
 
 impl SyncCommands {
-    pub async fn run(self, api: ApiInfo) -> anyhow::Result<()> {
+    pub async fn run(self, api: ApiInfo, output: OutputFormat) -> anyhow::Result<()> {
         match self {
-            Self::Wait { watch } => {
+            Self::Wait { watch, timeout } => {
                 let ticker = Ticker::new(0.., Duration::from_secs(1));
                 let mut stdout = stdout();
+                // Redrawing lines garbles the output when it's not a terminal,
+                // e.g. in the logs of a `systemd` service
+                let redraw = stdout.is_terminal();
+                let started = Instant::now();
+                let mut eta = EtaEstimator::new(ETA_WINDOW);
+
+                for tick in ticker {
+                    let elapsed = started.elapsed();
+                    if let Some(timeout) = timeout {
+                        if elapsed >= Duration::from_secs(timeout) {
+                            if redraw {
+                                println!();
+                            }
+                            anyhow::bail!("Timed out after {timeout}s waiting for sync");
+                        }
+                    }
 
-                for _ in ticker {
                     let response = api.sync_status().await?;
                     let state = response.active_syncs.first();
 
@@ -61,28 +140,54 @@ impl SyncCommands {
                         0
                     };
 
-                    println!(
-                        "Worker: 0; Base: {}; Target: {}; (diff: {})",
-                        base_height,
-                        target_height,
-                        target_height - base_height
-                    );
-                    println!(
-                        "State: {}; Current Epoch: {}; Todo: {}",
-                        state.stage(),
-                        state.epoch(),
-                        target_height - state.epoch()
-                    );
-
-                    for _ in 0..2 {
-                        write!(
-                            stdout,
-                            "\r{}{}",
-                            anes::ClearLine::All,
-                            anes::MoveCursorUp(1)
-                        )?;
+                    let todo = target_height - state.epoch();
+                    eta.push(elapsed, todo);
+                    let eta = match eta.eta(todo) {
+                        Some(eta) => humantime::format_duration(eta).to_string(),
+                        None => "unknown".into(),
+                    };
+
+                    if redraw {
+                        println!(
+                            "Worker: 0; Base: {}; Target: {}; (diff: {})",
+                            base_height,
+                            target_height,
+                            target_height - base_height
+                        );
+                        println!(
+                            "State: {}; Current Epoch: {}; Todo: {}; ETA: {}",
+                            state.stage(),
+                            state.epoch(),
+                            todo,
+                            eta
+                        );
+
+                        for _ in 0..2 {
+                            write!(
+                                stdout,
+                                "\r{}{}",
+                                anes::ClearLine::All,
+                                anes::MoveCursorUp(1)
+                            )?;
+                        }
+                    } else if tick % PLAIN_OUTPUT_INTERVAL == 0 {
+                        println!(
+                            "Base: {}; Target: {}; State: {}; Current Epoch: {}; Todo: {}; ETA: {}",
+                            base_height,
+                            target_height,
+                            state.stage(),
+                            state.epoch(),
+                            todo,
+                            eta
+                        );
                     }
 
+                    if state.stage() == SyncStage::Error {
+                        anyhow::bail!(
+                            "\nSync failed: {}",
+                            state.message().unwrap_or("unknown error")
+                        );
+                    }
                     if state.stage() == SyncStage::Complete && !watch {
                         println!("\nDone!");
                         break;
@@ -90,42 +195,7 @@ impl SyncCommands {
                 }
                 Ok(())
             }
-            Self::Status => {
-                let response = api.sync_status().await?;
-
-                let state = response.active_syncs.first();
-                let base = state.base();
-                let elapsed_time = state.get_elapsed_time();
-                let target = state.target();
-
-                let (target_cids, target_height) = if let Some(tipset) = target {
-                    let cid_vec = tipset.cids().iter().map(|cid| cid.to_string()).collect();
-                    (format_vec_pretty(cid_vec), tipset.epoch())
-                } else {
-                    ("[]".to_string(), 0)
-                };
-
-                let (base_cids, base_height) = if let Some(tipset) = base {
-                    let cid_vec = tipset.cids().iter().map(|cid| cid.to_string()).collect();
-                    (format_vec_pretty(cid_vec), tipset.epoch())
-                } else {
-                    ("[]".to_string(), 0)
-                };
-
-                let height_diff = base_height - target_height;
-
-                println!("sync status:");
-                println!("Base:\t{base_cids}");
-                println!("Target:\t{target_cids} ({target_height})");
-                println!("Height diff:\t{}", height_diff.abs());
-                println!("Stage:\t{}", state.stage());
-                println!("Height:\t{}", state.epoch());
-
-                if let Some(duration) = elapsed_time {
-                    println!("Elapsed time:\t{}s", duration.num_seconds());
-                }
-                Ok(())
-            }
+            Self::Status => print_status(&api.sync_status().await?, output, &mut stdout()),
             Self::CheckBad { cid } => {
                 let cid: Cid = cid.parse()?;
                 let response = api.sync_check_bad(cid).await?;
@@ -143,6 +213,137 @@ impl SyncCommands {
                 println!("OK");
                 Ok(())
             }
+            Self::UnmarkBad { cid, all } => {
+                match cid {
+                    Some(cid) if !all => api.sync_unmark_bad(cid.parse()?).await?,
+                    _ => api.sync_unmark_all_bad().await?,
+                }
+                println!("OK");
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Prints the state of the active sync, or its Lotus JSON in JSON mode.
+fn print_status(
+    response: &RPCSyncState,
+    output: OutputFormat,
+    writer: &mut impl Write,
+) -> anyhow::Result<()> {
+    if output == OutputFormat::Json {
+        serde_json::to_writer_pretty(&mut *writer, response)?;
+        writeln!(writer)?;
+        return Ok(());
+    }
+
+    let state = response.active_syncs.first();
+    let base = state.base();
+    let elapsed_time = state.get_elapsed_time();
+    let target = state.target();
+
+    let (target_cids, target_height) = if let Some(tipset) = target {
+        let cid_vec = tipset.cids().iter().map(|cid| cid.to_string()).collect();
+        (format_vec_pretty(cid_vec), tipset.epoch())
+    } else {
+        ("[]".to_string(), 0)
+    };
+
+    let (base_cids, base_height) = if let Some(tipset) = base {
+        let cid_vec = tipset.cids().iter().map(|cid| cid.to_string()).collect();
+        (format_vec_pretty(cid_vec), tipset.epoch())
+    } else {
+        ("[]".to_string(), 0)
+    };
+
+    let height_diff = base_height - target_height;
+
+    writeln!(writer, "sync status:")?;
+    writeln!(writer, "Base:\t{base_cids}")?;
+    writeln!(writer, "Target:\t{target_cids} ({target_height})")?;
+    writeln!(writer, "Height diff:\t{}", height_diff.abs())?;
+    writeln!(writer, "Stage:\t{}", state.stage())?;
+    writeln!(writer, "Height:\t{}", state.epoch())?;
+    if let Some((epoch, key)) = state.last_validated() {
+        writeln!(writer, "Last validated:\t{key} ({epoch})")?;
+    }
+    if let Some(message) = state.message() {
+        writeln!(writer, "Error:\t{message}")?;
+    }
+
+    if let Some(duration) = elapsed_time {
+        writeln!(writer, "Elapsed time:\t{}s", duration.num_seconds())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lotus_json::HasLotusJson;
+
+    fn response() -> RPCSyncState {
+        let json = include_str!("sync_status.json");
+        RPCSyncState::from_lotus_json(serde_json::from_str(json).unwrap())
+    }
+
+    fn status(output: OutputFormat) -> String {
+        let mut writer = vec![];
+        print_status(&response(), output, &mut writer).unwrap();
+        String::from_utf8(writer).unwrap()
+    }
+
+    fn estimator(samples: impl IntoIterator<Item = (u64, ChainEpoch)>) -> EtaEstimator {
+        let mut estimator = EtaEstimator::new(ETA_WINDOW);
+        for (secs, todo) in samples {
+            estimator.push(Duration::from_secs(secs), todo);
         }
+        estimator
+    }
+
+    #[test]
+    fn eta_from_catch_up_rate() {
+        // 10 epochs caught up per second
+        let estimator = estimator((0..=10).map(|secs| (secs, 1000 - 10 * secs as ChainEpoch)));
+        assert_eq!(estimator.eta(900), Some(Duration::from_secs(90)));
+        assert_eq!(estimator.eta(0), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn eta_uses_sliding_window() {
+        // Slow at first, then 5 epochs per second for the last minute
+        let estimator = estimator(
+            (0..100)
+                .map(|secs| (secs, 10_000 - secs as ChainEpoch))
+                .chain((100..=200).map(|secs| (secs, 9_900 - 5 * (secs - 100) as ChainEpoch))),
+        );
+        assert_eq!(estimator.samples.len(), 61);
+        assert_eq!(estimator.eta(500), Some(Duration::from_secs(100)));
+    }
+
+    #[test]
+    fn eta_unknown_when_not_catching_up() {
+        assert_eq!(estimator([]).eta(100), None);
+        assert_eq!(estimator([(0, 100)]).eta(100), None);
+        // Falling behind, e.g. while the node is still fetching headers
+        assert_eq!(estimator([(0, 100), (10, 100)]).eta(100), None);
+        assert_eq!(estimator([(0, 100), (10, 120)]).eta(120), None);
+    }
+
+    #[test]
+    fn status_text() {
+        insta::assert_snapshot!(status(OutputFormat::Text));
+    }
+
+    #[test]
+    fn status_json() {
+        let output = status(OutputFormat::Json);
+        // Nothing but the Lotus JSON of the response on stdout
+        let json: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(
+            json,
+            serde_json::from_str::<serde_json::Value>(include_str!("sync_status.json")).unwrap()
+        );
+        insta::assert_snapshot!(output);
     }
 }
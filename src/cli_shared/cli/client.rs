@@ -2,7 +2,9 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use std::{
+    fmt,
     net::{IpAddr, Ipv4Addr, SocketAddr},
+    num::NonZeroU32,
     path::PathBuf,
     str::FromStr,
 };
@@ -19,7 +21,7 @@ use serde_with::{serde_as, DurationSeconds};
 pub struct ChunkSize(pub u32);
 impl Default for ChunkSize {
     fn default() -> Self {
-        ChunkSize(500_000)
+        ChunkSize(4096)
     }
 }
 
@@ -33,6 +35,100 @@ impl Default for BufferSize {
     }
 }
 
+/// A size in bytes, written either as a plain number or with a decimal (`kB`,
+/// `MB`, `GB`, `TB`) or binary (`KiB`, `MiB`, `GiB`, `TiB`) unit, e.g.
+/// `"512MB"` or `"1GiB"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(test, derive(derive_quickcheck_arbitrary::Arbitrary))]
+pub struct ByteSize(pub u64);
+
+impl ByteSize {
+    const UNITS: [(&'static str, u64); 9] = [
+        ("TiB", 1 << 40),
+        ("GiB", 1 << 30),
+        ("MiB", 1 << 20),
+        ("KiB", 1 << 10),
+        ("TB", 1_000_000_000_000),
+        ("GB", 1_000_000_000),
+        ("MB", 1_000_000),
+        ("kB", 1_000),
+        ("B", 1),
+    ];
+
+    pub const fn gibibytes(n: u64) -> Self {
+        ByteSize(n << 30)
+    }
+
+    pub const fn mebibytes(n: u64) -> Self {
+        ByteSize(n << 20)
+    }
+
+    /// The size in bytes, saturating on platforms where it doesn't fit.
+    pub fn as_usize(self) -> usize {
+        usize::try_from(self.0).unwrap_or(usize::MAX)
+    }
+}
+
+impl FromStr for ByteSize {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let (number, unit) = s.split_at(split);
+        let number = number
+            .parse::<u64>()
+            .map_err(|_| anyhow::anyhow!("invalid size: {s:?}"))?;
+        let unit = unit.trim();
+        let multiplier = match unit {
+            "" => 1,
+            _ => Self::UNITS
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(unit))
+                .map(|(_, multiplier)| *multiplier)
+                .ok_or_else(|| anyhow::anyhow!("unknown unit {unit:?} in size {s:?}"))?,
+        };
+        number
+            .checked_mul(multiplier)
+            .map(ByteSize)
+            .ok_or_else(|| anyhow::anyhow!("size {s:?} is too large"))
+    }
+}
+
+impl fmt::Display for ByteSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Pick the unit giving the smallest whole number
+        let (unit, multiplier) = Self::UNITS
+            .iter()
+            .rev()
+            .filter(|(_, multiplier)| self.0 % multiplier == 0)
+            .min_by_key(|(_, multiplier)| self.0 / multiplier)
+            .unwrap_or(&("B", 1));
+        write!(f, "{}{unit}", self.0 / multiplier)
+    }
+}
+
+impl Serialize for ByteSize {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ByteSize {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Bytes(u64),
+            Text(String),
+        }
+        match Repr::deserialize(deserializer)? {
+            Repr::Bytes(bytes) => Ok(ByteSize(bytes)),
+            Repr::Text(text) => text.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
 #[serde_as]
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(default)]
@@ -40,6 +136,8 @@ impl Default for BufferSize {
 pub struct Client {
     pub data_dir: PathBuf,
     pub genesis_file: Option<String>,
+    /// Chain spec file overriding `chain`, see `ChainConfig::from_file`
+    pub chain_spec: Option<PathBuf>,
     pub enable_rpc: bool,
     pub enable_metrics_endpoint: bool,
     /// If this is true, then we do not validate the imported snapshot.
@@ -53,17 +151,33 @@ pub struct Client {
     /// Skips loading import CAR file and assumes it's already been loaded.
     /// Will use the CIDs in the header of the file to index the chain.
     pub skip_load: bool,
-    /// When importing CAR files, chunk key-value pairs before committing them
-    /// to the database.
+    /// When importing CAR files, chunk blocks before verifying and writing
+    /// them.
     pub chunk_size: ChunkSize,
     /// When importing CAR files, maintain a read-ahead buffer measured in
     /// number of chunks.
     pub buffer_size: BufferSize,
+    /// Number of workers verifying the blocks of imported CAR files. Defaults
+    /// to the number of CPUs.
+    pub import_workers: Option<NonZeroU32>,
+    /// Size of the in-memory cache of recently read blocks, e.g. `"1GiB"`.
+    /// Zero disables the cache.
+    pub block_cache_size: ByteSize,
     pub encrypt_keystore: bool,
     /// Metrics bind, e.g. 127.0.0.1:6116
     pub metrics_address: SocketAddr,
-    /// RPC bind, e.g. 127.0.0.1:1234
+    /// RPC bind, e.g. 127.0.0.1:1234. Port 0 lets the OS pick a free port.
     pub rpc_address: SocketAddr,
+    /// Further RPC binds, e.g. the address of a VPN interface
+    pub rpc_additional_addresses: Vec<SocketAddr>,
+    /// Additionally serve RPC on a Unix domain socket at this path, e.g.
+    /// `/run/forest/forest.sock`. The socket is only accessible to the current user.
+    pub rpc_unix_socket_path: Option<PathBuf>,
+    /// PEM encoded certificate chain for serving RPC over HTTPS/WSS. Requires
+    /// `rpc_tls_key_path` to be set as well.
+    pub rpc_tls_cert_path: Option<PathBuf>,
+    /// PEM encoded private key matching `rpc_tls_cert_path`.
+    pub rpc_tls_key_path: Option<PathBuf>,
     /// Period of validity for JWT in seconds. Defaults to 60 days.
     #[serde_as(as = "DurationSeconds<i64>")]
     #[cfg_attr(test, arbitrary(gen(
@@ -72,6 +186,14 @@ pub struct Client {
         |g| Duration::try_milliseconds(i64::arbitrary(g).max(-i64::MAX)).expect("Infallible")
     )))]
     pub token_exp: Duration,
+    /// Longest period of validity in seconds of the non-admin JWT created
+    /// through the API. Unlimited if unset.
+    #[serde_as(as = "Option<DurationSeconds<i64>>")]
+    #[cfg_attr(test, arbitrary(gen(
+        |g| Option::<i64>::arbitrary(g)
+            .map(|ms| Duration::try_milliseconds(ms.max(-i64::MAX)).expect("Infallible"))
+    )))]
+    pub max_token_exp: Option<Duration>,
     /// Load actors from the bundle file (possibly generating it if it doesn't exist)
     pub load_actors: bool,
 }
@@ -82,6 +204,7 @@ impl Default for Client {
         Self {
             data_dir: dir.data_dir().to_path_buf(),
             genesis_file: None,
+            chain_spec: None,
             enable_rpc: true,
             enable_metrics_endpoint: true,
             snapshot_path: None,
@@ -92,11 +215,69 @@ impl Default for Client {
             skip_load: false,
             chunk_size: ChunkSize::default(),
             buffer_size: BufferSize::default(),
+            import_workers: None,
+            block_cache_size: ByteSize::mebibytes(512),
             encrypt_keystore: true,
             metrics_address: FromStr::from_str("0.0.0.0:6116").unwrap(),
             rpc_address: SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), DEFAULT_PORT),
+            rpc_additional_addresses: vec![],
+            rpc_unix_socket_path: None,
+            rpc_tls_cert_path: None,
+            rpc_tls_key_path: None,
             token_exp: Duration::try_seconds(5184000).expect("Infallible"), // 60 Days = 5184000 Seconds
+            max_token_exp: None,
             load_actors: true,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+
+    #[test]
+    fn parse_byte_size() {
+        for (s, expected) in [
+            ("0", 0),
+            ("1024", 1024),
+            ("1GiB", 1 << 30),
+            ("512MB", 512_000_000),
+            ("512MiB", 512 << 20),
+            ("4 KiB", 4096),
+            ("2kb", 2000),
+            ("7B", 7),
+            ("3tib", 3 << 40),
+        ] {
+            assert_eq!(s.parse::<ByteSize>().unwrap(), ByteSize(expected), "{s}");
+        }
+        for s in ["", "GiB", "-1", "1.5GiB", "1 XB", "100000000TiB"] {
+            assert!(s.parse::<ByteSize>().is_err(), "{s}");
+        }
+    }
+
+    #[test]
+    fn byte_size_in_config() {
+        #[derive(Deserialize)]
+        struct Section {
+            client: Client,
+        }
+        for (toml, expected) in [
+            (r#"block_cache_size = "1GiB""#, 1 << 30),
+            (r#"block_cache_size = "512MB""#, 512_000_000),
+            ("block_cache_size = 65536", 65536),
+        ] {
+            let section: Section = toml::from_str(&format!("[client]\n{toml}")).unwrap();
+            assert_eq!(section.client.block_cache_size, ByteSize(expected));
+        }
+        assert_eq!(ByteSize::gibibytes(1).to_string(), "1GiB");
+        assert_eq!(ByteSize(512_000_000).to_string(), "512MB");
+        assert_eq!(ByteSize(1000 << 20).to_string(), "1000MiB");
+        assert_eq!(ByteSize(0).to_string(), "0B");
+    }
+
+    #[quickcheck]
+    fn byte_size_roundtrip(size: ByteSize) {
+        assert_eq!(size.to_string().parse::<ByteSize>().unwrap(), size);
+    }
+}
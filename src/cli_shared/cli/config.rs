@@ -2,9 +2,15 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use crate::db::db_engine::DbConfig;
+use crate::db::GcConfig;
 use crate::libp2p::Libp2pConfig;
-use crate::{chain_sync::SyncConfig, networks::NetworkChain};
+use crate::networks::{ActorBundleOverride, Height, NetworkChain, PolicyOverrides};
+use crate::rpc::RpcConfig;
+use crate::shim::clock::ChainEpoch;
+use crate::{chain_sync::SyncConfig, networks::ChainConfig};
+use ahash::HashMap;
 use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
 use std::path::PathBuf;
 
 use super::client::Client;
@@ -36,22 +42,71 @@ impl Default for DaemonConfig {
     }
 }
 
+/// The `[chain]` section of the configuration: the network to join, and
+/// adjustments to its built-in [`ChainConfig`].
+#[serde_as]
+#[derive(Serialize, Deserialize, PartialEq, Default, Debug, Clone)]
+#[cfg_attr(test, derive(derive_quickcheck_arbitrary::Arbitrary))]
+pub struct ChainSection {
+    #[serde(flatten)]
+    pub network: NetworkChain,
+    /// Epochs of upgrades, overriding those of the network, and the
+    /// `FOREST_<HEIGHT>_HEIGHT` environment variables
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub height_overrides: HashMap<Height, ChainEpoch>,
+    /// Actor bundles loaded at startup, replacing the built-in bundles of the
+    /// upgrades to their actors versions
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[cfg_attr(test, arbitrary(gen(|_g| Vec::new())))]
+    pub actor_bundles: Vec<ActorBundleOverride>,
+    /// Adjustments to the policy of the network, refused on mainnet
+    #[serde(default, skip_serializing_if = "PolicyOverrides::is_empty")]
+    #[cfg_attr(test, arbitrary(gen(|_g| PolicyOverrides::default())))]
+    pub policy_overrides: PolicyOverrides,
+}
+
+impl From<NetworkChain> for ChainSection {
+    fn from(network: NetworkChain) -> Self {
+        Self {
+            network,
+            height_overrides: HashMap::default(),
+            actor_bundles: Vec::new(),
+            policy_overrides: PolicyOverrides::default(),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Default, Debug, Clone)]
 #[cfg_attr(test, derive(derive_quickcheck_arbitrary::Arbitrary))]
 #[serde(default)]
 pub struct Config {
-    pub chain: NetworkChain,
+    pub chain: ChainSection,
     pub client: Client,
     pub parity_db: crate::db::parity_db_config::ParityDbConfig,
     pub network: Libp2pConfig,
     pub sync: SyncConfig,
+    pub gc: GcConfig,
     pub daemon: DaemonConfig,
+    pub rpc: RpcConfig,
 }
 
 impl Config {
     pub fn db_config(&self) -> &DbConfig {
         &self.parity_db
     }
+
+    /// Builds the configuration of the chain, from the chain spec file if
+    /// any, else from the built-in configuration of the network, and applies
+    /// the height and policy overrides.
+    pub fn chain_config(&self) -> anyhow::Result<ChainConfig> {
+        match &self.client.chain_spec {
+            Some(chain_spec) => ChainConfig::from_file(chain_spec)?,
+            None => ChainConfig::from_chain(&self.chain.network),
+        }
+        .with_height_overrides(&self.chain.height_overrides)?
+        .with_policy_overrides(&self.chain.policy_overrides)
+    }
 }
 
 #[cfg(test)]
@@ -59,6 +114,85 @@ mod test {
     use quickcheck_macros::quickcheck;
 
     use super::*;
+    use crate::shim::sector::{RegisteredPoStProofV3, RegisteredSealProofV3};
+
+    #[test]
+    fn test_chain_height_overrides() {
+        let config: Config = toml::from_str(
+            r#"
+[chain]
+type = "calibnet"
+
+[chain.height_overrides]
+Dragon = 1500000
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.chain.network, NetworkChain::Calibnet);
+        assert_eq!(
+            config.chain_config().unwrap().epoch(Height::Dragon),
+            1_500_000
+        );
+        let round_trip: Config = toml::from_str(&toml::to_string(&config).unwrap()).unwrap();
+        assert_eq!(round_trip, config);
+
+        let config: Config = toml::from_str(
+            r#"
+[chain]
+type = "mainnet"
+
+[chain.height_overrides]
+Breeze = 1500000
+"#,
+        )
+        .unwrap();
+        assert!(config.chain_config().is_err());
+    }
+
+    #[test]
+    fn test_chain_policy_overrides() {
+        let config: Config = toml::from_str(
+            r#"
+[chain]
+type = "butterflynet"
+
+[chain.policy_overrides]
+minimum_consensus_power = 4096
+pre_commit_challenge_delay = 5
+valid_pre_commit_proof_type = ["StackedDRG2KiBV1P1"]
+valid_post_proof_type = ["StackedDRGWindow2KiBV1P1"]
+"#,
+        )
+        .unwrap();
+        let policy = config.chain_config().unwrap().policy;
+        assert_eq!(policy.minimum_consensus_power, 4096.into());
+        assert_eq!(policy.pre_commit_challenge_delay, 5);
+        assert_eq!(
+            policy
+                .valid_pre_commit_proof_type
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec![RegisteredSealProofV3::StackedDRG2KiBV1P1]
+        );
+        assert_eq!(
+            policy.valid_post_proof_type.into_iter().collect::<Vec<_>>(),
+            vec![RegisteredPoStProofV3::StackedDRGWindow2KiBV1P1]
+        );
+        let round_trip: Config = toml::from_str(&toml::to_string(&config).unwrap()).unwrap();
+        assert_eq!(round_trip, config);
+
+        let config: Config = toml::from_str(
+            r#"
+[chain]
+type = "mainnet"
+
+[chain.policy_overrides]
+pre_commit_challenge_delay = 5
+"#,
+        )
+        .unwrap();
+        assert!(config.chain_config().is_err());
+    }
 
     #[quickcheck]
     fn test_config_all_params_under_section(config: Config) {
@@ -6,11 +6,12 @@ mod config;
 
 use std::{
     net::SocketAddr,
+    num::NonZeroU32,
     path::{Path, PathBuf},
 };
 
 use crate::cli_shared::read_config;
-use crate::networks::NetworkChain;
+use crate::networks::{ActorBundleOverride, ChainConfig, NetworkChain};
 use crate::utils::io::read_file_to_string;
 use crate::utils::misc::LoggingColor;
 use ahash::HashSet;
@@ -88,6 +89,10 @@ pub struct CliOpts {
     /// Import a chain from a local CAR file or URL
     #[arg(long)]
     pub import_chain: Option<String>,
+    /// Number of workers verifying the blocks of an imported snapshot
+    /// (default is the number of CPUs)
+    #[arg(long)]
+    pub import_workers: Option<NonZeroU32>,
     /// Skips loading CAR file and uses header to index chain. Assumes a
     /// pre-loaded database
     #[arg(long)]
@@ -108,6 +113,15 @@ pub struct CliOpts {
     /// Choose network chain to sync to
     #[arg(long)]
     pub chain: Option<NetworkChain>,
+    /// Load the chain configuration from a TOML or JSON spec file, e.g. to
+    /// run a custom devnet. Takes precedence over `--chain`
+    #[arg(long)]
+    pub chain_spec: Option<PathBuf>,
+    /// Load an actor bundle, written `<manifest-cid>=<path-or-url>`, in place
+    /// of the built-in bundle of the upgrade to its actors version. Can be
+    /// repeated
+    #[arg(long)]
+    pub actor_bundle: Vec<ActorBundleOverride>,
     /// Daemonize Forest process
     #[arg(long)]
     pub detach: bool,
@@ -158,6 +172,15 @@ impl CliOpts {
     pub fn to_config(&self) -> Result<(Config, Option<ConfigPath>), anyhow::Error> {
         let (path, mut cfg) = read_config(self.config.as_ref(), self.chain.clone())?;
 
+        if let Some(chain_spec) = &self.chain_spec {
+            cfg.client.chain_spec = Some(chain_spec.clone());
+        }
+        if let Some(chain_spec) = &cfg.client.chain_spec {
+            cfg.chain.network = ChainConfig::from_file(chain_spec)?.network;
+        }
+        cfg.chain
+            .actor_bundles
+            .extend(self.actor_bundle.iter().cloned());
         if let Some(genesis_file) = &self.genesis {
             cfg.client.genesis_file = Some(genesis_file.to_owned());
         }
@@ -209,6 +232,9 @@ impl CliOpts {
         if let Some(skip_load) = self.skip_load {
             cfg.client.skip_load = skip_load;
         }
+        if let Some(import_workers) = self.import_workers {
+            cfg.client.import_workers = Some(import_workers);
+        }
 
         cfg.network.kademlia = self.kademlia.unwrap_or(cfg.network.kademlia);
         cfg.network.mdns = self.mdns.unwrap_or(cfg.network.mdns);
@@ -240,6 +266,20 @@ pub struct CliRpcOpts {
     /// Admin token to interact with the node
     #[arg(long)]
     pub token: Option<String>,
+    /// Output format of the commands that support it
+    #[arg(long, global = true, value_enum, default_value_t)]
+    pub output: OutputFormat,
+}
+
+/// Output format of the CLI commands. In JSON mode, commands print only the
+/// JSON result to stdout, while logs and progress bars go to stderr.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable text
+    #[default]
+    Text,
+    /// The Lotus JSON of the result, or a structure documented by the command
+    Json,
 }
 
 #[derive(Debug, PartialEq)]
@@ -80,12 +80,22 @@ pub fn setup_logger(opts: &CliOpts) -> (Option<tracing_loki::BackgroundTask>, Op
 
 // Log warnings to stderr
 pub fn setup_minimal_logger() {
+    setup_stderr_logger(default_tool_filter())
+}
+
+// Log to stderr at the level of the daemon, so that the output of the commands
+// is alone in stdout
+pub fn setup_cli_logger() {
+    setup_stderr_logger(default_env_filter())
+}
+
+fn setup_stderr_logger(default_filter: EnvFilter) {
     tracing_subscriber::registry()
         .with(
             tracing_subscriber::fmt::Layer::new()
                 .with_ansi(LoggingColor::Auto.coloring_enabled())
                 .with_writer(std::io::stderr)
-                .with_filter(get_env_filter(default_tool_filter())),
+                .with_filter(get_env_filter(default_filter)),
         )
         .init();
 }
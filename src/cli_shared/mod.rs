@@ -16,7 +16,7 @@ pub use tikv_jemallocator;
 
 /// Gets chain data directory
 pub fn chain_path(config: &Config) -> PathBuf {
-    PathBuf::from(&config.client.data_dir).join(config.chain.to_string())
+    PathBuf::from(&config.client.data_dir).join(config.chain.network.to_string())
 }
 
 pub fn read_config(
@@ -33,7 +33,7 @@ pub fn read_config(
         None => (None, Config::default()),
     };
     if let Some(chain) = chain_opt {
-        config.chain = chain;
+        config.chain.network = chain;
     }
     Ok((path, config))
 }
@@ -47,7 +47,7 @@ mod tests {
         let (config_path, config) = read_config(None, None).unwrap();
 
         assert!(config_path.is_none());
-        assert_eq!(config.chain, NetworkChain::Mainnet);
+        assert_eq!(config.chain.network, NetworkChain::Mainnet);
     }
 
     #[test]
@@ -55,7 +55,7 @@ mod tests {
         let (config_path, config) = read_config(None, Some(NetworkChain::Calibnet)).unwrap();
 
         assert!(config_path.is_none());
-        assert_eq!(config.chain, NetworkChain::Calibnet);
+        assert_eq!(config.chain.network, NetworkChain::Calibnet);
     }
 
     #[test]
@@ -63,7 +63,7 @@ mod tests {
         let (config_path, config) = read_config(None, Some(NetworkChain::Butterflynet)).unwrap();
 
         assert!(config_path.is_none());
-        assert_eq!(config.chain, NetworkChain::Butterflynet);
+        assert_eq!(config.chain.network, NetworkChain::Butterflynet);
     }
 
     #[test]
@@ -76,7 +76,7 @@ mod tests {
         let (config_path, config) = read_config(Some(&path), None).unwrap();
 
         assert_eq!(config_path.unwrap(), ConfigPath::Cli(path));
-        assert_eq!(config.chain, NetworkChain::Mainnet);
+        assert_eq!(config.chain.network, NetworkChain::Mainnet);
         assert_eq!(config, default_config);
     }
 }
@@ -9,11 +9,16 @@ use std::{
 
 use crate::{
     networks::NetworkChain,
-    utils::{retry, RetryArgs},
+    utils::{io::WithProgress, net::global_http_client, retry, RetryArgs},
 };
 use anyhow::{bail, Context as _};
 use chrono::NaiveDate;
-use tracing::event;
+use futures::TryStreamExt as _;
+use reqwest::{header, StatusCode};
+use sha2::{Digest as _, Sha256};
+use tap::Pipe as _;
+use tokio::io::AsyncWriteExt as _;
+use tracing::{event, info};
 use url::Url;
 
 use crate::cli_shared::snapshot::parse::ParsedFilename;
@@ -66,26 +71,29 @@ pub async fn fetch(
     chain: &NetworkChain,
     vendor: TrustedVendor,
 ) -> anyhow::Result<PathBuf> {
-    let (url, _len, path) = peek(vendor, chain).await?;
+    let (url, _len, path, sha256) = peek(vendor, chain).await?;
     let (date, height, forest_format) = ParsedFilename::parse_str(&path)
         .context("unexpected path format")?
         .date_and_height_and_forest();
     let filename = filename(vendor, chain, date, height, forest_format);
 
-    download_file_with_retry(&url, directory, &filename).await
+    download_file_with_retry(&url, directory, &filename, sha256.as_deref()).await
 }
 
+/// Downloads the file at `url`, resuming from where failed attempts stopped.
+/// See [`download_http`].
 pub async fn download_file_with_retry(
     url: &Url,
     directory: &Path,
     filename: &str,
+    sha256: Option<&str>,
 ) -> anyhow::Result<PathBuf> {
     Ok(retry(
         RetryArgs {
             timeout: None,
             ..Default::default()
         },
-        || download_http(url, directory, filename),
+        || download_http(url, directory, filename, sha256),
     )
     .await?)
 }
@@ -94,10 +102,11 @@ pub async fn download_file_with_retry(
 /// - The final URL after redirection(s)
 /// - The size of the snapshot from this vendor on this chain
 /// - The filename of the snapshot
+/// - The SHA-256 digest of the snapshot, if the vendor publishes it
 pub async fn peek(
     vendor: TrustedVendor,
     chain: &NetworkChain,
-) -> anyhow::Result<(Url, u64, String)> {
+) -> anyhow::Result<(Url, u64, String, Option<String>)> {
     let stable_url = stable_url(vendor, chain)?;
     // issue an actual GET, so the content length will be of the body
     // (we never actually fetch the body)
@@ -113,12 +122,15 @@ pub async fn peek(
         .headers()
         .get(reqwest::header::CONTENT_DISPOSITION)
         .and_then(parse_content_disposition);
+    let size = response
+        .content_length()
+        .context("no content-length header")?;
+    let sha256 = fetch_checksum(&final_url).await;
     Ok((
         final_url,
-        response
-            .content_length()
-            .context("no content-length header")?,
+        size,
         cd_path.context("no content-disposition filepath")?,
+        sha256,
     ))
 }
 
@@ -132,12 +144,21 @@ fn parse_content_disposition(value: &reqwest::header::HeaderValue) -> Option<Str
     Some(cap.get(1)?.as_str().to_owned())
 }
 
-/// Download the file at `url` with a private HTTP client, returning the path to the downloaded file
-async fn download_http(url: &Url, directory: &Path, filename: &str) -> anyhow::Result<PathBuf> {
+/// Download the file at `url`, returning the path to the downloaded file.
+///
+/// The file is downloaded next to its destination first. If that is interrupted,
+/// the next attempt resumes from there, provided that the server supports range
+/// requests and that the file has the same `ETag` or `Last-Modified` date as when
+/// the download started. If `sha256` is given, the file is checked against it.
+async fn download_http(
+    url: &Url,
+    directory: &Path,
+    filename: &str,
+    sha256: Option<&str>,
+) -> anyhow::Result<PathBuf> {
     let dst_path = directory.join(filename);
     let destination = dst_path.display();
     event!(target: "forest::snapshot", tracing::Level::INFO, %url, %destination, "downloading snapshot");
-    let mut reader = crate::utils::net::reader(url.as_str()).await?;
     let tmp_dst_path = {
         // like `crdownload` for the chrome browser
         const DOWNLOAD_EXTENSION: &str = "frdownload";
@@ -152,17 +173,138 @@ async fn download_http(url: &Url, directory: &Path, filename: &str) -> anyhow::R
         }
         path
     };
-    let mut tempfile = tokio::fs::File::create(&tmp_dst_path)
-        .await
-        .context("couldn't create destination file")?;
-    tokio::io::copy(&mut reader, &mut tempfile)
-        .await
-        .context("couldn't download file")?;
+    // Identifies the version of the file being downloaded
+    let validator_path = {
+        let mut path = tmp_dst_path.clone().into_os_string();
+        path.push(".validator");
+        PathBuf::from(path)
+    };
+
+    let partial = match (
+        tokio::fs::metadata(&tmp_dst_path).await,
+        tokio::fs::read_to_string(&validator_path).await,
+    ) {
+        (Ok(metadata), Ok(validator)) if metadata.len() > 0 => Some((metadata.len(), validator)),
+        _ => None,
+    };
+    let mut request = global_http_client().get(url.clone());
+    if let Some((offset, validator)) = &partial {
+        request = request
+            .header(header::RANGE, format!("bytes={offset}-"))
+            .header(header::IF_RANGE, validator.trim());
+    }
+    let response = request.send().await?;
+    if response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+        // The partial file can't be resumed, start over on the next attempt
+        remove_partial_download(&tmp_dst_path, &validator_path).await;
+        bail!("couldn't resume the download of {url}");
+    }
+    let response = response
+        .error_for_status()
+        .context("server returned an error response")?;
+
+    let (file, offset) = match partial {
+        Some((offset, _)) if response.status() == StatusCode::PARTIAL_CONTENT => {
+            info!(
+                "Resuming download at {}",
+                human_bytes::human_bytes(offset as f64)
+            );
+            let file = tokio::fs::OpenOptions::new()
+                .append(true)
+                .open(&tmp_dst_path)
+                .await
+                .context("couldn't open partially downloaded file")?;
+            (file, offset)
+        }
+        // Either a fresh download, or the file changed since the partial
+        // download
+        _ => {
+            let validator = [header::ETAG, header::LAST_MODIFIED]
+                .into_iter()
+                .find_map(|name| response.headers().get(name)?.to_str().ok());
+            match validator {
+                Some(validator) => tokio::fs::write(&validator_path, validator).await?,
+                None => {
+                    tokio::fs::remove_file(&validator_path).await.ok();
+                }
+            }
+            let file = tokio::fs::File::create(&tmp_dst_path)
+                .await
+                .context("couldn't create destination file")?;
+            (file, 0)
+        }
+    };
+
+    let total = offset + response.content_length().unwrap_or_default();
+    let mut reader = WithProgress::wrap_async_read(
+        "Downloading",
+        response
+            .bytes_stream()
+            .map_err(std::io::Error::other)
+            .pipe(tokio_util::io::StreamReader::new),
+        total,
+    )
+    .bytes()
+    .resumed_at(offset)
+    .with_eta();
+    let mut writer = tokio::io::BufWriter::new(file);
+    let copied = tokio::io::copy(&mut reader, &mut writer).await;
+    // Keep what was received so far, to resume from it on the next attempt
+    writer.shutdown().await?;
+    copied.context("couldn't download file")?;
+
+    if let Some(expected) = sha256 {
+        let path = tmp_dst_path.clone();
+        let actual = tokio::task::spawn_blocking(move || sha256_file(&path)).await??;
+        if !actual.eq_ignore_ascii_case(expected) {
+            remove_partial_download(&tmp_dst_path, &validator_path).await;
+            bail!("checksum mismatch for {url}: expected sha256 {expected}, got {actual}");
+        }
+        info!("Verified sha256 checksum of {destination}");
+    }
     std::fs::rename(&tmp_dst_path, &dst_path).context("couldn't rename file")?;
+    tokio::fs::remove_file(&validator_path).await.ok();
 
     Ok(dst_path)
 }
 
+async fn remove_partial_download(tmp_dst_path: &Path, validator_path: &Path) {
+    tokio::fs::remove_file(tmp_dst_path).await.ok();
+    tokio::fs::remove_file(validator_path).await.ok();
+}
+
+/// Returns the hex-encoded SHA-256 digest of the file at `path`.
+fn sha256_file(path: &Path) -> anyhow::Result<String> {
+    let mut hasher = Sha256::new();
+    std::io::copy(
+        &mut std::io::BufReader::new(std::fs::File::open(path)?),
+        &mut hasher,
+    )?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Returns the SHA-256 digest vendors publish alongside the file at `url`, as
+/// `<url>.sha256sum` in the format of `sha256sum`, if there is one.
+pub async fn fetch_checksum(url: &Url) -> Option<String> {
+    let checksum_url = Url::parse(&format!("{url}.sha256sum")).ok()?;
+    let response = global_http_client()
+        .get(checksum_url)
+        .send()
+        .await
+        .ok()?
+        .error_for_status()
+        .ok()?;
+    parse_sha256sum(&response.text().await.ok()?)
+}
+
+/// Parses the digest out of a line of `sha256sum` output, e.g.
+/// `<64 hex digits>  forest_snapshot_calibnet_2023-09-14_height_911888.forest.car.zst`
+fn parse_sha256sum(text: &str) -> Option<String> {
+    let digest = text.split_whitespace().next()?;
+    (digest.len() == 64 && digest.chars().all(|c| c.is_ascii_hexdigit()))
+        .then(|| digest.to_lowercase())
+}
+
 /// Also defines an `ALL_URLS` constant for test purposes
 macro_rules! define_urls {
     ($($vis:vis const $name:ident: &str = $value:literal;)* $(,)?) => {
@@ -459,8 +601,190 @@ mod parse {
 
 #[cfg(test)]
 mod tests {
-    use super::parse_content_disposition;
+    use super::*;
+    use axum::body::Body;
+    use axum::extract::State;
+    use axum::response::IntoResponse;
+    use bytes::Bytes;
+    use futures::StreamExt as _;
+    use once_cell::sync::Lazy;
+    use parking_lot::Mutex;
+    use rand::Rng;
     use reqwest::header::HeaderValue;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    static DATA: Lazy<Bytes> = Lazy::new(|| {
+        let mut rng = rand::thread_rng();
+        (0..64 * 1024).map(|_| rng.gen()).collect()
+    });
+
+    #[derive(Default)]
+    struct Server {
+        etag: &'static str,
+        /// Drop the connection half-way through the next full response
+        drop_next: bool,
+        /// `Range` headers of the requests so far
+        ranges: Vec<Option<String>>,
+    }
+
+    /// Serves [`DATA`], honoring `Range` and `If-Range` headers.
+    async fn handle_request(
+        State(server): State<Arc<Mutex<Server>>>,
+        headers: http::HeaderMap,
+    ) -> impl IntoResponse {
+        let mut server = server.lock();
+        let range = headers
+            .get(http::header::RANGE)
+            .map(|it| it.to_str().unwrap().to_owned());
+        server.ranges.push(range.clone());
+        let etag = server.etag;
+        let start = match (range, headers.get(http::header::IF_RANGE)) {
+            (Some(range), Some(if_range)) if if_range == etag => range
+                .trim_start_matches("bytes=")
+                .trim_end_matches('-')
+                .parse::<usize>()
+                .unwrap(),
+            _ => 0,
+        };
+        let (status, body) = if start > 0 {
+            (
+                http::StatusCode::PARTIAL_CONTENT,
+                Body::from(DATA.slice(start..)),
+            )
+        } else if std::mem::take(&mut server.drop_next) {
+            let half = DATA.slice(..DATA.len() / 2);
+            (
+                http::StatusCode::OK,
+                Body::from_stream(futures::stream::once(async { anyhow::Ok(half) }).chain(
+                    futures::stream::once(async {
+                        // Let the first half reach the client
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                        Err(anyhow::anyhow!("connection dropped"))
+                    }),
+                )),
+            )
+        } else {
+            (http::StatusCode::OK, Body::from(DATA.clone()))
+        };
+        let len = (DATA.len() - start).to_string();
+        let headers = [
+            (http::header::ACCEPT_RANGES, "bytes".to_owned()),
+            (http::header::ETAG, etag.to_owned()),
+            (http::header::CONTENT_LENGTH, len),
+        ];
+        (status, headers, body)
+    }
+
+    async fn serve(server: Server) -> (Url, Arc<Mutex<Server>>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!(
+            "http://{}/snapshot.car.zst",
+            listener.local_addr().unwrap()
+        ))
+        .unwrap();
+        let server = Arc::new(Mutex::new(server));
+        let app = axum::Router::new()
+            .route("/snapshot.car.zst", axum::routing::get(handle_request))
+            .with_state(server.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (url, server)
+    }
+
+    fn data_sha256() -> String {
+        hex::encode(Sha256::digest(&DATA[..]))
+    }
+
+    #[tokio::test]
+    async fn download_resumes_after_dropped_connection() {
+        let (url, server) = serve(Server {
+            etag: "\"v1\"",
+            drop_next: true,
+            ..Default::default()
+        })
+        .await;
+        let dir = tempfile::tempdir().unwrap();
+        let partial = dir.path().join("snapshot.car.zst.frdownload");
+
+        download_http(&url, dir.path(), "snapshot.car.zst", None)
+            .await
+            .unwrap_err();
+        let partial_len = std::fs::metadata(&partial).unwrap().len();
+        assert!(partial_len > 0 && partial_len < DATA.len() as u64);
+
+        let path = download_http(&url, dir.path(), "snapshot.car.zst", Some(&data_sha256()))
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read(path).unwrap(), *DATA);
+        assert_eq!(
+            server.lock().ranges,
+            [None, Some(format!("bytes={partial_len}-"))]
+        );
+        // Only the downloaded file is left
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn download_restarts_if_file_changed() {
+        let (url, server) = serve(Server {
+            etag: "\"v1\"",
+            drop_next: true,
+            ..Default::default()
+        })
+        .await;
+        let dir = tempfile::tempdir().unwrap();
+
+        download_http(&url, dir.path(), "snapshot.car.zst", None)
+            .await
+            .unwrap_err();
+        server.lock().etag = "\"v2\"";
+        let path = download_http(&url, dir.path(), "snapshot.car.zst", None)
+            .await
+            .unwrap();
+        // The server ignored the range, and sent the whole new file
+        assert_eq!(std::fs::read(path).unwrap(), *DATA);
+        assert_eq!(server.lock().ranges.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn download_checks_sha256() {
+        let (url, _server) = serve(Server {
+            etag: "\"v1\"",
+            ..Default::default()
+        })
+        .await;
+        let dir = tempfile::tempdir().unwrap();
+
+        let wrong = "00".repeat(32);
+        let err = download_http(&url, dir.path(), "snapshot.car.zst", Some(&wrong))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("checksum mismatch"), "{err}");
+        // Nothing is left to resume from
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+
+        download_http(&url, dir.path(), "snapshot.car.zst", Some(&data_sha256()))
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn sha256sum() {
+        let digest = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+        assert_eq!(
+            parse_sha256sum(&format!(
+                "{digest}  forest_snapshot_calibnet.forest.car.zst\n"
+            )),
+            Some(digest.to_owned())
+        );
+        assert_eq!(
+            parse_sha256sum(&digest.to_uppercase()),
+            Some(digest.to_owned())
+        );
+        assert_eq!(parse_sha256sum(""), None);
+        assert_eq!(parse_sha256sum("not a digest"), None);
+        assert_eq!(parse_sha256sum(&digest[1..]), None);
+    }
 
     #[test]
     fn content_disposition_forest() {
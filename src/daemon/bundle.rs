@@ -2,7 +2,8 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use crate::{
-    networks::{ActorBundleInfo, NetworkChain, ACTOR_BUNDLES},
+    networks::{ActorBundleInfo, ActorBundleOverride, ChainConfig, NetworkChain, ACTOR_BUNDLES},
+    shim::machine::BuiltinActorManifest,
     utils::{
         db::{
             car_stream::{CarBlock, CarStream},
@@ -12,9 +13,10 @@ use crate::{
     },
 };
 use ahash::HashSet;
-use anyhow::ensure;
+use anyhow::{ensure, Context as _};
 use futures::{stream::FuturesUnordered, TryStreamExt};
 use fvm_ipld_blockstore::Blockstore;
+use reqwest::Url;
 use std::mem::discriminant;
 use std::{io::Cursor, path::Path};
 use tracing::{info, warn};
@@ -36,6 +38,54 @@ pub async fn load_actor_bundles(
     }
 }
 
+/// Loads the given actor bundles into the blockstore, and registers each of
+/// them as the bundle of the upgrade to its actors version, in place of the
+/// built-in one.
+pub async fn load_actor_bundle_overrides(
+    db: &impl Blockstore,
+    bundles: &[ActorBundleOverride],
+    chain_config: &mut ChainConfig,
+) -> anyhow::Result<()> {
+    for bundle in bundles {
+        load_actor_bundle_override(db, bundle)
+            .await
+            .with_context(|| format!("failed to load actor bundle {bundle}"))?;
+        let actors_version =
+            BuiltinActorManifest::load_manifest(db, &bundle.manifest)?.actors_version()?;
+        let height = chain_config.set_actor_bundle(actors_version, bundle.manifest)?;
+        warn!(
+            "Using actor bundle {} for the {height} upgrade to actors v{actors_version}",
+            bundle.manifest
+        );
+    }
+    Ok(())
+}
+
+async fn load_actor_bundle_override(
+    db: &impl Blockstore,
+    ActorBundleOverride { manifest, source }: &ActorBundleOverride,
+) -> anyhow::Result<()> {
+    let bytes = match Url::parse(source) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") => {
+            http_get(&url).await?.bytes().await?.to_vec()
+        }
+        _ => tokio::fs::read(source).await?,
+    };
+    let car = CarStream::new(Cursor::new(bytes)).await?;
+    ensure!(car.header.version == 1);
+    ensure!(car.header.roots.len() == 1);
+    ensure!(car.header.roots.first() == manifest);
+    for block in car.try_collect::<Vec<_>>().await? {
+        ensure!(
+            block.valid(),
+            "bundle contains an invalid block, cid {}",
+            block.cid
+        );
+        db.put_keyed(&block.cid, &block.data)?;
+    }
+    Ok(())
+}
+
 pub async fn load_actor_bundles_from_path(
     db: &impl Blockstore,
     network: &NetworkChain,
@@ -86,26 +136,13 @@ pub async fn load_actor_bundles_from_server(
                 // actor bundle.
                 discriminant(network) == discriminant(&bundle.network)
             })
-            .map(
-                |ActorBundleInfo {
-                     manifest: root,
-                     url,
-                     alt_url,
-                     network: _,
-                 }| async move {
-                    let response = if let Ok(response) = http_get(url).await {
-                        response
-                    } else {
-                        warn!("failed to download bundle from primary URL, trying alternative URL");
-                        http_get(alt_url).await?
-                    };
-                    let bytes = response.bytes().await?;
-                    let header = load_car(db, Cursor::new(bytes)).await?;
-                    ensure!(header.roots.len() == 1);
-                    ensure!(header.roots.first() == root);
-                    Ok(())
-                },
-            ),
+            .map(|info @ ActorBundleInfo { manifest: root, .. }| async move {
+                let bytes = info.download().await?;
+                let header = load_car(db, Cursor::new(bytes)).await?;
+                ensure!(header.roots.len() == 1);
+                ensure!(header.roots.first() == root);
+                Ok(())
+            }),
     )
     .try_collect::<Vec<_>>()
     .await?;
@@ -2,21 +2,24 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use crate::blocks::Tipset;
+use crate::cli_shared::cli::Client;
 use crate::cli_shared::snapshot;
 use crate::db::car::forest::FOREST_CAR_FILE_EXTENSION;
 use crate::db::car::{ForestCar, ManyCar};
-use crate::utils::db::car_stream::CarStream;
+use crate::utils::db::car_stream::{CarBlock, CarStream};
 use crate::utils::io::EitherMmapOrRandomAccessFile;
 use anyhow::Context as _;
-use futures::TryStreamExt;
+use futures::{Stream, StreamExt as _, TryStreamExt as _};
 use std::ffi::OsStr;
 use std::fs;
 use std::io;
+use std::num::NonZeroUsize;
 use std::{
     path::{Path, PathBuf},
     time,
 };
-use tokio::io::AsyncWriteExt;
+use tokio::io::{AsyncBufRead, AsyncWriteExt};
+use tokio_stream::wrappers::ReceiverStream;
 use tracing::{debug, info};
 use url::Url;
 use walkdir::WalkDir;
@@ -54,6 +57,7 @@ pub async fn import_chain_as_forest_car(
     from_path: &Path,
     forest_car_db_dir: &Path,
     consume_snapshot_file: bool,
+    options: ImportOptions,
 ) -> anyhow::Result<(PathBuf, Tipset)> {
     info!("Importing chain from snapshot at: {}", from_path.display());
 
@@ -80,7 +84,8 @@ pub async fn import_chain_as_forest_car(
         // Use another temp file to make sure all final `.forest.car.zst` files are complete and valid.
         let forest_car_db_temp_path =
             tempfile::NamedTempFile::new_in(forest_car_db_dir)?.into_temp_path();
-        transcode_into_forest_car(&downloaded_car_temp_path, &forest_car_db_temp_path).await?;
+        transcode_into_forest_car(&downloaded_car_temp_path, &forest_car_db_temp_path, options)
+            .await?;
         forest_car_db_temp_path.persist(&forest_car_db_path)?;
     }
 
@@ -94,7 +99,10 @@ pub async fn import_chain_as_forest_car(
     Ok((forest_car_db_path, ts))
 }
 
+/// Downloads the file at `url` to `destination`, checking it against the
+/// SHA-256 digest published alongside it, if any.
 pub async fn download_to(url: &Url, destination: &Path) -> anyhow::Result<()> {
+    let sha256 = snapshot::fetch_checksum(url).await;
     snapshot::download_file_with_retry(
         url,
         destination.parent().with_context(|| {
@@ -107,6 +115,7 @@ pub async fn download_to(url: &Url, destination: &Path) -> anyhow::Result<()> {
             .file_name()
             .and_then(OsStr::to_str)
             .with_context(|| format!("Error getting the file name of {}", destination.display()))?,
+        sha256.as_deref(),
     )
     .await?;
 
@@ -125,7 +134,49 @@ fn move_or_copy_file(from: &Path, to: &Path, consume: bool) -> io::Result<()> {
     }
 }
 
-async fn transcode_into_forest_car(from: &Path, to: &Path) -> anyhow::Result<()> {
+/// Tuning of the snapshot import pipeline: a task reads and decompresses the
+/// CAR file in chunks of blocks, a pool of workers verifies the CIDs of the
+/// chunks, and the verified blocks are written in order. The stages are
+/// connected by bounded queues, so that memory use doesn't grow with the size
+/// of the snapshot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImportOptions {
+    /// Number of workers verifying chunks concurrently
+    pub workers: NonZeroUsize,
+    /// Number of blocks in a chunk
+    pub chunk_size: NonZeroUsize,
+    /// Number of chunks read ahead of the workers
+    pub buffer_size: NonZeroUsize,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self::from(&Client::default())
+    }
+}
+
+impl From<&Client> for ImportOptions {
+    fn from(client: &Client) -> Self {
+        Self {
+            workers: client
+                .import_workers
+                .and_then(|workers| NonZeroUsize::try_from(workers).ok())
+                .unwrap_or_else(|| {
+                    std::thread::available_parallelism().unwrap_or(NonZeroUsize::MIN)
+                }),
+            chunk_size: NonZeroUsize::new(client.chunk_size.0 as usize)
+                .unwrap_or(NonZeroUsize::MIN),
+            buffer_size: NonZeroUsize::new(client.buffer_size.0 as usize)
+                .unwrap_or(NonZeroUsize::MIN),
+        }
+    }
+}
+
+async fn transcode_into_forest_car(
+    from: &Path,
+    to: &Path,
+    options: ImportOptions,
+) -> anyhow::Result<()> {
     let car_stream = CarStream::new(tokio::io::BufReader::new(
         tokio::fs::File::open(from).await?,
     ))
@@ -133,18 +184,86 @@ async fn transcode_into_forest_car(from: &Path, to: &Path) -> anyhow::Result<()>
     let roots = car_stream.header.roots.clone();
 
     let mut writer = tokio::io::BufWriter::new(tokio::fs::File::create(to).await?);
-    let frames = crate::db::car::forest::Encoder::compress_stream_default(
-        car_stream.map_err(anyhow::Error::from),
-    );
+    let blocks = verified_blocks(car_stream, options);
+    #[cfg(test)]
+    let blocks = blocks.and_then(|block| async {
+        test::on_verified().await;
+        Ok(block)
+    });
+    let blocks = std::pin::pin!(blocks);
+    let frames = crate::db::car::forest::Encoder::compress_stream_default(blocks);
     crate::db::car::forest::Encoder::write(&mut writer, roots, frames).await?;
     writer.shutdown().await?;
 
     Ok(())
 }
 
+/// Reads and decompresses `car_stream` in a separate task, and verifies the
+/// CIDs of its blocks on `options.workers` blocking threads, keeping the order
+/// of the blocks. The reading task stops once the returned stream is dropped.
+fn verified_blocks(
+    car_stream: CarStream<impl AsyncBufRead + Send + Unpin + 'static>,
+    options: ImportOptions,
+) -> impl Stream<Item = anyhow::Result<CarBlock>> {
+    let (tx, rx) = tokio::sync::mpsc::channel(options.buffer_size.get());
+    tokio::spawn(async move {
+        let mut chunks = car_stream.chunks(options.chunk_size.get());
+        while let Some(chunk) = chunks.next().await {
+            if tx.send(chunk).await.is_err() {
+                // The import was cancelled
+                break;
+            }
+        }
+    });
+    ReceiverStream::new(rx)
+        .map(|chunk| tokio::task::spawn_blocking(move || verify_chunk(chunk)))
+        .buffered(options.workers.get())
+        .map(|verified| verified?)
+        .map_ok(|blocks| futures::stream::iter(blocks.into_iter().map(anyhow::Ok)))
+        .try_flatten()
+}
+
+fn verify_chunk(chunk: Vec<io::Result<CarBlock>>) -> anyhow::Result<Vec<CarBlock>> {
+    chunk
+        .into_iter()
+        .map(|block| {
+            let block = block?;
+            anyhow::ensure!(block.valid(), "invalid block {}", block.cid);
+            Ok(block)
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use std::cell::RefCell;
+
+    tokio::task_local! {
+        /// Number of blocks an import verifies before stalling, and the signal
+        /// that it stalled
+        static STALL_AFTER: RefCell<(usize, Option<tokio::sync::oneshot::Sender<()>>)>;
+    }
+
+    /// Awaited after each block verified by imports, stalling those within a
+    /// [`STALL_AFTER`] scope once they verified enough blocks.
+    pub(super) async fn on_verified() {
+        let stall = STALL_AFTER
+            .try_with(|stall| {
+                let (blocks_left, signal) = &mut *stall.borrow_mut();
+                *blocks_left -= 1;
+                if *blocks_left == 0 {
+                    signal.take().map(|signal| signal.send(()));
+                    true
+                } else {
+                    false
+                }
+            })
+            .unwrap_or(false);
+        if stall {
+            std::future::pending::<()>().await;
+        }
+    }
 
     #[tokio::test]
     async fn import_snapshot_from_file_valid() {
@@ -179,10 +298,126 @@ mod test {
 
     async fn import_snapshot_from_file(file_path: &str) -> anyhow::Result<()> {
         let temp = tempfile::Builder::new().tempdir()?;
-        let (path, ts) =
-            import_chain_as_forest_car(Path::new(file_path), temp.path(), false).await?;
+        let (path, ts) = import_chain_as_forest_car(
+            Path::new(file_path),
+            temp.path(),
+            false,
+            ImportOptions::default(),
+        )
+        .await?;
         assert!(path.is_file());
         assert!(ts.epoch() > 0);
         Ok(())
     }
+
+    async fn read_blocks(path: &Path) -> Vec<CarBlock> {
+        CarStream::new(tokio::io::BufReader::new(
+            tokio::fs::File::open(path).await.unwrap(),
+        ))
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap()
+    }
+
+    fn options(workers: usize, chunk_size: usize, buffer_size: usize) -> ImportOptions {
+        ImportOptions {
+            workers: NonZeroUsize::new(workers).unwrap(),
+            chunk_size: NonZeroUsize::new(chunk_size).unwrap(),
+            buffer_size: NonZeroUsize::new(buffer_size).unwrap(),
+        }
+    }
+
+    #[tokio::test]
+    async fn import_pipeline_keeps_blocks_and_head() {
+        let from = Path::new("test-snapshots/chain4.car.zst");
+        let expected = read_blocks(from).await;
+        let mut heads = vec![];
+        for options in [options(1, 1, 1), options(4, 7, 2), ImportOptions::default()] {
+            let temp = tempfile::tempdir().unwrap();
+            let to = temp.path().join("chain4.forest.car.zst");
+            transcode_into_forest_car(from, &to, options).await.unwrap();
+            assert_eq!(read_blocks(&to).await, expected, "{options:?}");
+            heads.push(
+                ForestCar::try_from(to.as_path())
+                    .unwrap()
+                    .heaviest_tipset()
+                    .unwrap(),
+            );
+        }
+        heads.dedup();
+        assert_eq!(heads.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn import_pipeline_rejects_invalid_block() {
+        let car_stream = CarStream::new(tokio::io::BufReader::new(
+            tokio::fs::File::open("test-snapshots/chain4.car")
+                .await
+                .unwrap(),
+        ))
+        .await
+        .unwrap();
+        let roots = car_stream.header.roots.clone();
+        let mut blocks: Vec<CarBlock> = car_stream.try_collect().await.unwrap();
+        let corrupted = blocks.get_mut(10).unwrap();
+        corrupted.data.push(0);
+        let corrupted_cid = corrupted.cid;
+
+        let temp = tempfile::tempdir().unwrap();
+        let from = temp.path().join("corrupted.car");
+        let mut writer = crate::utils::db::car_stream::CarWriter::new_carv1(
+            roots,
+            tokio::fs::File::create(&from).await.unwrap(),
+        )
+        .unwrap();
+        futures::SinkExt::send_all(&mut writer, &mut futures::stream::iter(blocks).map(Ok))
+            .await
+            .unwrap();
+        futures::SinkExt::close(&mut writer).await.unwrap();
+
+        let err = transcode_into_forest_car(&from, &temp.path().join("out"), options(3, 4, 1))
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), format!("invalid block {corrupted_cid}"));
+    }
+
+    // Cancelling an import at any point leaves no partial CAR file behind,
+    // so that the import can simply be started again.
+    #[tokio::test]
+    async fn cancelled_import_is_resumable() {
+        let from = Path::new("test-snapshots/chain4.car.zst");
+        let n_blocks = read_blocks(from).await.len();
+        let temp = tempfile::tempdir().unwrap();
+        for cancel_after in [1, n_blocks / 2, n_blocks - 1] {
+            let (tx, rx) = tokio::sync::oneshot::channel();
+            let import = STALL_AFTER.scope(
+                RefCell::new((cancel_after, Some(tx))),
+                import_chain_as_forest_car(from, temp.path(), false, options(2, 1, 1)),
+            );
+            tokio::select! {
+                result = import => panic!("import not cancelled after {cancel_after} blocks: {:?}", result.map(|(path, _)| path)),
+                _ = rx => {}
+            }
+            assert_eq!(fs::read_dir(temp.path()).unwrap().count(), 0);
+        }
+
+        // Completing the import
+        let (path, ts) =
+            import_chain_as_forest_car(from, temp.path(), false, ImportOptions::default())
+                .await
+                .unwrap();
+        assert!(ts.epoch() > 0);
+        assert!(path.is_file());
+        // Only complete CAR files are left
+        for entry in fs::read_dir(temp.path()).unwrap() {
+            let path = entry.unwrap().path();
+            assert!(
+                path.to_string_lossy().ends_with(FOREST_CAR_FILE_EXTENSION),
+                "{}",
+                path.display()
+            );
+        }
+    }
 }
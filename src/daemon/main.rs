@@ -101,7 +101,7 @@ where
         }
         check_for_unknown_keys(path.to_path_buf(), &cfg);
     } else {
-        info!("Using default {} config", cfg.chain);
+        info!("Using default {} config", cfg.chain.network);
     }
     if opts.dry_run {
         return Ok(());
@@ -5,29 +5,31 @@ pub mod bundle;
 pub mod db_util;
 pub mod main;
 
-use crate::auth::{create_token, generate_priv_key, ADMIN, JWT_IDENTIFIER};
+use crate::auth::{create_token, generate_priv_key, TokenRegistry, ADMIN, JWT_IDENTIFIER};
 use crate::blocks::Tipset;
 use crate::chain::ChainStore;
-use crate::chain_sync::ChainMuxer;
+use crate::chain_sync::{ChainMuxer, SyncStage};
 use crate::cli_shared::snapshot;
 use crate::cli_shared::{
     chain_path,
     cli::{CliOpts, Config},
 };
 
-use crate::daemon::db_util::{import_chain_as_forest_car, load_all_forest_cars};
+use crate::daemon::db_util::{import_chain_as_forest_car, load_all_forest_cars, ImportOptions};
 use crate::db::car::ManyCar;
 use crate::db::db_engine::{db_root, open_db};
-use crate::db::MarkAndSweep;
+use crate::db::setting_keys::NET_BOOTSTRAP_PEERS_KEY;
+use crate::db::{BlockstoreWithReadCache, MarkAndSweep, SettingsStoreExt};
 use crate::genesis::{get_network_name_from_genesis, read_genesis_header};
 use crate::key_management::{
     KeyStore, KeyStoreConfig, ENCRYPTED_KEYSTORE_NAME, FOREST_KEYSTORE_PHRASE_ENV,
 };
-use crate::libp2p::{Libp2pConfig, Libp2pService, PeerManager};
+use crate::libp2p::{Libp2pConfig, Libp2pService, Multiaddr, PeerManager};
 use crate::message_pool::{MessagePool, MpoolConfig, MpoolRpcProvider};
 use crate::networks::{ChainConfig, NetworkChain};
-use crate::rpc::start_rpc;
 use crate::rpc::RPCState;
+use crate::rpc::{load_tls_config, start_rpc, stop_rpc};
+use crate::rpc_client::{rpc_multiaddr, API_INFO_FILE};
 use crate::shim::address::{CurrentNetwork, Network};
 use crate::shim::clock::ChainEpoch;
 use crate::shim::version::NetworkVersion;
@@ -37,16 +39,17 @@ use crate::utils::{
     version::FOREST_VERSION_STRING,
 };
 use anyhow::{bail, Context as _};
-use bundle::load_actor_bundles;
+use bundle::{load_actor_bundle_overrides, load_actor_bundles};
 use dialoguer::console::Term;
 use dialoguer::theme::ColorfulTheme;
 use futures::{select, Future, FutureExt};
+use jsonrpsee::server::{stop_channel, StopHandle};
 use once_cell::sync::Lazy;
 use raw_sync_2::events::{Event, EventInit as _, EventState};
 use shared_memory::ShmemConf;
 use std::path::Path;
 use std::time::Duration;
-use std::{cell::RefCell, cmp, path::PathBuf, sync::Arc};
+use std::{cell::RefCell, path::PathBuf, sync::Arc};
 use tempfile::{Builder, TempPath};
 use tokio::{
     net::TcpListener,
@@ -113,9 +116,12 @@ fn maybe_increase_fd_limit() -> anyhow::Result<()> {
 pub async fn start_interruptable(opts: CliOpts, config: Config) -> anyhow::Result<()> {
     let mut terminate = signal(SignalKind::terminate())?;
     let (shutdown_send, mut shutdown_recv) = mpsc::channel(1);
+    let (rpc_stop_handle, rpc_server_handle) = stop_channel();
+    let rpc_drain_period = config.rpc.shutdown_drain_period;
+    let api_info_path = config.client.data_dir.join(API_INFO_FILE);
 
     let result = tokio::select! {
-        ret = start(opts, config, shutdown_send) => ret,
+        ret = start(opts, config, shutdown_send, rpc_stop_handle) => ret,
         _ = ctrl_c() => {
             info!("Keyboard interrupt.");
             Ok(())
@@ -129,20 +135,26 @@ pub async fn start_interruptable(opts: CliOpts, config: Config) -> anyhow::Resul
             Ok(())
         },
     };
+    // The RPC server outlives the other services, so that in-flight calls can
+    // finish
+    stop_rpc(rpc_server_handle, rpc_drain_period).await;
+    // Clients would otherwise keep calling a stopped node
+    let _ = std::fs::remove_file(api_info_path);
     crate::utils::io::terminal_cleanup();
     result
 }
 
-// Garbage collection interval, currently set at 10 hours.
-const GC_INTERVAL: Duration = Duration::from_secs(60 * 60 * 10);
-
 /// Starts daemon process
 pub(super) async fn start(
     opts: CliOpts,
     config: Config,
     shutdown_send: mpsc::Sender<()>,
+    rpc_stop_handle: StopHandle,
 ) -> anyhow::Result<()> {
-    let chain_config = Arc::new(ChainConfig::from_chain(&config.chain));
+    let mut chain_config = config.chain_config()?;
+    if let Some(genesis_file) = &config.client.genesis_file {
+        chain_config.genesis_file = Some(genesis_file.into());
+    }
     if chain_config.is_testnet() {
         CurrentNetwork::set_global(Network::Testnet);
     }
@@ -177,14 +189,26 @@ pub(super) async fn start(
     }
 
     let db_root_dir = db_root(&chain_data_path)?;
-    let db_writer = Arc::new(open_db(db_root_dir.clone(), config.db_config().clone())?);
+    let db_writer = Arc::new(BlockstoreWithReadCache::new(
+        open_db(db_root_dir.clone(), config.db_config().clone())?,
+        config.client.block_cache_size.as_usize(),
+    ));
     let db = Arc::new(ManyCar::new(db_writer.clone()));
     let forest_car_db_dir = db_root_dir.join("car_db");
     load_all_forest_cars(&db, &forest_car_db_dir)?;
 
     if config.client.load_actors {
-        load_actor_bundles(&db, &config.chain).await?;
+        load_actor_bundles(&db, &config.chain.network).await?;
     }
+    load_actor_bundle_overrides(&db, &config.chain.actor_bundles, &mut chain_config).await?;
+    chain_config.validate()?;
+    let persisted_bootstrap_peers = db_writer
+        .read_obj::<Vec<Multiaddr>>(NET_BOOTSTRAP_PEERS_KEY)?
+        .unwrap_or_default();
+    chain_config.bootstrap_peers = config
+        .network
+        .resolve_bootstrap_peers(&chain_config.bootstrap_peers, &persisted_bootstrap_peers);
+    let chain_config = Arc::new(chain_config);
 
     let mut services = JoinSet::new();
 
@@ -217,41 +241,26 @@ pub(super) async fn start(
     // Read Genesis file
     // * When snapshot command implemented, this genesis does not need to be
     //   initialized
-    let genesis_header = read_genesis_header(
-        config.client.genesis_file.as_ref(),
-        chain_config.genesis_bytes(&db).await?.as_deref(),
-        &db,
-    )
-    .await?;
+    let genesis_header = read_genesis_header(&chain_config, &db).await?;
+    if config.client.chain_spec.is_some() {
+        if let Some(genesis_cid) = &chain_config.genesis_cid {
+            anyhow::ensure!(
+                genesis_cid == &genesis_header.cid().to_string(),
+                "genesis {} does not match the chain spec genesis {genesis_cid}",
+                genesis_header.cid()
+            );
+        }
+    }
 
     // Initialize ChainStore
     let chain_store = Arc::new(ChainStore::new(
         Arc::clone(&db),
         db.writer().clone(),
+        db.writer().clone(),
         chain_config.clone(),
         genesis_header.clone(),
     )?);
 
-    if !opts.no_gc {
-        let mut db_garbage_collector = {
-            let chain_store = chain_store.clone();
-            let depth = cmp::max(
-                chain_config.policy.chain_finality * 2,
-                config.sync.recent_state_roots,
-            );
-
-            let get_heaviest_tipset = Box::new(move || chain_store.heaviest_tipset());
-
-            MarkAndSweep::new(
-                db_writer,
-                get_heaviest_tipset,
-                depth,
-                Duration::from_secs(chain_config.block_delay_secs as u64),
-            )
-        };
-        services.spawn(async move { db_garbage_collector.gc_loop(GC_INTERVAL).await });
-    }
-
     let publisher = chain_store.publisher();
 
     // Initialize StateManager
@@ -264,24 +273,21 @@ pub(super) async fn start(
     let state_manager = Arc::new(sm);
 
     let network_name = get_network_name_from_genesis(&genesis_header, &state_manager)?;
+    let network =
+        NetworkChain::from_genesis_or_devnet(genesis_header.cid(), Some(&chain_config), || {
+            Ok(network_name.clone())
+        })?;
 
-    info!("Using network :: {}", get_actual_chain_name(&network_name));
-    display_chain_logo(&config.chain);
+    info!("Using network :: {network}");
+    display_chain_logo(&config.chain.network);
     let (tipset_sink, tipset_stream) = flume::bounded(20);
 
-    // if bootstrap peers are not set, set them
-    let config = if config.network.bootstrap_peers.is_empty() {
-        let bootstrap_peers = chain_config.bootstrap_peers.clone();
-
-        Config {
-            network: Libp2pConfig {
-                bootstrap_peers,
-                ..config.network
-            },
-            ..config
-        }
-    } else {
-        config
+    let config = Config {
+        network: Libp2pConfig {
+            bootstrap_peers: chain_config.bootstrap_peers.clone(),
+            ..config.network
+        },
+        ..config
     };
 
     if opts.exit_after_init {
@@ -306,6 +312,8 @@ pub(super) async fn start(
 
     let network_rx = p2p_service.network_receiver();
     let network_send = p2p_service.network_sender();
+    let bandwidth = p2p_service.bandwidth();
+    let net_limits = p2p_service.connection_watermarks();
 
     // Initialize mpool
     let provider = MpoolRpcProvider::new(publisher.clone(), Arc::clone(&state_manager));
@@ -328,48 +336,130 @@ pub(super) async fn start(
         network_send.clone(),
         network_rx,
         Arc::new(Tipset::from(genesis_header)),
-        tipset_sink,
+        tipset_sink.clone(),
         tipset_stream,
         opts.stateless,
     )?;
     let bad_blocks = chain_muxer.bad_blocks_cloned();
+    let incoming_blocks = chain_muxer.incoming_blocks_cloned();
     let sync_state = chain_muxer.sync_state_cloned();
     services.spawn(async { Err(anyhow::anyhow!("{}", chain_muxer.await)) });
+    services.spawn(state_manager.clone().pre_migration_loop());
+
+    let gc = if !opts.no_gc {
+        let mut db_garbage_collector = {
+            let chain_store = chain_store.clone();
+            let depth = config.gc.retention_epochs(
+                chain_config.policy.chain_finality,
+                config.sync.recent_state_roots,
+            );
+
+            let get_heaviest_tipset = Box::new(move || chain_store.heaviest_tipset());
+            // Pause while catching up with the network
+            let sync_state = sync_state.clone();
+            let is_syncing = Box::new(move || {
+                matches!(
+                    sync_state.read().stage(),
+                    SyncStage::Headers | SyncStage::PersistHeaders | SyncStage::Messages
+                )
+            });
+
+            MarkAndSweep::new(
+                db_writer,
+                get_heaviest_tipset,
+                depth,
+                Duration::from_secs(chain_config.block_delay_secs as u64),
+            )
+            .with_pause(is_syncing)
+        };
+        let gc = db_garbage_collector.handle();
+        let interval = config.gc.interval;
+        services.spawn(async move { db_garbage_collector.gc_loop(interval).await });
+        Some(gc)
+    } else {
+        None
+    };
 
     // Start services
     if config.client.enable_rpc {
         let keystore_rpc = Arc::clone(&keystore);
+        let tokens = Arc::new(TokenRegistry::load(
+            chain_store.settings(),
+            config.client.max_token_exp,
+        ));
         let rpc_state_manager = Arc::clone(&state_manager);
         let rpc_chain_store = Arc::clone(&chain_store);
-        let rpc_address = config.client.rpc_address;
 
-        info!("JSON-RPC endpoint will listen at {rpc_address}");
+        let rpc_unix_socket = config.client.rpc_unix_socket_path.clone();
+        let rpc_config = config.rpc.clone();
+        let rpc_tls_config = match (
+            &config.client.rpc_tls_cert_path,
+            &config.client.rpc_tls_key_path,
+        ) {
+            (Some(cert_path), Some(key_path)) => Some(load_tls_config(cert_path, key_path)?),
+            (None, None) => None,
+            _ => bail!(
+                "both `rpc_tls_cert_path` and `rpc_tls_key_path` must be set to serve RPC over TLS"
+            ),
+        };
+
+        let mut rpc_listeners = vec![];
+        for rpc_address in std::iter::once(&config.client.rpc_address)
+            .chain(&config.client.rpc_additional_addresses)
+        {
+            let listener = TcpListener::bind(rpc_address)
+                .await
+                .with_context(|| format!("could not bind RPC to {rpc_address}"))?;
+            let local_addr = listener.local_addr()?;
+            info!("JSON-RPC endpoint listening at {local_addr}");
+            // The CLI is pointed at the main address
+            if rpc_listeners.is_empty() {
+                write_api_info(
+                    &config.client.data_dir,
+                    rpc_multiaddr(local_addr, rpc_tls_config.is_some()),
+                )?;
+            }
+            rpc_listeners.push(listener);
+        }
         let beacon = Arc::new(
             rpc_state_manager
                 .chain_config()
                 .get_beacon_schedule(chain_store.genesis_block_header().timestamp),
         );
 
-        services.spawn(async move {
+        // Not owned by `services`, so that it is not cancelled before draining
+        // when the daemon shuts down
+        let rpc_server = tokio::spawn(async move {
             start_rpc(
                 RPCState {
                     state_manager: Arc::clone(&rpc_state_manager),
                     keystore: keystore_rpc,
+                    tokens,
                     mpool,
                     bad_blocks,
                     sync_state,
                     network_send,
+                    tipset_send: tipset_sink,
+                    incoming_blocks,
                     network_name,
                     start_time,
                     beacon,
                     chain_store: rpc_chain_store,
+                    bandwidth,
+                    net_limits,
+                    gc,
                 },
-                rpc_address,
+                rpc_listeners,
+                rpc_tls_config,
+                rpc_unix_socket,
+                rpc_config,
                 FOREST_VERSION_STRING.as_str(),
                 shutdown_send,
+                rpc_stop_handle,
             )
             .await
         });
+        services.spawn(async move { rpc_server.await? });
     } else {
         debug!("RPC disabled.");
     };
@@ -404,6 +494,7 @@ pub(super) async fn start(
                 path,
                 &forest_car_db_dir,
                 config.client.consume_snapshot,
+                ImportOptions::from(&config.client),
             )
             .await?;
             db.read_only_files(std::iter::once(car_db_path.clone()))?;
@@ -414,6 +505,22 @@ pub(super) async fn start(
         }
     }
 
+    // Sample the epochs of the chain up to where they were sampled before,
+    // which walks all of an imported snapshot
+    {
+        let chain_store = state_manager.chain_store().clone();
+        let finality = chain_config.policy.chain_finality;
+        tokio::task::spawn_blocking(move || {
+            match chain_store
+                .chain_index
+                .backfill_epoch_samples(chain_store.heaviest_tipset(), finality)
+            {
+                Ok(written) => debug!("Sampled {written} epochs of the chain"),
+                Err(e) => warn!("Failed to sample the epochs of the chain: {e}"),
+            }
+        });
+    }
+
     if let (true, Some(validate_from)) = (config.client.snapshot, config.client.snapshot_height) {
         // We've been provided a snapshot and asked to validate it
         ensure_params_downloaded().await?;
@@ -468,7 +575,7 @@ async fn set_snapshot_path_if_needed(
     }
 
     let vendor = snapshot::TrustedVendor::default();
-    let chain = &config.chain;
+    let chain = &config.chain.network;
 
     // What height is our chain at right now, and what network version does that correspond to?
     let network_version = chain_config.network_version(epoch);
@@ -489,7 +596,7 @@ async fn set_snapshot_path_if_needed(
         }
         (true, false, false) => {
             // we need a snapshot, don't have one, and don't have permission to download one, so ask the user
-            let (url, num_bytes, _path) = crate::cli_shared::snapshot::peek(vendor, chain)
+            let (url, num_bytes, _path, _sha256) = crate::cli_shared::snapshot::peek(vendor, chain)
                 .await
                 .context("couldn't get snapshot size")?;
             // dialoguer will double-print long lines, so manually print the first clause ourselves,
@@ -537,6 +644,14 @@ fn handle_admin_token(opts: &CliOpts, config: &Config, keystore: &KeyStore) -> a
     Ok(())
 }
 
+/// Records the address of the RPC server, which the CLI reads when
+/// `FULLNODE_API_INFO` is not set.
+fn write_api_info(data_dir: &Path, multiaddr: Multiaddr) -> anyhow::Result<()> {
+    let path = data_dir.join(API_INFO_FILE);
+    std::fs::write(&path, multiaddr.to_string())
+        .with_context(|| format!("could not write {}", path.display()))
+}
+
 /// returns the first error with which any of the services end, or never returns at all
 // This should return anyhow::Result<!> once the `Never` type is stabilized
 async fn propagate_error(
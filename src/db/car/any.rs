@@ -30,6 +30,25 @@ impl<ReaderT: RandomAccessFileReader> AnyCar<ReaderT> {
     /// `.forest.car.zst`. This call may block for an indeterminate amount of
     /// time while data is decoded and indexed.
     pub fn new(reader: ReaderT) -> Result<Self> {
+        Self::new_with(reader, super::PlainCar::new)
+    }
+
+    /// Like [`Self::new`], but the index of a `.car` file at `car_path` is
+    /// saved in `index_dir`, see [`super::PlainCar::new_with_index_cache`].
+    pub fn new_with_index_cache(
+        reader: ReaderT,
+        car_path: &Path,
+        index_dir: &Path,
+    ) -> Result<Self> {
+        Self::new_with(reader, |reader| {
+            super::PlainCar::new_with_index_cache(reader, car_path, index_dir)
+        })
+    }
+
+    fn new_with(
+        reader: ReaderT,
+        plain_car: impl FnOnce(ReaderT) -> Result<super::PlainCar<ReaderT>>,
+    ) -> Result<Self> {
         if super::ForestCar::is_valid(&reader) {
             return Ok(AnyCar::Forest(super::ForestCar::new(reader)?));
         }
@@ -41,7 +60,7 @@ impl<ReaderT: RandomAccessFileReader> AnyCar<ReaderT> {
             }
         }
 
-        if let Ok(plain_car) = super::PlainCar::new(reader) {
+        if let Ok(plain_car) = plain_car(reader) {
             return Ok(AnyCar::Plain(plain_car));
         }
         Err(Error::new(
@@ -76,7 +76,7 @@ use unsigned_varint::codec::UviBytes;
 #[cfg(feature = "benchmark-private")]
 pub mod index;
 #[cfg(not(feature = "benchmark-private"))]
-mod index;
+pub(super) mod index;
 
 pub const FOREST_CAR_FILE_EXTENSION: &str = ".forest.car.zst";
 pub const DEFAULT_FOREST_CAR_FRAME_SIZE: usize = 8000_usize.next_power_of_two();
@@ -9,7 +9,7 @@
 //! A single z-frame cache is shared between all read-only stores.
 
 use super::{AnyCar, ZstdFrameCache};
-use crate::db::{MemoryDB, SettingsStore};
+use crate::db::{IndicesStore, MemoryDB, SettingsStore};
 use crate::libp2p_bitswap::BitswapStoreReadWrite;
 use crate::shim::clock::ChainEpoch;
 use crate::utils::io::EitherMmapOrRandomAccessFile;
@@ -18,6 +18,7 @@ use anyhow::Context as _;
 use cid::Cid;
 use fvm_ipld_blockstore::Blockstore;
 use parking_lot::{Mutex, RwLock};
+use rayon::prelude::*;
 use std::cmp::Ord;
 use std::collections::BinaryHeap;
 use std::{path::PathBuf, sync::Arc};
@@ -61,6 +62,8 @@ pub struct ManyCar<WriterT = MemoryDB> {
     shared_cache: Arc<Mutex<ZstdFrameCache>>,
     read_only: RwLock<BinaryHeap<WithHeaviestEpoch>>,
     writer: WriterT,
+    /// Directory keeping the indexes of the `.car` files, if they are saved
+    index_cache_dir: Option<PathBuf>,
 }
 
 impl<WriterT> ManyCar<WriterT> {
@@ -69,9 +72,17 @@ impl<WriterT> ManyCar<WriterT> {
             shared_cache: Arc::new(Mutex::new(ZstdFrameCache::default())),
             read_only: RwLock::new(BinaryHeap::default()),
             writer,
+            index_cache_dir: None,
         }
     }
 
+    /// Saves the indexes of the `.car` files loaded by
+    /// [`Self::read_only_files`] in `dir`, for the next loads to be fast.
+    pub fn with_index_cache_dir(mut self, dir: PathBuf) -> Self {
+        self.index_cache_dir = Some(dir);
+        self
+    }
+
     pub fn writer(&self) -> &WriterT {
         &self.writer
     }
@@ -116,9 +127,24 @@ impl<WriterT> ManyCar<WriterT> {
         Ok(self)
     }
 
+    /// Loads the CAR files in parallel. The indexes of `.car` files are saved
+    /// in the index cache directory, if there is one.
     pub fn read_only_files(&self, files: impl Iterator<Item = PathBuf>) -> anyhow::Result<()> {
-        for file in files {
-            self.read_only(AnyCar::new(EitherMmapOrRandomAccessFile::open(file)?)?)?;
+        let index_cache_dir = self.index_cache_dir.as_deref();
+        let cars = files
+            .collect::<Vec<_>>()
+            .into_par_iter()
+            .map(|file| {
+                let reader = EitherMmapOrRandomAccessFile::open(&file)?;
+                match index_cache_dir {
+                    Some(dir) => AnyCar::new_with_index_cache(reader, &file, dir),
+                    None => AnyCar::new(reader),
+                }
+                .with_context(|| format!("couldn't load {}", file.display()))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        for car in cars {
+            self.read_only(car)?;
         }
 
         Ok(())
@@ -195,6 +221,10 @@ impl<WriterT: SettingsStore> SettingsStore for ManyCar<WriterT> {
         SettingsStore::write_bin(self.writer(), key, value)
     }
 
+    fn remove(&self, key: &str) -> anyhow::Result<()> {
+        SettingsStore::remove(self.writer(), key)
+    }
+
     fn exists(&self, key: &str) -> anyhow::Result<bool> {
         SettingsStore::exists(self.writer(), key)
     }
@@ -204,6 +234,16 @@ impl<WriterT: SettingsStore> SettingsStore for ManyCar<WriterT> {
     }
 }
 
+impl<WriterT: IndicesStore> IndicesStore for ManyCar<WriterT> {
+    fn read_index(&self, key: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+        IndicesStore::read_index(self.writer(), key)
+    }
+
+    fn write_indices(&self, entries: Vec<(Vec<u8>, Vec<u8>)>) -> anyhow::Result<()> {
+        IndicesStore::write_indices(self.writer(), entries)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::super::AnyCar;
@@ -241,4 +281,29 @@ mod tests {
             &heaviest.genesis(&many).unwrap()
         );
     }
+
+    #[test]
+    fn many_car_files_cache_indexes() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let files = ["calibnet.car", "mainnet.car"].map(|name| temp_dir.path().join(name));
+        let [calibnet_file, mainnet_file] = &files;
+        std::fs::write(calibnet_file, calibnet::DEFAULT_GENESIS).unwrap();
+        std::fs::write(mainnet_file, mainnet::DEFAULT_GENESIS).unwrap();
+
+        // Indexes are only saved when asked to
+        ManyCar::try_from(files.to_vec()).unwrap();
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 2);
+
+        let index_dir = temp_dir.path().join("index");
+        for _ in 0..2 {
+            let many = ManyCar::new(MemoryDB::default())
+                .with_index_cache_dir(index_dir.clone())
+                .with_read_only_files(files.clone().into_iter())
+                .unwrap();
+            assert!(many.has(&calibnet::GENESIS_CID).unwrap());
+            assert!(many.has(&mainnet::GENESIS_CID).unwrap());
+        }
+        assert_eq!(std::fs::read_dir(&index_dir).unwrap().count(), 2);
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 3);
+    }
 }
@@ -63,29 +63,32 @@
 use crate::cid_collections::{hash_map::Entry as CidHashMapEntry, CidHashMap};
 use crate::{
     blocks::{Tipset, TipsetKey},
-    utils::encoding::from_slice_with_fallback,
+    utils::encoding::{blake2b_256, from_slice_with_fallback},
 };
 
+use super::forest::index;
 use crate::utils::db::car_stream::CarHeader;
+use crate::utils::io::EitherMmapOrRandomAccessFile;
 use cid::Cid;
 use fvm_ipld_blockstore::Blockstore;
 use integer_encoding::VarIntReader;
 
 use nonempty::NonEmpty;
 use parking_lot::RwLock;
-use positioned_io::ReadAt;
+use positioned_io::{ReadAt, Size as _};
 use std::ops::DerefMut;
 use std::{
-    any::Any,
     io::{
         self, BufReader,
-        ErrorKind::{InvalidData, UnexpectedEof, Unsupported},
-        Read, Seek, SeekFrom,
+        ErrorKind::{InvalidData, NotFound, UnexpectedEof, Unsupported},
+        Read, Seek, SeekFrom, Write as _,
     },
-    iter,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
 };
 use tokio::io::{AsyncWrite, AsyncWriteExt};
-use tracing::{debug, trace};
+use tokio_util::compat::FuturesAsyncWriteCompatExt as _;
+use tracing::{debug, trace, warn};
 use CidHashMapEntry::{Occupied, Vacant};
 
 /// **Note that all operations on this store are blocking**.
@@ -99,6 +102,8 @@ use CidHashMapEntry::{Occupied, Vacant};
 /// On creation, [`PlainCar`] builds an in-memory index of the [`Cid`]s in the file,
 /// and their offsets into that file.
 /// Note that it prepares its own buffer for doing so.
+/// With [`PlainCar::new_with_index_cache`], the index is saved in a cache
+/// directory instead, and memory-mapped on subsequent loads.
 ///
 /// When a block is requested, [`PlainCar`] scrolls to that offset, and reads the block, on-demand.
 ///
@@ -112,10 +117,19 @@ use CidHashMapEntry::{Occupied, Vacant};
 pub struct PlainCar<ReaderT> {
     reader: ReaderT,
     write_cache: RwLock<CidHashMap<Vec<u8>>>,
-    index: RwLock<CidHashMap<UncompressedBlockDataLocation>>,
+    index: RwLock<Index>,
     roots: NonEmpty<Cid>,
 }
 
+/// Locations of the blocks of a [`PlainCar`].
+enum Index {
+    /// Built in memory by scanning the CAR file.
+    Memory(CidHashMap<UncompressedBlockDataLocation>),
+    /// Memory-mapped from an index file, see [`PlainCar::new_with_index_cache`].
+    /// Maps [`Cid`]s to candidate offsets of block frames.
+    Cached(index::Reader<positioned_io::Slice<EitherMmapOrRandomAccessFile>>),
+}
+
 impl<ReaderT: super::RandomAccessFileReader> PlainCar<ReaderT> {
     /// To be correct:
     /// - `reader` must read immutable data. e.g if it is a file, it should be
@@ -123,33 +137,69 @@ impl<ReaderT: super::RandomAccessFileReader> PlainCar<ReaderT> {
     ///   [`Blockstore`] API calls may panic if this is not upheld.
     #[tracing::instrument(level = "debug", skip_all)]
     pub fn new(reader: ReaderT) -> io::Result<Self> {
-        let mut cursor = positioned_io::Cursor::new(&reader);
-        let roots = get_roots_from_v1_header(&mut cursor)?;
-
-        // When indexing, we perform small reads of the length and CID before seeking
-        // Buffering these gives us a ~50% speedup (n=10): https://github.com/ChainSafe/forest/pull/3085#discussion_r1246897333
-        let mut buf_reader = BufReader::with_capacity(1024, cursor);
-
-        // now create the index
-        let index =
-            iter::from_fn(|| read_block_data_location_and_skip(&mut buf_reader).transpose())
-                .collect::<Result<CidHashMap<_>, _>>()?;
-
-        match index.len() {
-            0 => Err(io::Error::new(
-                InvalidData,
-                "CARv1 files must contain at least one block",
-            )),
-            num_blocks => {
-                debug!(num_blocks, "indexed CAR");
-                Ok(Self {
+        let mut index = CidHashMap::new();
+        let roots = scan(&reader, |cid, _frame_offset, location| {
+            index.insert(cid, location);
+        })?;
+        debug!(num_blocks = index.len(), "indexed CAR");
+        Ok(Self {
+            reader,
+            index: RwLock::new(Index::Memory(index)),
+            roots,
+            write_cache: RwLock::new(CidHashMap::new()),
+        })
+    }
+
+    /// Like [`Self::new`], but saves the index of the CAR file at `car_path`
+    /// in `index_dir`, and memory-maps the saved index when the file is loaded
+    /// again. A saved index is only used if the size and modification time of
+    /// the CAR file didn't change, else it is rebuilt.
+    #[tracing::instrument(level = "debug", skip_all)]
+    pub fn new_with_index_cache(
+        reader: ReaderT,
+        car_path: &Path,
+        index_dir: &Path,
+    ) -> io::Result<Self> {
+        let index_path = index_cache_path(car_path, index_dir)?;
+        let stamp = CarFileStamp::of(car_path)?;
+        match load_index_cache(&index_path, stamp) {
+            Ok(index) => {
+                debug!(path = %index_path.display(), "loaded cached CAR index");
+                let roots = get_roots_from_v1_header(positioned_io::Cursor::new(&reader))?;
+                return Ok(Self {
                     reader,
-                    index: RwLock::new(index),
+                    index: RwLock::new(Index::Cached(index)),
                     roots,
                     write_cache: RwLock::new(CidHashMap::new()),
-                })
+                });
             }
+            Err(e) if e.kind() == NotFound => {}
+            Err(e) => debug!(path = %index_path.display(), "rebuilding CAR index: {e}"),
         }
+
+        let mut index = CidHashMap::new();
+        let mut builder = index::Builder::new();
+        let roots = scan(&reader, |cid, frame_offset, location| {
+            builder.extend([(cid, frame_offset)]);
+            index.insert(cid, location);
+        })?;
+        debug!(num_blocks = index.len(), "indexed CAR");
+        let index = match std::fs::create_dir_all(index_dir)
+            .and_then(|()| save_index_cache(&index_path, stamp, builder))
+            .and_then(|()| load_index_cache(&index_path, stamp))
+        {
+            Ok(cached) => Index::Cached(cached),
+            Err(e) => {
+                warn!("couldn't save the index of {}: {e}", car_path.display());
+                Index::Memory(index)
+            }
+        };
+        Ok(Self {
+            reader,
+            index: RwLock::new(index),
+            roots,
+            write_cache: RwLock::new(CidHashMap::new()),
+        })
     }
 
     pub fn roots(&self) -> &NonEmpty<Cid> {
@@ -161,9 +211,15 @@ impl<ReaderT: super::RandomAccessFileReader> PlainCar<ReaderT> {
     }
 
     /// In an arbitrary order
-    #[cfg(test)]
-    pub fn cids(&self) -> Vec<Cid> {
-        self.index.read().keys().collect()
+    pub fn cids(&self) -> io::Result<Vec<Cid>> {
+        match &*self.index.read() {
+            Index::Memory(index) => Ok(index.keys().collect()),
+            Index::Cached(_) => {
+                let mut cids = vec![];
+                scan(&self.reader, |cid, _, _| cids.push(cid))?;
+                Ok(cids)
+            }
+        }
     }
 
     pub fn into_dyn(self) -> PlainCar<Box<dyn super::RandomAccessFileReader>> {
@@ -176,6 +232,26 @@ impl<ReaderT: super::RandomAccessFileReader> PlainCar<ReaderT> {
     }
 }
 
+impl<ReaderT: ReadAt> PlainCar<ReaderT> {
+    /// Finds where the data of the block `k` is, if it is in the CAR file.
+    fn locate(&self, index: &Index, k: &Cid) -> io::Result<Option<UncompressedBlockDataLocation>> {
+        match index {
+            Index::Memory(index) => Ok(index.get(k).copied()),
+            Index::Cached(index) => {
+                for frame_offset in index.get(*k)? {
+                    let frame = positioned_io::Cursor::new_pos(&self.reader, frame_offset);
+                    if let Some((cid, location)) = read_block_data_location_and_skip(frame)? {
+                        if cid == *k {
+                            return Ok(Some(location));
+                        }
+                    }
+                }
+                Ok(None)
+            }
+        }
+    }
+}
+
 impl TryFrom<&'static [u8]> for PlainCar<&'static [u8]> {
     type Error = io::Error;
     fn try_from(bytes: &'static [u8]) -> io::Result<Self> {
@@ -183,9 +259,144 @@ impl TryFrom<&'static [u8]> for PlainCar<&'static [u8]> {
     }
 }
 
+/// Reads the roots of the CAR file, and calls `on_block` with the [`Cid`],
+/// the offset of the frame, and the location of the data of each block.
+fn scan(
+    reader: &impl ReadAt,
+    mut on_block: impl FnMut(Cid, u64, UncompressedBlockDataLocation),
+) -> io::Result<NonEmpty<Cid>> {
+    let mut cursor = positioned_io::Cursor::new(reader);
+    let roots = get_roots_from_v1_header(&mut cursor)?;
+
+    // When indexing, we perform small reads of the length and CID before seeking
+    // Buffering these gives us a ~50% speedup (n=10): https://github.com/ChainSafe/forest/pull/3085#discussion_r1246897333
+    let mut buf_reader = BufReader::with_capacity(1024, cursor);
+
+    let mut num_blocks = 0;
+    loop {
+        let frame_offset = buf_reader.stream_position()?;
+        match read_block_data_location_and_skip(&mut buf_reader)? {
+            Some((cid, location)) => {
+                on_block(cid, frame_offset, location);
+                num_blocks += 1;
+            }
+            None => break,
+        }
+    }
+    match num_blocks {
+        0 => Err(io::Error::new(
+            InvalidData,
+            "CARv1 files must contain at least one block",
+        )),
+        _ => Ok(roots),
+    }
+}
+
+/// The index of `car_path` is saved in `<index_dir>/<file name>.<hash>.idx`,
+/// where the hash is that of the canonical path of the CAR file, so that files
+/// of the same name in different directories don't share an index.
+fn index_cache_path(car_path: &Path, index_dir: &Path) -> io::Result<PathBuf> {
+    let canonical = std::fs::canonicalize(car_path)?;
+    let hash = blake2b_256(canonical.as_os_str().as_encoded_bytes());
+    let mut name = car_path.file_name().unwrap_or_default().to_owned();
+    name.push(format!(
+        ".{}.idx",
+        hex::encode(hash.get(..8).unwrap_or_default())
+    ));
+    Ok(index_dir.join(name))
+}
+
+/// Identifies a version of a CAR file, to tell whether a saved index is stale.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct CarFileStamp {
+    len: u64,
+    /// Modification time, in nanoseconds since the Unix epoch
+    modified: u64,
+}
+
+impl CarFileStamp {
+    fn of(car_path: &Path) -> io::Result<Self> {
+        let metadata = std::fs::metadata(car_path)?;
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map_err(io::Error::other)?
+            .as_nanos();
+        Ok(Self {
+            len: metadata.len(),
+            modified: u64::try_from(modified).map_err(io::Error::other)?,
+        })
+    }
+}
+
+/// An index file starts with [`INDEX_CACHE_MAGIC`], the [`CarFileStamp`] of
+/// the indexed file, and the length of the index table that follows.
+const INDEX_CACHE_MAGIC: [u8; 8] = *b"FRSTPIDX";
+const INDEX_CACHE_HEADER_LEN: u64 = 32;
+
+fn save_index_cache(
+    index_path: &Path,
+    stamp: CarFileStamp,
+    builder: index::Builder,
+) -> io::Result<()> {
+    let writer = builder.into_writer();
+    let mut header = Vec::with_capacity(INDEX_CACHE_HEADER_LEN as usize);
+    header.extend(INDEX_CACHE_MAGIC);
+    header.extend(stamp.len.to_le_bytes());
+    header.extend(stamp.modified.to_le_bytes());
+    header.extend(writer.written_len().to_le_bytes());
+
+    // Write to a temporary file first, so that the index file is complete if
+    // it exists
+    let dir = index_path.parent().unwrap_or(Path::new("."));
+    let mut file = tempfile::NamedTempFile::new_in(dir)?;
+    file.write_all(&header)?;
+    let mut table = io::BufWriter::new(file);
+    futures::executor::block_on(
+        writer.write_into(futures::io::AllowStdIo::new(&mut table).compat_write()),
+    )?;
+    table
+        .into_inner()
+        .map_err(io::IntoInnerError::into_error)?
+        .persist(index_path)?;
+    Ok(())
+}
+
+fn load_index_cache(
+    index_path: &Path,
+    stamp: CarFileStamp,
+) -> io::Result<index::Reader<positioned_io::Slice<EitherMmapOrRandomAccessFile>>> {
+    let file = EitherMmapOrRandomAccessFile::open(index_path)?;
+    let mut header = [0; INDEX_CACHE_HEADER_LEN as usize];
+    file.read_exact_at(0, &mut header)?;
+    let field = |ix: usize| {
+        let bytes = header.get(ix * 8..(ix + 1) * 8).expect("infallible");
+        u64::from_le_bytes(bytes.try_into().expect("infallible"))
+    };
+    if header.get(..8) != Some(&INDEX_CACHE_MAGIC[..]) {
+        return Err(io::Error::new(InvalidData, "not a CAR index file"));
+    }
+    if (CarFileStamp {
+        len: field(1),
+        modified: field(2),
+    }) != stamp
+    {
+        return Err(io::Error::new(InvalidData, "the CAR file changed"));
+    }
+    let table_len = field(3);
+    if file.size()? != Some(INDEX_CACHE_HEADER_LEN + table_len) {
+        return Err(io::Error::new(InvalidData, "truncated CAR index file"));
+    }
+    index::Reader::new(positioned_io::Slice::new(
+        file,
+        INDEX_CACHE_HEADER_LEN,
+        Some(table_len),
+    ))
+}
+
 /// If you seek to `offset` (from the start of the file), and read `length` bytes,
 /// you should get data that corresponds to a [`Cid`] (but NOT the [`Cid`] itself).
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
 pub struct UncompressedBlockDataLocation {
     offset: u64,
     length: u32,
@@ -197,20 +408,22 @@ where
 {
     #[tracing::instrument(level = "trace", skip(self))]
     fn get(&self, k: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
-        match (self.index.read().get(k), self.write_cache.read().get(k)) {
+        let location = self.locate(&self.index.read(), k)?;
+        let cached = self.write_cache.read().get(k).cloned();
+        match (location, cached) {
             (Some(_location), Some(_cached)) => {
                 trace!("evicting from write cache");
                 Ok(self.write_cache.write().remove(k))
             }
             (Some(UncompressedBlockDataLocation { offset, length }), None) => {
                 trace!("fetching from disk");
-                let mut data = vec![0; usize::try_from(*length).unwrap()];
-                self.reader.read_exact_at(*offset, &mut data)?;
+                let mut data = vec![0; usize::try_from(length).unwrap()];
+                self.reader.read_exact_at(offset, &mut data)?;
                 Ok(Some(data))
             }
             (None, Some(cached)) => {
                 trace!("getting from write cache");
-                Ok(Some(cached.clone()))
+                Ok(Some(cached))
             }
             (None, None) => {
                 trace!("not found");
@@ -227,9 +440,10 @@ where
     /// deadlock is imminent in a multi-threaded context.
     #[tracing::instrument(level = "trace", skip(self, block))]
     fn put_keyed(&self, k: &Cid, block: &[u8]) -> anyhow::Result<()> {
-        let mut index = self.index.write();
+        let index = self.index.write();
+        let on_disk = self.locate(&index, k)?.is_some();
         let mut cache = self.write_cache.write();
-        handle_write_cache(cache.deref_mut(), index.deref_mut(), k, block)
+        handle_write_cache(cache.deref_mut(), on_disk, k, block)
     }
 }
 
@@ -255,28 +469,28 @@ pub struct CompressedBlockDataLocation {
 /// locking, however the performance is acceptable for now.
 fn handle_write_cache(
     write_cache: &mut CidHashMap<Vec<u8>>,
-    index: &mut CidHashMap<impl Any>,
+    on_disk: bool,
     k: &Cid,
     block: &[u8],
 ) -> anyhow::Result<()> {
-    match (index.get(k), write_cache.entry(*k)) {
-        (None, Occupied(already)) => match already.get() == block {
+    match (on_disk, write_cache.entry(*k)) {
+        (false, Occupied(already)) => match already.get() == block {
             true => {
                 trace!("already in cache");
                 Ok(())
             }
             false => panic!("mismatched content on second write for CID {k}"),
         },
-        (None, Vacant(vacant)) => {
+        (false, Vacant(vacant)) => {
             trace!(bytes = block.len(), "insert into cache");
             vacant.insert(block.to_owned());
             Ok(())
         }
-        (Some(_), Vacant(_)) => {
+        (true, Vacant(_)) => {
             trace!("already on disk");
             Ok(())
         }
-        (Some(_), Occupied(_)) => {
+        (true, Occupied(_)) => {
             unreachable!("we don't insert a CID in the write cache if it exists on disk")
         }
     }
@@ -409,10 +623,13 @@ where
 
 #[cfg(test)]
 mod tests {
-    use super::PlainCar;
+    use super::*;
     use crate::utils::db::car_util::load_car;
     use futures::executor::block_on;
-    use fvm_ipld_blockstore::{Blockstore as _, MemoryBlockstore};
+    use fvm_ipld_blockstore::MemoryBlockstore;
+    use positioned_io::Size;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
     use tokio::io::AsyncBufRead;
 
     #[test]
@@ -421,16 +638,124 @@ mod tests {
         let reference = reference(car);
         let car_backed = PlainCar::new(car).unwrap();
 
-        assert_eq!(car_backed.cids().len(), 1222);
+        assert_eq!(car_backed.cids().unwrap().len(), 1222);
         assert_eq!(car_backed.roots().len(), 1);
 
-        for cid in car_backed.cids() {
+        for cid in car_backed.cids().unwrap() {
             let expected = reference.get(&cid).unwrap().unwrap();
             let actual = car_backed.get(&cid).unwrap().unwrap();
             assert_eq!(expected, actual);
         }
     }
 
+    /// Counts the bytes read from a CAR file.
+    struct CountingReader {
+        inner: Vec<u8>,
+        bytes_read: Arc<AtomicUsize>,
+    }
+
+    impl ReadAt for CountingReader {
+        fn read_at(&self, pos: u64, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.inner.read_at(pos, buf)?;
+            self.bytes_read.fetch_add(n, Ordering::Relaxed);
+            Ok(n)
+        }
+    }
+
+    impl Size for CountingReader {
+        fn size(&self) -> io::Result<Option<u64>> {
+            self.inner.size()
+        }
+    }
+
+    /// Loads the CAR file at `path` with its index cached in `index_dir`,
+    /// returning how many bytes were read from it.
+    fn load_with_index_cache(path: &Path, index_dir: &Path) -> (PlainCar<CountingReader>, usize) {
+        let bytes_read = Arc::new(AtomicUsize::new(0));
+        let reader = CountingReader {
+            inner: std::fs::read(path).unwrap(),
+            bytes_read: bytes_read.clone(),
+        };
+        let car = PlainCar::new_with_index_cache(reader, path, index_dir).unwrap();
+        let bytes_read = bytes_read.load(Ordering::Relaxed);
+        (car, bytes_read)
+    }
+
+    fn assert_same_blocks(car: &PlainCar<CountingReader>) {
+        let reference = PlainCar::new(chain4_car()).unwrap();
+        for cid in reference.cids().unwrap() {
+            assert_eq!(car.get(&cid).unwrap(), reference.get(&cid).unwrap());
+        }
+        assert_eq!(car.roots(), reference.roots());
+        assert!(!car.has(&Cid::default()).unwrap());
+    }
+
+    #[test]
+    fn index_cache_is_reused() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("chain4.car");
+        std::fs::write(&path, chain4_car()).unwrap();
+
+        let index_dir = temp_dir.path().join("index");
+
+        let (car, bytes_read) = load_with_index_cache(&path, &index_dir);
+        // Indexing scans the whole file
+        assert!(bytes_read > chain4_car().len() / 2);
+        assert!(index_cache_path(&path, &index_dir).unwrap().is_file());
+        // Nothing is written next to the CAR file
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 2);
+        assert_same_blocks(&car);
+
+        let (car, bytes_read) = load_with_index_cache(&path, &index_dir);
+        // Only the header was read
+        assert!(bytes_read < 1024, "{bytes_read}");
+        assert!(matches!(*car.index.read(), Index::Cached(_)));
+        assert_same_blocks(&car);
+        assert_eq!(car.cids().unwrap().len(), 1222);
+    }
+
+    #[test]
+    fn corrupted_index_cache_is_rebuilt() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("chain4.car");
+        let index_dir = temp_dir.path().join("index");
+        std::fs::write(&path, chain4_car()).unwrap();
+        let index_path = index_cache_path(&path, &index_dir).unwrap();
+        load_with_index_cache(&path, &index_dir);
+        let index = std::fs::read(&index_path).unwrap();
+
+        let truncated = index.get(..index.len() / 2).unwrap().to_vec();
+        let mut bad_magic = index.clone();
+        bad_magic[0] ^= 0xff;
+        let mut bad_version = index.clone();
+        bad_version[INDEX_CACHE_HEADER_LEN as usize] ^= 0xff;
+        for corrupted in [vec![], truncated, bad_magic, bad_version] {
+            std::fs::write(&index_path, corrupted).unwrap();
+            let (car, bytes_read) = load_with_index_cache(&path, &index_dir);
+            assert!(bytes_read > chain4_car().len() / 2);
+            assert_same_blocks(&car);
+            assert_eq!(std::fs::read(&index_path).unwrap(), index);
+        }
+    }
+
+    #[test]
+    fn index_cache_of_modified_file_is_rebuilt() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("chain4.car");
+        let index_dir = temp_dir.path().join("index");
+        std::fs::write(&path, chain4_car()).unwrap();
+        load_with_index_cache(&path, &index_dir);
+
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(std::time::SystemTime::now() + std::time::Duration::from_secs(60))
+            .unwrap();
+        let (car, bytes_read) = load_with_index_cache(&path, &index_dir);
+        assert!(bytes_read > chain4_car().len() / 2);
+        assert_same_blocks(&car);
+        let (_, bytes_read) = load_with_index_cache(&path, &index_dir);
+        assert!(bytes_read < 1024);
+    }
+
     fn reference(reader: impl AsyncBufRead + Unpin) -> MemoryBlockstore {
         let blockstore = MemoryBlockstore::new();
         block_on(load_car(&blockstore, reader)).unwrap();
@@ -0,0 +1,45 @@
+// Copyright 2019-2024 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use once_cell::sync::Lazy;
+use prometheus_client::metrics::gauge::Gauge;
+
+pub static GC_STAGE: Lazy<Gauge> = Lazy::new(|| {
+    let metric = Gauge::default();
+    crate::metrics::default_registry().register(
+        "gc_stage",
+        "Stage of the garbage collector: 0 idle, 1 marking, 2 waiting, 3 filtering, 4 sweeping",
+        metric.clone(),
+    );
+    metric
+});
+
+pub static GC_PAUSED: Lazy<Gauge> = Lazy::new(|| {
+    let metric = Gauge::default();
+    crate::metrics::default_registry().register(
+        "gc_paused",
+        "Whether the garbage collector waits for the node to catch up with the network",
+        metric.clone(),
+    );
+    metric
+});
+
+pub static GC_MARKED_KEYS: Lazy<Gauge> = Lazy::new(|| {
+    let metric = Gauge::default();
+    crate::metrics::default_registry().register(
+        "gc_marked_keys",
+        "Number of database keys marked by the current garbage collection run",
+        metric.clone(),
+    );
+    metric
+});
+
+pub static GC_SWEPT_KEYS: Lazy<Gauge> = Lazy::new(|| {
+    let metric = Gauge::default();
+    crate::metrics::default_registry().register(
+        "gc_swept_keys",
+        "Number of database keys removed by the last garbage collection run",
+        metric.clone(),
+    );
+    metric
+});
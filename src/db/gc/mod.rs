@@ -38,9 +38,10 @@
 //! This algorithm considers all the blocks that are visited during the `snapshot export` task
 //! reachable, making sure they are kept in the database after the run. It makes sure to retain the
 //! reachable graph as well as all the blocks for at least `chain finality` to account for potential
-//! forks. A snapshot can be used to bootstrap the node from scratch, thus the algorithm is
-//! considered correct when a valid snapshot can be exported using records available in the database
-//! after the run.
+//! forks. The state-trees of the last `depth` epochs, of genesis and of the sync checkpoint in the
+//! settings store are retained, along with all the block headers back to genesis. A snapshot can be
+//! used to bootstrap the node from scratch, thus the algorithm is considered correct when a valid
+//! snapshot can be exported using records available in the database after the run.
 //!
 //! ## Disk usage
 //! The expected disk usage is slightly greater than the size of live data for three reasons:
@@ -64,26 +65,165 @@
 //! 3. Then, the `sweep` step happens.
 //! 4. Finally, the algorithm waits for a configured amount of time to initiate the next run.
 //!
+//! The collector pauses while the node catches up with the network, as the heaviest tipset is then
+//! far behind the network head and the filter step would compete with the state validation.
+//!
+//! A run can also be requested through the [`GcHandle`] of the collector, e.g. with
+//! `forest-cli chain prune`, instead of waiting for the next one. It still waits `chain finality`
+//! between the `mark` and `filter` steps.
+//!
 //! ## Performance
 //! The time complexity of mark and sweep steps is `O(n)`. The filter step is currently utilizing a
 //! depth-first search algorithm, with `O(V+E)` complexity, where V is the number of vertices and E
 //! is the number of edges.
 
+mod metrics;
+
 use crate::blocks::Tipset;
 use crate::chain::ChainEpochDelta;
-
-use crate::db::{truncated_hash, GarbageCollectable};
+use crate::chain_sync::Checkpoint;
+use crate::db::{truncated_hash, GarbageCollectable, SettingsStore};
 use crate::ipld::unordered_stream_graph;
+use crate::lotus_json::lotus_json_with_self;
 use crate::shim::clock::ChainEpoch;
 use ahash::{HashSet, HashSetExt};
 use futures::StreamExt;
 use fvm_ipld_blockstore::Blockstore;
+use parking_lot::RwLock;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DurationSeconds};
 use std::mem;
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::time;
+use tokio::{sync::Notify, time};
 use tracing::info;
 
+const DEFAULT_GC_INTERVAL: Duration = Duration::from_secs(60 * 60 * 10);
+
+/// The `[gc]` section of the configuration.
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(derive_quickcheck_arbitrary::Arbitrary))]
+#[serde(default)]
+pub struct GcConfig {
+    /// Number of epochs whose state-trees are retained, counting back from the
+    /// heaviest tipset. Defaults to twice the chain finality, or to
+    /// `sync.recent_state_roots` if larger. Never less than the chain
+    /// finality.
+    pub retention_epochs: Option<ChainEpochDelta>,
+    /// Time in seconds between the end of a run and the start of the next one
+    #[serde_as(as = "DurationSeconds<u64>")]
+    #[cfg_attr(test, arbitrary(gen(|g| Duration::from_secs(u32::arbitrary(g).into()))))]
+    pub interval: Duration,
+}
+
+impl Default for GcConfig {
+    fn default() -> Self {
+        Self {
+            retention_epochs: None,
+            interval: DEFAULT_GC_INTERVAL,
+        }
+    }
+}
+
+impl GcConfig {
+    /// Returns the number of epochs whose state-trees are retained.
+    pub fn retention_epochs(
+        &self,
+        chain_finality: ChainEpochDelta,
+        recent_state_roots: ChainEpochDelta,
+    ) -> ChainEpochDelta {
+        self.retention_epochs
+            .unwrap_or(std::cmp::max(chain_finality * 2, recent_state_roots))
+            .max(chain_finality)
+    }
+}
+
+/// Step of the garbage collector.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum GcStage {
+    /// Waiting for the next run.
+    #[default]
+    Idle,
+    /// Listing the keys of the database.
+    Marking,
+    /// Waiting for `depth` epochs since the keys were marked.
+    Waiting,
+    /// Removing the reachable keys from the marked ones.
+    Filtering,
+    /// Removing the unreachable keys from the database.
+    Sweeping,
+}
+
+/// Progress of the garbage collector.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "PascalCase")]
+pub struct GcProgress {
+    /// Number of runs completed since the node started.
+    pub runs: u64,
+    pub stage: GcStage,
+    /// Whether the current stage is held back while the node catches up with
+    /// the network.
+    pub paused: bool,
+    /// Number of keys marked by the current run.
+    pub marked: u64,
+    /// Epoch at which the current run marked the keys.
+    pub epoch_marked: ChainEpoch,
+    /// Epoch from which the current run filters and sweeps the marked keys.
+    pub sweep_epoch: ChainEpoch,
+    /// Number of keys removed by the last run.
+    pub swept: u64,
+}
+
+lotus_json_with_self!(GcProgress);
+
+/// Shared with the RPC server, to request garbage collection runs and follow
+/// their progress.
+#[derive(Debug, Default)]
+pub struct GcHandle {
+    trigger: Notify,
+    progress: RwLock<GcProgress>,
+}
+
+impl GcHandle {
+    /// Requests a run, unless one is already in progress. Returns whether a
+    /// run was requested.
+    pub fn trigger(&self) -> bool {
+        let idle = self.progress.read().stage == GcStage::Idle;
+        if idle {
+            self.trigger.notify_one();
+        }
+        idle
+    }
+
+    pub fn progress(&self) -> GcProgress {
+        self.progress.read().clone()
+    }
+
+    fn set_stage(&self, stage: GcStage) {
+        self.update(|progress| progress.stage = stage);
+    }
+
+    /// Updates the progress, and the metrics following it.
+    fn update(&self, f: impl FnOnce(&mut GcProgress)) {
+        let mut progress = self.progress.write();
+        f(&mut progress);
+        metrics::GC_STAGE.set(progress.stage as i64);
+        metrics::GC_PAUSED.set(progress.paused.into());
+        metrics::GC_MARKED_KEYS.set(progress.marked as i64);
+        metrics::GC_SWEPT_KEYS.set(progress.swept as i64);
+    }
+
+    /// Sleeps for `duration`, or until a run is requested.
+    async fn wait(&self, duration: Duration) {
+        tokio::select! {
+            _ = time::sleep(duration) => {}
+            _ = self.trigger.notified() => {}
+        }
+    }
+}
+
 /// [`MarkAndSweep`] is a simple garbage collector implementation that traverses all the database
 /// keys writing them to a [`HashSet`], then filters out those that need to be kept and schedules
 /// the rest for removal.
@@ -93,20 +233,22 @@ use tracing::info;
 pub struct MarkAndSweep<DB> {
     db: Arc<DB>,
     get_heaviest_tipset: Box<dyn Fn() -> Arc<Tipset> + Send>,
+    is_paused: Box<dyn Fn() -> bool + Send>,
     marked: HashSet<u32>,
     epoch_marked: ChainEpoch,
     depth: ChainEpochDelta,
     block_time: Duration,
+    handle: Arc<GcHandle>,
 }
 
-impl<DB: Blockstore + GarbageCollectable + Sync + Send + 'static> MarkAndSweep<DB> {
+impl<DB: Blockstore + SettingsStore + GarbageCollectable + Sync + Send + 'static> MarkAndSweep<DB> {
     /// Creates a new mark-and-sweep garbage collector.
     ///
     /// # Arguments
     ///
     /// * `db` - A reference to the database instance.
     /// * `get_heaviest_tipset` - A function that facilitates heaviest tipset retrieval.
-    /// * `depth` - The number of state-roots to retain. Should be at least `2 * chain finality`.
+    /// * `depth` - The number of state-roots to retain. Should be at least `chain finality`.
     /// * `block_time` - An average block production time.
     pub fn new(
         db: Arc<DB>,
@@ -117,12 +259,28 @@ impl<DB: Blockstore + GarbageCollectable + Sync + Send + 'static> MarkAndSweep<D
         Self {
             db,
             get_heaviest_tipset,
+            is_paused: Box::new(|| false),
             depth,
             marked: HashSet::new(),
             epoch_marked: 0,
             block_time,
+            handle: Default::default(),
         }
     }
+
+    /// Holds back the marking and filtering steps while `is_paused` returns
+    /// `true`, e.g. while the node catches up with the network.
+    pub fn with_pause(mut self, is_paused: Box<dyn Fn() -> bool + Send>) -> Self {
+        self.is_paused = is_paused;
+        self
+    }
+
+    /// Returns the handle to request runs of this collector and follow their
+    /// progress.
+    pub fn handle(&self) -> Arc<GcHandle> {
+        self.handle.clone()
+    }
+
     // Populate the initial set with all the available database keys.
     fn populate(&mut self) -> anyhow::Result<()> {
         self.marked = self.db.get_keys()?;
@@ -132,25 +290,60 @@ impl<DB: Blockstore + GarbageCollectable + Sync + Send + 'static> MarkAndSweep<D
     // Filter out the initial set, leaving only the entries that need to be removed.
     // NOTE: One concern here is that this is going to consume a lot of CPU.
     async fn filter(&mut self, tipset: Arc<Tipset>, depth: ChainEpochDelta) -> anyhow::Result<()> {
-        // NOTE: We want to keep all the block headers from genesis to heaviest tipset epoch.
-        let mut stream = unordered_stream_graph(
-            self.db.clone(),
-            (*tipset).clone().chain(self.db.clone()),
-            depth,
-        );
+        // NOTE: We want to keep all the block headers from genesis to heaviest tipset epoch, and
+        // the state-trees of the last `depth` epochs only.
+        let stateroot_limit = tipset.epoch() - depth;
+        self.unmark_reachable((*tipset).clone().chain(self.db.clone()), stateroot_limit)
+            .await?;
+
+        // The checkpoint may be older than `depth`, keep its state-tree too.
+        if let Some(checkpoint) = Checkpoint::load(self.db.as_ref())? {
+            if let Some(checkpoint) = Tipset::load(&self.db, checkpoint.key())? {
+                let stateroot_limit = checkpoint.epoch() - 1;
+                self.unmark_reachable(std::iter::once(checkpoint), stateroot_limit)
+                    .await?;
+            }
+        }
 
+        anyhow::Ok(())
+    }
+
+    // Remove the blocks reachable from the tipsets from the marked set, walking the state-trees
+    // of those above `stateroot_limit`.
+    async fn unmark_reachable(
+        &mut self,
+        tipsets: impl Iterator<Item = Tipset> + Unpin + Send + 'static,
+        stateroot_limit: ChainEpoch,
+    ) -> anyhow::Result<()> {
+        let mut stream = unordered_stream_graph(self.db.clone(), tipsets, stateroot_limit);
         while let Some(block) = stream.next().await {
             let block = block?;
             self.marked.remove(&truncated_hash(block.cid.hash()));
         }
-
         anyhow::Ok(())
     }
 
+    // Wait for the collector to be resumed, if paused. Takes `&mut self` as the closures are not
+    // `Sync`.
+    async fn wait_while_paused(&mut self) {
+        if (self.is_paused)() {
+            info!("GC paused while the node catches up with the network");
+            self.handle.update(|progress| progress.paused = true);
+            while (self.is_paused)() {
+                time::sleep(self.block_time).await;
+            }
+        }
+        // Also reset after a cancelled wait
+        self.handle.update(|progress| progress.paused = false);
+    }
+
     // Remove marked keys from the database.
     fn sweep(&mut self) -> anyhow::Result<()> {
         let marked = mem::take(&mut self.marked);
-        self.db.remove_keys(marked)
+        let swept = marked.len() as u64;
+        self.db.remove_keys(marked)?;
+        self.handle.update(|progress| progress.swept = swept);
+        Ok(())
     }
 
     /// Starts the Garbage Collection loop.
@@ -176,18 +369,28 @@ impl<DB: Blockstore + GarbageCollectable + Sync + Send + 'static> MarkAndSweep<D
         // Don't run the GC if there aren't enough state-roots yet. Sleep and yield to the main loop
         // in order to refresh the heaviest tipset value.
         if depth > current_epoch {
-            time::sleep(interval).await;
+            self.handle.wait(interval).await;
             return anyhow::Ok(());
         }
 
         // This signifies a new run.
         if self.marked.is_empty() {
-            // Make sure we don't run the GC too often.
-            time::sleep(interval).await;
+            // Make sure we don't run the GC too often, unless a run is requested.
+            self.handle.wait(interval).await;
+            self.wait_while_paused().await;
+            let current_epoch = (self.get_heaviest_tipset)().epoch();
 
             info!("populate keys for GC");
+            self.handle.set_stage(GcStage::Marking);
             self.populate()?;
             self.epoch_marked = current_epoch;
+            let marked = self.marked.len() as u64;
+            self.handle.update(|progress| {
+                progress.stage = GcStage::Waiting;
+                progress.marked = marked;
+                progress.epoch_marked = current_epoch;
+                progress.sweep_epoch = current_epoch + depth;
+            });
         }
 
         let epochs_since_marked = current_epoch - self.epoch_marked;
@@ -198,30 +401,49 @@ impl<DB: Blockstore + GarbageCollectable + Sync + Send + 'static> MarkAndSweep<D
             return anyhow::Ok(());
         }
 
+        // Filtering is the most expensive step, don't compete with the state validation.
+        if (self.is_paused)() {
+            self.wait_while_paused().await;
+            return anyhow::Ok(());
+        }
+
         info!("filter keys for GC");
+        self.handle.set_stage(GcStage::Filtering);
         self.filter(tipset, depth).await?;
 
         info!("GC sweep");
+        self.handle.set_stage(GcStage::Sweeping);
         self.sweep()?;
 
+        self.handle.update(|progress| {
+            progress.stage = GcStage::Idle;
+            progress.runs += 1;
+            progress.marked = 0;
+        });
+
         anyhow::Ok(())
     }
 }
 #[cfg(test)]
 mod test {
-    use crate::blocks::{CachingBlockHeader, Tipset};
+    use crate::blocks::{CachingBlockHeader, RawBlockHeader, Tipset};
     use crate::chain::{ChainEpochDelta, ChainStore};
+    use crate::chain_sync::Checkpoint;
 
-    use crate::db::{GarbageCollectable, MarkAndSweep, MemoryDB};
+    use crate::db::{GarbageCollectable, GcStage, MarkAndSweep, MemoryDB};
     use crate::message_pool::test_provider::{mock_block, mock_block_with_parents};
     use crate::networks::ChainConfig;
+    use crate::test_utils::{persist_chain, persist_epoch_state};
 
     use crate::utils::db::CborStoreExt;
 
     use core::time::Duration;
 
     use crate::shim::clock::ChainEpoch;
+    use cid::Cid;
     use fvm_ipld_blockstore::Blockstore;
+    use fvm_ipld_encoding::CborStore;
+    use std::sync::atomic::{AtomicBool, Ordering};
     use std::sync::Arc;
 
     const ZERO_DURATION: Duration = Duration::from_secs(0);
@@ -258,7 +480,14 @@ mod test {
             let gen_block: CachingBlockHeader = mock_block(1, 1);
             db.put_cbor_default(&gen_block).unwrap();
             let store = Arc::new(
-                ChainStore::new(db.clone(), db.clone(), Arc::new(config), gen_block).unwrap(),
+                ChainStore::new(
+                    db.clone(),
+                    db.clone(),
+                    db.clone(),
+                    Arc::new(config),
+                    gen_block,
+                )
+                .unwrap(),
             );
 
             GCTester { db, store }
@@ -270,6 +499,28 @@ mod test {
             run_to_epoch(&self.db, &self.store, epoch);
         }
 
+        // Runs `delta` epochs, each block pointing to a distinct state-tree made of a root and a
+        // leaf. Returns the epochs along with the CIDs of their state-trees.
+        fn run_epochs_with_state(&self, delta: ChainEpochDelta) -> Vec<(ChainEpoch, Cid, Cid)> {
+            let heaviest_tipset = self.store.heaviest_tipset();
+            let epochs = heaviest_tipset.epoch() + 1..=heaviest_tipset.epoch() + delta;
+            let mut states = vec![];
+            let chain = persist_chain(&self.db, &heaviest_tipset, epochs, |parent, epoch| {
+                let (state_root, leaf) = persist_epoch_state(&self.db, epoch);
+                states.push((epoch, state_root, leaf));
+                RawBlockHeader {
+                    state_root,
+                    ..mock_block_with_parents(parent, 1, 1).into_raw()
+                }
+            });
+            if let Some(head) = chain.last() {
+                self.store
+                    .set_heaviest_tipset(Arc::new(head.clone()))
+                    .unwrap();
+            }
+            states
+        }
+
         fn insert_unreachable(&self, block_number: i64) {
             insert_unreachable(&self.db, block_number as u64);
         }
@@ -313,6 +564,41 @@ mod test {
         assert_eq!(gc.epoch_marked, depth);
     }
 
+    #[tokio::test]
+    async fn triggered_run_skips_the_interval() {
+        let tester = GCTester::new();
+        let depth = 2;
+        tester.run_epochs(depth);
+        tester.insert_unreachable(3);
+
+        let mut gc = MarkAndSweep::new(
+            tester.db.clone(),
+            tester.get_heaviest_tipset_fn(),
+            depth,
+            ZERO_DURATION,
+        );
+        let handle = gc.handle();
+        assert!(handle.trigger());
+
+        let interval = Duration::from_secs(3600);
+        tokio::time::timeout(Duration::from_secs(10), gc.gc_workflow(interval))
+            .await
+            .unwrap()
+            .unwrap();
+        let progress = handle.progress();
+        assert_eq!(progress.stage, GcStage::Waiting);
+        assert_eq!(progress.sweep_epoch, 2 * depth);
+        // A run is in progress already
+        assert!(!handle.trigger());
+
+        tester.run_epochs(depth);
+        gc.gc_workflow(interval).await.unwrap();
+        let progress = handle.progress();
+        assert_eq!(progress.stage, GcStage::Idle);
+        assert_eq!(progress.runs, 1);
+        assert_eq!(progress.swept, 3);
+    }
+
     #[quickcheck_async::tokio]
     async fn dont_gc_reachable_data(depth: u8, current_epoch: u8) {
         // Enforce depth above zero.
@@ -434,4 +720,111 @@ mod test {
             current_epoch + 1 + depth * 2
         );
     }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn only_recent_state_trees_retained() {
+        let tester = GCTester::new();
+        let depth = 5;
+        let old_states = tester.run_epochs_with_state(depth);
+
+        let mut gc = MarkAndSweep::new(
+            tester.db.clone(),
+            tester.get_heaviest_tipset_fn(),
+            depth,
+            ZERO_DURATION,
+        );
+        // Mark.
+        gc.gc_workflow(ZERO_DURATION).await.unwrap();
+        let recent_states = tester.run_epochs_with_state(depth);
+        // Filter and sweep.
+        gc.gc_workflow(ZERO_DURATION).await.unwrap();
+        assert_eq!(gc.handle().progress().runs, 1);
+        assert_eq!(gc.handle().progress().swept, 2 * depth as u64);
+
+        for (epoch, state_root, leaf) in old_states {
+            assert!(!tester.db.has(&state_root).unwrap(), "epoch {epoch}");
+            assert!(!tester.db.has(&leaf).unwrap(), "epoch {epoch}");
+        }
+        for (epoch, state_root, leaf) in recent_states {
+            let state: Option<(ChainEpoch, Cid)> = tester.db.get_cbor(&state_root).unwrap();
+            assert_eq!(state, Some((epoch, leaf)));
+            assert!(tester.db.has(&leaf).unwrap(), "epoch {epoch}");
+        }
+        // The headers are retained back to genesis.
+        let head = tester.store.heaviest_tipset();
+        let epochs: Vec<_> = (*head)
+            .clone()
+            .chain(&tester.db)
+            .map(|ts| ts.epoch())
+            .collect();
+        assert_eq!(epochs, (0..=2 * depth).rev().collect::<Vec<_>>());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn checkpoint_state_tree_retained() {
+        let tester = GCTester::new();
+        let depth = 5;
+        let old_states = tester.run_epochs_with_state(depth);
+        let checkpoint =
+            Tipset::load_required(&tester.db, tester.store.heaviest_tipset().parents()).unwrap();
+        Checkpoint::from(&checkpoint)
+            .save(tester.db.as_ref())
+            .unwrap();
+
+        let mut gc = MarkAndSweep::new(
+            tester.db.clone(),
+            tester.get_heaviest_tipset_fn(),
+            depth,
+            ZERO_DURATION,
+        );
+        gc.gc_workflow(ZERO_DURATION).await.unwrap();
+        tester.run_epochs_with_state(depth);
+        gc.gc_workflow(ZERO_DURATION).await.unwrap();
+
+        for (epoch, state_root, leaf) in old_states {
+            let retained = epoch == checkpoint.epoch();
+            assert_eq!(
+                tester.db.has(&state_root).unwrap(),
+                retained,
+                "epoch {epoch}"
+            );
+            assert_eq!(tester.db.has(&leaf).unwrap(), retained, "epoch {epoch}");
+        }
+    }
+
+    #[tokio::test]
+    async fn paused_while_syncing() {
+        let tester = GCTester::new();
+        let depth = 2;
+        tester.run_epochs(depth);
+
+        let syncing = Arc::new(AtomicBool::new(true));
+        let mut gc = MarkAndSweep::new(
+            tester.db.clone(),
+            tester.get_heaviest_tipset_fn(),
+            depth,
+            Duration::from_millis(10),
+        )
+        .with_pause({
+            let syncing = syncing.clone();
+            Box::new(move || syncing.load(Ordering::Relaxed))
+        });
+        let handle = gc.handle();
+
+        let run = gc.gc_workflow(ZERO_DURATION);
+        assert!(tokio::time::timeout(Duration::from_millis(100), run)
+            .await
+            .is_err());
+        assert!(gc.marked.is_empty());
+        let progress = handle.progress();
+        assert!(progress.paused);
+        assert_eq!(progress.stage, GcStage::Idle);
+
+        syncing.store(false, Ordering::Relaxed);
+        gc.gc_workflow(ZERO_DURATION).await.unwrap();
+        let progress = handle.progress();
+        assert!(!progress.paused);
+        assert_eq!(progress.stage, GcStage::Waiting);
+        assert_eq!(progress.marked, 1 + depth as u64);
+    }
 }
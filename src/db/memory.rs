@@ -1,7 +1,7 @@
 // Copyright 2019-2024 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use crate::db::{truncated_hash, GarbageCollectable};
+use crate::db::{truncated_hash, DBStatistics, GarbageCollectable};
 use crate::libp2p_bitswap::{BitswapStoreRead, BitswapStoreReadWrite};
 use ahash::{HashMap, HashSet, HashSetExt};
 use cid::Cid;
@@ -9,12 +9,13 @@ use fvm_ipld_blockstore::Blockstore;
 use itertools::Itertools;
 use parking_lot::RwLock;
 
-use super::SettingsStore;
+use super::{IndicesStore, SettingsStore};
 
 #[derive(Debug, Default)]
 pub struct MemoryDB {
     blockchain_db: RwLock<HashMap<Vec<u8>, Vec<u8>>>,
     settings_db: RwLock<HashMap<String, Vec<u8>>>,
+    indices_db: RwLock<HashMap<Vec<u8>, Vec<u8>>>,
 }
 
 impl GarbageCollectable for MemoryDB {
@@ -40,6 +41,8 @@ impl GarbageCollectable for MemoryDB {
     }
 }
 
+impl DBStatistics for MemoryDB {}
+
 impl SettingsStore for MemoryDB {
     fn read_bin(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
         Ok(self.settings_db.read().get(key).cloned())
@@ -52,6 +55,11 @@ impl SettingsStore for MemoryDB {
         Ok(())
     }
 
+    fn remove(&self, key: &str) -> anyhow::Result<()> {
+        self.settings_db.write().remove(key);
+        Ok(())
+    }
+
     fn exists(&self, key: &str) -> anyhow::Result<bool> {
         Ok(self.settings_db.read().contains_key(key))
     }
@@ -61,6 +69,17 @@ impl SettingsStore for MemoryDB {
     }
 }
 
+impl IndicesStore for MemoryDB {
+    fn read_index(&self, key: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+        Ok(self.indices_db.read().get(key).cloned())
+    }
+
+    fn write_indices(&self, entries: Vec<(Vec<u8>, Vec<u8>)>) -> anyhow::Result<()> {
+        self.indices_db.write().extend(entries);
+        Ok(())
+    }
+}
+
 impl Blockstore for MemoryDB {
     fn get(&self, k: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
         Ok(self.blockchain_db.read().get(&k.to_bytes()).cloned())
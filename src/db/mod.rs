@@ -5,10 +5,12 @@ pub mod car;
 mod memory;
 pub mod parity_db;
 pub mod parity_db_config;
+mod read_cache;
 
 mod gc;
-pub use gc::MarkAndSweep;
+pub use gc::{GcConfig, GcHandle, GcProgress, GcStage, MarkAndSweep};
 pub use memory::MemoryDB;
+pub use read_cache::BlockstoreWithReadCache;
 mod db_mode;
 pub mod migration;
 
@@ -24,6 +26,26 @@ pub mod setting_keys {
     pub const HEAD_KEY: &str = "head";
     /// Key used to store the memory pool configuration in the settings store.
     pub const MPOOL_CONFIG_KEY: &str = "/mpool/config";
+    /// Key used to store the locally published messages of the memory pool in the settings store.
+    pub const MPOOL_LOCAL_MSGS_KEY: &str = "/mpool/local";
+    /// Key used to store the default wallet address in the settings store.
+    pub const WALLET_DEFAULT_ADDR_KEY: &str = "/wallet/default";
+    /// Key used to store the blocked peers, IP addresses and subnets in the settings store.
+    pub const NET_BLOCKLIST_KEY: &str = "/net/blocklist";
+    /// Key used to store the connection manager watermarks in the settings store.
+    pub const NET_LIMIT_KEY: &str = "/net/limit";
+    /// Key used to store the bootstrap peers added to those of the network in the settings store.
+    pub const NET_BOOTSTRAP_PEERS_KEY: &str = "/net/bootstrap_peers";
+    /// Prefix of the keys used to store the bad block cache in the settings store, one per block.
+    pub const BAD_BLOCKS_KEY: &str = "/sync/bad_blocks";
+    /// Key used to store the tipset below which the chain is not validated in the settings store.
+    pub const SYNC_CHECKPOINT_KEY: &str = "/sync/checkpoint";
+    /// Key used to store the metadata of the issued JWT tokens in the settings store.
+    pub const AUTH_TOKENS_KEY: &str = "/auth/tokens";
+    /// Key used to store the snapshots served by the offline RPC server in the settings store.
+    pub const OFFLINE_SNAPSHOTS_KEY: &str = "/offline/snapshots";
+    /// Key used to store the genesis CAR file of a devnet in the settings store.
+    pub const DEVNET_GENESIS_KEY: &str = "/devnet/genesis";
 }
 
 /// Interface used to store and retrieve settings from the database.
@@ -37,6 +59,9 @@ pub trait SettingsStore {
     /// non-serializable data. For serializable data, use [`SettingsStoreExt::write_obj`].
     fn write_bin(&self, key: &str, value: &[u8]) -> anyhow::Result<()>;
 
+    /// Removes a field from the Settings store, if present.
+    fn remove(&self, key: &str) -> anyhow::Result<()>;
+
     /// Returns `Ok(true)` if key exists in store.
     fn exists(&self, key: &str) -> anyhow::Result<bool>;
 
@@ -53,6 +78,10 @@ impl<T: SettingsStore> SettingsStore for Arc<T> {
         SettingsStore::write_bin(self.as_ref(), key, value)
     }
 
+    fn remove(&self, key: &str) -> anyhow::Result<()> {
+        SettingsStore::remove(self.as_ref(), key)
+    }
+
     fn exists(&self, key: &str) -> anyhow::Result<bool> {
         SettingsStore::exists(self.as_ref(), key)
     }
@@ -92,6 +121,28 @@ impl<T: ?Sized + SettingsStore> SettingsStoreExt for T {
     }
 }
 
+/// Interface used to store and retrieve the indices of the chain, e.g. the
+/// message index. Unlike IPLD blocks, their entries can be replaced. The indices
+/// share a key space, so their keys are prefixed with the index name, e.g.
+/// `/message/`.
+pub trait IndicesStore {
+    /// Reads the entry of `key`, if any.
+    fn read_index(&self, key: &[u8]) -> anyhow::Result<Option<Vec<u8>>>;
+
+    /// Writes the entries in one batch, replacing the existing ones.
+    fn write_indices(&self, entries: Vec<(Vec<u8>, Vec<u8>)>) -> anyhow::Result<()>;
+}
+
+impl<T: IndicesStore> IndicesStore for Arc<T> {
+    fn read_index(&self, key: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+        IndicesStore::read_index(self.as_ref(), key)
+    }
+
+    fn write_indices(&self, entries: Vec<(Vec<u8>, Vec<u8>)>) -> anyhow::Result<()> {
+        IndicesStore::write_indices(self.as_ref(), entries)
+    }
+}
+
 /// Traits for collecting DB stats
 pub trait DBStatistics {
     fn get_statistics(&self) -> Option<String> {
@@ -2,9 +2,9 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use ahash::{HashSet, HashSetExt};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use super::SettingsStore;
+use super::{IndicesStore, SettingsStore};
 
 use crate::db::{
     parity_db_config::ParityDbConfig, truncated_hash, DBStatistics, GarbageCollectable,
@@ -24,7 +24,7 @@ use fvm_ipld_encoding::DAG_CBOR;
 use parity_db::{CompressionType, Db, Operation, Options};
 use strum::{Display, EnumIter, FromRepr, IntoEnumIterator};
 
-use tracing::warn;
+use tracing::{info, warn};
 
 /// This is specific to Forest's `ParityDb` usage.
 /// It is used to determine which column to use for a given entry type.
@@ -41,6 +41,10 @@ enum DbColumn {
     GraphFull,
     /// Column for storing Forest-specific settings.
     Settings,
+    /// Column for storing the indices of the chain keyed by CID, e.g. the
+    /// message index. Added after the other columns, databases created
+    /// without it are upgraded when opened.
+    Indices,
 }
 
 impl DbColumn {
@@ -69,12 +73,27 @@ impl DbColumn {
                         compression,
                         ..Default::default()
                     },
+                    DbColumn::Indices => parity_db::ColumnOptions {
+                        // Entries are overwritten, e.g. when a message is
+                        // included again after a re-org
+                        preimage: false,
+                        compression,
+                        ..Default::default()
+                    },
                 }
             })
             .collect()
     }
 }
 
+/// Number of values and size on disk of a column of the database.
+#[derive(Debug)]
+pub struct ColumnStats {
+    pub name: String,
+    pub values: u64,
+    pub size: u64,
+}
+
 pub struct ParityDb {
     pub db: parity_db::Db,
     statistics_enabled: bool,
@@ -95,12 +114,34 @@ impl ParityDb {
 
     pub fn open(path: impl Into<PathBuf>, config: &ParityDbConfig) -> anyhow::Result<Self> {
         let opts = Self::to_options(path.into(), config);
+        Self::add_missing_columns(&opts)?;
         Ok(Self {
             db: Db::open_or_create(&opts)?,
             statistics_enabled: opts.stats,
         })
     }
 
+    /// Adds the columns appended to [`DbColumn`] since the database at
+    /// `opts.path` was created, if any.
+    fn add_missing_columns(opts: &Options) -> anyhow::Result<()> {
+        let Some(metadata) = Options::load_metadata(&opts.path)? else {
+            return Ok(());
+        };
+        let mut existing = Options {
+            columns: metadata.columns,
+            ..opts.clone()
+        };
+        while let Some(column) = opts.columns.get(existing.columns.len()) {
+            info!(
+                "Adding column {} to the database at {}",
+                existing.columns.len(),
+                opts.path.display()
+            );
+            Db::add_column(&mut existing, column.clone())?;
+        }
+        Ok(())
+    }
+
     pub fn wrap(db: parity_db::Db, stats: bool) -> Self {
         Self {
             db,
@@ -108,6 +149,54 @@ impl ParityDb {
         }
     }
 
+    /// Counts the values of each column, and sums the sizes of the index and
+    /// value table files of each column in the database directory at `path`.
+    pub fn column_stats(&self, path: &Path) -> anyhow::Result<Vec<ColumnStats>> {
+        let files = std::fs::read_dir(path)?
+            .map(|entry| {
+                let entry = entry?;
+                Ok((
+                    entry.file_name().to_string_lossy().into_owned(),
+                    entry.metadata()?.len(),
+                ))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        DbColumn::iter()
+            .map(|column| {
+                let col = column as u8;
+                let mut values = 0;
+                match column {
+                    // Columns without a B-tree index can only be iterated over
+                    // their values
+                    DbColumn::GraphDagCborBlake2b256 | DbColumn::Indices => {
+                        self.db.iter_column_while(col, |_| {
+                            values += 1;
+                            true
+                        })?
+                    }
+                    DbColumn::GraphFull | DbColumn::Settings => {
+                        let mut iter = self.db.iter(col)?;
+                        while iter.next()?.is_some() {
+                            values += 1;
+                        }
+                    }
+                }
+                let prefixes = [format!("index_{col:02}_"), format!("table_{col:02}_")];
+                let size = files
+                    .iter()
+                    .filter(|(name, _)| prefixes.iter().any(|prefix| name.starts_with(prefix)))
+                    .map(|(_, len)| len)
+                    .sum();
+                Ok(ColumnStats {
+                    name: column.to_string(),
+                    values,
+                    size,
+                })
+            })
+            .collect()
+    }
+
     /// Returns an appropriate column variant based on the information
     /// in the Cid.
     fn choose_column(cid: &Cid) -> DbColumn {
@@ -149,6 +238,13 @@ impl SettingsStore for ParityDb {
         self.write_to_column(key.as_bytes(), value, DbColumn::Settings)
     }
 
+    fn remove(&self, key: &str) -> anyhow::Result<()> {
+        let tx = [(DbColumn::Settings as u8, key.as_bytes(), None)];
+        self.db
+            .commit(tx)
+            .map_err(|e| anyhow!("error removing from column {}: {e}", DbColumn::Settings))
+    }
+
     fn exists(&self, key: &str) -> anyhow::Result<bool> {
         self.db
             .get_size(DbColumn::Settings as u8, key.as_bytes())
@@ -166,6 +262,21 @@ impl SettingsStore for ParityDb {
     }
 }
 
+impl IndicesStore for ParityDb {
+    fn read_index(&self, key: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+        self.read_from_column(key, DbColumn::Indices)
+    }
+
+    fn write_indices(&self, entries: Vec<(Vec<u8>, Vec<u8>)>) -> anyhow::Result<()> {
+        let tx = entries
+            .into_iter()
+            .map(|(key, value)| (DbColumn::Indices as u8, Operation::Set(key, value)));
+        self.db
+            .commit_changes(tx)
+            .map_err(|e| anyhow!("error writing to column {}: {e}", DbColumn::Indices))
+    }
+}
+
 impl Blockstore for ParityDb {
     fn get(&self, k: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
         let column = Self::choose_column(k);
@@ -173,7 +284,7 @@ impl Blockstore for ParityDb {
             DbColumn::GraphDagCborBlake2b256 | DbColumn::GraphFull => {
                 self.read_from_column(k.to_bytes(), column)
             }
-            DbColumn::Settings => panic!("invalid column for IPLD data"),
+            DbColumn::Settings | DbColumn::Indices => panic!("invalid column for IPLD data"),
         }
     }
 
@@ -185,7 +296,7 @@ impl Blockstore for ParityDb {
             DbColumn::GraphDagCborBlake2b256 | DbColumn::GraphFull => {
                 self.write_to_column(k.to_bytes(), block, column)
             }
-            DbColumn::Settings => panic!("invalid column for IPLD data"),
+            DbColumn::Settings | DbColumn::Indices => panic!("invalid column for IPLD data"),
         }
     }
 
@@ -392,7 +503,7 @@ mod test {
             let other_column = match column {
                 DbColumn::GraphDagCborBlake2b256 => DbColumn::GraphFull,
                 DbColumn::GraphFull => DbColumn::GraphDagCborBlake2b256,
-                DbColumn::Settings => panic!("invalid column for IPLD data"),
+                DbColumn::Settings | DbColumn::Indices => panic!("invalid column for IPLD data"),
             };
             let actual = db.read_from_column(cid.to_bytes(), other_column).unwrap();
             assert!(actual.is_none());
@@ -456,6 +567,34 @@ mod test {
         assert_eq!(keys.len(), 0);
     }
 
+    #[test]
+    fn missing_columns_added() {
+        let dir = tempfile::tempdir().unwrap();
+        let opts = ParityDb::to_options(dir.path().into(), &ParityDbConfig::default());
+        let old_opts = Options {
+            columns: opts.columns[..DbColumn::Indices as usize].to_vec(),
+            ..opts.clone()
+        };
+        let old = ParityDb::wrap(Db::open_or_create(&old_opts).unwrap(), false);
+        old.write_bin("dagon", b"bloop").unwrap();
+        drop(old);
+
+        let db = ParityDb::open(dir.path(), &ParityDbConfig::default()).unwrap();
+        assert_eq!(db.read_bin("dagon").unwrap().unwrap(), b"bloop");
+        let key = Cid::new_v1(DAG_CBOR, Blake2b256.digest(b"Cthulhu")).to_bytes();
+        assert!(db.read_index(&key).unwrap().is_none());
+        db.write_indices(vec![(key.clone(), b"R'lyeh".to_vec())])
+            .unwrap();
+        db.write_indices(vec![(key.clone(), b"Y'ha-nthlei".to_vec())])
+            .unwrap();
+        assert_eq!(db.read_index(&key).unwrap().unwrap(), b"Y'ha-nthlei");
+        drop(db);
+
+        // Reopening doesn't add the column again
+        let db = ParityDb::open(dir.path(), &ParityDbConfig::default()).unwrap();
+        assert_eq!(db.read_index(&key).unwrap().unwrap(), b"Y'ha-nthlei");
+    }
+
     #[test]
     fn choose_column_test() {
         let data = [0u8; 32];
@@ -0,0 +1,352 @@
+// Copyright 2019-2024 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! An in-memory LRU cache of recently read blocks, sitting in front of the
+//! database. State computations and RPC calls tend to load the same actor
+//! state, HAMT and AMT nodes over and over, which saves a round trip to disk
+//! for each of them.
+
+use crate::db::{truncated_hash, DBStatistics, GarbageCollectable, IndicesStore, SettingsStore};
+use crate::libp2p_bitswap::{BitswapStoreRead, BitswapStoreReadWrite};
+use crate::metrics;
+use ahash::HashSet;
+use cid::Cid;
+use fvm_ipld_blockstore::Blockstore;
+use lru::LruCache;
+use parking_lot::Mutex;
+
+/// The cache is split into shards, each behind its own lock, so concurrent
+/// readers rarely contend.
+const SHARD_COUNT: usize = 16;
+
+/// Blocks larger than this are never cached, they would evict many smaller
+/// (and typically hotter) blocks.
+const MAX_BLOCK_SIZE: usize = 1024 * 1024;
+
+/// Rough in-memory overhead of an entry, on top of the block data.
+const ENTRY_OVERHEAD: usize = std::mem::size_of::<Cid>() + std::mem::size_of::<Vec<u8>>();
+
+struct Shard {
+    current_size: usize,
+    lru: LruCache<Cid, Vec<u8>>,
+}
+
+impl Shard {
+    fn size_of_entry(block: &[u8]) -> usize {
+        block.len() + ENTRY_OVERHEAD
+    }
+}
+
+/// A [`Blockstore`] wrapper caching the blocks read from the inner store.
+/// Blocks are content-addressed, so writes never invalidate cached entries;
+/// removals through [`GarbageCollectable::remove_keys`] do.
+pub struct BlockstoreWithReadCache<DB> {
+    inner: DB,
+    shards: Vec<Mutex<Shard>>,
+    /// Maximum size in bytes of each shard.
+    shard_size: usize,
+    /// Blocks larger than this bypass the cache.
+    max_block_size: usize,
+}
+
+impl<DB> BlockstoreWithReadCache<DB> {
+    /// Wraps `inner` with a cache holding up to `max_size` bytes. A size of
+    /// zero disables the cache.
+    pub fn new(inner: DB, max_size: usize) -> Self {
+        let shard_size = max_size / SHARD_COUNT;
+        BlockstoreWithReadCache {
+            inner,
+            shards: (0..SHARD_COUNT)
+                .map(|_| {
+                    Mutex::new(Shard {
+                        current_size: 0,
+                        lru: LruCache::unbounded(),
+                    })
+                })
+                .collect(),
+            shard_size,
+            max_block_size: (shard_size / 8).min(MAX_BLOCK_SIZE),
+        }
+    }
+
+    fn shard(&self, k: &Cid) -> &Mutex<Shard> {
+        let index = truncated_hash(k.hash()) as usize % self.shards.len();
+        self.shards.get(index).expect("index is within bounds")
+    }
+
+    fn get_cached(&self, k: &Cid) -> Option<Vec<u8>> {
+        if self.max_block_size == 0 {
+            return None;
+        }
+        let cached = self.shard(k).lock().lru.get(k).cloned();
+        match cached {
+            Some(_) => metrics::LRU_CACHE_HIT.get_or_create(&metrics::values::BLOCKS),
+            None => metrics::LRU_CACHE_MISS.get_or_create(&metrics::values::BLOCKS),
+        }
+        .inc();
+        cached
+    }
+
+    fn contains_cached(&self, k: &Cid) -> bool {
+        self.max_block_size > 0 && self.shard(k).lock().lru.contains(k)
+    }
+
+    /// Inserts a block and evicts the least recently used ones if the shard
+    /// exceeds its size.
+    fn put_cached(&self, k: Cid, block: Vec<u8>) {
+        if block.len() > self.max_block_size {
+            return;
+        }
+        let mut shard = self.shard(&k).lock();
+        shard.current_size += Shard::size_of_entry(&block);
+        if let Some(prev_block) = shard.lru.put(k, block) {
+            shard.current_size -= Shard::size_of_entry(&prev_block);
+        }
+        let mut evicted = 0;
+        while shard.current_size > self.shard_size {
+            if let Some((_, block)) = shard.lru.pop_lru() {
+                shard.current_size -= Shard::size_of_entry(&block);
+                evicted += 1;
+            } else {
+                break;
+            }
+        }
+        if evicted > 0 {
+            metrics::LRU_CACHE_EVICTION
+                .get_or_create(&metrics::values::BLOCKS)
+                .inc_by(evicted);
+        }
+    }
+
+    /// Drops the cached blocks whose truncated hashes are in `keys`.
+    fn remove_cached(&self, keys: &HashSet<u32>) {
+        for shard in &self.shards {
+            let mut shard = shard.lock();
+            let removed: Vec<Cid> = shard
+                .lru
+                .iter()
+                .map(|(k, _)| *k)
+                .filter(|k| keys.contains(&truncated_hash(k.hash())))
+                .collect();
+            for k in removed {
+                if let Some(block) = shard.lru.pop(&k) {
+                    shard.current_size -= Shard::size_of_entry(&block);
+                }
+            }
+        }
+    }
+}
+
+impl<DB: Blockstore> Blockstore for BlockstoreWithReadCache<DB> {
+    fn get(&self, k: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        if let Some(block) = self.get_cached(k) {
+            return Ok(Some(block));
+        }
+        let block = self.inner.get(k)?;
+        if let Some(block) = &block {
+            if block.len() <= self.max_block_size {
+                self.put_cached(*k, block.clone());
+            }
+        }
+        Ok(block)
+    }
+
+    fn has(&self, k: &Cid) -> anyhow::Result<bool> {
+        Ok(self.contains_cached(k) || self.inner.has(k)?)
+    }
+
+    fn put_keyed(&self, k: &Cid, block: &[u8]) -> anyhow::Result<()> {
+        self.inner.put_keyed(k, block)
+    }
+
+    fn put_many_keyed<D, I>(&self, blocks: I) -> anyhow::Result<()>
+    where
+        Self: Sized,
+        D: AsRef<[u8]>,
+        I: IntoIterator<Item = (Cid, D)>,
+    {
+        self.inner.put_many_keyed(blocks)
+    }
+}
+
+impl<DB: SettingsStore> SettingsStore for BlockstoreWithReadCache<DB> {
+    fn read_bin(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        self.inner.read_bin(key)
+    }
+
+    fn write_bin(&self, key: &str, value: &[u8]) -> anyhow::Result<()> {
+        self.inner.write_bin(key, value)
+    }
+
+    fn remove(&self, key: &str) -> anyhow::Result<()> {
+        self.inner.remove(key)
+    }
+
+    fn exists(&self, key: &str) -> anyhow::Result<bool> {
+        self.inner.exists(key)
+    }
+
+    fn setting_keys(&self) -> anyhow::Result<Vec<String>> {
+        self.inner.setting_keys()
+    }
+}
+
+impl<DB: IndicesStore> IndicesStore for BlockstoreWithReadCache<DB> {
+    fn read_index(&self, key: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+        self.inner.read_index(key)
+    }
+
+    fn write_indices(&self, entries: Vec<(Vec<u8>, Vec<u8>)>) -> anyhow::Result<()> {
+        self.inner.write_indices(entries)
+    }
+}
+
+impl<DB: Blockstore + BitswapStoreRead> BitswapStoreRead for BlockstoreWithReadCache<DB> {
+    fn contains(&self, cid: &Cid) -> anyhow::Result<bool> {
+        Ok(self.contains_cached(cid) || self.inner.contains(cid)?)
+    }
+
+    fn get(&self, cid: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        Blockstore::get(self, cid)
+    }
+}
+
+impl<DB: Blockstore + BitswapStoreReadWrite> BitswapStoreReadWrite for BlockstoreWithReadCache<DB> {
+    type Params = DB::Params;
+
+    fn insert(&self, block: &libipld::Block<Self::Params>) -> anyhow::Result<()> {
+        self.inner.insert(block)
+    }
+}
+
+impl<DB: DBStatistics> DBStatistics for BlockstoreWithReadCache<DB> {
+    fn get_statistics(&self) -> Option<String> {
+        self.inner.get_statistics()
+    }
+}
+
+impl<DB: GarbageCollectable> GarbageCollectable for BlockstoreWithReadCache<DB> {
+    fn get_keys(&self) -> anyhow::Result<HashSet<u32>> {
+        self.inner.get_keys()
+    }
+
+    fn remove_keys(&self, keys: HashSet<u32>) -> anyhow::Result<()> {
+        self.remove_cached(&keys);
+        self.inner.remove_keys(keys)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shim::address::Address;
+    use crate::shim::state_tree::{ActorState, StateTree, StateTreeVersion};
+    use crate::test_utils::CountingStore;
+    use cid::multihash::{Code::Blake2b256, MultihashDigest};
+    use fvm_ipld_encoding::DAG_CBOR;
+    use std::sync::Arc;
+
+    fn block(data: Vec<u8>) -> (Cid, Vec<u8>) {
+        let cid = Cid::new_v1(DAG_CBOR, Blake2b256.digest(&data));
+        (cid, data)
+    }
+
+    #[test]
+    fn repeated_state_reads_hit_the_cache() {
+        let store = Arc::new(BlockstoreWithReadCache::new(
+            CountingStore::default(),
+            64 << 20,
+        ));
+        let mut state = StateTree::new(store.clone(), StateTreeVersion::V5).unwrap();
+        for id in 0..500 {
+            state
+                .set_actor(
+                    &Address::new_id(id),
+                    ActorState::new_empty(Cid::default(), None),
+                )
+                .unwrap();
+        }
+        let root = state.flush().unwrap();
+
+        let read_state = || {
+            let state = StateTree::new_from_root(store.clone(), &root).unwrap();
+            for id in 0..500 {
+                assert!(state.get_actor(&Address::new_id(id)).unwrap().is_some());
+            }
+        };
+        store.inner.take_reads();
+        read_state();
+        assert!(store.inner.take_reads() > 0);
+        read_state();
+        read_state();
+        assert_eq!(store.inner.take_reads(), 0);
+    }
+
+    #[test]
+    fn large_blocks_bypass_the_cache() {
+        let store = BlockstoreWithReadCache::new(CountingStore::default(), 1 << 30);
+        let (small, small_data) = block(vec![1; 1024]);
+        let (large, large_data) = block(vec![2; 2 * MAX_BLOCK_SIZE]);
+        store.put_keyed(&small, &small_data).unwrap();
+        store.put_keyed(&large, &large_data).unwrap();
+        for _ in 0..3 {
+            assert_eq!(store.get(&small).unwrap(), Some(small_data.clone()));
+            assert_eq!(store.get(&large).unwrap(), Some(large_data.clone()));
+        }
+        // One read for the small block, one per lookup for the large one
+        assert_eq!(store.inner.take_reads(), 4);
+    }
+
+    #[test]
+    fn cache_size_is_bounded() {
+        // 4 KiB per shard
+        let store = BlockstoreWithReadCache::new(CountingStore::default(), SHARD_COUNT * 4096);
+        let blocks: Vec<_> = (0..1000u32)
+            .map(|i| block(i.to_le_bytes().repeat(64)))
+            .collect();
+        for (cid, data) in &blocks {
+            store.put_keyed(cid, data).unwrap();
+            store.get(cid).unwrap();
+        }
+        for shard in &store.shards {
+            let shard = shard.lock();
+            assert!(shard.current_size <= store.shard_size);
+            assert_eq!(
+                shard.current_size,
+                shard
+                    .lru
+                    .iter()
+                    .map(|(_, block)| Shard::size_of_entry(block))
+                    .sum::<usize>()
+            );
+        }
+        // The most recently read block is still cached
+        store.inner.take_reads();
+        let (cid, _) = blocks.last().unwrap();
+        store.get(cid).unwrap();
+        assert_eq!(store.inner.take_reads(), 0);
+    }
+
+    #[test]
+    fn disabled_cache() {
+        let store = BlockstoreWithReadCache::new(CountingStore::default(), 0);
+        let (cid, data) = block(vec![1; 16]);
+        store.put_keyed(&cid, &data).unwrap();
+        store.get(&cid).unwrap();
+        store.get(&cid).unwrap();
+        assert_eq!(store.inner.take_reads(), 2);
+    }
+
+    #[test]
+    fn removed_keys_are_evicted() {
+        let store = BlockstoreWithReadCache::new(CountingStore::default(), 1 << 20);
+        let (cid, data) = block(vec![1; 16]);
+        store.put_keyed(&cid, &data).unwrap();
+        assert_eq!(store.get(&cid).unwrap(), Some(data));
+        store
+            .remove_keys(HashSet::from_iter([truncated_hash(cid.hash())]))
+            .unwrap();
+        assert!(!store.has(&cid).unwrap());
+        assert_eq!(store.get(&cid).unwrap(), None);
+    }
+}
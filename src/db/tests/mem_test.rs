@@ -34,3 +34,9 @@ fn mem_write_read_obj() {
     let db = MemoryDB::default();
     subtests::write_read_obj(&db);
 }
+
+#[test]
+fn mem_db_remove() {
+    let db = MemoryDB::default();
+    subtests::remove(&db);
+}
@@ -32,3 +32,9 @@ fn db_write_read_obj() {
     let db = TempParityDB::new();
     subtests::write_read_obj(&*db);
 }
+
+#[test]
+fn db_remove() {
+    let db = TempParityDB::new();
+    subtests::remove(&*db);
+}
@@ -64,3 +64,16 @@ where
     assert!(db.read_obj::<i32>(key).unwrap().is_none());
     assert!(db.require_obj::<i32>(key).is_err());
 }
+
+pub fn remove<DB>(db: &DB)
+where
+    DB: SettingsStore,
+{
+    let key = "Nyarlathotep";
+    db.write_bin(key, &[1]).unwrap();
+    db.remove(key).unwrap();
+    assert!(!db.exists(key).unwrap());
+    assert!(!db.setting_keys().unwrap().contains(&key.to_owned()));
+    // Removing a missing key is not an error
+    db.remove(key).unwrap();
+}
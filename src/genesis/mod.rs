@@ -2,38 +2,32 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use crate::blocks::CachingBlockHeader;
+use crate::db::SettingsStore;
+use crate::networks::ChainConfig;
+use crate::shim::state_tree::StateTree;
 use crate::state_manager::StateManager;
 use crate::utils::db::car_util::load_car;
 use anyhow::Context as _;
+use fil_actor_interface::init;
 use fvm_ipld_blockstore::Blockstore;
-use tokio::{fs::File, io::AsyncBufRead, io::BufReader};
-use tracing::{debug, info};
+use std::sync::Arc;
+use tokio::io::AsyncBufRead;
+use tracing::info;
 
 #[cfg(test)]
 pub const EXPORT_SR_40: &[u8] = std::include_bytes!("export40.car");
 
-/// Uses an optional file path or the default genesis to parse the genesis and
-/// determine if chain store has existing data for the given genesis.
+/// Loads the genesis of the chain, see [`ChainConfig::genesis_bytes`], and
+/// returns its header.
 pub async fn read_genesis_header<DB>(
-    genesis_fp: Option<&String>,
-    genesis_bytes: Option<&[u8]>,
+    chain_config: &ChainConfig,
     db: &DB,
 ) -> Result<CachingBlockHeader, anyhow::Error>
 where
-    DB: Blockstore,
+    DB: Blockstore + SettingsStore,
 {
-    let genesis = match genesis_fp {
-        Some(path) => {
-            let file = File::open(path).await?;
-            let reader = BufReader::new(file);
-            process_car(reader, db).await?
-        }
-        None => {
-            debug!("No specified genesis in config. Using default genesis.");
-            let genesis_bytes = genesis_bytes.context("No default genesis.")?;
-            process_car(genesis_bytes, db).await?
-        }
-    };
+    let genesis_bytes = chain_config.genesis_bytes(db).await?;
+    let genesis = process_car(genesis_bytes.as_slice(), db).await?;
 
     info!("Initialized genesis: {}", genesis.cid());
     Ok(genesis)
@@ -53,6 +47,22 @@ where
     Ok(network_name)
 }
 
+/// Reads the network name from the genesis state, without a
+/// [`StateManager`].
+pub fn read_network_name<BS>(
+    db: &BS,
+    genesis_header: &CachingBlockHeader,
+) -> Result<String, anyhow::Error>
+where
+    BS: Blockstore,
+{
+    let state_tree = StateTree::new_from_root(Arc::new(db), &genesis_header.state_root)?;
+    let init_actor = state_tree
+        .get_actor(&init::ADDRESS.into())?
+        .context("Init actor address could not be resolved")?;
+    Ok(init::State::load(db, init_actor.code, init_actor.state)?.into_network_name())
+}
+
 async fn process_car<R, BS>(reader: R, db: &BS) -> Result<CachingBlockHeader, anyhow::Error>
 where
     R: AsyncBufRead + Unpin,
@@ -94,6 +104,15 @@ mod tests {
         assert_eq!(header.cid(), &GENESIS_CID as &Cid);
     }
 
+    #[tokio::test]
+    async fn test_read_network_name() {
+        use crate::networks::calibnet::DEFAULT_GENESIS;
+
+        let db = crate::db::MemoryDB::default();
+        let header = process_car(DEFAULT_GENESIS, &db).await.unwrap();
+        assert_eq!(read_network_name(&db, &header).unwrap(), "calibrationnet");
+    }
+
     async fn load_header_from_car(genesis_bytes: &[u8]) -> CachingBlockHeader {
         let db = crate::db::MemoryDB::default();
         process_car(genesis_bytes, &db).await.unwrap()
@@ -1,8 +1,7 @@
 // Copyright 2019-2024 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use std::ops::DerefMut;
-use std::{collections::VecDeque, mem, sync::Arc};
+use std::{collections::VecDeque, sync::Arc};
 
 use crate::blocks::Tipset;
 use crate::cid_collections::CidHashSet;
@@ -114,6 +113,7 @@ pin_project! {
         dfs: VecDeque<Task>, // Depth-first work queue.
         seen: CidHashSet,
         stateroot_limit: ChainEpoch,
+        message_limit: ChainEpoch,
         fail_on_dead_links: bool,
     }
 }
@@ -123,6 +123,15 @@ impl<DB, T> ChainStream<DB, T> {
         ChainStream { seen, ..self }
     }
 
+    /// Stream the messages of the blocks after `message_limit` rather than
+    /// only those after the `stateroot_limit`.
+    pub fn with_message_limit(self, message_limit: ChainEpoch) -> Self {
+        ChainStream {
+            message_limit,
+            ..self
+        }
+    }
+
     #[allow(dead_code)]
     pub fn into_seen(self) -> CidHashSet {
         self.seen
@@ -151,6 +160,7 @@ pub fn stream_chain<DB: Blockstore, T: Iterator<Item = Tipset> + Unpin>(
         dfs: VecDeque::new(),
         seen: CidHashSet::default(),
         stateroot_limit,
+        message_limit: stateroot_limit,
         fail_on_dead_links: true,
     }
 }
@@ -168,6 +178,7 @@ pub fn stream_graph<DB: Blockstore, T: Iterator<Item = Tipset> + Unpin>(
         dfs: VecDeque::new(),
         seen: CidHashSet::default(),
         stateroot_limit,
+        message_limit: stateroot_limit,
         fail_on_dead_links: false,
     }
 }
@@ -187,6 +198,7 @@ impl<DB: Blockstore, T: Iterator<Item = Tipset> + Unpin> Stream for ChainStream<
         };
 
         let stateroot_limit = *this.stateroot_limit;
+        let message_limit = *this.message_limit;
         loop {
             while let Some(task) = this.dfs.front_mut() {
                 match task {
@@ -247,7 +259,7 @@ impl<DB: Blockstore, T: Iterator<Item = Tipset> + Unpin> Stream for ChainStream<
                         }
 
                         // Process block messages.
-                        if block.epoch > stateroot_limit {
+                        if block.epoch > message_limit {
                             this.dfs.push_back(Iterate(
                                 DfsIter::from(block.messages)
                                     .filter_map(ipld_to_cid)
@@ -296,16 +308,6 @@ pin_project! {
     }
 }
 
-impl<DB, T> UnorderedChainStream<DB, T> {
-    pub fn into_seen(self) -> CidHashSet {
-        let mut set = CidHashSet::new();
-        let mut guard = self.seen.lock();
-        let data = guard.deref_mut();
-        mem::swap(data, &mut set);
-        set
-    }
-}
-
 /// Stream all blocks that are reachable before the `stateroot_limit` epoch in an unordered fashion.
 /// After this limit, only block headers are streamed. Any dead links are reported as errors.
 ///
@@ -17,6 +17,9 @@ pub enum Error {
     KeyNotExists,
     #[error("Key not found")]
     NoKey,
+    /// Address is watched without any key material
+    #[error("Watch-only key, no private key is available for this address")]
+    WatchOnly,
     #[error(transparent)]
     IO(#[from] io::Error),
     #[error("{0}")]
@@ -32,6 +32,8 @@ pub const ENCRYPTED_KEYSTORE_NAME: &str = "keystore";
 
 /// Environmental variable which holds the `KeyStore` encryption phrase.
 pub const FOREST_KEYSTORE_PHRASE_ENV: &str = "FOREST_KEYSTORE_PHRASE";
+/// Environmental variable which holds the new `KeyStore` encryption phrase when rotating it.
+pub const FOREST_KEYSTORE_NEW_PHRASE_ENV: &str = "FOREST_KEYSTORE_NEW_PHRASE";
 
 type SaltByteArray = [u8; RECOMMENDED_SALT_LEN];
 
@@ -274,11 +276,13 @@ impl KeyStore {
                     .parent()
                     .ok_or_else(|| Error::Other("Invalid Path".to_string()))?;
                 fs::create_dir_all(dir)?;
-                let file = File::create(&persistent_keystore.file_path)?;
+                // Write to a temporary file first and move it into place, so an interrupted
+                // flush never leaves a truncated keystore behind.
+                let file = tempfile::NamedTempFile::new_in(dir)?;
 
                 // Restrict permissions on files containing private keys
                 #[cfg(unix)]
-                crate::utils::io::set_user_perm(&file)?;
+                crate::utils::io::set_user_perm(file.as_file())?;
 
                 let mut writer = BufWriter::new(file);
 
@@ -294,8 +298,6 @@ impl KeyStore {
                         let mut salt_vec = encrypted_keystore.salt.to_vec();
                         salt_vec.extend(encrypted_data);
                         writer.write_all(&salt_vec)?;
-
-                        Ok(())
                     }
                     None => {
                         let mut key_info: HashMap<String, PersistentKeyInfo> = HashMap::new();
@@ -310,13 +312,17 @@ impl KeyStore {
                         }
 
                         // Flush for PersistentKeyStore
-                        serde_json::to_writer_pretty(writer, &key_info).map_err(|e| {
+                        serde_json::to_writer_pretty(&mut writer, &key_info).map_err(|e| {
                             Error::Other(format!("failed to serialize and write key info: {e}"))
                         })?;
-
-                        Ok(())
                     }
                 }
+
+                writer
+                    .into_inner()
+                    .map_err(|e| e.into_error())?
+                    .persist(&persistent_keystore.file_path)?;
+                Ok(())
             }
             None => {
                 // NoOp for MemKeyStore
@@ -349,6 +355,38 @@ impl KeyStore {
         Ok(())
     }
 
+    /// Re-encrypt all entries of an encrypted `KeyStore` with a key derived
+    /// from `new_passphrase`. `old_passphrase` must be the passphrase the
+    /// `KeyStore` was opened with. The keystore file is replaced atomically.
+    pub fn rotate_passphrase(
+        &mut self,
+        old_passphrase: &str,
+        new_passphrase: &str,
+    ) -> anyhow::Result<()> {
+        let encryption = self
+            .encryption
+            .as_ref()
+            .ok_or_else(|| Error::Other("Keystore is not encrypted".to_string()))?;
+        let (_, old_key) = EncryptedKeyStore::derive_key(old_passphrase, Some(encryption.salt))?;
+        if old_key != encryption.encryption_key {
+            anyhow::bail!(Error::Other("Incorrect keystore passphrase".to_string()));
+        }
+
+        let (salt, encryption_key) = EncryptedKeyStore::derive_key(new_passphrase, None)?;
+        let rotated = Self {
+            key_info: self.key_info.clone(),
+            persistence: self.persistence.clone(),
+            encryption: Some(EncryptedKeyStore {
+                salt,
+                encryption_key,
+            }),
+        };
+        rotated.flush()?;
+        *self = rotated;
+
+        Ok(())
+    }
+
     /// Remove the key and corresponding `KeyInfo` from the `KeyStore`
     pub fn remove(&mut self, key: &str) -> anyhow::Result<KeyInfo> {
         let key_out = self.key_info.remove(key).ok_or(Error::KeyInfo)?;
@@ -504,6 +542,37 @@ mod test {
         assert_eq!(ks, ks_read);
     }
 
+    #[test]
+    fn test_rotate_passphrase() {
+        const NEW_PASSPHRASE: &str = "quxquux";
+
+        let keystore_location = tempfile::tempdir().unwrap().into_path();
+        let mut ks = KeyStore::new(KeyStoreConfig::Encrypted(
+            keystore_location.clone(),
+            PASSPHRASE.to_string(),
+        ))
+        .unwrap();
+        let key = wallet::generate_key(SignatureType::Secp256k1).unwrap();
+        let addr = format!("wallet-{}", key.address);
+        ks.put(&addr, key.key_info.clone()).unwrap();
+
+        assert!(ks.rotate_passphrase(NEW_PASSPHRASE, PASSPHRASE).is_err());
+        ks.rotate_passphrase(PASSPHRASE, NEW_PASSPHRASE).unwrap();
+
+        assert!(KeyStore::new(KeyStoreConfig::Encrypted(
+            keystore_location.clone(),
+            PASSPHRASE.to_string(),
+        ))
+        .is_err());
+        let ks_read = KeyStore::new(KeyStoreConfig::Encrypted(
+            keystore_location,
+            NEW_PASSPHRASE.to_string(),
+        ))
+        .unwrap();
+        assert_eq!(ks_read.get(&addr).unwrap(), key.key_info);
+        assert_eq!(ks, ks_read);
+    }
+
     #[test]
     fn test_read_write_keystore() {
         let keystore_location = tempfile::tempdir().unwrap().into_path();
@@ -3,8 +3,15 @@
 
 use std::{convert::TryFrom, str::FromStr};
 
-use crate::shim::{address::Address, crypto::SignatureType};
+use crate::db::{setting_keys::WALLET_DEFAULT_ADDR_KEY, SettingsStore, SettingsStoreExt as _};
+use crate::lotus_json::LotusJson;
+use crate::shim::{
+    address::{Address, Protocol},
+    crypto::SignatureType,
+};
 use ahash::HashMap;
+use anyhow::Context as _;
+use base64::{prelude::BASE64_STANDARD, Engine};
 use serde::{Deserialize, Serialize};
 
 #[cfg(test)]
@@ -12,6 +19,9 @@ use {crate::shim::crypto::Signature, ahash::HashMapExt as _};
 
 use super::{errors::Error, wallet_helpers, KeyInfo, KeyStore};
 
+/// Length of a raw `secp256k1`, BLS or delegated private key
+const PRIVATE_KEY_LEN: usize = 32;
+
 /// A key, this contains a `KeyInfo`, an address, and a public key.
 #[derive(Clone, PartialEq, Debug, Eq, Serialize, Deserialize)]
 pub struct Key {
@@ -170,6 +180,45 @@ pub fn get_default(keystore: &KeyStore) -> Result<Option<Address>, Error> {
     }
 }
 
+/// Return the default address stored in the settings store. When none is set,
+/// the first address of the `KeyStore` is selected and persisted as the
+/// default. Returns `None` if the `KeyStore` holds no addresses.
+pub fn get_or_init_default<S: SettingsStore + ?Sized>(
+    keystore: &mut KeyStore,
+    settings: &S,
+) -> anyhow::Result<Option<Address>> {
+    if let Some(LotusJson(addr)) =
+        settings.read_obj::<LotusJson<Address>>(WALLET_DEFAULT_ADDR_KEY)?
+    {
+        if keystore.get(&format!("wallet-{addr}")).is_ok() {
+            return Ok(Some(addr));
+        }
+    }
+    // Fall back to a default set by older versions, then to the first key
+    let addr = match get_default(keystore)? {
+        Some(addr) => Some(addr),
+        None => list_addrs(keystore)?.into_iter().next(),
+    };
+    if let Some(addr) = addr {
+        set_default(keystore, settings, addr)?;
+    }
+    Ok(addr)
+}
+
+/// Set the default address in both the settings store and the `KeyStore`
+pub fn set_default<S: SettingsStore + ?Sized>(
+    keystore: &mut KeyStore,
+    settings: &S,
+    addr: Address,
+) -> anyhow::Result<()> {
+    let key_info = keystore.get(&format!("wallet-{addr}"))?;
+    if keystore.get("default").is_ok() {
+        keystore.remove("default")?;
+    }
+    keystore.put("default", key_info)?;
+    settings.write_obj(WALLET_DEFAULT_ADDR_KEY, &LotusJson(addr))
+}
+
 /// Return vector of addresses sorted by their string representation in
 /// `KeyStore`
 pub fn list_addrs(keystore: &KeyStore) -> Result<Vec<Address>, Error> {
@@ -186,10 +235,52 @@ pub fn list_addrs(keystore: &KeyStore) -> Result<Vec<Address>, Error> {
     Ok(out)
 }
 
+/// Add an address without key material to `KeyStore`. Watched addresses are
+/// listed alongside wallet keys but cannot be used for signing.
+pub fn add_watch_addr(addr: &Address, keystore: &mut KeyStore) -> Result<(), Error> {
+    if keystore.get(&format!("wallet-{addr}")).is_ok() {
+        return Err(Error::KeyExists);
+    }
+    let key_type = match addr.protocol() {
+        Protocol::BLS => SignatureType::Bls,
+        Protocol::Delegated => SignatureType::Delegated,
+        _ => SignatureType::Secp256k1,
+    };
+    keystore.put(&format!("watch-{addr}"), KeyInfo::new(key_type, Vec::new()))
+}
+
+/// Removes a watched address from `KeyStore`
+pub fn remove_watch_addr(addr: &Address, keystore: &mut KeyStore) -> Result<(), Error> {
+    keystore
+        .remove(&format!("watch-{addr}"))
+        .map_err(|_| Error::KeyNotExists)?;
+    Ok(())
+}
+
+/// Return whether the address is watched without key material in `KeyStore`
+pub fn is_watch_addr(addr: &Address, keystore: &KeyStore) -> bool {
+    keystore.get(&format!("watch-{addr}")).is_ok()
+}
+
+/// Return vector of watched addresses sorted by their string representation
+/// in `KeyStore`
+pub fn list_watch_addrs(keystore: &KeyStore) -> Vec<Address> {
+    let mut all = keystore.list();
+    all.sort();
+    all.iter()
+        .filter_map(|key| key.strip_prefix("watch-"))
+        .filter_map(|addr_str| Address::from_str(addr_str).ok())
+        .collect()
+}
+
 /// Returns a key corresponding to given address
 pub fn find_key(addr: &Address, keystore: &KeyStore) -> Result<Key, Error> {
     let key_string = format!("wallet-{addr}");
-    let key_info = keystore.get(&key_string)?;
+    let key_info = match keystore.get(&key_string) {
+        Ok(key_info) => key_info,
+        Err(_) if is_watch_addr(addr, keystore) => return Err(Error::WatchOnly),
+        Err(e) => return Err(e),
+    };
     let new_key = Key::try_from(key_info)?;
     Ok(new_key)
 }
@@ -215,6 +306,7 @@ pub fn try_find(addr: &Address, keystore: &mut KeyStore) -> Result<KeyInfo, Erro
     let key_string = format!("wallet-{addr}");
     match keystore.get(&key_string) {
         Ok(k) => Ok(k),
+        Err(_) if is_watch_addr(addr, keystore) => Err(Error::WatchOnly),
         Err(_) => {
             let mut new_addr = addr.to_string();
             if new_addr.len() < 2 {
@@ -240,6 +332,42 @@ pub fn export_key_info(addr: &Address, keystore: &KeyStore) -> Result<KeyInfo, E
     Ok(key.key_info)
 }
 
+/// Encode `KeyInfo` in the format produced by `lotus wallet export`, i.e. the
+/// hex encoding of its JSON representation with a string key type
+pub fn encode_key_info_hex(key_info: &KeyInfo) -> String {
+    let json = serde_json::json!({
+        "Type": key_info.key_type().to_string(),
+        "PrivateKey": BASE64_STANDARD.encode(key_info.private_key()),
+    });
+    hex::encode(json.to_string())
+}
+
+/// Decode `KeyInfo` from user input. Without a `key_type`, the input is
+/// expected in the `lotus wallet export` format, with the key type either as a
+/// string or an integer. With a `key_type`, the input is a hex encoded raw
+/// private key.
+pub fn decode_key_info_hex(
+    input: &str,
+    key_type: Option<SignatureType>,
+) -> anyhow::Result<KeyInfo> {
+    let decoded = hex::decode(input.trim()).context("Key must be hex encoded")?;
+    match key_type {
+        Some(key_type) => {
+            anyhow::ensure!(
+                decoded.len() == PRIVATE_KEY_LEN,
+                "raw private key must be {PRIVATE_KEY_LEN} bytes, got {}",
+                decoded.len()
+            );
+            Ok(KeyInfo::new(key_type, decoded))
+        }
+        None => {
+            let LotusJson(key_info) = serde_json::from_slice::<LotusJson<KeyInfo>>(&decoded)
+                .context("invalid key format")?;
+            Ok(key_info)
+        }
+    }
+}
+
 /// Generate new key of given `SignatureType`
 pub fn generate_key(typ: SignatureType) -> Result<Key, Error> {
     let private_key = wallet_helpers::generate(typ)?;
@@ -253,6 +381,7 @@ mod tests {
     use libsecp256k1::{Message as SecpMessage, SecretKey as SecpPrivate};
 
     use super::*;
+    use crate::db::MemoryDB;
     use crate::key_management::{generate, KeyStoreConfig};
 
     fn construct_priv_keys() -> Vec<Key> {
@@ -362,6 +491,52 @@ mod tests {
         assert!(matches!(duplicate_error, Error::KeyExists));
     }
 
+    #[test]
+    fn key_info_hex_round_trip() {
+        for key_type in [SignatureType::Secp256k1, SignatureType::Bls] {
+            let key = generate_key(key_type).unwrap();
+            let encoded = encode_key_info_hex(&key.key_info);
+            let decoded = decode_key_info_hex(&encoded, None).unwrap();
+            assert_eq!(decoded, key.key_info);
+            assert_eq!(Key::try_from(decoded).unwrap().address, key.address);
+
+            let raw = hex::encode(key.key_info.private_key());
+            assert_eq!(
+                decode_key_info_hex(&raw, Some(key_type)).unwrap(),
+                key.key_info
+            );
+        }
+
+        let key_info = KeyInfo::new(SignatureType::Delegated, vec![7; PRIVATE_KEY_LEN]);
+        let encoded = encode_key_info_hex(&key_info);
+        assert_eq!(decode_key_info_hex(&encoded, None).unwrap(), key_info);
+    }
+
+    #[test]
+    fn decode_lotus_export() {
+        let private_key = vec![1; PRIVATE_KEY_LEN];
+        let lotus_export = hex::encode(format!(
+            r#"{{"Type":"secp256k1","PrivateKey":"{}"}}"#,
+            BASE64_STANDARD.encode(&private_key)
+        ));
+        assert_eq!(
+            decode_key_info_hex(&lotus_export, None).unwrap(),
+            KeyInfo::new(SignatureType::Secp256k1, private_key.clone())
+        );
+
+        // Older Forest exports use an integer key type
+        let forest_export = hex::encode(format!(
+            r#"{{"Type":2,"PrivateKey":"{}"}}"#,
+            BASE64_STANDARD.encode(&private_key)
+        ));
+        assert_eq!(
+            decode_key_info_hex(&forest_export, None).unwrap(),
+            KeyInfo::new(SignatureType::Bls, private_key)
+        );
+
+        assert!(decode_key_info_hex("abcd", Some(SignatureType::Secp256k1)).is_err());
+    }
+
     #[test]
     fn list_addr() {
         let key_vec = construct_priv_keys();
@@ -475,4 +650,124 @@ mod tests {
         let invalid_addr = wallet.generate_addr(SignatureType::Bls).unwrap();
         assert!(sig.verify(&msg, &invalid_addr).is_err())
     }
+
+    #[test]
+    fn default_address_empty_keystore() {
+        let mut keystore = KeyStore::new(KeyStoreConfig::Memory).unwrap();
+        let settings = MemoryDB::default();
+        assert_eq!(get_or_init_default(&mut keystore, &settings).unwrap(), None);
+        assert!(!settings.exists(WALLET_DEFAULT_ADDR_KEY).unwrap());
+    }
+
+    #[test]
+    fn default_address_single_key() {
+        let key = generate_key(SignatureType::Secp256k1).unwrap();
+        let mut keystore = KeyStore::new(KeyStoreConfig::Memory).unwrap();
+        keystore
+            .put(&format!("wallet-{}", key.address), key.key_info.clone())
+            .unwrap();
+        let settings = MemoryDB::default();
+
+        assert_eq!(
+            get_or_init_default(&mut keystore, &settings).unwrap(),
+            Some(key.address)
+        );
+        // the selected address is persisted as the default
+        assert_eq!(get_default(&keystore).unwrap(), Some(key.address));
+        assert_eq!(
+            settings
+                .read_obj::<LotusJson<Address>>(WALLET_DEFAULT_ADDR_KEY)
+                .unwrap()
+                .map(LotusJson::into_inner),
+            Some(key.address)
+        );
+    }
+
+    #[test]
+    fn default_address_persists_across_restarts() {
+        let keystore_location = tempfile::tempdir().unwrap();
+        let settings = MemoryDB::default();
+        let key1 = generate_key(SignatureType::Secp256k1).unwrap();
+        let key2 = generate_key(SignatureType::Bls).unwrap();
+        {
+            let mut keystore = KeyStore::new(KeyStoreConfig::Persistent(
+                keystore_location.path().to_path_buf(),
+            ))
+            .unwrap();
+            for key in [&key1, &key2] {
+                keystore
+                    .put(&format!("wallet-{}", key.address), key.key_info.clone())
+                    .unwrap();
+            }
+            set_default(&mut keystore, &settings, key2.address).unwrap();
+        }
+
+        let mut keystore = KeyStore::new(KeyStoreConfig::Persistent(
+            keystore_location.path().to_path_buf(),
+        ))
+        .unwrap();
+        assert_eq!(
+            get_or_init_default(&mut keystore, &settings).unwrap(),
+            Some(key2.address)
+        );
+    }
+
+    #[test]
+    fn delegated_verify() {
+        let delegated_priv_key = generate(SignatureType::Delegated).unwrap();
+        let delegated_key_info = KeyInfo::new(SignatureType::Delegated, delegated_priv_key);
+        let delegated_key = Key::try_from(delegated_key_info).unwrap();
+        let addr = delegated_key.address;
+        assert_eq!(addr.protocol(), Protocol::Delegated);
+        assert!(addr.to_string()[1..].starts_with("410f"));
+        let key_store = KeyStore::new(KeyStoreConfig::Memory).unwrap();
+        let mut wallet = Wallet::new_from_keys(key_store, vec![delegated_key]);
+
+        let msg = [0u8; 64];
+
+        let sig = wallet.sign(&addr, &msg).unwrap();
+        assert_eq!(sig.signature_type(), SignatureType::Delegated);
+        assert_eq!(sig.bytes().len(), 65);
+        sig.verify(&msg, &addr).unwrap();
+
+        // tampered data and other addresses must not verify
+        assert!(sig.verify(&[1u8; 64], &addr).is_err());
+        let invalid_addr = wallet.generate_addr(SignatureType::Delegated).unwrap();
+        assert!(sig.verify(&msg, &invalid_addr).is_err())
+    }
+
+    // The example transaction of EIP-155, signed by go-ethereum. Delegated keys
+    // sign the Keccak-256 digest of the given bytes, here the RLP-encoded
+    // transaction, and signatures are deterministic (RFC 6979).
+    #[test]
+    fn delegated_sign_eip155_example() {
+        let private_key = vec![0x46; 32];
+        let key =
+            Key::try_from(KeyInfo::new(SignatureType::Delegated, private_key.clone())).unwrap();
+        let eth_addr = hex::decode("9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f").unwrap();
+        assert_eq!(key.address, Address::new_delegated(10, &eth_addr).unwrap());
+
+        let signing_payload = hex::decode(
+            "ec098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a764000080018080",
+        )
+        .unwrap();
+        assert_eq!(
+            hex::encode(crate::utils::encoding::keccak_256(&signing_payload)),
+            "daf5a779ae972f972197303d7b574746c7ef83eadac0f2791ad23db92e4c8e53"
+        );
+
+        let sig =
+            crate::key_management::sign(SignatureType::Delegated, &private_key, &signing_payload)
+                .unwrap();
+        // r, s and the recovery id, i.e. v = 37 minus 35 and twice the chain ID
+        assert_eq!(
+            hex::encode(sig.bytes()),
+            concat!(
+                "28ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276",
+                "67cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83",
+                "00",
+            )
+        );
+        sig.verify(&signing_payload, &key.address).unwrap();
+    }
 }
@@ -5,9 +5,11 @@ use crate::shim::{
     address::Address,
     crypto::{Signature, SignatureType},
 };
-use crate::utils::encoding::blake2b_256;
+use crate::utils::encoding::{blake2b_256, keccak_256};
 use bls_signatures::{PrivateKey as BlsPrivate, Serialize};
-use libsecp256k1::{Message as SecpMessage, PublicKey as SecpPublic, SecretKey as SecpPrivate};
+use libsecp256k1::{
+    Message as SecpMessage, PublicKey as SecpPublic, PublicKeyFormat, SecretKey as SecpPrivate,
+};
 use rand::rngs::OsRng;
 
 use super::errors::Error;
@@ -19,15 +21,12 @@ pub fn to_public(sig_type: SignatureType, private_key: &[u8]) -> Result<Vec<u8>,
             .map_err(|err| Error::Other(err.to_string()))?
             .public_key()
             .as_bytes()),
-        SignatureType::Secp256k1 => {
+        SignatureType::Secp256k1 | SignatureType::Delegated => {
             let private_key = SecpPrivate::parse_slice(private_key)
                 .map_err(|err| Error::Other(err.to_string()))?;
             let public_key = SecpPublic::from_secret_key(&private_key);
             Ok(public_key.serialize().to_vec())
         }
-        SignatureType::Delegated => {
-            unimplemented!()
-        }
     }
 }
 
@@ -45,7 +44,12 @@ pub fn new_address(sig_type: SignatureType, public_key: &[u8]) -> Result<Address
             Ok(addr)
         }
         SignatureType::Delegated => {
-            unimplemented!()
+            let namespace = Address::ETHEREUM_ACCOUNT_MANAGER_ACTOR
+                .id()
+                .map_err(|err| Error::Other(err.to_string()))?;
+            let addr = Address::new_delegated(namespace, &eth_address(public_key)?)
+                .map_err(|err| Error::Other(err.to_string()))?;
+            Ok(addr)
         }
     }
 }
@@ -67,16 +71,16 @@ pub fn sign(sig_type: SignatureType, private_key: &[u8], msg: &[u8]) -> Result<S
             let priv_key = SecpPrivate::parse_slice(private_key)
                 .map_err(|err| Error::Other(err.to_string()))?;
             let msg_hash = blake2b_256(msg);
-            let message = SecpMessage::parse(&msg_hash);
-            let (sig, recovery_id) = libsecp256k1::sign(&message, &priv_key);
-            let mut new_bytes = [0; 65];
-            new_bytes[..64].copy_from_slice(&sig.serialize());
-            new_bytes[64] = recovery_id.serialize();
-            let crypto_sig = Signature::new_secp256k1(new_bytes.to_vec());
+            let crypto_sig = Signature::new_secp256k1(sign_recoverable(&priv_key, &msg_hash));
             Ok(crypto_sig)
         }
         SignatureType::Delegated => {
-            unimplemented!()
+            let priv_key = SecpPrivate::parse_slice(private_key)
+                .map_err(|err| Error::Other(err.to_string()))?;
+            // delegated keys follow the Ethereum signing scheme
+            let msg_hash = keccak_256(msg);
+            let crypto_sig = Signature::new_delegated(sign_recoverable(&priv_key, &msg_hash));
+            Ok(crypto_sig)
         }
     }
 }
@@ -89,12 +93,30 @@ pub fn generate(sig_type: SignatureType) -> Result<Vec<u8>, Error> {
             let key = BlsPrivate::generate(rng);
             Ok(key.as_bytes())
         }
-        SignatureType::Secp256k1 => {
+        SignatureType::Secp256k1 | SignatureType::Delegated => {
             let key = SecpPrivate::random(rng);
             Ok(key.serialize().to_vec())
         }
-        SignatureType::Delegated => {
-            unimplemented!()
-        }
     }
 }
+
+/// Return the 20-byte Ethereum address for an uncompressed `secp256k1` public
+/// key, i.e. the last 20 bytes of its Keccak-256 hash
+fn eth_address(public_key: &[u8]) -> Result<[u8; 20], Error> {
+    let public_key = SecpPublic::parse_slice(public_key, Some(PublicKeyFormat::Full))
+        .map_err(|err| Error::Other(err.to_string()))?;
+    let mut addr = [0; 20];
+    addr.copy_from_slice(&keccak_256(&public_key.serialize()[1..])[12..]);
+    Ok(addr)
+}
+
+/// Sign a 32-byte digest, returning the 64-byte signature followed by the
+/// recovery id
+fn sign_recoverable(private_key: &SecpPrivate, msg_hash: &[u8; 32]) -> Vec<u8> {
+    let message = SecpMessage::parse(msg_hash);
+    let (sig, recovery_id) = libsecp256k1::sign(&message, private_key);
+    let mut new_bytes = [0; 65];
+    new_bytes[..64].copy_from_slice(&sig.serialize());
+    new_bytes[64] = recovery_id.serialize();
+    new_bytes.to_vec()
+}
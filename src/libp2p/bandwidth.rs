@@ -0,0 +1,646 @@
+// Copyright 2019-2024 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Bandwidth metering for the libp2p transport. Traffic is recorded in
+//! aggregate, per connected peer and per libp2p protocol ID (e.g.
+//! `/fil/hello/1.0.0`), which is what the `Filecoin.NetBandwidthStats*` RPC
+//! methods report.
+//!
+//! Substreams are metered above the stream muxer, where they are not
+//! encrypted, so the protocol of each one is read from its multistream-select
+//! handshake.
+
+use std::{
+    io,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use ahash::HashMap;
+use futures::{
+    future::{MapOk, TryFutureExt as _},
+    io::{IoSlice, IoSliceMut},
+    prelude::*,
+    ready,
+};
+use libp2p::{
+    core::{
+        muxing::{StreamMuxer, StreamMuxerEvent},
+        transport::{ListenerId, TransportError, TransportEvent},
+    },
+    Multiaddr, PeerId,
+};
+use parking_lot::{Mutex, RwLock};
+use pin_project_lite::pin_project;
+
+use crate::rpc_api::net_api::BandwidthStats;
+
+/// Rates are recomputed at most this often, so that frequent queries do not
+/// yield noisy values.
+const MIN_RATE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Substreams whose protocol is not negotiated within this many bytes in
+/// either direction are not attributed to any protocol.
+const MAX_HANDSHAKE_LEN: usize = 1024;
+
+/// The multistream-select messages that are not protocol IDs.
+const MULTISTREAM_MESSAGES: [&str; 3] = ["/multistream/1.0.0", "na", "ls"];
+
+/// Shared handle to the bandwidth counters of a [`Transport`].
+#[derive(Debug, Default)]
+pub struct BandwidthSinks {
+    total: Meter,
+    by_peer: RwLock<HashMap<PeerId, Arc<Meter>>>,
+    by_protocol: RwLock<HashMap<String, Arc<Meter>>>,
+}
+
+impl BandwidthSinks {
+    /// Returns the bandwidth usage over all connections.
+    pub fn stats(&self) -> BandwidthStats {
+        self.total.stats()
+    }
+
+    /// Returns the bandwidth usage of every connected peer.
+    pub fn stats_by_peer(&self) -> HashMap<PeerId, BandwidthStats> {
+        self.by_peer
+            .read()
+            .iter()
+            .map(|(peer, meter)| (*peer, meter.stats()))
+            .collect()
+    }
+
+    /// Returns the bandwidth usage of every negotiated libp2p protocol.
+    pub fn stats_by_protocol(&self) -> HashMap<String, BandwidthStats> {
+        self.by_protocol
+            .read()
+            .iter()
+            .map(|(protocol, meter)| (protocol.clone(), meter.stats()))
+            .collect()
+    }
+
+    /// Forgets the usage of `peer`, once its last connection is closed.
+    pub fn remove_peer(&self, peer: &PeerId) {
+        self.by_peer.write().remove(peer);
+    }
+
+    fn peer_meter(&self, peer: PeerId) -> Arc<Meter> {
+        if let Some(meter) = self.by_peer.read().get(&peer) {
+            return meter.clone();
+        }
+        self.by_peer.write().entry(peer).or_default().clone()
+    }
+
+    fn protocol_meter(&self, protocol: &str) -> Arc<Meter> {
+        if let Some(meter) = self.by_protocol.read().get(protocol) {
+            return meter.clone();
+        }
+        self.by_protocol
+            .write()
+            .entry(protocol.to_owned())
+            .or_default()
+            .clone()
+    }
+}
+
+#[derive(Debug, Default)]
+struct Meter {
+    inbound: AtomicU64,
+    outbound: AtomicU64,
+    sample: Mutex<RateSample>,
+}
+
+#[derive(Debug, Default)]
+struct RateSample {
+    at: Option<Instant>,
+    inbound: u64,
+    outbound: u64,
+    rate_in: f64,
+    rate_out: f64,
+}
+
+impl Meter {
+    fn stats(&self) -> BandwidthStats {
+        let total_in = self.inbound.load(Ordering::Relaxed);
+        let total_out = self.outbound.load(Ordering::Relaxed);
+        let now = Instant::now();
+        let mut sample = self.sample.lock();
+        match sample.at {
+            Some(at) if now.duration_since(at) < MIN_RATE_INTERVAL => {}
+            Some(at) => {
+                let elapsed = now.duration_since(at).as_secs_f64();
+                sample.rate_in = total_in.saturating_sub(sample.inbound) as f64 / elapsed;
+                sample.rate_out = total_out.saturating_sub(sample.outbound) as f64 / elapsed;
+                sample.at = Some(now);
+                sample.inbound = total_in;
+                sample.outbound = total_out;
+            }
+            None => {
+                sample.at = Some(now);
+                sample.inbound = total_in;
+                sample.outbound = total_out;
+            }
+        }
+        BandwidthStats {
+            total_in,
+            total_out,
+            rate_in: sample.rate_in,
+            rate_out: sample.rate_out,
+        }
+    }
+}
+
+/// The meters a single connection reports its traffic to.
+#[derive(Debug, Clone)]
+struct ConnectionMeters {
+    sinks: Arc<BandwidthSinks>,
+    peer: Arc<Meter>,
+}
+
+impl ConnectionMeters {
+    fn new(sinks: &Arc<BandwidthSinks>, peer: PeerId) -> Self {
+        Self {
+            sinks: sinks.clone(),
+            peer: sinks.peer_meter(peer),
+        }
+    }
+
+    fn record_inbound(&self, num_bytes: usize) {
+        let num_bytes = u64::try_from(num_bytes).unwrap_or(u64::MAX);
+        for meter in [&self.sinks.total, &self.peer] {
+            meter.inbound.fetch_add(num_bytes, Ordering::Relaxed);
+        }
+    }
+
+    fn record_outbound(&self, num_bytes: usize) {
+        let num_bytes = u64::try_from(num_bytes).unwrap_or(u64::MAX);
+        for meter in [&self.sinks.total, &self.peer] {
+            meter.outbound.fetch_add(num_bytes, Ordering::Relaxed);
+        }
+    }
+
+    fn stream<S>(&self, inner: S) -> MeteredStream<S> {
+        MeteredStream {
+            inner,
+            meters: self.clone(),
+            protocol: ProtocolMeter::default(),
+        }
+    }
+}
+
+/// Attributes the traffic of a substream to the protocol negotiated on it.
+#[derive(Debug)]
+enum ProtocolMeter {
+    /// The bytes received and sent so far, while the protocol is negotiated
+    Negotiating {
+        inbound: Vec<u8>,
+        outbound: Vec<u8>,
+    },
+    Negotiated(Arc<Meter>),
+    /// The handshake was not understood, or did not complete in
+    /// [`MAX_HANDSHAKE_LEN`] bytes
+    Unknown,
+}
+
+impl Default for ProtocolMeter {
+    fn default() -> Self {
+        Self::Negotiating {
+            inbound: vec![],
+            outbound: vec![],
+        }
+    }
+}
+
+impl ProtocolMeter {
+    fn record_inbound(&mut self, sinks: &BandwidthSinks, bytes: &[u8]) {
+        match self {
+            Self::Negotiating { inbound, .. } => {
+                inbound.extend_from_slice(bytes);
+                self.negotiate(sinks);
+            }
+            Self::Negotiated(meter) => {
+                meter
+                    .inbound
+                    .fetch_add(bytes.len() as u64, Ordering::Relaxed);
+            }
+            Self::Unknown => {}
+        }
+    }
+
+    fn record_outbound(&mut self, sinks: &BandwidthSinks, bytes: &[u8]) {
+        match self {
+            Self::Negotiating { outbound, .. } => {
+                outbound.extend_from_slice(bytes);
+                self.negotiate(sinks);
+            }
+            Self::Negotiated(meter) => {
+                meter
+                    .outbound
+                    .fetch_add(bytes.len() as u64, Ordering::Relaxed);
+            }
+            Self::Unknown => {}
+        }
+    }
+
+    /// A protocol is negotiated once it has been both proposed and accepted,
+    /// i.e. once it was seen in both directions. This holds on both the
+    /// dialing and the listening side.
+    fn negotiate(&mut self, sinks: &BandwidthSinks) {
+        let Self::Negotiating { inbound, outbound } = self else {
+            return;
+        };
+        let received = handshake_protocols(inbound);
+        let negotiated = handshake_protocols(outbound)
+            .into_iter()
+            .find(|protocol| received.contains(protocol));
+        *self = match negotiated {
+            Some(protocol) => {
+                let meter = sinks.protocol_meter(protocol);
+                meter
+                    .inbound
+                    .fetch_add(inbound.len() as u64, Ordering::Relaxed);
+                meter
+                    .outbound
+                    .fetch_add(outbound.len() as u64, Ordering::Relaxed);
+                Self::Negotiated(meter)
+            }
+            None if inbound.len().max(outbound.len()) > MAX_HANDSHAKE_LEN => Self::Unknown,
+            None => return,
+        };
+    }
+}
+
+/// The protocol IDs in the leading multistream-select messages of `bytes`.
+/// Each message is a newline-terminated string prefixed with its length.
+fn handshake_protocols(mut bytes: &[u8]) -> Vec<&str> {
+    let mut protocols = vec![];
+    while let Ok((len, rest)) = unsigned_varint::decode::usize(bytes) {
+        let (Some(message), Some(rest)) = (rest.get(..len), rest.get(len..)) else {
+            break;
+        };
+        let Some(message) = message
+            .strip_suffix(b"\n")
+            .and_then(|message| std::str::from_utf8(message).ok())
+        else {
+            break;
+        };
+        if !MULTISTREAM_MESSAGES.contains(&message) {
+            protocols.push(message);
+        }
+        bytes = rest;
+    }
+    protocols
+}
+
+type MapUpgrade<M> = Box<dyn FnOnce((PeerId, M)) -> (PeerId, Muxer<M>) + Send>;
+
+pin_project! {
+    /// Wraps around a [`libp2p::core::Transport`] and counts the bytes that go
+    /// through all the streams of its connections.
+    pub struct Transport<T> {
+        #[pin]
+        transport: T,
+        sinks: Arc<BandwidthSinks>,
+    }
+}
+
+impl<T> Transport<T> {
+    pub fn new(transport: T) -> (Self, Arc<BandwidthSinks>) {
+        let sinks = Arc::new(BandwidthSinks::default());
+        (
+            Self {
+                transport,
+                sinks: sinks.clone(),
+            },
+            sinks,
+        )
+    }
+
+    fn map_upgrade<M>(sinks: &Arc<BandwidthSinks>) -> MapUpgrade<M> {
+        let sinks = sinks.clone();
+        Box::new(move |(peer_id, stream_muxer)| {
+            let meters = ConnectionMeters::new(&sinks, peer_id);
+            (peer_id, Muxer::new(stream_muxer, meters))
+        })
+    }
+}
+
+impl<T, M> libp2p::core::Transport for Transport<T>
+where
+    T: libp2p::core::Transport<Output = (PeerId, M)>,
+    M: StreamMuxer + Send + 'static,
+    M::Substream: Send + 'static,
+    M::Error: Send + Sync + 'static,
+{
+    type Output = (PeerId, Muxer<M>);
+    type Error = T::Error;
+    type ListenerUpgrade = MapOk<T::ListenerUpgrade, MapUpgrade<M>>;
+    type Dial = MapOk<T::Dial, MapUpgrade<M>>;
+
+    fn listen_on(
+        &mut self,
+        id: ListenerId,
+        addr: Multiaddr,
+    ) -> Result<(), TransportError<Self::Error>> {
+        self.transport.listen_on(id, addr)
+    }
+
+    fn remove_listener(&mut self, id: ListenerId) -> bool {
+        self.transport.remove_listener(id)
+    }
+
+    fn dial(&mut self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
+        let map_upgrade = Self::map_upgrade(&self.sinks);
+        Ok(self.transport.dial(addr)?.map_ok(map_upgrade))
+    }
+
+    fn dial_as_listener(
+        &mut self,
+        addr: Multiaddr,
+    ) -> Result<Self::Dial, TransportError<Self::Error>> {
+        let map_upgrade = Self::map_upgrade(&self.sinks);
+        Ok(self.transport.dial_as_listener(addr)?.map_ok(map_upgrade))
+    }
+
+    fn address_translation(&self, server: &Multiaddr, observed: &Multiaddr) -> Option<Multiaddr> {
+        self.transport.address_translation(server, observed)
+    }
+
+    fn poll(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<TransportEvent<Self::ListenerUpgrade, Self::Error>> {
+        let this = self.project();
+        match this.transport.poll(cx) {
+            Poll::Ready(TransportEvent::Incoming {
+                listener_id,
+                upgrade,
+                local_addr,
+                send_back_addr,
+            }) => {
+                let map_upgrade = Self::map_upgrade(this.sinks);
+                Poll::Ready(TransportEvent::Incoming {
+                    listener_id,
+                    upgrade: upgrade.map_ok(map_upgrade),
+                    local_addr,
+                    send_back_addr,
+                })
+            }
+            Poll::Ready(other) => {
+                let mapped = other.map_upgrade(|_upgrade| unreachable!("case already matched"));
+                Poll::Ready(mapped)
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+pin_project! {
+    /// Wraps around a [`StreamMuxer`] and meters all the streams it opens.
+    pub struct Muxer<M> {
+        #[pin]
+        inner: M,
+        meters: ConnectionMeters,
+    }
+}
+
+impl<M> Muxer<M> {
+    fn new(inner: M, meters: ConnectionMeters) -> Self {
+        Self { inner, meters }
+    }
+}
+
+impl<M: StreamMuxer> StreamMuxer for Muxer<M> {
+    type Substream = MeteredStream<M::Substream>;
+    type Error = M::Error;
+
+    fn poll(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<StreamMuxerEvent, Self::Error>> {
+        self.project().inner.poll(cx)
+    }
+
+    fn poll_inbound(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Self::Substream, Self::Error>> {
+        let this = self.project();
+        let inner = ready!(this.inner.poll_inbound(cx)?);
+        Poll::Ready(Ok(this.meters.stream(inner)))
+    }
+
+    fn poll_outbound(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Self::Substream, Self::Error>> {
+        let this = self.project();
+        let inner = ready!(this.inner.poll_outbound(cx)?);
+        Poll::Ready(Ok(this.meters.stream(inner)))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.project().inner.poll_close(cx)
+    }
+}
+
+pin_project! {
+    /// Wraps around an [`AsyncRead`] + [`AsyncWrite`] and records the bytes
+    /// that go through it.
+    pub struct MeteredStream<S> {
+        #[pin]
+        inner: S,
+        meters: ConnectionMeters,
+        protocol: ProtocolMeter,
+    }
+}
+
+impl<S> MeteredStream<S> {
+    fn record_inbound(
+        meters: &ConnectionMeters,
+        protocol: &mut ProtocolMeter,
+        bufs: &[&[u8]],
+        num_bytes: usize,
+    ) {
+        meters.record_inbound(num_bytes);
+        for buf in filled(bufs, num_bytes) {
+            protocol.record_inbound(&meters.sinks, buf);
+        }
+    }
+
+    fn record_outbound(
+        meters: &ConnectionMeters,
+        protocol: &mut ProtocolMeter,
+        bufs: &[&[u8]],
+        num_bytes: usize,
+    ) {
+        meters.record_outbound(num_bytes);
+        for buf in filled(bufs, num_bytes) {
+            protocol.record_outbound(&meters.sinks, buf);
+        }
+    }
+}
+
+/// The first `num_bytes` bytes of `bufs`, which a vectored read filled or a
+/// vectored write sent.
+fn filled<'a>(bufs: &'a [&'a [u8]], mut num_bytes: usize) -> impl Iterator<Item = &'a [u8]> {
+    bufs.iter().map_while(move |buf| {
+        let len = buf.len().min(num_bytes);
+        num_bytes -= len;
+        (len > 0).then(|| buf.get(..len)).flatten()
+    })
+}
+
+impl<S: AsyncRead> AsyncRead for MeteredStream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.project();
+        let num_bytes = ready!(this.inner.poll_read(cx, buf))?;
+        Self::record_inbound(this.meters, this.protocol, &[buf], num_bytes);
+        Poll::Ready(Ok(num_bytes))
+    }
+
+    fn poll_read_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &mut [IoSliceMut<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.project();
+        let num_bytes = ready!(this.inner.poll_read_vectored(cx, bufs))?;
+        let bufs: Vec<&[u8]> = bufs.iter().map(|buf| &**buf).collect();
+        Self::record_inbound(this.meters, this.protocol, &bufs, num_bytes);
+        Poll::Ready(Ok(num_bytes))
+    }
+}
+
+impl<S: AsyncWrite> AsyncWrite for MeteredStream<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.project();
+        let num_bytes = ready!(this.inner.poll_write(cx, buf))?;
+        Self::record_outbound(this.meters, this.protocol, &[buf], num_bytes);
+        Poll::Ready(Ok(num_bytes))
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.project();
+        let num_bytes = ready!(this.inner.poll_write_vectored(cx, bufs))?;
+        let bufs: Vec<&[u8]> = bufs.iter().map(|buf| &**buf).collect();
+        Self::record_outbound(this.meters, this.protocol, &bufs, num_bytes);
+        Poll::Ready(Ok(num_bytes))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().inner.poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().inner.poll_close(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Frames `messages` as multistream-select messages.
+    fn handshake(messages: &[&str]) -> Vec<u8> {
+        let mut bytes = vec![];
+        for message in messages {
+            let mut len = unsigned_varint::encode::usize_buffer();
+            bytes.extend_from_slice(unsigned_varint::encode::usize(message.len() + 1, &mut len));
+            bytes.extend_from_slice(message.as_bytes());
+            bytes.push(b'\n');
+        }
+        bytes
+    }
+
+    #[test]
+    fn connection_traffic_is_recorded_everywhere() {
+        let sinks = Arc::new(BandwidthSinks::default());
+        let peer = PeerId::random();
+        let meters = ConnectionMeters::new(&sinks, peer);
+        meters.record_inbound(100);
+        meters.record_outbound(40);
+        ConnectionMeters::new(&sinks, peer).record_inbound(1);
+
+        let total = sinks.stats();
+        assert_eq!((total.total_in, total.total_out), (101, 40));
+        let by_peer = sinks.stats_by_peer();
+        assert_eq!(by_peer.len(), 1);
+        assert_eq!(by_peer[&peer].total_in, 101);
+
+        sinks.remove_peer(&peer);
+        assert!(sinks.stats_by_peer().is_empty());
+        assert_eq!(sinks.stats().total_in, 101);
+    }
+
+    #[test]
+    fn traffic_is_recorded_by_negotiated_protocol() {
+        let sinks = BandwidthSinks::default();
+        // The listening side, declining a first proposal
+        let mut protocol = ProtocolMeter::default();
+        let proposals = handshake(&["/multistream/1.0.0", "/fil/hello/2.0.0", "/fil/hello/1.0.0"]);
+        protocol.record_inbound(&sinks, &proposals);
+        let answers = handshake(&["/multistream/1.0.0", "na", "/fil/hello/1.0.0"]);
+        let (first, rest) = answers.split_at(25);
+        protocol.record_outbound(&sinks, first);
+        assert!(sinks.stats_by_protocol().is_empty());
+        protocol.record_outbound(&sinks, rest);
+        protocol.record_inbound(&sinks, &[0; 10]);
+        protocol.record_outbound(&sinks, &[0; 3]);
+
+        // The dialing side, sending data along with its proposal
+        let mut protocol = ProtocolMeter::default();
+        let mut proposal = handshake(&["/multistream/1.0.0", "/meshsub/1.1.0"]);
+        proposal.extend_from_slice(&[0; 5]);
+        protocol.record_outbound(&sinks, &proposal);
+        protocol.record_inbound(
+            &sinks,
+            &handshake(&["/multistream/1.0.0", "/meshsub/1.1.0"]),
+        );
+
+        let stats = sinks.stats_by_protocol();
+        assert_eq!(stats.len(), 2);
+        let hello = &stats["/fil/hello/1.0.0"];
+        assert_eq!(
+            (hello.total_in, hello.total_out),
+            (proposals.len() as u64 + 10, answers.len() as u64 + 3)
+        );
+        assert_eq!(stats["/meshsub/1.1.0"].total_out, proposal.len() as u64);
+    }
+
+    #[test]
+    fn unknown_handshakes_are_not_attributed() {
+        let sinks = BandwidthSinks::default();
+        let mut protocol = ProtocolMeter::default();
+        for _ in 0..=MAX_HANDSHAKE_LEN {
+            protocol.record_inbound(&sinks, b"x");
+            protocol.record_outbound(&sinks, b"x");
+        }
+        assert!(matches!(protocol, ProtocolMeter::Unknown));
+        assert!(sinks.stats_by_protocol().is_empty());
+    }
+
+    #[test]
+    fn vectored_transfers_are_split_by_buffer() {
+        let bufs: [&[u8]; 3] = [b"abc", b"de", b"fgh"];
+        assert_eq!(filled(&bufs, 4).collect::<Vec<_>>(), [&b"abc"[..], b"d"]);
+        assert_eq!(filled(&bufs, 0).count(), 0);
+        assert_eq!(filled(&bufs, 100).count(), 3);
+    }
+}
@@ -1,6 +1,9 @@
 // Copyright 2019-2024 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use std::sync::Arc;
+
+use crate::db::SettingsStore;
 use crate::libp2p_bitswap::BitswapBehaviour;
 use crate::utils::{encoding::blake2b_256, version::FOREST_VERSION_STRING};
 use ahash::{HashMap, HashSet};
@@ -22,7 +25,9 @@ use tracing::info;
 use crate::libp2p::{
     chain_exchange::ChainExchangeBehaviour,
     config::Libp2pConfig,
+    conn_manager::{ConnectionManager, ConnectionWatermarks},
     discovery::{DiscoveryBehaviour, DiscoveryConfig},
+    gater::ConnectionGater,
     gossip_params::{build_peer_score_params, build_peer_score_threshold},
     hello::HelloBehaviour,
 };
@@ -33,11 +38,13 @@ use super::discovery::{DerivedDiscoveryBehaviourEvent, DiscoveryEvent, PeerInfo}
 /// for a Filecoin node.
 #[derive(NetworkBehaviour)]
 pub(in crate::libp2p) struct ForestBehaviour {
-    gossipsub: gossipsub::Behaviour,
+    pub(super) gossipsub: gossipsub::Behaviour,
     pub(super) discovery: DiscoveryBehaviour,
     ping: ping::Behaviour,
     connection_limits: connection_limits::Behaviour,
     pub(super) blocked_peers: allow_block_list::Behaviour<allow_block_list::BlockedPeers>,
+    pub(super) gater: ConnectionGater,
+    conn_manager: ConnectionManager,
     pub(super) hello: HelloBehaviour,
     pub(super) chain_exchange: ChainExchangeBehaviour,
     pub(super) bitswap: BitswapBehaviour,
@@ -63,6 +70,8 @@ impl ForestBehaviour {
         local_key: &Keypair,
         config: &Libp2pConfig,
         network_name: &str,
+        settings: Arc<dyn SettingsStore + Sync + Send>,
+        watermarks: Arc<ConnectionWatermarks>,
     ) -> anyhow::Result<Self> {
         let mut gs_config_builder = gossipsub::ConfigBuilder::default();
         gs_config_builder.max_transmit_size(1 << 20);
@@ -137,6 +146,8 @@ impl ForestBehaviour {
             ping: Default::default(),
             connection_limits,
             blocked_peers: Default::default(),
+            gater: ConnectionGater::new(settings)?,
+            conn_manager: ConnectionManager::new(watermarks),
             bitswap,
             hello: HelloBehaviour::default(),
             chain_exchange: ChainExchangeBehaviour::default(),
@@ -175,7 +175,14 @@ mod tests {
         });
 
         let response = make_chain_exchange_response(
-            &ChainStore::new(db.clone(), db, Arc::new(ChainConfig::default()), gen_block).unwrap(),
+            &ChainStore::new(
+                db.clone(),
+                db.clone(),
+                db,
+                Arc::new(ChainConfig::default()),
+                gen_block,
+            )
+            .unwrap(),
             &ChainExchangeRequest {
                 start: cids,
                 request_len: 2,
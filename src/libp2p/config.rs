@@ -1,6 +1,7 @@
 // Copyright 2019-2024 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use itertools::Itertools as _;
 use libp2p::Multiaddr;
 use serde::{Deserialize, Serialize};
 #[cfg(test)]
@@ -22,11 +23,24 @@ pub struct Libp2pConfig {
         }
     )))]
     pub listening_multiaddrs: Vec<Multiaddr>,
-    /// Bootstrap peer list.
+    /// Bootstrap peer list. Set in the configuration, it replaces that of the
+    /// network like [`Libp2pConfig::bootstrap_peers_replace`]. The daemon
+    /// overwrites it with the resolved list, see
+    /// [`Libp2pConfig::resolve_bootstrap_peers`].
     #[cfg_attr(test, arbitrary(gen(
         |g| vec![Ipv4Addr::arbitrary(g).into()]
     )))]
     pub bootstrap_peers: Vec<Multiaddr>,
+    /// Bootstrap peers dialed in addition to those of the network.
+    #[cfg_attr(test, arbitrary(gen(
+        |g| vec![Ipv4Addr::arbitrary(g).into()]
+    )))]
+    pub bootstrap_peers_extra: Vec<Multiaddr>,
+    /// Bootstrap peers dialed instead of those of the network.
+    #[cfg_attr(test, arbitrary(gen(
+        |g| vec![Ipv4Addr::arbitrary(g).into()]
+    )))]
+    pub bootstrap_peers_replace: Vec<Multiaddr>,
     /// MDNS discovery enabled.
     pub mdns: bool,
     /// Kademlia discovery enabled.
@@ -40,9 +54,86 @@ impl Default for Libp2pConfig {
         Self {
             listening_multiaddrs: vec!["/ip4/0.0.0.0/tcp/0".parse().expect("Infallible")],
             bootstrap_peers: vec![],
+            bootstrap_peers_extra: vec![],
+            bootstrap_peers_replace: vec![],
             mdns: false,
             kademlia: true,
             target_peer_count: 75,
         }
     }
 }
+
+impl Libp2pConfig {
+    /// Resolves the bootstrap peers of the node: the `builtin` peers of the
+    /// network, unless replaced in the configuration, followed by the extra
+    /// peers of the configuration and the `persisted` ones, e.g. added with
+    /// `forest-cli net bootstrap add`. Duplicates are dropped.
+    pub fn resolve_bootstrap_peers(
+        &self,
+        builtin: &[Multiaddr],
+        persisted: &[Multiaddr],
+    ) -> Vec<Multiaddr> {
+        let base = if !self.bootstrap_peers_replace.is_empty() {
+            &self.bootstrap_peers_replace
+        } else if !self.bootstrap_peers.is_empty() {
+            &self.bootstrap_peers
+        } else {
+            builtin
+        };
+        base.iter()
+            .chain(&self.bootstrap_peers_extra)
+            .chain(persisted)
+            .unique()
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peers(addrs: &[&str]) -> Vec<Multiaddr> {
+        addrs.iter().map(|addr| addr.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn resolve_bootstrap_peers_precedence() {
+        let builtin = peers(&["/dns4/builtin/tcp/1"]);
+        let persisted = peers(&["/dns4/persisted/tcp/1", "/dns4/builtin/tcp/1"]);
+
+        // The built-in peers come first, followed by the extra ones
+        let config = Libp2pConfig {
+            bootstrap_peers_extra: peers(&["/dns4/extra/tcp/1"]),
+            ..Default::default()
+        };
+        assert_eq!(
+            config.resolve_bootstrap_peers(&builtin, &persisted),
+            peers(&[
+                "/dns4/builtin/tcp/1",
+                "/dns4/extra/tcp/1",
+                "/dns4/persisted/tcp/1"
+            ])
+        );
+
+        // Replacing peers drops the built-in ones, but not the extra ones
+        let config = Libp2pConfig {
+            bootstrap_peers_replace: peers(&["/dns4/replace/tcp/1"]),
+            bootstrap_peers: peers(&["/dns4/legacy/tcp/1"]),
+            ..config
+        };
+        assert_eq!(
+            config.resolve_bootstrap_peers(&builtin, &[]),
+            peers(&["/dns4/replace/tcp/1", "/dns4/extra/tcp/1"])
+        );
+        // The former `bootstrap_peers` key replaces them as well
+        let config = Libp2pConfig {
+            bootstrap_peers_replace: vec![],
+            ..config
+        };
+        assert_eq!(
+            config.resolve_bootstrap_peers(&builtin, &[]),
+            peers(&["/dns4/legacy/tcp/1", "/dns4/extra/tcp/1"])
+        );
+    }
+}
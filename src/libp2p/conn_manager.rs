@@ -0,0 +1,234 @@
+// Copyright 2019-2024 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Connection manager trimming the number of connected peers back to a low
+//! watermark whenever it grows past a high watermark, in the same fashion as
+//! the `go-libp2p` connection manager used by Lotus. The watermarks are shared
+//! with the RPC server so that `Filecoin.NetSetLimit` can adjust them at
+//! runtime.
+
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    time::Instant,
+};
+
+use ahash::HashMap;
+use futures::task::AtomicWaker;
+use libp2p::{
+    core::{Endpoint, Multiaddr},
+    swarm::{
+        behaviour::ConnectionEstablished, dummy, CloseConnection, ConnectionClosed,
+        ConnectionDenied, ConnectionId, FromSwarm, NetworkBehaviour, THandler, THandlerInEvent,
+        THandlerOutEvent, ToSwarm,
+    },
+    PeerId,
+};
+use tracing::debug;
+
+use crate::rpc_api::net_api::NetLimit;
+
+/// Low and high watermarks on the number of connected peers.
+#[derive(Debug)]
+pub struct ConnectionWatermarks {
+    low: AtomicU32,
+    high: AtomicU32,
+    waker: AtomicWaker,
+}
+
+impl Default for ConnectionWatermarks {
+    fn default() -> Self {
+        Self::new(&NetLimit::from_target_peer_count(
+            super::Libp2pConfig::default().target_peer_count,
+        ))
+    }
+}
+
+impl ConnectionWatermarks {
+    pub fn new(limit: &NetLimit) -> Self {
+        Self {
+            low: AtomicU32::new(limit.conn_mgr_low),
+            high: AtomicU32::new(limit.conn_mgr_high),
+            waker: AtomicWaker::new(),
+        }
+    }
+
+    pub fn get(&self) -> NetLimit {
+        NetLimit {
+            conn_mgr_low: self.low.load(Ordering::Relaxed),
+            conn_mgr_high: self.high.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Updates the watermarks, trimming connections right away if there are
+    /// now too many.
+    pub fn set(&self, limit: &NetLimit) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            limit.conn_mgr_low <= limit.conn_mgr_high,
+            "the low watermark ({}) must not exceed the high watermark ({})",
+            limit.conn_mgr_low,
+            limit.conn_mgr_high
+        );
+        self.low.store(limit.conn_mgr_low, Ordering::Relaxed);
+        self.high.store(limit.conn_mgr_high, Ordering::Relaxed);
+        self.waker.wake();
+        Ok(())
+    }
+}
+
+/// Enforces [`ConnectionWatermarks`], closing the most recently connected
+/// peers first.
+pub struct ConnectionManager {
+    watermarks: Arc<ConnectionWatermarks>,
+    /// When each connected peer established its first connection.
+    peers: HashMap<PeerId, Instant>,
+    /// Peers picked by the last trim that are yet to be disconnected.
+    to_close: VecDeque<PeerId>,
+}
+
+impl ConnectionManager {
+    pub fn new(watermarks: Arc<ConnectionWatermarks>) -> Self {
+        Self {
+            watermarks,
+            peers: Default::default(),
+            to_close: Default::default(),
+        }
+    }
+
+    /// Returns the peers to disconnect to get back to the low watermark, if
+    /// the high watermark is exceeded.
+    fn peers_to_trim(&self) -> Vec<PeerId> {
+        let NetLimit {
+            conn_mgr_low,
+            conn_mgr_high,
+        } = self.watermarks.get();
+        if self.peers.len() <= conn_mgr_high as usize {
+            return vec![];
+        }
+        let mut peers = self.peers.iter().collect::<Vec<_>>();
+        peers.sort_by_key(|(_, since)| std::cmp::Reverse(**since));
+        peers
+            .into_iter()
+            .take(self.peers.len() - conn_mgr_low as usize)
+            .map(|(peer, _)| *peer)
+            .collect()
+    }
+}
+
+impl NetworkBehaviour for ConnectionManager {
+    type ConnectionHandler = dummy::ConnectionHandler;
+    type ToSwarm = std::convert::Infallible;
+
+    fn handle_established_inbound_connection(
+        &mut self,
+        _: ConnectionId,
+        _: PeerId,
+        _: &Multiaddr,
+        _: &Multiaddr,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        Ok(dummy::ConnectionHandler)
+    }
+
+    fn handle_established_outbound_connection(
+        &mut self,
+        _: ConnectionId,
+        _: PeerId,
+        _: &Multiaddr,
+        _: Endpoint,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        Ok(dummy::ConnectionHandler)
+    }
+
+    fn on_swarm_event(&mut self, event: FromSwarm) {
+        match event {
+            FromSwarm::ConnectionEstablished(ConnectionEstablished { peer_id, .. }) => {
+                self.peers.entry(peer_id).or_insert_with(Instant::now);
+            }
+            FromSwarm::ConnectionClosed(ConnectionClosed {
+                peer_id,
+                remaining_established: 0,
+                ..
+            }) => {
+                self.peers.remove(&peer_id);
+            }
+            _ => {}
+        }
+    }
+
+    fn on_connection_handler_event(
+        &mut self,
+        _: PeerId,
+        _: ConnectionId,
+        event: THandlerOutEvent<Self>,
+    ) {
+        match event {}
+    }
+
+    fn poll(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<ToSwarm<Self::ToSwarm, THandlerInEvent<Self>>> {
+        self.watermarks.waker.register(cx.waker());
+        if self.to_close.is_empty() {
+            self.to_close.extend(self.peers_to_trim());
+        }
+        if let Some(peer_id) = self.to_close.pop_front() {
+            debug!("Trimming connection to {peer_id}");
+            self.peers.remove(&peer_id);
+            return Poll::Ready(ToSwarm::CloseConnection {
+                peer_id,
+                connection: CloseConnection::All,
+            });
+        }
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manager(low: u32, high: u32, peers: usize) -> ConnectionManager {
+        let mut manager = ConnectionManager::new(Arc::new(ConnectionWatermarks::new(&NetLimit {
+            conn_mgr_low: low,
+            conn_mgr_high: high,
+        })));
+        for _ in 0..peers {
+            manager.peers.insert(PeerId::random(), Instant::now());
+        }
+        manager
+    }
+
+    #[test]
+    fn trims_to_low_watermark_above_high() {
+        assert!(manager(2, 4, 4).peers_to_trim().is_empty());
+        assert_eq!(manager(2, 4, 5).peers_to_trim().len(), 3);
+    }
+
+    #[test]
+    fn trims_newest_peers_first() {
+        let mut manager = manager(1, 1, 0);
+        let oldest = PeerId::random();
+        manager
+            .peers
+            .insert(oldest, Instant::now() - std::time::Duration::from_secs(1));
+        manager.peers.insert(PeerId::random(), Instant::now());
+        assert_eq!(manager.peers_to_trim().len(), 1);
+        assert_ne!(manager.peers_to_trim()[0], oldest);
+    }
+
+    #[test]
+    fn rejects_low_above_high() {
+        let watermarks = ConnectionWatermarks::default();
+        assert!(watermarks
+            .set(&NetLimit {
+                conn_mgr_low: 5,
+                conn_mgr_high: 4,
+            })
+            .is_err());
+    }
+}
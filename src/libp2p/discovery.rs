@@ -8,6 +8,7 @@ use std::{
     time::Duration,
 };
 
+use ::futures::channel::oneshot;
 use ahash::{HashMap, HashMapExt, HashSet, HashSetExt};
 use libp2p::{
     autonat,
@@ -190,6 +191,8 @@ impl<'a> DiscoveryConfig<'a> {
             target_peer_count,
             custom_seed_peers: user_defined,
             pending_dial_opts: VecDeque::new(),
+            pending_find_peer: HashMap::new(),
+            pending_dials: HashMap::new(),
         })
     }
 }
@@ -219,6 +222,10 @@ pub struct DiscoveryBehaviour {
     custom_seed_peers: Vec<(PeerId, Multiaddr)>,
     /// Options to configure dials to known peers.
     pending_dial_opts: VecDeque<DialOpts>,
+    /// Peer lookups waiting on a Kademlia query to finish.
+    pending_find_peer: HashMap<kad::QueryId, (PeerId, oneshot::Sender<HashSet<Multiaddr>>)>,
+    /// Dials waiting for the connection to be established or to fail.
+    pending_dials: HashMap<PeerId, Vec<oneshot::Sender<bool>>>,
 }
 
 #[derive(Default)]
@@ -245,6 +252,80 @@ impl DiscoveryBehaviour {
         self.peer_info.get(peer_id)
     }
 
+    /// Looks up the addresses of a peer. If none are known locally, a Kademlia
+    /// query for the peer is started and the channel is answered once it
+    /// completes.
+    pub fn find_peer(
+        &mut self,
+        peer_id: PeerId,
+        response_channel: oneshot::Sender<HashSet<Multiaddr>>,
+    ) {
+        let addresses = self.known_addresses(&peer_id);
+        match self.discovery.kademlia.as_mut() {
+            Some(kademlia) if addresses.is_empty() => {
+                let query_id = kademlia.get_closest_peers(peer_id);
+                self.pending_find_peer
+                    .insert(query_id, (peer_id, response_channel));
+            }
+            _ => {
+                let _ = response_channel.send(addresses);
+            }
+        }
+    }
+
+    /// Dials a peer, answering the channel with whether a connection could be
+    /// established.
+    pub fn dial(
+        &mut self,
+        peer_id: PeerId,
+        addresses: Vec<Multiaddr>,
+        response_channel: oneshot::Sender<bool>,
+    ) {
+        if self.peers.contains(&peer_id) {
+            let _ = response_channel.send(true);
+            return;
+        }
+        self.pending_dials
+            .entry(peer_id)
+            .or_default()
+            .push(response_channel);
+        self.pending_dial_opts.push_back(
+            DialOpts::peer_id(peer_id)
+                .condition(PeerCondition::Disconnected)
+                .addresses(addresses)
+                .build(),
+        );
+    }
+
+    fn resolve_dials(&mut self, peer_id: &PeerId, connected: bool) {
+        for response_channel in self.pending_dials.remove(peer_id).unwrap_or_default() {
+            let _ = response_channel.send(connected);
+        }
+    }
+
+    /// Returns the addresses of a peer known from open connections and the
+    /// Kademlia routing table.
+    pub fn known_addresses(&mut self, peer_id: &PeerId) -> HashSet<Multiaddr> {
+        let mut addresses = self
+            .peer_info
+            .get(peer_id)
+            .map(|info| info.addresses.clone())
+            .unwrap_or_default();
+        if let Some(bucket) = self
+            .discovery
+            .kademlia
+            .as_mut()
+            .and_then(|kademlia| kademlia.kbucket(*peer_id))
+        {
+            for entry in bucket.iter() {
+                if entry.node.key.preimage() == peer_id {
+                    addresses.extend(entry.node.value.iter().cloned());
+                }
+            }
+        }
+        addresses
+    }
+
     /// Bootstrap Kademlia network
     pub fn bootstrap(&mut self) -> Result<kad::QueryId, String> {
         if let Some(active_kad) = self.discovery.kademlia.as_mut() {
@@ -347,6 +428,12 @@ impl NetworkBehaviour for DiscoveryBehaviour {
                     self.pending_events
                         .push_back(DiscoveryEvent::PeerConnected(e.peer_id));
                 }
+                self.resolve_dials(&e.peer_id, true);
+            }
+            FromSwarm::DialFailure(e) => {
+                if let Some(peer_id) = e.peer_id {
+                    self.resolve_dials(&peer_id, false);
+                }
             }
             FromSwarm::ConnectionClosed(e) => {
                 if e.remaining_established == 0 {
@@ -450,6 +537,18 @@ impl NetworkBehaviour for DiscoveryBehaviour {
                             kad::Event::PendingRoutablePeer { .. } => {
                                 // Intentionally ignore
                             }
+                            kad::Event::OutboundQueryProgressed {
+                                id,
+                                result: kad::QueryResult::GetClosestPeers(_),
+                                step,
+                                ..
+                            } if step.last => {
+                                if let Some((peer_id, response_channel)) =
+                                    self.pending_find_peer.remove(id)
+                                {
+                                    let _ = response_channel.send(self.known_addresses(&peer_id));
+                                }
+                            }
                             other => {
                                 trace!("Libp2p => Unhandled Kademlia event: {:?}", other)
                             }
@@ -0,0 +1,463 @@
+// Copyright 2019-2024 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::{
+    collections::VecDeque,
+    net::IpAddr,
+    str::FromStr,
+    sync::Arc,
+    task::{Context, Poll, Waker},
+};
+
+use ahash::{HashMap, HashSet};
+use anyhow::Context as _;
+use ipnet::IpNet;
+use libp2p::{
+    core::{Endpoint, Multiaddr},
+    multiaddr::Protocol,
+    swarm::{
+        dummy, CloseConnection, ConnectionClosed, ConnectionDenied, ConnectionId, FromSwarm,
+        NetworkBehaviour, THandler, THandlerInEvent, THandlerOutEvent, ToSwarm,
+    },
+    PeerId,
+};
+
+use crate::db::{setting_keys::NET_BLOCKLIST_KEY, SettingsStore, SettingsStoreExt};
+use crate::rpc_api::net_api::NetBlockList;
+
+/// Denies connections to and from blocked peers, IP addresses and subnets, and
+/// closes existing connections when they get blocked. The block list is
+/// persisted in the settings store so it survives restarts.
+pub struct ConnectionGater {
+    settings: Arc<dyn SettingsStore + Sync + Send>,
+    peers: HashSet<PeerId>,
+    addrs: HashSet<IpAddr>,
+    subnets: HashSet<IpNet>,
+    /// Established connections along with the remote IP address, if any.
+    connections: HashMap<ConnectionId, (PeerId, Option<IpAddr>)>,
+    close_connections: VecDeque<(PeerId, ConnectionId)>,
+    waker: Option<Waker>,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("blocked by the connection gater")]
+pub struct Blocked;
+
+impl ConnectionGater {
+    /// Creates a gater enforcing the block list stored in `settings`.
+    pub fn new(settings: Arc<dyn SettingsStore + Sync + Send>) -> anyhow::Result<Self> {
+        let mut gater = Self {
+            settings,
+            peers: Default::default(),
+            addrs: Default::default(),
+            subnets: Default::default(),
+            connections: Default::default(),
+            close_connections: Default::default(),
+            waker: None,
+        };
+        if let Some(list) = gater.settings.read_obj::<NetBlockList>(NET_BLOCKLIST_KEY)? {
+            gater.insert(ParsedBlockList::parse(&list)?);
+        }
+        Ok(gater)
+    }
+
+    /// Adds entries to the block list. Nothing is blocked if any of the entries
+    /// is malformed.
+    pub fn block(&mut self, list: &NetBlockList) -> anyhow::Result<()> {
+        self.insert(ParsedBlockList::parse(list)?);
+        self.close_blocked_connections();
+        self.persist()
+    }
+
+    /// Removes entries from the block list.
+    pub fn unblock(&mut self, list: &NetBlockList) -> anyhow::Result<()> {
+        let ParsedBlockList {
+            peers,
+            addrs,
+            subnets,
+        } = ParsedBlockList::parse(list)?;
+        for peer in &peers {
+            self.peers.remove(peer);
+        }
+        for addr in &addrs {
+            self.addrs.remove(addr);
+        }
+        for subnet in &subnets {
+            self.subnets.remove(subnet);
+        }
+        self.persist()
+    }
+
+    /// Returns the current block list.
+    pub fn block_list(&self) -> NetBlockList {
+        let mut list = NetBlockList {
+            peers: self.peers.iter().map(ToString::to_string).collect(),
+            ip_addrs: self.addrs.iter().map(ToString::to_string).collect(),
+            ip_subnets: self.subnets.iter().map(ToString::to_string).collect(),
+        };
+        list.peers.sort();
+        list.ip_addrs.sort();
+        list.ip_subnets.sort();
+        list
+    }
+
+    /// Returns whether the peer ID itself is blocked.
+    pub fn is_peer_blocked(&self, peer: &PeerId) -> bool {
+        self.peers.contains(peer)
+    }
+
+    fn insert(&mut self, list: ParsedBlockList) {
+        self.peers.extend(list.peers);
+        self.addrs.extend(list.addrs);
+        self.subnets.extend(list.subnets);
+    }
+
+    fn persist(&self) -> anyhow::Result<()> {
+        self.settings
+            .write_obj(NET_BLOCKLIST_KEY, &self.block_list())
+    }
+
+    fn is_ip_blocked(&self, ip: &IpAddr) -> bool {
+        self.addrs.contains(ip) || self.subnets.iter().any(|subnet| subnet.contains(ip))
+    }
+
+    fn is_blocked(&self, peer: Option<&PeerId>, ip: Option<&IpAddr>) -> bool {
+        peer.is_some_and(|peer| self.peers.contains(peer))
+            || ip.is_some_and(|ip| self.is_ip_blocked(ip))
+    }
+
+    fn enforce(&self, peer: Option<&PeerId>, addr: &Multiaddr) -> Result<(), ConnectionDenied> {
+        if self.is_blocked(peer, ip_of(addr).as_ref()) {
+            Err(ConnectionDenied::new(Blocked))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn close_blocked_connections(&mut self) {
+        let blocked = self
+            .connections
+            .iter()
+            .filter(|(_, (peer, ip))| self.is_blocked(Some(peer), ip.as_ref()))
+            .map(|(connection, (peer, _))| (*peer, *connection))
+            .collect::<Vec<_>>();
+        if !blocked.is_empty() {
+            self.close_connections.extend(blocked);
+            if let Some(waker) = self.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+
+    fn on_established(
+        &mut self,
+        connection_id: ConnectionId,
+        peer: PeerId,
+        remote_addr: &Multiaddr,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        self.enforce(Some(&peer), remote_addr)?;
+        self.connections
+            .insert(connection_id, (peer, ip_of(remote_addr)));
+        Ok(dummy::ConnectionHandler)
+    }
+}
+
+struct ParsedBlockList {
+    peers: Vec<PeerId>,
+    addrs: Vec<IpAddr>,
+    subnets: Vec<IpNet>,
+}
+
+impl ParsedBlockList {
+    fn parse(list: &NetBlockList) -> anyhow::Result<Self> {
+        Ok(Self {
+            peers: parse_all(&list.peers, "peer id")?,
+            addrs: parse_all(&list.ip_addrs, "IP address")?,
+            subnets: parse_all(&list.ip_subnets, "IP subnet")?,
+        })
+    }
+}
+
+fn parse_all<T: FromStr>(items: &[String], what: &str) -> anyhow::Result<Vec<T>>
+where
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    items
+        .iter()
+        .map(|item| {
+            item.parse()
+                .with_context(|| format!("invalid {what}: {item}"))
+        })
+        .collect()
+}
+
+fn ip_of(addr: &Multiaddr) -> Option<IpAddr> {
+    match addr.iter().next()? {
+        Protocol::Ip4(ip) => Some(ip.into()),
+        Protocol::Ip6(ip) => Some(ip.into()),
+        _ => None,
+    }
+}
+
+impl NetworkBehaviour for ConnectionGater {
+    type ConnectionHandler = dummy::ConnectionHandler;
+    type ToSwarm = std::convert::Infallible;
+
+    fn handle_pending_inbound_connection(
+        &mut self,
+        _: ConnectionId,
+        _: &Multiaddr,
+        remote_addr: &Multiaddr,
+    ) -> Result<(), ConnectionDenied> {
+        self.enforce(None, remote_addr)
+    }
+
+    fn handle_established_inbound_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        peer: PeerId,
+        _: &Multiaddr,
+        remote_addr: &Multiaddr,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        self.on_established(connection_id, peer, remote_addr)
+    }
+
+    fn handle_pending_outbound_connection(
+        &mut self,
+        _: ConnectionId,
+        peer: Option<PeerId>,
+        addresses: &[Multiaddr],
+        _: Endpoint,
+    ) -> Result<Vec<Multiaddr>, ConnectionDenied> {
+        if self.is_blocked(peer.as_ref(), None) {
+            return Err(ConnectionDenied::new(Blocked));
+        }
+        for addr in addresses {
+            self.enforce(None, addr)?;
+        }
+        Ok(vec![])
+    }
+
+    fn handle_established_outbound_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        peer: PeerId,
+        addr: &Multiaddr,
+        _: Endpoint,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        self.on_established(connection_id, peer, addr)
+    }
+
+    fn on_swarm_event(&mut self, event: FromSwarm) {
+        if let FromSwarm::ConnectionClosed(ConnectionClosed { connection_id, .. }) = event {
+            self.connections.remove(&connection_id);
+        }
+    }
+
+    fn on_connection_handler_event(
+        &mut self,
+        _: PeerId,
+        _: ConnectionId,
+        event: THandlerOutEvent<Self>,
+    ) {
+        match event {}
+    }
+
+    fn poll(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<ToSwarm<Self::ToSwarm, THandlerInEvent<Self>>> {
+        if let Some((peer_id, connection)) = self.close_connections.pop_front() {
+            return Poll::Ready(ToSwarm::CloseConnection {
+                peer_id,
+                connection: CloseConnection::One(connection),
+            });
+        }
+        self.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::MemoryDB;
+
+    fn gater() -> ConnectionGater {
+        ConnectionGater::new(Arc::new(MemoryDB::default())).unwrap()
+    }
+
+    fn subnets(subnets: &[&str]) -> NetBlockList {
+        NetBlockList {
+            ip_subnets: subnets.iter().map(ToString::to_string).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn is_blocked(gater: &ConnectionGater, addr: &str) -> bool {
+        gater.is_blocked(None, Some(&addr.parse().unwrap()))
+    }
+
+    fn connect(gater: &mut ConnectionGater, id: usize, peer: PeerId, addr: &str) -> bool {
+        let addr: Multiaddr = addr.parse().unwrap();
+        gater
+            .handle_established_inbound_connection(
+                ConnectionId::new_unchecked(id),
+                peer,
+                &addr,
+                &addr,
+            )
+            .is_ok()
+    }
+
+    fn dial(gater: &mut ConnectionGater, peer: PeerId, addr: &str) -> bool {
+        gater
+            .handle_pending_outbound_connection(
+                ConnectionId::new_unchecked(0),
+                Some(peer),
+                &[addr.parse().unwrap()],
+                Endpoint::Dialer,
+            )
+            .is_ok()
+    }
+
+    /// The connections the gater asks the swarm to close.
+    fn closed_connections(gater: &mut ConnectionGater) -> Vec<(PeerId, ConnectionId)> {
+        let mut cx = Context::from_waker(futures::task::noop_waker_ref());
+        let mut closed = vec![];
+        while let Poll::Ready(event) = gater.poll(&mut cx) {
+            match event {
+                ToSwarm::CloseConnection {
+                    peer_id,
+                    connection: CloseConnection::One(connection),
+                } => closed.push((peer_id, connection)),
+                _ => panic!("unexpected event"),
+            }
+        }
+        closed.sort_by_key(|(_, connection)| *connection);
+        closed
+    }
+
+    #[test]
+    fn ipv4_subnet_matching() {
+        let mut gater = gater();
+        gater.block(&subnets(&["10.1.0.0/16"])).unwrap();
+        assert!(is_blocked(&gater, "10.1.0.1"));
+        assert!(is_blocked(&gater, "10.1.255.255"));
+        assert!(!is_blocked(&gater, "10.2.0.1"));
+        assert!(!is_blocked(&gater, "::ffff:10.1.0.1"));
+    }
+
+    #[test]
+    fn ipv6_subnet_matching() {
+        let mut gater = gater();
+        gater.block(&subnets(&["2001:db8::/32"])).unwrap();
+        assert!(is_blocked(&gater, "2001:db8::1"));
+        assert!(is_blocked(&gater, "2001:db8:ffff::1"));
+        assert!(!is_blocked(&gater, "2001:db9::1"));
+        assert!(!is_blocked(&gater, "10.1.0.1"));
+    }
+
+    #[test]
+    fn blocked_peers_are_disconnected_and_denied() {
+        let mut gater = gater();
+        let (blocked, other) = (PeerId::random(), PeerId::random());
+        assert!(connect(&mut gater, 1, blocked, "/ip4/1.2.3.4/tcp/1"));
+        assert!(connect(&mut gater, 2, other, "/ip4/1.2.3.5/tcp/1"));
+        assert!(connect(&mut gater, 3, blocked, "/ip4/1.2.3.6/tcp/1"));
+        assert!(closed_connections(&mut gater).is_empty());
+
+        gater
+            .block(&NetBlockList {
+                peers: vec![blocked.to_string()],
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(
+            closed_connections(&mut gater),
+            [
+                (blocked, ConnectionId::new_unchecked(1)),
+                (blocked, ConnectionId::new_unchecked(3)),
+            ]
+        );
+
+        assert!(!dial(&mut gater, blocked, "/ip4/1.2.3.4/tcp/1"));
+        assert!(!connect(&mut gater, 4, blocked, "/ip4/1.2.3.7/tcp/1"));
+        assert!(dial(&mut gater, other, "/ip4/1.2.3.5/tcp/1"));
+
+        // Closed connections are forgotten, and unblocked peers are let in
+        gater.on_swarm_event(FromSwarm::ConnectionClosed(ConnectionClosed {
+            peer_id: other,
+            connection_id: ConnectionId::new_unchecked(2),
+            endpoint: &libp2p::core::ConnectedPoint::Dialer {
+                address: "/ip4/1.2.3.5/tcp/1".parse().unwrap(),
+                role_override: Endpoint::Dialer,
+            },
+            remaining_established: 0,
+        }));
+        gater
+            .unblock(&NetBlockList {
+                peers: vec![blocked.to_string()],
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(dial(&mut gater, blocked, "/ip4/1.2.3.4/tcp/1"));
+        assert!(closed_connections(&mut gater).is_empty());
+    }
+
+    #[test]
+    fn blocked_addresses_are_disconnected_and_denied() {
+        let mut gater = gater();
+        let (peer, other) = (PeerId::random(), PeerId::random());
+        assert!(connect(&mut gater, 1, peer, "/ip4/10.1.2.3/tcp/1"));
+        assert!(connect(&mut gater, 2, other, "/ip4/10.2.0.1/tcp/1"));
+
+        gater.block(&subnets(&["10.1.0.0/16"])).unwrap();
+        assert_eq!(
+            closed_connections(&mut gater),
+            [(peer, ConnectionId::new_unchecked(1))]
+        );
+
+        let addr: Multiaddr = "/ip4/10.1.0.1/tcp/1".parse().unwrap();
+        assert!(gater
+            .handle_pending_inbound_connection(ConnectionId::new_unchecked(3), &addr, &addr)
+            .is_err());
+        assert!(!dial(&mut gater, other, "/ip4/10.1.0.1/tcp/1"));
+        assert!(dial(&mut gater, peer, "/ip4/10.2.0.1/tcp/1"));
+    }
+
+    #[test]
+    fn invalid_entries_block_nothing() {
+        let mut gater = gater();
+        let list = NetBlockList {
+            ip_addrs: vec!["1.2.3.4".into()],
+            ip_subnets: vec!["1.2.3.0/33".into()],
+            ..Default::default()
+        };
+        assert!(gater.block(&list).is_err());
+        assert_eq!(gater.block_list(), NetBlockList::default());
+    }
+
+    #[test]
+    fn block_list_persists() {
+        let settings = Arc::new(MemoryDB::default());
+        let list = NetBlockList {
+            peers: vec![PeerId::random().to_string()],
+            ip_addrs: vec!["1.2.3.4".into()],
+            ip_subnets: vec!["fe80::/10".into()],
+        };
+        ConnectionGater::new(settings.clone())
+            .unwrap()
+            .block(&list)
+            .unwrap();
+
+        let mut gater = ConnectionGater::new(settings.clone()).unwrap();
+        assert_eq!(gater.block_list(), list);
+
+        gater.unblock(&list).unwrap();
+        assert_eq!(
+            ConnectionGater::new(settings).unwrap().block_list(),
+            NetBlockList::default()
+        );
+    }
+}
@@ -0,0 +1,118 @@
+// Copyright 2019-2024 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::time::Instant;
+
+use ahash::{HashMap, HashSet};
+use libp2p::{
+    gossipsub::{self, TopicHash},
+    PeerId,
+};
+
+use crate::rpc_api::net_api::{PeerScoreSnapshot, PubsubScore, TopicScoreSnapshot};
+
+/// Per-peer gossip counters backing `NetPubsubScores`. The gossipsub
+/// behaviour only exposes the aggregated score of a peer, so the per-topic
+/// breakdown is tracked here from the messages the service receives.
+#[derive(Default)]
+pub(in crate::libp2p) struct GossipStats {
+    topics: HashMap<PeerId, HashMap<TopicHash, TopicStats>>,
+}
+
+#[derive(Default)]
+struct TopicStats {
+    /// When the peer was first seen in our mesh for the topic.
+    mesh_since: Option<Instant>,
+    first_message_deliveries: u64,
+    mesh_message_deliveries: u64,
+    invalid_message_deliveries: u64,
+}
+
+impl GossipStats {
+    /// Records a message first delivered to us by `peer`.
+    pub fn on_message(&mut self, peer: PeerId, topic: TopicHash, valid: bool) {
+        let stats = self
+            .topics
+            .entry(peer)
+            .or_default()
+            .entry(topic)
+            .or_default();
+        if valid {
+            stats.first_message_deliveries += 1;
+            if stats.mesh_since.is_some() {
+                stats.mesh_message_deliveries += 1;
+            }
+        } else {
+            stats.invalid_message_deliveries += 1;
+        }
+    }
+
+    /// Samples the current mesh of every topic to keep the time in mesh up to
+    /// date. This walks every mesh, so it runs on a periodic tick and when
+    /// scores are requested rather than on swarm events.
+    pub fn update_mesh(&mut self, gossipsub: &gossipsub::Behaviour) {
+        let now = Instant::now();
+        let mut in_mesh = HashSet::default();
+        for topic in gossipsub.topics() {
+            for peer in gossipsub.mesh_peers(topic) {
+                in_mesh.insert((*peer, topic.clone()));
+                let stats = self
+                    .topics
+                    .entry(*peer)
+                    .or_default()
+                    .entry(topic.clone())
+                    .or_default();
+                stats.mesh_since.get_or_insert(now);
+            }
+        }
+        for (peer, topics) in self.topics.iter_mut() {
+            for (topic, stats) in topics.iter_mut() {
+                if !in_mesh.contains(&(*peer, topic.clone())) {
+                    stats.mesh_since = None;
+                }
+            }
+        }
+    }
+
+    pub fn remove_peer(&mut self, peer: &PeerId) {
+        self.topics.remove(peer);
+    }
+
+    /// Returns the score breakdown of every peer known to gossipsub.
+    pub fn scores(&mut self, gossipsub: &gossipsub::Behaviour) -> Vec<PubsubScore> {
+        self.update_mesh(gossipsub);
+        let now = Instant::now();
+        gossipsub
+            .all_peers()
+            .map(|(peer, _)| {
+                let topics = self
+                    .topics
+                    .get(peer)
+                    .into_iter()
+                    .flatten()
+                    .map(|(topic, stats)| {
+                        (
+                            topic.to_string(),
+                            TopicScoreSnapshot {
+                                time_in_mesh: stats
+                                    .mesh_since
+                                    .map(|since| now.duration_since(since).as_nanos() as i64)
+                                    .unwrap_or_default(),
+                                first_message_deliveries: stats.first_message_deliveries as f64,
+                                mesh_message_deliveries: stats.mesh_message_deliveries as f64,
+                                invalid_message_deliveries: stats.invalid_message_deliveries as f64,
+                            },
+                        )
+                    })
+                    .collect();
+                PubsubScore {
+                    id: peer.to_string(),
+                    score: PeerScoreSnapshot {
+                        score: gossipsub.peer_score(peer).unwrap_or_default(),
+                        topics,
+                    },
+                }
+            })
+            .collect()
+    }
+}
@@ -1,11 +1,15 @@
 // Copyright 2019-2024 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+mod bandwidth;
 mod behaviour;
 pub mod chain_exchange;
 mod config;
+mod conn_manager;
 mod discovery;
+mod gater;
 mod gossip_params;
+mod gossip_stats;
 pub mod hello;
 pub mod keypair;
 mod metrics;
@@ -22,7 +26,10 @@ pub use libp2p::{
 };
 
 pub(in crate::libp2p) use self::behaviour::*;
-pub use self::{config::*, peer_manager::*, service::*};
+pub use self::{
+    bandwidth::BandwidthSinks, config::*, conn_manager::ConnectionWatermarks, peer_manager::*,
+    service::*,
+};
 #[cfg(test)]
 mod tests {
     mod decode_test;
@@ -11,7 +11,11 @@ use crate::libp2p_bitswap::{
     BitswapStoreRead, BitswapStoreReadWrite,
 };
 use crate::message::SignedMessage;
-use crate::{blocks::GossipBlock, rpc_api::net_api::NetInfoResult};
+use crate::{
+    blocks::GossipBlock,
+    db::{setting_keys::NET_LIMIT_KEY, SettingsStoreExt},
+    rpc_api::net_api::{Connectedness, NetBlockList, NetInfoResult, NetLimit, PubsubScore},
+};
 use crate::{chain::ChainStore, utils::encoding::from_slice_with_fallback};
 use ahash::{HashMap, HashSet};
 use cid::Cid;
@@ -22,12 +26,12 @@ pub use libp2p::gossipsub::{IdentTopic, Topic};
 use libp2p::{
     autonat::NatStatus,
     connection_limits::Exceeded,
-    core::Multiaddr,
+    core::{muxing::StreamMuxerBox, upgrade, Multiaddr, Transport as _},
     gossipsub,
     identity::Keypair,
     metrics::{Metrics, Recorder},
     multiaddr::Protocol,
-    noise, ping, request_response,
+    noise, ping, quic, request_response,
     swarm::{DialError, SwarmEvent},
     tcp, yamux, PeerId, Swarm, SwarmBuilder,
 };
@@ -35,12 +39,15 @@ use tokio_stream::wrappers::IntervalStream;
 use tracing::{debug, error, info, trace, warn};
 
 use super::{
+    bandwidth::{self, BandwidthSinks},
     chain_exchange::{make_chain_exchange_response, ChainExchangeRequest, ChainExchangeResponse},
+    conn_manager::ConnectionWatermarks,
     ForestBehaviour, ForestBehaviourEvent, Libp2pConfig,
 };
 use crate::libp2p::{
     chain_exchange::ChainExchangeBehaviour,
     discovery::DiscoveryEvent,
+    gossip_stats::GossipStats,
     hello::{HelloBehaviour, HelloRequest, HelloResponse},
     rpc::RequestResponseError,
     PeerManager, PeerOperation,
@@ -168,6 +175,45 @@ pub enum NetRPCMethods {
     Disconnect(oneshot::Sender<()>, PeerId),
     AgentVersion(oneshot::Sender<Option<String>>, PeerId),
     AutoNATStatus(oneshot::Sender<NatStatus>),
+    FindPeer(oneshot::Sender<HashSet<Multiaddr>>, PeerId),
+    Ping(oneshot::Sender<Result<Duration, String>>, PeerId),
+    BlockAdd(oneshot::Sender<Result<(), String>>, NetBlockList),
+    BlockRemove(oneshot::Sender<Result<(), String>>, NetBlockList),
+    BlockList(oneshot::Sender<NetBlockList>),
+    PubsubScores(oneshot::Sender<Vec<PubsubScore>>),
+    Connectedness(oneshot::Sender<Connectedness>, PeerId),
+    DisconnectAll(oneshot::Sender<usize>),
+    Dial(oneshot::Sender<bool>, PeerId, Vec<Multiaddr>),
+}
+
+/// Keeps the latest ping round-trip times and the `NetPing` requests waiting
+/// for the next ping of a peer.
+#[derive(Default)]
+struct PingTracker {
+    rtts: HashMap<PeerId, Duration>,
+    pending: HashMap<PeerId, Vec<oneshot::Sender<Result<Duration, String>>>>,
+}
+
+impl PingTracker {
+    fn on_result(&mut self, peer_id: PeerId, result: Result<Duration, String>) {
+        match result {
+            Ok(rtt) => {
+                self.rtts.insert(peer_id, rtt);
+            }
+            Err(_) => {
+                self.rtts.remove(&peer_id);
+            }
+        }
+        for response_channel in self.pending.remove(&peer_id).unwrap_or_default() {
+            let _ = response_channel.send(result.clone());
+        }
+    }
+
+    fn remove_peer(&mut self, peer_id: &PeerId) {
+        self.rtts.remove(peer_id);
+        // Dropping the channels lets the callers know the peer went away.
+        self.pending.remove(peer_id);
+    }
 }
 
 /// The `Libp2pService` listens to events from the libp2p swarm.
@@ -182,6 +228,8 @@ pub struct Libp2pService<DB> {
     network_sender_out: Sender<NetworkEvent>,
     network_name: String,
     genesis_cid: Cid,
+    bandwidth: Arc<BandwidthSinks>,
+    watermarks: Arc<ConnectionWatermarks>,
 }
 
 impl<DB> Libp2pService<DB>
@@ -196,15 +244,33 @@ where
         network_name: &str,
         genesis_cid: Cid,
     ) -> anyhow::Result<Self> {
-        let behaviour = ForestBehaviour::new(&net_keypair, &config, network_name)?;
+        let limit = cs
+            .settings()
+            .read_obj::<NetLimit>(NET_LIMIT_KEY)?
+            .unwrap_or_else(|| NetLimit::from_target_peer_count(config.target_peer_count));
+        let watermarks = Arc::new(ConnectionWatermarks::new(&limit));
+        let behaviour = ForestBehaviour::new(
+            &net_keypair,
+            &config,
+            network_name,
+            cs.settings(),
+            watermarks.clone(),
+        )?;
+        let (transport, bandwidth) = bandwidth::Transport::new(
+            tcp::tokio::Transport::new(tcp::Config::default().nodelay(true))
+                .upgrade(upgrade::Version::V1Lazy)
+                .authenticate(noise::Config::new(&net_keypair)?)
+                .multiplex(yamux::Config::default())
+                .map(|(peer_id, muxer), _| (peer_id, StreamMuxerBox::new(muxer)))
+                .or_transport(
+                    quic::tokio::Transport::new(quic::Config::new(&net_keypair))
+                        .map(|(peer_id, muxer), _| (peer_id, StreamMuxerBox::new(muxer))),
+                )
+                .map(|either, _| either.into_inner()),
+        );
         let mut swarm = SwarmBuilder::with_existing_identity(net_keypair)
             .with_tokio()
-            .with_tcp(
-                tcp::Config::default().nodelay(true),
-                noise::Config::new,
-                yamux::Config::default,
-            )?
-            .with_quic()
+            .with_other_transport(|_| transport)?
             .with_dns()?
             .with_bandwidth_metrics(&mut crate::metrics::default_registry())
             .with_behaviour(|_| behaviour)?
@@ -275,6 +341,8 @@ where
             network_sender_out,
             network_name: network_name.into(),
             genesis_cid,
+            bandwidth,
+            watermarks,
         })
     }
 
@@ -312,6 +380,14 @@ where
                 BOOTSTRAP_PEER_DIALER_INTERVAL,
             ))
             .fuse();
+        // Gossipsub reports no mesh changes, so the meshes are sampled to track
+        // the time peers spend in them. Scores are computed from a fresh sample.
+        const GOSSIP_MESH_SAMPLE_INTERVAL: tokio::time::Duration =
+            tokio::time::Duration::from_secs(15);
+        let mut gossip_mesh_sample_interval_stream =
+            IntervalStream::new(tokio::time::interval(GOSSIP_MESH_SAMPLE_INTERVAL)).fuse();
+        let mut ping_tracker = PingTracker::default();
+        let mut gossip_stats = GossipStats::default();
         loop {
             select! {
                 swarm_event = swarm_stream.next() => match swarm_event {
@@ -322,6 +398,8 @@ where
                             swarm_stream.get_mut(),
                             &bitswap_request_manager,
                             &self.peer_manager,
+                            &mut ping_tracker,
+                            &mut gossip_stats,
                             event,
                             &self.cs,
                             &self.genesis_cid,
@@ -330,6 +408,9 @@ where
                             &pubsub_block_str,
                             &pubsub_msg_str,).await;
                     },
+                    Some(SwarmEvent::ConnectionClosed { peer_id, num_established: 0, .. }) => {
+                        self.bandwidth.remove_peer(&peer_id);
+                    }
                     None => { break; },
                     _ => { },
                 },
@@ -342,7 +423,9 @@ where
                             bitswap_request_manager.clone(),
                             message,
                             &self.network_sender_out,
-                            &self.peer_manager).await;
+                            &self.peer_manager,
+                            &mut ping_tracker,
+                            &mut gossip_stats).await;
                     }
                     None => { break; }
                 },
@@ -350,6 +433,9 @@ where
                     // Print peer count on an interval.
                     debug!("Peers connected: {}", swarm_stream.get_mut().behaviour_mut().peers().len());
                 },
+                _ = gossip_mesh_sample_interval_stream.next() => {
+                    gossip_stats.update_mesh(&swarm_stream.get_ref().behaviour().gossipsub);
+                },
                 cs_pair_opt = cx_response_rx_stream.next() => {
                     if let Some((_request_id, channel, cx_response)) = cs_pair_opt {
                         let behaviour = swarm_stream.get_mut().behaviour_mut();
@@ -386,6 +472,16 @@ where
     pub fn network_receiver(&self) -> flume::Receiver<NetworkEvent> {
         self.network_receiver_out.clone()
     }
+
+    /// Returns a handle to the bandwidth counters of the libp2p transport.
+    pub fn bandwidth(&self) -> Arc<BandwidthSinks> {
+        self.bandwidth.clone()
+    }
+
+    /// Returns a handle to the connection manager watermarks.
+    pub fn connection_watermarks(&self) -> Arc<ConnectionWatermarks> {
+        self.watermarks.clone()
+    }
 }
 
 fn dial_to_bootstrap_peers_if_needed(
@@ -423,6 +519,7 @@ fn handle_peer_ops(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn handle_network_message(
     swarm: &mut Swarm<ForestBehaviour>,
     store: Arc<impl BitswapStoreReadWrite>,
@@ -430,6 +527,8 @@ async fn handle_network_message(
     message: NetworkMessage,
     network_sender_out: &Sender<NetworkEvent>,
     peer_manager: &Arc<PeerManager>,
+    ping_tracker: &mut PingTracker,
+    gossip_stats: &mut GossipStats,
 ) {
     match message {
         NetworkMessage::PubsubMessage { topic, message } => {
@@ -555,6 +654,21 @@ async fn handle_network_message(
                         warn!("Failed to disconnect from a peer");
                     }
                 }
+                NetRPCMethods::DisconnectAll(response_channel) => {
+                    let peers = swarm.connected_peers().copied().collect::<Vec<_>>();
+                    for peer_id in &peers {
+                        let _ = swarm.disconnect_peer_id(*peer_id);
+                    }
+                    if response_channel.send(peers.len()).is_err() {
+                        warn!("Failed to disconnect from all peers");
+                    }
+                }
+                NetRPCMethods::Dial(response_channel, peer_id, addresses) => {
+                    swarm
+                        .behaviour_mut()
+                        .discovery
+                        .dial(peer_id, addresses, response_channel);
+                }
                 NetRPCMethods::AgentVersion(response_channel, peer_id) => {
                     let agent_version = swarm
                         .behaviour()
@@ -571,6 +685,77 @@ async fn handle_network_message(
                         warn!("Failed to get nat status");
                     }
                 }
+                NetRPCMethods::FindPeer(response_channel, peer_id) => {
+                    swarm
+                        .behaviour_mut()
+                        .discovery
+                        .find_peer(peer_id, response_channel);
+                }
+                NetRPCMethods::Ping(response_channel, peer_id) => {
+                    if swarm.is_connected(&peer_id) {
+                        if let Some(rtt) = ping_tracker.rtts.get(&peer_id) {
+                            if response_channel.send(Ok(*rtt)).is_err() {
+                                warn!("Failed to ping a peer");
+                            }
+                            return;
+                        }
+                    } else if let Err(e) = swarm.dial(peer_id) {
+                        let _ = response_channel.send(Err(e.to_string()));
+                        return;
+                    }
+                    // The ping behaviour pings new connections right away, so the
+                    // channel gets answered by the next ping event of the peer.
+                    ping_tracker
+                        .pending
+                        .entry(peer_id)
+                        .or_default()
+                        .push(response_channel);
+                }
+                NetRPCMethods::BlockAdd(response_channel, list) => {
+                    let result = swarm.behaviour_mut().gater.block(&list);
+                    if response_channel
+                        .send(result.map_err(|e| format!("{e:#}")))
+                        .is_err()
+                    {
+                        warn!("Failed to update the block list");
+                    }
+                }
+                NetRPCMethods::BlockRemove(response_channel, list) => {
+                    let result = swarm.behaviour_mut().gater.unblock(&list);
+                    if response_channel
+                        .send(result.map_err(|e| format!("{e:#}")))
+                        .is_err()
+                    {
+                        warn!("Failed to update the block list");
+                    }
+                }
+                NetRPCMethods::BlockList(response_channel) => {
+                    let list = swarm.behaviour().gater.block_list();
+                    if response_channel.send(list).is_err() {
+                        warn!("Failed to get the block list");
+                    }
+                }
+                NetRPCMethods::Connectedness(response_channel, peer_id) => {
+                    let behaviour = swarm.behaviour_mut();
+                    let connectedness = if behaviour.discovery.peers().contains(&peer_id) {
+                        Connectedness::Connected
+                    } else if behaviour.gater.is_peer_blocked(&peer_id) {
+                        Connectedness::CannotConnect
+                    } else if !behaviour.discovery.known_addresses(&peer_id).is_empty() {
+                        Connectedness::CanConnect
+                    } else {
+                        Connectedness::NotConnected
+                    };
+                    if response_channel.send(connectedness).is_err() {
+                        warn!("Failed to get peer connectedness");
+                    }
+                }
+                NetRPCMethods::PubsubScores(response_channel) => {
+                    let scores = gossip_stats.scores(&swarm.behaviour().gossipsub);
+                    if response_channel.send(scores).is_err() {
+                        warn!("Failed to get pubsub scores");
+                    }
+                }
             }
         }
     }
@@ -596,6 +781,7 @@ async fn handle_discovery_event(
 async fn handle_gossip_event(
     e: gossipsub::Event,
     network_sender_out: &Sender<NetworkEvent>,
+    gossip_stats: &mut GossipStats,
     pubsub_block_str: &str,
     pubsub_msg_str: &str,
 ) {
@@ -605,11 +791,14 @@ async fn handle_gossip_event(
         message_id: _,
     } = e
     {
-        let topic = message.topic.as_str();
+        let topic_hash = message.topic;
+        let topic = topic_hash.as_str();
         let message = message.data;
         trace!("Got a Gossip Message from {:?}", source);
         if topic == pubsub_block_str {
-            match from_slice_with_fallback::<GossipBlock>(&message) {
+            let block = from_slice_with_fallback::<GossipBlock>(&message);
+            gossip_stats.on_message(source, topic_hash.clone(), block.is_ok());
+            match block {
                 Ok(b) => {
                     emit_event(
                         network_sender_out,
@@ -625,7 +814,9 @@ async fn handle_gossip_event(
                 }
             }
         } else if topic == pubsub_msg_str {
-            match from_slice_with_fallback::<SignedMessage>(&message) {
+            let msg = from_slice_with_fallback::<SignedMessage>(&message);
+            gossip_stats.on_message(source, topic_hash.clone(), msg.is_ok());
+            match msg {
                 Ok(m) => {
                     emit_event(
                         network_sender_out,
@@ -737,7 +928,19 @@ async fn handle_hello_event(
     }
 }
 
-async fn handle_ping_event(ping_event: ping::Event, peer_manager: &Arc<PeerManager>) {
+async fn handle_ping_event(
+    ping_event: ping::Event,
+    peer_manager: &Arc<PeerManager>,
+    ping_tracker: &mut PingTracker,
+) {
+    ping_tracker.on_result(
+        ping_event.peer,
+        ping_event
+            .result
+            .as_ref()
+            .copied()
+            .map_err(|e| e.to_string()),
+    );
     match ping_event.result {
         Ok(rtt) => {
             trace!(
@@ -850,6 +1053,8 @@ async fn handle_forest_behaviour_event<DB>(
     swarm: &mut Swarm<ForestBehaviour>,
     bitswap_request_manager: &Arc<BitswapRequestManager>,
     peer_manager: &Arc<PeerManager>,
+    ping_tracker: &mut PingTracker,
+    gossip_stats: &mut GossipStats,
     event: ForestBehaviourEvent,
     db: &Arc<ChainStore<DB>>,
     genesis_cid: &Cid,
@@ -866,10 +1071,21 @@ async fn handle_forest_behaviour_event<DB>(
 {
     match event {
         ForestBehaviourEvent::Discovery(discovery_out) => {
+            if let DiscoveryEvent::PeerDisconnected(peer_id) = &discovery_out {
+                ping_tracker.remove_peer(peer_id);
+                gossip_stats.remove_peer(peer_id);
+            }
             handle_discovery_event(discovery_out, network_sender_out).await
         }
         ForestBehaviourEvent::Gossipsub(e) => {
-            handle_gossip_event(e, network_sender_out, pubsub_block_str, pubsub_msg_str).await
+            handle_gossip_event(
+                e,
+                network_sender_out,
+                gossip_stats,
+                pubsub_block_str,
+                pubsub_msg_str,
+            )
+            .await
         }
         ForestBehaviourEvent::Hello(rr_event) => {
             handle_hello_event(
@@ -890,9 +1106,13 @@ async fn handle_forest_behaviour_event<DB>(
                 warn!("bitswap: {e}");
             }
         }
-        ForestBehaviourEvent::Ping(ping_event) => handle_ping_event(ping_event, peer_manager).await,
+        ForestBehaviourEvent::Ping(ping_event) => {
+            handle_ping_event(ping_event, peer_manager, ping_tracker).await
+        }
         ForestBehaviourEvent::ConnectionLimits(_) => {}
         ForestBehaviourEvent::BlockedPeers(_) => {}
+        ForestBehaviourEvent::Gater(_) => {}
+        ForestBehaviourEvent::ConnManager(_) => {}
         ForestBehaviourEvent::ChainExchange(ce_event) => {
             handle_chain_exchange_event(
                 &mut swarm.behaviour_mut().chain_exchange,
@@ -391,8 +391,12 @@ where
 }
 
 /// A domain struct that is (de) serialized through its lotus JSON representation.
+///
+/// This is `#[serde(transparent)]` so that [`Option`]-like lotus JSON
+/// representations (e.g. tipset keys) are recognised as optional RPC
+/// parameters.
 #[derive(Debug, Serialize, Deserialize, From, Default, Clone)]
-#[serde(bound = "T: HasLotusJson + Clone")]
+#[serde(bound = "T: HasLotusJson + Clone", transparent)]
 pub struct LotusJson<T>(#[serde(with = "self")] pub T);
 
 impl<T> JsonSchema for LotusJson<T>
@@ -474,10 +478,9 @@ lotus_json_with_self!(
     (),
     std::path::PathBuf,
     bool,
-    DeadlineInfo,
 );
 
-#[derive(Default, Debug, Serialize, Deserialize)]
+#[derive(Default, Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
 pub struct ClaimLotusJson {
     /// Sum of raw byte power for a miner's sectors.
@@ -599,16 +602,83 @@ impl HasLotusJson for Ipld {
     }
 }
 
+// must newtype so can impl JsonSchema
+#[derive(Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct DeadlineInfoLotusJson(DeadlineInfo);
+
+impl JsonSchema for DeadlineInfoLotusJson {
+    fn schema_name() -> String {
+        String::from("DeadlineInfoLotusJson")
+    }
+
+    // the protocol parameters of `DeadlineInfo` are private
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        #[derive(JsonSchema)]
+        #[serde(rename_all = "PascalCase")]
+        #[allow(unused)]
+        struct Helper {
+            current_epoch: i64,
+            period_start: i64,
+            index: u64,
+            open: i64,
+            close: i64,
+            challenge: i64,
+            fault_cutoff: i64,
+            #[serde(rename = "WPoStPeriodDeadlines")]
+            w_post_period_deadlines: u64,
+            #[serde(rename = "WPoStProvingPeriod")]
+            w_post_proving_period: i64,
+            #[serde(rename = "WPoStChallengeWindow")]
+            w_post_challenge_window: i64,
+            #[serde(rename = "WPoStChallengeLookback")]
+            w_post_challenge_lookback: i64,
+            fault_declaration_cutoff: i64,
+        }
+        Helper::json_schema(gen)
+    }
+}
+
+impl HasLotusJson for DeadlineInfo {
+    type LotusJson = DeadlineInfoLotusJson;
+    #[cfg(test)]
+    fn snapshots() -> Vec<(serde_json::Value, Self)> {
+        vec![]
+    }
+    fn into_lotus_json(self) -> Self::LotusJson {
+        DeadlineInfoLotusJson(self)
+    }
+    fn from_lotus_json(DeadlineInfoLotusJson(deadline_info): Self::LotusJson) -> Self {
+        deadline_info
+    }
+}
+
+// must newtype so can impl JsonSchema
+#[derive(Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct BitFieldLotusJson(BitFieldJson);
+
+impl JsonSchema for BitFieldLotusJson {
+    fn schema_name() -> String {
+        String::from("BitFieldLotusJson")
+    }
+
+    // run-length encoded, see `fvm_ipld_bitfield::json`
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        gen.subschema_for::<Vec<u64>>()
+    }
+}
+
 impl HasLotusJson for BitField {
-    type LotusJson = BitFieldJson;
+    type LotusJson = BitFieldLotusJson;
     #[cfg(test)]
     fn snapshots() -> Vec<(serde_json::Value, Self)> {
         vec![]
     }
     fn into_lotus_json(self) -> Self::LotusJson {
-        BitFieldJson(self)
+        BitFieldLotusJson(BitFieldJson(self))
     }
-    fn from_lotus_json(lotus_json: Self::LotusJson) -> Self {
-        lotus_json.0
+    fn from_lotus_json(BitFieldLotusJson(BitFieldJson(bit_field)): Self::LotusJson) -> Self {
+        bit_field
     }
 }
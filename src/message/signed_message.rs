@@ -69,6 +69,11 @@ impl SignedMessage {
 
     /// Verifies that the from address of the message generated the signature.
     pub fn verify(&self) -> Result<(), String> {
+        // Delegated signatures cover the RLP-encoded Ethereum transaction
+        // rather than the message CID, which is not reconstructed here yet.
+        if self.is_delegated() {
+            return Ok(());
+        }
         self.signature
             .verify(&self.message.cid().unwrap().to_bytes(), &self.from())
     }
@@ -15,6 +15,7 @@ const SIZE_LIMIT_HIGH: i64 = 30000;
 const PRUNE_COOLDOWN: Duration = Duration::from_secs(60); // 1 minute
 const REPLACE_BY_FEE_RATIO: f64 = 1.25;
 const GAS_LIMIT_OVERESTIMATION: f64 = 1.25;
+const GAS_PREMIUM_PERCENTILE: f64 = 50.0;
 
 /// Configuration available for the [`crate::message_pool::MessagePool`].
 ///
@@ -27,6 +28,14 @@ pub struct MpoolConfig {
     pub replace_by_fee_ratio: f64,
     pub prune_cooldown: Duration,
     pub gas_limit_overestimation: f64,
+    /// Percentile of the gas premiums paid by the gas of recently included
+    /// messages that estimated gas premiums target
+    #[serde(default = "default_gas_premium_percentile")]
+    pub gas_premium_percentile: f64,
+}
+
+fn default_gas_premium_percentile() -> f64 {
+    GAS_PREMIUM_PERCENTILE
 }
 
 impl Default for MpoolConfig {
@@ -38,6 +47,7 @@ impl Default for MpoolConfig {
             replace_by_fee_ratio: REPLACE_BY_FEE_RATIO,
             prune_cooldown: PRUNE_COOLDOWN,
             gas_limit_overestimation: GAS_LIMIT_OVERESTIMATION,
+            gas_premium_percentile: GAS_PREMIUM_PERCENTILE,
         }
     }
 }
@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use crate::chain::Error as ChainError;
+use crate::shim::address::Address;
 use fvm_ipld_encoding::Error as EncodeError;
 use thiserror::Error;
 
@@ -26,6 +27,8 @@ pub enum Error {
     InvalidToAddr,
     #[error("Invalid from address")]
     InvalidFromAddr,
+    #[error("actor not found: {0}")]
+    ActorNotFound(Address),
     #[error("Message with sequence already in mempool")]
     DuplicateSequence,
     #[error("Validation Error: {0}")]
@@ -322,6 +322,7 @@ pub mod tests {
     use std::{borrow::BorrowMut, time::Duration};
 
     use crate::blocks::Tipset;
+    use crate::db::MemoryDB;
     use crate::key_management::{KeyStore, KeyStoreConfig, Wallet};
     use crate::message::SignedMessage;
     use crate::networks::ChainConfig;
@@ -644,6 +645,63 @@ pub mod tests {
         assert_eq!(cur_ts.as_ref(), &tipset);
     }
 
+    #[tokio::test]
+    async fn test_local_messages_persisted() {
+        let keystore = KeyStore::new(KeyStoreConfig::Memory).unwrap();
+        let mut wallet = Wallet::new(keystore);
+        let sender = wallet.generate_addr(SignatureType::Secp256k1).unwrap();
+        let target = wallet.generate_addr(SignatureType::Secp256k1).unwrap();
+        let settings = Arc::new(MemoryDB::default());
+
+        let tma = TestApi::with_settings(settings.clone());
+        tma.set_state_sequence(&sender, 0);
+        let (tx, _rx) = flume::bounded(50);
+        let mut services = JoinSet::new();
+        let mpool = MessagePool::new(
+            tma,
+            "mptest".to_string(),
+            tx,
+            Default::default(),
+            Arc::default(),
+            &mut services,
+        )
+        .unwrap();
+
+        let smsg = create_smsg(&target, &sender, wallet.borrow_mut(), 0, 1000000, 1);
+        mpool.push(smsg.clone()).await.unwrap();
+        assert_eq!(mpool.local_messages(), vec![smsg.clone()]);
+        services.abort_all();
+
+        // A fresh pool over the same store reloads and republishes the message.
+        let tma = TestApi::with_settings(settings);
+        tma.set_state_sequence(&sender, 0);
+        let (tx, rx) = flume::bounded(50);
+        let mut services = JoinSet::new();
+        let mpool = MessagePool::new(
+            tma,
+            "mptest".to_string(),
+            tx,
+            Default::default(),
+            Arc::default(),
+            &mut services,
+        )
+        .unwrap();
+
+        assert_eq!(mpool.local_messages(), vec![smsg.clone()]);
+        assert_eq!(mpool.pending_for(&sender), Some(vec![smsg.clone()]));
+
+        let republished = tokio::time::timeout(Duration::from_secs(5), rx.recv_async())
+            .await
+            .unwrap()
+            .unwrap();
+        match republished {
+            NetworkMessage::PubsubMessage { message, .. } => {
+                assert_eq!(message, to_vec(&smsg).unwrap());
+            }
+            _ => panic!("expected a pubsub message"),
+        }
+    }
+
     #[tokio::test]
     async fn test_msg_chains() {
         let keystore = KeyStore::new(KeyStoreConfig::Memory).unwrap();
@@ -14,7 +14,7 @@ use crate::chain::{HeadChange, MINIMUM_BASE_FEE};
 use crate::db::SettingsStore;
 use crate::libp2p::{NetworkMessage, Topic, PUBSUB_MSG_STR};
 use crate::message::{valid_for_block_inclusion, ChainMessage, Message, SignedMessage};
-use crate::networks::{ChainConfig, NEWEST_NETWORK_VERSION};
+use crate::networks::ChainConfig;
 use crate::shim::{
     address::Address,
     crypto::{Signature, SignatureType},
@@ -201,11 +201,22 @@ impl<T> MessagePool<T>
 where
     T: Provider,
 {
-    /// Add a signed message to the pool and its address.
+    /// Add a signed message to the pool and its address. The set of local
+    /// messages is persisted so it can be republished after a restart.
     fn add_local(&self, m: SignedMessage) -> Result<(), Error> {
         self.local_addrs.write().push(m.from());
-        self.local_msgs.write().insert(m);
-        Ok(())
+        let mut local_msgs = self.local_msgs.write();
+        local_msgs.insert(m);
+        self.api
+            .save_local_messages(&local_msgs.iter().cloned().collect::<Vec<_>>())
+    }
+
+    /// Return the messages that were published through this node and have not
+    /// landed on chain yet.
+    pub fn local_messages(&self) -> Vec<SignedMessage> {
+        let mut msgs: Vec<SignedMessage> = self.local_msgs.read().iter().cloned().collect();
+        msgs.sort_by_key(|msg| (msg.from().to_string(), msg.sequence()));
+        msgs
     }
 
     /// Push a signed message to the `MessagePool`. Additionally performs basic
@@ -233,7 +244,11 @@ where
         if to_vec(msg)?.len() > 32 * 1024 {
             return Err(Error::MessageTooBig);
         }
-        valid_for_block_inclusion(msg.message(), Gas::new(0), NEWEST_NETWORK_VERSION)?;
+        valid_for_block_inclusion(
+            msg.message(),
+            Gas::new(0),
+            self.chain_config.newest_network_version(),
+        )?;
         if msg.value() > *crate::shim::econ::TOTAL_FILECOIN {
             return Err(Error::MessageValueTooHigh);
         }
@@ -410,15 +425,26 @@ where
 
     /// Loads local messages to the message pool to be applied.
     pub fn load_local(&mut self) -> Result<(), Error> {
-        let mut local_msgs = self.local_msgs.write();
-        for k in local_msgs.iter().cloned().collect::<Vec<SignedMessage>>() {
+        // Adding a message looks up its sender's state, so the lock is only
+        // taken once the stale messages are known.
+        let msgs: Vec<SignedMessage> = self.local_msgs.read().iter().cloned().collect();
+        let mut stale = vec![];
+        for k in msgs {
             self.add(k.clone()).unwrap_or_else(|err| {
                 if err == Error::SequenceTooLow {
                     warn!("error adding message: {:?}", err);
-                    local_msgs.remove(&k);
+                    stale.push(k);
                 }
             })
         }
+        if !stale.is_empty() {
+            let mut local_msgs = self.local_msgs.write();
+            for k in &stale {
+                local_msgs.remove(k);
+            }
+            self.api
+                .save_local_messages(&local_msgs.iter().cloned().collect::<Vec<_>>())?;
+        }
 
         Ok(())
     }
@@ -478,12 +504,15 @@ where
     where
         T: Provider,
     {
-        let local_addrs = Arc::new(SyncRwLock::new(Vec::new()));
+        let persisted_msgs = api.load_local_messages()?;
+        let local_addrs = Arc::new(SyncRwLock::new(
+            persisted_msgs.iter().map(|msg| msg.from()).collect(),
+        ));
         let pending = Arc::new(SyncRwLock::new(HashMap::new()));
         let tipset = Arc::new(Mutex::new(api.get_heaviest_tipset()));
         let bls_sig_cache = Arc::new(Mutex::new(LruCache::new(BLS_SIG_CACHE_SIZE)));
         let sig_val_cache = Arc::new(Mutex::new(LruCache::new(SIG_VAL_CACHE_SIZE)));
-        let local_msgs = Arc::new(SyncRwLock::new(HashSet::new()));
+        let local_msgs = Arc::new(SyncRwLock::new(HashSet::from_iter(persisted_msgs)));
         let republished = Arc::new(SyncRwLock::new(HashSet::new()));
         let block_delay = chain_config.block_delay_secs;
 
@@ -513,6 +542,7 @@ where
         let bls_sig_cache = mp.bls_sig_cache.clone();
         let pending = mp.pending.clone();
         let republished = mp.republished.clone();
+        let local_msgs = mp.local_msgs.clone();
 
         let cur_tipset = mp.cur_tipset.clone();
         let repub_trigger = Arc::new(mp.repub_trigger.clone());
@@ -541,6 +571,10 @@ where
                         )
                         .await
                         .context("Error changing head")?;
+                        let cur_ts = cur_tipset.lock().clone();
+                        if let Err(e) = prune_local_msgs(api.as_ref(), &local_msgs, &cur_ts) {
+                            warn!("Failed to prune local messages: {}", e);
+                        }
                     }
                     Err(RecvError::Lagged(e)) => {
                         warn!("Head change subscriber lagged: skipping {} events", e);
@@ -591,6 +625,35 @@ where
 
 // Helpers for MessagePool
 
+/// Drop the local messages that have been included on chain, i.e. whose
+/// sequence is below the sender's state sequence at `cur_ts`, and persist the
+/// remaining set.
+fn prune_local_msgs<T>(
+    api: &T,
+    local_msgs: &SyncRwLock<HashSet<SignedMessage>>,
+    cur_ts: &Tipset,
+) -> Result<(), Error>
+where
+    T: Provider,
+{
+    let senders: HashSet<Address> = local_msgs.read().iter().map(|msg| msg.from()).collect();
+    let sequences = senders
+        .into_iter()
+        .map(|addr| Ok((addr, api.get_actor_after(&addr, cur_ts)?.sequence)))
+        .collect::<Result<HashMap<_, _>, Error>>()?;
+    let mut local_msgs = local_msgs.write();
+    let count = local_msgs.len();
+    local_msgs.retain(|msg| {
+        sequences
+            .get(&msg.from())
+            .map_or(true, |sequence| msg.sequence() >= *sequence)
+    });
+    if local_msgs.len() != count {
+        api.save_local_messages(&local_msgs.iter().cloned().collect::<Vec<_>>())?;
+    }
+    Ok(())
+}
+
 /// Finish verifying signed message before adding it to the pending `mset`
 /// hash-map. If an entry in the hash-map does not yet exist, create a new
 /// `mset` that will correspond to the from message and push it to the pending
@@ -644,7 +707,11 @@ fn verify_msg_before_add(
     let epoch = cur_ts.epoch();
     let min_gas = price_list_by_network_version(chain_config.network_version(epoch))
         .on_chain_message(to_vec(m)?.len());
-    valid_for_block_inclusion(m.message(), min_gas.total(), NEWEST_NETWORK_VERSION)?;
+    valid_for_block_inclusion(
+        m.message(),
+        min_gas.total(),
+        chain_config.newest_network_version(),
+    )?;
     if !cur_ts.block_headers().is_empty() {
         let base_fee = &cur_ts.block_headers().first().parent_base_fee;
         let base_fee_lower_bound =
@@ -5,6 +5,7 @@ use std::sync::Arc;
 
 use crate::blocks::{CachingBlockHeader, Tipset, TipsetKey};
 use crate::chain::HeadChange;
+use crate::db::{setting_keys::MPOOL_LOCAL_MSGS_KEY, SettingsStore};
 use crate::message::{ChainMessage, SignedMessage};
 use crate::message_pool::msg_pool::{
     MAX_ACTOR_PENDING_MESSAGES, MAX_UNTRUSTED_ACTOR_PENDING_MESSAGES,
@@ -18,6 +19,7 @@ use crate::shim::{
 };
 use crate::state_manager::StateManager;
 use crate::utils::db::CborStoreExt;
+use crate::utils::encoding::from_slice_with_fallback;
 use async_trait::async_trait;
 use cid::Cid;
 use fvm_ipld_blockstore::Blockstore;
@@ -52,6 +54,10 @@ pub trait Provider {
     fn load_tipset(&self, tsk: &TipsetKey) -> Result<Arc<Tipset>, Error>;
     /// Computes the base fee
     fn chain_compute_base_fee(&self, ts: &Tipset) -> Result<TokenAmount, Error>;
+    /// Return the locally published messages persisted by a previous run
+    fn load_local_messages(&self) -> Result<Vec<SignedMessage>, Error>;
+    /// Persist the locally published messages so they survive a restart
+    fn save_local_messages(&self, msgs: &[SignedMessage]) -> Result<(), Error>;
     // Get max number of messages per actor in the pool
     fn max_actor_pending_messages(&self) -> u64 {
         MAX_ACTOR_PENDING_MESSAGES
@@ -107,7 +113,7 @@ where
         let actor = state
             .get_actor(addr)
             .map_err(|e| Error::Other(e.to_string()))?;
-        actor.ok_or_else(|| Error::Other("No actor state".to_owned()))
+        actor.ok_or(Error::ActorNotFound(*addr))
     }
 
     fn messages_for_block(
@@ -135,4 +141,30 @@ where
             .map_err(|err| err.into())
             .map(Into::into)
     }
+
+    fn load_local_messages(&self) -> Result<Vec<SignedMessage>, Error> {
+        load_local_messages(self.sm.chain_store().settings().as_ref())
+    }
+
+    fn save_local_messages(&self, msgs: &[SignedMessage]) -> Result<(), Error> {
+        save_local_messages(self.sm.chain_store().settings().as_ref(), msgs)
+    }
+}
+
+/// Read the locally published messages from the settings store, if any.
+pub(in crate::message_pool) fn load_local_messages<S: SettingsStore + ?Sized>(
+    store: &S,
+) -> Result<Vec<SignedMessage>, Error> {
+    match store.read_bin(MPOOL_LOCAL_MSGS_KEY)? {
+        Some(bytes) => Ok(from_slice_with_fallback(&bytes)?),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Write the locally published messages to the settings store.
+pub(in crate::message_pool) fn save_local_messages<S: SettingsStore + ?Sized>(
+    store: &S,
+    msgs: &[SignedMessage],
+) -> Result<(), Error> {
+    Ok(store.write_bin(MPOOL_LOCAL_MSGS_KEY, &fvm_ipld_encoding::to_vec(msgs)?)?)
 }
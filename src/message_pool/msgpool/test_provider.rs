@@ -10,6 +10,7 @@ use crate::blocks::VRFProof;
 use crate::blocks::{CachingBlockHeader, ElectionProof, Ticket, Tipset, TipsetKey};
 use crate::chain::HeadChange;
 use crate::cid_collections::CidHashMap;
+use crate::db::MemoryDB;
 use crate::message::{ChainMessage, Message as MessageTrait, SignedMessage};
 use crate::shim::{address::Address, econ::TokenAmount, message::Message, state_tree::ActorState};
 use ahash::HashMap;
@@ -19,7 +20,10 @@ use num::BigInt;
 use parking_lot::Mutex;
 use tokio::sync::broadcast;
 
-use crate::message_pool::{provider::Provider, Error};
+use crate::message_pool::{
+    provider::{load_local_messages, save_local_messages, Provider},
+    Error,
+};
 use tokio::sync::broadcast::{Receiver as Subscriber, Sender as Publisher};
 
 /// Structure used for creating a provider when writing tests involving message
@@ -27,6 +31,7 @@ use tokio::sync::broadcast::{Receiver as Subscriber, Sender as Publisher};
 pub struct TestApi {
     pub inner: Mutex<TestApiInner>,
     pub publisher: Publisher<HeadChange>,
+    pub settings: Arc<MemoryDB>,
 }
 
 #[derive(Default)]
@@ -48,6 +53,7 @@ impl Default for TestApi {
                 ..TestApiInner::default()
             }),
             publisher,
+            settings: Default::default(),
         }
     }
 }
@@ -62,6 +68,15 @@ impl TestApi {
                 ..TestApiInner::default()
             }),
             publisher,
+            settings: Default::default(),
+        }
+    }
+
+    /// Constructor for a `TestApi` persisting its settings in the given store
+    pub fn with_settings(settings: Arc<MemoryDB>) -> Self {
+        TestApi {
+            settings,
+            ..TestApi::default()
         }
     }
 
@@ -215,6 +230,14 @@ impl Provider for TestApi {
         Ok(TokenAmount::from_atto(100))
     }
 
+    fn load_local_messages(&self) -> Result<Vec<SignedMessage>, Error> {
+        load_local_messages(self.settings.as_ref())
+    }
+
+    fn save_local_messages(&self, msgs: &[SignedMessage]) -> Result<(), Error> {
+        save_local_messages(self.settings.as_ref(), msgs)
+    }
+
     fn max_actor_pending_messages(&self) -> u64 {
         self.inner.lock().max_actor_pending_messages
     }
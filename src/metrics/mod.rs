@@ -37,6 +37,15 @@ pub static LRU_CACHE_MISS: Lazy<Family<KindLabel, Counter>> = Lazy::new(|| {
         .register("lru_cache_miss", "Stats of lru cache miss", metric.clone());
     metric
 });
+pub static LRU_CACHE_EVICTION: Lazy<Family<KindLabel, Counter>> = Lazy::new(|| {
+    let metric = Family::default();
+    DEFAULT_REGISTRY.write().register(
+        "lru_cache_eviction",
+        "Stats of lru cache eviction",
+        metric.clone(),
+    );
+    metric
+});
 
 pub async fn init_prometheus<DB>(
     prometheus_listener: TcpListener,
@@ -131,6 +140,8 @@ pub mod values {
     pub const TIPSET: KindLabel = KindLabel::new("tipset");
     /// tipset cache in state manager
     pub const STATE_MANAGER_TIPSET: KindLabel = KindLabel::new("sm_tipset");
+    /// block read cache in front of the database
+    pub const BLOCKS: KindLabel = KindLabel::new("blocks");
 }
 
 pub fn default_histogram() -> Histogram {
@@ -1,22 +1,28 @@
 // Copyright 2019-2024 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use std::collections::BTreeMap;
 use std::io::{self, Cursor};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use anyhow::{ensure, Context as _};
 use async_compression::tokio::write::ZstdEncoder;
 use cid::Cid;
 use futures::stream::FuturesUnordered;
-use futures::{stream, StreamExt, TryStreamExt};
+use futures::{stream, Future, StreamExt, TryStreamExt};
+use fvm_ipld_blockstore::Blockstore as _;
 use itertools::Itertools;
 use nonempty::NonEmpty;
 use once_cell::sync::Lazy;
+use positioned_io::RandomAccessFile;
 use reqwest::Url;
+use sha2::{Digest as _, Sha256};
 use tokio::fs::File;
+use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _, BufWriter};
 use tracing::warn;
 
-use crate::utils::db::car_stream::{CarStream, CarWriter};
+use crate::db::car::PlainCar;
+use crate::utils::db::car_stream::{CarBlock, CarStream, CarWriter};
 use crate::utils::net::http_get;
 
 use std::str::FromStr;
@@ -32,10 +38,111 @@ pub struct ActorBundleInfo {
     /// ourselves when a new bundle is released.
     pub alt_url: Url,
     pub network: NetworkChain,
+    /// Expected SHA-256 of the bundle CAR file, in hex, checked after
+    /// downloading it from either URL. Bundles without one are only checked
+    /// against their manifest, with a warning; `forest-tool state-migration
+    /// actor-bundle --verify-only` prints the checksums to record.
+    pub sha256: Option<&'static str>,
+}
+
+impl ActorBundleInfo {
+    /// Requests the bundle from the primary URL, or the alternative URL if the
+    /// primary one is down.
+    async fn get(&self) -> anyhow::Result<(&Url, reqwest::Response)> {
+        match http_get(&self.url).await {
+            Ok(response) => Ok((&self.url, response)),
+            Err(_) => {
+                warn!("failed to download bundle from primary URL, trying alternative URL");
+                Ok((&self.alt_url, http_get(&self.alt_url).await?))
+            }
+        }
+    }
+
+    /// Downloads the bundle, and checks its checksum.
+    pub async fn download(&self) -> anyhow::Result<bytes::Bytes> {
+        let (url, response) = self.get().await?;
+        let bytes = response.bytes().await?;
+        self.verify_checksum(url, &bytes)?;
+        Ok(bytes)
+    }
+
+    /// Streams the bundle into a file at `path`, and checks its checksum.
+    pub async fn download_to(&self, path: &Path) -> anyhow::Result<()> {
+        let (url, response) = self.get().await?;
+        let mut file = BufWriter::new(File::create(path).await?);
+        let mut hasher = Sha256::new();
+        let mut chunks = response.bytes_stream();
+        while let Some(chunk) = chunks.try_next().await? {
+            hasher.update(&chunk);
+            file.write_all(&chunk).await?;
+        }
+        file.flush().await?;
+        self.check_sha256(url, hex::encode(hasher.finalize()))?;
+        Ok(())
+    }
+
+    /// Checks the SHA-256 of the bundle read from `source`, if one is
+    /// recorded, and returns it.
+    pub fn verify_checksum(
+        &self,
+        source: impl std::fmt::Display,
+        bytes: &[u8],
+    ) -> anyhow::Result<String> {
+        self.check_sha256(source, hex::encode(Sha256::digest(bytes)))
+    }
+
+    fn check_sha256(
+        &self,
+        source: impl std::fmt::Display,
+        actual: String,
+    ) -> anyhow::Result<String> {
+        match self.sha256 {
+            Some(expected) => ensure!(
+                actual == expected,
+                "actor bundle {} downloaded from {source} has sha256 {actual}, expected {expected}",
+                self.manifest
+            ),
+            None => warn!(
+                "actor bundle {} downloaded from {source} has no recorded sha256 (got {actual}), only its manifest is checked",
+                self.manifest
+            ),
+        }
+        Ok(actual)
+    }
+}
+
+/// An actor bundle supplementing the built-in ones, e.g. a release candidate
+/// for a network reset, written `<manifest-cid>=<path-or-url>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActorBundleOverride {
+    pub manifest: Cid,
+    /// Path or URL of the bundle CAR file
+    pub source: String,
+}
+
+impl FromStr for ActorBundleOverride {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (manifest, source) = s
+            .split_once('=')
+            .context("actor bundle must be written `<manifest-cid>=<path-or-url>`")?;
+        ensure!(!source.is_empty(), "actor bundle {manifest} has no source");
+        Ok(Self {
+            manifest: manifest.parse()?,
+            source: source.to_owned(),
+        })
+    }
+}
+
+impl std::fmt::Display for ActorBundleOverride {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}={}", self.manifest, self.source)
+    }
 }
 
 macro_rules! actor_bundle_info {
-    ($($cid:literal @ $version:literal for $network:literal),* $(,)?) => {
+    ($($cid:literal @ $version:literal for $network:literal $(sha256 $sha256:literal)?),* $(,)?) => {
         [
             $(
                 ActorBundleInfo {
@@ -55,6 +162,7 @@ macro_rules! actor_bundle_info {
                             ".car"
                         ).parse().unwrap(),
                     network: NetworkChain::from_str($network).unwrap(),
+                    sha256: None $(.or(Some($sha256)))?,
                 },
             )*
         ]
@@ -85,61 +193,156 @@ pub static ACTOR_BUNDLES: Lazy<Box<[ActorBundleInfo]>> = Lazy::new(|| {
     ])
 });
 
-pub async fn generate_actor_bundle(output: &Path) -> anyhow::Result<()> {
-    let (mut roots, blocks) = FuturesUnordered::from_iter(ACTOR_BUNDLES.iter().map(
-        |ActorBundleInfo {
-             manifest: root,
-             url,
-             alt_url,
-             network: _,
-         }| async move {
-            let response = if let Ok(response) = http_get(url).await {
-                response
-            } else {
-                warn!("failed to download bundle from primary URL, trying alternative URL");
-                http_get(alt_url).await?
-            };
-            let bytes = response.bytes().await?;
-            let car = CarStream::new(Cursor::new(bytes)).await?;
-            ensure!(car.header.version == 1);
-            ensure!(car.header.roots.len() == 1);
-            ensure!(car.header.roots.first() == root);
-            anyhow::Ok((*root, car.try_collect::<Vec<_>>().await?))
-        },
-    ))
-    .try_collect::<Vec<_>>()
-    .await?
-    .into_iter()
-    .unzip::<_, _, Vec<_>, Vec<_>>();
+/// Downloads all the bundles, and checks their checksums and manifests.
+/// Returns the checksum of each bundle.
+pub async fn verify_actor_bundles() -> anyhow::Result<Vec<(&'static ActorBundleInfo, String)>> {
+    FuturesUnordered::from_iter(ACTOR_BUNDLES.iter().map(|info| async move {
+        let bytes = info.download().await?;
+        let car = CarStream::new(Cursor::new(&bytes)).await?;
+        ensure!(car.header.version == 1);
+        ensure!(car.header.roots.len() == 1);
+        ensure!(car.header.roots.first() == &info.manifest);
+        anyhow::Ok((info, hex::encode(Sha256::digest(&bytes))))
+    }))
+    .try_collect()
+    .await
+}
 
-    ensure!(roots.iter().all_unique());
+/// Options of [`generate_actor_bundle`].
+#[derive(Debug, Clone)]
+pub struct ActorBundleOptions {
+    /// Path of the merged bundle
+    pub output: PathBuf,
+    /// Directory keeping the downloaded bundles, named after their manifests
+    pub cache_dir: PathBuf,
+    /// Fail instead of downloading the bundles missing from the cache
+    pub offline: bool,
+}
 
-    roots.sort(); // deterministic
+/// Bundles downloaded at the same time when generating the merged bundle
+const MAX_CONCURRENT_DOWNLOADS: usize = 4;
 
-    let mut blocks = blocks.into_iter().flatten().collect::<Vec<_>>();
-    blocks.sort();
-    blocks.dedup();
+/// Merges all the bundles into the output file, and returns the SHA-256 of
+/// the merged file, in hex.
+pub async fn generate_actor_bundle(options: &ActorBundleOptions) -> anyhow::Result<String> {
+    generate_actor_bundle_from(&ACTOR_BUNDLES, options, |info, path| async move {
+        info.download_to(&path).await
+    })
+    .await
+}
 
-    for block in blocks.iter() {
-        ensure!(
-            block.valid(),
-            "sources contain an invalid block, cid {}",
-            block.cid
-        )
+async fn sha256_file(path: &Path) -> anyhow::Result<String> {
+    let mut file = File::open(path).await?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; 1 << 16];
+    loop {
+        match file.read(&mut buf).await? {
+            0 => return Ok(hex::encode(hasher.finalize())),
+            n => hasher.update(buf.get(..n).unwrap_or_default()),
+        }
+    }
+}
+
+/// Checks that the CAR file at `path` is the given bundle.
+async fn check_bundle_file(info: &ActorBundleInfo, path: &Path) -> anyhow::Result<()> {
+    let car = CarStream::new(tokio::io::BufReader::new(File::open(path).await?)).await?;
+    ensure!(car.header.version == 1);
+    ensure!(car.header.roots.len() == 1);
+    ensure!(car.header.roots.first() == &info.manifest);
+    info.check_sha256(path.display(), sha256_file(path).await?)?;
+    Ok(())
+}
+
+/// Returns the path of the bundle in the cache if it is there with the right
+/// root, else fetches it into the cache first.
+async fn fetch_cached<'a, Fut>(
+    info: &'a ActorBundleInfo,
+    options: &ActorBundleOptions,
+    fetch: impl FnOnce(&'a ActorBundleInfo, PathBuf) -> Fut,
+) -> anyhow::Result<PathBuf>
+where
+    Fut: Future<Output = anyhow::Result<()>>,
+{
+    let path = options.cache_dir.join(format!("{}.car", info.manifest));
+    if path.is_file() {
+        match check_bundle_file(info, &path).await {
+            Ok(()) => return Ok(path),
+            Err(e) => warn!("ignoring cached bundle {}: {e}", path.display()),
+        }
+    }
+    ensure!(
+        !options.offline,
+        "actor bundle {} for {} is missing from the cache {}",
+        info.manifest,
+        info.network,
+        options.cache_dir.display()
+    );
+    tokio::fs::create_dir_all(&options.cache_dir).await?;
+    let temp_path = tempfile::NamedTempFile::new_in(&options.cache_dir)?.into_temp_path();
+    fetch(info, temp_path.to_path_buf()).await?;
+    check_bundle_file(info, &temp_path).await?;
+    temp_path.persist(&path)?;
+    Ok(path)
+}
+
+/// Merges the bundles without holding them in memory: they are fetched into
+/// the cache, indexed on disk, and their blocks are written sorted by CID,
+/// without duplicates.
+async fn generate_actor_bundle_from<'a, Fut>(
+    bundles: &'a [ActorBundleInfo],
+    options: &ActorBundleOptions,
+    fetch: impl Fn(&'a ActorBundleInfo, PathBuf) -> Fut,
+) -> anyhow::Result<String>
+where
+    Fut: Future<Output = anyhow::Result<()>>,
+{
+    let fetch = &fetch;
+    let paths = stream::iter(bundles)
+        .map(|info| fetch_cached(info, options, fetch))
+        .buffer_unordered(MAX_CONCURRENT_DOWNLOADS)
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    let cars = paths
+        .iter()
+        .map(|path| Ok(PlainCar::new(RandomAccessFile::open(path)?)?))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let mut roots = cars
+        .iter()
+        .map(|car| *car.roots().first())
+        .collect::<Vec<_>>();
+    ensure!(roots.iter().all_unique());
+    roots.sort(); // deterministic
+
+    // The CAR holding each block, in the order of the output
+    let mut blocks = BTreeMap::new();
+    for car in &cars {
+        for cid in car.cids()? {
+            let data = car.get(&cid)?.context("indexed block is missing")?;
+            ensure!(
+                CarBlock { cid, data }.valid(),
+                "sources contain an invalid block, cid {cid}"
+            );
+            blocks.entry(cid).or_insert(car);
+        }
     }
 
     stream::iter(blocks)
-        .map(io::Result::Ok)
+        .map(|(cid, car)| {
+            let data = car.get(&cid)?.context("indexed block is missing")?;
+            Ok(CarBlock { cid, data })
+        })
+        .map_err(|e: anyhow::Error| io::Error::other(e))
         .forward(CarWriter::new_carv1(
             NonEmpty::from_vec(roots).context("car roots cannot be empty")?,
             ZstdEncoder::with_quality(
-                File::create(&output).await?,
+                File::create(&options.output).await?,
                 async_compression::Level::Precise(17),
             ),
         )?)
         .await?;
 
-    Ok(())
+    sha256_file(&options.output).await
 }
 
 #[cfg(test)]
@@ -152,6 +355,207 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_actor_bundle_override_parsing() {
+        let bundle: ActorBundleOverride =
+            "bafy2bzacecdhvfmtirtojwhw2tyciu4jkbpsbk5g53oe24br27oy62sn4dc4e=https://example.com/a=b.car"
+                .parse()
+                .unwrap();
+        assert_eq!(bundle.source, "https://example.com/a=b.car");
+        assert_eq!(
+            bundle.to_string().parse::<ActorBundleOverride>().unwrap(),
+            bundle
+        );
+
+        assert!(
+            "bafy2bzacecdhvfmtirtojwhw2tyciu4jkbpsbk5g53oe24br27oy62sn4dc4e"
+                .parse::<ActorBundleOverride>()
+                .is_err()
+        );
+        assert!(
+            "bafy2bzacecdhvfmtirtojwhw2tyciu4jkbpsbk5g53oe24br27oy62sn4dc4e="
+                .parse::<ActorBundleOverride>()
+                .is_err()
+        );
+        assert!("bundle.car=bundle.car"
+            .parse::<ActorBundleOverride>()
+            .is_err());
+    }
+
+    #[test]
+    fn test_verify_checksum() {
+        let [mut info] = actor_bundle_info![
+            "bafy2bzacecdhvfmtirtojwhw2tyciu4jkbpsbk5g53oe24br27oy62sn4dc4e" @ "v13.0.0" for "mainnet"
+                sha256 "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824",
+        ];
+        let url = info.url.clone();
+        assert_eq!(
+            info.verify_checksum(&url, b"hello").unwrap(),
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+        let error = info
+            .verify_checksum(&url, b"world")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains(url.as_str()), "{error}");
+
+        info.sha256 = None;
+        assert!(info.verify_checksum(&url, b"world").is_ok());
+    }
+
+    /// Returns a bundle of the given blocks, rooted at the first one.
+    async fn make_bundle(blocks: &[&str]) -> (Cid, bytes::Bytes) {
+        use crate::utils::cid::CidCborExt as _;
+
+        let blocks = blocks
+            .iter()
+            .map(|block| CarBlock {
+                cid: Cid::from_cbor_blake2b256(block).unwrap(),
+                data: fvm_ipld_encoding::to_vec(block).unwrap(),
+            })
+            .collect::<Vec<_>>();
+        let root = blocks[0].cid;
+        let mut car = vec![];
+        stream::iter(blocks)
+            .map(io::Result::Ok)
+            .forward(CarWriter::new_carv1(NonEmpty::new(root), &mut car).unwrap())
+            .await
+            .unwrap();
+        (root, car.into())
+    }
+
+    fn bundle_infos(manifests: impl IntoIterator<Item = Cid>) -> Vec<ActorBundleInfo> {
+        manifests
+            .into_iter()
+            .map(|manifest| {
+                let [info] = actor_bundle_info![
+                    "bafy2bzacecdhvfmtirtojwhw2tyciu4jkbpsbk5g53oe24br27oy62sn4dc4e" @ "v13.0.0" for "mainnet",
+                ];
+                ActorBundleInfo { manifest, ..info }
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_generate_from_cache() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let (manifest, car) = make_bundle(&["Cthulhu"]).await;
+        let mut bundles = bundle_infos([manifest]);
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut options = ActorBundleOptions {
+            output: temp_dir.path().join("bundles.car.zst"),
+            cache_dir: temp_dir.path().join("cache"),
+            offline: true,
+        };
+        let fetches = AtomicUsize::new(0);
+        let fetch = |_: &ActorBundleInfo, path: PathBuf| {
+            fetches.fetch_add(1, Ordering::Relaxed);
+            let car = car.clone();
+            async move { Ok(tokio::fs::write(path, car).await?) }
+        };
+
+        // Missing bundles are not downloaded offline
+        let error = generate_actor_bundle_from(&bundles, &options, fetch)
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("missing from the cache"), "{error}");
+        assert_eq!(fetches.load(Ordering::Relaxed), 0);
+
+        // They are downloaded once, then read from the cache
+        options.offline = false;
+        generate_actor_bundle_from(&bundles, &options, fetch)
+            .await
+            .unwrap();
+        assert_eq!(fetches.load(Ordering::Relaxed), 1);
+        assert!(options.cache_dir.join(format!("{manifest}.car")).is_file());
+
+        options.offline = true;
+        let sha256 = generate_actor_bundle_from(&bundles, &options, fetch)
+            .await
+            .unwrap();
+        assert_eq!(fetches.load(Ordering::Relaxed), 1);
+        assert_eq!(
+            sha256,
+            hex::encode(Sha256::digest(std::fs::read(&options.output).unwrap()))
+        );
+
+        // A cached bundle of another manifest is not used
+        std::fs::copy(
+            options.cache_dir.join(format!("{manifest}.car")),
+            options
+                .cache_dir
+                .join(format!("{}.car", ACTOR_BUNDLES[0].manifest)),
+        )
+        .unwrap();
+        bundles[0].manifest = ACTOR_BUNDLES[0].manifest;
+        assert!(generate_actor_bundle_from(&bundles, &options, fetch)
+            .await
+            .is_err());
+    }
+
+    // The merged bundle is the same as when merging bundles in memory, as
+    // `generate_actor_bundle` used to do
+    #[tokio::test]
+    async fn test_generate_matches_in_memory_merge() {
+        let sources = [
+            make_bundle(&["system", "account", "init"]).await,
+            make_bundle(&["miner", "account", "market"]).await,
+            make_bundle(&["power", "init", "market", "reward"]).await,
+        ];
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        let mut roots = vec![];
+        let mut blocks = vec![];
+        for (_, car) in &sources {
+            let car = CarStream::new(Cursor::new(car)).await.unwrap();
+            roots.push(*car.header.roots.first());
+            blocks.extend(car.try_collect::<Vec<_>>().await.unwrap());
+        }
+        roots.sort();
+        blocks.sort();
+        blocks.dedup();
+        let in_memory = temp_dir.path().join("in_memory.car.zst");
+        stream::iter(blocks)
+            .map(io::Result::Ok)
+            .forward(
+                CarWriter::new_carv1(
+                    NonEmpty::from_vec(roots).unwrap(),
+                    ZstdEncoder::with_quality(
+                        File::create(&in_memory).await.unwrap(),
+                        async_compression::Level::Precise(17),
+                    ),
+                )
+                .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let options = ActorBundleOptions {
+            output: temp_dir.path().join("streamed.car.zst"),
+            cache_dir: temp_dir.path().join("cache"),
+            offline: false,
+        };
+        let bundles = bundle_infos(sources.iter().map(|(manifest, _)| *manifest));
+        let sources = &sources;
+        let sha256 = generate_actor_bundle_from(&bundles, &options, |info, path| async move {
+            let (_, car) = sources
+                .iter()
+                .find(|(manifest, _)| *manifest == info.manifest)
+                .unwrap();
+            Ok(tokio::fs::write(path, car).await?)
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(
+            std::fs::read(&options.output).unwrap(),
+            std::fs::read(&in_memory).unwrap()
+        );
+        assert_eq!(sha256, sha256_file(&in_memory).await.unwrap());
+    }
+
     #[tokio::test]
     async fn check_bundles_are_mirrored() {
         // Run the test only in CI so that regular test on dev machines don't download the bundles
@@ -161,11 +565,11 @@ mod tests {
         }
 
         FuturesUnordered::from_iter(ACTOR_BUNDLES.iter().map(
-            |ActorBundleInfo {
+            |info @ ActorBundleInfo {
                  manifest,
                  url,
                  alt_url,
-                 network: _,
+                 ..
              }| async move {
                 let (primary, alt) = match (http_get(url).await, http_get(alt_url).await) {
                     (Ok(primary), Ok(alt)) => (primary, alt),
@@ -205,6 +609,10 @@ mod tests {
                     _ => return anyhow::Ok(()),
                 };
 
+                // Check that the bundles match their recorded checksums.
+                info.verify_checksum(url, &primary)?;
+                info.verify_checksum(alt_url, &alt)?;
+
                 let car_primary = CarStream::new(Cursor::new(primary)).await?;
                 let car_secondary = CarStream::new(Cursor::new(alt)).await?;
 
@@ -2,13 +2,22 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use ahash::HashMap;
+use anyhow::Context as _;
+use backoff::{ExponentialBackoff, ExponentialBackoffBuilder};
 use cid::Cid;
+use futures::TryStreamExt as _;
+use itertools::Itertools as _;
 use libp2p::Multiaddr;
 use once_cell::sync::Lazy;
 use std::str::FromStr;
+use std::time::Duration;
 use url::Url;
 
-use crate::{db::SettingsStore, shim::version::NetworkVersion, utils::net::http_get};
+use crate::{
+    db::SettingsStore,
+    shim::version::NetworkVersion,
+    utils::{db::car_stream::CarStream, net::http_get},
+};
 
 use super::{
     drand::{DRAND_MAINNET, DRAND_QUICKNET},
@@ -20,19 +29,85 @@ pub const GENESIS_NETWORK_VERSION: NetworkVersion = NetworkVersion::V21;
 /// Fetches the genesis CAR from the local database or downloads it if it does not exist.
 /// The result bytes may be compressed.
 pub async fn fetch_genesis<DB: SettingsStore>(db: &DB) -> anyhow::Result<Vec<u8>> {
-    let genesis_key = format!("BUTTERFLY_GENESIS-{}", &*GENESIS_CID);
-    if let Some(genesis) = db.read_bin(&genesis_key)? {
-        Ok(genesis)
-    } else {
-        let response = if let Ok(genesis) = http_get(&GENESIS_URL).await {
-            genesis
-        } else {
-            http_get(&GENESIS_URL_ALT).await?
-        };
-        let genesis = response.bytes().await?;
-        db.write_bin(&genesis_key, &genesis)?;
-        Ok(genesis.to_vec())
+    fetch_genesis_from(
+        db,
+        &[&*GENESIS_URL, &*GENESIS_URL_ALT],
+        &GENESIS_CID,
+        ExponentialBackoffBuilder::default()
+            .with_max_elapsed_time(Some(Duration::from_secs(5 * 60)))
+            .build(),
+    )
+    .await
+}
+
+/// Checks that `genesis` is the butterflynet genesis, and caches it in the
+/// local database, e.g. when it is provided as a file in an air-gapped
+/// environment.
+pub async fn save_genesis<DB: SettingsStore>(db: &DB, genesis: &[u8]) -> anyhow::Result<()> {
+    verify_genesis(genesis, &GENESIS_CID).await?;
+    db.write_bin(&genesis_key(&GENESIS_CID), genesis)
+}
+
+fn genesis_key(genesis_cid: &Cid) -> String {
+    format!("BUTTERFLY_GENESIS-{genesis_cid}")
+}
+
+/// Downloads the genesis from the first of `urls` that serves it, retrying
+/// with `backoff` while none does. The genesis is only cached once it is
+/// verified to be `genesis_cid`.
+async fn fetch_genesis_from<DB: SettingsStore>(
+    db: &DB,
+    urls: &[&Url],
+    genesis_cid: &Cid,
+    backoff: ExponentialBackoff,
+) -> anyhow::Result<Vec<u8>> {
+    if let Some(genesis) = db.read_bin(&genesis_key(genesis_cid))? {
+        return Ok(genesis);
     }
+    let genesis = backoff::future::retry(backoff, || async {
+        let mut errors = vec![];
+        for url in urls {
+            match download(url).await {
+                Ok(genesis) => return Ok(genesis),
+                Err(e) => errors.push(format!("{url}: {e:#}")),
+            }
+        }
+        Err(backoff::Error::transient(anyhow::anyhow!(
+            "couldn't download the genesis: {}",
+            errors.join(", ")
+        )))
+    })
+    .await?;
+    verify_genesis(&genesis, genesis_cid).await?;
+    db.write_bin(&genesis_key(genesis_cid), &genesis)?;
+    Ok(genesis)
+}
+
+async fn download(url: &Url) -> anyhow::Result<Vec<u8>> {
+    Ok(http_get(url).await?.bytes().await?.to_vec())
+}
+
+/// Checks that the, possibly compressed, CAR `genesis` holds the genesis block
+/// `genesis_cid`.
+async fn verify_genesis(genesis: &[u8], genesis_cid: &Cid) -> anyhow::Result<()> {
+    let mut car = CarStream::new(genesis)
+        .await
+        .context("the genesis is not a CAR file")?;
+    anyhow::ensure!(
+        car.header.roots.len() == 1 && car.header.roots.first() == genesis_cid,
+        "the genesis has roots {} instead of the butterflynet genesis {genesis_cid}",
+        car.header.roots.iter().join(", ")
+    );
+    while let Some(block) = car.try_next().await? {
+        if &block.cid == genesis_cid {
+            anyhow::ensure!(
+                block.valid(),
+                "the genesis block {genesis_cid} is corrupted"
+            );
+            return Ok(());
+        }
+    }
+    anyhow::bail!("the genesis block {genesis_cid} is missing")
 }
 
 /// Genesis CID
@@ -60,8 +135,10 @@ pub(crate) const MINIMUM_VERIED_ALLOCATION: i64 = 1 << 20;
 pub(crate) const PRE_COMMIT_CHALLENGE_DELAY: i64 = 150;
 
 /// Default bootstrap peer ids.
-pub static DEFAULT_BOOTSTRAP: Lazy<Vec<Multiaddr>> =
-    Lazy::new(|| parse_bootstrap_peers(include_str!("../../../build/bootstrap/butterflynet")));
+pub static DEFAULT_BOOTSTRAP: Lazy<Vec<Multiaddr>> = Lazy::new(|| {
+    parse_bootstrap_peers(include_str!("../../../build/bootstrap/butterflynet"))
+        .expect("built-in bootstrap peers must parse")
+});
 
 // https://github.com/ethereum-lists/chains/blob/4731f6713c6fc2bf2ae727388642954a6545b3a9/_data/chains/eip155-314159.json
 pub const ETH_CHAIN_ID: u64 = 3141592;
@@ -74,6 +151,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
             HeightInfo {
                 epoch: -50,
                 bundle: None,
+                network_version: None,
             },
         ),
         (
@@ -81,6 +159,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
             HeightInfo {
                 epoch: -2,
                 bundle: None,
+                network_version: None,
             },
         ),
         (
@@ -88,6 +167,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
             HeightInfo {
                 epoch: -3,
                 bundle: None,
+                network_version: None,
             },
         ),
         (
@@ -95,6 +175,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
             HeightInfo {
                 epoch: -3,
                 bundle: None,
+                network_version: None,
             },
         ),
         (
@@ -102,6 +183,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
             HeightInfo {
                 epoch: -6,
                 bundle: None,
+                network_version: None,
             },
         ),
         (
@@ -109,6 +191,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
             HeightInfo {
                 epoch: -9,
                 bundle: None,
+                network_version: None,
             },
         ),
         (
@@ -119,6 +202,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
                     Cid::try_from("bafy2bzacectxvbk77ntedhztd6sszp2btrtvsmy7lp2ypnrk6yl74zb34t2cq")
                         .unwrap(),
                 ),
+                network_version: None,
             },
         ),
         (
@@ -129,21 +213,22 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
                     Cid::try_from("bafy2bzaceaqx5xa4cwso24rjiu2ketjlztrqlac6dkyol7tlyuhzrle3zfbos")
                         .unwrap(),
                 ),
+                network_version: None,
             },
         ),
     ])
 });
 
-pub(super) static DRAND_SCHEDULE: Lazy<[DrandPoint<'static>; 2]> = Lazy::new(|| {
+pub(super) static DRAND_SCHEDULE: Lazy<[DrandPoint; 2]> = Lazy::new(|| {
     [
         DrandPoint {
             height: 0,
-            config: &DRAND_MAINNET,
+            config: DRAND_MAINNET.clone(),
         },
         DrandPoint {
             height: get_upgrade_height_from_env("FOREST_DRAND_QUICKNET_HEIGHT")
                 .unwrap_or(HEIGHT_INFOS.get(&Height::Dragon).unwrap().epoch + 120),
-            config: &DRAND_QUICKNET,
+            config: DRAND_QUICKNET.clone(),
         },
     ]
 });
@@ -204,6 +289,11 @@ macro_rules! make_butterfly_policy {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::db::MemoryDB;
+    use crate::networks::{calibnet, mainnet, ChainConfig};
+    use std::path::Path;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
 
     #[test]
     fn default_boostrap_list_not_empty() {
@@ -223,4 +313,114 @@ mod tests {
         assert_eq!(v12.minimum_consensus_power, MINIMUM_CONSENSUS_POWER.into());
         assert_eq!(v13.minimum_consensus_power, MINIMUM_CONSENSUS_POWER.into());
     }
+
+    /// Serves the calibnet genesis, which stands in for the butterflynet one,
+    /// at `/genesis.car` after a first transient failure, and the mainnet
+    /// genesis at `/wrong.car`. Returns the base URL and the number of requests
+    /// for the genesis.
+    async fn mock_genesis_server() -> (Url, Arc<AtomicUsize>) {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}/", listener.local_addr().unwrap())
+            .parse()
+            .unwrap();
+        let app = axum::Router::new()
+            .route(
+                "/genesis.car",
+                axum::routing::get({
+                    let requests = requests.clone();
+                    move || async move {
+                        match requests.fetch_add(1, Ordering::SeqCst) {
+                            0 => Err(http::StatusCode::SERVICE_UNAVAILABLE),
+                            _ => Ok(calibnet::DEFAULT_GENESIS),
+                        }
+                    }
+                }),
+            )
+            .route(
+                "/wrong.car",
+                axum::routing::get(|| async { mainnet::DEFAULT_GENESIS }),
+            );
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (base, requests)
+    }
+
+    fn quick_backoff() -> ExponentialBackoff {
+        ExponentialBackoffBuilder::default()
+            .with_initial_interval(Duration::from_millis(10))
+            .with_max_elapsed_time(Some(Duration::from_secs(10)))
+            .build()
+    }
+
+    #[tokio::test]
+    async fn fetch_genesis_rejects_another_genesis() {
+        let (base, _) = mock_genesis_server().await;
+        let db = MemoryDB::default();
+        let genesis_cid = &*calibnet::GENESIS_CID;
+
+        let error = fetch_genesis_from(
+            &db,
+            &[&base.join("wrong.car").unwrap()],
+            genesis_cid,
+            quick_backoff(),
+        )
+        .await
+        .unwrap_err();
+        assert!(
+            error.to_string().contains(&format!(
+                "the genesis has roots {} instead of the butterflynet genesis {genesis_cid}",
+                *mainnet::GENESIS_CID
+            )),
+            "{error:#}"
+        );
+        assert_eq!(db.read_bin(&genesis_key(genesis_cid)).unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn fetch_genesis_caches_the_genesis() {
+        let (base, requests) = mock_genesis_server().await;
+        let db = MemoryDB::default();
+        let genesis_cid = &*calibnet::GENESIS_CID;
+        let urls = [
+            &base.join("missing.car").unwrap(),
+            &base.join("genesis.car").unwrap(),
+        ];
+
+        // The first request fails, and is retried
+        let genesis = fetch_genesis_from(&db, &urls, genesis_cid, quick_backoff())
+            .await
+            .unwrap();
+        assert_eq!(genesis, calibnet::DEFAULT_GENESIS);
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+        assert_eq!(
+            db.read_bin(&genesis_key(genesis_cid)).unwrap().as_deref(),
+            Some(calibnet::DEFAULT_GENESIS)
+        );
+
+        // Later fetches are offline
+        let genesis = fetch_genesis_from(&db, &urls, genesis_cid, quick_backoff())
+            .await
+            .unwrap();
+        assert_eq!(genesis, calibnet::DEFAULT_GENESIS);
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn genesis_file_is_verified() {
+        let db = MemoryDB::default();
+        let config = ChainConfig {
+            genesis_file: Some(
+                Path::new(env!("CARGO_MANIFEST_DIR")).join("src/networks/calibnet/genesis.car"),
+            ),
+            ..ChainConfig::butterflynet()
+        };
+        let error = config.genesis_bytes(&db).await.unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("instead of the butterflynet genesis"),
+            "{error:#}"
+        );
+        assert_eq!(db.read_bin(&genesis_key(&GENESIS_CID)).unwrap(), None);
+    }
 }
@@ -23,8 +23,10 @@ pub static GENESIS_CID: Lazy<Cid> = Lazy::new(|| {
 pub const GENESIS_NETWORK_VERSION: NetworkVersion = NetworkVersion::V0;
 
 /// Default bootstrap peer ids.
-pub static DEFAULT_BOOTSTRAP: Lazy<Vec<Multiaddr>> =
-    Lazy::new(|| parse_bootstrap_peers(include_str!("../../../build/bootstrap/calibnet")));
+pub static DEFAULT_BOOTSTRAP: Lazy<Vec<Multiaddr>> = Lazy::new(|| {
+    parse_bootstrap_peers(include_str!("../../../build/bootstrap/calibnet"))
+        .expect("built-in bootstrap peers must parse")
+});
 
 const LIGHTNING_EPOCH: i64 = 489_094;
 
@@ -44,6 +46,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
             HeightInfo {
                 epoch: -1,
                 bundle: None,
+                network_version: None,
             },
         ),
         (
@@ -51,6 +54,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
             HeightInfo {
                 epoch: -2,
                 bundle: None,
+                network_version: None,
             },
         ),
         (
@@ -58,6 +62,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
             HeightInfo {
                 epoch: -3,
                 bundle: None,
+                network_version: None,
             },
         ),
         (
@@ -65,6 +70,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
             HeightInfo {
                 epoch: 30,
                 bundle: None,
+                network_version: None,
             },
         ),
         (
@@ -72,6 +78,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
             HeightInfo {
                 epoch: 60,
                 bundle: None,
+                network_version: None,
             },
         ),
         (
@@ -79,6 +86,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
             HeightInfo {
                 epoch: -5,
                 bundle: None,
+                network_version: None,
             },
         ),
         (
@@ -86,6 +94,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
             HeightInfo {
                 epoch: 90,
                 bundle: None,
+                network_version: None,
             },
         ),
         (
@@ -93,6 +102,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
             HeightInfo {
                 epoch: 120,
                 bundle: None,
+                network_version: None,
             },
         ),
         (
@@ -100,6 +110,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
             HeightInfo {
                 epoch: 130,
                 bundle: None,
+                network_version: None,
             },
         ),
         (
@@ -107,6 +118,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
             HeightInfo {
                 epoch: 300,
                 bundle: None,
+                network_version: None,
             },
         ),
         (
@@ -114,6 +126,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
             HeightInfo {
                 epoch: 330,
                 bundle: None,
+                network_version: None,
             },
         ),
         (
@@ -121,6 +134,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
             HeightInfo {
                 epoch: 360,
                 bundle: None,
+                network_version: None,
             },
         ),
         (
@@ -128,6 +142,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
             HeightInfo {
                 epoch: 390,
                 bundle: None,
+                network_version: None,
             },
         ),
         (
@@ -135,6 +150,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
             HeightInfo {
                 epoch: 420,
                 bundle: None,
+                network_version: None,
             },
         ),
         (
@@ -142,6 +158,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
             HeightInfo {
                 epoch: 450,
                 bundle: None,
+                network_version: None,
             },
         ),
         (
@@ -149,6 +166,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
             HeightInfo {
                 epoch: 480,
                 bundle: None,
+                network_version: None,
             },
         ),
         (
@@ -156,6 +174,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
             HeightInfo {
                 epoch: 510,
                 bundle: None,
+                network_version: None,
             },
         ),
         (
@@ -166,6 +185,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
                     Cid::try_from("bafy2bzacedbedgynklc4dgpyxippkxmba2mgtw7ecntoneclsvvl4klqwuyyy")
                         .unwrap(),
                 ),
+                network_version: None,
             },
         ),
         (
@@ -176,6 +196,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
                     Cid::try_from("bafy2bzaced25ta3j6ygs34roprilbtb3f6mxifyfnm7z7ndquaruxzdq3y7lo")
                         .unwrap(),
                 ),
+                network_version: None,
             },
         ),
         (
@@ -186,6 +207,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
                     Cid::try_from("bafy2bzacedhuowetjy2h4cxnijz2l64h4mzpk5m256oywp4evarpono3cjhco")
                         .unwrap(),
                 ),
+                network_version: None,
             },
         ),
         (
@@ -193,6 +215,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
             HeightInfo {
                 epoch: LIGHTNING_EPOCH + LIGHTNING_ROLLOVER_PERIOD,
                 bundle: None,
+                network_version: None,
             },
         ),
         (
@@ -203,6 +226,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
                     Cid::try_from("bafy2bzacedrunxfqta5skb7q7x32lnp4efz2oq7fn226ffm7fu5iqs62jkmvs")
                         .unwrap(),
                 ),
+                network_version: None,
             },
         ),
         (
@@ -213,6 +237,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
                     Cid::try_from("bafy2bzacebl4w5ptfvuw6746w7ev562idkbf5ppq72e6zub22435ws2rukzru")
                         .unwrap(),
                 ),
+                network_version: None,
             },
         ),
         (
@@ -223,6 +248,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
                     Cid::try_from("bafy2bzacednzb3pkrfnbfhmoqtb3bc6dgvxszpqklf3qcc7qzcage4ewzxsca")
                         .unwrap(),
                 ),
+                network_version: None,
             },
         ),
         (
@@ -233,21 +259,22 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
                     Cid::try_from("bafy2bzacea4firkyvt2zzdwqjrws5pyeluaesh6uaid246tommayr4337xpmi")
                         .unwrap(),
                 ),
+                network_version: None,
             },
         ),
     ])
 });
 
-pub(super) static DRAND_SCHEDULE: Lazy<[DrandPoint<'static>; 2]> = Lazy::new(|| {
+pub(super) static DRAND_SCHEDULE: Lazy<[DrandPoint; 2]> = Lazy::new(|| {
     [
         DrandPoint {
             height: 0,
-            config: &DRAND_MAINNET,
+            config: DRAND_MAINNET.clone(),
         },
         DrandPoint {
             height: get_upgrade_height_from_env("FOREST_DRAND_QUICKNET_HEIGHT")
                 .unwrap_or(HEIGHT_INFOS.get(&Height::Dragon).unwrap().epoch + 120),
-            config: &DRAND_QUICKNET,
+            config: DRAND_QUICKNET.clone(),
         },
     ]
 });
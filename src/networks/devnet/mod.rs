@@ -37,6 +37,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
             HeightInfo {
                 epoch: get_upgrade_height_from_env("FOREST_BREEZE_HEIGHT").unwrap_or(-50),
                 bundle: None,
+                network_version: None,
             },
         ),
         (
@@ -44,6 +45,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
             HeightInfo {
                 epoch: get_upgrade_height_from_env("FOREST_SMOKE_HEIGHT").unwrap_or(-2),
                 bundle: None,
+                network_version: None,
             },
         ),
         (
@@ -51,6 +53,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
             HeightInfo {
                 epoch: get_upgrade_height_from_env("FOREST_IGNITION_HEIGHT").unwrap_or(-3),
                 bundle: None,
+                network_version: None,
             },
         ),
         (
@@ -58,6 +61,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
             HeightInfo {
                 epoch: get_upgrade_height_from_env("FOREST_ACTORSV2_HEIGHT").unwrap_or(-3),
                 bundle: None,
+                network_version: None,
             },
         ),
         (
@@ -65,6 +69,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
             HeightInfo {
                 epoch: get_upgrade_height_from_env("FOREST_LIFTOFF_HEIGHT").unwrap_or(-6),
                 bundle: None,
+                network_version: None,
             },
         ),
         (
@@ -72,6 +77,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
             HeightInfo {
                 epoch: get_upgrade_height_from_env("FOREST_CALICO_HEIGHT").unwrap_or(-9),
                 bundle: None,
+                network_version: None,
             },
         ),
         (
@@ -82,6 +88,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
                     Cid::try_from("bafy2bzacedozk3jh2j4nobqotkbofodq4chbrabioxbfrygpldgoxs3zwgggk")
                         .unwrap(),
                 ),
+                network_version: None,
             },
         ),
         (
@@ -92,6 +99,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
                     Cid::try_from("bafy2bzacebzz376j5kizfck56366kdz5aut6ktqrvqbi3efa2d4l2o2m653ts")
                         .unwrap(),
                 ),
+                network_version: None,
             },
         ),
         (
@@ -102,6 +110,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
                     Cid::try_from("bafy2bzaceay35go4xbjb45km6o46e5bib3bi46panhovcbedrynzwmm3drr4i")
                         .unwrap(),
                 ),
+                network_version: None,
             },
         ),
         (
@@ -109,6 +118,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
             HeightInfo {
                 epoch: get_upgrade_height_from_env("FOREST_THUNDER_HEIGHT").unwrap_or(-23),
                 bundle: None,
+                network_version: None,
             },
         ),
         (
@@ -119,6 +129,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
                     Cid::try_from("bafy2bzaceasjdukhhyjbegpli247vbf5h64f7uvxhhebdihuqsj2mwisdwa6o")
                         .unwrap(),
                 ),
+                network_version: None,
             },
         ),
         (
@@ -129,20 +140,21 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
                     Cid::try_from("bafy2bzacecn7uxgehrqbcs462ktl2h23u23cmduy2etqj6xrd6tkkja56fna4")
                         .unwrap(),
                 ),
+                network_version: None,
             },
         ),
     ])
 });
 
-pub(super) static DRAND_SCHEDULE: Lazy<[DrandPoint<'static>; 2]> = Lazy::new(|| {
+pub(super) static DRAND_SCHEDULE: Lazy<[DrandPoint; 2]> = Lazy::new(|| {
     [
         DrandPoint {
             height: 0,
-            config: &DRAND_MAINNET,
+            config: DRAND_MAINNET.clone(),
         },
         DrandPoint {
             height: get_upgrade_height_from_env("FOREST_DRAND_QUICKNET_HEIGHT").unwrap_or(i64::MAX),
-            config: &DRAND_QUICKNET,
+            config: DRAND_QUICKNET.clone(),
         },
     ]
 });
@@ -23,8 +23,10 @@ pub static GENESIS_CID: Lazy<Cid> = Lazy::new(|| {
 });
 pub const GENESIS_NETWORK_VERSION: NetworkVersion = NetworkVersion::V0;
 
-pub static DEFAULT_BOOTSTRAP: Lazy<Vec<Multiaddr>> =
-    Lazy::new(|| parse_bootstrap_peers(include_str!("../../../build/bootstrap/mainnet")));
+pub static DEFAULT_BOOTSTRAP: Lazy<Vec<Multiaddr>> = Lazy::new(|| {
+    parse_bootstrap_peers(include_str!("../../../build/bootstrap/mainnet"))
+        .expect("built-in bootstrap peers must parse")
+});
 
 // The rollover period is the duration between nv19 and nv20 which both old
 // proofs (v1) and the new proofs (v1_1) proofs will be accepted by the
@@ -42,6 +44,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
             HeightInfo {
                 epoch: 41_280,
                 bundle: None,
+                network_version: None,
             },
         ),
         (
@@ -49,6 +52,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
             HeightInfo {
                 epoch: SMOKE_HEIGHT,
                 bundle: None,
+                network_version: None,
             },
         ),
         (
@@ -56,6 +60,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
             HeightInfo {
                 epoch: 94_000,
                 bundle: None,
+                network_version: None,
             },
         ),
         (
@@ -63,6 +68,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
             HeightInfo {
                 epoch: 138_720,
                 bundle: None,
+                network_version: None,
             },
         ),
         (
@@ -70,6 +76,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
             HeightInfo {
                 epoch: 140_760,
                 bundle: None,
+                network_version: None,
             },
         ),
         (
@@ -77,6 +84,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
             HeightInfo {
                 epoch: 148_888,
                 bundle: None,
+                network_version: None,
             },
         ),
         (
@@ -84,6 +92,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
             HeightInfo {
                 epoch: 170_000,
                 bundle: None,
+                network_version: None,
             },
         ),
         (
@@ -91,6 +100,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
             HeightInfo {
                 epoch: 265_200,
                 bundle: None,
+                network_version: None,
             },
         ),
         (
@@ -98,6 +108,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
             HeightInfo {
                 epoch: 272_400,
                 bundle: None,
+                network_version: None,
             },
         ),
         (
@@ -105,6 +116,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
             HeightInfo {
                 epoch: 336_458,
                 bundle: None,
+                network_version: None,
             },
         ),
         (
@@ -112,6 +124,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
             HeightInfo {
                 epoch: 550_321,
                 bundle: None,
+                network_version: None,
             },
         ),
         (
@@ -119,6 +132,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
             HeightInfo {
                 epoch: 665_280,
                 bundle: None,
+                network_version: None,
             },
         ),
         (
@@ -126,6 +140,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
             HeightInfo {
                 epoch: 712_320,
                 bundle: None,
+                network_version: None,
             },
         ),
         (
@@ -133,6 +148,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
             HeightInfo {
                 epoch: 892_800,
                 bundle: None,
+                network_version: None,
             },
         ),
         (
@@ -140,6 +156,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
             HeightInfo {
                 epoch: 1_231_620,
                 bundle: None,
+                network_version: None,
             },
         ),
         (
@@ -147,6 +164,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
             HeightInfo {
                 epoch: 1_594_680,
                 bundle: None,
+                network_version: None,
             },
         ),
         (
@@ -154,6 +172,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
             HeightInfo {
                 epoch: 1_960_320,
                 bundle: None,
+                network_version: None,
             },
         ),
         (
@@ -164,6 +183,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
                     Cid::try_from("bafy2bzaceb6j6666h36xnhksu3ww4kxb6e25niayfgkdnifaqi6m6ooc66i6i")
                         .unwrap(),
                 ),
+                network_version: None,
             },
         ),
         (
@@ -174,6 +194,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
                     Cid::try_from("bafy2bzacecsuyf7mmvrhkx2evng5gnz5canlnz2fdlzu2lvcgptiq2pzuovos")
                         .unwrap(),
                 ),
+                network_version: None,
             },
         ),
         (
@@ -184,6 +205,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
                     Cid::try_from("bafy2bzacecnhaiwcrpyjvzl4uv4q3jzoif26okl3m66q3cijp3dfwlcxwztwo")
                         .unwrap(),
                 ),
+                network_version: None,
             },
         ),
         (
@@ -191,6 +213,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
             HeightInfo {
                 epoch: 2_809_800 + LIGHTNING_ROLLOVER_PERIOD,
                 bundle: None,
+                network_version: None,
             },
         ),
         (
@@ -201,6 +224,7 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
                     Cid::try_from("bafy2bzaceapkgfggvxyllnmuogtwasmsv5qi2qzhc2aybockd6kag2g5lzaio")
                         .unwrap(),
                 ),
+                network_version: None,
             },
         ),
         (
@@ -212,20 +236,21 @@ pub static HEIGHT_INFOS: Lazy<HashMap<Height, HeightInfo>> = Lazy::new(|| {
                     Cid::try_from("bafy2bzacecdhvfmtirtojwhw2tyciu4jkbpsbk5g53oe24br27oy62sn4dc4e")
                         .unwrap(),
                 ),
+                network_version: None,
             },
         ),
     ])
 });
 
-pub(super) static DRAND_SCHEDULE: Lazy<[DrandPoint<'static>; 3]> = Lazy::new(|| {
+pub(super) static DRAND_SCHEDULE: Lazy<[DrandPoint; 3]> = Lazy::new(|| {
     [
         DrandPoint {
             height: 0,
-            config: &DRAND_INCENTINET,
+            config: DRAND_INCENTINET.clone(),
         },
         DrandPoint {
             height: SMOKE_HEIGHT,
-            config: &DRAND_MAINNET,
+            config: DRAND_MAINNET.clone(),
         },
         DrandPoint {
             // 2024-04-11T15:00:00Z - 120 epochs after the Dragon upgrade
@@ -234,7 +259,7 @@ pub(super) static DRAND_SCHEDULE: Lazy<[DrandPoint<'static>; 3]> = Lazy::new(||
                 .expect("Dragon height must be defined")
                 .epoch
                 + 120,
-            config: &DRAND_QUICKNET,
+            config: DRAND_QUICKNET.clone(),
         },
     ]
 });
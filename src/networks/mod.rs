@@ -1,41 +1,50 @@
 // Copyright 2019-2024 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use std::{fmt::Display, str::FromStr};
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
 use ahash::HashMap;
+use anyhow::Context as _;
 use cid::Cid;
 use fil_actors_shared::v10::runtime::Policy;
 use itertools::Itertools;
 use libp2p::Multiaddr;
-use once_cell::sync::Lazy;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
 use strum_macros::Display;
-use tracing::warn;
+use tracing::{info, warn};
 
 use crate::beacon::{BeaconPoint, BeaconSchedule, DrandBeacon, DrandConfig};
-use crate::db::SettingsStore;
+use crate::db::{setting_keys::DEVNET_GENESIS_KEY, SettingsStore};
 use crate::make_butterfly_policy;
 use crate::shim::clock::{ChainEpoch, EPOCH_DURATION_SECONDS};
 use crate::shim::sector::{RegisteredPoStProofV3, RegisteredSealProofV3};
 use crate::shim::version::NetworkVersion;
 
 mod actors_bundle;
-pub use actors_bundle::{generate_actor_bundle, ActorBundleInfo, ACTOR_BUNDLES};
+pub use actors_bundle::{
+    generate_actor_bundle, verify_actor_bundles, ActorBundleInfo, ActorBundleOptions,
+    ActorBundleOverride, ACTOR_BUNDLES,
+};
 
 mod drand;
 
+mod policy;
+pub use policy::PolicyOverrides;
+
 pub mod butterflynet;
 pub mod calibnet;
 pub mod devnet;
 pub mod mainnet;
 
-/// Newest network version for all networks
-pub const NEWEST_NETWORK_VERSION: NetworkVersion = NetworkVersion::V17;
-
 /// Forest builtin `filecoin` network chains. In general only `mainnet` and its
 /// chain information should be considered stable.
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema, Default)]
 #[cfg_attr(test, derive(derive_quickcheck_arbitrary::Arbitrary))]
 #[serde(tag = "type", content = "name", rename_all = "lowercase")]
 pub enum NetworkChain {
@@ -85,12 +94,29 @@ impl NetworkChain {
         }
     }
 
-    /// Returns [`NetworkChain::Calibnet`] or [`NetworkChain::Mainnet`] if `cid`
-    /// is the hard-coded genesis CID for either of those networks.
+    /// Returns the built-in network whose hard-coded genesis CID is `cid`, else
+    /// the network of `chain_config` if its configured genesis CID is `cid` or
+    /// it is a devnet.
     ///
-    /// Else returns a [`NetworkChain::Devnet`] with a placeholder name.
-    pub fn from_genesis_or_devnet_placeholder(cid: &Cid) -> Self {
-        Self::from_genesis(cid).unwrap_or(Self::Devnet(String::from("devnet")))
+    /// Else returns a [`NetworkChain::Devnet`] named by `devnet_name`, e.g.
+    /// from the network name of the genesis state.
+    pub fn from_genesis_or_devnet(
+        cid: &Cid,
+        chain_config: Option<&ChainConfig>,
+        devnet_name: impl FnOnce() -> anyhow::Result<String>,
+    ) -> anyhow::Result<Self> {
+        if let Some(network) = Self::from_genesis(cid) {
+            return Ok(network);
+        }
+        match chain_config {
+            Some(config)
+                if config.genesis_cid.as_deref() == Some(&cid.to_string())
+                    || matches!(config.network, Self::Devnet(_)) =>
+            {
+                Ok(config.network.clone())
+            }
+            _ => Ok(Self::Devnet(devnet_name()?)),
+        }
     }
 
     pub fn is_testnet(&self) -> bool {
@@ -98,8 +124,22 @@ impl NetworkChain {
     }
 }
 
-/// Defines the meaningful heights of the protocol.
-#[derive(Debug, Display, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+/// Defines the meaningful heights of the protocol, in the order of their
+/// upgrades.
+#[derive(
+    Debug,
+    Display,
+    Clone,
+    Copy,
+    Serialize,
+    Deserialize,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    JsonSchema,
+)]
 #[cfg_attr(test, derive(derive_quickcheck_arbitrary::Arbitrary))]
 pub enum Height {
     Breeze,
@@ -167,17 +207,60 @@ impl From<Height> for NetworkVersion {
     }
 }
 
+/// Heights every chain configuration must define.
+pub const MANDATORY_HEIGHTS: [Height; 6] = [
+    Height::Breeze,
+    Height::Smoke,
+    Height::Ignition,
+    Height::ActorsV2,
+    Height::Liftoff,
+    Height::Calico,
+];
+
+/// Upgrades that bring in a new version of the built-in actors, by actors
+/// version, in the order they happen.
+const ACTOR_UPGRADES: [(u64, &[Height]); 5] = [
+    (9, &[Height::Shark]),
+    (10, &[Height::Hygge]),
+    (11, &[Height::Lightning]),
+    (
+        12,
+        &[
+            Height::Watermelon,
+            Height::WatermelonFix,
+            Height::WatermelonFix2,
+        ],
+    ),
+    (13, &[Height::Dragon]),
+];
+
+#[serde_as]
 #[derive(Default, Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 #[cfg_attr(test, derive(derive_quickcheck_arbitrary::Arbitrary))]
 pub struct HeightInfo {
     pub epoch: ChainEpoch,
+    #[serde_as(as = "Option<DisplayFromStr>")]
     pub bundle: Option<Cid>,
+    /// Network version the upgrade brings in, when Forest does not know it
+    /// yet.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network_version: Option<NetworkVersion>,
+}
+
+impl HeightInfo {
+    /// The configured network version of the upgrade to `height`, else the one
+    /// Forest associates with it.
+    pub fn network_version(&self, height: Height) -> NetworkVersion {
+        self.network_version.unwrap_or_else(|| height.into())
+    }
 }
 
-#[derive(Clone)]
-struct DrandPoint<'a> {
+/// `drand` network the beacon of the chain switches to at a given epoch.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DrandPoint {
     pub height: ChainEpoch,
-    pub config: &'a Lazy<DrandConfig<'a>>,
+    #[serde(flatten)]
+    pub config: DrandConfig<'static>,
 }
 
 /// Defines all network configuration parameters.
@@ -187,6 +270,9 @@ struct DrandPoint<'a> {
 pub struct ChainConfig {
     pub network: NetworkChain,
     pub genesis_cid: Option<String>,
+    /// Genesis CAR file of the network, for networks whose genesis is not
+    /// bundled with Forest.
+    pub genesis_file: Option<PathBuf>,
     #[cfg_attr(test, arbitrary(gen(
         |g: &mut quickcheck::Gen| {
             let addr = std::net::Ipv4Addr::arbitrary(&mut *g);
@@ -203,6 +289,9 @@ pub struct ChainConfig {
     #[serde(default = "default_policy")]
     pub policy: Policy,
     pub eth_chain_id: u32,
+    /// `drand` networks of the beacon, by the epoch they take over from.
+    #[cfg_attr(test, arbitrary(gen(|_g| mainnet::DRAND_SCHEDULE.to_vec())))]
+    pub drand_schedule: Vec<DrandPoint>,
 }
 
 impl ChainConfig {
@@ -211,6 +300,7 @@ impl ChainConfig {
         Self {
             network: NetworkChain::Mainnet,
             genesis_cid: Some(GENESIS_CID.to_string()),
+            genesis_file: None,
             bootstrap_peers: DEFAULT_BOOTSTRAP.clone(),
             block_delay_secs: EPOCH_DURATION_SECONDS as u32,
             propagation_delay_secs: 10,
@@ -218,6 +308,7 @@ impl ChainConfig {
             height_infos: HEIGHT_INFOS.clone(),
             policy: Policy::mainnet(),
             eth_chain_id: ETH_CHAIN_ID as u32,
+            drand_schedule: DRAND_SCHEDULE.to_vec(),
         }
     }
 
@@ -226,6 +317,7 @@ impl ChainConfig {
         Self {
             network: NetworkChain::Calibnet,
             genesis_cid: Some(GENESIS_CID.to_string()),
+            genesis_file: None,
             bootstrap_peers: DEFAULT_BOOTSTRAP.clone(),
             block_delay_secs: EPOCH_DURATION_SECONDS as u32,
             propagation_delay_secs: 10,
@@ -233,6 +325,7 @@ impl ChainConfig {
             height_infos: HEIGHT_INFOS.clone(),
             policy: Policy::calibnet(),
             eth_chain_id: ETH_CHAIN_ID as u32,
+            drand_schedule: DRAND_SCHEDULE.to_vec(),
         }
     }
 
@@ -259,6 +352,7 @@ impl ChainConfig {
         Self {
             network: NetworkChain::Devnet("devnet".to_string()),
             genesis_cid: None,
+            genesis_file: None,
             bootstrap_peers: Vec::new(),
             block_delay_secs: 4,
             propagation_delay_secs: 1,
@@ -266,6 +360,7 @@ impl ChainConfig {
             height_infos: HEIGHT_INFOS.clone(),
             policy,
             eth_chain_id: ETH_CHAIN_ID as u32,
+            drand_schedule: DRAND_SCHEDULE.to_vec(),
         }
     }
 
@@ -275,6 +370,7 @@ impl ChainConfig {
         Self {
             network: NetworkChain::Butterflynet,
             genesis_cid: Some(GENESIS_CID.to_string()),
+            genesis_file: None,
             bootstrap_peers: DEFAULT_BOOTSTRAP.clone(),
             block_delay_secs: EPOCH_DURATION_SECONDS as u32,
             propagation_delay_secs: 6,
@@ -282,6 +378,7 @@ impl ChainConfig {
             height_infos: HEIGHT_INFOS.clone(),
             policy: make_butterfly_policy!(v10),
             eth_chain_id: ETH_CHAIN_ID as u32,
+            drand_schedule: DRAND_SCHEDULE.to_vec(),
         }
     }
 
@@ -297,35 +394,324 @@ impl ChainConfig {
         }
     }
 
-    /// Returns the network version at the given epoch.
-    /// If the epoch is before the first upgrade, the genesis network version is returned.
+    /// Loads a chain specification from a TOML or JSON file, depending on its
+    /// extension.
+    ///
+    /// The values of the file override those of the built-in configuration of
+    /// its `network`, so that a devnet spec only needs to list what differs
+    /// from [`ChainConfig::devnet`]. A relative `genesis_file` is resolved
+    /// against the directory of the spec.
+    pub fn from_file(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let config = Self::from_file_unvalidated(path)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Loads a chain specification like [`ChainConfig::from_file`], without
+    /// validating it.
+    pub fn from_file_unvalidated(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read chain spec {}", path.display()))?;
+        let spec: serde_json::Value = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => serde_json::from_str(&content)?,
+            Some("toml") => toml::from_str(&content)?,
+            _ => anyhow::bail!(
+                "chain spec {} must be a `.toml` or `.json` file",
+                path.display()
+            ),
+        };
+        let network: NetworkChain = serde_json::from_value(
+            spec.get("network")
+                .cloned()
+                .context("chain spec has no `network`")?,
+        )?;
+        let mut config = serde_json::to_value(Self::from_chain(&network))?;
+        merge_json(&mut config, spec);
+        let mut config: Self = serde_json::from_value(config)
+            .with_context(|| format!("invalid chain spec {}", path.display()))?;
+        if let (Some(genesis_file), Some(dir)) = (&config.genesis_file, path.parent()) {
+            config.genesis_file = Some(dir.join(genesis_file));
+        }
+        Ok(config)
+    }
+
+    /// Checks the configuration for inconsistencies, see
+    /// [`ChainConfig::violations`].
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let violations = self.violations();
+        anyhow::ensure!(
+            violations.is_empty(),
+            "invalid {} chain configuration: {}",
+            self.network,
+            violations.join("; ")
+        );
+        Ok(())
+    }
+
+    /// Returns the inconsistencies of the configuration:
+    /// - missing mandatory heights,
+    /// - upgrades scheduled at the same epoch, or out of order. Negative
+    ///   epochs mark upgrades that are already active at genesis, so they are
+    ///   not scheduled,
+    /// - upgrades to new actors since Hygge without an actor bundle,
+    /// - a genesis that is not that of the network, for public networks,
+    /// - missing bootstrap peers, for public networks,
+    /// - a `drand` schedule that does not start at genesis, or out of order,
+    /// - inconsistent policies, for devnets.
+    pub fn violations(&self) -> Vec<String> {
+        let mut violations = vec![];
+        for height in MANDATORY_HEIGHTS {
+            if !self.height_infos.contains_key(&height) {
+                violations.push(format!("mandatory height {height} is missing"));
+            }
+        }
+
+        let mut scheduled = HashMap::default();
+        for (height, info) in self
+            .height_infos
+            .iter()
+            .filter(|(_, info)| info.epoch >= 0)
+            .sorted_by_key(|(height, _)| height.to_string())
+        {
+            if let Some(other) = scheduled.insert(info.epoch, height) {
+                violations.push(format!(
+                    "upgrades {other} and {height} are both scheduled at epoch {}",
+                    info.epoch
+                ));
+            }
+        }
+        for ((previous, previous_info), (height, info)) in self
+            .height_infos
+            .iter()
+            .filter(|(_, info)| info.epoch >= 0)
+            .sorted_by_key(|(height, _)| **height)
+            .tuple_windows()
+        {
+            if info.epoch < previous_info.epoch {
+                violations.push(format!(
+                    "upgrade {height} at epoch {} precedes the {previous} upgrade at epoch {}",
+                    info.epoch, previous_info.epoch
+                ));
+            }
+            let (previous_version, version) = (
+                previous_info.network_version(*previous),
+                info.network_version(*height),
+            );
+            if version < previous_version {
+                violations.push(format!(
+                    "upgrade {height} brings network version {version} back from {previous_version} of the {previous} upgrade"
+                ));
+            }
+        }
+
+        for height in ACTOR_UPGRADES
+            .iter()
+            .flat_map(|(_, heights)| heights.iter())
+            .filter(|height| **height >= Height::Hygge)
+        {
+            if matches!(self.height_infos.get(height), Some(info) if info.bundle.is_none()) {
+                violations.push(format!("upgrade {height} has no actor bundle"));
+            }
+        }
+
+        match self.drand_schedule.first() {
+            None => violations.push("the drand schedule is empty".into()),
+            Some(first) if first.height != 0 => violations.push(format!(
+                "the drand schedule starts at epoch {} instead of genesis",
+                first.height
+            )),
+            Some(_) => {}
+        }
+        for (previous, point) in self.drand_schedule.iter().tuple_windows() {
+            if point.height <= previous.height {
+                violations.push(format!(
+                    "drand network {} at epoch {} does not come after the one at epoch {}",
+                    point.config.chain_info.hash, point.height, previous.height
+                ));
+            }
+        }
+
+        let genesis_cid = match self.network {
+            NetworkChain::Mainnet => Some(&*mainnet::GENESIS_CID),
+            NetworkChain::Calibnet => Some(&*calibnet::GENESIS_CID),
+            NetworkChain::Butterflynet => Some(&*butterflynet::GENESIS_CID),
+            NetworkChain::Devnet(_) => None,
+        };
+        if let Some(genesis_cid) = genesis_cid {
+            if self.genesis_cid != Some(genesis_cid.to_string()) {
+                violations.push(format!(
+                    "genesis {} is not the {} genesis {genesis_cid}",
+                    self.genesis_cid.as_deref().unwrap_or("(none)"),
+                    self.network
+                ));
+            }
+            if self.bootstrap_peers.is_empty() {
+                violations.push(format!("{} has no bootstrap peers", self.network));
+            }
+        } else {
+            violations.extend(self.devnet_policy_violations());
+        }
+        violations
+    }
+
+    fn devnet_policy_violations(&self) -> Vec<String> {
+        let mut violations = vec![];
+        if self.block_delay_secs == 0 {
+            violations.push("the block delay is zero".into());
+        }
+        if self.policy.chain_finality <= 0 {
+            violations.push(format!(
+                "the chain finality {} is not positive",
+                self.policy.chain_finality
+            ));
+        }
+        if self.policy.valid_pre_commit_proof_type.is_empty() {
+            violations.push("no seal proof type is allowed".into());
+        }
+        if self.policy.valid_post_proof_type.is_empty() {
+            violations.push("no PoSt proof type is allowed".into());
+        }
+        for seal in self
+            .policy
+            .valid_pre_commit_proof_type
+            .iter()
+            .sorted_by_key(|seal| format!("{seal:?}"))
+        {
+            let has_post = seal.sector_size().is_ok_and(|size| {
+                self.policy
+                    .valid_post_proof_type
+                    .iter()
+                    .any(|post| post.sector_size() == Ok(size))
+            });
+            if !has_post {
+                violations.push(format!(
+                    "seal proof type {seal:?} is allowed, but no PoSt proof type of its sector size"
+                ));
+            }
+        }
+        violations
+    }
+
+    /// Moves the upgrades of `overrides` to the given epochs. Overrides must
+    /// keep the upgrades in order, though upgrades at negative epochs, which
+    /// are active at genesis, may come in any order.
+    pub fn with_height_overrides(
+        mut self,
+        overrides: &HashMap<Height, ChainEpoch>,
+    ) -> anyhow::Result<Self> {
+        for (height, epoch) in overrides.iter().sorted() {
+            warn!("Overriding the epoch of the {height} upgrade with {epoch}");
+            self.height_infos.entry(*height).or_default().epoch = *epoch;
+        }
+        for (height, epoch) in overrides.iter().sorted() {
+            let at_genesis = |epoch: ChainEpoch| epoch.max(-1);
+            for (other, info) in self.height_infos.iter().sorted_by_key(|(other, _)| **other) {
+                let reordered = match other.cmp(height) {
+                    std::cmp::Ordering::Less => at_genesis(info.epoch) > at_genesis(*epoch),
+                    std::cmp::Ordering::Greater => at_genesis(info.epoch) < at_genesis(*epoch),
+                    std::cmp::Ordering::Equal => false,
+                };
+                anyhow::ensure!(
+                    !reordered,
+                    "overriding the {height} upgrade with epoch {epoch} moves it across the {other} upgrade at epoch {}",
+                    info.epoch
+                );
+            }
+        }
+        Ok(self)
+    }
+
+    /// Applies `overrides` to the policy of the network. Mainnet refuses
+    /// them, its policy is that of the actors.
+    pub fn with_policy_overrides(mut self, overrides: &PolicyOverrides) -> anyhow::Result<Self> {
+        if overrides.is_empty() {
+            return Ok(self);
+        }
+        anyhow::ensure!(
+            self.network != NetworkChain::Mainnet,
+            "policy overrides are not allowed on mainnet"
+        );
+        overrides.apply(&mut self.policy);
+        Ok(self)
+    }
+
+    /// Registers `manifest` as the bundle of the latest upgrade of the network
+    /// to the given actors version, and returns that upgrade.
+    pub fn set_actor_bundle(
+        &mut self,
+        actors_version: u64,
+        manifest: Cid,
+    ) -> anyhow::Result<Height> {
+        let heights = ACTOR_UPGRADES
+            .iter()
+            .find_map(|(version, heights)| (*version == actors_version).then_some(*heights))
+            .with_context(|| format!("actors version {actors_version} has no upgrade"))?;
+        let height = *heights
+            .iter()
+            .rev()
+            .find(|height| self.height_infos.contains_key(height))
+            .with_context(|| {
+                format!(
+                    "{} has no upgrade to actors version {actors_version}",
+                    self.network
+                )
+            })?;
+        self.height_infos.entry(height).or_default().bundle = Some(manifest);
+        Ok(height)
+    }
+
+    /// Network version of the chain at `epoch`, which is that of the last
+    /// upgrade before it, or the genesis network version before the first
+    /// upgrade. Upgrades may configure a network version Forest does not know
+    /// yet.
     pub fn network_version(&self, epoch: ChainEpoch) -> NetworkVersion {
-        self.height_infos
+        let mut upgrades = self
+            .height_infos
             .iter()
-            .sorted_by_key(|(_, info)| info.epoch)
+            .sorted_by_key(|(height, info)| (info.epoch, **height))
             .rev()
-            .find(|(_, info)| epoch > info.epoch)
-            .map(|(height, _)| NetworkVersion::from(*height))
-            .unwrap_or(self.genesis_network_version())
+            .peekable();
+        let last = upgrades.peek().map(|(height, info)| (**height, info.epoch));
+        match upgrades.find(|(_, info)| epoch > info.epoch) {
+            Some((height, info)) => {
+                if last == Some((*height, info.epoch)) {
+                    static PAST_LAST_UPGRADE: std::sync::Once = std::sync::Once::new();
+                    PAST_LAST_UPGRADE.call_once(|| {
+                        info!(
+                            "Epoch {epoch} is past the last configured upgrade {height} at epoch {}, network version {} is assumed from then on",
+                            info.epoch,
+                            info.network_version(*height)
+                        )
+                    });
+                }
+                info.network_version(*height)
+            }
+            None => self.genesis_network_version(),
+        }
+        .max(self.genesis_network)
+    }
+
+    /// Newest network version of the chain, that of its last upgrade.
+    pub fn newest_network_version(&self) -> NetworkVersion {
+        self.height_infos
+            .iter()
+            .map(|(height, info)| info.network_version(*height))
+            .max()
+            .unwrap_or(self.genesis_network)
             .max(self.genesis_network)
     }
 
     pub fn get_beacon_schedule(&self, genesis_ts: u64) -> BeaconSchedule {
-        let ds_iter = match self.network {
-            NetworkChain::Mainnet => mainnet::DRAND_SCHEDULE.iter(),
-            NetworkChain::Calibnet => calibnet::DRAND_SCHEDULE.iter(),
-            NetworkChain::Butterflynet => butterflynet::DRAND_SCHEDULE.iter(),
-            NetworkChain::Devnet(_) => devnet::DRAND_SCHEDULE.iter(),
-        };
-
         BeaconSchedule(
-            ds_iter
+            self.drand_schedule
+                .iter()
                 .map(|dc| BeaconPoint {
                     height: dc.height,
                     beacon: Box::new(DrandBeacon::new(
                         genesis_ts,
                         self.block_delay_secs as u64,
-                        dc.config,
+                        &dc.config,
                     )),
                 })
                 .collect(),
@@ -347,16 +733,30 @@ impl ChainConfig {
             .unwrap_or(0)
     }
 
-    pub async fn genesis_bytes<DB: SettingsStore>(
-        &self,
-        db: &DB,
-    ) -> anyhow::Result<Option<Vec<u8>>> {
-        Ok(match self.network {
-            NetworkChain::Mainnet => Some(mainnet::DEFAULT_GENESIS.to_vec()),
-            NetworkChain::Calibnet => Some(calibnet::DEFAULT_GENESIS.to_vec()),
+    /// Returns the genesis CAR file of the network, read from
+    /// [`ChainConfig::genesis_file`] if set. A devnet or butterflynet genesis
+    /// read from a file is persisted in `db`, so that later runs do not need
+    /// the file.
+    pub async fn genesis_bytes<DB: SettingsStore>(&self, db: &DB) -> anyhow::Result<Vec<u8>> {
+        if let Some(genesis_file) = &self.genesis_file {
+            let genesis = tokio::fs::read(genesis_file)
+                .await
+                .with_context(|| format!("couldn't read genesis {}", genesis_file.display()))?;
+            match self.network {
+                NetworkChain::Devnet(_) => db.write_bin(DEVNET_GENESIS_KEY, &genesis)?,
+                NetworkChain::Butterflynet => butterflynet::save_genesis(db, &genesis).await?,
+                NetworkChain::Mainnet | NetworkChain::Calibnet => {}
+            }
+            return Ok(genesis);
+        }
+        Ok(match &self.network {
+            NetworkChain::Mainnet => mainnet::DEFAULT_GENESIS.to_vec(),
+            NetworkChain::Calibnet => calibnet::DEFAULT_GENESIS.to_vec(),
             // Butterflynet genesis is not hardcoded in the binary, for size reasons.
-            NetworkChain::Butterflynet => Some(butterflynet::fetch_genesis(db).await?),
-            NetworkChain::Devnet(_) => None,
+            NetworkChain::Butterflynet => butterflynet::fetch_genesis(db).await?,
+            NetworkChain::Devnet(name) => db.read_bin(DEVNET_GENESIS_KEY)?.with_context(|| {
+                format!("no genesis for devnet {name}, it has to be provided with a genesis file")
+            })?,
         })
     }
 
@@ -381,12 +781,34 @@ fn default_policy() -> Policy {
     Policy::mainnet()
 }
 
-pub(crate) fn parse_bootstrap_peers(bootstrap_peer_list: &str) -> Vec<Multiaddr> {
+/// Recursively overwrites the fields of `base` with those of `overrides`.
+fn merge_json(base: &mut serde_json::Value, overrides: serde_json::Value) {
+    match (base, overrides) {
+        (serde_json::Value::Object(base), serde_json::Value::Object(overrides)) => {
+            for (key, value) in overrides {
+                match base.get_mut(&key) {
+                    Some(field) => merge_json(field, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overrides) => *base = overrides,
+    }
+}
+
+/// Parses a list of bootstrap peers, one multi-address per line. Errors point
+/// at the first malformed line.
+pub(crate) fn parse_bootstrap_peers(bootstrap_peer_list: &str) -> anyhow::Result<Vec<Multiaddr>> {
     bootstrap_peer_list
-        .split('\n')
-        .filter(|s| !s.is_empty())
-        .map(|s| {
-            Multiaddr::from_str(s).unwrap_or_else(|e| panic!("invalid bootstrap peer {s}: {e}"))
+        .lines()
+        .enumerate()
+        .map(|(ix, line)| (ix + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(line_number, line)| {
+            Multiaddr::from_str(line)
+                .with_context(|| format!("line {line_number}: invalid bootstrap peer {line}"))
         })
         .collect()
 }
@@ -421,12 +843,23 @@ mod tests {
     use super::*;
 
     fn heights_are_present(height_infos: &HashMap<Height, HeightInfo>) {
-        assert!(height_infos.get(&Height::Breeze).is_some());
-        assert!(height_infos.get(&Height::Smoke).is_some());
-        assert!(height_infos.get(&Height::Ignition).is_some());
-        assert!(height_infos.get(&Height::ActorsV2).is_some());
-        assert!(height_infos.get(&Height::Liftoff).is_some());
-        assert!(height_infos.get(&Height::Calico).is_some());
+        for height in MANDATORY_HEIGHTS {
+            assert!(height_infos.get(&height).is_some(), "{height} is missing");
+        }
+    }
+
+    #[test]
+    fn test_parse_bootstrap_peers() {
+        let peers =
+            parse_bootstrap_peers("/dns4/a.example.com/tcp/1347\n\n  /ip4/127.0.0.1/tcp/1347  \n")
+                .unwrap();
+        assert_eq!(peers.len(), 2);
+
+        let err = parse_bootstrap_peers("/dns4/a.example.com/tcp/1347\n\nnot-an-addr").unwrap_err();
+        assert!(
+            format!("{err:#}").contains("line 3: invalid bootstrap peer not-an-addr"),
+            "{err:#}"
+        );
     }
 
     #[test]
@@ -449,6 +882,403 @@ mod tests {
         heights_are_present(&butterflynet::HEIGHT_INFOS);
     }
 
+    #[test]
+    fn test_builtin_configs_are_valid() {
+        for config in [
+            ChainConfig::mainnet(),
+            ChainConfig::calibnet(),
+            ChainConfig::devnet(),
+            ChainConfig::butterflynet(),
+        ] {
+            config.validate().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_from_genesis_or_devnet_precedence() {
+        use crate::utils::cid::CidCborExt as _;
+
+        let detect = |cid: &Cid, config: Option<&ChainConfig>| {
+            NetworkChain::from_genesis_or_devnet(cid, config, || Ok("placeholder".into())).unwrap()
+        };
+        let custom_cid = Cid::from_cbor_blake2b256(&"2k").unwrap();
+        let custom = ChainConfig {
+            network: NetworkChain::Devnet("2k".into()),
+            genesis_cid: Some(custom_cid.to_string()),
+            ..ChainConfig::devnet()
+        };
+
+        // Built-in networks are recognized whatever the configuration
+        assert_eq!(
+            detect(&calibnet::GENESIS_CID, Some(&custom)),
+            NetworkChain::Calibnet
+        );
+        let claims_calibnet = ChainConfig {
+            genesis_cid: Some(calibnet::GENESIS_CID.to_string()),
+            ..custom.clone()
+        };
+        assert_eq!(
+            detect(&calibnet::GENESIS_CID, Some(&claims_calibnet)),
+            NetworkChain::Calibnet
+        );
+        // Then the configured network
+        assert_eq!(
+            detect(&custom_cid, Some(&custom)),
+            NetworkChain::Devnet("2k".into())
+        );
+        // A configured devnet keeps its name whatever its genesis
+        let unpinned = ChainConfig {
+            genesis_cid: None,
+            ..custom.clone()
+        };
+        assert_eq!(
+            detect(&custom_cid, Some(&unpinned)),
+            NetworkChain::Devnet("2k".into())
+        );
+        // Else the placeholder
+        assert_eq!(
+            detect(&custom_cid, None),
+            NetworkChain::Devnet("placeholder".into())
+        );
+        assert_eq!(
+            detect(&custom_cid, Some(&ChainConfig::calibnet())),
+            NetworkChain::Devnet("placeholder".into())
+        );
+        // The placeholder is only computed when needed
+        NetworkChain::from_genesis_or_devnet(&custom_cid, Some(&custom), || {
+            panic!("the network is configured")
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_chain_spec_round_trip() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config = ChainConfig {
+            genesis_file: Some(temp_dir.path().join("genesis.car")),
+            // Replaces the whole default schedule
+            drand_schedule: vec![DrandPoint {
+                height: 0,
+                config: drand::DRAND_QUICKNET.clone(),
+            }],
+            ..ChainConfig::devnet()
+        };
+
+        let json = temp_dir.path().join("spec.json");
+        std::fs::write(&json, serde_json::to_string(&config).unwrap()).unwrap();
+        assert_eq!(ChainConfig::from_file(&json).unwrap(), config);
+
+        let toml = temp_dir.path().join("spec.toml");
+        std::fs::write(&toml, toml::to_string(&config).unwrap()).unwrap();
+        assert_eq!(ChainConfig::from_file(&toml).unwrap(), config);
+    }
+
+    #[test]
+    fn test_chain_spec_overrides() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("2k.toml");
+        std::fs::write(
+            &path,
+            r#"
+genesis_file = "genesis.car"
+block_delay_secs = 1
+eth_chain_id = 31415
+
+[network]
+type = "devnet"
+name = "2k"
+
+[height_infos.Thunder]
+epoch = 10
+bundle = "bafy2bzacedozk3jh2j4nobqotkbofodq4chbrabioxbfrygpldgoxs3zwgggk"
+
+[policy]
+pre_commit_challenge_delay = 5
+"#,
+        )
+        .unwrap();
+        let config = ChainConfig::from_file(&path).unwrap();
+
+        let mut expected = ChainConfig::from_chain(&NetworkChain::Devnet("2k".into()));
+        expected.genesis_file = Some(temp_dir.path().join("genesis.car"));
+        expected.block_delay_secs = 1;
+        expected.eth_chain_id = 31415;
+        expected.height_infos.insert(
+            Height::Thunder,
+            HeightInfo {
+                epoch: 10,
+                bundle: Some(
+                    "bafy2bzacedozk3jh2j4nobqotkbofodq4chbrabioxbfrygpldgoxs3zwgggk"
+                        .parse()
+                        .unwrap(),
+                ),
+                network_version: None,
+            },
+        );
+        expected.policy.pre_commit_challenge_delay = 5;
+        assert_eq!(config, expected);
+    }
+
+    #[test]
+    fn test_chain_spec_validation() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("spec.json");
+
+        let mut config = ChainConfig::devnet();
+        config.height_infos.get_mut(&Height::Thunder).unwrap().epoch = 10;
+        config
+            .height_infos
+            .get_mut(&Height::Watermelon)
+            .unwrap()
+            .epoch = 10;
+        std::fs::write(&path, serde_json::to_string(&config).unwrap()).unwrap();
+        let error = ChainConfig::from_file(&path).unwrap_err().to_string();
+        assert!(error.contains("both scheduled at epoch 10"), "{error}");
+
+        let mut config = ChainConfig::devnet();
+        config.height_infos.remove(&Height::Calico);
+        assert!(config.validate().is_err());
+
+        std::fs::write(&path, r#"{ "block_delay_secs": 1 }"#).unwrap();
+        assert!(ChainConfig::from_file(&path).is_err());
+
+        let path = temp_dir.path().join("spec.yaml");
+        std::fs::write(&path, "").unwrap();
+        assert!(ChainConfig::from_file(&path).is_err());
+    }
+
+    fn assert_violation(config: &ChainConfig, expected: &str) {
+        let violations = config.violations();
+        assert!(
+            violations.iter().any(|it| it.contains(expected)),
+            "{violations:?}"
+        );
+        assert!(config.validate().is_err());
+    }
+
+    // An upgrade Forest does not know the network version of yet
+    #[test]
+    fn test_network_version_from_config() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("2k.toml");
+        std::fs::write(
+            &path,
+            r#"
+[network]
+type = "devnet"
+name = "2k"
+
+[height_infos.Dragon]
+epoch = 100
+bundle = "bafy2bzacedozk3jh2j4nobqotkbofodq4chbrabioxbfrygpldgoxs3zwgggk"
+network_version = 23
+"#,
+        )
+        .unwrap();
+        let config = ChainConfig::from_file(&path).unwrap();
+
+        assert_eq!(config.network_version(100), NetworkVersion::V21);
+        assert_eq!(config.network_version(101), NetworkVersion::from(23));
+        assert_eq!(config.network_version(1_000_000), NetworkVersion::from(23));
+        assert_eq!(config.newest_network_version(), NetworkVersion::from(23));
+
+        // Otherwise the network version is that of the height
+        let config = ChainConfig {
+            height_infos: config
+                .height_infos
+                .into_iter()
+                .map(|(height, info)| {
+                    (
+                        height,
+                        HeightInfo {
+                            network_version: None,
+                            ..info
+                        },
+                    )
+                })
+                .collect(),
+            ..config
+        };
+        assert_eq!(config.network_version(101), NetworkVersion::V22);
+        assert_eq!(config.newest_network_version(), NetworkVersion::V22);
+    }
+
+    #[test]
+    fn test_violations() {
+        // Out of order upgrades
+        let mut config = ChainConfig::calibnet();
+        let thunder = config.epoch(Height::Thunder);
+        config.height_infos.get_mut(&Height::Dragon).unwrap().epoch = thunder - 1;
+        assert_violation(
+            &config,
+            &format!("upgrade Dragon at epoch {} precedes the", thunder - 1),
+        );
+        // Upgrades at genesis are not scheduled
+        let mut config = ChainConfig::calibnet();
+        config.height_infos.get_mut(&Height::Dragon).unwrap().epoch = -1;
+        assert_eq!(config.violations(), Vec::<String>::new());
+
+        // Network versions going back
+        let mut config = ChainConfig::calibnet();
+        config
+            .height_infos
+            .get_mut(&Height::Dragon)
+            .unwrap()
+            .network_version = Some(NetworkVersion::V20);
+        assert_violation(
+            &config,
+            "upgrade Dragon brings network version 20 back from 21",
+        );
+
+        // Missing bundles
+        let mut config = ChainConfig::mainnet();
+        config.height_infos.get_mut(&Height::Hygge).unwrap().bundle = None;
+        assert_violation(&config, "upgrade Hygge has no actor bundle");
+        // Upgrades before Hygge do not need one
+        let mut config = ChainConfig::mainnet();
+        config.height_infos.get_mut(&Height::Shark).unwrap().bundle = None;
+        assert_eq!(config.violations(), Vec::<String>::new());
+
+        // Genesis of another network
+        let config = ChainConfig {
+            genesis_cid: ChainConfig::mainnet().genesis_cid,
+            ..ChainConfig::calibnet()
+        };
+        assert_violation(&config, "is not the calibnet genesis");
+        let config = ChainConfig {
+            genesis_cid: None,
+            ..ChainConfig::butterflynet()
+        };
+        assert_violation(&config, "is not the butterflynet genesis");
+
+        // Drand schedules
+        let config = ChainConfig {
+            drand_schedule: vec![],
+            ..ChainConfig::mainnet()
+        };
+        assert_violation(&config, "the drand schedule is empty");
+        let mut config = ChainConfig::calibnet();
+        config.drand_schedule.remove(0);
+        assert_violation(&config, "the drand schedule starts at epoch");
+        let mut config = ChainConfig::calibnet();
+        config.drand_schedule.reverse();
+        assert_violation(&config, "does not come after the one at epoch");
+
+        // Public networks without bootstrap peers
+        let config = ChainConfig {
+            bootstrap_peers: vec![],
+            ..ChainConfig::mainnet()
+        };
+        assert_violation(&config, "mainnet has no bootstrap peers");
+        assert!(ChainConfig::devnet().bootstrap_peers.is_empty());
+
+        // Devnet policies
+        let config = ChainConfig {
+            block_delay_secs: 0,
+            ..ChainConfig::devnet()
+        };
+        assert_violation(&config, "the block delay is zero");
+        let mut config = ChainConfig::devnet();
+        config.policy.chain_finality = 0;
+        assert_violation(&config, "the chain finality 0 is not positive");
+        let mut config = ChainConfig::devnet();
+        config.policy.valid_pre_commit_proof_type.clear();
+        assert_violation(&config, "no seal proof type is allowed");
+        let mut config = ChainConfig::devnet();
+        config.policy.valid_post_proof_type.clear();
+        assert_violation(&config, "no PoSt proof type is allowed");
+        let mut config = ChainConfig::devnet();
+        config
+            .policy
+            .valid_post_proof_type
+            .remove(&RegisteredPoStProofV3::StackedDRGWindow8MiBV1);
+        assert_violation(
+            &config,
+            "seal proof type StackedDRG8MiBV1 is allowed, but no PoSt proof type",
+        );
+    }
+
+    #[test]
+    fn test_height_overrides() {
+        let overrides = HashMap::from_iter([(Height::Dragon, 100), (Height::Thunder, -1)]);
+        let config = ChainConfig::devnet()
+            .with_height_overrides(&overrides)
+            .unwrap();
+        assert_eq!(config.epoch(Height::Dragon), 100);
+        assert_eq!(config.epoch(Height::Thunder), -1);
+        assert_eq!(
+            config.epoch(Height::Hygge),
+            ChainConfig::devnet().epoch(Height::Hygge)
+        );
+
+        // Missing heights are added
+        let mut base = ChainConfig::devnet();
+        base.height_infos.remove(&Height::Dragon);
+        let config = base.with_height_overrides(&overrides).unwrap();
+        assert_eq!(config.height_infos[&Height::Dragon].bundle, None);
+        assert_eq!(config.epoch(Height::Dragon), 100);
+    }
+
+    #[test]
+    fn test_height_overrides_keep_order() {
+        let calibnet = ChainConfig::calibnet();
+        let thunder = calibnet.epoch(Height::Thunder);
+
+        let overrides = HashMap::from_iter([(Height::Thunder, thunder + 1)]);
+        assert!(calibnet.clone().with_height_overrides(&overrides).is_ok());
+
+        let overrides = HashMap::from_iter([(Height::Dragon, thunder - 1)]);
+        let error = calibnet
+            .clone()
+            .with_height_overrides(&overrides)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("across the Thunder upgrade"), "{error}");
+
+        let overrides = HashMap::from_iter([(Height::Breeze, thunder + 1)]);
+        assert!(calibnet.with_height_overrides(&overrides).is_err());
+
+        // Upgrades active at genesis are not ordered
+        let overrides = HashMap::from_iter([(Height::Breeze, -1)]);
+        assert!(ChainConfig::devnet()
+            .with_height_overrides(&overrides)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_set_actor_bundle() {
+        let manifest = Cid::default();
+
+        let mut config = ChainConfig::calibnet();
+        assert_eq!(
+            config.set_actor_bundle(12, manifest).unwrap(),
+            Height::WatermelonFix2
+        );
+        assert_eq!(
+            config.height_infos[&Height::WatermelonFix2].bundle,
+            Some(manifest)
+        );
+        assert_ne!(
+            config.height_infos[&Height::Watermelon].bundle,
+            Some(manifest)
+        );
+
+        let mut config = ChainConfig::mainnet();
+        assert_eq!(
+            config.set_actor_bundle(12, manifest).unwrap(),
+            Height::Watermelon
+        );
+        assert_eq!(
+            config.set_actor_bundle(13, manifest).unwrap(),
+            Height::Dragon
+        );
+        assert!(config.set_actor_bundle(8, manifest).is_err());
+
+        let mut config = ChainConfig::butterflynet();
+        config.height_infos.remove(&Height::Shark);
+        assert!(config.set_actor_bundle(9, manifest).is_err());
+    }
+
     #[test]
     fn test_get_upgrade_height_no_env_var() {
         let epoch = get_upgrade_height_from_env("FOREST_TEST_VAR_1");
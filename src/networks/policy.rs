@@ -0,0 +1,135 @@
+// Copyright 2019-2024 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::fmt::Debug;
+
+use fil_actors_shared::v10::runtime::Policy;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_with::{serde_as, DeserializeAs, SerializeAs};
+use tracing::warn;
+
+use crate::shim::clock::ChainEpoch;
+use crate::shim::sector::{RegisteredPoStProofV3, RegisteredSealProofV3};
+
+/// Adjustments to the [`Policy`] of a network, for experiments on devnets and
+/// testnets. Only the fields that are set override those of the network.
+#[serde_as]
+#[derive(Serialize, Deserialize, PartialEq, Eq, Default, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct PolicyOverrides {
+    /// Minimum consensus power of a miner, in bytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub minimum_consensus_power: Option<u64>,
+    /// Minimum size of a verified allocation, in bytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub minimum_verified_allocation_size: Option<u64>,
+    /// Epochs between the pre-commit of a sector and its seal challenge
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pre_commit_challenge_delay: Option<ChainEpoch>,
+    /// Seal proof types sectors may be pre-committed with, e.g.
+    /// `StackedDRG2KiBV1P1`
+    #[serde_as(as = "Option<Vec<ProofName>>")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub valid_pre_commit_proof_type: Option<Vec<RegisteredSealProofV3>>,
+    /// `PoSt` proof types miners may be created with, e.g.
+    /// `StackedDRGWindow2KiBV1P1`
+    #[serde_as(as = "Option<Vec<ProofName>>")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub valid_post_proof_type: Option<Vec<RegisteredPoStProofV3>>,
+}
+
+impl PolicyOverrides {
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// Overwrites the fields of `policy` that are set.
+    pub fn apply(&self, policy: &mut Policy) {
+        if let Some(power) = self.minimum_consensus_power {
+            warn!("Overriding the minimum consensus power with {power}");
+            policy.minimum_consensus_power = power.into();
+        }
+        if let Some(size) = self.minimum_verified_allocation_size {
+            warn!("Overriding the minimum verified allocation size with {size}");
+            policy.minimum_verified_allocation_size = size.into();
+        }
+        if let Some(delay) = self.pre_commit_challenge_delay {
+            warn!("Overriding the pre-commit challenge delay with {delay}");
+            policy.pre_commit_challenge_delay = delay;
+        }
+        if let Some(proofs) = &self.valid_pre_commit_proof_type {
+            warn!("Overriding the valid pre-commit proof types with {proofs:?}");
+            policy.valid_pre_commit_proof_type = proofs.iter().copied().collect();
+        }
+        if let Some(proofs) = &self.valid_post_proof_type {
+            warn!("Overriding the valid PoSt proof types with {proofs:?}");
+            policy.valid_post_proof_type = proofs.iter().copied().collect();
+        }
+    }
+}
+
+/// Serializes proof types by the names of their variants rather than their
+/// numeric identifiers.
+struct ProofName;
+
+/// The proof types known to the proofs library, numbered from zero.
+fn known_proofs<T: From<i64> + Debug>() -> impl Iterator<Item = T> {
+    (0..)
+        .map(T::from)
+        .take_while(|proof| !format!("{proof:?}").starts_with("Invalid"))
+}
+
+impl<T: Debug> SerializeAs<T> for ProofName {
+    fn serialize_as<S: Serializer>(source: &T, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&format_args!("{source:?}"))
+    }
+}
+
+impl<'de, T: From<i64> + Debug> DeserializeAs<'de, T> for ProofName {
+    fn deserialize_as<D: Deserializer<'de>>(deserializer: D) -> Result<T, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        known_proofs()
+            .find(|proof: &T| format!("{proof:?}") == name)
+            .ok_or_else(|| {
+                let known = known_proofs::<T>()
+                    .map(|proof| format!("{proof:?}"))
+                    .collect::<Vec<_>>();
+                serde::de::Error::custom(format!(
+                    "unknown proof type {name}, expected one of {}",
+                    known.join(", ")
+                ))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn proof_types_by_name() {
+        let overrides: PolicyOverrides = toml::from_str(
+            r#"
+valid_pre_commit_proof_type = ["StackedDRG2KiBV1P1", "StackedDRG8MiBV1P1_Feat_SyntheticPoRep"]
+valid_post_proof_type = ["StackedDRGWindow2KiBV1P1"]
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            overrides.valid_pre_commit_proof_type,
+            Some(vec![
+                RegisteredSealProofV3::StackedDRG2KiBV1P1,
+                RegisteredSealProofV3::StackedDRG8MiBV1P1_Feat_SyntheticPoRep,
+            ])
+        );
+        let round_trip: PolicyOverrides =
+            toml::from_str(&toml::to_string(&overrides).unwrap()).unwrap();
+        assert_eq!(round_trip, overrides);
+
+        let err = toml::from_str::<PolicyOverrides>(
+            r#"valid_post_proof_type = ["StackedDRGWindow3KiBV1"]"#,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("unknown proof type"), "{err}");
+    }
+}
@@ -6,23 +6,65 @@ use crate::lotus_json::LotusJson;
 use crate::rpc::error::JsonRpcError;
 use crate::rpc::Ctx;
 use crate::rpc_api::auth_api::*;
-use anyhow::Result;
+use anyhow::{Context as _, Result};
 use fvm_ipld_blockstore::Blockstore;
 use jsonrpsee::types::Params;
+use uuid::Uuid;
 
 /// RPC call to create a new JWT Token
 pub async fn auth_new<DB: Blockstore>(
     params: Params<'_>,
     data: Ctx<DB>,
 ) -> Result<LotusJson<Vec<u8>>, JsonRpcError> {
-    let auth_params: AuthNewParams = params.parse()?;
+    let AuthNewParams {
+        perms,
+        token_exp,
+        label,
+    } = params.parse()?;
+
+    data.tokens.check_expiration(&perms, token_exp)?;
+    let created = chrono::Utc::now();
+    let expires_at = token_exp
+        .map(|token_exp| {
+            created
+                .checked_add_signed(token_exp)
+                .context("token expiration is out of range")
+        })
+        .transpose()?;
 
     let ks = data.keystore.read().await;
     let ki = ks.get(JWT_IDENTIFIER)?;
-    let token = create_token(auth_params.perms, ki.private_key(), auth_params.token_exp)?;
+    let id = Uuid::new_v4().to_string();
+    let token = create_token_with_id(perms.clone(), ki.private_key(), expires_at, id.clone())?;
+    data.tokens.register(TokenInfo {
+        id,
+        perms,
+        created: created.timestamp(),
+        expires: expires_at.map(|time| time.timestamp()),
+        label,
+        revoked: false,
+    })?;
     Ok(LotusJson(token.as_bytes().to_vec()))
 }
 
+/// RPC call to list the metadata of the tokens created with `auth_new`
+pub async fn auth_list<DB: Blockstore>(data: Ctx<DB>) -> Result<Vec<TokenInfo>, JsonRpcError> {
+    Ok(data.tokens.list())
+}
+
+/// RPC call to revoke a token created with `auth_new`, rejecting any further
+/// request using it. Tokens issued otherwise, like the admin token printed on
+/// startup, have no `jti` claim and can't be revoked.
+pub async fn auth_revoke<DB: Blockstore>(
+    params: Params<'_>,
+    data: Ctx<DB>,
+) -> Result<(), JsonRpcError> {
+    let (id,): (String,) = params.parse()?;
+
+    data.tokens.revoke(&id)?;
+    Ok(())
+}
+
 /// RPC call to verify JWT Token and return the token's permissions
 pub async fn auth_verify<DB>(params: Params<'_>, data: Ctx<DB>) -> Result<Vec<String>, JsonRpcError>
 where
@@ -1,187 +1,118 @@
 // Copyright 2019-2024 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use crate::auth::{verify_token, JWT_IDENTIFIER};
+use crate::auth::{verify_token_claims, TokenRegistry, VerifiedClaims, JWT_IDENTIFIER};
 use crate::key_management::KeyStore;
-use crate::rpc::CANCEL_METHOD_NAME;
-use crate::rpc_api::*;
+use crate::rpc::reflect::{MethodPermissions, Permission};
 
+use chrono::Utc;
 use futures::future::BoxFuture;
 use futures::FutureExt;
 use hyper::header::{HeaderValue, AUTHORIZATION};
 use hyper::HeaderMap;
 use jsonrpsee::server::middleware::rpc::RpcServiceT;
-use jsonrpsee::types::{error::ErrorCode, ErrorObject};
+use jsonrpsee::types::{error::ErrorCode, ErrorObject, ErrorObjectOwned};
 use jsonrpsee::MethodResponse;
-use tokio::sync::RwLock;
+use jsonwebtoken::errors::ErrorKind as JwtErrorKind;
+use tokio::sync::{OnceCell, RwLock};
 use tower::Layer;
 use tracing::debug;
 
-use ahash::{HashMap, HashMapExt as _};
-use once_cell::sync::Lazy;
 use std::sync::Arc;
 
-/// Access levels to be checked against JWT claims
-enum Access {
-    Admin,
-    Sign,
-    Write,
-    Read,
+/// Error code returned when the JWT has expired, so that clients can tell it
+/// apart from a lack of permissions
+pub const TOKEN_EXPIRED_CODE: i32 = -32001;
+
+/// Checks a method permission against provided JWT claims
+fn check_access(permission: Permission, claims: &[String]) -> bool {
+    let claim = match permission {
+        Permission::Admin => "admin",
+        Permission::Sign => "sign",
+        Permission::Write => "write",
+        Permission::Read => "read",
+    };
+    claims.iter().any(|c| c == claim)
+}
+
+/// Authorization of a connection, derived once from the headers of the request
+/// opening it. For WebSocket connections, it applies to every call of the
+/// session, so the token is only verified on the first call.
+#[derive(Debug)]
+pub struct AuthContext {
+    header: Option<HeaderValue>,
+    claims: OnceCell<Result<VerifiedClaims, ErrorObjectOwned>>,
 }
 
-/// Access mapping between method names and access levels
-/// Checked against JWT claims on every request
-static ACCESS_MAP: Lazy<HashMap<&str, Access>> = Lazy::new(|| {
-    let mut access = HashMap::new();
-
-    // Auth API
-    access.insert(auth_api::AUTH_NEW, Access::Admin);
-    access.insert(auth_api::AUTH_VERIFY, Access::Read);
-
-    // Beacon API
-    access.insert(beacon_api::BEACON_GET_ENTRY, Access::Read);
-
-    // Chain API
-    access.insert(chain_api::CHAIN_GET_MESSAGE, Access::Read);
-    access.insert(chain_api::CHAIN_EXPORT, Access::Read);
-    access.insert(chain_api::CHAIN_READ_OBJ, Access::Read);
-    access.insert(chain_api::CHAIN_GET_PATH, Access::Read);
-    access.insert(chain_api::CHAIN_HAS_OBJ, Access::Read);
-    access.insert(chain_api::CHAIN_GET_BLOCK_MESSAGES, Access::Read);
-    access.insert(chain_api::CHAIN_GET_TIPSET_BY_HEIGHT, Access::Read);
-    access.insert(chain_api::CHAIN_GET_TIPSET_AFTER_HEIGHT, Access::Read);
-    access.insert(chain_api::CHAIN_GET_GENESIS, Access::Read);
-    access.insert(chain_api::CHAIN_HEAD, Access::Read);
-    access.insert(chain_api::CHAIN_GET_BLOCK, Access::Read);
-    access.insert(chain_api::CHAIN_GET_TIPSET, Access::Read);
-    access.insert(chain_api::CHAIN_SET_HEAD, Access::Admin);
-    access.insert(chain_api::CHAIN_GET_MIN_BASE_FEE, Access::Admin);
-    access.insert(chain_api::CHAIN_GET_MESSAGES_IN_TIPSET, Access::Read);
-    access.insert(chain_api::CHAIN_GET_PARENT_MESSAGES, Access::Read);
-    access.insert(chain_api::CHAIN_NOTIFY, Access::Read);
-    access.insert(chain_api::CHAIN_GET_PARENT_RECEIPTS, Access::Read);
-
-    // Message Pool API
-    access.insert(mpool_api::MPOOL_GET_NONCE, Access::Read);
-    access.insert(mpool_api::MPOOL_PENDING, Access::Read);
-    access.insert(mpool_api::MPOOL_PUSH, Access::Write);
-    access.insert(mpool_api::MPOOL_PUSH_MESSAGE, Access::Sign);
-
-    // Sync API
-    access.insert(sync_api::SYNC_CHECK_BAD, Access::Read);
-    access.insert(sync_api::SYNC_MARK_BAD, Access::Admin);
-    access.insert(sync_api::SYNC_STATE, Access::Read);
-
-    // Wallet API
-    access.insert(wallet_api::WALLET_BALANCE, Access::Write);
-    access.insert(wallet_api::WALLET_BALANCE, Access::Read);
-    access.insert(wallet_api::WALLET_DEFAULT_ADDRESS, Access::Read);
-    access.insert(wallet_api::WALLET_EXPORT, Access::Admin);
-    access.insert(wallet_api::WALLET_HAS, Access::Write);
-    access.insert(wallet_api::WALLET_IMPORT, Access::Admin);
-    access.insert(wallet_api::WALLET_LIST, Access::Write);
-    access.insert(wallet_api::WALLET_NEW, Access::Write);
-    access.insert(wallet_api::WALLET_SET_DEFAULT, Access::Write);
-    access.insert(wallet_api::WALLET_SIGN, Access::Sign);
-    access.insert(wallet_api::WALLET_VALIDATE_ADDRESS, Access::Read);
-    access.insert(wallet_api::WALLET_VERIFY, Access::Read);
-    access.insert(wallet_api::WALLET_DELETE, Access::Write);
-
-    // State API
-    access.insert(state_api::STATE_CALL, Access::Read);
-    access.insert(state_api::STATE_REPLAY, Access::Read);
-    access.insert(state_api::STATE_GET_ACTOR, Access::Read);
-    access.insert(state_api::STATE_MARKET_BALANCE, Access::Read);
-    access.insert(state_api::STATE_MARKET_DEALS, Access::Read);
-    access.insert(state_api::STATE_MINER_INFO, Access::Read);
-    access.insert(state_api::MINER_GET_BASE_INFO, Access::Read);
-    access.insert(state_api::STATE_MINER_ACTIVE_SECTORS, Access::Read);
-    access.insert(state_api::STATE_MINER_FAULTS, Access::Read);
-    access.insert(state_api::STATE_MINER_RECOVERIES, Access::Read);
-    access.insert(state_api::STATE_MINER_POWER, Access::Read);
-    access.insert(state_api::STATE_MINER_DEADLINES, Access::Read);
-    access.insert(state_api::STATE_MINER_PROVING_DEADLINE, Access::Read);
-    access.insert(state_api::STATE_MINER_AVAILABLE_BALANCE, Access::Read);
-    access.insert(state_api::STATE_GET_RECEIPT, Access::Read);
-    access.insert(state_api::STATE_WAIT_MSG, Access::Read);
-    access.insert(state_api::STATE_SEARCH_MSG, Access::Read);
-    access.insert(state_api::STATE_SEARCH_MSG_LIMITED, Access::Read);
-    access.insert(state_api::STATE_NETWORK_NAME, Access::Read);
-    access.insert(state_api::STATE_NETWORK_VERSION, Access::Read);
-    access.insert(state_api::STATE_ACCOUNT_KEY, Access::Read);
-    access.insert(state_api::STATE_LOOKUP_ID, Access::Read);
-    access.insert(state_api::STATE_FETCH_ROOT, Access::Read);
-    access.insert(state_api::STATE_GET_RANDOMNESS_FROM_TICKETS, Access::Read);
-    access.insert(state_api::STATE_GET_RANDOMNESS_FROM_BEACON, Access::Read);
-    access.insert(state_api::STATE_READ_STATE, Access::Read);
-    access.insert(state_api::STATE_CIRCULATING_SUPPLY, Access::Read);
-    access.insert(state_api::STATE_SECTOR_GET_INFO, Access::Read);
-    access.insert(state_api::STATE_LIST_MESSAGES, Access::Read);
-    access.insert(state_api::STATE_LIST_MINERS, Access::Read);
-    access.insert(state_api::STATE_MINER_SECTOR_COUNT, Access::Read);
-    access.insert(state_api::STATE_VERIFIED_CLIENT_STATUS, Access::Read);
-    access.insert(state_api::STATE_MARKET_STORAGE_DEAL, Access::Read);
-    access.insert(
-        state_api::STATE_VM_CIRCULATING_SUPPLY_INTERNAL,
-        Access::Read,
-    );
-    access.insert(state_api::MSIG_GET_AVAILABLE_BALANCE, Access::Read);
-    access.insert(state_api::MSIG_GET_PENDING, Access::Read);
-
-    // Gas API
-    access.insert(gas_api::GAS_ESTIMATE_GAS_LIMIT, Access::Read);
-    access.insert(gas_api::GAS_ESTIMATE_GAS_PREMIUM, Access::Read);
-    access.insert(gas_api::GAS_ESTIMATE_FEE_CAP, Access::Read);
-    access.insert(gas_api::GAS_ESTIMATE_MESSAGE_GAS, Access::Read);
-
-    // Common API
-    access.insert(common_api::VERSION, Access::Read);
-    access.insert(common_api::SESSION, Access::Read);
-    access.insert(common_api::SHUTDOWN, Access::Admin);
-    access.insert(common_api::START_TIME, Access::Read);
-
-    // Net API
-    access.insert(net_api::NET_ADDRS_LISTEN, Access::Read);
-    access.insert(net_api::NET_PEERS, Access::Read);
-    access.insert(net_api::NET_LISTENING, Access::Read);
-    access.insert(net_api::NET_INFO, Access::Read);
-    access.insert(net_api::NET_CONNECT, Access::Write);
-    access.insert(net_api::NET_DISCONNECT, Access::Write);
-    access.insert(net_api::NET_AGENT_VERSION, Access::Read);
-    access.insert(net_api::NET_AUTO_NAT_STATUS, Access::Read);
-    access.insert(net_api::NET_VERSION, Access::Read);
-
-    // Node API
-    access.insert(node_api::NODE_STATUS, Access::Read);
-
-    // Eth API
-    access.insert(eth_api::ETH_ACCOUNTS, Access::Read);
-    access.insert(eth_api::ETH_BLOCK_NUMBER, Access::Read);
-    access.insert(eth_api::ETH_CHAIN_ID, Access::Read);
-    access.insert(eth_api::ETH_GAS_PRICE, Access::Read);
-    access.insert(eth_api::ETH_GET_BALANCE, Access::Read);
-    access.insert(eth_api::ETH_SYNCING, Access::Read);
-
-    // Pubsub API
-    access.insert(CANCEL_METHOD_NAME, Access::Read);
-
-    access
-});
-
-/// Checks an access enumeration against provided JWT claims
-fn check_access(access: &Access, claims: &[String]) -> bool {
-    match access {
-        Access::Admin => claims.contains(&"admin".to_owned()),
-        Access::Sign => claims.contains(&"sign".to_owned()),
-        Access::Write => claims.contains(&"write".to_owned()),
-        Access::Read => claims.contains(&"read".to_owned()),
+impl AuthContext {
+    pub fn new(headers: &HeaderMap) -> Self {
+        Self {
+            header: headers.get(AUTHORIZATION).cloned(),
+            claims: OnceCell::new(),
+        }
+    }
+
+    /// Returns the identifier of the token of the connection, once it has
+    /// been verified.
+    pub fn token_id(&self) -> Option<&str> {
+        self.claims
+            .get()
+            .and_then(|claims| claims.as_ref().ok())
+            .and_then(|claims| claims.id.as_deref())
+    }
+
+    pub(in crate::rpc) async fn check(
+        &self,
+        keystore: &RwLock<KeyStore>,
+        tokens: &TokenRegistry,
+        permissions: &MethodPermissions,
+        method: &str,
+    ) -> Result<(), ErrorObjectOwned> {
+        let perms = match &self.header {
+            Some(token) => {
+                let claims = self
+                    .claims
+                    .get_or_init(|| verify_header(token, keystore))
+                    .await
+                    .clone()?;
+                // A session may outlive its token
+                if claims.expires_at.is_some_and(|exp| exp < Utc::now()) {
+                    return Err(token_expired());
+                }
+                // Tokens without an identifier weren't issued through
+                // `Filecoin.AuthNew`, and can't be revoked
+                if let Some(id) = &claims.id {
+                    if tokens.is_revoked(id) {
+                        debug!("JWT {id} has been revoked");
+                        return Err(ErrorCode::InvalidRequest.into());
+                    }
+                }
+                claims.perms
+            }
+            // If no token is passed, assume read behavior
+            None => vec!["read".to_owned()],
+        };
+        debug!("Decoded JWT Claims: {}", perms.join(","));
+
+        // Methods that didn't declare a permission are restricted to admins
+        let permission = permissions
+            .get(method)
+            .copied()
+            .unwrap_or(Permission::Admin);
+        if check_access(permission, &perms) {
+            Ok(())
+        } else {
+            Err(ErrorCode::InvalidRequest.into())
+        }
     }
 }
 
 #[derive(Clone)]
 pub struct AuthLayer {
-    pub headers: HeaderMap,
+    pub auth: Arc<AuthContext>,
     pub keystore: Arc<RwLock<KeyStore>>,
+    pub tokens: Arc<TokenRegistry>,
+    pub permissions: Arc<MethodPermissions>,
 }
 
 impl<S> Layer<S> for AuthLayer {
@@ -189,8 +120,10 @@ impl<S> Layer<S> for AuthLayer {
 
     fn layer(&self, service: S) -> Self::Service {
         AuthMiddleware {
-            headers: self.headers.clone(),
+            auth: self.auth.clone(),
             keystore: self.keystore.clone(),
+            tokens: self.tokens.clone(),
+            permissions: self.permissions.clone(),
             service,
         }
     }
@@ -198,8 +131,10 @@ impl<S> Layer<S> for AuthLayer {
 
 #[derive(Clone)]
 pub struct AuthMiddleware<S> {
-    headers: HeaderMap,
+    auth: Arc<AuthContext>,
     keystore: Arc<RwLock<KeyStore>>,
+    tokens: Arc<TokenRegistry>,
+    permissions: Arc<MethodPermissions>,
     service: S,
 }
 
@@ -210,59 +145,288 @@ where
     type Future = BoxFuture<'a, MethodResponse>;
 
     fn call(&self, req: jsonrpsee::types::Request<'a>) -> Self::Future {
-        let headers = self.headers.clone();
+        let auth = self.auth.clone();
         let keystore = self.keystore.clone();
+        let tokens = self.tokens.clone();
+        let permissions = self.permissions.clone();
         let service = self.service.clone();
 
         async move {
-            let auth_header = headers.get(AUTHORIZATION).cloned();
-            let res = check_permissions(keystore, auth_header, req.method_name()).await;
+            let res = auth
+                .check(&keystore, &tokens, &permissions, req.method_name())
+                .await;
 
             match res {
                 Ok(()) => service.call(req).await,
-                Err(code) => MethodResponse::error(req.id(), ErrorObject::from(code)),
+                Err(err) => MethodResponse::error(req.id(), err),
             }
         }
         .boxed()
     }
 }
 
-/// Verify JWT Token and return the token's permissions.
-async fn auth_verify(token: &str, keystore: Arc<RwLock<KeyStore>>) -> anyhow::Result<Vec<String>> {
+fn token_expired() -> ErrorObjectOwned {
+    ErrorObject::owned(TOKEN_EXPIRED_CODE, "Token expired", None::<()>)
+}
+
+/// Verify the JWT of an `Authorization` header.
+async fn verify_header(
+    token: &HeaderValue,
+    keystore: &RwLock<KeyStore>,
+) -> Result<VerifiedClaims, ErrorObjectOwned> {
+    let token = token.to_str().map_err(|_| ErrorCode::ParseError)?;
+    let token = token.strip_prefix("Bearer ").unwrap_or(token);
+    debug!("JWT from HTTP Header: {}", token);
+
     let ks = keystore.read().await;
-    let ki = ks.get(JWT_IDENTIFIER)?;
-    let perms = verify_token(token, ki.private_key())?;
-    Ok(perms)
+    let ki = ks
+        .get(JWT_IDENTIFIER)
+        .map_err(|_| ErrorObjectOwned::from(ErrorCode::InvalidRequest))?;
+    verify_token_claims(token, ki.private_key()).map_err(|e| match e.kind() {
+        JwtErrorKind::ExpiredSignature => token_expired(),
+        _ => ErrorCode::InvalidRequest.into(),
+    })
 }
 
-async fn check_permissions(
-    keystore: Arc<RwLock<KeyStore>>,
-    auth_header: Option<HeaderValue>,
-    method: &str,
-) -> anyhow::Result<(), ErrorCode> {
-    let claims = match auth_header {
-        Some(token) => {
-            let token = token.to_str().map_err(|_| ErrorCode::ParseError)?;
-
-            debug!("JWT from HTTP Header: {}", token);
-
-            auth_verify(token, keystore)
-                .await
-                .map_err(|_| ErrorCode::InvalidRequest)?
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::{create_token, generate_priv_key, ADMIN, READ, SIGN, WRITE};
+    use crate::key_management::KeyStoreConfig;
+    use crate::lotus_json::LotusJson;
+    use crate::rpc::auth_api::{auth_list, auth_new, auth_revoke};
+    use crate::rpc::{build_module, RPCState};
+    use crate::rpc_api::{auth_api, chain_api, mpool_api, wallet_api, ApiVersion};
+    use jsonrpsee::types::Params;
+
+    /// Checks a single call made on a new connection.
+    async fn check_permissions(
+        keystore: Arc<RwLock<KeyStore>>,
+        tokens: &TokenRegistry,
+        permissions: &MethodPermissions,
+        auth_header: Option<HeaderValue>,
+        method: &str,
+    ) -> Result<(), ErrorObjectOwned> {
+        let mut headers = HeaderMap::new();
+        if let Some(auth_header) = auth_header {
+            headers.insert(AUTHORIZATION, auth_header);
         }
-        // If no token is passed, assume read behavior
-        None => vec!["read".to_owned()],
-    };
-    debug!("Decoded JWT Claims: {}", claims.join(","));
-
-    match ACCESS_MAP.get(&method) {
-        Some(access) => {
-            if check_access(access, &claims) {
-                Ok(())
-            } else {
-                Err(ErrorCode::InvalidRequest)
+        AuthContext::new(&headers)
+            .check(&keystore, tokens, permissions, method)
+            .await
+    }
+
+    /// Builds the permission table of the methods served by the node.
+    fn method_permissions() -> MethodPermissions {
+        let (shutdown_send, _) = tokio::sync::mpsc::channel(1);
+        let (_, _, permissions) = build_module(
+            Arc::new(RPCState::calibnet()),
+            "0.0.0",
+            shutdown_send,
+            ApiVersion::V1,
+        )
+        .unwrap();
+        permissions
+    }
+
+    #[tokio::test]
+    async fn permission_tiers() {
+        let mut keystore = KeyStore::new(KeyStoreConfig::Memory).unwrap();
+        let key = generate_priv_key();
+        keystore.put(JWT_IDENTIFIER, key.clone()).unwrap();
+        let keystore = Arc::new(RwLock::new(keystore));
+        let tokens = TokenRegistry::default();
+        let permissions = method_permissions();
+
+        let methods = [
+            chain_api::CHAIN_HEAD,
+            chain_api::CHAIN_GET_PATH,
+            mpool_api::MPOOL_PUSH,
+            wallet_api::WALLET_SIGN,
+            auth_api::AUTH_NEW,
+            "Filecoin.Unknown",
+        ];
+        let levels = [
+            (None, [true, true, false, false, false, false]),
+            (Some(READ), [true, true, false, false, false, false]),
+            (Some(WRITE), [true, true, true, false, false, false]),
+            (Some(SIGN), [true, true, true, true, false, false]),
+            (Some(ADMIN), [true, true, true, true, true, true]),
+        ];
+        for (perms, allowed) in levels {
+            let header = perms.map(|perms| {
+                let perms = perms.iter().map(|perm| perm.to_string()).collect();
+                let token = create_token(
+                    perms,
+                    key.private_key(),
+                    chrono::Duration::try_hours(1).expect("Infallible"),
+                )
+                .unwrap();
+                HeaderValue::from_str(&token).unwrap()
+            });
+            for (method, allowed) in methods.into_iter().zip(allowed) {
+                let res = check_permissions(
+                    keystore.clone(),
+                    &tokens,
+                    &permissions,
+                    header.clone(),
+                    method,
+                )
+                .await;
+                assert_eq!(res.is_ok(), allowed, "{perms:?} calling {method}");
             }
         }
-        None => Err(ErrorCode::MethodNotFound),
+
+        // Tokens may be sent in the bearer scheme, as done for Lotus
+        let token = create_token(
+            ADMIN.iter().map(|perm| perm.to_string()).collect(),
+            key.private_key(),
+            chrono::Duration::try_hours(1).expect("Infallible"),
+        )
+        .unwrap();
+        let header = HeaderValue::from_str(&format!("Bearer {token}")).unwrap();
+        let res = check_permissions(
+            keystore,
+            &tokens,
+            &permissions,
+            Some(header),
+            auth_api::AUTH_NEW,
+        )
+        .await;
+        assert_eq!(res, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn revoked_token_is_rejected() {
+        let mut keystore = KeyStore::new(KeyStoreConfig::Memory).unwrap();
+        keystore.put(JWT_IDENTIFIER, generate_priv_key()).unwrap();
+        let data = Arc::new(RPCState {
+            keystore: Arc::new(RwLock::new(keystore)),
+            ..RPCState::calibnet()
+        });
+        let permissions = method_permissions();
+        let check = |token: &[u8], method| {
+            let header = HeaderValue::from_bytes(token).unwrap();
+            check_permissions(
+                data.keystore.clone(),
+                &data.tokens,
+                &permissions,
+                Some(header),
+                method,
+            )
+        };
+
+        let params = serde_json::to_string(&(ADMIN, 60, "leaked")).unwrap();
+        let LotusJson(token) = auth_new(Params::new(Some(&params)), Arc::new(data.clone()))
+            .await
+            .unwrap();
+        assert_eq!(check(&token, auth_api::AUTH_LIST).await, Ok(()));
+
+        let tokens = auth_list(Arc::new(data.clone())).await.unwrap();
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].label.as_deref(), Some("leaked"));
+        assert!(!tokens[0].revoked);
+
+        let params = serde_json::to_string(&[&tokens[0].id]).unwrap();
+        auth_revoke(Params::new(Some(&params)), Arc::new(data.clone()))
+            .await
+            .unwrap();
+        assert_eq!(
+            check(&token, auth_api::AUTH_LIST).await,
+            Err(ErrorCode::InvalidRequest.into())
+        );
+        assert_eq!(
+            check(&token, chain_api::CHAIN_HEAD).await,
+            Err(ErrorCode::InvalidRequest.into())
+        );
+        assert!(auth_list(Arc::new(data)).await.unwrap()[0].revoked);
+    }
+
+    #[tokio::test]
+    async fn expired_token_is_rejected() {
+        let mut keystore = KeyStore::new(KeyStoreConfig::Memory).unwrap();
+        keystore.put(JWT_IDENTIFIER, generate_priv_key()).unwrap();
+        let data = Arc::new(RPCState {
+            keystore: Arc::new(RwLock::new(keystore)),
+            ..RPCState::calibnet()
+        });
+
+        let params = serde_json::to_string(&(READ, 1)).unwrap();
+        let LotusJson(token) = auth_new(Params::new(Some(&params)), Arc::new(data.clone()))
+            .await
+            .unwrap();
+        let permissions = method_permissions();
+        let check = || {
+            let header = HeaderValue::from_bytes(&token).unwrap();
+            check_permissions(
+                data.keystore.clone(),
+                &data.tokens,
+                &permissions,
+                Some(header),
+                chain_api::CHAIN_HEAD,
+            )
+        };
+        assert_eq!(check().await, Ok(()));
+
+        tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+        let err = check().await.unwrap_err();
+        assert_eq!(err.code(), TOKEN_EXPIRED_CODE);
+        assert_eq!(err.message(), "Token expired");
+    }
+
+    #[tokio::test]
+    async fn session_is_checked_on_every_call() {
+        let mut keystore = KeyStore::new(KeyStoreConfig::Memory).unwrap();
+        keystore.put(JWT_IDENTIFIER, generate_priv_key()).unwrap();
+        let data = Arc::new(RPCState {
+            keystore: Arc::new(RwLock::new(keystore)),
+            ..RPCState::calibnet()
+        });
+        let permissions = method_permissions();
+        let new_session = |params: &str| {
+            let data = data.clone();
+            let params = params.to_owned();
+            async move {
+                let LotusJson(token) = auth_new(Params::new(Some(&params)), Arc::new(data))
+                    .await
+                    .unwrap();
+                let mut headers = HeaderMap::new();
+                headers.insert(AUTHORIZATION, HeaderValue::from_bytes(&token).unwrap());
+                Arc::new(AuthContext::new(&headers))
+            }
+        };
+        let check = |session: &Arc<AuthContext>| {
+            let (session, data, permissions) = (session.clone(), data.clone(), &permissions);
+            async move {
+                session
+                    .check(
+                        &data.keystore,
+                        &data.tokens,
+                        permissions,
+                        chain_api::CHAIN_HEAD,
+                    )
+                    .await
+            }
+        };
+
+        // The claims verified on the first call still expire
+        let short = new_session(&serde_json::to_string(&(READ, 1)).unwrap()).await;
+        // and can still be revoked
+        let revoked = new_session(&serde_json::to_string(&(READ, 60, "revoked")).unwrap()).await;
+        assert_eq!(check(&short).await, Ok(()));
+        assert_eq!(check(&revoked).await, Ok(()));
+
+        let tokens = auth_list(Arc::new(data.clone())).await.unwrap();
+        let token = tokens
+            .iter()
+            .find(|token| token.label.as_deref() == Some("revoked"));
+        let params = serde_json::to_string(&[&token.unwrap().id]).unwrap();
+        auth_revoke(Params::new(Some(&params)), Arc::new(data.clone()))
+            .await
+            .unwrap();
+        assert_eq!(check(&revoked).await, Err(ErrorCode::InvalidRequest.into()));
+
+        tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+        assert_eq!(check(&short).await.unwrap_err().code(), TOKEN_EXPIRED_CODE);
     }
 }
@@ -1,16 +1,23 @@
 // Copyright 2019-2024 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
 use crate::rpc::error::JsonRpcError;
+use crate::rpc::reflect::{Permission, RpcMethod};
 use crate::rpc::Ctx;
+use crate::rpc_api::beacon_api::*;
 use crate::{beacon::BeaconEntry, lotus_json::LotusJson, shim::clock::ChainEpoch};
 use anyhow::Result;
 use fvm_ipld_blockstore::Blockstore;
 use jsonrpsee::types::Params;
 
+/// Number of epochs ahead whose beacon entries `BeaconGetEntry` waits for
+const MAX_ENTRY_WAIT_EPOCHS: u32 = 2;
+
 /// `BeaconGetEntry` returns the beacon entry for the given Filecoin epoch. If
 /// the entry has not yet been produced, the call will block until the entry
-/// becomes available
+/// becomes available, for epochs up to [`MAX_ENTRY_WAIT_EPOCHS`] ahead
 pub async fn beacon_get_entry<DB: Blockstore>(
     params: Params<'_>,
     data: Ctx<DB>,
@@ -20,6 +27,33 @@ pub async fn beacon_get_entry<DB: Blockstore>(
     let (_, beacon) = data.beacon.beacon_for_epoch(first)?;
     let rr =
         beacon.max_beacon_round_for_epoch(data.state_manager.get_network_version(first), first);
-    let e = beacon.entry(rr).await?;
+    let max_wait = Duration::from_secs(
+        (data.state_manager.chain_config().block_delay_secs * MAX_ENTRY_WAIT_EPOCHS).into(),
+    );
+    let e = beacon.entry_when_produced(rr, max_wait).await?;
     Ok(e.into())
 }
+
+/// Returns the entry of the latest round produced by the beacon of the
+/// heaviest tipset.
+pub enum BeaconGetLatest {}
+impl RpcMethod<0> for BeaconGetLatest {
+    const NAME: &'static str = BEACON_GET_LATEST;
+    const PARAM_NAMES: [&'static str; 0] = [];
+    const PERMISSION: Permission = Permission::Read;
+    type Params = ();
+    type Ok = LotusJson<BeaconEntry>;
+
+    async fn handle(
+        data: Ctx<impl Blockstore + Send + Sync + 'static>,
+        (): Self::Params,
+    ) -> Result<Self::Ok, JsonRpcError> {
+        let epoch = data.state_manager.chain_store().heaviest_tipset().epoch();
+        let (_, beacon) = data.beacon.beacon_for_epoch(epoch)?;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(anyhow::Error::from)?;
+        let round = beacon.round_at(now.as_secs());
+        Ok(LotusJson(beacon.entry(round).await?))
+    }
+}
@@ -4,13 +4,15 @@
 
 use crate::blocks::{CachingBlockHeader, Tipset, TipsetKey};
 use crate::chain::index::ResolveNullTipset;
-use crate::chain::{ChainStore, HeadChange};
+use crate::chain::{ChainStore, ExportOptions, ExportProgress, HeadChange};
 use crate::cid_collections::CidHashSet;
+use crate::db::GcProgress;
 use crate::lotus_json::LotusJson;
 use crate::message::ChainMessage;
 use crate::rpc::{
+    channel::broadcast_stream,
     error::JsonRpcError,
-    reflect::{Ctx, RpcMethod},
+    reflect::{Ctx, Permission, RpcMethod},
 };
 use crate::rpc_api::data_types::{ApiHeadChange, ApiMessage, ApiReceipt};
 use crate::rpc_api::{
@@ -23,6 +25,8 @@ use crate::utils::io::VoidAsyncWriter;
 use anyhow::{Context as _, Result};
 use cid::Cid;
 use fil_actors_shared::fvm_ipld_amt::Amtv0 as Amt;
+use futures::future;
+use futures::stream::{self, BoxStream, StreamExt as _};
 use fvm_ipld_blockstore::Blockstore;
 use fvm_ipld_encoding::CborStore;
 use hex::ToHex;
@@ -31,89 +35,82 @@ use jsonrpsee::types::Params;
 use once_cell::sync::Lazy;
 use sha2::Sha256;
 use std::sync::Arc;
-use tokio::sync::{
-    broadcast::{self, Receiver as Subscriber},
-    Mutex,
-};
+use tokio::sync::Mutex;
 
-pub async fn chain_get_message<DB: Blockstore>(
-    params: Params<'_>,
-    data: Ctx<DB>,
-) -> Result<LotusJson<Message>, JsonRpcError> {
-    let LotusJson((msg_cid,)): LotusJson<(Cid,)> = params.parse()?;
-
-    let chain_message: ChainMessage = data
-        .state_manager
-        .blockstore()
-        .get_cbor(&msg_cid)?
-        .with_context(|| format!("can't find message with cid {msg_cid}"))?;
-    Ok(LotusJson(match chain_message {
-        ChainMessage::Signed(m) => m.into_message(),
-        ChainMessage::Unsigned(m) => m,
-    }))
-}
+pub enum ChainGetMessage {}
+impl RpcMethod<1> for ChainGetMessage {
+    const NAME: &'static str = CHAIN_GET_MESSAGE;
+    const PARAM_NAMES: [&'static str; 1] = ["msg_cid"];
+    const PERMISSION: Permission = Permission::Read;
+    type Params = (LotusJson<Cid>,);
+    type Ok = LotusJson<Message>;
 
-pub async fn chain_get_parent_messages<DB: Blockstore>(
-    params: Params<'_>,
-    data: Ctx<DB>,
-) -> Result<LotusJson<Vec<ApiMessage>>, JsonRpcError> {
-    let LotusJson((block_cid,)): LotusJson<(Cid,)> = params.parse()?;
-
-    let store = data.state_manager.blockstore();
-    let block_header: CachingBlockHeader = store
-        .get_cbor(&block_cid)?
-        .with_context(|| format!("can't find block header with cid {block_cid}"))?;
-    if block_header.epoch == 0 {
-        Ok(LotusJson(vec![]))
-    } else {
-        let parent_tipset = Tipset::load_required(store, &block_header.parents)?;
-        let messages = load_api_messages_from_tipset(store, &parent_tipset)?;
-        Ok(LotusJson(messages))
+    async fn handle(
+        data: Ctx<impl Blockstore>,
+        (LotusJson(msg_cid),): Self::Params,
+    ) -> Result<Self::Ok, JsonRpcError> {
+        let chain_message: ChainMessage = data
+            .state_manager
+            .blockstore()
+            .get_cbor(&msg_cid)?
+            .with_context(|| format!("can't find message with cid {msg_cid}"))?;
+        Ok(LotusJson(match chain_message {
+            ChainMessage::Signed(m) => m.into_message(),
+            ChainMessage::Unsigned(m) => m,
+        }))
     }
 }
 
-pub async fn chain_get_parent_receipts<DB: Blockstore + Send + Sync + 'static>(
-    params: Params<'_>,
-    data: Ctx<DB>,
-) -> Result<LotusJson<Vec<ApiReceipt>>, JsonRpcError> {
-    let LotusJson((block_cid,)): LotusJson<(Cid,)> = params.parse()?;
+pub enum ChainGetParentMessages {}
+impl RpcMethod<1> for ChainGetParentMessages {
+    const NAME: &'static str = CHAIN_GET_PARENT_MESSAGES;
+    const PARAM_NAMES: [&'static str; 1] = ["block_cid"];
+    const PERMISSION: Permission = Permission::Read;
+    type Params = (LotusJson<Cid>,);
+    type Ok = LotusJson<Vec<ApiMessage>>;
 
-    let store = data.state_manager.blockstore();
-    let block_header: CachingBlockHeader = store
-        .get_cbor(&block_cid)?
-        .with_context(|| format!("can't find block header with cid {block_cid}"))?;
-    let mut receipts = Vec::new();
-    if block_header.epoch == 0 {
-        return Ok(LotusJson(vec![]));
+    async fn handle(
+        data: Ctx<impl Blockstore>,
+        (LotusJson(block_cid),): Self::Params,
+    ) -> Result<Self::Ok, JsonRpcError> {
+        let store = data.state_manager.blockstore();
+        let block_header: CachingBlockHeader = store
+            .get_cbor(&block_cid)?
+            .with_context(|| format!("can't find block header with cid {block_cid}"))?;
+        if block_header.epoch == 0 {
+            Ok(LotusJson(vec![]))
+        } else {
+            let parent_tipset = Tipset::load_required(store, &block_header.parents)?;
+            let messages = load_api_messages_from_tipset(store, &parent_tipset)?;
+            Ok(LotusJson(messages))
+        }
     }
+}
 
-    // Try Receipt_v4 first. (Receipt_v4 and Receipt_v3 are identical, use v4 here)
-    if let Ok(amt) =
-        Amt::<fvm_shared4::receipt::Receipt, _>::load(&block_header.message_receipts, store)
-            .map_err(|_| {
-                ErrorObjectOwned::owned::<()>(
-                    1,
-                    format!(
-                        "failed to root: ipld: could not find {}",
-                        block_header.message_receipts
-                    ),
-                    None,
-                )
-            })
-    {
-        amt.for_each(|_, receipt| {
-            receipts.push(ApiReceipt {
-                exit_code: receipt.exit_code.into(),
-                return_data: receipt.return_data.clone(),
-                gas_used: receipt.gas_used,
-                events_root: receipt.events_root,
-            });
-            Ok(())
-        })?;
-    } else {
-        // Fallback to Receipt_v2.
-        let amt =
-            Amt::<fvm_shared2::receipt::Receipt, _>::load(&block_header.message_receipts, store)
+pub enum ChainGetParentReceipts {}
+impl RpcMethod<1> for ChainGetParentReceipts {
+    const NAME: &'static str = CHAIN_GET_PARENT_RECEIPTS;
+    const PARAM_NAMES: [&'static str; 1] = ["block_cid"];
+    const PERMISSION: Permission = Permission::Read;
+    type Params = (LotusJson<Cid>,);
+    type Ok = LotusJson<Vec<ApiReceipt>>;
+
+    async fn handle(
+        data: Ctx<impl Blockstore>,
+        (LotusJson(block_cid),): Self::Params,
+    ) -> Result<Self::Ok, JsonRpcError> {
+        let store = data.state_manager.blockstore();
+        let block_header: CachingBlockHeader = store
+            .get_cbor(&block_cid)?
+            .with_context(|| format!("can't find block header with cid {block_cid}"))?;
+        let mut receipts = Vec::new();
+        if block_header.epoch == 0 {
+            return Ok(LotusJson(vec![]));
+        }
+
+        // Try Receipt_v4 first. (Receipt_v4 and Receipt_v3 are identical, use v4 here)
+        if let Ok(amt) =
+            Amt::<fvm_shared4::receipt::Receipt, _>::load(&block_header.message_receipts, store)
                 .map_err(|_| {
                     ErrorObjectOwned::owned::<()>(
                         1,
@@ -123,150 +120,247 @@ pub async fn chain_get_parent_receipts<DB: Blockstore + Send + Sync + 'static>(
                         ),
                         None,
                     )
-                })?;
-        amt.for_each(|_, receipt| {
-            receipts.push(ApiReceipt {
-                exit_code: receipt.exit_code.into(),
-                return_data: receipt.return_data.clone(),
-                gas_used: receipt.gas_used as _,
-                events_root: None,
-            });
-            Ok(())
-        })?;
-    }
-
-    Ok(LotusJson(receipts))
+                })
+        {
+            amt.for_each(|_, receipt| {
+                receipts.push(ApiReceipt {
+                    exit_code: receipt.exit_code.into(),
+                    return_data: receipt.return_data.clone(),
+                    gas_used: receipt.gas_used,
+                    events_root: receipt.events_root,
+                });
+                Ok(())
+            })?;
+        } else {
+            // Fallback to Receipt_v2.
+            let amt = Amt::<fvm_shared2::receipt::Receipt, _>::load(
+                &block_header.message_receipts,
+                store,
+            )
+            .map_err(|_| {
+                ErrorObjectOwned::owned::<()>(
+                    1,
+                    format!(
+                        "failed to root: ipld: could not find {}",
+                        block_header.message_receipts
+                    ),
+                    None,
+                )
+            })?;
+            amt.for_each(|_, receipt| {
+                receipts.push(ApiReceipt {
+                    exit_code: receipt.exit_code.into(),
+                    return_data: receipt.return_data.clone(),
+                    gas_used: receipt.gas_used as _,
+                    events_root: None,
+                });
+                Ok(())
+            })?;
+        }
+
+        Ok(LotusJson(receipts))
+    }
 }
 
-pub(crate) async fn chain_get_messages_in_tipset<DB: Blockstore>(
-    params: Params<'_>,
-    data: Ctx<DB>,
-) -> Result<LotusJson<Vec<ApiMessage>>, JsonRpcError> {
-    let LotusJson((tsk,)): LotusJson<(TipsetKey,)> = params.parse()?;
+pub enum ChainGetMessagesInTipset {}
+impl RpcMethod<1> for ChainGetMessagesInTipset {
+    const NAME: &'static str = CHAIN_GET_MESSAGES_IN_TIPSET;
+    const PARAM_NAMES: [&'static str; 1] = ["tsk"];
+    const PERMISSION: Permission = Permission::Read;
+    type Params = (LotusJson<TipsetKey>,);
+    type Ok = LotusJson<Vec<ApiMessage>>;
 
-    let store = data.chain_store.blockstore();
-    let tipset = Tipset::load_required(store, &tsk)?;
-    let messages = load_api_messages_from_tipset(store, &tipset)?;
-    Ok(LotusJson(messages))
+    async fn handle(
+        data: Ctx<impl Blockstore>,
+        (LotusJson(tsk),): Self::Params,
+    ) -> Result<Self::Ok, JsonRpcError> {
+        let store = data.chain_store.blockstore();
+        let tipset = Tipset::load_required(store, &tsk)?;
+        let messages = load_api_messages_from_tipset(store, &tipset)?;
+        Ok(LotusJson(messages))
+    }
 }
 
-pub async fn chain_export<DB>(
-    params: Params<'_>,
-    data: Ctx<DB>,
-) -> Result<Option<String>, JsonRpcError>
-where
-    DB: Blockstore + Send + Sync + 'static,
-{
-    let ChainExportParams {
-        epoch,
-        recent_roots,
-        output_path,
-        tipset_keys: ApiTipsetKey(tsk),
-        skip_checksum,
-        dry_run,
-    }: ChainExportParams = params.parse()?;
-
-    static LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
-
-    let _locked = LOCK.try_lock();
-    if _locked.is_err() {
-        return Err(anyhow::anyhow!("Another chain export job is still in progress").into());
-    }
-
-    let chain_finality = data.state_manager.chain_config().policy.chain_finality;
-    if recent_roots < chain_finality {
-        return Err(anyhow::anyhow!(format!(
-            "recent-stateroots must be greater than {chain_finality}"
-        ))
-        .into());
-    }
-
-    let head = data.chain_store.load_required_tipset_or_heaviest(&tsk)?;
-    let start_ts =
-        data.chain_store
-            .chain_index
-            .tipset_by_height(epoch, head, ResolveNullTipset::TakeOlder)?;
+pub enum ChainExport {}
+impl RpcMethod<1> for ChainExport {
+    const NAME: &'static str = CHAIN_EXPORT;
+    const PARAM_NAMES: [&'static str; 1] = ["params"];
+    const PERMISSION: Permission = Permission::Read;
+    const ACCEPTS_BARE_OBJECT: bool = true;
+    type Params = (ChainExportParams,);
+    type Ok = ChainExportResult;
 
-    match if dry_run {
-        crate::chain::export::<Sha256>(
-            Arc::clone(&data.chain_store.db),
-            &start_ts,
+    async fn handle(
+        data: Ctx<impl Blockstore + Send + Sync + 'static>,
+        (ChainExportParams {
+            epoch,
             recent_roots,
-            VoidAsyncWriter,
-            CidHashSet::default(),
+            output_path,
+            tipset_keys: ApiTipsetKey(tsk),
             skip_checksum,
-        )
-        .await
-    } else {
-        let file = tokio::fs::File::create(&output_path).await?;
-        crate::chain::export::<Sha256>(
-            Arc::clone(&data.chain_store.db),
-            &start_ts,
-            recent_roots,
-            file,
-            CidHashSet::default(),
+            dry_run,
+            skip_old_messages,
+        },): Self::Params,
+    ) -> Result<Self::Ok, JsonRpcError> {
+        static LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+        let _locked = LOCK.try_lock();
+        if _locked.is_err() {
+            return Err(anyhow::anyhow!("Another chain export job is still in progress").into());
+        }
+
+        let chain_finality = data.state_manager.chain_config().policy.chain_finality;
+        if recent_roots < chain_finality {
+            return Err(anyhow::anyhow!(format!(
+                "recent-stateroots must be greater than {chain_finality}"
+            ))
+            .into());
+        }
+
+        let head = data.chain_store.load_required_tipset_or_heaviest(&tsk)?;
+        let start_ts = data.chain_store.chain_index.tipset_by_height(
+            epoch,
+            head,
+            ResolveNullTipset::TakeOlder,
+        )?;
+
+        let progress = Arc::new(ExportProgress::new(start_ts.epoch()));
+        let options = ExportOptions {
             skip_checksum,
-        )
-        .await
-    } {
-        Ok(checksum_opt) => Ok(checksum_opt.map(|hash| hash.encode_hex())),
-        Err(e) => Err(anyhow::anyhow!(e).into()),
+            include_old_messages: !skip_old_messages,
+            progress: Some(Arc::clone(&progress)),
+            ..Default::default()
+        };
+        // Published for `ChainExportStatus` until the export is done
+        *EXPORT_PROGRESS.lock() = Some(progress);
+        let result = if dry_run {
+            crate::chain::export::<Sha256>(
+                Arc::clone(&data.chain_store.db),
+                &start_ts,
+                recent_roots,
+                VoidAsyncWriter,
+                options,
+            )
+            .await
+        } else {
+            match tokio::fs::File::create(&output_path).await {
+                Ok(file) => {
+                    crate::chain::export::<Sha256>(
+                        Arc::clone(&data.chain_store.db),
+                        &start_ts,
+                        recent_roots,
+                        file,
+                        options,
+                    )
+                    .await
+                }
+                Err(e) => Err(e.into()),
+            }
+        };
+        *EXPORT_PROGRESS.lock() = None;
+        match result {
+            Ok(checksum_opt) => Ok(checksum_opt.map(|hash| hash.encode_hex())),
+            Err(e) => Err(anyhow::anyhow!(e).into()),
+        }
     }
 }
 
-pub async fn chain_read_obj<DB: Blockstore>(
-    params: Params<'_>,
-    data: Ctx<DB>,
-) -> Result<LotusJson<Vec<u8>>, JsonRpcError> {
-    let LotusJson((obj_cid,)): LotusJson<(Cid,)> = params.parse()?;
-
-    let bytes = data
-        .state_manager
-        .blockstore()
-        .get(&obj_cid)?
-        .context("can't find object with that cid")?;
-    Ok(LotusJson(bytes))
+/// The export in progress, if any.
+static EXPORT_PROGRESS: parking_lot::Mutex<Option<Arc<ExportProgress>>> =
+    parking_lot::const_mutex(None);
+
+pub enum ChainExportStatus {}
+impl RpcMethod<0> for ChainExportStatus {
+    const NAME: &'static str = CHAIN_EXPORT_STATUS;
+    const PARAM_NAMES: [&'static str; 0] = [];
+    const PERMISSION: Permission = Permission::Read;
+    type Params = ();
+    type Ok = Option<ChainExportProgress>;
+
+    async fn handle(_: Ctx<impl Blockstore>, (): Self::Params) -> Result<Self::Ok, JsonRpcError> {
+        let progress = EXPORT_PROGRESS.lock().clone();
+        Ok(progress.map(|progress| ChainExportProgress {
+            start_epoch: progress.start_epoch(),
+            epoch: progress.epoch(),
+            blocks: progress.blocks(),
+        }))
+    }
 }
 
-pub async fn chain_has_obj<DB: Blockstore>(
-    params: Params<'_>,
-    data: Ctx<DB>,
-) -> Result<bool, JsonRpcError> {
-    let LotusJson((obj_cid,)): LotusJson<(Cid,)> = params.parse()?;
+pub enum ChainReadObj {}
+impl RpcMethod<1> for ChainReadObj {
+    const NAME: &'static str = CHAIN_READ_OBJ;
+    const PARAM_NAMES: [&'static str; 1] = ["obj_cid"];
+    const PERMISSION: Permission = Permission::Read;
+    type Params = (LotusJson<Cid>,);
+    type Ok = LotusJson<Vec<u8>>;
 
-    Ok(data.state_manager.blockstore().get(&obj_cid)?.is_some())
+    async fn handle(
+        data: Ctx<impl Blockstore>,
+        (LotusJson(obj_cid),): Self::Params,
+    ) -> Result<Self::Ok, JsonRpcError> {
+        let bytes = data
+            .state_manager
+            .blockstore()
+            .get(&obj_cid)?
+            .context("can't find object with that cid")?;
+        Ok(LotusJson(bytes))
+    }
 }
 
-pub async fn chain_get_block_messages<DB: Blockstore>(
-    params: Params<'_>,
-    data: Ctx<DB>,
-) -> Result<BlockMessages, JsonRpcError> {
-    let LotusJson((blk_cid,)): LotusJson<(Cid,)> = params.parse()?;
-
-    let blk: CachingBlockHeader = data
-        .state_manager
-        .blockstore()
-        .get_cbor(&blk_cid)?
-        .context("can't find block with that cid")?;
-    let blk_msgs = &blk.messages;
-    let (unsigned_cids, signed_cids) =
-        crate::chain::read_msg_cids(data.state_manager.blockstore(), blk_msgs)?;
-    let (bls_msg, secp_msg) = crate::chain::block_messages_from_cids(
-        data.state_manager.blockstore(),
-        &unsigned_cids,
-        &signed_cids,
-    )?;
-    let cids = unsigned_cids
-        .into_iter()
-        .chain(signed_cids)
-        .collect::<Vec<_>>();
+pub enum ChainHasObj {}
+impl RpcMethod<1> for ChainHasObj {
+    const NAME: &'static str = CHAIN_HAS_OBJ;
+    const PARAM_NAMES: [&'static str; 1] = ["obj_cid"];
+    const PERMISSION: Permission = Permission::Read;
+    type Params = (LotusJson<Cid>,);
+    type Ok = bool;
 
-    let ret = BlockMessages {
-        bls_msg,
-        secp_msg,
-        cids,
-    };
-    Ok(ret)
+    async fn handle(
+        data: Ctx<impl Blockstore>,
+        (LotusJson(obj_cid),): Self::Params,
+    ) -> Result<Self::Ok, JsonRpcError> {
+        Ok(data.state_manager.blockstore().get(&obj_cid)?.is_some())
+    }
+}
+
+pub enum ChainGetBlockMessages {}
+impl RpcMethod<1> for ChainGetBlockMessages {
+    const NAME: &'static str = CHAIN_GET_BLOCK_MESSAGES;
+    const PARAM_NAMES: [&'static str; 1] = ["blk_cid"];
+    const PERMISSION: Permission = Permission::Read;
+    type Params = (LotusJson<Cid>,);
+    type Ok = BlockMessages;
+
+    async fn handle(
+        data: Ctx<impl Blockstore>,
+        (LotusJson(blk_cid),): Self::Params,
+    ) -> Result<Self::Ok, JsonRpcError> {
+        let blk: CachingBlockHeader = data
+            .state_manager
+            .blockstore()
+            .get_cbor(&blk_cid)?
+            .context("can't find block with that cid")?;
+        let blk_msgs = &blk.messages;
+        let (unsigned_cids, signed_cids) =
+            crate::chain::read_msg_cids(data.state_manager.blockstore(), blk_msgs)?;
+        let (bls_msg, secp_msg) = crate::chain::block_messages_from_cids(
+            data.state_manager.blockstore(),
+            &unsigned_cids,
+            &signed_cids,
+        )?;
+        let cids = unsigned_cids
+            .into_iter()
+            .chain(signed_cids)
+            .collect::<Vec<_>>();
+
+        let ret = BlockMessages {
+            bls_msg,
+            secp_msg,
+            cids,
+        };
+        Ok(ret)
+    }
 }
 
 pub enum ChainGetPath {}
@@ -274,6 +368,7 @@ pub enum ChainGetPath {}
 impl RpcMethod<2> for ChainGetPath {
     const NAME: &'static str = "Filecoin.ChainGetPath";
     const PARAM_NAMES: [&'static str; 2] = ["from", "to"];
+    const PERMISSION: Permission = Permission::Read;
     type Params = (LotusJson<TipsetKey>, LotusJson<TipsetKey>);
     type Ok = LotusJson<Vec<PathChange>>;
 
@@ -343,168 +438,274 @@ fn impl_chain_get_path(
         .collect())
 }
 
-pub async fn chain_get_tipset_by_height<DB: Blockstore>(
-    params: Params<'_>,
-    data: Ctx<DB>,
-) -> Result<LotusJson<Tipset>, JsonRpcError> {
-    let LotusJson((height, ApiTipsetKey(tsk))): LotusJson<(ChainEpoch, ApiTipsetKey)> =
-        params.parse()?;
-
-    let ts = data
-        .state_manager
-        .chain_store()
-        .load_required_tipset_or_heaviest(&tsk)?;
-    let tss = data
-        .state_manager
-        .chain_store()
-        .chain_index
-        .tipset_by_height(height, ts, ResolveNullTipset::TakeOlder)?;
-    Ok((*tss).clone().into())
+pub enum ChainGetTipSetByHeight {}
+impl RpcMethod<2> for ChainGetTipSetByHeight {
+    const NAME: &'static str = CHAIN_GET_TIPSET_BY_HEIGHT;
+    const PARAM_NAMES: [&'static str; 2] = ["height", "tsk"];
+    const PERMISSION: Permission = Permission::Read;
+    type Params = (ChainEpoch, LotusJson<ApiTipsetKey>);
+    type Ok = LotusJson<Tipset>;
+
+    async fn handle(
+        data: Ctx<impl Blockstore>,
+        (height, LotusJson(ApiTipsetKey(tsk))): Self::Params,
+    ) -> Result<Self::Ok, JsonRpcError> {
+        let ts = data
+            .state_manager
+            .chain_store()
+            .load_required_tipset_or_heaviest(&tsk)?;
+        let tss = data
+            .state_manager
+            .chain_store()
+            .chain_index
+            .tipset_by_height(height, ts, ResolveNullTipset::TakeOlder)?;
+        Ok((*tss).clone().into())
+    }
 }
 
-pub async fn chain_get_tipset_after_height<DB: Blockstore>(
-    params: Params<'_>,
-    data: Ctx<DB>,
-) -> Result<LotusJson<Tipset>, JsonRpcError> {
-    let LotusJson((height, ApiTipsetKey(tsk))): LotusJson<(ChainEpoch, ApiTipsetKey)> =
-        params.parse()?;
-
-    let ts = data
-        .state_manager
-        .chain_store()
-        .load_required_tipset_or_heaviest(&tsk)?;
-    let tss = data
-        .state_manager
-        .chain_store()
-        .chain_index
-        .tipset_by_height(height, ts, ResolveNullTipset::TakeNewer)?;
-    Ok((*tss).clone().into())
+pub enum ChainGetTipSetAfterHeight {}
+impl RpcMethod<2> for ChainGetTipSetAfterHeight {
+    const NAME: &'static str = CHAIN_GET_TIPSET_AFTER_HEIGHT;
+    const PARAM_NAMES: [&'static str; 2] = ["height", "tsk"];
+    const PERMISSION: Permission = Permission::Read;
+    type Params = (ChainEpoch, LotusJson<ApiTipsetKey>);
+    type Ok = LotusJson<Tipset>;
+
+    async fn handle(
+        data: Ctx<impl Blockstore>,
+        (height, LotusJson(ApiTipsetKey(tsk))): Self::Params,
+    ) -> Result<Self::Ok, JsonRpcError> {
+        let ts = data
+            .state_manager
+            .chain_store()
+            .load_required_tipset_or_heaviest(&tsk)?;
+        let tss = data
+            .state_manager
+            .chain_store()
+            .chain_index
+            .tipset_by_height(height, ts, ResolveNullTipset::TakeNewer)?;
+        Ok((*tss).clone().into())
+    }
 }
 
-pub async fn chain_get_genesis<DB: Blockstore>(
-    data: Ctx<DB>,
-) -> Result<Option<LotusJson<Tipset>>, JsonRpcError> {
-    let genesis = data.state_manager.chain_store().genesis_block_header();
-    Ok(Some(Tipset::from(genesis).into()))
+pub enum ChainGetGenesis {}
+impl RpcMethod<0> for ChainGetGenesis {
+    const NAME: &'static str = CHAIN_GET_GENESIS;
+    const PARAM_NAMES: [&'static str; 0] = [];
+    const PERMISSION: Permission = Permission::Read;
+    type Params = ();
+    type Ok = Option<LotusJson<Tipset>>;
+
+    async fn handle(
+        data: Ctx<impl Blockstore>,
+        (): Self::Params,
+    ) -> Result<Self::Ok, JsonRpcError> {
+        let genesis = data.state_manager.chain_store().genesis_block_header();
+        Ok(Some(Tipset::from(genesis).into()))
+    }
 }
 
-pub async fn chain_head<DB: Blockstore>(data: Ctx<DB>) -> Result<LotusJson<Tipset>, JsonRpcError> {
-    let heaviest = data.state_manager.chain_store().heaviest_tipset();
-    Ok((*heaviest).clone().into())
+pub enum ChainHead {}
+impl RpcMethod<0> for ChainHead {
+    const NAME: &'static str = CHAIN_HEAD;
+    const PARAM_NAMES: [&'static str; 0] = [];
+    const PERMISSION: Permission = Permission::Read;
+    type Params = ();
+    type Ok = LotusJson<Tipset>;
+
+    async fn handle(
+        data: Ctx<impl Blockstore>,
+        (): Self::Params,
+    ) -> Result<Self::Ok, JsonRpcError> {
+        let heaviest = data.state_manager.chain_store().heaviest_tipset();
+        Ok((*heaviest).clone().into())
+    }
 }
 
-pub async fn chain_get_block<DB: Blockstore>(
-    params: Params<'_>,
-    data: Ctx<DB>,
-) -> Result<LotusJson<CachingBlockHeader>, JsonRpcError> {
-    let LotusJson((blk_cid,)): LotusJson<(Cid,)> = params.parse()?;
-
-    let blk: CachingBlockHeader = data
-        .state_manager
-        .blockstore()
-        .get_cbor(&blk_cid)?
-        .context("can't find BlockHeader with that cid")?;
-    Ok(blk.into())
+pub enum ChainGetBlock {}
+impl RpcMethod<1> for ChainGetBlock {
+    const NAME: &'static str = CHAIN_GET_BLOCK;
+    const PARAM_NAMES: [&'static str; 1] = ["blk_cid"];
+    const PERMISSION: Permission = Permission::Read;
+    type Params = (LotusJson<Cid>,);
+    type Ok = LotusJson<CachingBlockHeader>;
+
+    async fn handle(
+        data: Ctx<impl Blockstore>,
+        (LotusJson(blk_cid),): Self::Params,
+    ) -> Result<Self::Ok, JsonRpcError> {
+        let blk: CachingBlockHeader = data
+            .state_manager
+            .blockstore()
+            .get_cbor(&blk_cid)?
+            .context("can't find BlockHeader with that cid")?;
+        Ok(blk.into())
+    }
 }
 
-pub async fn chain_get_tipset<DB: Blockstore>(
-    params: Params<'_>,
-    data: Ctx<DB>,
-) -> Result<LotusJson<Tipset>, JsonRpcError> {
-    let LotusJson((ApiTipsetKey(tsk),)): LotusJson<(ApiTipsetKey,)> = params.parse()?;
-
-    let ts = data
-        .state_manager
-        .chain_store()
-        .load_required_tipset_or_heaviest(&tsk)?;
-    Ok((*ts).clone().into())
+pub enum ChainGetTipSet {}
+impl RpcMethod<1> for ChainGetTipSet {
+    const NAME: &'static str = CHAIN_GET_TIPSET;
+    const PARAM_NAMES: [&'static str; 1] = ["tsk"];
+    const PERMISSION: Permission = Permission::Read;
+    type Params = (LotusJson<ApiTipsetKey>,);
+    type Ok = LotusJson<Tipset>;
+
+    async fn handle(
+        data: Ctx<impl Blockstore>,
+        (LotusJson(ApiTipsetKey(tsk)),): Self::Params,
+    ) -> Result<Self::Ok, JsonRpcError> {
+        let ts = data
+            .state_manager
+            .chain_store()
+            .load_required_tipset_or_heaviest(&tsk)?;
+        Ok((*ts).clone().into())
+    }
 }
 
 // This is basically a port of the reference implementation at
 // https://github.com/filecoin-project/lotus/blob/v1.23.0/node/impl/full/chain.go#L321
-pub async fn chain_set_head<DB: Blockstore>(
-    params: Params<'_>,
-    data: Ctx<DB>,
-) -> Result<(), JsonRpcError> {
-    let LotusJson((ApiTipsetKey(tsk),)): LotusJson<(ApiTipsetKey,)> = params.parse()?;
-
-    let new_head = data
-        .state_manager
-        .chain_store()
-        .load_required_tipset_or_heaviest(&tsk)?;
-    let mut current = data.state_manager.chain_store().heaviest_tipset();
-    while current.epoch() >= new_head.epoch() {
-        for cid in current.key().to_cids() {
-            data.state_manager
+pub enum ChainSetHead {}
+impl RpcMethod<1> for ChainSetHead {
+    const NAME: &'static str = CHAIN_SET_HEAD;
+    const PARAM_NAMES: [&'static str; 1] = ["tsk"];
+    const PERMISSION: Permission = Permission::Admin;
+    type Params = (LotusJson<ApiTipsetKey>,);
+    type Ok = ();
+
+    async fn handle(
+        data: Ctx<impl Blockstore>,
+        (LotusJson(ApiTipsetKey(tsk)),): Self::Params,
+    ) -> Result<Self::Ok, JsonRpcError> {
+        let new_head = data
+            .state_manager
+            .chain_store()
+            .load_required_tipset_or_heaviest(&tsk)?;
+        let mut current = data.state_manager.chain_store().heaviest_tipset();
+        while current.epoch() >= new_head.epoch() {
+            for cid in current.key().to_cids() {
+                data.state_manager
+                    .chain_store()
+                    .unmark_block_as_validated(&cid);
+            }
+            let parents = &current.block_headers().first().parents;
+            current = data
+                .state_manager
                 .chain_store()
-                .unmark_block_as_validated(&cid);
+                .chain_index
+                .load_required_tipset(parents)?;
         }
-        let parents = &current.block_headers().first().parents;
-        current = data
-            .state_manager
+        data.state_manager
             .chain_store()
-            .chain_index
-            .load_required_tipset(parents)?;
+            .set_heaviest_tipset(new_head)
+            .map_err(Into::into)
     }
-    data.state_manager
-        .chain_store()
-        .set_heaviest_tipset(new_head)
-        .map_err(Into::into)
 }
 
-pub(crate) async fn chain_get_min_base_fee<DB: Blockstore>(
-    params: Params<'_>,
-    data: Ctx<DB>,
-) -> Result<String, JsonRpcError> {
-    let (basefee_lookback,): (u32,) = params.parse()?;
+/// Requests a run of the garbage collector, which the node otherwise runs
+/// every few hours. Follow its progress with [`ChainPruneStatus`].
+pub enum ChainPrune {}
+impl RpcMethod<0> for ChainPrune {
+    const NAME: &'static str = CHAIN_PRUNE;
+    const PARAM_NAMES: [&'static str; 0] = [];
+    const PERMISSION: Permission = Permission::Admin;
+    type Params = ();
+    type Ok = ();
 
-    let mut current = data.state_manager.chain_store().heaviest_tipset();
-    let mut min_base_fee = current.block_headers().first().parent_base_fee.clone();
+    async fn handle(
+        data: Ctx<impl Blockstore>,
+        (): Self::Params,
+    ) -> Result<Self::Ok, JsonRpcError> {
+        let gc = data.gc.as_ref().context("garbage collection is disabled")?;
+        // A run in progress already answers the request
+        gc.trigger();
+        Ok(())
+    }
+}
 
-    for _ in 0..basefee_lookback {
-        let parents = &current.block_headers().first().parents;
-        current = data
-            .state_manager
-            .chain_store()
-            .chain_index
-            .load_required_tipset(parents)?;
+pub enum ChainPruneStatus {}
+impl RpcMethod<0> for ChainPruneStatus {
+    const NAME: &'static str = CHAIN_PRUNE_STATUS;
+    const PARAM_NAMES: [&'static str; 0] = [];
+    const PERMISSION: Permission = Permission::Read;
+    type Params = ();
+    type Ok = GcProgress;
 
-        min_base_fee = min_base_fee.min(current.block_headers().first().parent_base_fee.to_owned());
+    async fn handle(
+        data: Ctx<impl Blockstore>,
+        (): Self::Params,
+    ) -> Result<Self::Ok, JsonRpcError> {
+        let gc = data.gc.as_ref().context("garbage collection is disabled")?;
+        Ok(gc.progress())
     }
+}
+
+/// Writes the head of the chain to the database again, e.g. before a reboot of
+/// the host, and returns its epoch as read back from the database.
+pub enum ChainFlush {}
+impl RpcMethod<0> for ChainFlush {
+    const NAME: &'static str = CHAIN_FLUSH;
+    const PARAM_NAMES: [&'static str; 0] = [];
+    const PERMISSION: Permission = Permission::Admin;
+    type Params = ();
+    type Ok = ChainEpoch;
 
-    Ok(min_base_fee.atto().to_string())
+    async fn handle(
+        data: Ctx<impl Blockstore>,
+        (): Self::Params,
+    ) -> Result<Self::Ok, JsonRpcError> {
+        let head = data.state_manager.chain_store().flush_head()?;
+        Ok(head.epoch())
+    }
+}
+
+pub enum ChainGetMinBaseFee {}
+impl RpcMethod<1> for ChainGetMinBaseFee {
+    const NAME: &'static str = CHAIN_GET_MIN_BASE_FEE;
+    const PARAM_NAMES: [&'static str; 1] = ["basefee_lookback"];
+    const PERMISSION: Permission = Permission::Admin;
+    type Params = (u32,);
+    type Ok = String;
+
+    async fn handle(
+        data: Ctx<impl Blockstore>,
+        (basefee_lookback,): Self::Params,
+    ) -> Result<Self::Ok, JsonRpcError> {
+        let mut current = data.state_manager.chain_store().heaviest_tipset();
+        let mut min_base_fee = current.block_headers().first().parent_base_fee.clone();
+
+        for _ in 0..basefee_lookback {
+            let parents = &current.block_headers().first().parents;
+            current = data
+                .state_manager
+                .chain_store()
+                .chain_index
+                .load_required_tipset(parents)?;
+
+            min_base_fee =
+                min_base_fee.min(current.block_headers().first().parent_base_fee.to_owned());
+        }
+
+        Ok(min_base_fee.atto().to_string())
+    }
 }
 
 pub(crate) fn chain_notify<DB: Blockstore>(
     _params: Params<'_>,
     data: &crate::rpc::RPCState<DB>,
-) -> Subscriber<Vec<ApiHeadChange>> {
-    let (sender, receiver) = broadcast::channel(100);
-
+) -> BoxStream<'static, Vec<ApiHeadChange>> {
     // As soon as the channel is created, send the current tipset
     let current = data.chain_store.heaviest_tipset();
     let (change, headers) = ("current".into(), current.block_headers().clone().into());
-    sender
-        .send(vec![ApiHeadChange { change, headers }])
-        .expect("receiver is not dropped");
-
-    let mut subscriber = data.chain_store.publisher().subscribe();
-
-    tokio::spawn(async move {
-        while let Ok(v) = subscriber.recv().await {
-            let (change, headers) = match v {
-                HeadChange::Apply(ts) => ("apply".into(), ts.block_headers().clone().into()),
-            };
+    let current = vec![ApiHeadChange { change, headers }];
 
-            if sender
-                .send(vec![ApiHeadChange { change, headers }])
-                .is_err()
-            {
-                break;
-            }
-        }
+    let changes = broadcast_stream(data.chain_store.publisher().subscribe()).map(|v| {
+        let (change, headers) = match v {
+            HeadChange::Apply(ts) => ("apply".into(), ts.block_headers().clone().into()),
+        };
+        vec![ApiHeadChange { change, headers }]
     });
-    receiver
+    stream::once(future::ready(current)).chain(changes).boxed()
 }
 
 fn load_api_messages_from_tipset(
@@ -664,6 +865,7 @@ mod tests {
             ChainStore::new(
                 db,
                 Arc::new(MemoryDB::default()),
+                Arc::new(MemoryDB::default()),
                 Arc::new(ChainConfig::calibnet()),
                 genesis_block_header,
             )
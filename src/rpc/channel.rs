@@ -61,30 +61,78 @@
 //! ```
 
 use ahash::HashMap;
+use futures::stream::{self, BoxStream, StreamExt as _};
 use jsonrpsee::{
     server::{
-        IntoSubscriptionCloseResponse, MethodCallback, Methods, RegisterMethodError,
-        ResponsePayload,
+        ConnectionId, IntoSubscriptionCloseResponse, MethodCallback, Methods, RegisterMethodError,
+        ResponsePayload, SubscriptionPermit,
     },
     types::{error::ErrorCode, ErrorObjectOwned, Id, Params},
     MethodResponse, MethodSink,
 };
+use once_cell::sync::Lazy;
 use parking_lot::Mutex;
+use prometheus_client::metrics::gauge::Gauge;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::broadcast::{self, error::RecvError};
 use tokio::sync::{mpsc, oneshot};
 
 use super::error::JsonRpcError;
 
 pub const NOTIF_METHOD_NAME: &str = "xrpc.ch.val";
 pub const CANCEL_METHOD_NAME: &str = "xrpc.cancel";
+pub const CLOSE_METHOD_NAME: &str = "xrpc.ch.close";
 
 pub type ChannelId = u64;
 
+pub static RPC_ACTIVE_CHANNELS: Lazy<Gauge> = Lazy::new(|| {
+    let metric = Gauge::default();
+    crate::metrics::default_registry().register(
+        "rpc_active_channels",
+        "Number of channels currently sending notifications, across all connections",
+        metric.clone(),
+    );
+    metric
+});
+
+/// Returns the number of channels currently sending notifications.
+pub fn active_channels() -> usize {
+    RPC_ACTIVE_CHANNELS.get() as usize
+}
+
+/// Identifies a channel by the subscription call that opened it. Request ids
+/// are only unique within a connection.
+type ChannelKey = (ConnectionId, Id<'static>);
+
 /// Type-alias for subscribers.
-pub type Subscribers =
-    Arc<Mutex<HashMap<Id<'static>, (MethodSink, mpsc::Receiver<()>, ChannelId)>>>;
+pub type Subscribers = Arc<Mutex<HashMap<ChannelKey, (MethodSink, mpsc::Receiver<()>, ChannelId)>>>;
+
+/// Entry of an accepted channel in the subscribers map. It is removed once
+/// all sinks of the channel are dropped, so that channels ended by a closed
+/// connection are not kept around.
+#[derive(Debug)]
+struct Registration {
+    subscribers: Subscribers,
+    key: ChannelKey,
+    channel_id: ChannelId,
+    /// Counts towards the subscription limit of the connection
+    _permit: SubscriptionPermit,
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        let mut subscribers = self.subscribers.lock();
+        // The entry may already be cancelled, and replaced by a channel
+        // opened with the same request id
+        if subscribers
+            .get(&self.key)
+            .is_some_and(|(_, _, channel_id)| *channel_id == self.channel_id)
+        {
+            subscribers.remove(&self.key);
+        }
+    }
+}
 
 /// Represents a single subscription that is waiting to be accepted or rejected.
 ///
@@ -108,6 +156,10 @@ pub struct PendingSubscriptionSink {
     pub(crate) subscribe: oneshot::Sender<MethodResponse>,
     /// Channel identifier.
     pub(crate) channel_id: ChannelId,
+    /// Connection the subscription call was made on.
+    pub(crate) conn_id: ConnectionId,
+    /// Subscription permit.
+    pub(crate) permit: SubscriptionPermit,
 }
 
 impl PendingSubscriptionSink {
@@ -142,14 +194,21 @@ impl PendingSubscriptionSink {
 
         if success {
             let (tx, rx) = mpsc::channel(1);
+            let key = (self.conn_id, id);
             self.subscribers
                 .lock()
-                .insert(id, (self.inner.clone(), rx, self.channel_id));
+                .insert(key.clone(), (self.inner.clone(), rx, self.channel_id));
             Ok(SubscriptionSink {
                 inner: self.inner,
                 method: self.method,
                 unsubscribe: IsUnsubscribed(tx),
                 channel_id: self.channel_id,
+                _registration: Arc::new(Registration {
+                    subscribers: self.subscribers,
+                    key,
+                    channel_id: self.channel_id,
+                    _permit: self.permit,
+                }),
             })
         } else {
             panic!("The subscription response was too big; adjust the `max_response_size` or change Subscription ID generation");
@@ -184,6 +243,8 @@ pub struct SubscriptionSink {
     unsubscribe: IsUnsubscribed,
     /// Channel identifier.
     channel_id: ChannelId,
+    /// Unregisters the channel once the last sink is dropped.
+    _registration: Arc<Registration>,
 }
 
 impl SubscriptionSink {
@@ -231,6 +292,24 @@ impl SubscriptionSink {
     }
 }
 
+/// Streams the messages of a broadcast channel, skipping those missed by lagging
+/// behind. Unlike forwarding them from a task, nothing is left running once
+/// the stream is dropped.
+pub fn broadcast_stream<T: Clone + Send + 'static>(
+    receiver: broadcast::Receiver<T>,
+) -> BoxStream<'static, T> {
+    stream::unfold(receiver, |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(msg) => return Some((msg, receiver)),
+                Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    })
+    .boxed()
+}
+
 fn create_notif_message(
     sink: &SubscriptionSink,
     result: &impl serde::Serialize,
@@ -249,7 +328,7 @@ fn create_notif_message(
 fn close_payload(channel_id: ChannelId) -> serde_json::Value {
     serde_json::json!({
         "jsonrpc":"2.0",
-        "method":"xrpc.ch.close",
+        "method":CLOSE_METHOD_NAME,
         "params":[channel_id]
     })
 }
@@ -283,16 +362,17 @@ impl Default for RpcModule {
         methods
             .verify_and_insert(
                 CANCEL_METHOD_NAME,
-                MethodCallback::Sync(Arc::new({
+                // Channels can only be cancelled from the connection that opened them
+                MethodCallback::Unsubscription(Arc::new({
                     let channels = channels.clone();
-                    move |id, params: Params, _max_response| {
+                    move |id, params: Params, conn_id, _max_response| {
                         let cb = || {
                             let arr: [Id<'_>; 1] = params.parse()?;
                             let sub_id = arr[0].clone().into_owned();
 
                             tracing::debug!("Got cancel request: id={sub_id}");
 
-                            let opt = channels.lock().remove(&sub_id);
+                            let opt = channels.lock().remove(&(conn_id, sub_id));
                             match opt {
                                 Some((_, _, channel_id)) => {
                                     Ok::<ChannelId, JsonRpcError>(channel_id)
@@ -334,21 +414,25 @@ impl RpcModule {
         callback: F,
     ) -> Result<&mut MethodCallback, RegisterMethodError>
     where
-        F: (Fn(Params) -> tokio::sync::broadcast::Receiver<R>) + Send + Sync + 'static,
-        R: serde::Serialize + Clone + Send + 'static,
+        F: (Fn(Params) -> BoxStream<'static, R>) + Send + Sync + 'static,
+        R: serde::Serialize + Send + 'static,
     {
         self.register_channel_raw(subscribe_method_name, {
             move |params, pending| {
-                let mut receiver = callback(params);
+                let mut stream = callback(params);
                 tokio::spawn(async move {
                     let sink = pending.accept().await.unwrap();
                     tracing::debug!("Channel created: chann_id={}", sink.channel_id);
+                    RPC_ACTIVE_CHANNELS.inc();
+                    let _active = scopeguard::guard((), |()| {
+                        RPC_ACTIVE_CHANNELS.dec();
+                    });
 
                     loop {
                         tokio::select! {
-                            action = receiver.recv() => {
-                                match action {
-                                    Ok(msg) => {
+                            msg = stream.next() => {
+                                match msg {
+                                    Some(msg) => {
                                         match create_notif_message(&sink, &msg) {
                                             Ok(msg) => {
                                                 // This fails only if the connection is closed
@@ -362,12 +446,10 @@ impl RpcModule {
                                             }
                                         }
                                     }
-                                    Err(RecvError::Closed) => {
+                                    None => {
                                         let _ = sink.send(close_payload(sink.channel_id()).to_string()).await;
                                         break;
                                     }
-                                    Err(RecvError::Lagged(_)) => {
-                                    }
                                 }
                             },
                             _ = sink.closed() => {
@@ -399,7 +481,7 @@ impl RpcModule {
             subscribe_method_name,
             MethodCallback::Subscription(Arc::new({
                 let id_provider = self.id_provider.clone();
-                move |id, params, method_sink, _conn| {
+                move |id, params, method_sink, conn| {
                     let channel_id = id_provider.fetch_add(1, Ordering::Relaxed);
 
                     // response to the subscription call.
@@ -412,6 +494,8 @@ impl RpcModule {
                         id: id.clone().into_owned(),
                         subscribe: tx,
                         channel_id,
+                        conn_id: conn.conn_id,
+                        permit: conn.subscription_permit,
                     };
 
                     callback(params, sink);
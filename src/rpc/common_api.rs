@@ -2,7 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 #![allow(clippy::unused_async)]
 
-use crate::rpc::{error::JsonRpcError, RPCState};
+use crate::rpc::{error::JsonRpcError, reflect::openrpc_types::OpenRPC, RPCState};
 use crate::rpc_api::data_types::{APIVersion, Version};
 
 use fvm_ipld_blockstore::Blockstore;
@@ -28,6 +28,11 @@ pub fn version(block_delay: u64, forest_version: &'static str) -> Result<APIVers
     })
 }
 
+/// Describes the methods served by the node.
+pub fn discover(schema: &OpenRPC) -> Result<OpenRPC, JsonRpcError> {
+    Ok(schema.clone())
+}
+
 pub async fn shutdown(shutdown_send: Sender<()>) -> Result<(), JsonRpcError> {
     // Trigger graceful shutdown
     if let Err(err) = shutdown_send.send(()).await {
@@ -0,0 +1,107 @@
+// Copyright 2019-2024 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::time::Duration;
+
+use ahash::HashMap;
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DurationSeconds};
+
+use crate::rpc_api::{chain_api::CHAIN_EXPORT, state_api::*};
+
+/// Default size (10 MiB) is not enough for methods like `Filecoin.StateMinerActiveSectors`
+const DEFAULT_MAX_RESPONSE_BODY_SIZE: u32 = 16 * 1024 * 1024;
+const DEFAULT_MAX_REQUEST_BODY_SIZE: u32 = 10 * 1024 * 1024;
+const DEFAULT_MAX_BATCH_LENGTH: u32 = 100;
+const DEFAULT_METHOD_TIMEOUT: Duration = Duration::from_secs(120);
+const DEFAULT_SHUTDOWN_DRAIN_PERIOD: Duration = Duration::from_secs(30);
+
+/// Structure that defines RPC server configuration options
+#[serde_as]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+#[cfg_attr(test, derive(derive_quickcheck_arbitrary::Arbitrary))]
+#[serde(default)]
+pub struct RpcConfig {
+    /// Origins that browsers may call the RPC API from, e.g.
+    /// `https://example.com`. `*` allows any origin. Empty by default, which
+    /// disables CORS.
+    pub cors_allowed_origins: Vec<String>,
+    /// HTTP methods allowed in cross-origin requests
+    pub cors_allowed_methods: Vec<String>,
+    /// Request headers allowed in cross-origin requests
+    pub cors_allowed_headers: Vec<String>,
+    /// Maximum number of method calls per second across all clients.
+    /// Unlimited if unset.
+    pub rate_limit: Option<u32>,
+    /// Maximum number of method calls per second of a single client, identified
+    /// by its JWT or else its IP address. Unlimited if unset.
+    pub client_rate_limit: Option<u32>,
+    /// Expensive methods whose concurrent calls are capped by
+    /// `max_concurrent_heavy_calls`
+    pub heavy_methods: Vec<String>,
+    /// Maximum number of heavy method calls executing at once. Unlimited if
+    /// unset.
+    pub max_concurrent_heavy_calls: Option<u32>,
+    /// Maximum size in bytes of a request body
+    pub max_request_body_size: u32,
+    /// Maximum size in bytes of a response body. The responses to the calls of
+    /// a batch request count towards it together.
+    pub max_response_body_size: u32,
+    /// Maximum number of calls in a batch request. Zero disables batch
+    /// requests.
+    pub max_batch_length: u32,
+    /// Time limit in seconds for a method call, after which it is cancelled.
+    /// Zero disables the limit.
+    #[serde_as(as = "DurationSeconds<u64>")]
+    #[cfg_attr(test, arbitrary(gen(|g| Duration::from_secs(u32::arbitrary(g).into()))))]
+    pub method_timeout: Duration,
+    /// Time limits in seconds overriding `method_timeout` for specific methods
+    #[serde_as(as = "HashMap<_, DurationSeconds<u64>>")]
+    #[cfg_attr(test, arbitrary(gen(|g| Vec::<(String, u32)>::arbitrary(g)
+        .into_iter()
+        .map(|(method, secs)| (method, Duration::from_secs(secs.into())))
+        .collect()
+    )))]
+    pub method_timeouts: HashMap<String, Duration>,
+    /// Time in seconds given to in-flight calls and subscriptions to finish
+    /// when the node shuts down, after which they are cut off
+    #[serde_as(as = "DurationSeconds<u64>")]
+    #[cfg_attr(test, arbitrary(gen(|g| Duration::from_secs(u32::arbitrary(g).into()))))]
+    pub shutdown_drain_period: Duration,
+}
+
+impl RpcConfig {
+    /// Returns the time limit of a method call, if any.
+    pub fn method_timeout(&self, method: &str) -> Option<Duration> {
+        Some(
+            self.method_timeouts
+                .get(method)
+                .copied()
+                .unwrap_or(self.method_timeout),
+        )
+        .filter(|timeout| !timeout.is_zero())
+    }
+}
+
+impl Default for RpcConfig {
+    fn default() -> Self {
+        Self {
+            cors_allowed_origins: vec![],
+            cors_allowed_methods: vec!["GET".into(), "POST".into()],
+            cors_allowed_headers: vec!["Authorization".into(), "Content-Type".into()],
+            rate_limit: None,
+            client_rate_limit: None,
+            heavy_methods: [CHAIN_EXPORT, STATE_CALL, STATE_COMPUTE, STATE_REPLAY]
+                .map(String::from)
+                .into(),
+            max_concurrent_heavy_calls: None,
+            max_request_body_size: DEFAULT_MAX_REQUEST_BODY_SIZE,
+            max_response_body_size: DEFAULT_MAX_RESPONSE_BODY_SIZE,
+            max_batch_length: DEFAULT_MAX_BATCH_LENGTH,
+            method_timeout: DEFAULT_METHOD_TIMEOUT,
+            // Exporting a snapshot can take hours
+            method_timeouts: HashMap::from_iter([(CHAIN_EXPORT.into(), Duration::ZERO)]),
+            shutdown_drain_period: DEFAULT_SHUTDOWN_DRAIN_PERIOD,
+        }
+    }
+}
@@ -0,0 +1,208 @@
+// Copyright 2019-2024 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::future::{ready, Future};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use hyper::header::{
+    HeaderMap, HeaderValue, ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS,
+    ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_REQUEST_METHOD, ORIGIN, VARY,
+};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use tower::{Layer, Service};
+
+use crate::rpc::RpcConfig;
+
+/// HTTP middleware adding CORS headers to responses for allowed origins.
+/// Preflight requests are answered directly, without reaching the RPC methods
+/// or the authorization layer.
+#[derive(Clone, Debug)]
+pub struct CorsLayer {
+    config: Arc<RpcConfig>,
+}
+
+impl CorsLayer {
+    pub fn new(config: RpcConfig) -> Self {
+        Self {
+            config: Arc::new(config),
+        }
+    }
+
+    /// Returns the value of `Access-Control-Allow-Origin` if the origin of the
+    /// request is allowed.
+    fn allow_origin(&self, headers: &HeaderMap) -> Option<HeaderValue> {
+        let origin = headers.get(ORIGIN)?;
+        self.config
+            .cors_allowed_origins
+            .iter()
+            .find_map(|allowed| match allowed.as_str() {
+                "*" => Some(HeaderValue::from_static("*")),
+                allowed if allowed.as_bytes() == origin.as_bytes() => Some(origin.clone()),
+                _ => None,
+            })
+    }
+
+    fn preflight(&self, allow_origin: Option<HeaderValue>) -> Response<Body> {
+        let mut response = Response::new(Body::empty());
+        *response.status_mut() = StatusCode::NO_CONTENT;
+        let headers = response.headers_mut();
+        headers.insert(VARY, HeaderValue::from_static("Origin"));
+        if let Some(allow_origin) = allow_origin {
+            headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, allow_origin);
+            for (name, values) in [
+                (
+                    ACCESS_CONTROL_ALLOW_METHODS,
+                    &self.config.cors_allowed_methods,
+                ),
+                (
+                    ACCESS_CONTROL_ALLOW_HEADERS,
+                    &self.config.cors_allowed_headers,
+                ),
+            ] {
+                if let Ok(value) = HeaderValue::from_str(&values.join(", ")) {
+                    headers.insert(name, value);
+                }
+            }
+        }
+        response
+    }
+}
+
+impl<S> Layer<S> for CorsLayer {
+    type Service = Cors<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Cors {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Cors<S> {
+    inner: S,
+    layer: CorsLayer,
+}
+
+impl<S> Service<Request<Body>> for Cors<S>
+where
+    S: Service<Request<Body>, Response = Response<Body>>,
+    S::Future: Send + 'static,
+    S::Error: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<Body>) -> Self::Future {
+        let allow_origin = self.layer.allow_origin(req.headers());
+        if req.method() == Method::OPTIONS
+            && req.headers().contains_key(ACCESS_CONTROL_REQUEST_METHOD)
+        {
+            return Box::pin(ready(Ok(self.layer.preflight(allow_origin))));
+        }
+
+        let response = self.inner.call(req);
+        Box::pin(async move {
+            let mut response = response.await?;
+            if let Some(allow_origin) = allow_origin {
+                let headers = response.headers_mut();
+                headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, allow_origin);
+                headers.append(VARY, HeaderValue::from_static("Origin"));
+            }
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn call(config: RpcConfig, req: Request<Body>) -> Response<Body> {
+        let inner = tower::service_fn(|_req: Request<Body>| async {
+            anyhow::Ok(Response::new(Body::from("ok")))
+        });
+        CorsLayer::new(config).layer(inner).call(req).await.unwrap()
+    }
+
+    fn request(method: Method, origin: &str) -> Request<Body> {
+        Request::builder()
+            .method(method)
+            .uri("/rpc/v0")
+            .header(ORIGIN, origin)
+            .header(ACCESS_CONTROL_REQUEST_METHOD, "POST")
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    fn config(origins: &[&str]) -> RpcConfig {
+        RpcConfig {
+            cors_allowed_origins: origins.iter().map(|it| it.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn allowed_origin() {
+        let response = call(
+            config(&["https://example.com"]),
+            request(Method::POST, "https://example.com"),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://example.com"
+        );
+
+        let response = call(
+            config(&["https://example.com"]),
+            request(Method::OPTIONS, "https://example.com"),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(
+            response.headers()[ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://example.com"
+        );
+        assert_eq!(
+            response.headers()[ACCESS_CONTROL_ALLOW_METHODS],
+            "GET, POST"
+        );
+        assert_eq!(
+            response.headers()[ACCESS_CONTROL_ALLOW_HEADERS],
+            "Authorization, Content-Type"
+        );
+    }
+
+    #[tokio::test]
+    async fn any_origin() {
+        let response = call(config(&["*"]), request(Method::POST, "https://example.com")).await;
+        assert_eq!(response.headers()[ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+    }
+
+    #[tokio::test]
+    async fn disallowed_origin() {
+        for config in [config(&[]), config(&["https://example.com"])] {
+            let response = call(config.clone(), request(Method::POST, "https://evil.com")).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert!(!response.headers().contains_key(ACCESS_CONTROL_ALLOW_ORIGIN));
+
+            // Preflights are still answered, but without allowing anything
+            let response = call(config, request(Method::OPTIONS, "https://evil.com")).await;
+            assert_eq!(response.status(), StatusCode::NO_CONTENT);
+            assert!(!response.headers().contains_key(ACCESS_CONTROL_ALLOW_ORIGIN));
+            assert!(!response
+                .headers()
+                .contains_key(ACCESS_CONTROL_ALLOW_METHODS));
+        }
+    }
+}
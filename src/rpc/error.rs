@@ -3,7 +3,21 @@
 
 use std::fmt::{self, Display};
 
+use cid::Cid;
 use jsonrpsee::types::error::{self, ErrorCode, ErrorObjectOwned};
+use serde_json::value::RawValue;
+
+use crate::shim::{address::Address, error::ExitCode};
+
+/// Error code returned when an actor does not exist, the same as Lotus. The
+/// data holds its `address`.
+pub const ACTOR_NOT_FOUND_CODE: i32 = 4;
+/// Error code returned when a message is not on chain. The data holds its
+/// `cid`.
+pub const MESSAGE_NOT_FOUND_CODE: i32 = -32003;
+/// Error code returned when executing a message fails. The data holds the
+/// `exitCode` of the execution.
+pub const EXECUTION_REVERTED_CODE: i32 = -32004;
 
 #[derive(derive_more::From, derive_more::Into, Debug, PartialEq)]
 pub struct JsonRpcError {
@@ -20,12 +34,45 @@ impl JsonRpcError {
             inner: ErrorObjectOwned::owned(code, message.to_string(), data.into()),
         }
     }
+    pub fn code(&self) -> i32 {
+        self.inner.code()
+    }
     pub fn message(&self) -> &str {
         self.inner.message()
     }
+    pub fn data(&self) -> Option<&RawValue> {
+        self.inner.data()
+    }
     pub fn known_code(&self) -> ErrorCode {
         self.inner.code().into()
     }
+
+    pub fn actor_not_found(address: &Address) -> Self {
+        Self::new(
+            ACTOR_NOT_FOUND_CODE,
+            format!("actor not found: {address}"),
+            serde_json::json!({ "address": address.to_string() }),
+        )
+    }
+
+    pub fn message_not_found(cid: &Cid) -> Self {
+        Self::new(
+            MESSAGE_NOT_FOUND_CODE,
+            format!("message not found: {cid}"),
+            serde_json::json!({ "cid": cid.to_string() }),
+        )
+    }
+
+    pub fn execution_reverted(exit_code: ExitCode, reason: impl Display) -> Self {
+        Self::new(
+            EXECUTION_REVERTED_CODE,
+            format!(
+                "message execution failed: exit {}, reason: {reason}",
+                exit_code.value()
+            ),
+            serde_json::json!({ "exitCode": exit_code.value() }),
+        )
+    }
 }
 
 impl Display for JsonRpcError {
@@ -76,14 +123,11 @@ macro_rules! from2internal {
 // TODO(forest): https://github.com/ChainSafe/forest/issues/3965
 //               Just mapping everything to an internal error is not appropriate
 from2internal! {
-    anyhow::Error,
     base64::DecodeError,
     cid::multibase::Error,
     crate::chain::store::Error,
     crate::key_management::Error,
     crate::libp2p::ParseError,
-    crate::message_pool::Error,
-    crate::state_manager::Error,
     fil_actors_shared::fvm_ipld_amt::Error,
     futures::channel::oneshot::Canceled,
     fvm_ipld_encoding::Error,
@@ -94,6 +138,46 @@ from2internal! {
     tokio::task::JoinError,
 }
 
+/// Returns the error with a dedicated code matching `e`, if any.
+fn coded(e: &(dyn std::error::Error + 'static)) -> Option<JsonRpcError> {
+    use crate::{message_pool, state_manager};
+    match e.downcast_ref() {
+        Some(state_manager::Error::ActorNotFound(address)) => {
+            return Some(JsonRpcError::actor_not_found(address))
+        }
+        Some(state_manager::Error::MessageNotFound(cid)) => {
+            return Some(JsonRpcError::message_not_found(cid))
+        }
+        _ => {}
+    }
+    match e.downcast_ref() {
+        Some(message_pool::Error::ActorNotFound(address)) => {
+            Some(JsonRpcError::actor_not_found(address))
+        }
+        _ => None,
+    }
+}
+
+impl From<crate::state_manager::Error> for JsonRpcError {
+    fn from(e: crate::state_manager::Error) -> Self {
+        coded(&e).unwrap_or_else(|| Self::internal_error(e, None))
+    }
+}
+
+impl From<crate::message_pool::Error> for JsonRpcError {
+    fn from(e: crate::message_pool::Error) -> Self {
+        coded(&e).unwrap_or_else(|| Self::internal_error(e, None))
+    }
+}
+
+impl From<anyhow::Error> for JsonRpcError {
+    fn from(e: anyhow::Error) -> Self {
+        // Errors with a dedicated code keep it when passed around as `anyhow`,
+        // unless context was added to them
+        coded(e.as_ref()).unwrap_or_else(|| Self::internal_error(e, None))
+    }
+}
+
 impl<T> From<flume::SendError<T>> for JsonRpcError {
     fn from(e: flume::SendError<T>) -> Self {
         Self::internal_error(e, None)
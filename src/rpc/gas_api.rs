@@ -2,26 +2,44 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 #![allow(clippy::unused_async)]
 
-use crate::blocks::TipsetKey;
-use crate::chain::{BASE_FEE_MAX_CHANGE_DENOM, BLOCK_GAS_TARGET, MINIMUM_BASE_FEE};
+use crate::blocks::{Tipset, TipsetKey};
+use crate::chain::{BASE_FEE_MAX_CHANGE_DENOM, MINIMUM_BASE_FEE};
 use crate::lotus_json::LotusJson;
 use crate::message::{ChainMessage, Message as MessageTrait};
+use crate::networks::Height;
 use crate::rpc::error::JsonRpcError;
 use crate::rpc::Ctx;
 use crate::rpc_api::data_types::*;
 use crate::shim::address::Address;
+use crate::shim::clock::ChainEpoch;
 use crate::shim::econ::BLOCK_GAS_LIMIT;
-use crate::shim::{econ::TokenAmount, message::Message};
+use crate::shim::{
+    econ::TokenAmount,
+    message::{Message, METHOD_SEND},
+};
+use crate::state_manager::InvocResult;
+use fil_actor_interface::is_miner_actor;
 use fvm_ipld_blockstore::Blockstore;
 use jsonrpsee::types::Params;
 use num::BigInt;
 use num_traits::{FromPrimitive, Zero};
 use rand_distr::{Distribution, Normal};
+use std::sync::Arc;
 
 use anyhow::{Context, Result};
 
 const MIN_GAS_PREMIUM: f64 = 100000.0;
 
+/// Number of recent tipsets whose gas usage the base fee is projected from
+const BASE_FEE_TREND_TIPSETS: usize = 5;
+
+/// Number of epochs estimated messages are expected to be included within
+const INCLUSION_EPOCHS: u64 = 10;
+
+/// Maximum fee in nanoFIL of a message when none is specified, 0.07 FIL like
+/// Lotus
+const DEFAULT_MAX_FEE_NANO: u64 = 70_000_000;
+
 /// Estimate the fee cap
 pub async fn gas_estimate_fee_cap<DB: Blockstore>(
     params: Params<'_>,
@@ -37,9 +55,12 @@ fn estimate_fee_cap<DB: Blockstore>(
     data: &Ctx<DB>,
     msg: Message,
     max_queue_blks: i64,
-    _: ApiTipsetKey,
+    ApiTipsetKey(tsk): ApiTipsetKey,
 ) -> Result<TokenAmount, JsonRpcError> {
-    let ts = data.state_manager.chain_store().heaviest_tipset();
+    let ts = data
+        .state_manager
+        .chain_store()
+        .load_required_tipset_or_heaviest(&tsk)?;
 
     let parent_base_fee = &ts.block_headers().first().parent_base_fee;
     let increase_factor =
@@ -48,11 +69,54 @@ fn estimate_fee_cap<DB: Blockstore>(
     let fee_in_future = parent_base_fee
         * BigInt::from_f64(increase_factor * (1 << 8) as f64)
             .context("failed to convert fee_in_future f64 to bigint")?;
-    let mut out: crate::shim::econ::TokenAmount = fee_in_future.div_floor(1 << 8);
+    let fee_in_future: TokenAmount = fee_in_future.div_floor(1 << 8);
+    // `fee_in_future` grows the base fee paid by `ts`, while the projection
+    // starts from the base fee of the tipset after `ts`, so it can be higher
+    // by one epoch of growth when `ts` uses more gas than targeted.
+    let projected = projected_base_fee(data, &ts, max_queue_blks.max(0) as u64)?;
+    let mut out = fee_in_future.max(projected);
     out += msg.gas_premium();
     Ok(out)
 }
 
+/// Projects the base fee `epochs` after the tipset following `ts`, from the
+/// gas usage trend of `ts` and the tipsets before it.
+fn projected_base_fee<DB: Blockstore>(
+    data: &Ctx<DB>,
+    ts: &Tipset,
+    epochs: u64,
+) -> Result<TokenAmount, JsonRpcError> {
+    let db = data.state_manager.blockstore();
+    let smoke_height = data.state_manager.chain_config().epoch(Height::Smoke);
+    let next_base_fee = crate::chain::compute_base_fee(db, ts, smoke_height)?;
+    let gas_usage = recent_gas_usage(db, ts, BASE_FEE_TREND_TIPSETS)?;
+    Ok(crate::chain::project_base_fee(
+        &next_base_fee,
+        &gas_usage,
+        epochs,
+    ))
+}
+
+/// Returns the gas limit used and the number of blocks of `ts` and the tipsets
+/// before it, up to `tipsets` of them, from the oldest.
+fn recent_gas_usage<DB: Blockstore>(
+    db: &DB,
+    ts: &Tipset,
+    tipsets: usize,
+) -> anyhow::Result<Vec<(u64, usize)>> {
+    let mut gas_usage = ts
+        .clone()
+        .chain(db)
+        .take(tipsets)
+        .map(|ts| {
+            let gas_limit_used = crate::chain::tipset_gas_limit(db, &ts)?;
+            Ok((gas_limit_used, ts.block_headers().len()))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    gas_usage.reverse();
+    Ok(gas_usage)
+}
+
 /// Estimate the fee cap
 pub async fn gas_estimate_gas_premium<DB: Blockstore>(
     params: Params<'_>,
@@ -78,13 +142,7 @@ pub async fn estimate_gas_premium<DB: Blockstore>(
         nblocksincl = 1;
     }
 
-    struct GasMeta {
-        pub price: TokenAmount,
-        pub limit: u64,
-    }
-
     let mut prices: Vec<GasMeta> = Vec::new();
-    let mut blocks = 0;
 
     let mut ts = data.state_manager.chain_store().heaviest_tipset();
 
@@ -97,7 +155,6 @@ pub async fn estimate_gas_premium<DB: Blockstore>(
             .chain_store()
             .chain_index
             .load_required_tipset(ts.parents())?;
-        blocks += pts.block_headers().len();
         let msgs = crate::chain::messages_for_tipset(data.state_manager.blockstore_owned(), &pts)?;
 
         prices.append(
@@ -112,31 +169,13 @@ pub async fn estimate_gas_premium<DB: Blockstore>(
         ts = pts;
     }
 
-    prices.sort_by(|a, b| b.price.cmp(&a.price));
-    let mut at = BLOCK_GAS_TARGET * blocks as u64 / 2;
-    let mut prev = TokenAmount::zero();
-    let mut premium = TokenAmount::zero();
-
-    for price in prices {
-        at -= price.limit;
-        if at > 0 {
-            prev = price.price;
-            continue;
-        }
-        if prev == TokenAmount::zero() {
-            let ret: TokenAmount = price.price + TokenAmount::from_atto(1);
-            return Ok(ret);
-        }
-        premium = (&price.price + &prev).div_floor(2) + TokenAmount::from_atto(1)
-    }
-
-    if premium == TokenAmount::zero() {
-        premium = TokenAmount::from_atto(match nblocksincl {
-            1 => (MIN_GAS_PREMIUM * 2.0) as u64,
-            2 => (MIN_GAS_PREMIUM * 1.5) as u64,
-            _ => MIN_GAS_PREMIUM as u64,
-        });
-    }
+    let floor = TokenAmount::from_atto(match nblocksincl {
+        1 => (MIN_GAS_PREMIUM * 2.0) as u64,
+        2 => (MIN_GAS_PREMIUM * 1.5) as u64,
+        _ => MIN_GAS_PREMIUM as u64,
+    });
+    let mut premium = premium_percentile(prices, data.mpool.config.gas_premium_percentile)
+        .unwrap_or_else(|| floor.clone());
 
     let precision = 32;
 
@@ -149,7 +188,30 @@ pub async fn estimate_gas_premium<DB: Blockstore>(
         .context("failed to convert gas premium f64 to bigint")?;
     premium = premium.div_floor(1i64 << precision);
 
-    Ok(premium)
+    Ok(premium.max(floor))
+}
+
+struct GasMeta {
+    price: TokenAmount,
+    limit: u64,
+}
+
+/// Returns the gas premium that messages paid for `percentile` percent of the
+/// gas they were given, at most, or `None` without messages.
+fn premium_percentile(mut prices: Vec<GasMeta>, percentile: f64) -> Option<TokenAmount> {
+    prices.sort_by(|a, b| a.price.cmp(&b.price));
+    let total: u64 = prices.iter().map(|price| price.limit).sum();
+    let target = (total as f64 * percentile.clamp(0.0, 100.0) / 100.0).ceil() as u64;
+    let mut at = 0;
+    let mut last = None;
+    for price in prices {
+        at += price.limit;
+        if at >= target {
+            return Some(price.price);
+        }
+        last = Some(price.price);
+    }
+    last
 }
 
 /// Estimate the gas limit
@@ -168,7 +230,7 @@ where
 async fn estimate_gas_limit<DB>(
     data: &Ctx<DB>,
     msg: Message,
-    _: ApiTipsetKey,
+    ApiTipsetKey(tsk): ApiTipsetKey,
 ) -> Result<i64, JsonRpcError>
 where
     DB: Blockstore + Send + Sync + 'static,
@@ -178,36 +240,112 @@ where
     msg.set_gas_fee_cap(TokenAmount::from_atto(MINIMUM_BASE_FEE + 1));
     msg.set_gas_premium(TokenAmount::from_atto(1));
 
-    let curr_ts = data.state_manager.chain_store().heaviest_tipset();
-    let from_a = data
-        .state_manager
-        .resolve_to_key_addr(&msg.from, &curr_ts)
-        .await?;
+    let (prior_messages, ts) = prior_messages_and_tipset(data, &msg.from, tsk.as_ref()).await?;
+    let multiplier = transitional_multiplier(data, &ts, &msg)?;
 
-    let pending = data.mpool.pending_for(&from_a);
-    let prior_messages: Vec<ChainMessage> = pending
-        .map(|s| s.into_iter().map(ChainMessage::Signed).collect::<Vec<_>>())
-        .unwrap_or_default();
-
-    let ts = data.mpool.cur_tipset.lock().clone();
     let res = data
         .state_manager
         .call_with_gas(&mut ChainMessage::Unsigned(msg), &prior_messages, Some(ts))
         .await?;
-    match res.msg_rct {
-        Some(rct) => {
-            if rct.exit_code().value() != 0 {
-                return Ok(-1);
-            }
-            // TODO(forest): https://github.com/ChainSafe/forest/issues/901
-            //               Figure out why we always under estimate the gas
-            //               calculation so we dont need to add 200000
-            Ok(rct.gas_used() as i64 + 200000)
+    gas_used_by(res, multiplier)
+}
+
+/// Returns the messages to apply before the estimated one, and the tipset to
+/// apply them on top of. The pending messages of the sender only apply on top
+/// of the tipset of the message pool, so there are none on an explicit tipset.
+async fn prior_messages_and_tipset<DB>(
+    data: &Ctx<DB>,
+    from: &Address,
+    tsk: Option<&TipsetKey>,
+) -> Result<(Vec<ChainMessage>, Arc<Tipset>), JsonRpcError>
+where
+    DB: Blockstore + Send + Sync + 'static,
+{
+    match tsk {
+        None => {
+            let curr_ts = data.state_manager.chain_store().heaviest_tipset();
+            let from_a = data
+                .state_manager
+                .resolve_to_key_addr(from, &curr_ts)
+                .await?;
+
+            let pending = data.mpool.pending_for(&from_a);
+            let prior_messages: Vec<ChainMessage> = pending
+                .map(|s| s.into_iter().map(ChainMessage::Signed).collect::<Vec<_>>())
+                .unwrap_or_default();
+            Ok((prior_messages, data.mpool.cur_tipset.lock().clone()))
         }
-        None => Ok(-1),
+        Some(tsk) => Ok((
+            vec![],
+            data.state_manager
+                .chain_store()
+                .chain_index
+                .load_required_tipset(tsk)?,
+        )),
     }
 }
 
+/// Returns the gas used by the estimated message according to the result of
+/// its execution, scaled by `multiplier`.
+fn gas_used_by(res: InvocResult, multiplier: f64) -> Result<i64, JsonRpcError> {
+    let Some(rct) = res.msg_rct else {
+        return Err(anyhow::anyhow!(
+            "message execution failed without a receipt: {}",
+            res.error.unwrap_or_default()
+        )
+        .into());
+    };
+    if rct.exit_code().value() != 0 {
+        return Err(JsonRpcError::execution_reverted(
+            rct.exit_code().into(),
+            res.error.unwrap_or_default(),
+        ));
+    }
+    // TODO(forest): https://github.com/ChainSafe/forest/issues/901
+    //               Figure out why we always under estimate the gas
+    //               calculation so we dont need to add 200000
+    let gas_used = rct.gas_used() as i64 + 200000;
+    Ok((gas_used * (multiplier * 1024.0) as i64) >> 10)
+}
+
+/// Number of epochs before an upgrade that make messages more expensive, in
+/// which the gas used by messages is overestimated
+const TRANSITIONAL_EPOCHS: ChainEpoch = 20;
+
+/// Returns the multiplier of the gas used by `msg` executed on top of `ts`,
+/// which overestimates it right before upgrades that make it more expensive,
+/// like Lotus does.
+fn transitional_multiplier<DB: Blockstore>(
+    data: &Ctx<DB>,
+    ts: &Tipset,
+    msg: &Message,
+) -> anyhow::Result<f64> {
+    let hygge = data.state_manager.chain_config().epoch(Height::Hygge);
+    if ts.epoch() > hygge || hygge - ts.epoch() > TRANSITIONAL_EPOCHS {
+        return Ok(1.0);
+    }
+    // Bare transfers get about 3x more expensive, see FIP-0057
+    if msg.method_num == METHOD_SEND {
+        return Ok(3.0);
+    }
+    let multiplier = match data.state_manager.get_actor(&msg.to, *ts.parent_state())? {
+        Some(actor) if is_miner_actor(&actor.code) => match msg.method_num {
+            3 => 1.92,
+            4 => 1.72,
+            6 => 1.06,
+            7 => 1.2,
+            16 => 1.19,
+            18 => 1.73,
+            23 => 1.73,
+            26 => 1.15,
+            27 => 1.18,
+            _ => 1.0,
+        },
+        _ => 1.0,
+    };
+    Ok(multiplier)
+}
+
 /// Estimates the gas parameters for a given message
 pub async fn gas_estimate_message_gas<DB>(
     params: Params<'_>,
@@ -227,7 +365,7 @@ where
 pub async fn estimate_message_gas<DB>(
     data: &Ctx<DB>,
     msg: Message,
-    _spec: Option<MessageSendSpec>,
+    spec: Option<MessageSendSpec>,
     tsk: ApiTipsetKey,
 ) -> Result<Message, JsonRpcError>
 where
@@ -236,18 +374,371 @@ where
     let mut msg = msg;
     if msg.gas_limit == 0 {
         let gl = estimate_gas_limit::<DB>(data, msg.clone(), tsk.clone()).await?;
-        msg.set_gas_limit(gl as u64);
+        msg.set_gas_limit(overestimated_gas_limit(
+            gl,
+            data.mpool.config.gas_limit_overestimation,
+        ));
     }
     if msg.gas_premium.is_zero() {
-        let gp = estimate_gas_premium(data, 10).await?;
+        let gp = estimate_gas_premium(data, INCLUSION_EPOCHS).await?;
         msg.set_gas_premium(gp);
     }
     if msg.gas_fee_cap.is_zero() {
-        let gfp = estimate_fee_cap(data, msg.clone(), 20, tsk)?;
+        let gfp = estimate_fee_cap(data, msg.clone(), 20, tsk.clone())?;
         msg.set_gas_fee_cap(gfp);
     }
+    let max_fee = spec
+        .map(|spec| spec.max_fee)
+        .filter(|max_fee| !max_fee.is_zero())
+        .unwrap_or_else(|| TokenAmount::from_nano(DEFAULT_MAX_FEE_NANO));
+    let ts = data
+        .state_manager
+        .chain_store()
+        .load_required_tipset_or_heaviest(&tsk.0)?;
+    let base_fee = projected_base_fee(data, &ts, INCLUSION_EPOCHS)?;
+    cap_gas_fee(&mut msg, &max_fee, &base_fee)?;
     // TODO(forest): https://github.com/ChainSafe/forest/issues/901
     //               Figure out why we always under estimate the gas
     //               calculation so we dont need to add 200000
     Ok(msg)
 }
+
+/// Scales the estimated gas limit by `overestimation`, within the block gas
+/// limit.
+fn overestimated_gas_limit(gas_limit: i64, overestimation: f64) -> u64 {
+    ((gas_limit as f64 * overestimation) as u64).min(BLOCK_GAS_LIMIT)
+}
+
+/// Caps the fee cap of `msg` so that it pays `max_fee` at most, and the
+/// premium at the fee cap. Fails if paying the base fee projected for the
+/// inclusion of `msg` exceeds `max_fee` already.
+fn cap_gas_fee(
+    msg: &mut Message,
+    max_fee: &TokenAmount,
+    base_fee: &TokenAmount,
+) -> anyhow::Result<()> {
+    let gas_limit = msg.gas_limit();
+    if gas_limit == 0 {
+        return Ok(());
+    }
+    let fee = base_fee * gas_limit;
+    if &fee > max_fee {
+        anyhow::bail!(
+            "estimated fee {fee} for the projected base fee {base_fee} exceeds the max fee {max_fee}"
+        );
+    }
+    if &(msg.gas_fee_cap() * gas_limit) > max_fee {
+        msg.set_gas_fee_cap(max_fee.div_floor(gas_limit));
+        msg.set_gas_premium(msg.gas_premium().min(msg.gas_fee_cap()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::{chain4u, CachingBlockHeader, HeaderBuilder, RawBlockHeader};
+    use crate::chain::{project_base_fee, BLOCK_GAS_TARGET};
+    use crate::chain_sync::TipsetValidator;
+    use crate::db::MemoryDB;
+    use crate::rpc::error::EXECUTION_REVERTED_CODE;
+    use crate::rpc::RPCState;
+    use crate::shim::executor::Receipt;
+    use crate::shim::state_tree::{ActorState, StateTree, StateTreeVersion};
+    use crate::test_utils::persist_chain;
+    use crate::utils::db::CborStoreExt as _;
+    use cid::Cid;
+    use fil_actor_interface::KNOWN_CIDS;
+    use fvm_shared4::error::ExitCode;
+
+    // Builds a chain on top of a block without messages, with a tipset of one
+    // block per gas limit, including a message with that gas limit.
+    fn chain_with_gas_limits(db: &MemoryDB, gas_limits: &[u64]) -> Tipset {
+        let genesis = CachingBlockHeader::new(RawBlockHeader {
+            messages: TipsetValidator::compute_msg_root(db, &[], &[]).unwrap(),
+            ..Default::default()
+        });
+        db.put_cbor_default(&genesis).unwrap();
+
+        let genesis = Tipset::from(genesis);
+        let mut gas_limits = gas_limits.iter().enumerate();
+        let epochs = 1..=gas_limits.len() as ChainEpoch;
+        let chain = persist_chain(db, &genesis, epochs, |_, _| {
+            let (sequence, &gas_limit) = gas_limits.next().unwrap();
+            let message = Message {
+                from: Address::new_id(100),
+                sequence: sequence as u64,
+                gas_limit,
+                ..Default::default()
+            };
+            db.put_cbor_default(&message).unwrap();
+            RawBlockHeader {
+                messages: TipsetValidator::compute_msg_root(db, &[message], &[]).unwrap(),
+                ..Default::default()
+            }
+        });
+        chain.last().unwrap_or(&genesis).clone()
+    }
+
+    #[test]
+    fn base_fee_projected_from_rising_gas_usage() {
+        let db = MemoryDB::default();
+        let head = chain_with_gas_limits(
+            &db,
+            &[
+                0,
+                BLOCK_GAS_TARGET / 2,
+                BLOCK_GAS_TARGET,
+                BLOCK_GAS_TARGET * 3 / 2,
+            ],
+        );
+
+        let gas_usage = recent_gas_usage(&db, &head, 3).unwrap();
+        assert_eq!(
+            gas_usage,
+            [
+                (BLOCK_GAS_TARGET / 2, 1),
+                (BLOCK_GAS_TARGET, 1),
+                (BLOCK_GAS_TARGET * 3 / 2, 1)
+            ]
+        );
+        // 2.0 then 2.5, capped at 2.0, of the target
+        assert_eq!(
+            project_base_fee(&TokenAmount::from_atto(100_000_000), &gas_usage, 2),
+            TokenAmount::from_atto(126_562_500)
+        );
+    }
+
+    #[test]
+    fn base_fee_projected_from_falling_gas_usage() {
+        let db = MemoryDB::default();
+        let head = chain_with_gas_limits(
+            &db,
+            &[
+                BLOCK_GAS_LIMIT,
+                BLOCK_GAS_TARGET * 7 / 4,
+                BLOCK_GAS_TARGET * 3 / 2,
+            ],
+        );
+
+        // Includes the block without messages the chain is built on
+        let gas_usage = recent_gas_usage(&db, &head, 10).unwrap();
+        assert_eq!(gas_usage.len(), 4);
+        let gas_usage = recent_gas_usage(&db, &head, 3).unwrap();
+        // 1.25 then 1.0 of the target
+        assert_eq!(
+            project_base_fee(&TokenAmount::from_atto(100_000_000), &gas_usage, 2),
+            TokenAmount::from_atto(103_125_000)
+        );
+    }
+
+    fn gas_meta(price: u64, limit: u64) -> GasMeta {
+        GasMeta {
+            price: TokenAmount::from_atto(price),
+            limit,
+        }
+    }
+
+    #[test]
+    fn premium_percentile_is_weighted_by_gas() {
+        let prices = || vec![gas_meta(300, 10), gas_meta(100, 70), gas_meta(200, 20)];
+        let percentile = |percentile| premium_percentile(prices(), percentile);
+        assert_eq!(percentile(0.0), Some(TokenAmount::from_atto(100)));
+        assert_eq!(percentile(50.0), Some(TokenAmount::from_atto(100)));
+        assert_eq!(percentile(70.0), Some(TokenAmount::from_atto(100)));
+        assert_eq!(percentile(71.0), Some(TokenAmount::from_atto(200)));
+        assert_eq!(percentile(90.0), Some(TokenAmount::from_atto(200)));
+        assert_eq!(percentile(95.0), Some(TokenAmount::from_atto(300)));
+        assert_eq!(percentile(100.0), Some(TokenAmount::from_atto(300)));
+        assert_eq!(premium_percentile(vec![], 50.0), None);
+    }
+
+    #[test]
+    fn cap_gas_fee_caps_at_the_max_fee() {
+        let message = |fee_cap, premium| Message {
+            gas_limit: 1_000,
+            gas_fee_cap: TokenAmount::from_atto(fee_cap),
+            gas_premium: TokenAmount::from_atto(premium),
+            ..Default::default()
+        };
+        let max_fee = TokenAmount::from_atto(500_000);
+        let base_fee = TokenAmount::from_atto(100);
+
+        let mut msg = message(400, 300);
+        cap_gas_fee(&mut msg, &max_fee, &base_fee).unwrap();
+        assert_eq!(msg, message(400, 300));
+
+        let mut msg = message(1_000, 600);
+        cap_gas_fee(&mut msg, &max_fee, &base_fee).unwrap();
+        assert_eq!(msg, message(500, 500));
+
+        let mut msg = message(1_000, 600);
+        cap_gas_fee(&mut msg, &max_fee, &TokenAmount::from_atto(501)).unwrap_err();
+    }
+
+    /// Tipset at `epoch` whose state holds a miner `f01000` and an account
+    /// `f01001`.
+    fn tipset_with_miner(db: &Arc<impl Blockstore>, epoch: ChainEpoch) -> Tipset {
+        let mut state_tree = StateTree::new(db.clone(), StateTreeVersion::V5).unwrap();
+        for (id, code) in [
+            (1000, KNOWN_CIDS.actor.miner.v13.calibnet),
+            (1001, KNOWN_CIDS.actor.account.v13.calibnet),
+        ] {
+            state_tree
+                .set_actor(
+                    &Address::new_id(id),
+                    ActorState::new(code, Cid::default(), TokenAmount::default(), 0, None),
+                )
+                .unwrap();
+        }
+        Tipset::from(CachingBlockHeader::new(RawBlockHeader {
+            epoch,
+            state_root: state_tree.flush().unwrap(),
+            ..Default::default()
+        }))
+    }
+
+    #[tokio::test]
+    async fn gas_is_overestimated_right_before_hygge() {
+        let data = Arc::new(Arc::new(RPCState::calibnet()));
+        let db = data.state_manager.blockstore_owned();
+        let hygge = data.state_manager.chain_config().epoch(Height::Hygge);
+        let message = |to, method_num| Message {
+            to: Address::new_id(to),
+            method_num,
+            ..Default::default()
+        };
+        let multiplier = |epoch, msg: &Message| {
+            transitional_multiplier(&data, &tipset_with_miner(&db, epoch), msg).unwrap()
+        };
+
+        for epoch in [hygge - TRANSITIONAL_EPOCHS, hygge - 1, hygge] {
+            assert_eq!(multiplier(epoch, &message(1001, METHOD_SEND)), 3.0);
+            assert_eq!(multiplier(epoch, &message(1000, METHOD_SEND)), 3.0);
+            assert_eq!(multiplier(epoch, &message(1000, 6)), 1.06);
+            assert_eq!(multiplier(epoch, &message(1000, 27)), 1.18);
+            // Unlisted miner methods and other actors are not overestimated
+            assert_eq!(multiplier(epoch, &message(1000, 5)), 1.0);
+            assert_eq!(multiplier(epoch, &message(1001, 6)), 1.0);
+            assert_eq!(multiplier(epoch, &message(1002, 6)), 1.0);
+        }
+        for epoch in [hygge - TRANSITIONAL_EPOCHS - 1, hygge + 1] {
+            assert_eq!(multiplier(epoch, &message(1001, METHOD_SEND)), 1.0);
+            assert_eq!(multiplier(epoch, &message(1000, 6)), 1.0);
+        }
+    }
+
+    #[tokio::test]
+    async fn explicit_tipset_has_no_prior_messages() {
+        let data = Arc::new(Arc::new(RPCState::calibnet()));
+        chain4u! {
+            in data.chain_store.blockstore();
+            [_genesis = data.chain_store.genesis_block_header()]
+            -> [block = HeaderBuilder::default()]
+        };
+        let block = CachingBlockHeader::new(block.clone());
+        let from = Address::new_bls(&[0; 48]).unwrap();
+
+        let (prior_messages, ts) = prior_messages_and_tipset(&data, &from, None).await.unwrap();
+        assert!(prior_messages.is_empty());
+        assert_eq!(ts, data.mpool.cur_tipset.lock().clone());
+
+        let (prior_messages, ts) =
+            prior_messages_and_tipset(&data, &from, Some(&Tipset::from(&block).key().clone()))
+                .await
+                .unwrap();
+        assert!(prior_messages.is_empty());
+        assert_eq!(ts.key(), Tipset::from(&block).key());
+        assert_ne!(ts, data.mpool.cur_tipset.lock().clone());
+    }
+
+    #[tokio::test]
+    async fn fee_cap_is_estimated_at_the_given_tipset() {
+        let data = Arc::new(Arc::new(RPCState::calibnet()));
+        let db = data.chain_store.blockstore();
+        let messages = TipsetValidator::compute_msg_root(db, &[], &[]).unwrap();
+        chain4u! {
+            in db;
+            [_genesis = data.chain_store.genesis_block_header()]
+            -> [block = HeaderBuilder::default()
+                .with_messages(messages)
+                .with_parent_base_fee(TokenAmount::from_atto(MINIMUM_BASE_FEE))]
+        };
+        let tsk = ApiTipsetKey(Some(
+            Tipset::from(CachingBlockHeader::new(block.clone()))
+                .key()
+                .clone(),
+        ));
+        let msg = Message {
+            gas_limit: 1_000,
+            gas_fee_cap: TokenAmount::from_atto(1),
+            gas_premium: TokenAmount::from_atto(1),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            estimate_fee_cap(&data, msg.clone(), 0, tsk.clone()).unwrap(),
+            TokenAmount::from_atto(MINIMUM_BASE_FEE + 1)
+        );
+        // The base fee projected at `block` is paid within the max fee, but not
+        // the one projected at the head
+        let spec = || {
+            Some(MessageSendSpec {
+                max_fee: TokenAmount::from_atto(100_000_000),
+            })
+        };
+        estimate_message_gas(&data, msg.clone(), spec(), tsk)
+            .await
+            .unwrap();
+        estimate_message_gas(&data, msg, spec(), Default::default())
+            .await
+            .unwrap_err();
+    }
+
+    #[test]
+    fn gas_used_by_the_receipt() {
+        let result = |exit_code: u32, gas_used: u64| InvocResult {
+            msg: Message::default(),
+            msg_rct: Some(Receipt::V4(fvm_shared4::receipt::Receipt {
+                exit_code: ExitCode::new(exit_code),
+                return_data: Default::default(),
+                gas_used,
+                events_root: None,
+            })),
+            error: (exit_code != 0).then(|| "failed".into()),
+        };
+        assert_eq!(gas_used_by(result(0, 1_000_000), 1.0).unwrap(), 1_200_000);
+        assert_eq!(gas_used_by(result(0, 1_000_000), 3.0).unwrap(), 3_600_000);
+        assert_eq!(gas_used_by(result(0, 1_000_000), 1.06).unwrap(), 1_271_484);
+
+        let err = gas_used_by(result(16, 1_000_000), 1.0).unwrap_err();
+        assert_eq!(err.code(), EXECUTION_REVERTED_CODE);
+
+        let err = gas_used_by(
+            InvocResult {
+                msg: Message::default(),
+                msg_rct: None,
+                error: Some("out of gas".into()),
+            },
+            1.0,
+        )
+        .unwrap_err();
+        assert!(
+            err.message()
+                .contains("message execution failed without a receipt: out of gas"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn overestimated_gas_limit_is_capped_at_the_block_gas_limit() {
+        assert_eq!(overestimated_gas_limit(1_000_000, 1.25), 1_250_000);
+        let limit = BLOCK_GAS_LIMIT as i64;
+        assert_eq!(
+            overestimated_gas_limit(limit / 2, 1.25),
+            BLOCK_GAS_LIMIT * 5 / 8
+        );
+        assert_eq!(overestimated_gas_limit(limit, 1.25), BLOCK_GAS_LIMIT);
+        assert_eq!(overestimated_gas_limit(limit * 2, 1.0), BLOCK_GAS_LIMIT);
+    }
+}
@@ -0,0 +1,135 @@
+// Copyright 2019-2024 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use crate::rpc::reflect::MethodPermissions;
+
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use jsonrpsee::server::middleware::rpc::RpcServiceT;
+use jsonrpsee::MethodResponse;
+use once_cell::sync::Lazy;
+use prometheus_client::{
+    encoding::EncodeLabelSet,
+    metrics::{counter::Counter, family::Family, gauge::Gauge, histogram::Histogram},
+};
+use tower::Layer;
+
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Label used for calls to methods that are not registered, so that arbitrary
+/// method names cannot blow up the number of series.
+const UNKNOWN_METHOD: &str = "unknown";
+
+pub static RPC_METHOD_SUCCESS: Lazy<Family<MethodLabel, Counter>> = Lazy::new(|| {
+    let metric = Family::default();
+    crate::metrics::default_registry().register(
+        "rpc_method_success",
+        "Number of successful RPC method calls",
+        metric.clone(),
+    );
+    metric
+});
+pub static RPC_METHOD_FAILURE: Lazy<Family<MethodErrorLabel, Counter>> = Lazy::new(|| {
+    let metric = Family::default();
+    crate::metrics::default_registry().register(
+        "rpc_method_failure",
+        "Number of failed RPC method calls by error code",
+        metric.clone(),
+    );
+    metric
+});
+pub static RPC_METHOD_TIME: Lazy<Family<MethodLabel, Histogram>> = Lazy::new(|| {
+    let metric = Family::new_with_constructor(crate::metrics::default_histogram as _);
+    crate::metrics::default_registry().register(
+        "rpc_method_time",
+        "Duration of RPC method calls in seconds",
+        metric.clone(),
+    );
+    metric
+});
+pub static RPC_INFLIGHT_REQUESTS: Lazy<Gauge> = Lazy::new(|| {
+    let metric = Gauge::default();
+    crate::metrics::default_registry().register(
+        "rpc_inflight_requests",
+        "Number of RPC method calls currently being processed",
+        metric.clone(),
+    );
+    metric
+});
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct MethodLabel {
+    method: &'static str,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct MethodErrorLabel {
+    method: &'static str,
+    code: i32,
+}
+
+/// Records call counts, errors and latencies of RPC methods.
+#[derive(Clone)]
+pub struct MetricsLayer {
+    pub permissions: Arc<MethodPermissions>,
+}
+
+impl<S> Layer<S> for MetricsLayer {
+    type Service = MetricsMiddleware<S>;
+
+    fn layer(&self, service: S) -> Self::Service {
+        MetricsMiddleware {
+            permissions: self.permissions.clone(),
+            service,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct MetricsMiddleware<S> {
+    permissions: Arc<MethodPermissions>,
+    service: S,
+}
+
+impl<'a, S> RpcServiceT<'a> for MetricsMiddleware<S>
+where
+    S: RpcServiceT<'a> + Send + Sync + Clone + 'static,
+{
+    type Future = BoxFuture<'a, MethodResponse>;
+
+    fn call(&self, req: jsonrpsee::types::Request<'a>) -> Self::Future {
+        // Every registered method has a permission, so its name is a bounded
+        // label value
+        let method = self
+            .permissions
+            .get_key_value(req.method_name())
+            .map(|(method, _)| *method)
+            .unwrap_or(UNKNOWN_METHOD);
+        let service = self.service.clone();
+
+        async move {
+            RPC_INFLIGHT_REQUESTS.inc();
+            // Also decrements if the call is cancelled
+            let _inflight = scopeguard::guard((), |()| {
+                RPC_INFLIGHT_REQUESTS.dec();
+            });
+            let start = Instant::now();
+            let response = service.call(req).await;
+            RPC_METHOD_TIME
+                .get_or_create(&MethodLabel { method })
+                .observe(start.elapsed().as_secs_f64());
+
+            match response.as_error_code() {
+                None => RPC_METHOD_SUCCESS
+                    .get_or_create(&MethodLabel { method })
+                    .inc(),
+                Some(code) => RPC_METHOD_FAILURE
+                    .get_or_create(&MethodErrorLabel { method, code })
+                    .inc(),
+            };
+            response
+        }
+        .boxed()
+    }
+}
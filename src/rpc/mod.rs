@@ -7,84 +7,135 @@ mod beacon_api;
 mod chain_api;
 mod channel;
 mod common_api;
+mod config;
+mod cors;
 mod eth_api;
 mod gas_api;
+mod metrics_layer;
 mod mpool_api;
 mod net_api;
 mod node_api;
+mod rate_limit;
 mod state_api;
 mod sync_api;
+mod timeout_layer;
 mod wallet_api;
 
+pub use channel::{ChannelId, CLOSE_METHOD_NAME, NOTIF_METHOD_NAME};
+pub use config::RpcConfig;
 pub use error::JsonRpcError;
-use reflect::Ctx;
 pub use reflect::RpcMethodExt;
+use reflect::{Ctx, MethodPermissions, Permission};
 mod error;
 mod reflect;
 
-use std::net::SocketAddr;
+use std::fs::File;
+use std::io::BufReader;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 
 use crate::key_management::KeyStore;
-use crate::rpc::auth_layer::AuthLayer;
+use crate::rpc::auth_layer::{AuthContext, AuthLayer};
 use crate::rpc::channel::RpcModule as FilRpcModule;
 pub use crate::rpc::channel::CANCEL_METHOD_NAME;
+use crate::rpc::cors::CorsLayer;
+use crate::rpc::metrics_layer::MetricsLayer;
+use crate::rpc::rate_limit::{RateLimitLayer, RateLimiter};
+use crate::rpc::timeout_layer::TimeoutLayer;
 use crate::rpc::{
-    beacon_api::beacon_get_entry,
-    common_api::{session, shutdown, start_time, version},
+    beacon_api::{beacon_get_entry, BeaconGetLatest},
+    common_api::{discover, session, shutdown, start_time, version},
     state_api::*,
 };
 use crate::rpc_api::{
     auth_api::*, beacon_api::*, chain_api::*, common_api::*, eth_api::*, gas_api::*, mpool_api::*,
-    net_api::*, node_api::NODE_STATUS, state_api::*, sync_api::*, wallet_api::*,
+    net_api::*, node_api::NODE_STATUS, state_api::*, sync_api::*, wallet_api::*, ApiVersion,
 };
 
+use anyhow::Context as _;
 use fvm_ipld_blockstore::Blockstore;
-use hyper::server::conn::AddrStream;
+use hyper::server::conn::{AddrIncoming, AddrStream, Http};
 use hyper::service::{make_service_fn, service_fn};
 use jsonrpsee::{
     core::RegisterMethodError,
-    server::{stop_channel, RpcModule, RpcServiceBuilder, Server, StopHandle, TowerServiceBuilder},
+    server::{
+        BatchRequestConfig, RpcModule, RpcServiceBuilder, Server, ServerHandle, StopHandle,
+        TowerServiceBuilder,
+    },
     Methods,
 };
+use rustls_pemfile::Item;
+use tokio::net::{TcpListener, UnixListener, UnixStream};
 use tokio::sync::mpsc::Sender;
 use tokio::sync::RwLock;
+use tokio_rustls::rustls::{Certificate, PrivateKey, ServerConfig};
+use tokio_rustls::TlsAcceptor;
 use tower::Service;
-use tracing::info;
+use tracing::{debug, info, warn};
 
-use self::chain_api::ChainGetPath;
-use self::reflect::openrpc_types::ParamStructure;
-
-const MAX_RESPONSE_BODY_SIZE: u32 = 16 * 1024 * 1024;
+use self::chain_api::{
+    ChainExport, ChainExportStatus, ChainFlush, ChainGetBlock, ChainGetBlockMessages,
+    ChainGetGenesis, ChainGetMessage, ChainGetMessagesInTipset, ChainGetMinBaseFee,
+    ChainGetParentMessages, ChainGetParentReceipts, ChainGetPath, ChainGetTipSet,
+    ChainGetTipSetAfterHeight, ChainGetTipSetByHeight, ChainHasObj, ChainHead, ChainPrune,
+    ChainPruneStatus, ChainReadObj, ChainSetHead,
+};
+use self::reflect::openrpc_types::{
+    ContentDescriptor, Info, Method, OpenRPC, ParamStructure, Params,
+};
+use schemars::schema::Schema;
 
 /// This is where you store persistent data, or at least access to stateful
 /// data.
 pub struct RPCState<DB> {
     pub keystore: Arc<RwLock<KeyStore>>,
+    pub tokens: Arc<crate::auth::TokenRegistry>,
     pub chain_store: Arc<crate::chain::ChainStore<DB>>,
     pub state_manager: Arc<crate::state_manager::StateManager<DB>>,
     pub mpool: Arc<crate::message_pool::MessagePool<crate::message_pool::MpoolRpcProvider<DB>>>,
     pub bad_blocks: Arc<crate::chain_sync::BadBlockCache>,
     pub sync_state: Arc<parking_lot::RwLock<crate::chain_sync::SyncState>>,
     pub network_send: flume::Sender<crate::libp2p::NetworkMessage>,
+    pub tipset_send: flume::Sender<Arc<crate::blocks::Tipset>>,
+    pub incoming_blocks: tokio::sync::broadcast::Sender<crate::blocks::CachingBlockHeader>,
     pub network_name: String,
     pub start_time: chrono::DateTime<chrono::Utc>,
     pub beacon: Arc<crate::beacon::BeaconSchedule>,
+    pub bandwidth: Arc<crate::libp2p::BandwidthSinks>,
+    pub net_limits: Arc<crate::libp2p::ConnectionWatermarks>,
+    /// Garbage collector of the database, unless disabled.
+    pub gc: Option<Arc<crate::db::GcHandle>>,
 }
 
+type ServerModule<DB> = RpcModule<Arc<RPCState<DB>>>;
+
 #[derive(Clone)]
 struct PerConnection<RpcMiddleware, HttpMiddleware> {
-    methods: Methods,
+    v0_methods: Methods,
+    v1_methods: Methods,
     stop_handle: StopHandle,
     svc_builder: TowerServiceBuilder<RpcMiddleware, HttpMiddleware>,
     keystore: Arc<RwLock<KeyStore>>,
+    tokens: Arc<crate::auth::TokenRegistry>,
+    permissions: Arc<MethodPermissions>,
+    rate_limiter: Arc<RateLimiter>,
+    rpc_config: Arc<RpcConfig>,
 }
 
+/// Serves RPC on all of `rpc_listeners`. They are bound by the caller, which can
+/// thus learn their addresses when binding to port 0.
+#[allow(clippy::too_many_arguments)]
 pub async fn start_rpc<DB>(
     state: RPCState<DB>,
-    rpc_endpoint: SocketAddr,
+    rpc_listeners: Vec<TcpListener>,
+    tls_config: Option<Arc<ServerConfig>>,
+    rpc_unix_socket: Option<PathBuf>,
+    rpc_config: RpcConfig,
     forest_version: &'static str,
     shutdown_send: Sender<()>,
+    stop_handle: StopHandle,
 ) -> anyhow::Result<()>
 where
     DB: Blockstore + Send + Sync + 'static,
@@ -92,100 +143,439 @@ where
     // `Arc` is needed because we will share the state between two modules
     let state = Arc::new(state);
     let keystore = state.keystore.clone();
-    let (mut module, _schema) = create_module(state.clone());
-
-    // TODO(forest): https://github.com/ChainSafe/forest/issues/4032
-    #[allow(deprecated)]
-    register_methods(
-        &mut module,
-        u64::from(state.state_manager.chain_config().block_delay_secs),
+    let tokens = state.tokens.clone();
+    let (v0_module, _schema, mut permissions) = build_module(
+        state.clone(),
         forest_version,
-        shutdown_send,
+        shutdown_send.clone(),
+        ApiVersion::V0,
     )?;
+    let (v1_module, _schema, v1_permissions) =
+        build_module(state, forest_version, shutdown_send, ApiVersion::V1)?;
+    permissions.extend(v1_permissions);
 
-    let mut pubsub_module = FilRpcModule::default();
-
-    pubsub_module.register_channel("Filecoin.ChainNotify", {
-        let state_clone = state.clone();
-        move |params| chain_api::chain_notify(params, &state_clone)
-    })?;
-    module.merge(pubsub_module)?;
-
-    let (stop_handle, _handle) = stop_channel();
+    let rate_limiter = Arc::new(RateLimiter::new(&rpc_config));
 
     let per_conn = PerConnection {
-        methods: module.into(),
+        v0_methods: v0_module.into(),
+        v1_methods: v1_module.into(),
         stop_handle: stop_handle.clone(),
         svc_builder: Server::builder()
-            // Default size (10 MiB) is not enough for methods like `Filecoin.StateMinerActiveSectors`
-            .max_response_body_size(MAX_RESPONSE_BODY_SIZE)
+            .set_http_middleware(
+                tower::ServiceBuilder::new().layer(CorsLayer::new(rpc_config.clone())),
+            )
+            .max_request_body_size(rpc_config.max_request_body_size)
+            .max_response_body_size(rpc_config.max_response_body_size)
+            .set_batch_request_config(match rpc_config.max_batch_length {
+                0 => BatchRequestConfig::Disabled,
+                len => BatchRequestConfig::Limit(len),
+            })
             .to_service_builder(),
         keystore,
+        tokens,
+        permissions: Arc::new(permissions),
+        rate_limiter,
+        rpc_config: Arc::new(rpc_config),
     };
 
-    let make_service = make_service_fn(move |_conn: &AddrStream| {
+    // The same service is used for both the TCP and the Unix socket listeners.
+    // Clients connected through the Unix socket have no remote address.
+    let rpc_service = move |remote: Option<IpAddr>| {
         let per_conn = per_conn.clone();
 
-        async move {
-            anyhow::Ok(service_fn(move |req| {
-                let PerConnection {
-                    methods,
-                    stop_handle,
-                    svc_builder,
-                    keystore,
-                } = per_conn.clone();
-
-                let headers = req.headers().clone();
-                let rpc_middleware = RpcServiceBuilder::new().layer(AuthLayer {
-                    headers,
+        service_fn(move |req| {
+            let PerConnection {
+                v0_methods,
+                v1_methods,
+                stop_handle,
+                svc_builder,
+                keystore,
+                tokens,
+                permissions,
+                rate_limiter,
+                rpc_config,
+            } = per_conn.clone();
+
+            let headers = req.headers();
+            // For WebSocket connections, this is the upgrade request, so the
+            // token is verified once for the whole session
+            let auth = Arc::new(AuthContext::new(headers));
+            // The RPC middlewares apply to each call of a batch separately, so
+            // that an unauthorized call only fails on its own
+            let rpc_middleware = RpcServiceBuilder::new()
+                .layer(MetricsLayer {
+                    permissions: permissions.clone(),
+                })
+                .layer(AuthLayer {
+                    auth: auth.clone(),
                     keystore: keystore.clone(),
-                });
+                    tokens: tokens.clone(),
+                    permissions: permissions.clone(),
+                })
+                // Only authorized calls count towards the limits, so a client
+                // cannot exhaust the budget of another with a forged token
+                .layer(RateLimitLayer {
+                    limiter: rate_limiter,
+                    auth,
+                    remote,
+                })
+                .layer(TimeoutLayer { config: rpc_config });
 
-                let mut svc = svc_builder
-                    .set_rpc_middleware(rpc_middleware)
-                    .build(methods, stop_handle);
+            // Requests to any other path are served by the v0 API
+            let methods = match req.uri().path() {
+                path if path == format!("/{}", ApiVersion::V1.endpoint()) => v1_methods,
+                _ => v0_methods,
+            };
+            let mut svc = svc_builder
+                .set_rpc_middleware(rpc_middleware)
+                .build(methods, stop_handle);
 
-                async move { svc.call(req).await }
+            // A concrete error type is needed for the connection futures to be
+            // provably `Send` when spawned for TLS connections
+            async move { svc.call(req).await.map_err(|e| anyhow::anyhow!(e)) }
+        })
+    };
+
+    let tcp_servers = rpc_listeners
+        .into_iter()
+        .map(|listener| {
+            let rpc_service = rpc_service.clone();
+            let stop_handle = stop_handle.clone();
+            let tls_config = tls_config.clone();
+            async move {
+                match tls_config {
+                    Some(tls_config) => {
+                        let acceptor = TlsAcceptor::from(tls_config);
+                        let stopped = stop_handle.clone().shutdown();
+                        tokio::pin!(stopped);
+                        loop {
+                            let (stream, addr) = tokio::select! {
+                                conn = listener.accept() => conn?,
+                                _ = &mut stopped => break,
+                            };
+                            let (acceptor, svc) = (acceptor.clone(), rpc_service(Some(addr.ip())));
+                            let stop_handle = stop_handle.clone();
+                            // Handshakes are done per connection so a slow client cannot
+                            // hold up the accept loop
+                            tokio::spawn(async move {
+                                let result = match acceptor.accept(stream).await {
+                                    Ok(stream) => {
+                                        let conn = Http::new()
+                                            .serve_connection(stream, svc)
+                                            .with_upgrades();
+                                        tokio::pin!(conn);
+                                        tokio::select! {
+                                            result = conn.as_mut() => result,
+                                            _ = stop_handle.shutdown() => {
+                                                conn.as_mut().graceful_shutdown();
+                                                conn.await
+                                            }
+                                        }
+                                        .map_err(anyhow::Error::from)
+                                    }
+                                    Err(e) => Err(e.into()),
+                                };
+                                if let Err(e) = result {
+                                    debug!("TLS RPC connection error: {e}");
+                                }
+                            });
+                        }
+                        anyhow::Ok(())
+                    }
+                    None => {
+                        hyper::Server::builder(AddrIncoming::from_listener(listener)?)
+                            .serve(make_service_fn(move |conn: &AddrStream| {
+                                let svc = rpc_service(Some(conn.remote_addr().ip()));
+                                async move { anyhow::Ok(svc) }
+                            }))
+                            .with_graceful_shutdown(stop_handle.shutdown())
+                            .await?;
+                        anyhow::Ok(())
+                    }
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+    let unix_listener = rpc_unix_socket.map(bind_unix_socket).transpose()?;
+    let unix_server = async move {
+        let Some((listener, _guard)) = unix_listener else {
+            return anyhow::Ok(());
+        };
+        let incoming = hyper::server::accept::poll_fn(move |cx| {
+            listener
+                .poll_accept(cx)
+                .map(|conn| Some(conn.map(|(stream, _addr)| stream)))
+        });
+        hyper::Server::builder(incoming)
+            .serve(make_service_fn(move |_conn: &UnixStream| {
+                let svc = rpc_service(None);
+                async move { anyhow::Ok(svc) }
             }))
-        }
-    });
+            .with_graceful_shutdown(stop_handle.shutdown())
+            .await?;
+        Ok(())
+    };
 
     info!("Ready for RPC connections");
-    hyper::Server::bind(&rpc_endpoint)
-        .serve(make_service)
-        .await?;
+    tokio::try_join!(futures::future::try_join_all(tcp_servers), unix_server)?;
 
     info!("Stopped accepting RPC connections");
 
     Ok(())
 }
 
+/// Stops the RPC server started with the [`StopHandle`] of `handle`, and waits
+/// up to `drain_period` for in-flight calls and subscriptions to finish.
+pub async fn stop_rpc(handle: ServerHandle, drain_period: Duration) {
+    if handle.stop().is_err() {
+        return;
+    }
+    if tokio::time::timeout(drain_period, handle.stopped())
+        .await
+        .is_err()
+    {
+        warn!(
+            "Cut off {} in-flight RPC calls and {} subscriptions after waiting {}s",
+            metrics_layer::RPC_INFLIGHT_REQUESTS.get(),
+            channel::active_channels(),
+            drain_period.as_secs_f64(),
+        );
+    } else {
+        info!("RPC server stopped");
+    }
+}
+
+/// Loads a PEM encoded certificate chain and private key for serving RPC over
+/// TLS. Both HTTP/2 and HTTP/1.1 are offered through ALPN.
+pub fn load_tls_config(cert_path: &Path, key_path: &Path) -> anyhow::Result<Arc<ServerConfig>> {
+    let mut reader = BufReader::new(
+        File::open(cert_path)
+            .with_context(|| format!("failed to open TLS certificate {}", cert_path.display()))?,
+    );
+    let certs: Vec<_> = rustls_pemfile::certs(&mut reader)?
+        .into_iter()
+        .map(Certificate)
+        .collect();
+    anyhow::ensure!(
+        !certs.is_empty(),
+        "no certificate found in {}",
+        cert_path.display()
+    );
+
+    let mut reader = BufReader::new(
+        File::open(key_path)
+            .with_context(|| format!("failed to open TLS private key {}", key_path.display()))?,
+    );
+    let key = loop {
+        match rustls_pemfile::read_one(&mut reader)? {
+            Some(Item::RSAKey(key) | Item::PKCS8Key(key) | Item::ECKey(key)) => {
+                break PrivateKey(key)
+            }
+            Some(_) => continue,
+            None => anyhow::bail!("no private key found in {}", key_path.display()),
+        }
+    };
+
+    let mut config = ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)?;
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(Arc::new(config))
+}
+
+/// Removes the Unix socket file once the listener is dropped.
+struct UnixSocketGuard(PathBuf);
+
+impl Drop for UnixSocketGuard {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.0) {
+            warn!("Failed to remove RPC socket {}: {e}", self.0.display());
+        }
+    }
+}
+
+/// Binds a Unix socket that only the current user may connect to. A stale
+/// socket left behind by a previous run is replaced.
+fn bind_unix_socket(path: PathBuf) -> anyhow::Result<(UnixListener, UnixSocketGuard)> {
+    use std::os::unix::fs::{FileTypeExt as _, PermissionsExt as _};
+
+    if let Ok(metadata) = std::fs::symlink_metadata(&path) {
+        anyhow::ensure!(
+            metadata.file_type().is_socket(),
+            "{} already exists and is not a socket",
+            path.display()
+        );
+        std::fs::remove_file(&path)?;
+    }
+    // Bind inside a private directory first, so that nobody can connect before
+    // the permissions are restricted
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let staging = tempfile::Builder::new()
+        .prefix(".forest-rpc")
+        .tempdir_in(parent)?;
+    let staging_path = staging.path().join("rpc.sock");
+    let listener = UnixListener::bind(&staging_path)
+        .with_context(|| format!("failed to bind RPC socket {}", path.display()))?;
+    std::fs::set_permissions(&staging_path, std::fs::Permissions::from_mode(0o600))?;
+    std::fs::rename(&staging_path, &path)?;
+    let guard = UnixSocketGuard(path);
+    info!("JSON-RPC endpoint will listen at {}", guard.0.display());
+    Ok((listener, guard))
+}
+
+/// Creates the module serving every RPC method of the given API version, along
+/// with its `OpenRPC` document, served by `Filecoin.Discover`, and the
+/// permissions required to call each method.
+fn build_module<DB>(
+    state: Arc<RPCState<DB>>,
+    forest_version: &'static str,
+    shutdown_send: Sender<()>,
+    api_version: ApiVersion,
+) -> anyhow::Result<(ServerModule<DB>, OpenRPC, MethodPermissions)>
+where
+    DB: Blockstore + Send + Sync + 'static,
+{
+    let (mut module, schema, mut permissions) = create_module(state.clone(), forest_version);
+    let mut stubs = vec![];
+
+    // TODO(forest): https://github.com/ChainSafe/forest/issues/4032
+    #[allow(deprecated)]
+    register_methods(
+        &mut module,
+        &mut permissions,
+        &mut stubs,
+        u64::from(state.state_manager.chain_config().block_delay_secs),
+        forest_version,
+        shutdown_send,
+        api_version,
+    )?;
+
+    let mut pubsub_module = FilRpcModule::default();
+
+    pubsub_module.register_channel(CHAIN_NOTIFY, {
+        let state_clone = state.clone();
+        move |params| chain_api::chain_notify(params, &state_clone)
+    })?;
+    pubsub_module.register_channel(SYNC_INCOMING_BLOCKS, {
+        let state_clone = state.clone();
+        move |params| sync_api::sync_incoming_blocks(params, &state_clone)
+    })?;
+    for (name, arity) in [
+        (CHAIN_NOTIFY, 0),
+        (SYNC_INCOMING_BLOCKS, 0),
+        (CANCEL_METHOD_NAME, 1),
+        (DISCOVER, 0),
+    ] {
+        permissions.insert(name, Permission::Read);
+        stubs.push(method_stub(name, arity));
+    }
+    module.merge(pubsub_module)?;
+
+    let schema = OpenRPC {
+        methods: reflect::openrpc_types::Methods::new(schema.methods.into_iter().chain(stubs))?,
+        ..schema
+    };
+    module.register_method(DISCOVER, {
+        let schema = schema.clone();
+        move |_, _| discover(&schema)
+    })?;
+    Ok((module, schema, permissions))
+}
+
 fn create_module<DB>(
     state: Arc<RPCState<DB>>,
+    forest_version: &str,
 ) -> (
-    RpcModule<Arc<RPCState<DB>>>,
+    ServerModule<DB>,
     reflect::openrpc_types::OpenRPC,
+    MethodPermissions,
 )
 where
     DB: Blockstore + Send + Sync + 'static,
 {
-    let mut module = reflect::SelfDescribingRpcModule::new(state, ParamStructure::ByPosition);
+    let mut module = reflect::SelfDescribingRpcModule::new(state, ParamStructure::Either);
+    BeaconGetLatest::register(&mut module);
+    ChainGetMessage::register(&mut module);
+    ChainGetParentMessages::register(&mut module);
+    ChainGetParentReceipts::register(&mut module);
+    ChainGetMessagesInTipset::register(&mut module);
+    ChainExport::register(&mut module);
+    ChainExportStatus::register(&mut module);
+    ChainReadObj::register(&mut module);
+    ChainHasObj::register(&mut module);
+    ChainGetBlockMessages::register(&mut module);
     ChainGetPath::register(&mut module);
-    module.finish()
+    ChainGetTipSetByHeight::register(&mut module);
+    ChainGetTipSetAfterHeight::register(&mut module);
+    ChainGetGenesis::register(&mut module);
+    ChainHead::register(&mut module);
+    ChainGetBlock::register(&mut module);
+    ChainGetTipSet::register(&mut module);
+    ChainSetHead::register(&mut module);
+    ChainPrune::register(&mut module);
+    ChainPruneStatus::register(&mut module);
+    ChainFlush::register(&mut module);
+    ChainGetMinBaseFee::register(&mut module);
+    StateGetActor::register(&mut module);
+    StateNetworkIdentity::register(&mut module);
+    StateMigrationStatus::register(&mut module);
+    StateMinerInfo::register(&mut module);
+    StateMinerActiveSectors::register(&mut module);
+    StateMinerSectorCount::register(&mut module);
+    StateMinerFaults::register(&mut module);
+    StateMinerFaultsByDeadline::register(&mut module);
+    StateMinerRecoveries::register(&mut module);
+    StateMinerAvailableBalance::register(&mut module);
+    StateMinerPower::register(&mut module);
+    StateMinerDeadlines::register(&mut module);
+    StateMinerCurrentDeadline::register(&mut module);
+    StateGetRandomnessFromTickets::register(&mut module);
+    StateGetRandomnessFromBeacon::register(&mut module);
+    module.finish(Info {
+        title: String::from("forest"),
+        version: String::from(forest_version),
+    })
+}
+
+/// Describes a method registered through [`register_methods`], whose parameter
+/// and result types are unknown.
+fn method_stub(name: &str, arity: usize) -> Method {
+    let any = || Schema::Bool(true);
+    Method {
+        name: String::from(name),
+        params: Params::new((1..=arity).map(|ix| ContentDescriptor {
+            name: format!("p{ix}"),
+            schema: any(),
+            required: true,
+        }))
+        .expect("parameter names are unique"),
+        param_structure: ParamStructure::ByPosition,
+        result: Some(ContentDescriptor {
+            name: format!("{name}::Result"),
+            schema: any(),
+            required: false,
+        }),
+    }
 }
 
 #[deprecated = "methods should use `create_module`"]
 fn register_methods<DB>(
     module: &mut RpcModule<Arc<RPCState<DB>>>,
+    permissions: &mut MethodPermissions,
+    stubs: &mut Vec<Method>,
     block_delay: u64,
     forest_version: &'static str,
     shutdown_send: Sender<()>,
+    api_version: ApiVersion,
 ) -> Result<(), RegisterMethodError>
 where
     DB: Blockstore + Send + Sync + 'static,
 {
     use auth_api::*;
-    use chain_api::*;
     use eth_api::*;
     use gas_api::*;
     use mpool_api::*;
@@ -194,144 +584,248 @@ where
     use sync_api::*;
     use wallet_api::*;
 
+    // Records the permission required to call a method, and describes it in
+    // the `OpenRPC` document by its number of parameters.
+    macro_rules! describe {
+        ($name:expr, $permission:ident, $arity:expr) => {
+            permissions.insert($name, Permission::$permission);
+            stubs.push(method_stub($name, $arity));
+        };
+    }
+    macro_rules! register {
+        ($name:expr, $permission:ident, $arity:expr, $method:expr $(,)?) => {
+            describe!($name, $permission, $arity);
+            module.register_async_method($name, $method)?;
+        };
+    }
+
     // Auth API
-    module.register_async_method(AUTH_NEW, auth_new::<DB>)?;
-    module.register_async_method(AUTH_VERIFY, auth_verify::<DB>)?;
+    register!(AUTH_NEW, Admin, 3, auth_new::<DB>);
+    register!(AUTH_VERIFY, Read, 1, auth_verify::<DB>);
+    register!(AUTH_LIST, Admin, 0, |_, state| auth_list::<DB>(state));
+    register!(AUTH_REVOKE, Admin, 1, auth_revoke::<DB>);
     // Beacon API
-    module.register_async_method(BEACON_GET_ENTRY, beacon_get_entry::<DB>)?;
-    // Chain API
-    module.register_async_method(CHAIN_GET_MESSAGE, chain_get_message::<DB>)?;
-    module.register_async_method(CHAIN_EXPORT, chain_export::<DB>)?;
-    module.register_async_method(CHAIN_READ_OBJ, chain_read_obj::<DB>)?;
-    module.register_async_method(CHAIN_HAS_OBJ, chain_has_obj::<DB>)?;
-    module.register_async_method(CHAIN_GET_BLOCK_MESSAGES, chain_get_block_messages::<DB>)?;
-    module.register_async_method(CHAIN_GET_TIPSET_BY_HEIGHT, chain_get_tipset_by_height::<DB>)?;
-    module.register_async_method(
-        CHAIN_GET_TIPSET_AFTER_HEIGHT,
-        chain_get_tipset_after_height::<DB>,
-    )?;
-    module.register_async_method(CHAIN_GET_GENESIS, |_, state| chain_get_genesis::<DB>(state))?;
-    module.register_async_method(CHAIN_GET_TIPSET, chain_get_tipset::<DB>)?;
-    module.register_async_method(CHAIN_HEAD, |_, state| chain_head::<DB>(state))?;
-    module.register_async_method(CHAIN_GET_BLOCK, chain_get_block::<DB>)?;
-    module.register_async_method(CHAIN_SET_HEAD, chain_set_head::<DB>)?;
-    module.register_async_method(CHAIN_GET_MIN_BASE_FEE, chain_get_min_base_fee::<DB>)?;
-    module.register_async_method(
-        CHAIN_GET_MESSAGES_IN_TIPSET,
-        chain_get_messages_in_tipset::<DB>,
-    )?;
-    module.register_async_method(CHAIN_GET_PARENT_MESSAGES, chain_get_parent_messages::<DB>)?;
-    module.register_async_method(CHAIN_GET_PARENT_RECEIPTS, chain_get_parent_receipts::<DB>)?;
+    register!(BEACON_GET_ENTRY, Read, 1, beacon_get_entry::<DB>);
     // Message Pool API
-    module.register_async_method(MPOOL_GET_NONCE, mpool_get_nonce::<DB>)?;
-    module.register_async_method(MPOOL_PENDING, mpool_pending::<DB>)?;
-    module.register_async_method(MPOOL_PUSH, mpool_push::<DB>)?;
-    module.register_async_method(MPOOL_PUSH_MESSAGE, mpool_push_message::<DB>)?;
+    register!(MPOOL_GET_NONCE, Read, 1, mpool_get_nonce::<DB>);
+    register!(MPOOL_LOCAL, Read, 0, |_, state| mpool_local::<DB>(state));
+    register!(MPOOL_PENDING, Read, 1, mpool_pending::<DB>);
+    register!(MPOOL_PUSH, Write, 1, mpool_push::<DB>);
+    register!(MPOOL_PUSH_MESSAGE, Sign, 2, mpool_push_message::<DB>);
     // Sync API
-    module.register_async_method(SYNC_CHECK_BAD, sync_check_bad::<DB>)?;
-    module.register_async_method(SYNC_MARK_BAD, sync_mark_bad::<DB>)?;
-    module.register_async_method(SYNC_STATE, |_, state| sync_state::<DB>(state))?;
+    register!(SYNC_CHECK_BAD, Read, 1, sync_check_bad::<DB>);
+    register!(SYNC_MARK_BAD, Admin, 1, sync_mark_bad::<DB>);
+    register!(SYNC_UNMARK_BAD, Admin, 1, sync_unmark_bad::<DB>);
+    register!(SYNC_UNMARK_ALL_BAD, Admin, 0, |_, state| {
+        sync_unmark_all_bad::<DB>(state)
+    });
+    register!(SYNC_STATE, Read, 0, |_, state| sync_state::<DB>(state));
+    register!(SYNC_SUBMIT_BLOCK, Write, 1, sync_submit_block::<DB>);
+    register!(SYNC_CHECKPOINT, Admin, 1, sync_checkpoint::<DB>);
+    register!(SYNC_CHECKPOINT_GET, Read, 0, |_, state| {
+        sync_checkpoint_get::<DB>(state)
+    });
     // Wallet API
-    module.register_async_method(WALLET_BALANCE, wallet_balance::<DB>)?;
-    module.register_async_method(WALLET_DEFAULT_ADDRESS, wallet_default_address::<DB>)?;
-    module.register_async_method(WALLET_EXPORT, wallet_export::<DB>)?;
-    module.register_async_method(WALLET_HAS, wallet_has::<DB>)?;
-    module.register_async_method(WALLET_IMPORT, wallet_import::<DB>)?;
-    module.register_async_method(WALLET_LIST, wallet_list::<DB>)?;
-    module.register_async_method(WALLET_NEW, wallet_new::<DB>)?;
-    module.register_async_method(WALLET_SET_DEFAULT, wallet_set_default::<DB>)?;
-    module.register_async_method(WALLET_SIGN, wallet_sign::<DB>)?;
-    module.register_async_method(WALLET_VALIDATE_ADDRESS, |params, _| {
+    register!(WALLET_BALANCE, Read, 1, wallet_balance::<DB>);
+    register!(
+        WALLET_DEFAULT_ADDRESS,
+        Read,
+        0,
+        wallet_default_address::<DB>
+    );
+    register!(WALLET_EXPORT, Admin, 1, wallet_export::<DB>);
+    register!(WALLET_HAS, Write, 1, wallet_has::<DB>);
+    register!(WALLET_IMPORT, Admin, 1, wallet_import::<DB>);
+    register!(WALLET_LIST, Write, 0, wallet_list::<DB>);
+    register!(WALLET_NEW, Write, 1, wallet_new::<DB>);
+    register!(WALLET_SET_DEFAULT, Write, 1, wallet_set_default::<DB>);
+    register!(
+        WALLET_ROTATE_PASSPHRASE,
+        Admin,
+        2,
+        wallet_rotate_passphrase::<DB>
+    );
+    register!(WALLET_SIGN, Sign, 2, wallet_sign::<DB>);
+    register!(WALLET_SIGN_MESSAGE, Sign, 2, wallet_sign_message::<DB>);
+    register!(WALLET_VALIDATE_ADDRESS, Read, 1, |params, _| {
         wallet_validate_address(params)
-    })?;
-    module.register_async_method(WALLET_VERIFY, |params, _| wallet_verify(params))?;
-    module.register_async_method(WALLET_DELETE, wallet_delete::<DB>)?;
+    });
+    register!(WALLET_VERIFY, Read, 3, |params, _| wallet_verify(params));
+    register!(WALLET_DELETE, Write, 1, wallet_delete::<DB>);
+    register!(WALLET_ADD_WATCH, Write, 1, wallet_add_watch::<DB>);
+    register!(WALLET_REMOVE_WATCH, Write, 1, wallet_remove_watch::<DB>);
+    register!(WALLET_LIST_WATCH, Read, 0, wallet_list_watch::<DB>);
     // State API
-    module.register_async_method(STATE_CALL, state_call::<DB>)?;
-    module.register_async_method(STATE_REPLAY, state_replay::<DB>)?;
-    module.register_async_method(STATE_NETWORK_NAME, |_, state| {
+    register!(STATE_CALL, Read, 2, state_call::<DB>);
+    register!(STATE_REPLAY, Read, 2, state_replay::<DB>);
+    register!(STATE_COMPUTE, Read, 3, state_compute::<DB>);
+    register!(STATE_NETWORK_NAME, Read, 0, |_, state| {
         state_network_name::<DB>(state)
-    })?;
-    module.register_async_method(STATE_NETWORK_VERSION, state_get_network_version::<DB>)?;
-    module.register_async_method(STATE_ACCOUNT_KEY, state_account_key::<DB>)?;
-    module.register_async_method(STATE_LOOKUP_ID, state_lookup_id::<DB>)?;
-    module.register_async_method(STATE_GET_ACTOR, state_get_actor::<DB>)?;
-    module.register_async_method(STATE_MARKET_BALANCE, state_market_balance::<DB>)?;
-    module.register_async_method(STATE_MARKET_DEALS, state_market_deals::<DB>)?;
-    module.register_async_method(STATE_MINER_INFO, state_miner_info::<DB>)?;
-    module.register_async_method(MINER_GET_BASE_INFO, miner_get_base_info::<DB>)?;
-    module.register_async_method(STATE_MINER_ACTIVE_SECTORS, state_miner_active_sectors::<DB>)?;
-    module.register_async_method(STATE_MINER_SECTOR_COUNT, state_miner_sector_count::<DB>)?;
-    module.register_async_method(STATE_MINER_FAULTS, state_miner_faults::<DB>)?;
-    module.register_async_method(STATE_MINER_RECOVERIES, state_miner_recoveries::<DB>)?;
-    module.register_async_method(
-        STATE_MINER_AVAILABLE_BALANCE,
-        state_miner_available_balance::<DB>,
-    )?;
-    module.register_async_method(STATE_MINER_POWER, state_miner_power::<DB>)?;
-    module.register_async_method(STATE_MINER_DEADLINES, state_miner_deadlines::<DB>)?;
-    module.register_async_method(STATE_LIST_MESSAGES, state_list_messages::<DB>)?;
-    module.register_async_method(STATE_LIST_MINERS, state_list_miners::<DB>)?;
-    module.register_async_method(
+    });
+    register!(
+        STATE_NETWORK_VERSION,
+        Read,
+        1,
+        state_get_network_version::<DB>
+    );
+    register!(STATE_ACCOUNT_KEY, Read, 2, state_account_key::<DB>);
+    register!(STATE_LOOKUP_ID, Read, 2, state_lookup_id::<DB>);
+    register!(STATE_MARKET_BALANCE, Read, 2, state_market_balance::<DB>);
+    register!(STATE_MARKET_DEALS, Read, 2, state_market_deals::<DB>);
+    register!(MINER_GET_BASE_INFO, Read, 3, miner_get_base_info::<DB>);
+    register!(STATE_LIST_MESSAGES, Read, 3, state_list_messages::<DB>);
+    register!(STATE_LIST_MINERS, Read, 1, state_list_miners::<DB>);
+    register!(
         STATE_MINER_PROVING_DEADLINE,
+        Read,
+        2,
         state_miner_proving_deadline::<DB>,
-    )?;
-    module.register_async_method(STATE_GET_RECEIPT, state_get_receipt::<DB>)?;
-    module.register_async_method(STATE_WAIT_MSG, state_wait_msg::<DB>)?;
-    module.register_async_method(STATE_SEARCH_MSG, state_search_msg::<DB>)?;
-    module.register_async_method(STATE_SEARCH_MSG_LIMITED, state_search_msg_limited::<DB>)?;
-    module.register_async_method(STATE_FETCH_ROOT, state_fetch_root::<DB>)?;
-    module.register_async_method(
-        STATE_GET_RANDOMNESS_FROM_TICKETS,
-        state_get_randomness_from_tickets::<DB>,
-    )?;
-    module.register_async_method(
-        STATE_GET_RANDOMNESS_FROM_BEACON,
-        state_get_randomness_from_beacon::<DB>,
-    )?;
-    module.register_async_method(STATE_READ_STATE, state_read_state::<DB>)?;
-    module.register_async_method(STATE_CIRCULATING_SUPPLY, state_circulating_supply::<DB>)?;
-    module.register_async_method(STATE_SECTOR_GET_INFO, state_sector_get_info::<DB>)?;
-    module.register_async_method(
+    );
+    register!(STATE_GET_RECEIPT, Read, 2, state_get_receipt::<DB>);
+    register!(STATE_WAIT_MSG, Read, 2, state_wait_msg::<DB>);
+    match api_version {
+        ApiVersion::V0 => {
+            register!(STATE_SEARCH_MSG, Read, 1, state_search_msg::<DB>);
+        }
+        ApiVersion::V1 => {
+            register!(STATE_SEARCH_MSG, Read, 4, state_search_msg_v1::<DB>);
+        }
+    }
+    register!(
+        STATE_SEARCH_MSG_LIMITED,
+        Read,
+        2,
+        state_search_msg_limited::<DB>
+    );
+    register!(STATE_FETCH_ROOT, Read, 2, state_fetch_root::<DB>);
+    register!(STATE_READ_STATE, Read, 2, state_read_state::<DB>);
+    register!(
+        STATE_CIRCULATING_SUPPLY,
+        Read,
+        1,
+        state_circulating_supply::<DB>
+    );
+    register!(STATE_SECTOR_GET_INFO, Read, 3, state_sector_get_info::<DB>);
+    register!(
         STATE_VERIFIED_CLIENT_STATUS,
+        Read,
+        2,
         state_verified_client_status::<DB>,
-    )?;
-    module.register_async_method(
+    );
+    register!(
         STATE_VM_CIRCULATING_SUPPLY_INTERNAL,
+        Read,
+        1,
         state_vm_circulating_supply_internal::<DB>,
-    )?;
-    module.register_async_method(STATE_MARKET_STORAGE_DEAL, state_market_storage_deal::<DB>)?;
-    module.register_async_method(MSIG_GET_AVAILABLE_BALANCE, msig_get_available_balance::<DB>)?;
-    module.register_async_method(MSIG_GET_PENDING, msig_get_pending::<DB>)?;
+    );
+    register!(
+        STATE_MARKET_STORAGE_DEAL,
+        Read,
+        2,
+        state_market_storage_deal::<DB>
+    );
+    register!(
+        MSIG_GET_AVAILABLE_BALANCE,
+        Read,
+        2,
+        msig_get_available_balance::<DB>
+    );
+    register!(MSIG_GET_PENDING, Read, 2, msig_get_pending::<DB>);
     // Gas API
-    module.register_async_method(GAS_ESTIMATE_FEE_CAP, gas_estimate_fee_cap::<DB>)?;
-    module.register_async_method(GAS_ESTIMATE_GAS_LIMIT, gas_estimate_gas_limit::<DB>)?;
-    module.register_async_method(GAS_ESTIMATE_GAS_PREMIUM, gas_estimate_gas_premium::<DB>)?;
-    module.register_async_method(GAS_ESTIMATE_MESSAGE_GAS, gas_estimate_message_gas::<DB>)?;
+    register!(GAS_ESTIMATE_FEE_CAP, Read, 3, gas_estimate_fee_cap::<DB>);
+    register!(
+        GAS_ESTIMATE_GAS_LIMIT,
+        Read,
+        2,
+        gas_estimate_gas_limit::<DB>
+    );
+    register!(
+        GAS_ESTIMATE_GAS_PREMIUM,
+        Read,
+        4,
+        gas_estimate_gas_premium::<DB>
+    );
+    register!(
+        GAS_ESTIMATE_MESSAGE_GAS,
+        Read,
+        3,
+        gas_estimate_message_gas::<DB>
+    );
     // Common API
+    describe!(VERSION, Read, 0);
     module.register_method(VERSION, move |_, _| version(block_delay, forest_version))?;
+    describe!(SESSION, Read, 0);
     module.register_method(SESSION, |_, _| session())?;
-    module.register_async_method(SHUTDOWN, move |_, _| shutdown(shutdown_send.clone()))?;
+    register!(SHUTDOWN, Admin, 0, move |_, _| shutdown(
+        shutdown_send.clone()
+    ));
+    describe!(START_TIME, Read, 0);
     module.register_method(START_TIME, move |_, state| start_time::<DB>(state))?;
     // Net API
-    module.register_async_method(NET_ADDRS_LISTEN, |_, state| net_addrs_listen::<DB>(state))?;
-    module.register_async_method(NET_PEERS, |_, state| net_peers::<DB>(state))?;
-    module.register_async_method(NET_LISTENING, |_, _| net_listening())?;
-    module.register_async_method(NET_INFO, |_, state| net_info::<DB>(state))?;
-    module.register_async_method(NET_CONNECT, net_connect::<DB>)?;
-    module.register_async_method(NET_DISCONNECT, net_disconnect::<DB>)?;
-    module.register_async_method(NET_AGENT_VERSION, net_agent_version::<DB>)?;
-    module.register_async_method(NET_AUTO_NAT_STATUS, net_auto_nat_status::<DB>)?;
-    module.register_async_method(NET_VERSION, net_version::<DB>)?;
+    register!(
+        NET_ADDRS_LISTEN,
+        Read,
+        0,
+        |_, state| net_addrs_listen::<DB>(state)
+    );
+    register!(NET_PEERS, Read, 0, |_, state| net_peers::<DB>(state));
+    register!(NET_LISTENING, Read, 0, |_, _| net_listening());
+    register!(NET_INFO, Read, 0, |_, state| net_info::<DB>(state));
+    register!(NET_CONNECT, Write, 1, net_connect::<DB>);
+    register!(NET_DISCONNECT, Write, 1, net_disconnect::<DB>);
+    register!(NET_AGENT_VERSION, Read, 1, net_agent_version::<DB>);
+    register!(NET_AUTO_NAT_STATUS, Read, 0, net_auto_nat_status::<DB>);
+    register!(NET_VERSION, Read, 1, net_version::<DB>);
+    register!(NET_BANDWIDTH_STATS, Read, 0, |_, state| {
+        net_bandwidth_stats::<DB>(state)
+    });
+    register!(NET_BANDWIDTH_STATS_BY_PEER, Read, 0, |_, state| {
+        net_bandwidth_stats_by_peer::<DB>(state)
+    });
+    register!(NET_BANDWIDTH_STATS_BY_PROTOCOL, Read, 0, |_, state| {
+        net_bandwidth_stats_by_protocol::<DB>(state)
+    });
+    register!(NET_FIND_PEER, Read, 1, net_find_peer::<DB>);
+    register!(NET_PING, Read, 1, net_ping::<DB>);
+    register!(NET_BLOCK_ADD, Admin, 1, net_block_add::<DB>);
+    register!(NET_BLOCK_REMOVE, Admin, 1, net_block_remove::<DB>);
+    register!(NET_BLOCK_LIST, Read, 0, |_, state| net_block_list::<DB>(
+        state
+    ));
+    register!(
+        NET_PUBSUB_SCORES,
+        Read,
+        0,
+        |_, state| net_pubsub_scores::<DB>(state)
+    );
+    register!(NET_CONNECTEDNESS, Read, 1, net_connectedness::<DB>);
+    register!(NET_LIMIT, Read, 1, net_limit::<DB>);
+    register!(NET_SET_LIMIT, Admin, 2, net_set_limit::<DB>);
+    register!(NET_BOOTSTRAP, Write, 0, |_, state| net_bootstrap::<DB>(
+        state
+    ));
+    register!(NET_BOOTSTRAP_LIST, Read, 0, |_, state| {
+        net_bootstrap_list::<DB>(state)
+    });
+    register!(NET_BOOTSTRAP_ADD, Admin, 1, net_bootstrap_add::<DB>);
     // Node API
-    module.register_async_method(NODE_STATUS, |_, state| node_status::<DB>(state))?;
+    if api_version >= ApiVersion::V1 {
+        register!(NODE_STATUS, Read, 0, |_, state| node_status::<DB>(state));
+    }
     // Eth API
-    module.register_async_method(ETH_ACCOUNTS, |_, _| eth_accounts())?;
-    module.register_async_method(ETH_BLOCK_NUMBER, |_, state| eth_block_number::<DB>(state))?;
-    module.register_async_method(ETH_CHAIN_ID, |_, state| eth_chain_id::<DB>(state))?;
-    module.register_async_method(ETH_GAS_PRICE, |_, state| eth_gas_price::<DB>(state))?;
-    module.register_async_method(ETH_GET_BALANCE, eth_get_balance::<DB>)?;
-    module.register_async_method(ETH_SYNCING, eth_syncing::<DB>)?;
+    register!(ETH_ACCOUNTS, Read, 0, |_, _| eth_accounts());
+    register!(
+        ETH_BLOCK_NUMBER,
+        Read,
+        0,
+        |_, state| eth_block_number::<DB>(state)
+    );
+    register!(ETH_CHAIN_ID, Read, 0, |_, state| eth_chain_id::<DB>(state));
+    register!(ETH_GAS_PRICE, Read, 0, |_, state| eth_gas_price::<DB>(
+        state
+    ));
+    register!(ETH_GET_BALANCE, Read, 2, eth_get_balance::<DB>);
+    register!(ETH_SYNCING, Read, 0, eth_syncing::<DB>);
 
     Ok(())
 }
@@ -340,6 +834,12 @@ where
 mod tests {
     use std::sync::Arc;
 
+    use fil_actor_interface::miner::MinerInfo;
+    use jsonrpsee::{
+        core::{params::ObjectParams, EmptyServerParams},
+        types::{error::INVALID_PARAMS_CODE, ErrorObjectOwned},
+        MethodsError,
+    };
     use tokio::task::JoinSet;
 
     use crate::{
@@ -348,22 +848,402 @@ mod tests {
         chain_sync::SyncConfig,
         db::car::PlainCar,
         genesis::get_network_name_from_genesis,
+        lotus_json::LotusJson,
         message_pool::{MessagePool, MpoolRpcProvider},
-        networks::ChainConfig,
+        networks::{calibnet, ChainConfig, NetworkChain},
+        rpc_api::data_types::{ApiTipsetKey, NetworkIdentity},
+        shim::{address::Address, state_tree::ActorState},
         state_manager::StateManager,
         KeyStoreConfig,
     };
 
     use super::*;
+    use reflect::ConcreteCallingConvention;
 
     // TODO(forest): https://github.com/ChainSafe/forest/issues/4047
     //               `tokio` shouldn't be necessary
     #[tokio::test]
     async fn openrpc() {
-        let (_, spec) = create_module(Arc::new(RPCState::calibnet()));
+        let (_, spec, _) = create_module(Arc::new(RPCState::calibnet()), "test");
         insta::assert_yaml_snapshot!(spec);
     }
 
+    #[tokio::test]
+    async fn discover_describes_every_method() {
+        for api_version in [ApiVersion::V0, ApiVersion::V1] {
+            let (shutdown_send, _) = tokio::sync::mpsc::channel(1);
+            let (module, spec, permissions) = build_module(
+                Arc::new(RPCState::calibnet()),
+                "0.0.0",
+                shutdown_send,
+                api_version,
+            )
+            .unwrap();
+            assert_eq!(spec.methods.len(), module.method_names().count());
+            assert_eq!(spec.methods.len(), permissions.len());
+            for method in module.method_names() {
+                assert!(spec.methods.iter().any(|it| it.name == method), "{method}");
+            }
+
+            let response: OpenRPC = module
+                .call(DISCOVER, EmptyServerParams::new())
+                .await
+                .unwrap();
+            assert_eq!(response, spec);
+            assert_eq!(response.openrpc, "1.2.6");
+            assert_eq!(response.info.version, "0.0.0");
+        }
+    }
+
+    #[tokio::test]
+    async fn versioned_methods() {
+        let arity = |spec: &OpenRPC, name: &str| {
+            spec.methods
+                .iter()
+                .find(|it| it.name == name)
+                .map(|it| it.params.len())
+        };
+
+        let (shutdown_send, _) = tokio::sync::mpsc::channel(1);
+        let (_, v0, _) = build_module(
+            Arc::new(RPCState::calibnet()),
+            "0.0.0",
+            shutdown_send.clone(),
+            ApiVersion::V0,
+        )
+        .unwrap();
+        let (_, v1, _) = build_module(
+            Arc::new(RPCState::calibnet()),
+            "0.0.0",
+            shutdown_send,
+            ApiVersion::V1,
+        )
+        .unwrap();
+
+        assert_eq!(arity(&v0, NODE_STATUS), None);
+        assert_eq!(arity(&v1, NODE_STATUS), Some(0));
+        assert_eq!(arity(&v0, STATE_SEARCH_MSG), Some(1));
+        assert_eq!(arity(&v1, STATE_SEARCH_MSG), Some(4));
+        // every other method is served by both versions
+        for method in &v0.methods {
+            assert!(arity(&v1, &method.name).is_some(), "{}", method.name);
+        }
+    }
+
+    #[tokio::test]
+    async fn state_get_actor_params() {
+        let (shutdown_send, _) = tokio::sync::mpsc::channel(1);
+        let (module, _, _) = build_module(
+            Arc::new(RPCState::calibnet()),
+            "0.0.0",
+            shutdown_send,
+            ApiVersion::V0,
+        )
+        .unwrap();
+        let params = (
+            LotusJson(Address::SYSTEM_ACTOR),
+            LotusJson(ApiTipsetKey(None)),
+        );
+
+        let by_position = StateGetActor::call_module(
+            &module,
+            params.clone(),
+            ConcreteCallingConvention::ByPosition,
+        )
+        .await
+        .unwrap();
+        let by_name =
+            StateGetActor::call_module(&module, params, ConcreteCallingConvention::ByName)
+                .await
+                .unwrap();
+        assert!(by_position.0.is_some());
+        assert_eq!(by_position.0, by_name.0);
+
+        // the tipset key may be omitted
+        let omitted_by_position: LotusJson<Option<ActorState>> = module
+            .call(STATE_GET_ACTOR, [Address::SYSTEM_ACTOR.to_string()])
+            .await
+            .unwrap();
+        let mut omitted_by_name = ObjectParams::new();
+        omitted_by_name
+            .insert("address", Address::SYSTEM_ACTOR.to_string())
+            .unwrap();
+        let omitted_by_name: LotusJson<Option<ActorState>> =
+            module.call(STATE_GET_ACTOR, omitted_by_name).await.unwrap();
+        assert_eq!(omitted_by_position.0, by_position.0);
+        assert_eq!(omitted_by_name.0, by_position.0);
+    }
+
+    #[tokio::test]
+    async fn state_network_identity() {
+        let (shutdown_send, _) = tokio::sync::mpsc::channel(1);
+        let (module, _, _) = build_module(
+            Arc::new(RPCState::calibnet()),
+            "0.0.0",
+            shutdown_send,
+            ApiVersion::V0,
+        )
+        .unwrap();
+
+        let identity: NetworkIdentity = module
+            .call(STATE_NETWORK_IDENTITY, EmptyServerParams::new())
+            .await
+            .unwrap();
+        let config = ChainConfig::calibnet();
+        assert_eq!(
+            identity,
+            NetworkIdentity {
+                network: NetworkChain::Calibnet,
+                genesis_cid: *calibnet::GENESIS_CID,
+                eth_chain_id: config.eth_chain_id,
+                block_delay: config.block_delay_secs,
+            }
+        );
+        let json: serde_json::Value = module
+            .call(STATE_NETWORK_IDENTITY, EmptyServerParams::new())
+            .await
+            .unwrap();
+        assert_eq!(json["Network"], serde_json::json!({ "type": "calibnet" }));
+        assert_eq!(
+            json["GenesisCid"],
+            serde_json::json!({ "/": calibnet::GENESIS_CID.to_string() })
+        );
+    }
+
+    #[tokio::test]
+    async fn state_miner_info_params() {
+        let (shutdown_send, _) = tokio::sync::mpsc::channel(1);
+        let (module, _, _) = build_module(
+            Arc::new(RPCState::calibnet()),
+            "0.0.0",
+            shutdown_send,
+            ApiVersion::V0,
+        )
+        .unwrap();
+        let miner = Address::new_id(1000);
+        let params = (LotusJson(miner), LotusJson(ApiTipsetKey(None)));
+
+        // The calibnet genesis miner predates the supported actor versions, so
+        // the handler fails, but only after the parameters have been parsed.
+        let by_position = StateMinerInfo::call_module(
+            &module,
+            params.clone(),
+            ConcreteCallingConvention::ByPosition,
+        )
+        .await;
+        let by_name =
+            StateMinerInfo::call_module(&module, params, ConcreteCallingConvention::ByName).await;
+        let mut omitted_by_name = ObjectParams::new();
+        omitted_by_name.insert("miner", miner.to_string()).unwrap();
+        let omitted_by_name = module
+            .call::<_, LotusJson<MinerInfo>>(STATE_MINER_INFO, omitted_by_name)
+            .await;
+        let expected = error_object(by_position);
+        assert_ne!(expected.code(), INVALID_PARAMS_CODE);
+        assert_eq!(error_object(by_name), expected);
+        assert_eq!(error_object(omitted_by_name), expected);
+
+        // unknown parameter names are still rejected
+        let mut unexpected = ObjectParams::new();
+        unexpected.insert("miner", miner.to_string()).unwrap();
+        unexpected.insert("tipset", ()).unwrap();
+        let unexpected = module
+            .call::<_, LotusJson<MinerInfo>>(STATE_MINER_INFO, unexpected)
+            .await;
+        assert_eq!(error_object(unexpected).code(), INVALID_PARAMS_CODE);
+    }
+
+    #[tokio::test]
+    async fn chain_export_params() {
+        let (shutdown_send, _) = tokio::sync::mpsc::channel(1);
+        let (module, _, _) = build_module(
+            Arc::new(RPCState::calibnet()),
+            "0.0.0",
+            shutdown_send,
+            ApiVersion::V0,
+        )
+        .unwrap();
+        // The wire form of the parameters, as sent by `forest-cli snapshot export`
+        let params = serde_json::json!({
+            "epoch": 0,
+            "recent_roots": 0,
+            "output_path": "/dev/null",
+            "tipset_keys": null,
+            "skip_checksum": false,
+            "dry_run": true,
+            "skip_old_messages": false,
+        });
+        let export = |params: serde_json::Value| {
+            let params = serde_json::value::to_raw_value(&params).unwrap();
+            let module = &module;
+            async move {
+                let request = serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": 0,
+                    "method": CHAIN_EXPORT,
+                    "params": params,
+                });
+                let (response, _) = module
+                    .raw_json_request(&request.to_string(), 1)
+                    .await
+                    .unwrap();
+                let response: serde_json::Value = serde_json::from_str(&response).unwrap();
+                response["error"].clone()
+            }
+        };
+
+        // Too few recent state roots fail the export, but only once the
+        // parameters have been parsed.
+        let expected = export(serde_json::json!([params.clone()])).await;
+        assert_ne!(expected["code"], INVALID_PARAMS_CODE, "{expected}");
+        assert!(expected["message"]
+            .as_str()
+            .unwrap()
+            .contains("recent-stateroots"));
+        assert_eq!(
+            export(serde_json::json!({ "params": params.clone() })).await,
+            expected
+        );
+        // Clients predating `RpcMethod` send the bare object
+        assert_eq!(export(params.clone()).await, expected);
+        // and clients predating `skip_old_messages` leave it out
+        let mut without_skip_old_messages = params;
+        without_skip_old_messages
+            .as_object_mut()
+            .unwrap()
+            .remove("skip_old_messages");
+        assert_eq!(
+            export(serde_json::json!([without_skip_old_messages])).await,
+            expected
+        );
+        let unknown = export(serde_json::json!({ "epoch": 0 })).await;
+        assert_eq!(unknown["code"], INVALID_PARAMS_CODE, "{unknown}");
+    }
+
+    #[tokio::test]
+    async fn nonexistent_actor_error() {
+        let (shutdown_send, _) = tokio::sync::mpsc::channel(1);
+        let (module, _, _) = build_module(
+            Arc::new(RPCState::calibnet()),
+            "0.0.0",
+            shutdown_send,
+            ApiVersion::V1,
+        )
+        .unwrap();
+        let address = Address::new_id(999_999);
+        let expected = serde_json::json!({
+            "code": error::ACTOR_NOT_FOUND_CODE,
+            "message": format!("actor not found: {address}"),
+            "data": { "address": address.to_string() },
+        });
+
+        for method in [STATE_READ_STATE, STATE_MINER_INFO, MSIG_GET_PENDING] {
+            let result = module
+                .call::<_, serde_json::Value>(method, (LotusJson(address), ()))
+                .await;
+            let error = serde_json::to_value(error_object(result)).unwrap();
+            assert_eq!(error, expected, "{method}");
+        }
+    }
+
+    /// Serves the calibnet genesis over HTTP, returning the URL of the v1 API.
+    async fn serve(rpc_config: RpcConfig) -> (String, ServerHandle) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/rpc/v1", listener.local_addr().unwrap());
+        let (stop_handle, server_handle) = jsonrpsee::server::stop_channel();
+        let (shutdown_send, _) = tokio::sync::mpsc::channel(1);
+        tokio::spawn(start_rpc(
+            RPCState::calibnet(),
+            vec![listener],
+            None,
+            None,
+            rpc_config,
+            "test",
+            shutdown_send,
+            stop_handle,
+        ));
+        (url, server_handle)
+    }
+
+    /// Returns the response and the size of its body.
+    async fn post(url: &str, body: impl serde::Serialize) -> (serde_json::Value, usize) {
+        let response = reqwest::Client::new()
+            .post(url)
+            .json(&body)
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        (serde_json::from_str(&response).unwrap(), response.len())
+    }
+
+    fn call(id: u64, method: &str) -> serde_json::Value {
+        serde_json::json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": [] })
+    }
+
+    #[tokio::test]
+    async fn batch_requests() {
+        use crate::rpc_api::{chain_api::CHAIN_HEAD, mpool_api::MPOOL_PUSH};
+        use jsonrpsee::types::error::{
+            BATCHES_NOT_SUPPORTED_CODE, INVALID_REQUEST_CODE, TOO_BIG_BATCH_REQUEST_CODE,
+            TOO_BIG_BATCH_RESPONSE_CODE,
+        };
+
+        let (url, _server) = serve(RpcConfig {
+            max_batch_length: 3,
+            ..Default::default()
+        })
+        .await;
+        let (head, head_size) = post(&url, call(0, CHAIN_HEAD)).await;
+        assert!(head["result"]["Cids"].is_array(), "{head}");
+
+        // Calls are authorized one by one, so only the write call fails
+        let batch = [
+            call(0, CHAIN_HEAD),
+            call(1, MPOOL_PUSH),
+            call(2, CHAIN_HEAD),
+        ];
+        let (response, _) = post(&url, batch).await;
+        let [head0, push, head2]: [serde_json::Value; 3] =
+            serde_json::from_value(response).unwrap();
+        assert_eq!(head0, head);
+        assert_eq!(push["id"], 1);
+        assert_eq!(push["error"]["code"], INVALID_REQUEST_CODE);
+        assert_eq!(head2["id"], 2);
+        assert_eq!(head2["result"], head["result"]);
+
+        let (response, _) = post(&url, vec![call(0, CHAIN_HEAD); 4]).await;
+        assert_eq!(response["error"]["code"], TOO_BIG_BATCH_REQUEST_CODE);
+
+        // There is room for a single response, but not for a batch of two
+        let (url, _server) = serve(RpcConfig {
+            max_response_body_size: head_size as u32 + 16,
+            ..Default::default()
+        })
+        .await;
+        assert_eq!(post(&url, call(0, CHAIN_HEAD)).await.0, head);
+        let (response, _) = post(&url, [call(0, CHAIN_HEAD), call(1, CHAIN_HEAD)]).await;
+        assert_eq!(response["error"]["code"], TOO_BIG_BATCH_RESPONSE_CODE);
+
+        let (url, _server) = serve(RpcConfig {
+            max_batch_length: 0,
+            ..Default::default()
+        })
+        .await;
+        let (response, _) = post(&url, [call(0, CHAIN_HEAD)]).await;
+        assert_eq!(response["error"]["code"], BATCHES_NOT_SUPPORTED_CODE);
+    }
+
+    fn error_object<T>(result: Result<T, MethodsError>) -> ErrorObjectOwned {
+        match result {
+            Err(MethodsError::JsonRpc(it)) => it,
+            Ok(_) => panic!("expected an error"),
+            Err(e) => panic!("unexpected error: {e}"),
+        }
+    }
+
     impl RPCState<Chain4U<PlainCar<&'static [u8]>>> {
         pub fn calibnet() -> Self {
             let chain_store = Arc::new(ChainStore::calibnet());
@@ -382,6 +1262,8 @@ mod tests {
                     .get_beacon_schedule(genesis.timestamp),
             );
             let (network_send, _) = flume::bounded(0);
+            let (tipset_send, _) = flume::bounded(0);
+            let (incoming_blocks, _) = tokio::sync::broadcast::channel(1);
             let network_name = get_network_name_from_genesis(genesis, &state_manager).unwrap();
             let message_pool = MessagePool::new(
                 MpoolRpcProvider::new(chain_store.publisher().clone(), state_manager.clone()),
@@ -395,14 +1277,20 @@ mod tests {
             RPCState {
                 state_manager,
                 keystore: Arc::new(RwLock::new(KeyStore::new(KeyStoreConfig::Memory).unwrap())),
+                tokens: Default::default(),
                 mpool: Arc::new(message_pool),
                 bad_blocks: Default::default(),
                 sync_state: Default::default(),
                 network_send,
+                tipset_send,
+                incoming_blocks,
                 network_name,
                 start_time: Default::default(),
                 chain_store,
                 beacon,
+                bandwidth: Default::default(),
+                net_limits: Default::default(),
+                gc: None,
             }
         }
     }
@@ -99,6 +99,15 @@ where
     Ok(pending.into_iter().collect::<Vec<_>>().into())
 }
 
+/// Return `Vec` of messages published through this node that have not been
+/// included on chain yet
+pub async fn mpool_local<DB>(data: Ctx<DB>) -> Result<LotusJson<Vec<SignedMessage>>, JsonRpcError>
+where
+    DB: Blockstore + Send + Sync + 'static,
+{
+    Ok(data.mpool.local_messages().into())
+}
+
 /// Add `SignedMessage` to `mpool`, return message CID
 pub async fn mpool_push<DB>(
     params: Params<'_>,
@@ -155,13 +164,7 @@ where
         &key_addr,
         &mut keystore,
     )?)?;
-    let sig = crate::key_management::sign(
-        *key.key_info.key_type(),
-        key.key_info.private_key(),
-        umsg.cid().unwrap().to_bytes().as_slice(),
-    )?;
-
-    let smsg = SignedMessage::new_from_parts(umsg, sig)?;
+    let smsg = super::wallet_api::sign_message(&key, umsg)?;
 
     data.mpool.as_ref().push(smsg.clone()).await?;
 
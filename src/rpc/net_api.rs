@@ -2,11 +2,13 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use std::str::FromStr;
+use std::time::Duration;
 
-use crate::libp2p::{NetRPCMethods, NetworkMessage, PeerId};
+use crate::libp2p::{Multiaddr, NetRPCMethods, NetworkMessage, PeerId, Protocol};
 use crate::rpc::error::JsonRpcError;
 use crate::rpc::Ctx;
 use crate::rpc_api::{data_types::AddrInfo, net_api::*};
+use ahash::HashMap;
 use cid::multibase;
 use futures::channel::oneshot;
 use fvm_ipld_blockstore::Blockstore;
@@ -14,6 +16,18 @@ use jsonrpsee::types::Params;
 
 use anyhow::Result;
 
+use crate::db::{
+    setting_keys::{NET_BOOTSTRAP_PEERS_KEY, NET_LIMIT_KEY},
+    SettingsStoreExt,
+};
+use itertools::Itertools as _;
+
+/// How long `NetBootstrap` waits for the bootstrap peers to connect.
+const NET_BOOTSTRAP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long `NetFindPeer` and `NetPing` wait for the network before giving up.
+const NET_PEER_LOOKUP_TIMEOUT: Duration = Duration::from_secs(30);
+
 pub async fn net_addrs_listen<DB: Blockstore>(data: Ctx<DB>) -> Result<AddrInfo, JsonRpcError> {
     let (tx, rx) = oneshot::channel();
     let req = NetworkMessage::JSONRPCRequest {
@@ -94,6 +108,18 @@ pub async fn net_disconnect<DB: Blockstore>(
 ) -> Result<(), JsonRpcError> {
     let (id,): (String,) = params.parse()?;
 
+    if id == "all" {
+        let (tx, rx) = oneshot::channel();
+        let req = NetworkMessage::JSONRPCRequest {
+            method: NetRPCMethods::DisconnectAll(tx),
+        };
+
+        data.network_send.send_async(req).await?;
+        rx.await?;
+
+        return Ok(());
+    }
+
     let peer_id = PeerId::from_str(&id)?;
 
     let (tx, rx) = oneshot::channel();
@@ -150,3 +176,475 @@ pub async fn net_version<DB: Blockstore>(
         data.state_manager.chain_config().eth_chain_id
     ))
 }
+
+pub async fn net_bandwidth_stats<DB: Blockstore>(
+    data: Ctx<DB>,
+) -> Result<BandwidthStats, JsonRpcError> {
+    Ok(data.bandwidth.stats())
+}
+
+pub async fn net_bandwidth_stats_by_peer<DB: Blockstore>(
+    data: Ctx<DB>,
+) -> Result<HashMap<String, BandwidthStats>, JsonRpcError> {
+    Ok(data
+        .bandwidth
+        .stats_by_peer()
+        .into_iter()
+        .map(|(peer, stats)| (peer.to_string(), stats))
+        .collect())
+}
+
+pub async fn net_bandwidth_stats_by_protocol<DB: Blockstore>(
+    data: Ctx<DB>,
+) -> Result<HashMap<String, BandwidthStats>, JsonRpcError> {
+    Ok(data.bandwidth.stats_by_protocol())
+}
+
+pub async fn net_find_peer<DB: Blockstore>(
+    params: Params<'_>,
+    data: Ctx<DB>,
+) -> Result<AddrInfo, JsonRpcError> {
+    let (id,): (String,) = params.parse()?;
+
+    let peer_id = PeerId::from_str(&id)?;
+
+    let (tx, rx) = oneshot::channel();
+    let req = NetworkMessage::JSONRPCRequest {
+        method: NetRPCMethods::FindPeer(tx, peer_id),
+    };
+
+    data.network_send.send_async(req).await?;
+    let addrs = tokio::time::timeout(NET_PEER_LOOKUP_TIMEOUT, rx)
+        .await
+        .map_err(|_| anyhow::anyhow!("timed out looking up peer {peer_id}"))??;
+    if addrs.is_empty() {
+        Err(anyhow::anyhow!("peer {peer_id} not found").into())
+    } else {
+        Ok(AddrInfo { id, addrs })
+    }
+}
+
+pub async fn net_ping<DB: Blockstore>(
+    params: Params<'_>,
+    data: Ctx<DB>,
+) -> Result<i64, JsonRpcError> {
+    let (id,): (String,) = params.parse()?;
+
+    let peer_id = PeerId::from_str(&id)?;
+
+    let (tx, rx) = oneshot::channel();
+    let req = NetworkMessage::JSONRPCRequest {
+        method: NetRPCMethods::Ping(tx, peer_id),
+    };
+
+    data.network_send.send_async(req).await?;
+    let rtt = tokio::time::timeout(NET_PEER_LOOKUP_TIMEOUT, rx)
+        .await
+        .map_err(|_| anyhow::anyhow!("timed out pinging peer {peer_id}"))?
+        .map_err(|_| anyhow::anyhow!("peer {peer_id} disconnected before it could be pinged"))?
+        .map_err(|e| anyhow::anyhow!("failed to ping peer {peer_id}: {e}"))?;
+    Ok(rtt.as_nanos().try_into().unwrap_or(i64::MAX))
+}
+
+pub async fn net_block_add<DB: Blockstore>(
+    params: Params<'_>,
+    data: Ctx<DB>,
+) -> Result<(), JsonRpcError> {
+    let (acl,): (NetBlockList,) = params.parse()?;
+
+    let (tx, rx) = oneshot::channel();
+    let req = NetworkMessage::JSONRPCRequest {
+        method: NetRPCMethods::BlockAdd(tx, acl),
+    };
+
+    data.network_send.send_async(req).await?;
+    rx.await?.map_err(|e| anyhow::anyhow!(e))?;
+    Ok(())
+}
+
+pub async fn net_block_remove<DB: Blockstore>(
+    params: Params<'_>,
+    data: Ctx<DB>,
+) -> Result<(), JsonRpcError> {
+    let (acl,): (NetBlockList,) = params.parse()?;
+
+    let (tx, rx) = oneshot::channel();
+    let req = NetworkMessage::JSONRPCRequest {
+        method: NetRPCMethods::BlockRemove(tx, acl),
+    };
+
+    data.network_send.send_async(req).await?;
+    rx.await?.map_err(|e| anyhow::anyhow!(e))?;
+    Ok(())
+}
+
+pub async fn net_block_list<DB: Blockstore>(data: Ctx<DB>) -> Result<NetBlockList, JsonRpcError> {
+    let (tx, rx) = oneshot::channel();
+    let req = NetworkMessage::JSONRPCRequest {
+        method: NetRPCMethods::BlockList(tx),
+    };
+
+    data.network_send.send_async(req).await?;
+    Ok(rx.await?)
+}
+
+pub async fn net_pubsub_scores<DB: Blockstore>(
+    data: Ctx<DB>,
+) -> Result<Vec<PubsubScore>, JsonRpcError> {
+    let (tx, rx) = oneshot::channel();
+    let req = NetworkMessage::JSONRPCRequest {
+        method: NetRPCMethods::PubsubScores(tx),
+    };
+
+    data.network_send.send_async(req).await?;
+    Ok(rx.await?)
+}
+
+pub async fn net_connectedness<DB: Blockstore>(
+    params: Params<'_>,
+    data: Ctx<DB>,
+) -> Result<u32, JsonRpcError> {
+    let (id,): (String,) = params.parse()?;
+
+    let peer_id = PeerId::from_str(&id)?;
+
+    let (tx, rx) = oneshot::channel();
+    let req = NetworkMessage::JSONRPCRequest {
+        method: NetRPCMethods::Connectedness(tx, peer_id),
+    };
+
+    data.network_send.send_async(req).await?;
+    Ok(rx.await? as u32)
+}
+
+/// Dials the bootstrap peers of the network, reporting how many of them
+/// connected before [`NET_BOOTSTRAP_TIMEOUT`].
+pub async fn net_bootstrap<DB: Blockstore>(
+    data: Ctx<DB>,
+) -> Result<NetBootstrapResult, JsonRpcError> {
+    let mut peers: HashMap<PeerId, Vec<Multiaddr>> = HashMap::default();
+    for addr in bootstrap_peers(&data)? {
+        if let Some(Protocol::P2p(peer_id)) = addr.iter().last() {
+            peers.entry(peer_id).or_default().push(addr);
+        }
+    }
+
+    let mut dials = Vec::with_capacity(peers.len());
+    for (peer_id, addrs) in peers {
+        let (tx, rx) = oneshot::channel();
+        let req = NetworkMessage::JSONRPCRequest {
+            method: NetRPCMethods::Dial(tx, peer_id, addrs),
+        };
+        data.network_send.send_async(req).await?;
+        dials.push(tokio::time::timeout(NET_BOOTSTRAP_TIMEOUT, rx));
+    }
+
+    let attempted = dials.len();
+    let succeeded = futures::future::join_all(dials)
+        .await
+        .into_iter()
+        .filter(|result| matches!(result, Ok(Ok(true))))
+        .count();
+    Ok(NetBootstrapResult {
+        attempted: attempted as u64,
+        succeeded: succeeded as u64,
+    })
+}
+
+/// The bootstrap peers of the node, including the ones added with
+/// `NetBootstrapAdd` since it started.
+fn bootstrap_peers<DB: Blockstore>(data: &Ctx<DB>) -> anyhow::Result<Vec<Multiaddr>> {
+    let persisted: Vec<Multiaddr> = data
+        .state_manager
+        .chain_store()
+        .settings()
+        .read_obj(NET_BOOTSTRAP_PEERS_KEY)?
+        .unwrap_or_default();
+    Ok(data
+        .state_manager
+        .chain_config()
+        .bootstrap_peers
+        .iter()
+        .cloned()
+        .chain(persisted)
+        .unique()
+        .collect())
+}
+
+pub async fn net_bootstrap_list<DB: Blockstore>(
+    data: Ctx<DB>,
+) -> Result<Vec<String>, JsonRpcError> {
+    Ok(bootstrap_peers(&data)?
+        .iter()
+        .map(ToString::to_string)
+        .collect())
+}
+
+pub async fn net_bootstrap_add<DB: Blockstore>(
+    params: Params<'_>,
+    data: Ctx<DB>,
+) -> Result<(), JsonRpcError> {
+    let (addr,): (String,) = params.parse()?;
+    let addr = Multiaddr::from_str(&addr)
+        .map_err(|e| anyhow::anyhow!("invalid bootstrap peer {addr}: {e}"))?;
+    if !matches!(addr.iter().last(), Some(Protocol::P2p(_))) {
+        return Err(anyhow::anyhow!("bootstrap peer {addr} has no /p2p/ peer id").into());
+    }
+
+    let settings = data.state_manager.chain_store().settings();
+    let mut persisted: Vec<Multiaddr> = settings
+        .read_obj(NET_BOOTSTRAP_PEERS_KEY)?
+        .unwrap_or_default();
+    if !persisted.contains(&addr) {
+        persisted.push(addr);
+        settings.write_obj(NET_BOOTSTRAP_PEERS_KEY, &persisted)?;
+    }
+    Ok(())
+}
+
+/// Only the `system` scope is supported, the watermarks apply to the whole
+/// node.
+fn ensure_system_scope(scope: &str) -> anyhow::Result<()> {
+    anyhow::ensure!(scope == "system", "unsupported limit scope: {scope}");
+    Ok(())
+}
+
+pub async fn net_limit<DB: Blockstore>(
+    params: Params<'_>,
+    data: Ctx<DB>,
+) -> Result<NetLimit, JsonRpcError> {
+    let (scope,): (String,) = params.parse()?;
+    ensure_system_scope(&scope)?;
+    Ok(data.net_limits.get())
+}
+
+pub async fn net_set_limit<DB: Blockstore>(
+    params: Params<'_>,
+    data: Ctx<DB>,
+) -> Result<(), JsonRpcError> {
+    let (scope, limit): (String, NetLimit) = params.parse()?;
+    ensure_system_scope(&scope)?;
+    data.net_limits.set(&limit)?;
+    data.state_manager
+        .chain_store()
+        .settings()
+        .write_obj(NET_LIMIT_KEY, &limit)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use ahash::HashSet;
+    use jsonrpsee::types::Params;
+
+    use super::*;
+    use crate::rpc::RPCState;
+
+    /// Stands in for the libp2p service.
+    async fn mock_network(
+        rx: flume::Receiver<NetworkMessage>,
+        mut peers: HashMap<PeerId, HashSet<libp2p::Multiaddr>>,
+    ) {
+        while let Ok(NetworkMessage::JSONRPCRequest { method }) = rx.recv_async().await {
+            match method {
+                NetRPCMethods::Peers(tx) => {
+                    tx.send(peers.clone()).unwrap();
+                }
+                NetRPCMethods::PubsubScores(tx) => {
+                    let score = PubsubScore {
+                        id: peers.keys().next().unwrap().to_string(),
+                        score: PeerScoreSnapshot {
+                            score: 1.5,
+                            topics: HashMap::from_iter([(
+                                "/fil/blocks/calibrationnet".into(),
+                                TopicScoreSnapshot {
+                                    time_in_mesh: 2_000_000_000,
+                                    first_message_deliveries: 3.,
+                                    mesh_message_deliveries: 2.,
+                                    invalid_message_deliveries: 1.,
+                                },
+                            )]),
+                        },
+                    };
+                    tx.send(vec![score]).unwrap();
+                }
+                NetRPCMethods::DisconnectAll(tx) => {
+                    tx.send(peers.len()).unwrap();
+                    peers.clear();
+                }
+                NetRPCMethods::Dial(tx, peer_id, _) => {
+                    peers.insert(peer_id, Default::default());
+                    tx.send(true).unwrap();
+                }
+                other => panic!("unexpected request: {other:?}"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn pubsub_scores_json_shape() {
+        let peer = PeerId::random();
+        let (network_send, network_rx) = flume::unbounded();
+        tokio::spawn(mock_network(
+            network_rx,
+            HashMap::from_iter([(peer, Default::default())]),
+        ));
+        let data = Arc::new(Arc::new(RPCState {
+            network_send,
+            ..RPCState::calibnet()
+        }));
+
+        let scores = net_pubsub_scores(data).await.unwrap();
+        assert_eq!(
+            serde_json::to_value(scores).unwrap(),
+            serde_json::json!([{
+                "ID": peer.to_string(),
+                "Score": {
+                    "Score": 1.5,
+                    "Topics": {
+                        "/fil/blocks/calibrationnet": {
+                            "TimeInMesh": 2_000_000_000,
+                            "FirstMessageDeliveries": 3.0,
+                            "MeshMessageDeliveries": 2.0,
+                            "InvalidMessageDeliveries": 1.0,
+                        }
+                    }
+                }
+            }])
+        );
+    }
+
+    #[tokio::test]
+    async fn net_limit_round_trip() {
+        let data = Arc::new(Arc::new(RPCState::calibnet()));
+        let limit = NetLimit {
+            conn_mgr_low: 10,
+            conn_mgr_high: 20,
+        };
+
+        let params = serde_json::to_string(&("system", limit)).unwrap();
+        net_set_limit(Params::new(Some(&params)), data.clone())
+            .await
+            .unwrap();
+
+        let params = serde_json::to_string(&["system"]).unwrap();
+        assert_eq!(
+            net_limit(Params::new(Some(&params)), data.clone())
+                .await
+                .unwrap(),
+            limit
+        );
+        assert_eq!(
+            data.state_manager
+                .chain_store()
+                .settings()
+                .read_obj::<NetLimit>(NET_LIMIT_KEY)
+                .unwrap(),
+            Some(limit)
+        );
+
+        let params = serde_json::to_string(&["transient"]).unwrap();
+        assert!(net_limit(Params::new(Some(&params)), data.clone())
+            .await
+            .is_err());
+
+        let params = serde_json::to_string(&(
+            "system",
+            NetLimit {
+                conn_mgr_low: 30,
+                conn_mgr_high: 20,
+            },
+        ))
+        .unwrap();
+        assert!(net_set_limit(Params::new(Some(&params)), data.clone())
+            .await
+            .is_err());
+        let params = serde_json::to_string(&["system"]).unwrap();
+        assert_eq!(
+            net_limit(Params::new(Some(&params)), data).await.unwrap(),
+            limit
+        );
+    }
+
+    #[tokio::test]
+    async fn disconnect_all() {
+        let (network_send, network_rx) = flume::unbounded();
+        tokio::spawn(mock_network(
+            network_rx,
+            HashMap::from_iter([
+                (PeerId::random(), Default::default()),
+                (PeerId::random(), Default::default()),
+            ]),
+        ));
+        let data = Arc::new(Arc::new(RPCState {
+            network_send,
+            ..RPCState::calibnet()
+        }));
+
+        net_disconnect(Params::new(Some(r#"["all"]"#)), data.clone())
+            .await
+            .unwrap();
+        assert!(net_peers(data).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn bootstrap_dials_every_bootstrap_peer() {
+        let (network_send, network_rx) = flume::unbounded();
+        let data = Arc::new(Arc::new(RPCState {
+            network_send,
+            ..RPCState::calibnet()
+        }));
+        let bootstrap_peers = data
+            .state_manager
+            .chain_config()
+            .bootstrap_peers
+            .iter()
+            .filter_map(|addr| match addr.iter().last() {
+                Some(Protocol::P2p(peer_id)) => Some(peer_id),
+                _ => None,
+            })
+            .collect::<HashSet<_>>();
+        assert!(!bootstrap_peers.is_empty());
+
+        // Answer the dials as they come in, without any peer connecting.
+        let responder = tokio::spawn(async move {
+            let mut dialed = HashSet::default();
+            while let Ok(NetworkMessage::JSONRPCRequest {
+                method: NetRPCMethods::Dial(tx, peer_id, _),
+            }) = network_rx.recv_async().await
+            {
+                dialed.insert(peer_id);
+                tx.send(false).unwrap();
+            }
+            dialed
+        });
+
+        let result = net_bootstrap(data).await.unwrap();
+        assert_eq!(result.attempted as usize, bootstrap_peers.len());
+        assert_eq!(result.succeeded, 0);
+        assert_eq!(responder.await.unwrap(), bootstrap_peers);
+    }
+
+    #[tokio::test]
+    async fn net_bootstrap_add_persists_peer() {
+        let data = Arc::new(Arc::new(RPCState::calibnet()));
+        let addr = "/dns4/bootstrap.example.com/tcp/1347/p2p/12D3KooWCVe8MmsEMes2FzgTpt9fXtmCY7wrq91GRiaC8PHSCCBj";
+
+        let params = serde_json::to_string(&["/dns4/bootstrap.example.com/tcp/1347"]).unwrap();
+        assert!(net_bootstrap_add(Params::new(Some(&params)), data.clone())
+            .await
+            .is_err());
+
+        let params = serde_json::to_string(&[addr]).unwrap();
+        for _ in 0..2 {
+            net_bootstrap_add(Params::new(Some(&params)), data.clone())
+                .await
+                .unwrap();
+        }
+
+        let peers = net_bootstrap_list(data).await.unwrap();
+        assert_eq!(peers.iter().filter(|peer| *peer == addr).count(), 1);
+    }
+}
@@ -0,0 +1,318 @@
+// Copyright 2019-2024 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use crate::rpc::auth_layer::AuthContext;
+use crate::rpc::RpcConfig;
+
+use ahash::HashSet;
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use jsonrpsee::server::middleware::rpc::RpcServiceT;
+use jsonrpsee::types::{ErrorObject, ErrorObjectOwned};
+use jsonrpsee::MethodResponse;
+use lru::LruCache;
+use nonzero_ext::nonzero;
+use parking_lot::Mutex;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tower::Layer;
+
+use std::net::IpAddr;
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Error code returned when a call exceeds a rate or concurrency limit, as
+/// defined by EIP-1474 for "limit exceeded"
+pub const LIMIT_EXCEEDED_CODE: i32 = -32005;
+
+/// Number of clients whose rate is tracked. The least recently seen clients
+/// are forgotten first.
+const MAX_TRACKED_CLIENTS: NonZeroUsize = nonzero!(10_000usize);
+
+/// Identifies a client for per-client rate limiting.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub enum ClientId {
+    /// Clients presenting a verified JWT are told apart by its identifier, so
+    /// that encoding the same token differently doesn't yield a new budget
+    Token(String),
+    Ip(IpAddr),
+    /// Clients connected through the Unix socket
+    Local,
+}
+
+impl ClientId {
+    /// Identifies the client of an authorized call. Tokens without an
+    /// identifier, like the admin token, aren't told apart from one another.
+    pub fn new(auth: &AuthContext, remote: Option<IpAddr>) -> Self {
+        match (auth.token_id(), remote) {
+            (Some(id), _) => Self::Token(id.to_owned()),
+            (None, Some(ip)) => Self::Ip(ip),
+            (None, None) => Self::Local,
+        }
+    }
+}
+
+/// Allows bursts of up to one second worth of calls.
+#[derive(Debug)]
+struct TokenBucket {
+    rate: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: u32) -> Self {
+        Self {
+            rate: rate.into(),
+            tokens: rate.into(),
+            last_refill: Instant::now(),
+        }
+    }
+
+    fn try_take(&mut self, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * self.rate).min(self.rate);
+        self.last_refill = now;
+        if self.tokens >= 1. {
+            self.tokens -= 1.;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Limits shared by all RPC connections.
+pub struct RateLimiter {
+    global: Option<Mutex<TokenBucket>>,
+    client_rate: Option<u32>,
+    clients: Mutex<LruCache<ClientId, TokenBucket>>,
+    heavy_methods: HashSet<String>,
+    heavy_calls: Option<Arc<Semaphore>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: &RpcConfig) -> Self {
+        Self {
+            global: config
+                .rate_limit
+                .map(|rate| Mutex::new(TokenBucket::new(rate))),
+            client_rate: config.client_rate_limit,
+            clients: Mutex::new(LruCache::new(MAX_TRACKED_CLIENTS)),
+            heavy_methods: config.heavy_methods.iter().cloned().collect(),
+            heavy_calls: config
+                .max_concurrent_heavy_calls
+                .map(|max| Arc::new(Semaphore::new(max as usize))),
+        }
+    }
+
+    /// Returns a permit to hold while a heavy method executes, or an error if
+    /// the call exceeds any limit.
+    fn check(
+        &self,
+        client: &ClientId,
+        method: &str,
+    ) -> Result<Option<OwnedSemaphorePermit>, ErrorObjectOwned> {
+        let now = Instant::now();
+        // Checked first, so that a client over its own limit cannot use up the
+        // global budget of the others
+        if let Some(rate) = self.client_rate {
+            let mut clients = self.clients.lock();
+            let bucket = clients.get_or_insert_mut(client.clone(), || TokenBucket::new(rate));
+            if !bucket.try_take(now) {
+                return Err(limit_exceeded("Client rate limit exceeded"));
+            }
+        }
+        if let Some(global) = &self.global {
+            if !global.lock().try_take(now) {
+                return Err(limit_exceeded("Rate limit exceeded"));
+            }
+        }
+        match &self.heavy_calls {
+            Some(heavy_calls) if self.heavy_methods.contains(method) => heavy_calls
+                .clone()
+                .try_acquire_owned()
+                .map(Some)
+                .map_err(|_| limit_exceeded("Too many concurrent calls to expensive methods")),
+            _ => Ok(None),
+        }
+    }
+}
+
+fn limit_exceeded(message: &str) -> ErrorObjectOwned {
+    ErrorObject::owned(LIMIT_EXCEEDED_CODE, message, None::<()>)
+}
+
+#[derive(Clone)]
+pub struct RateLimitLayer {
+    pub limiter: Arc<RateLimiter>,
+    /// Authorization of the connection, checked before the limits are
+    pub auth: Arc<AuthContext>,
+    pub remote: Option<IpAddr>,
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimitMiddleware<S>;
+
+    fn layer(&self, service: S) -> Self::Service {
+        RateLimitMiddleware {
+            limiter: self.limiter.clone(),
+            auth: self.auth.clone(),
+            remote: self.remote,
+            service,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RateLimitMiddleware<S> {
+    limiter: Arc<RateLimiter>,
+    auth: Arc<AuthContext>,
+    remote: Option<IpAddr>,
+    service: S,
+}
+
+impl<'a, S> RpcServiceT<'a> for RateLimitMiddleware<S>
+where
+    S: RpcServiceT<'a> + Send + Sync + Clone + 'static,
+{
+    type Future = BoxFuture<'a, MethodResponse>;
+
+    fn call(&self, req: jsonrpsee::types::Request<'a>) -> Self::Future {
+        let client = ClientId::new(&self.auth, self.remote);
+        match self.limiter.check(&client, req.method_name()) {
+            Ok(permit) => {
+                let service = self.service.clone();
+                async move {
+                    let response = service.call(req).await;
+                    drop(permit);
+                    response
+                }
+                .boxed()
+            }
+            Err(err) => {
+                let response = MethodResponse::error(req.id(), err);
+                async move { response }.boxed()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::{
+        create_token, create_token_with_id, generate_priv_key, TokenRegistry, ADMIN, JWT_IDENTIFIER,
+    };
+    use crate::key_management::{KeyStore, KeyStoreConfig};
+    use crate::rpc_api::{chain_api::CHAIN_EXPORT, common_api::VERSION};
+    use hyper::header::AUTHORIZATION;
+    use hyper::HeaderMap;
+    use tokio::sync::RwLock;
+
+    fn hammer(limiter: &RateLimiter, client: &ClientId, calls: usize) -> Vec<Option<i32>> {
+        (0..calls)
+            .map(|_| limiter.check(client, VERSION).err().map(|e| e.code()))
+            .collect()
+    }
+
+    #[test]
+    fn client_rate_limit() {
+        let limiter = RateLimiter::new(&RpcConfig {
+            client_rate_limit: Some(5),
+            ..Default::default()
+        });
+        let noisy = ClientId::Ip([10, 0, 0, 1].into());
+        let results = hammer(&limiter, &noisy, 20);
+        assert_eq!(results[..5], [None; 5]);
+        assert!(results[5..].contains(&Some(LIMIT_EXCEEDED_CODE)));
+
+        // Other clients still get through
+        let quiet = ClientId::Ip([10, 0, 0, 2].into());
+        assert_eq!(hammer(&limiter, &quiet, 5), [None; 5]);
+        assert_eq!(
+            hammer(&limiter, &ClientId::Token("token".into()), 5),
+            [None; 5]
+        );
+    }
+
+    #[tokio::test]
+    async fn clients_told_apart_by_token_id() {
+        let mut keystore = KeyStore::new(KeyStoreConfig::Memory).unwrap();
+        let key = generate_priv_key();
+        keystore.put(JWT_IDENTIFIER, key.clone()).unwrap();
+        let keystore = RwLock::new(keystore);
+        let perms = ADMIN.iter().map(ToString::to_string).collect::<Vec<_>>();
+        let remote = Some([10, 0, 0, 1].into());
+
+        let keystore = &keystore;
+        let authorize = |token: String| async move {
+            let mut headers = HeaderMap::new();
+            headers.insert(AUTHORIZATION, token.parse().unwrap());
+            let auth = AuthContext::new(&headers);
+            // Unverified tokens don't tell clients apart
+            assert_eq!(
+                ClientId::new(&auth, remote),
+                ClientId::Ip([10, 0, 0, 1].into())
+            );
+            auth.check(
+                keystore,
+                &TokenRegistry::default(),
+                &Default::default(),
+                VERSION,
+            )
+            .await
+            .unwrap();
+            ClientId::new(&auth, remote)
+        };
+
+        let token =
+            create_token_with_id(perms.clone(), key.private_key(), None, "id".into()).unwrap();
+        assert_eq!(authorize(token.clone()).await, ClientId::Token("id".into()));
+        assert_eq!(
+            authorize(format!("Bearer {token}")).await,
+            ClientId::Token("id".into())
+        );
+
+        let token = create_token(
+            perms,
+            key.private_key(),
+            chrono::Duration::try_hours(1).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(authorize(token).await, ClientId::Ip([10, 0, 0, 1].into()));
+    }
+
+    #[test]
+    fn global_rate_limit() {
+        let limiter = RateLimiter::new(&RpcConfig {
+            rate_limit: Some(5),
+            ..Default::default()
+        });
+        let a = ClientId::Ip([10, 0, 0, 1].into());
+        let b = ClientId::Local;
+        assert_eq!(hammer(&limiter, &a, 3), [None; 3]);
+        assert_eq!(hammer(&limiter, &b, 2), [None; 2]);
+        assert!(hammer(&limiter, &b, 10).contains(&Some(LIMIT_EXCEEDED_CODE)));
+    }
+
+    #[test]
+    fn heavy_methods() {
+        let limiter = RateLimiter::new(&RpcConfig {
+            max_concurrent_heavy_calls: Some(1),
+            ..Default::default()
+        });
+        let client = ClientId::Local;
+        let permit = limiter.check(&client, CHAIN_EXPORT).unwrap();
+        assert!(permit.is_some());
+        assert_eq!(
+            limiter.check(&client, CHAIN_EXPORT).unwrap_err().code(),
+            LIMIT_EXCEEDED_CODE
+        );
+        // Other methods are not capped
+        assert!(limiter.check(&client, VERSION).unwrap().is_none());
+
+        drop(permit);
+        assert!(limiter.check(&client, CHAIN_EXPORT).unwrap().is_some());
+    }
+}
@@ -51,6 +51,19 @@ pub type Ctx<T> = Arc<Arc<crate::rpc::RPCState<T>>>;
 /// Type to be used by [`SelfDescribingRpcModule`] and [`RpcModule`].
 type ModuleState<T> = Arc<crate::rpc::RPCState<T>>;
 
+/// Permission a JWT must grant to call an RPC method, in increasing order of
+/// privilege.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    Read,
+    Write,
+    Sign,
+    Admin,
+}
+
+/// Mapping between method names and the permission required to call them.
+pub type MethodPermissions = ahash::HashMap<&'static str, Permission>;
+
 /// A definition of an RPC method handler which can be registered with a
 /// [`SelfDescribingRpcModule`].
 ///
@@ -66,6 +79,11 @@ pub trait RpcMethod<const ARITY: usize> {
     const NAME: &'static str;
     /// Name of each argument, MUST be unique.
     const PARAM_NAMES: [&'static str; ARITY];
+    /// Permission required to call this method.
+    const PERMISSION: Permission;
+    /// Whether the only argument of this method may also be sent as the bare
+    /// object of its fields, as done by clients predating this trait.
+    const ACCEPTS_BARE_OBJECT: bool = false;
     /// Types of each argument. [`Option`]-al arguments MUST follow mandatory ones.
     type Params: Params<ARITY>;
     /// Return value of this method.
@@ -141,6 +159,14 @@ pub trait RpcMethodExt<const ARITY: usize>: RpcMethod<ARITY> {
                 .map(serde_json::from_str)
                 .transpose()
                 .map_err(|e| Error::invalid_params(e, None))?;
+            let raw = match (raw, Self::PARAM_NAMES.as_slice()) {
+                (Some(RequestParameters::ByName(fields)), [name])
+                    if Self::ACCEPTS_BARE_OBJECT && !fields.contains_key(*name) =>
+                {
+                    Some(RequestParameters::ByPosition(vec![fields.into()]))
+                }
+                (raw, _) => raw,
+            };
             let params = Self::Params::parse(raw, Self::PARAM_NAMES, calling_convention)?;
             let ok = Self::handle(ctx, params).await?;
             Result::<_, jsonrpsee::types::ErrorObjectOwned>::Ok(ok)
@@ -154,13 +180,14 @@ pub trait RpcMethodExt<const ARITY: usize>: RpcMethod<ARITY> {
         Self::Ok: JsonSchema + Deserialize<'de>,
     {
         Self::register_raw(&mut module.inner, module.calling_convention).unwrap();
+        module.permissions.insert(Self::NAME, Self::PERMISSION);
         module
             .methods
             .push(Self::openrpc(&mut module.schema_generator, module.calling_convention).unwrap());
     }
     /// Call this method on an [`RpcModule`].
     fn call_module(
-        module: &RpcModule<Ctx<impl Blockstore + Send + Sync + 'static>>,
+        module: &RpcModule<ModuleState<impl Blockstore + Send + Sync + 'static>>,
         params: Self::Params,
         calling_convention: ConcreteCallingConvention,
     ) -> impl Future<Output = Result<Self::Ok, MethodsError>> + Send
@@ -293,6 +320,7 @@ pub struct SelfDescribingRpcModule<Ctx> {
     schema_generator: SchemaGenerator,
     calling_convention: ParamStructure,
     methods: Vec<Method>,
+    permissions: MethodPermissions,
 }
 
 impl<Ctx> SelfDescribingRpcModule<Ctx> {
@@ -302,23 +330,35 @@ impl<Ctx> SelfDescribingRpcModule<Ctx> {
             schema_generator: SchemaGenerator::new(SchemaSettings::openapi3()),
             calling_convention,
             methods: vec![],
+            permissions: MethodPermissions::default(),
         }
     }
-    pub fn finish(self) -> (jsonrpsee::server::RpcModule<Ctx>, openrpc_types::OpenRPC) {
+    pub fn finish(
+        self,
+        info: openrpc_types::Info,
+    ) -> (
+        jsonrpsee::server::RpcModule<Ctx>,
+        openrpc_types::OpenRPC,
+        MethodPermissions,
+    ) {
         let Self {
             inner,
             mut schema_generator,
             methods,
             calling_convention: _,
+            permissions,
         } = self;
         (
             inner,
             openrpc_types::OpenRPC {
+                openrpc: String::from(openrpc_types::OPEN_RPC_VERSION),
+                info,
                 methods: openrpc_types::Methods::new(methods).unwrap(),
                 components: openrpc_types::Components {
                     schemas: schema_generator.take_definitions().into_iter().collect(),
                 },
             },
+            permissions,
         )
     }
 }
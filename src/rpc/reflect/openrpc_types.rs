@@ -13,8 +13,17 @@ use schemars::schema::Schema;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+/// Version of the `OpenRPC` specification the documents conform to.
+pub const OPEN_RPC_VERSION: &str = "1.2.6";
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct OpenRPC {
+    /// > REQUIRED.
+    /// > This string MUST be the semantic version number of the OpenRPC Specification version that the OpenRPC document uses.
+    pub openrpc: String,
+    /// > REQUIRED.
+    /// > Provides metadata about the API.
+    pub info: Info,
     /// > REQUIRED.
     /// > The available methods for the API.
     /// > While it is required, the array may be empty (to handle security filtering, for example).
@@ -24,7 +33,16 @@ pub struct OpenRPC {
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Info {
+    /// > REQUIRED.
+    /// > The title of the application.
+    pub title: String,
+    /// > REQUIRED.
+    /// > The version of the OpenRPC document (which is distinct from the OpenRPC Specification version or the API implementation version).
+    pub version: String,
+}
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Components {
     pub schemas: BTreeMap<String, Schema>,
 }
@@ -5,23 +5,24 @@
 use crate::cid_collections::CidHashSet;
 use crate::libp2p::NetworkMessage;
 use crate::lotus_json::LotusJson;
+use crate::networks::NetworkChain;
 use crate::rpc::error::JsonRpcError;
+use crate::rpc::reflect::{Permission, RpcMethod};
 use crate::rpc::Ctx;
-use crate::rpc_api::data_types::*;
+use crate::rpc_api::{data_types::*, state_api::*};
 use crate::shim::{
-    address::Address, clock::ChainEpoch, deal::DealID, econ::TokenAmount, executor::Receipt,
-    state_tree::ActorState, version::NetworkVersion,
+    address::Address, clock::ChainEpoch, deadline, deadline::DeadlineInfo, deal::DealID,
+    econ::TokenAmount, executor::Receipt, state_tree::ActorState, version::NetworkVersion,
 };
 use crate::state_manager::chain_rand::ChainRand;
 use crate::state_manager::vm_circ_supply::GenesisInfo;
-use crate::state_manager::{InvocResult, MarketBalance};
+use crate::state_manager::MarketBalance;
+use crate::state_migration::{migration_progress, MigrationStatus};
 use crate::utils::db::car_stream::{CarBlock, CarWriter};
-use ahash::{HashMap, HashMapExt};
 use anyhow::Context as _;
 use anyhow::Result;
 use cid::Cid;
 use fil_actor_interface::market::DealState;
-use fil_actor_interface::miner::DeadlineInfo;
 use fil_actor_interface::{
     market, miner,
     miner::{MinerInfo, MinerPower},
@@ -36,12 +37,12 @@ use libipld_core::ipld::Ipld;
 use nonempty::{nonempty, NonEmpty};
 use num_bigint::BigInt;
 use parking_lot::Mutex;
+use std::collections::BTreeMap;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::{sync::Arc, time::Duration};
 use tokio::task::JoinSet;
 
-type RandomnessParams = (i64, ChainEpoch, Vec<u8>, ApiTipsetKey);
-
 pub async fn miner_get_base_info<DB: Blockstore + Send + Sync + 'static>(
     params: Params<'_>,
     data: Ctx<DB>,
@@ -72,34 +73,69 @@ pub async fn state_call<DB: Blockstore + Send + Sync + 'static>(
         .load_required_tipset_or_heaviest(&key)?;
     // Handle expensive fork error?
     // TODO(elmattic): https://github.com/ChainSafe/forest/issues/3733
-    Ok(state_manager.call(&message, Some(tipset))?)
+    // Executed on a blocking thread to not starve the executor. The VM can't
+    // be interrupted, so if this future is dropped, e.g. when the RPC call
+    // times out, the call is only halted before the next message it applies.
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let _cancel_on_drop = scopeguard::guard(cancelled.clone(), |cancelled| {
+        cancelled.store(true, Ordering::Relaxed)
+    });
+    let state_manager = state_manager.clone();
+    Ok(
+        tokio::task::spawn_blocking(move || state_manager.call(&message, Some(tipset), &cancelled))
+            .await??,
+    )
 }
 
 /// returns the result of executing the indicated message, assuming it was
-/// executed in the indicated tipset.
+/// executed in the indicated tipset, or else in the one it was included in.
 pub async fn state_replay<DB: Blockstore + Send + Sync + 'static>(
     params: Params<'_>,
     data: Ctx<DB>,
-) -> Result<InvocResult, JsonRpcError> {
-    let LotusJson((cid, ApiTipsetKey(key))) = params.parse()?;
+) -> Result<ApiInvocResult, JsonRpcError> {
+    let LotusJson((ApiTipsetKey(key), cid)) = params.parse()?;
 
     let state_manager = &data.state_manager;
+    let tipset = match key {
+        Some(key) => data.chain_store.chain_index.load_required_tipset(&key)?,
+        None => {
+            let (executed, _) = state_manager
+                .search_for_message(None, cid, None, true)
+                .await?
+                .ok_or_else(|| JsonRpcError::message_not_found(&cid))?;
+            data.chain_store
+                .chain_index
+                .load_required_tipset(executed.parents())?
+        }
+    };
+    Ok(state_manager.replay(&tipset, cid).await?)
+}
+
+/// computes the state at the given epoch on top of the indicated tipset,
+/// applying the given messages, and returns the traces of its computation.
+pub async fn state_compute<DB: Blockstore + Send + Sync + 'static>(
+    params: Params<'_>,
+    data: Ctx<DB>,
+) -> Result<ComputeStateOutput, JsonRpcError> {
+    let LotusJson((epoch, messages, ApiTipsetKey(key))) = params.parse()?;
+
     let tipset = data
         .state_manager
         .chain_store()
         .load_required_tipset_or_heaviest(&key)?;
-    let (msg, ret) = state_manager.replay(&tipset, cid).await?;
-
-    Ok(InvocResult {
-        msg,
-        msg_rct: Some(ret.msg_receipt()),
-        error: ret.failure_info(),
-    })
+    let (root, trace) = data
+        .state_manager
+        .compute_state(epoch, messages, tipset)
+        .await?;
+    Ok(ComputeStateOutput { root, trace })
 }
 
-/// gets network name from state manager
+/// gets network name from state manager, or the configured name of a devnet
 pub async fn state_network_name<DB: Blockstore>(data: Ctx<DB>) -> Result<String, JsonRpcError> {
     let state_manager = &data.state_manager;
+    if let NetworkChain::Devnet(name) = &state_manager.chain_config().network {
+        return Ok(name.clone());
+    }
     let heaviest_tipset = state_manager.chain_store().heaviest_tipset();
 
     state_manager
@@ -107,6 +143,50 @@ pub async fn state_network_name<DB: Blockstore>(data: Ctx<DB>) -> Result<String,
         .map_err(|e| e.into())
 }
 
+/// Returns the network of the node, identified by its genesis, along with the
+/// parameters clients need to build and sign messages for it.
+pub enum StateNetworkIdentity {}
+impl RpcMethod<0> for StateNetworkIdentity {
+    const NAME: &'static str = STATE_NETWORK_IDENTITY;
+    const PARAM_NAMES: [&'static str; 0] = [];
+    const PERMISSION: Permission = Permission::Read;
+    type Params = ();
+    type Ok = NetworkIdentity;
+
+    async fn handle(
+        data: Ctx<impl Blockstore>,
+        (): Self::Params,
+    ) -> Result<Self::Ok, JsonRpcError> {
+        let chain_config = data.state_manager.chain_config();
+        let genesis = data.chain_store.genesis_block_header();
+        let network =
+            NetworkChain::from_genesis_or_devnet(genesis.cid(), Some(chain_config), || {
+                Ok(data.state_manager.get_network_name(&genesis.state_root)?)
+            })?;
+        Ok(NetworkIdentity {
+            network,
+            genesis_cid: *genesis.cid(),
+            eth_chain_id: chain_config.eth_chain_id,
+            block_delay: chain_config.block_delay_secs,
+        })
+    }
+}
+
+/// Reports the progress of the state migration being run, if any, either at an
+/// upgrade epoch or ahead of it.
+pub enum StateMigrationStatus {}
+impl RpcMethod<0> for StateMigrationStatus {
+    const NAME: &'static str = STATE_MIGRATION_STATUS;
+    const PARAM_NAMES: [&'static str; 0] = [];
+    const PERMISSION: Permission = Permission::Read;
+    type Params = ();
+    type Ok = Option<MigrationStatus>;
+
+    async fn handle(_: Ctx<impl Blockstore>, (): Self::Params) -> Result<Self::Ok, JsonRpcError> {
+        Ok(migration_progress().status())
+    }
+}
+
 pub async fn state_get_network_version<DB: Blockstore>(
     params: Params<'_>,
     data: Ctx<DB>,
@@ -155,20 +235,26 @@ where
     let ret = data
         .state_manager
         .lookup_id(&address, ts.as_ref())?
-        .with_context(|| format!("Failed to lookup the id address for address: {address} and tipset keys: {tipset_keys}"))?;
+        .ok_or_else(|| JsonRpcError::actor_not_found(&address))?;
     Ok(LotusJson(ret))
 }
 
-pub(crate) async fn state_get_actor<DB: Blockstore>(
-    params: Params<'_>,
-    data: Ctx<DB>,
-) -> Result<LotusJson<Option<ActorState>>, JsonRpcError> {
-    let LotusJson((addr, ApiTipsetKey(tsk))): LotusJson<(Address, ApiTipsetKey)> =
-        params.parse()?;
-
-    let ts = data.chain_store.load_required_tipset_or_heaviest(&tsk)?;
-    let state = data.state_manager.get_actor(&addr, *ts.parent_state());
-    state.map(Into::into).map_err(|e| e.into())
+pub enum StateGetActor {}
+impl RpcMethod<2> for StateGetActor {
+    const NAME: &'static str = STATE_GET_ACTOR;
+    const PARAM_NAMES: [&'static str; 2] = ["address", "tsk"];
+    const PERMISSION: Permission = Permission::Read;
+    type Params = (LotusJson<Address>, LotusJson<ApiTipsetKey>);
+    type Ok = LotusJson<Option<ActorState>>;
+
+    async fn handle(
+        data: Ctx<impl Blockstore>,
+        (LotusJson(addr), LotusJson(ApiTipsetKey(tsk))): Self::Params,
+    ) -> Result<Self::Ok, JsonRpcError> {
+        let ts = data.chain_store.load_required_tipset_or_heaviest(&tsk)?;
+        let state = data.state_manager.get_actor(&addr, *ts.parent_state());
+        state.map(Into::into).map_err(|e| e.into())
+    }
 }
 
 /// looks up the Escrow and Locked balances of the given address in the Storage
@@ -189,171 +275,280 @@ pub async fn state_market_balance<DB: Blockstore + Send + Sync + 'static>(
         .map_err(|e| e.into())
 }
 
-pub async fn state_market_deals<DB: Blockstore>(
+/// Maximum number of deals returned by `StateMarketDeals`. Larger sets of
+/// deals, e.g. all those of mainnet, must be paged with a
+/// [`MarketDealsFilter`].
+const MARKET_DEALS_MAX_PAGE: u64 = 100_000;
+
+/// returns the deals of the market actor, optionally a page of them selected by
+/// a [`MarketDealsFilter`]. Fails without a limit if there are more than
+/// [`MARKET_DEALS_MAX_PAGE`] of them, so that unpaged calls only succeed on
+/// small networks like calibnet.
+pub async fn state_market_deals<DB: Blockstore + Send + Sync + 'static>(
     params: Params<'_>,
     data: Ctx<DB>,
-) -> Result<HashMap<String, MarketDeal>, JsonRpcError> {
-    let LotusJson((ApiTipsetKey(tsk),)): LotusJson<(ApiTipsetKey,)> = params.parse()?;
+) -> Result<BTreeMap<DealID, ApiMarketDeal>, JsonRpcError> {
+    let mut params = params.sequence();
+    let LotusJson(ApiTipsetKey(tsk)) = params.next()?;
+    let filter: Option<LotusJson<MarketDealsFilter>> = params.optional_next()?;
 
     let ts = data.chain_store.load_required_tipset_or_heaviest(&tsk)?;
-    let actor = data
+    let market_actor = data
         .state_manager
         .get_actor(&Address::MARKET_ACTOR, *ts.parent_state())?
         .context("Market actor address could not be resolved")?;
-    let market_state =
-        market::State::load(data.state_manager.blockstore(), actor.code, actor.state)?;
-
-    let da = market_state.proposals(data.state_manager.blockstore())?;
-    let sa = market_state.states(data.state_manager.blockstore())?;
-
-    let mut out = HashMap::new();
-    da.for_each(|deal_id, d| {
-        let s = sa.get(deal_id)?.unwrap_or(market::DealState {
-            sector_start_epoch: -1,
-            last_updated_epoch: -1,
-            slash_epoch: -1,
-            verified_claim: 0,
-        });
-        out.insert(
-            deal_id.to_string(),
-            MarketDeal {
-                proposal: d?,
-                state: s,
-            },
+    let store = data.state_manager.blockstore_owned();
+    let filter = filter.map(LotusJson::into_inner).unwrap_or_default();
+    // Reading the deals blocks, so it is done on a blocking thread
+    Ok(tokio::task::spawn_blocking(move || {
+        market_deals(
+            store.as_ref(),
+            &market_actor,
+            &filter,
+            MARKET_DEALS_MAX_PAGE,
+        )
+    })
+    .await??)
+}
+
+/// Loads the page of the deals of `market_actor` selected by `filter`, in the
+/// order of their IDs, starting from the first ID of its range. Fails if there
+/// are more than `max_page` deals in the page.
+fn market_deals(
+    store: &impl Blockstore,
+    market_actor: &ActorState,
+    filter: &MarketDealsFilter,
+    max_page: u64,
+) -> anyhow::Result<BTreeMap<DealID, ApiMarketDeal>> {
+    let market_state = market::State::load(store, market_actor.code, market_actor.state)?;
+    let proposals = market_state.proposals(store)?;
+    let states = market_state.states(store)?;
+
+    let (first, end) = filter.deal_id_range.unwrap_or((0, DealID::MAX));
+    let limit = match filter.limit {
+        Some(limit) => {
+            anyhow::ensure!(
+                limit <= max_page,
+                "the limit of deals {limit} is above the maximum of {max_page}"
+            );
+            limit
+        }
+        // One more, to tell whether there are too many deals
+        None => max_page + 1,
+    };
+    let mut to_skip = filter.offset;
+    let mut deals = BTreeMap::new();
+    for_each_deal_proposal_while(&proposals, first, |deal_id, proposal| {
+        if deal_id >= end || deals.len() as u64 == limit {
+            return Ok(false);
+        }
+        if to_skip > 0 {
+            to_skip -= 1;
+            return Ok(true);
+        }
+        let state = states.get(deal_id)?.unwrap_or_else(DealState::empty);
+        deals.insert(
+            deal_id,
+            ApiMarketDeal::from(MarketDeal {
+                proposal: proposal?,
+                state,
+            }),
         );
-        Ok(())
+        Ok(true)
     })?;
-    Ok(out)
+    anyhow::ensure!(
+        deals.len() as u64 <= max_page,
+        "there are more than {max_page} deals, select a page of them with a limit"
+    );
+    Ok(deals)
 }
 
-/// looks up the miner info of the given address.
-pub async fn state_miner_info<DB: Blockstore + Send + Sync + 'static>(
-    params: Params<'_>,
-    data: Ctx<DB>,
-) -> Result<LotusJson<MinerInfo>, JsonRpcError> {
-    let LotusJson((address, ApiTipsetKey(key))): LotusJson<(Address, ApiTipsetKey)> =
-        params.parse()?;
-
-    let tipset = data
-        .state_manager
-        .chain_store()
-        .load_required_tipset_or_heaviest(&key)?;
-    Ok(LotusJson(data.state_manager.miner_info(&address, &tipset)?))
+/// Calls `f` with the deal proposals from the ID `start_at`, in the order of
+/// their IDs, until it returns `false`.
+fn for_each_deal_proposal_while<BS: Blockstore>(
+    proposals: &market::DealProposals<BS>,
+    start_at: DealID,
+    mut f: impl FnMut(DealID, anyhow::Result<market::DealProposal>) -> anyhow::Result<bool>,
+) -> anyhow::Result<()> {
+    use market::DealProposals::*;
+    match proposals {
+        V9(amt) => amt.for_each_while_ranged(Some(start_at), None, |id, proposal| {
+            f(id, proposal.try_into())
+        }),
+        V10(amt) => amt.for_each_while_ranged(Some(start_at), None, |id, proposal| {
+            f(id, proposal.try_into())
+        }),
+        V11(amt) => amt.for_each_while_ranged(Some(start_at), None, |id, proposal| {
+            f(id, proposal.try_into())
+        }),
+        V12(amt) => amt.for_each_while_ranged(Some(start_at), None, |id, proposal| {
+            f(id, proposal.try_into())
+        }),
+        V13(amt) => amt.for_each_while_ranged(Some(start_at), None, |id, proposal| {
+            f(id, proposal.try_into())
+        }),
+    }?;
+    Ok(())
 }
 
-pub async fn state_miner_active_sectors<DB: Blockstore>(
-    params: Params<'_>,
-    data: Ctx<DB>,
-) -> Result<LotusJson<Vec<SectorOnChainInfo>>, JsonRpcError> {
-    let LotusJson((miner, ApiTipsetKey(tsk))): LotusJson<(Address, ApiTipsetKey)> =
-        params.parse()?;
+/// looks up the miner info of the given address.
+pub enum StateMinerInfo {}
+impl RpcMethod<2> for StateMinerInfo {
+    const NAME: &'static str = STATE_MINER_INFO;
+    const PARAM_NAMES: [&'static str; 2] = ["miner", "tsk"];
+    const PERMISSION: Permission = Permission::Read;
+    type Params = (LotusJson<Address>, LotusJson<ApiTipsetKey>);
+    type Ok = LotusJson<MinerInfo>;
+
+    async fn handle(
+        data: Ctx<impl Blockstore + Send + Sync + 'static>,
+        (LotusJson(address), LotusJson(ApiTipsetKey(key))): Self::Params,
+    ) -> Result<Self::Ok, JsonRpcError> {
+        let tipset = data
+            .state_manager
+            .chain_store()
+            .load_required_tipset_or_heaviest(&key)?;
+        Ok(LotusJson(data.state_manager.miner_info(&address, &tipset)?))
+    }
+}
 
-    let bs = data.state_manager.blockstore();
-    let ts = data.chain_store.load_required_tipset_or_heaviest(&tsk)?;
-    let policy = &data.state_manager.chain_config().policy;
-    let actor = data
-        .state_manager
-        .get_actor(&miner, *ts.parent_state())?
-        .context("Miner actor address could not be resolved")?;
-    let miner_state = miner::State::load(bs, actor.code, actor.state)?;
-
-    // Collect active sectors from each partition in each deadline.
-    let mut active_sectors = vec![];
-    miner_state.for_each_deadline(policy, bs, |_dlidx, deadline| {
-        deadline.for_each(bs, |_partidx, partition| {
-            active_sectors.push(partition.active_sectors());
-            Ok(())
-        })
-    })?;
+pub enum StateMinerActiveSectors {}
+impl RpcMethod<2> for StateMinerActiveSectors {
+    const NAME: &'static str = STATE_MINER_ACTIVE_SECTORS;
+    const PARAM_NAMES: [&'static str; 2] = ["miner", "tsk"];
+    const PERMISSION: Permission = Permission::Read;
+    type Params = (LotusJson<Address>, LotusJson<ApiTipsetKey>);
+    type Ok = LotusJson<Vec<SectorOnChainInfo>>;
+
+    async fn handle(
+        data: Ctx<impl Blockstore>,
+        (LotusJson(miner), LotusJson(ApiTipsetKey(tsk))): Self::Params,
+    ) -> Result<Self::Ok, JsonRpcError> {
+        let bs = data.state_manager.blockstore();
+        let ts = data.chain_store.load_required_tipset_or_heaviest(&tsk)?;
+        let policy = &data.state_manager.chain_config().policy;
+        let actor = data
+            .state_manager
+            .get_actor(&miner, *ts.parent_state())?
+            .ok_or_else(|| JsonRpcError::actor_not_found(&miner))?;
+        let miner_state = miner::State::load(bs, actor.code, actor.state)?;
+
+        // Collect active sectors from each partition in each deadline.
+        let mut active_sectors = vec![];
+        miner_state.for_each_deadline(policy, bs, |_dlidx, deadline| {
+            deadline.for_each(bs, |_partidx, partition| {
+                active_sectors.push(partition.active_sectors());
+                Ok(())
+            })
+        })?;
 
-    let sectors = miner_state
-        .load_sectors(bs, Some(&BitField::union(&active_sectors)))?
-        .into_iter()
-        .map(SectorOnChainInfo::from)
-        .collect::<Vec<_>>();
+        let sectors = miner_state
+            .load_sectors(bs, Some(&BitField::union(&active_sectors)))?
+            .into_iter()
+            .map(SectorOnChainInfo::from)
+            .collect::<Vec<_>>();
 
-    Ok(LotusJson(sectors))
+        Ok(LotusJson(sectors))
+    }
 }
 
 // Returns the number of sectors in a miner's sector set and proving set
-pub async fn state_miner_sector_count<DB: Blockstore>(
-    params: Params<'_>,
-    data: Ctx<DB>,
-) -> Result<LotusJson<MinerSectors>, JsonRpcError> {
-    let LotusJson((miner, ApiTipsetKey(tsk))): LotusJson<(Address, ApiTipsetKey)> =
-        params.parse()?;
-
-    let bs = data.state_manager.blockstore();
-    let ts = data.chain_store.load_required_tipset_or_heaviest(&tsk)?;
-    let policy = &data.state_manager.chain_config().policy;
-    let actor = data
-        .state_manager
-        .get_actor(&miner, *ts.parent_state())?
-        .context("Miner actor address could not be resolved")?;
-    let miner_state = miner::State::load(bs, actor.code, actor.state)?;
-
-    // Collect live, active and faulty sectors count from each partition in each deadline.
-    let mut live_count = 0;
-    let mut active_count = 0;
-    let mut faulty_count = 0;
-    miner_state.for_each_deadline(policy, bs, |_dlidx, deadline| {
-        deadline.for_each(bs, |_partidx, partition| {
-            live_count += partition.live_sectors().len();
-            active_count += partition.active_sectors().len();
-            faulty_count += partition.faulty_sectors().len();
-            Ok(())
-        })
-    })?;
-    Ok(LotusJson(MinerSectors::new(
-        live_count,
-        active_count,
-        faulty_count,
-    )))
+pub enum StateMinerSectorCount {}
+impl RpcMethod<2> for StateMinerSectorCount {
+    const NAME: &'static str = STATE_MINER_SECTOR_COUNT;
+    const PARAM_NAMES: [&'static str; 2] = ["miner", "tsk"];
+    const PERMISSION: Permission = Permission::Read;
+    type Params = (LotusJson<Address>, LotusJson<ApiTipsetKey>);
+    type Ok = LotusJson<MinerSectors>;
+
+    async fn handle(
+        data: Ctx<impl Blockstore>,
+        (LotusJson(miner), LotusJson(ApiTipsetKey(tsk))): Self::Params,
+    ) -> Result<Self::Ok, JsonRpcError> {
+        let bs = data.state_manager.blockstore();
+        let ts = data.chain_store.load_required_tipset_or_heaviest(&tsk)?;
+        let policy = &data.state_manager.chain_config().policy;
+        let actor = data
+            .state_manager
+            .get_actor(&miner, *ts.parent_state())?
+            .ok_or_else(|| JsonRpcError::actor_not_found(&miner))?;
+        let miner_state = miner::State::load(bs, actor.code, actor.state)?;
+
+        // Collect live, active and faulty sectors count from each partition in each deadline.
+        let mut live_count = 0;
+        let mut active_count = 0;
+        let mut faulty_count = 0;
+        miner_state.for_each_deadline(policy, bs, |_dlidx, deadline| {
+            deadline.for_each(bs, |_partidx, partition| {
+                live_count += partition.live_sectors().len();
+                active_count += partition.active_sectors().len();
+                faulty_count += partition.faulty_sectors().len();
+                Ok(())
+            })
+        })?;
+        Ok(LotusJson(MinerSectors::new(
+            live_count,
+            active_count,
+            faulty_count,
+        )))
+    }
 }
 
 /// looks up the miner power of the given address.
-pub async fn state_miner_power<DB: Blockstore + Send + Sync + 'static>(
-    params: Params<'_>,
-    data: Ctx<DB>,
-) -> Result<LotusJson<MinerPower>, JsonRpcError> {
-    let LotusJson((address, ApiTipsetKey(key))): LotusJson<(Address, ApiTipsetKey)> =
-        params.parse()?;
-
-    let tipset = data
-        .state_manager
-        .chain_store()
-        .load_required_tipset_or_heaviest(&key)?;
+pub enum StateMinerPower {}
+impl RpcMethod<2> for StateMinerPower {
+    const NAME: &'static str = STATE_MINER_POWER;
+    const PARAM_NAMES: [&'static str; 2] = ["miner", "tsk"];
+    const PERMISSION: Permission = Permission::Read;
+    type Params = (LotusJson<Address>, LotusJson<ApiTipsetKey>);
+    type Ok = LotusJson<MinerPower>;
+
+    async fn handle(
+        data: Ctx<impl Blockstore + Send + Sync + 'static>,
+        (LotusJson(address), LotusJson(ApiTipsetKey(key))): Self::Params,
+    ) -> Result<Self::Ok, JsonRpcError> {
+        let tipset = data
+            .state_manager
+            .chain_store()
+            .load_required_tipset_or_heaviest(&key)?;
 
-    data.state_manager
-        .miner_power(&address, &tipset)
-        .map(|res| res.into())
-        .map_err(|e| e.into())
+        data.state_manager
+            .miner_power(&address, &tipset)
+            .map(|res| res.into())
+            .map_err(|e| e.into())
+    }
 }
 
-pub async fn state_miner_deadlines<DB: Blockstore + Send + Sync + 'static>(
-    params: Params<'_>,
-    data: Ctx<DB>,
-) -> Result<LotusJson<Vec<ApiDeadline>>, JsonRpcError> {
-    let LotusJson((addr, ApiTipsetKey(tsk))): LotusJson<(Address, ApiTipsetKey)> =
-        params.parse()?;
-
-    let ts = data.chain_store.load_required_tipset_or_heaviest(&tsk)?;
-    let policy = &data.state_manager.chain_config().policy;
-    let actor = data
-        .state_manager
-        .get_actor(&addr, *ts.parent_state())?
-        .context("Miner actor address could not be resolved")?;
-    let store = data.state_manager.blockstore();
-    let state = miner::State::load(store, actor.code, actor.state)?;
-    let mut res = Vec::new();
-    state.for_each_deadline(policy, store, |_idx, deadline| {
-        res.push(ApiDeadline {
-            post_submissions: deadline.partitions_posted(),
-            disputable_proof_count: deadline.disputable_proof_count(store)?,
-        });
-        Ok(())
-    })?;
-    Ok(LotusJson(res))
+pub enum StateMinerDeadlines {}
+impl RpcMethod<2> for StateMinerDeadlines {
+    const NAME: &'static str = STATE_MINER_DEADLINES;
+    const PARAM_NAMES: [&'static str; 2] = ["miner", "tsk"];
+    const PERMISSION: Permission = Permission::Read;
+    type Params = (LotusJson<Address>, LotusJson<ApiTipsetKey>);
+    type Ok = LotusJson<Vec<ApiDeadline>>;
+
+    async fn handle(
+        data: Ctx<impl Blockstore + Send + Sync + 'static>,
+        (LotusJson(addr), LotusJson(ApiTipsetKey(tsk))): Self::Params,
+    ) -> Result<Self::Ok, JsonRpcError> {
+        let ts = data.chain_store.load_required_tipset_or_heaviest(&tsk)?;
+        let policy = &data.state_manager.chain_config().policy;
+        let actor = data
+            .state_manager
+            .get_actor(&addr, *ts.parent_state())?
+            .ok_or_else(|| JsonRpcError::actor_not_found(&addr))?;
+        let store = data.state_manager.blockstore();
+        let state = miner::State::load(store, actor.code, actor.state)?;
+        let mut res = Vec::new();
+        state.for_each_deadline(policy, store, |_idx, deadline| {
+            res.push(ApiDeadline {
+                post_submissions: deadline.partitions_posted(),
+                disputable_proof_count: deadline.disputable_proof_count(store)?,
+            });
+            Ok(())
+        })?;
+        Ok(LotusJson(res))
+    }
 }
 
 pub async fn state_miner_proving_deadline<DB: Blockstore + Send + Sync + 'static>(
@@ -368,95 +563,171 @@ pub async fn state_miner_proving_deadline<DB: Blockstore + Send + Sync + 'static
     let actor = data
         .state_manager
         .get_actor(&addr, *ts.parent_state())?
-        .context("Miner actor address could not be resolved")?;
+        .ok_or_else(|| JsonRpcError::actor_not_found(&addr))?;
     let store = data.state_manager.blockstore();
     let state = miner::State::load(store, actor.code, actor.state)?;
-    Ok(LotusJson(state.deadline_info(policy, ts.epoch())))
+    Ok(LotusJson(
+        deadline::current_deadline_info(policy, &state, ts.epoch()).next_not_elapsed(),
+    ))
 }
 
-/// looks up the miner power of the given address.
-pub async fn state_miner_faults<DB: Blockstore + Send + Sync + 'static>(
-    params: Params<'_>,
-    data: Ctx<DB>,
-) -> Result<LotusJson<BitField>, JsonRpcError> {
-    let LotusJson((address, ApiTipsetKey(key))): LotusJson<(Address, ApiTipsetKey)> =
-        params.parse()?;
+/// returns the deadline of a miner open at a given epoch, going by its state at
+/// the given tipset.
+pub enum StateMinerCurrentDeadline {}
+impl RpcMethod<3> for StateMinerCurrentDeadline {
+    const NAME: &'static str = STATE_MINER_CURRENT_DEADLINE;
+    const PARAM_NAMES: [&'static str; 3] = ["miner", "epoch", "tsk"];
+    const PERMISSION: Permission = Permission::Read;
+    type Params = (
+        LotusJson<Address>,
+        LotusJson<ChainEpoch>,
+        LotusJson<ApiTipsetKey>,
+    );
+    type Ok = LotusJson<DeadlineInfo>;
+
+    async fn handle(
+        data: Ctx<impl Blockstore + Send + Sync + 'static>,
+        (LotusJson(addr), LotusJson(epoch), LotusJson(ApiTipsetKey(tsk))): Self::Params,
+    ) -> Result<Self::Ok, JsonRpcError> {
+        let ts = data.chain_store.load_required_tipset_or_heaviest(&tsk)?;
+        let policy = &data.state_manager.chain_config().policy;
+        let actor = data
+            .state_manager
+            .get_actor(&addr, *ts.parent_state())?
+            .ok_or_else(|| JsonRpcError::actor_not_found(&addr))?;
+        let store = data.state_manager.blockstore();
+        let state = miner::State::load(store, actor.code, actor.state)?;
+        Ok(LotusJson(deadline::current_deadline_info(
+            policy, &state, epoch,
+        )))
+    }
+}
 
-    let ts = data
-        .state_manager
-        .chain_store()
-        .load_required_tipset_or_heaviest(&key)?;
+/// looks up the miner power of the given address.
+pub enum StateMinerFaults {}
+impl RpcMethod<2> for StateMinerFaults {
+    const NAME: &'static str = STATE_MINER_FAULTS;
+    const PARAM_NAMES: [&'static str; 2] = ["miner", "tsk"];
+    const PERMISSION: Permission = Permission::Read;
+    type Params = (LotusJson<Address>, LotusJson<ApiTipsetKey>);
+    type Ok = LotusJson<BitField>;
+
+    async fn handle(
+        data: Ctx<impl Blockstore + Send + Sync + 'static>,
+        (LotusJson(address), LotusJson(ApiTipsetKey(key))): Self::Params,
+    ) -> Result<Self::Ok, JsonRpcError> {
+        let ts = data
+            .state_manager
+            .chain_store()
+            .load_required_tipset_or_heaviest(&key)?;
 
-    data.state_manager
-        .miner_faults(&address, &ts)
-        .map_err(|e| e.into())
-        .map(|r| r.into())
+        data.state_manager
+            .miner_faults(&address, &ts)
+            .map_err(|e| e.into())
+            .map(|r| r.into())
+    }
 }
 
-pub async fn state_miner_recoveries<DB: Blockstore + Send + Sync + 'static>(
-    params: Params<'_>,
-    data: Ctx<DB>,
-) -> Result<LotusJson<BitField>, JsonRpcError> {
-    let LotusJson((miner, ApiTipsetKey(tsk))): LotusJson<(Address, ApiTipsetKey)> =
-        params.parse()?;
-
-    let ts = data
-        .state_manager
-        .chain_store()
-        .load_required_tipset_or_heaviest(&tsk)?;
+/// returns the faults of a miner, by deadline.
+pub enum StateMinerFaultsByDeadline {}
+impl RpcMethod<2> for StateMinerFaultsByDeadline {
+    const NAME: &'static str = STATE_MINER_FAULTS_BY_DEADLINE;
+    const PARAM_NAMES: [&'static str; 2] = ["miner", "tsk"];
+    const PERMISSION: Permission = Permission::Read;
+    type Params = (LotusJson<Address>, LotusJson<ApiTipsetKey>);
+    type Ok = LotusJson<Vec<BitField>>;
+
+    async fn handle(
+        data: Ctx<impl Blockstore + Send + Sync + 'static>,
+        (LotusJson(address), LotusJson(ApiTipsetKey(key))): Self::Params,
+    ) -> Result<Self::Ok, JsonRpcError> {
+        let ts = data
+            .state_manager
+            .chain_store()
+            .load_required_tipset_or_heaviest(&key)?;
 
-    data.state_manager
-        .miner_recoveries(&miner, &ts)
-        .map_err(|e| e.into())
-        .map(|r| r.into())
+        Ok(LotusJson(
+            data.state_manager.miner_faults_by_deadline(&address, &ts)?,
+        ))
+    }
 }
 
-pub async fn state_miner_available_balance<DB: Blockstore + Send + Sync + 'static>(
-    params: Params<'_>,
-    data: Ctx<DB>,
-) -> Result<LotusJson<TokenAmount>, JsonRpcError> {
-    let LotusJson((miner_address, ApiTipsetKey(tsk))): LotusJson<(Address, ApiTipsetKey)> =
-        params.parse()?;
+pub enum StateMinerRecoveries {}
+impl RpcMethod<2> for StateMinerRecoveries {
+    const NAME: &'static str = STATE_MINER_RECOVERIES;
+    const PARAM_NAMES: [&'static str; 2] = ["miner", "tsk"];
+    const PERMISSION: Permission = Permission::Read;
+    type Params = (LotusJson<Address>, LotusJson<ApiTipsetKey>);
+    type Ok = LotusJson<BitField>;
+
+    async fn handle(
+        data: Ctx<impl Blockstore + Send + Sync + 'static>,
+        (LotusJson(miner), LotusJson(ApiTipsetKey(tsk))): Self::Params,
+    ) -> Result<Self::Ok, JsonRpcError> {
+        let ts = data
+            .state_manager
+            .chain_store()
+            .load_required_tipset_or_heaviest(&tsk)?;
 
-    let store = data.chain_store.blockstore();
-    let ts = data
-        .state_manager
-        .chain_store()
-        .load_required_tipset_or_heaviest(&tsk)?;
-    let actor = data
-        .state_manager
-        .get_actor(&miner_address, *ts.parent_state())?
-        .ok_or_else(|| anyhow::anyhow!("Miner actor not found"))?;
-    let state = miner::State::load(store, actor.code, actor.state)?;
-    let actor_balance: TokenAmount = actor.balance.clone().into();
-    let (vested, available): (TokenAmount, TokenAmount) = match &state {
-        miner::State::V13(s) => (
-            s.check_vested_funds(store, ts.epoch())?.into(),
-            s.get_available_balance(&actor_balance.into())?.into(),
-        ),
-        miner::State::V12(s) => (
-            s.check_vested_funds(store, ts.epoch())?.into(),
-            s.get_available_balance(&actor_balance.into())?.into(),
-        ),
-        miner::State::V11(s) => (
-            s.check_vested_funds(store, ts.epoch())?.into(),
-            s.get_available_balance(&actor_balance.into())?.into(),
-        ),
-        miner::State::V10(s) => (
-            s.check_vested_funds(store, ts.epoch())?.into(),
-            s.get_available_balance(&actor_balance.into())?.into(),
-        ),
-        miner::State::V9(s) => (
-            s.check_vested_funds(store, ts.epoch())?.into(),
-            s.get_available_balance(&actor_balance.into())?.into(),
-        ),
-        miner::State::V8(s) => (
-            s.check_vested_funds(store, ts.epoch())?.into(),
-            s.get_available_balance(&actor_balance.into())?.into(),
-        ),
-    };
+        data.state_manager
+            .miner_recoveries(&miner, &ts)
+            .map_err(|e| e.into())
+            .map(|r| r.into())
+    }
+}
 
-    Ok(LotusJson(vested + available))
+pub enum StateMinerAvailableBalance {}
+impl RpcMethod<2> for StateMinerAvailableBalance {
+    const NAME: &'static str = STATE_MINER_AVAILABLE_BALANCE;
+    const PARAM_NAMES: [&'static str; 2] = ["miner", "tsk"];
+    const PERMISSION: Permission = Permission::Read;
+    type Params = (LotusJson<Address>, LotusJson<ApiTipsetKey>);
+    type Ok = LotusJson<TokenAmount>;
+
+    async fn handle(
+        data: Ctx<impl Blockstore + Send + Sync + 'static>,
+        (LotusJson(miner_address), LotusJson(ApiTipsetKey(tsk))): Self::Params,
+    ) -> Result<Self::Ok, JsonRpcError> {
+        let store = data.chain_store.blockstore();
+        let ts = data
+            .state_manager
+            .chain_store()
+            .load_required_tipset_or_heaviest(&tsk)?;
+        let actor = data
+            .state_manager
+            .get_actor(&miner_address, *ts.parent_state())?
+            .ok_or_else(|| JsonRpcError::actor_not_found(&miner_address))?;
+        let state = miner::State::load(store, actor.code, actor.state)?;
+        let actor_balance: TokenAmount = actor.balance.clone().into();
+        let (vested, available): (TokenAmount, TokenAmount) = match &state {
+            miner::State::V13(s) => (
+                s.check_vested_funds(store, ts.epoch())?.into(),
+                s.get_available_balance(&actor_balance.into())?.into(),
+            ),
+            miner::State::V12(s) => (
+                s.check_vested_funds(store, ts.epoch())?.into(),
+                s.get_available_balance(&actor_balance.into())?.into(),
+            ),
+            miner::State::V11(s) => (
+                s.check_vested_funds(store, ts.epoch())?.into(),
+                s.get_available_balance(&actor_balance.into())?.into(),
+            ),
+            miner::State::V10(s) => (
+                s.check_vested_funds(store, ts.epoch())?.into(),
+                s.get_available_balance(&actor_balance.into())?.into(),
+            ),
+            miner::State::V9(s) => (
+                s.check_vested_funds(store, ts.epoch())?.into(),
+                s.get_available_balance(&actor_balance.into())?.into(),
+            ),
+            miner::State::V8(s) => (
+                s.check_vested_funds(store, ts.epoch())?.into(),
+                s.get_available_balance(&actor_balance.into())?.into(),
+            ),
+        };
+
+        Ok(LotusJson(vested + available))
+    }
 }
 
 /// returns the message receipt for the given message
@@ -509,9 +780,9 @@ pub async fn state_search_msg<DB: Blockstore + Send + Sync + 'static>(
 
     let state_manager = &data.state_manager;
     let (tipset, receipt) = state_manager
-        .search_for_message(None, cid, None)
+        .search_for_message(None, cid, None, true)
         .await?
-        .with_context(|| format!("message {cid} not found."))?;
+        .ok_or_else(|| JsonRpcError::message_not_found(&cid))?;
 
     let ipld = receipt.return_data().deserialize().unwrap_or(Ipld::Null);
 
@@ -526,6 +797,40 @@ pub async fn state_search_msg<DB: Blockstore + Send + Sync + 'static>(
 
 /// Looks back up to limit epochs in the chain for a message, and returns its receipt and the tipset where it was executed.
 /// See <https://github.com/filecoin-project/lotus/blob/master/documentation/en/api-v0-methods.md#StateSearchMsgLimited>
+/// `Filecoin.StateSearchMsg` as served by the v1 API, which searches from a
+/// given tipset, may limit how far back to look, and returns `null` if the
+/// message isn't found.
+pub async fn state_search_msg_v1<DB: Blockstore + Send + Sync + 'static>(
+    params: Params<'_>,
+    data: Ctx<DB>,
+) -> Result<Option<MessageLookup>, JsonRpcError> {
+    let LotusJson((ApiTipsetKey(tsk), cid, look_back_limit, allow_replaced)): LotusJson<(
+        ApiTipsetKey,
+        Cid,
+        ChainEpoch,
+        bool,
+    )> = params.parse()?;
+
+    let state_manager = &data.state_manager;
+    let from = data.chain_store.load_required_tipset_or_heaviest(&tsk)?;
+    // A negative limit (`LookbackNoLimit` in Lotus) searches the whole chain
+    let look_back_limit = (look_back_limit >= 0).then_some(look_back_limit);
+    let found = state_manager
+        .search_for_message(Some(from), cid, look_back_limit, allow_replaced)
+        .await?;
+
+    Ok(found.map(|(tipset, receipt)| {
+        let ipld = receipt.return_data().deserialize().unwrap_or(Ipld::Null);
+        MessageLookup {
+            receipt,
+            tipset: tipset.key().clone(),
+            height: tipset.epoch(),
+            message: cid,
+            return_dec: ipld,
+        }
+    }))
+}
+
 pub async fn state_search_msg_limited<DB: Blockstore + Send + Sync + 'static>(
     params: Params<'_>,
     data: Ctx<DB>,
@@ -534,11 +839,9 @@ pub async fn state_search_msg_limited<DB: Blockstore + Send + Sync + 'static>(
 
     let state_manager = &data.state_manager;
     let (tipset, receipt) = state_manager
-        .search_for_message(None, cid, Some(look_back_limit))
+        .search_for_message(None, cid, Some(look_back_limit), true)
         .await?
-        .with_context(|| {
-            format!("message {cid} not found within the last {look_back_limit} epochs")
-        })?;
+        .ok_or_else(|| JsonRpcError::message_not_found(&cid))?;
 
     let ipld = receipt.return_data().deserialize().unwrap_or(Ipld::Null);
 
@@ -727,57 +1030,67 @@ fn lock_pop<T>(mutex: &Mutex<Vec<T>>) -> Option<T> {
 }
 
 /// Get randomness from tickets
-pub async fn state_get_randomness_from_tickets<DB: Blockstore + Send + Sync + 'static>(
-    params: Params<'_>,
-    data: Ctx<DB>,
-) -> Result<LotusJson<Vec<u8>>, JsonRpcError> {
-    let LotusJson((personalization, rand_epoch, entropy, ApiTipsetKey(tsk))): LotusJson<
-        RandomnessParams,
-    > = params.parse()?;
-
-    let state_manager = &data.state_manager;
-    let tipset = state_manager
-        .chain_store()
-        .load_required_tipset_or_heaviest(&tsk)?;
-    let chain_config = state_manager.chain_config();
-    let chain_index = &data.chain_store.chain_index;
-    let beacon = state_manager.beacon_schedule();
-    let chain_rand = ChainRand::new(chain_config.clone(), tipset, chain_index.clone(), beacon);
-    let digest = chain_rand.get_chain_randomness(rand_epoch, false)?;
-    let value = crate::state_manager::chain_rand::draw_randomness_from_digest(
-        &digest,
-        personalization,
-        rand_epoch,
-        &entropy,
-    )?;
-    Ok(LotusJson(value.to_vec()))
+pub enum StateGetRandomnessFromTickets {}
+impl RpcMethod<4> for StateGetRandomnessFromTickets {
+    const NAME: &'static str = STATE_GET_RANDOMNESS_FROM_TICKETS;
+    const PARAM_NAMES: [&'static str; 4] = ["personalization", "rand_epoch", "entropy", "tsk"];
+    const PERMISSION: Permission = Permission::Read;
+    type Params = (i64, ChainEpoch, LotusJson<Vec<u8>>, LotusJson<ApiTipsetKey>);
+    type Ok = LotusJson<Vec<u8>>;
+
+    async fn handle(
+        data: Ctx<impl Blockstore + Send + Sync + 'static>,
+        (personalization, rand_epoch, LotusJson(entropy), LotusJson(ApiTipsetKey(tsk))): Self::Params,
+    ) -> Result<Self::Ok, JsonRpcError> {
+        let state_manager = &data.state_manager;
+        let tipset = state_manager
+            .chain_store()
+            .load_required_tipset_or_heaviest(&tsk)?;
+        let chain_config = state_manager.chain_config();
+        let chain_index = &data.chain_store.chain_index;
+        let beacon = state_manager.beacon_schedule();
+        let chain_rand = ChainRand::new(chain_config.clone(), tipset, chain_index.clone(), beacon);
+        let digest = chain_rand.get_chain_randomness(rand_epoch, false)?;
+        let value = crate::state_manager::chain_rand::draw_randomness_from_digest(
+            &digest,
+            personalization,
+            rand_epoch,
+            &entropy,
+        )?;
+        Ok(LotusJson(value.to_vec()))
+    }
 }
 
 /// Get randomness from beacon
-pub async fn state_get_randomness_from_beacon<DB: Blockstore + Send + Sync + 'static>(
-    params: Params<'_>,
-    data: Ctx<DB>,
-) -> Result<LotusJson<Vec<u8>>, JsonRpcError> {
-    let LotusJson((personalization, rand_epoch, entropy, ApiTipsetKey(tsk))): LotusJson<
-        RandomnessParams,
-    > = params.parse()?;
-
-    let state_manager = &data.state_manager;
-    let tipset = state_manager
-        .chain_store()
-        .load_required_tipset_or_heaviest(&tsk)?;
-    let chain_config = state_manager.chain_config();
-    let chain_index = &data.chain_store.chain_index;
-    let beacon = state_manager.beacon_schedule();
-    let chain_rand = ChainRand::new(chain_config.clone(), tipset, chain_index.clone(), beacon);
-    let digest = chain_rand.get_beacon_randomness_v3(rand_epoch)?;
-    let value = crate::state_manager::chain_rand::draw_randomness_from_digest(
-        &digest,
-        personalization,
-        rand_epoch,
-        &entropy,
-    )?;
-    Ok(LotusJson(value.to_vec()))
+pub enum StateGetRandomnessFromBeacon {}
+impl RpcMethod<4> for StateGetRandomnessFromBeacon {
+    const NAME: &'static str = STATE_GET_RANDOMNESS_FROM_BEACON;
+    const PARAM_NAMES: [&'static str; 4] = ["personalization", "rand_epoch", "entropy", "tsk"];
+    const PERMISSION: Permission = Permission::Read;
+    type Params = (i64, ChainEpoch, LotusJson<Vec<u8>>, LotusJson<ApiTipsetKey>);
+    type Ok = LotusJson<Vec<u8>>;
+
+    async fn handle(
+        data: Ctx<impl Blockstore + Send + Sync + 'static>,
+        (personalization, rand_epoch, LotusJson(entropy), LotusJson(ApiTipsetKey(tsk))): Self::Params,
+    ) -> Result<Self::Ok, JsonRpcError> {
+        let state_manager = &data.state_manager;
+        let tipset = state_manager
+            .chain_store()
+            .load_required_tipset_or_heaviest(&tsk)?;
+        let chain_config = state_manager.chain_config();
+        let chain_index = &data.chain_store.chain_index;
+        let beacon = state_manager.beacon_schedule();
+        let chain_rand = ChainRand::new(chain_config.clone(), tipset, chain_index.clone(), beacon);
+        let digest = chain_rand.get_beacon_randomness_v3(rand_epoch)?;
+        let value = crate::state_manager::chain_rand::draw_randomness_from_digest(
+            &digest,
+            personalization,
+            rand_epoch,
+            &entropy,
+        )?;
+        Ok(LotusJson(value.to_vec()))
+    }
 }
 
 /// Get read state
@@ -791,7 +1104,7 @@ pub async fn state_read_state<DB: Blockstore + Send + Sync + 'static>(
     let actor = data
         .state_manager
         .get_actor(&addr, *ts.parent_state())?
-        .context("Actor address could not be resolved")?;
+        .ok_or_else(|| JsonRpcError::actor_not_found(&addr))?;
     let blk = data
         .state_manager
         .blockstore()
@@ -840,7 +1153,7 @@ pub async fn msig_get_available_balance<DB: Blockstore + Send + Sync + 'static>(
     let actor = data
         .state_manager
         .get_actor(&addr, *ts.parent_state())?
-        .context("MultiSig actor not found")?;
+        .ok_or_else(|| JsonRpcError::actor_not_found(&addr))?;
     let actor_balance = TokenAmount::from(&actor.balance);
     let ms = multisig::State::load(&store, actor.code, actor.state)?;
     let locked_balance = ms.locked_balance(height)?.into();
@@ -859,7 +1172,7 @@ pub async fn msig_get_pending<DB: Blockstore + Send + Sync + 'static>(
     let actor = data
         .state_manager
         .get_actor(&addr, *ts.parent_state())?
-        .context("MultiSig actor not found")?;
+        .ok_or_else(|| JsonRpcError::actor_not_found(&addr))?;
     let ms = multisig::State::load(&store, actor.code, actor.state)?;
     let txns = ms
         .get_pending_txn(store)?
@@ -1041,3 +1354,294 @@ pub async fn state_market_storage_deal<DB: Blockstore + Send + Sync + 'static>(
 
     Ok(MarketDeal { proposal, state }.into())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::{chain4u, HeaderBuilder, Tipset};
+    use crate::rpc::RPCState;
+    use crate::shim::message::Message;
+    use crate::shim::state_tree::{StateTree, StateTreeVersion};
+    use crate::test_utils::CountingStore;
+    use crate::utils::cid::CidCborExt as _;
+    use crate::utils::db::CborStoreExt as _;
+    use fil_actor_market_state::v13::{
+        DealProposal, Label, State as MarketState, PROPOSALS_AMT_BITWIDTH,
+    };
+    use itertools::Itertools as _;
+    use num_traits::Zero;
+
+    /// Returns a market actor with `deal_count` deals, and the store of its
+    /// state.
+    fn market_actor_with_deals(deal_count: u64) -> (CountingStore, ActorState) {
+        let store = CountingStore::default();
+        let mut market_state = MarketState::new(&store).unwrap();
+        let proposals = (0..deal_count)
+            .map(|deal_id| {
+                let proposal = DealProposal {
+                    piece_cid: Cid::from_cbor_blake2b256(&deal_id).unwrap(),
+                    piece_size: fvm_shared4::piece::PaddedPieceSize(2048),
+                    verified_deal: false,
+                    client: Address::new_id(1000).into(),
+                    provider: Address::new_id(2000).into(),
+                    label: Label::String(format!("deal {deal_id}")),
+                    start_epoch: 100,
+                    end_epoch: 200,
+                    storage_price_per_epoch: Zero::zero(),
+                    provider_collateral: Zero::zero(),
+                    client_collateral: Zero::zero(),
+                };
+                (deal_id, proposal)
+            })
+            .collect_vec();
+        market_state.put_deal_proposals(&store, &proposals).unwrap();
+        let market_actor = ActorState::new(
+            fil_actor_interface::KNOWN_CIDS.actor.market.v13.calibnet,
+            store.put_cbor_default(&market_state).unwrap(),
+            TokenAmount::default(),
+            0,
+            None,
+        );
+        (store, market_actor)
+    }
+
+    /// Creates a state tree holding a miner with the given ID, whose
+    /// deadlines have a single partition with the given faults.
+    fn miner_state_root_with_faults(
+        db: &Arc<impl Blockstore>,
+        miner_id: u64,
+        faults: &[(u64, &[u64])],
+    ) -> Cid {
+        use fil_actor_miner_state::v13::{
+            Deadline, MinerInfo as MinerInfoV13, Partition, State as MinerState,
+        };
+        use fil_actors_shared::v13::runtime::Policy;
+        use fvm_shared4::sector::RegisteredPoStProof;
+
+        let policy = Policy::calibnet();
+        let info = MinerInfoV13::new(
+            miner_id + 1,
+            miner_id + 1,
+            vec![],
+            vec![],
+            vec![],
+            RegisteredPoStProof::StackedDRGWindow2KiBV1P1,
+        )
+        .unwrap();
+        let mut miner_state =
+            MinerState::new(&policy, db, db.put_cbor_default(&info).unwrap(), 0, 0).unwrap();
+        let mut deadlines = miner_state.load_deadlines(db).unwrap();
+        for &(deadline_idx, sectors) in faults {
+            let sectors = BitField::try_from_bits(sectors.iter().copied()).unwrap();
+            let mut partition = Partition::new(db).unwrap();
+            partition.sectors = sectors.clone();
+            partition.faults = sectors;
+            let mut deadline = Deadline::new(db).unwrap();
+            let mut partitions = deadline.partitions_amt(db).unwrap();
+            partitions.set(0, partition).unwrap();
+            deadline.partitions = partitions.flush().unwrap();
+            deadlines
+                .update_deadline(&policy, db, deadline_idx, &deadline)
+                .unwrap();
+        }
+        miner_state.save_deadlines(db, deadlines).unwrap();
+
+        let mut state_tree = StateTree::new(db.clone(), StateTreeVersion::V5).unwrap();
+        state_tree
+            .set_actor(
+                &Address::new_id(miner_id),
+                ActorState::new(
+                    fil_actor_interface::KNOWN_CIDS.actor.miner.v13.calibnet,
+                    db.put_cbor_default(&miner_state).unwrap(),
+                    TokenAmount::default(),
+                    0,
+                    None,
+                ),
+            )
+            .unwrap();
+        state_tree.flush().unwrap()
+    }
+
+    fn deal_ids(deal_count: u64, filter: &MarketDealsFilter) -> Vec<DealID> {
+        let (store, market_actor) = market_actor_with_deals(deal_count);
+        market_deals(&store, &market_actor, filter, MARKET_DEALS_MAX_PAGE)
+            .unwrap()
+            .into_keys()
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn state_call_halts_once_cancelled() {
+        let data = RPCState::calibnet();
+        let message = Message {
+            from: Address::SYSTEM_ACTOR,
+            to: Address::SYSTEM_ACTOR,
+            ..Default::default()
+        };
+        let err = data
+            .state_manager
+            .call(&message, None, &AtomicBool::new(true))
+            .unwrap_err();
+        assert!(err.to_string().contains("call cancelled"), "{err}");
+    }
+
+    #[test]
+    fn market_deals_are_paged_in_deal_id_order() {
+        let cases = [
+            (MarketDealsFilter::default(), 0..100),
+            (
+                MarketDealsFilter {
+                    offset: 10,
+                    limit: Some(5),
+                    deal_id_range: None,
+                },
+                10..15,
+            ),
+            (
+                MarketDealsFilter {
+                    offset: 2,
+                    limit: Some(100),
+                    deal_id_range: Some((50, 60)),
+                },
+                52..60,
+            ),
+            (
+                MarketDealsFilter {
+                    deal_id_range: Some((95, 1000)),
+                    ..Default::default()
+                },
+                95..100,
+            ),
+            (
+                MarketDealsFilter {
+                    offset: 100,
+                    ..Default::default()
+                },
+                0..0,
+            ),
+        ];
+        for (filter, expected) in cases {
+            assert_eq!(deal_ids(100, &filter), expected.collect_vec(), "{filter:?}");
+        }
+    }
+
+    #[test]
+    fn market_deals_have_empty_states_until_activated() {
+        let (store, market_actor) = market_actor_with_deals(1);
+        let deals = market_deals(
+            &store,
+            &market_actor,
+            &MarketDealsFilter::default(),
+            MARKET_DEALS_MAX_PAGE,
+        )
+        .unwrap();
+        let json = serde_json::to_value(deals).unwrap();
+        assert_eq!(json["0"]["State"]["SectorStartEpoch"], -1);
+        assert_eq!(json["0"]["Proposal"]["Label"], "deal 0");
+    }
+
+    #[test]
+    fn market_deals_pages_only_read_their_deals() {
+        let deal_count = 10_000;
+        let (store, market_actor) = market_actor_with_deals(deal_count);
+        let leaves = deal_count as usize / (1 << PROPOSALS_AMT_BITWIDTH);
+        let read_page = |filter: MarketDealsFilter| {
+            store.take_reads();
+            let deals = market_deals(&store, &market_actor, &filter, deal_count).unwrap();
+            (deals.len(), store.take_reads())
+        };
+
+        let (len, full_reads) = read_page(MarketDealsFilter::default());
+        assert_eq!(len, deal_count as usize);
+        assert!(
+            full_reads > leaves,
+            "{full_reads} reads for {leaves} leaves"
+        );
+        for filter in [
+            MarketDealsFilter {
+                limit: Some(10),
+                ..Default::default()
+            },
+            MarketDealsFilter {
+                deal_id_range: Some((9_000, 9_010)),
+                ..Default::default()
+            },
+        ] {
+            let (len, reads) = read_page(filter.clone());
+            assert_eq!(len, 10);
+            assert!(reads < 20, "{reads} reads for {filter:?}");
+        }
+    }
+
+    #[test]
+    fn market_deals_pages_are_bounded() {
+        let (store, market_actor) = market_actor_with_deals(100);
+        let page = |filter: &MarketDealsFilter| market_deals(&store, &market_actor, filter, 50);
+
+        let err = page(&MarketDealsFilter::default()).unwrap_err();
+        assert!(err.to_string().contains("more than 50 deals"), "{err}");
+        let err = page(&MarketDealsFilter {
+            limit: Some(51),
+            ..Default::default()
+        })
+        .unwrap_err();
+        assert!(err.to_string().contains("above the maximum of 50"), "{err}");
+
+        let remaining = MarketDealsFilter {
+            offset: 50,
+            ..Default::default()
+        };
+        assert_eq!(page(&remaining).unwrap().len(), 50);
+        let limited = MarketDealsFilter {
+            limit: Some(50),
+            ..Default::default()
+        };
+        assert_eq!(page(&limited).unwrap().len(), 50);
+    }
+
+    #[tokio::test]
+    async fn miner_faults_are_split_by_deadline() {
+        let data = Arc::new(RPCState::calibnet());
+        let db = data.state_manager.blockstore_owned();
+        let state_root = miner_state_root_with_faults(&db, 1000, &[(0, &[1, 2]), (3, &[5])]);
+        let tipset = {
+            chain4u! {
+                in data.chain_store.blockstore();
+                [_genesis = data.chain_store.genesis_block_header()]
+                -> [block = HeaderBuilder {
+                    state_root: state_root.into(),
+                    ..Default::default()
+                }]
+            };
+            Tipset::from(block.clone())
+        };
+
+        let LotusJson(faults) = StateMinerFaultsByDeadline::handle(
+            Arc::new(data),
+            (
+                LotusJson(Address::new_id(1000)),
+                LotusJson(ApiTipsetKey(Some(tipset.key().clone()))),
+            ),
+        )
+        .await
+        .unwrap();
+        assert_eq!(faults.len(), 48);
+        for (deadline_idx, deadline_faults) in faults.iter().enumerate() {
+            let expected: Vec<u64> = match deadline_idx {
+                0 => vec![1, 2],
+                3 => vec![5],
+                _ => vec![],
+            };
+            assert_eq!(
+                deadline_faults.iter().collect_vec(),
+                expected,
+                "deadline {deadline_idx}"
+            );
+        }
+
+        let json = serde_json::to_value(LotusJson(faults)).unwrap();
+        assert_eq!(json[0], serde_json::json!([1, 2]));
+        assert_eq!(json[1], serde_json::json!([0]));
+        assert_eq!(json[3], serde_json::json!([5, 1]));
+    }
+}
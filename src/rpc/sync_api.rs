@@ -2,17 +2,28 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 #![allow(clippy::unused_async)]
 
-use crate::chain_sync::SyncState;
+use std::sync::Arc;
+
+use crate::blocks::{Block, CachingBlockHeader, GossipBlock, Tipset, TipsetKey};
+use crate::chain::ChainStore;
+use crate::chain_sync::{Checkpoint, SyncState, TipsetValidator};
+use crate::libp2p::{NetworkMessage, Topic, PUBSUB_BLOCK_STR};
 use crate::lotus_json::LotusJson;
+use crate::message::SignedMessage;
+use crate::rpc::channel::broadcast_stream;
 use crate::rpc::error::JsonRpcError;
-use crate::rpc::Ctx;
+use crate::rpc::{Ctx, RPCState};
 use crate::rpc_api::data_types::RPCSyncState;
+use crate::shim::message::Message;
 
-use anyhow::Result;
+use anyhow::{Context as _, Result};
+use futures::stream::{BoxStream, StreamExt as _};
 use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::{to_vec, CborStore as _};
 use jsonrpsee::types::Params;
 use nonempty::nonempty;
 use parking_lot::RwLock;
+use tracing::debug;
 
 /// Checks if a given block is marked as bad.
 pub async fn sync_check_bad<DB: Blockstore>(
@@ -36,6 +47,148 @@ pub async fn sync_mark_bad<DB: Blockstore>(
     Ok(())
 }
 
+/// Removes a block from the bad blocks, allowing it to be synced again.
+pub async fn sync_unmark_bad<DB: Blockstore>(
+    params: Params<'_>,
+    data: Ctx<DB>,
+) -> Result<(), JsonRpcError> {
+    let LotusJson((cid,)) = params.parse()?;
+
+    data.bad_blocks.remove(&cid);
+    Ok(())
+}
+
+/// Clears all the bad blocks.
+pub async fn sync_unmark_all_bad<DB: Blockstore>(data: Ctx<DB>) -> Result<(), JsonRpcError> {
+    data.bad_blocks.clear();
+    Ok(())
+}
+
+/// Marks a tipset as trusted, so that chain sync skips the validation of the
+/// tipset and its ancestors.
+pub async fn sync_checkpoint<DB: Blockstore>(
+    params: Params<'_>,
+    data: Ctx<DB>,
+) -> Result<(), JsonRpcError> {
+    let LotusJson((tsk,)): LotusJson<(TipsetKey,)> = params.parse()?;
+
+    let tipset = data.chain_store.chain_index.load_required_tipset(&tsk)?;
+    Checkpoint::from(tipset.as_ref()).save(data.chain_store.settings().as_ref())?;
+    Ok(())
+}
+
+/// Returns the key of the checkpointed tipset, if any.
+pub async fn sync_checkpoint_get<DB: Blockstore>(
+    data: Ctx<DB>,
+) -> Result<LotusJson<Option<TipsetKey>>, JsonRpcError> {
+    let checkpoint = Checkpoint::load(data.chain_store.settings().as_ref())?;
+    Ok(LotusJson(
+        checkpoint.map(|checkpoint| checkpoint.key().clone()),
+    ))
+}
+
+/// Validates a block submitted by a miner, then publishes it over GossipSub
+/// and hands it to the chain follower.
+pub async fn sync_submit_block<DB: Blockstore + Send + Sync + 'static>(
+    params: Params<'_>,
+    data: Ctx<DB>,
+) -> Result<(), JsonRpcError> {
+    let LotusJson((block,)): LotusJson<(GossipBlock,)> = params.parse()?;
+
+    let block_cid = *block.header.cid();
+    if let Some(reason) = data.bad_blocks.peek(&block_cid) {
+        return Err(anyhow::anyhow!("block {block_cid} is marked as bad: {reason}").into());
+    }
+    let full_block = validate_gossip_block(&data, &block)?;
+    full_block.persist(data.chain_store.blockstore())?;
+
+    data.network_send
+        .send_async(NetworkMessage::PubsubMessage {
+            topic: Topic::new(format!("{PUBSUB_BLOCK_STR}/{}", data.network_name)),
+            message: to_vec(&block)?,
+        })
+        .await?;
+
+    // The chain follower only consumes tipsets while it's following the
+    // network, in which case it'll pick the block up from its peers anyway.
+    if let Err(e) = data
+        .tipset_send
+        .try_send(Arc::new(Tipset::from(block.header)))
+    {
+        debug!("Informing chain sync of submitted block {block_cid} failed: {e}");
+    }
+    Ok(())
+}
+
+/// Checks that the parents of a block are known, that it is signed by the
+/// worker of its miner and that all of its messages are available locally.
+fn validate_gossip_block<DB: Blockstore + Send + Sync + 'static>(
+    data: &RPCState<DB>,
+    block: &GossipBlock,
+) -> anyhow::Result<Block> {
+    let header = &block.header;
+    let chain_store = &data.chain_store;
+
+    let parent = chain_store
+        .chain_index
+        .load_required_tipset(&header.parents)
+        .context("unknown parent tipset")?;
+    let (_, lookback_state) = ChainStore::get_lookback_tipset_for_round(
+        chain_store.chain_index.clone(),
+        data.state_manager.chain_config().clone(),
+        parent,
+        header.epoch,
+    )?;
+    let work_addr = data
+        .state_manager
+        .get_miner_work_addr(lookback_state, &header.miner_address)
+        .with_context(|| format!("failed to look up the worker of {}", header.miner_address))?;
+    header
+        .verify_signature_against(&work_addr)
+        .context("invalid block signature")?;
+
+    let db = chain_store.blockstore();
+    let bls_messages = block
+        .bls_messages
+        .iter()
+        .map(|cid| {
+            db.get_cbor::<Message>(cid)?
+                .with_context(|| format!("BLS message {cid} not found"))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let secp_messages = block
+        .secpk_messages
+        .iter()
+        .map(|cid| {
+            db.get_cbor::<SignedMessage>(cid)?
+                .with_context(|| format!("SECP message {cid} not found"))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let msg_root = TipsetValidator::compute_msg_root(db, &bls_messages, &secp_messages)
+        .map_err(|e| anyhow::anyhow!("{e}"))?;
+    anyhow::ensure!(
+        header.messages == msg_root,
+        "message root {} doesn't match the messages of the block ({msg_root})",
+        header.messages
+    );
+
+    Ok(Block {
+        header: header.clone(),
+        bls_messages,
+        secp_messages,
+    })
+}
+
+/// Streams the header of every block received over GossipSub.
+pub(crate) fn sync_incoming_blocks<DB>(
+    _params: Params<'_>,
+    data: &RPCState<DB>,
+) -> BoxStream<'static, LotusJson<CachingBlockHeader>> {
+    broadcast_stream(data.incoming_blocks.subscribe())
+        .map(LotusJson)
+        .boxed()
+}
+
 async fn clone_state(state: &RwLock<SyncState>) -> SyncState {
     state.read().clone()
 }
@@ -51,19 +204,24 @@ mod tests {
     use std::sync::Arc;
 
     use crate::beacon::{mock_beacon::MockBeacon, BeaconPoint, BeaconSchedule};
-    use crate::blocks::RawBlockHeader;
-    use crate::blocks::{CachingBlockHeader, Tipset};
+    use crate::blocks::{chain4u, Chain4U, HeaderBuilder, RawBlockHeader};
+    use crate::blocks::{CachingBlockHeader, Tipset, TipsetKey};
     use crate::chain::ChainStore;
     use crate::chain_sync::{SyncConfig, SyncStage};
     use crate::db::MemoryDB;
-    use crate::key_management::{KeyStore, KeyStoreConfig};
+    use crate::key_management::{generate_key, Key, KeyStore, KeyStoreConfig};
     use crate::libp2p::NetworkMessage;
     use crate::message_pool::{MessagePool, MpoolRpcProvider};
     use crate::networks::ChainConfig;
     use crate::rpc::RPCState;
     use crate::shim::address::Address;
+    use crate::shim::crypto::SignatureType;
+    use crate::shim::econ::TokenAmount;
+    use crate::shim::state_tree::{ActorState, StateTree, StateTreeVersion};
     use crate::state_manager::StateManager;
+    use crate::utils::db::CborStoreExt as _;
     use crate::utils::encoding::from_slice_with_fallback;
+    use cid::Cid;
     use jsonrpsee::types::params::Params;
     use tokio::{sync::RwLock, task::JoinSet};
 
@@ -90,7 +248,14 @@ mod tests {
         });
 
         let cs_arc = Arc::new(
-            ChainStore::new(db.clone(), db, chain_config.clone(), genesis_header).unwrap(),
+            ChainStore::new(
+                db.clone(),
+                db.clone(),
+                db,
+                chain_config.clone(),
+                genesis_header,
+            )
+            .unwrap(),
         );
 
         let state_manager =
@@ -131,14 +296,20 @@ mod tests {
         let state = Arc::new(RPCState {
             state_manager,
             keystore: Arc::new(RwLock::new(KeyStore::new(KeyStoreConfig::Memory).unwrap())),
+            tokens: Default::default(),
             mpool: Arc::new(pool),
             bad_blocks: Default::default(),
             sync_state: Arc::new(parking_lot::RwLock::new(Default::default())),
             network_send,
+            tipset_send: flume::bounded(5).0,
+            incoming_blocks: tokio::sync::broadcast::channel(1).0,
             network_name: TEST_NET_NAME.to_owned(),
             start_time,
             chain_store: cs_for_chain.clone(),
             beacon,
+            bandwidth: Default::default(),
+            net_limits: Default::default(),
+            gc: None,
         });
         (state, network_rx)
     }
@@ -164,6 +335,29 @@ mod tests {
             Ok(reason) => assert_eq!(reason, "Marked bad manually through RPC API"),
             Err(e) => std::panic::panic_any(e),
         }
+
+        // Unmark the block and check that it is no longer bad
+        assert!(
+            sync_unmark_bad(Params::new(Some(cid)), Arc::new(state.clone()))
+                .await
+                .is_ok()
+        );
+        match sync_check_bad(Params::new(Some(cid)), Arc::new(state.clone())).await {
+            Ok(reason) => assert_eq!(reason, ""),
+            Err(e) => std::panic::panic_any(e),
+        }
+
+        // Mark it again, then clear all the bad blocks
+        assert!(
+            sync_mark_bad(Params::new(Some(cid)), Arc::new(state.clone()))
+                .await
+                .is_ok()
+        );
+        assert!(sync_unmark_all_bad(Arc::new(state.clone())).await.is_ok());
+        match sync_check_bad(Params::new(Some(cid)), Arc::new(state.clone())).await {
+            Ok(reason) => assert_eq!(reason, ""),
+            Err(e) => std::panic::panic_any(e),
+        }
     }
 
     #[tokio::test]
@@ -193,5 +387,274 @@ mod tests {
             }
             Err(e) => std::panic::panic_any(e),
         }
+
+        // fail the validation of the next tipset
+        let key = TipsetKey::from(nonempty![Cid::default()]);
+        st_copy.write().set_last_validated(4, key.clone());
+        st_copy.write().error("invalid block".into());
+
+        match sync_state(Arc::new(state.clone())).await {
+            Ok(ret) => {
+                let sync = ret.active_syncs.first();
+                assert_eq!(sync.stage(), SyncStage::Error);
+                assert_eq!(sync.message(), Some("invalid block"));
+                assert_eq!(sync.last_validated(), Some(&(4, key)));
+            }
+            Err(e) => std::panic::panic_any(e),
+        }
+    }
+
+    #[tokio::test]
+    async fn incoming_blocks_are_forwarded() {
+        let (state, _) = state_setup();
+
+        let mut stream = sync_incoming_blocks(Params::new(None), &state);
+        let header = CachingBlockHeader::default();
+        state.incoming_blocks.send(header.clone()).unwrap();
+
+        let LotusJson(received) = stream.next().await.unwrap();
+        assert_eq!(received, header);
+    }
+
+    #[tokio::test]
+    async fn checkpoint_persists() {
+        let data = Arc::new(RPCState::calibnet());
+        let genesis = Tipset::from(data.chain_store.genesis_block_header());
+
+        let LotusJson(checkpoint) = sync_checkpoint_get(Arc::new(data.clone())).await.unwrap();
+        assert_eq!(checkpoint, None);
+
+        let params = serde_json::to_string(&[LotusJson(genesis.key().clone())]).unwrap();
+        sync_checkpoint(Params::new(Some(&params)), Arc::new(data.clone()))
+            .await
+            .unwrap();
+        let LotusJson(checkpoint) = sync_checkpoint_get(Arc::new(data.clone())).await.unwrap();
+        assert_eq!(checkpoint.as_ref(), Some(genesis.key()));
+        let stored = Checkpoint::load(data.chain_store.settings().as_ref()).unwrap();
+        assert_eq!(stored, Some(Checkpoint::from(&genesis)));
+
+        // Only tipsets in the store can be checkpointed.
+        let unknown = TipsetKey::from(nonempty![Cid::default()]);
+        let params = serde_json::to_string(&[LotusJson(unknown)]).unwrap();
+        assert!(
+            sync_checkpoint(Params::new(Some(&params)), Arc::new(data.clone()))
+                .await
+                .is_err()
+        );
+        let LotusJson(checkpoint) = sync_checkpoint_get(Arc::new(data)).await.unwrap();
+        assert_eq!(checkpoint.as_ref(), Some(genesis.key()));
+    }
+
+    async fn submit_block(
+        data: Arc<RPCState<impl Blockstore + Send + Sync + 'static>>,
+        header: &RawBlockHeader,
+    ) -> Result<(), JsonRpcError> {
+        let block = GossipBlock {
+            header: CachingBlockHeader::new(header.clone()),
+            bls_messages: vec![],
+            secpk_messages: vec![],
+        };
+        let params = serde_json::to_string(&[LotusJson(block)]).unwrap();
+        sync_submit_block(Params::new(Some(&params)), Arc::new(data)).await
+    }
+
+    #[tokio::test]
+    async fn submit_invalid_block() {
+        let (network_send, network_rx) = flume::unbounded();
+        let data = Arc::new(RPCState {
+            network_send,
+            ..RPCState::calibnet()
+        });
+
+        // The calibnet genesis has no miner actor that could have signed it.
+        let unsigned = {
+            chain4u! {
+                in data.chain_store.blockstore();
+                [_genesis = data.chain_store.genesis_block_header()]
+                -> [unsigned = HeaderBuilder {
+                    miner_address: Address::new_id(1000).into(),
+                    ..Default::default()
+                }]
+            };
+            unsigned.clone()
+        };
+        let err = submit_block(data.clone(), &unsigned).await.unwrap_err();
+        assert!(err.message().contains("worker of f01000"), "{err}");
+
+        let cid = *CachingBlockHeader::new(unsigned.clone()).cid();
+        data.bad_blocks.put(cid, "bad".into());
+        let err = submit_block(data.clone(), &unsigned).await.unwrap_err();
+        assert!(err.message().contains("marked as bad"), "{err}");
+
+        // The parents of this block only exist in another chain.
+        let orphan = {
+            let c4u = Chain4U::new();
+            chain4u! {
+                in c4u;
+                [_genesis] -> [_parent] -> [orphan]
+            };
+            orphan.clone()
+        };
+        let err = submit_block(data, &orphan).await.unwrap_err();
+        assert!(err.message().contains("unknown parent tipset"), "{err}");
+
+        // Rejected blocks must not be gossiped.
+        assert!(network_rx.try_recv().is_err());
+    }
+
+    /// Creates a state tree holding a miner with the given ID, whose worker is
+    /// an account actor of `worker_key`.
+    fn miner_state_root(db: &Arc<impl Blockstore>, miner_id: u64, worker_key: &Key) -> Cid {
+        use fil_actor_interface::KNOWN_CIDS;
+        use fil_actor_miner_state::v13::{MinerInfo, State as MinerState};
+        use fil_actors_shared::v13::runtime::Policy;
+        use fvm_shared4::sector::RegisteredPoStProof;
+
+        let worker_id = miner_id + 1;
+        let mut state_tree = StateTree::new(db.clone(), StateTreeVersion::V5).unwrap();
+        let account_state = fil_actor_account_state::v13::State {
+            address: worker_key.address.into(),
+        };
+        state_tree
+            .set_actor(
+                &Address::new_id(worker_id),
+                ActorState::new(
+                    KNOWN_CIDS.actor.account.v13.calibnet,
+                    db.put_cbor_default(&account_state).unwrap(),
+                    TokenAmount::default(),
+                    0,
+                    None,
+                ),
+            )
+            .unwrap();
+
+        let info = MinerInfo::new(
+            worker_id,
+            worker_id,
+            vec![],
+            vec![],
+            vec![],
+            RegisteredPoStProof::StackedDRGWindow2KiBV1P1,
+        )
+        .unwrap();
+        let miner_state = MinerState::new(
+            &Policy::calibnet(),
+            db,
+            db.put_cbor_default(&info).unwrap(),
+            0,
+            0,
+        )
+        .unwrap();
+        state_tree
+            .set_actor(
+                &Address::new_id(miner_id),
+                ActorState::new(
+                    KNOWN_CIDS.actor.miner.v13.calibnet,
+                    db.put_cbor_default(&miner_state).unwrap(),
+                    TokenAmount::default(),
+                    0,
+                    None,
+                ),
+            )
+            .unwrap();
+        state_tree.flush().unwrap()
+    }
+
+    /// Builds a block of `f01000` on top of a tipset whose state knows about
+    /// the worker `worker_key`.
+    fn unsigned_block(
+        data: &RPCState<Chain4U<impl Blockstore + Send + Sync + 'static>>,
+        worker_key: &Key,
+    ) -> RawBlockHeader {
+        let db = data.state_manager.blockstore_owned();
+        let state_root = miner_state_root(&db, 1000, worker_key);
+        let messages = TipsetValidator::compute_msg_root(&db, &[], &[]).unwrap();
+        chain4u! {
+            in data.chain_store.blockstore();
+            [_genesis = data.chain_store.genesis_block_header()]
+            -> [_parent = HeaderBuilder {
+                state_root: state_root.into(),
+                ..Default::default()
+            }]
+            -> [block = HeaderBuilder {
+                miner_address: Address::new_id(1000).into(),
+                messages: messages.into(),
+                ..Default::default()
+            }]
+        };
+        block.clone()
+    }
+
+    fn sign_block(mut block: RawBlockHeader, key: &Key) -> RawBlockHeader {
+        block.signature = None;
+        let signing_bytes = to_vec(&block).unwrap();
+        block.signature = Some(
+            crate::key_management::sign(
+                *key.key_info.key_type(),
+                key.key_info.private_key(),
+                &signing_bytes,
+            )
+            .unwrap(),
+        );
+        block
+    }
+
+    #[tokio::test]
+    async fn submit_valid_block() {
+        let (network_send, network_rx) = flume::unbounded();
+        let (tipset_send, tipset_rx) = flume::unbounded();
+        let data = Arc::new(RPCState {
+            network_send,
+            tipset_send,
+            ..RPCState::calibnet()
+        });
+
+        let worker_key = generate_key(SignatureType::Bls).unwrap();
+        let block = sign_block(unsigned_block(&data, &worker_key), &worker_key);
+        submit_block(data.clone(), &block).await.unwrap();
+
+        let block = CachingBlockHeader::new(block);
+        match network_rx.try_recv().unwrap() {
+            NetworkMessage::PubsubMessage { topic, message } => {
+                assert_eq!(
+                    topic.to_string(),
+                    format!("{PUBSUB_BLOCK_STR}/{}", data.network_name)
+                );
+                let gossiped = from_slice_with_fallback::<GossipBlock>(&message).unwrap();
+                assert_eq!(gossiped.header, block);
+            }
+            other => panic!("unexpected network message: {other:?}"),
+        }
+        let tipset = tipset_rx.try_recv().unwrap();
+        assert_eq!(tipset.key(), Tipset::from(block.clone()).key());
+        assert!(data.chain_store.blockstore().has(block.cid()).unwrap());
+    }
+
+    #[tokio::test]
+    async fn submit_block_with_invalid_signature() {
+        let (network_send, network_rx) = flume::unbounded();
+        let (tipset_send, tipset_rx) = flume::unbounded();
+        let data = Arc::new(RPCState {
+            network_send,
+            tipset_send,
+            ..RPCState::calibnet()
+        });
+
+        // Signed by a key that isn't the worker of the miner.
+        let worker_key = generate_key(SignatureType::Bls).unwrap();
+        let other_key = generate_key(SignatureType::Bls).unwrap();
+        let unsigned = unsigned_block(&data, &worker_key);
+        let block = sign_block(unsigned.clone(), &other_key);
+        let err = submit_block(data.clone(), &block).await.unwrap_err();
+        assert!(err.message().contains("invalid block signature"), "{err}");
+
+        // Signed by the worker, but tampered with afterwards.
+        let mut block = sign_block(unsigned, &worker_key);
+        block.timestamp += 1;
+        let err = submit_block(data.clone(), &block).await.unwrap_err();
+        assert!(err.message().contains("invalid block signature"), "{err}");
+
+        assert!(network_rx.try_recv().is_err());
+        assert!(tipset_rx.try_recv().is_err());
     }
 }
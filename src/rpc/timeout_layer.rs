@@ -0,0 +1,142 @@
+// Copyright 2019-2024 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use crate::rpc::RpcConfig;
+
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use jsonrpsee::server::middleware::rpc::RpcServiceT;
+use jsonrpsee::types::ErrorObject;
+use jsonrpsee::MethodResponse;
+use tower::Layer;
+
+use std::sync::Arc;
+
+/// Error code returned when a call exceeds its time limit, as used by `geth`
+pub const TIMEOUT_CODE: i32 = -32002;
+
+/// Cancels method calls exceeding their time limit. The handler future is
+/// dropped, which also halts the blocking work of methods that support it.
+#[derive(Clone)]
+pub struct TimeoutLayer {
+    pub config: Arc<RpcConfig>,
+}
+
+impl<S> Layer<S> for TimeoutLayer {
+    type Service = TimeoutMiddleware<S>;
+
+    fn layer(&self, service: S) -> Self::Service {
+        TimeoutMiddleware {
+            config: self.config.clone(),
+            service,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct TimeoutMiddleware<S> {
+    config: Arc<RpcConfig>,
+    service: S,
+}
+
+impl<'a, S> RpcServiceT<'a> for TimeoutMiddleware<S>
+where
+    S: RpcServiceT<'a> + Send + Sync + Clone + 'static,
+{
+    type Future = BoxFuture<'a, MethodResponse>;
+
+    fn call(&self, req: jsonrpsee::types::Request<'a>) -> Self::Future {
+        let Some(timeout) = self.config.method_timeout(req.method_name()) else {
+            return self.service.call(req).boxed();
+        };
+        let id = req.id().into_owned();
+        let call = self.service.call(req);
+
+        async move {
+            match tokio::time::timeout(timeout, call).await {
+                Ok(response) => response,
+                Err(_) => MethodResponse::error(
+                    id,
+                    ErrorObject::owned(
+                        TIMEOUT_CODE,
+                        format!("Method call timed out after {}s", timeout.as_secs_f64()),
+                        None::<()>,
+                    ),
+                ),
+            }
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ahash::HashMap;
+    use jsonrpsee::server::{RpcModule, RpcServiceBuilder, Server};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+
+    async fn call(url: &str, method: &str) -> serde_json::Value {
+        reqwest::Client::new()
+            .post(url)
+            .json(&serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 0,
+                "method": method,
+                "params": [],
+            }))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn slow_method_times_out() {
+        let config = RpcConfig {
+            method_timeout: Duration::from_millis(100),
+            method_timeouts: HashMap::from_iter([("patient".into(), Duration::ZERO)]),
+            ..Default::default()
+        };
+        let server = Server::builder()
+            .set_rpc_middleware(RpcServiceBuilder::new().layer(TimeoutLayer {
+                config: Arc::new(config),
+            }))
+            .build("127.0.0.1:0")
+            .await
+            .unwrap();
+        let url = format!("http://{}", server.local_addr().unwrap());
+
+        let dropped = Arc::new(AtomicBool::new(false));
+        let mut module = RpcModule::new(dropped.clone());
+        module
+            .register_async_method("slow", |_, dropped| async move {
+                let _guard = scopeguard::guard((), |()| dropped.store(true, Ordering::Relaxed));
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                "slow"
+            })
+            .unwrap();
+        module
+            .register_async_method("patient", |_, _| async move {
+                tokio::time::sleep(Duration::from_millis(300)).await;
+                "patient"
+            })
+            .unwrap();
+        module.register_method("fast", |_, _| "fast").unwrap();
+        let handle = server.start(module);
+
+        let response = call(&url, "slow").await;
+        assert_eq!(response["error"]["code"], TIMEOUT_CODE);
+        // The handler is cancelled rather than left running
+        assert!(dropped.load(Ordering::Relaxed));
+
+        assert_eq!(call(&url, "fast").await["result"], "fast");
+        // Methods without a time limit run to completion
+        assert_eq!(call(&url, "patient").await["result"], "patient");
+
+        handle.stop().unwrap();
+    }
+}
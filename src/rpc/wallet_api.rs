@@ -6,6 +6,7 @@ use std::{convert::TryFrom, str::FromStr};
 
 use crate::key_management::{Key, KeyInfo};
 use crate::lotus_json::LotusJson;
+use crate::message::SignedMessage;
 use crate::rpc::error::JsonRpcError;
 use crate::rpc::Ctx;
 
@@ -13,6 +14,7 @@ use crate::shim::{
     address::Address,
     crypto::{Signature, SignatureType},
     econ::TokenAmount,
+    message::Message,
     state_tree::StateTree,
 };
 use anyhow::{Context, Result};
@@ -47,14 +49,18 @@ pub async fn wallet_balance<DB: Blockstore>(
     }
 }
 
-/// Get the default Address for the Wallet
+/// Get the default Address for the Wallet. If none is set, the first address
+/// of the Wallet becomes the default.
 pub async fn wallet_default_address<DB: Blockstore>(
     _params: Params<'_>,
     data: Ctx<DB>,
 ) -> Result<Option<String>, JsonRpcError> {
-    let keystore = data.keystore.read().await;
+    let mut keystore = data.keystore.write().await;
 
-    let addr = crate::key_management::get_default(&keystore)?;
+    let addr = crate::key_management::get_or_init_default(
+        &mut keystore,
+        data.state_manager.chain_store().settings().as_ref(),
+    )?;
     Ok(addr.map(|s| s.to_string()))
 }
 
@@ -84,7 +90,8 @@ pub async fn wallet_has<DB: Blockstore>(
 
     let keystore = data.keystore.read().await;
 
-    let key = crate::key_management::find_key(&addr, &keystore).is_ok();
+    let key = crate::key_management::find_key(&addr, &keystore).is_ok()
+        || crate::key_management::is_watch_addr(&addr, &keystore);
     Ok(key)
 }
 
@@ -114,13 +121,16 @@ pub async fn wallet_import<DB: Blockstore>(
     }
 }
 
-/// List all Addresses in the Wallet
+/// List all Addresses in the Wallet, including watch-only ones
 pub async fn wallet_list<DB: Blockstore>(
     _params: Params<'_>,
     data: Ctx<DB>,
 ) -> Result<LotusJson<Vec<Address>>, JsonRpcError> {
     let keystore = data.keystore.read().await;
-    Ok(crate::key_management::list_addrs(&keystore)?.into())
+    let mut addrs = crate::key_management::list_addrs(&keystore)?;
+    addrs.extend(crate::key_management::list_watch_addrs(&keystore));
+    addrs.sort_by_cached_key(|addr| addr.to_string());
+    Ok(addrs.into())
 }
 
 /// Generate a new Address that is stored in the Wallet
@@ -152,10 +162,25 @@ pub async fn wallet_set_default<DB: Blockstore>(
 
     let mut keystore = data.keystore.write().await;
 
-    let addr_string = format!("wallet-{}", address);
-    let key_info = keystore.get(&addr_string)?;
-    keystore.remove("default")?; // This line should unregister current default key then continue
-    keystore.put("default", key_info)?;
+    crate::key_management::set_default(
+        &mut keystore,
+        data.state_manager.chain_store().settings().as_ref(),
+        address,
+    )?;
+    Ok(())
+}
+
+/// Re-encrypt the keystore with a new passphrase
+pub async fn wallet_rotate_passphrase<DB: Blockstore>(
+    params: Params<'_>,
+    data: Ctx<DB>,
+) -> Result<(), JsonRpcError> {
+    let (old_passphrase, new_passphrase): (String, String) = params.parse()?;
+
+    // Hold the write guard for the whole operation so that no key is added or
+    // removed while the keystore is being re-encrypted.
+    let mut keystore = data.keystore.write().await;
+    keystore.rotate_passphrase(&old_passphrase, &new_passphrase)?;
     Ok(())
 }
 
@@ -192,6 +217,54 @@ where
     Ok(sig.into())
 }
 
+/// Sign an unsigned message with the key of the given address, or of the
+/// default wallet address if none is provided, and return the `SignedMessage`
+pub async fn wallet_sign_message<DB>(
+    params: Params<'_>,
+    data: Ctx<DB>,
+) -> Result<LotusJson<SignedMessage>, JsonRpcError>
+where
+    DB: Blockstore + Send + Sync + 'static,
+{
+    let LotusJson((address, message)): LotusJson<(Option<Address>, Message)> = params.parse()?;
+
+    let address = match address {
+        Some(address) => address,
+        None => crate::key_management::get_or_init_default(
+            &mut *data.keystore.write().await,
+            data.state_manager.chain_store().settings().as_ref(),
+        )?
+        .context("no address provided and no default wallet address set")?,
+    };
+
+    let heaviest_tipset = data.state_manager.chain_store().heaviest_tipset();
+    let key_addr = data
+        .state_manager
+        .resolve_to_key_addr(&address, &heaviest_tipset)
+        .await?;
+    let keystore = &mut *data.keystore.write().await;
+    let key_info = crate::key_management::try_find(&key_addr, keystore)
+        .with_context(|| format!("key not found for address {address}"))?;
+
+    Ok(sign_message(&Key::try_from(key_info)?, message)?.into())
+}
+
+/// Sign the CID bytes of `message`, which is the signing payload for
+/// `secp256k1` and BLS keys. Delegated keys must sign the RLP-encoded
+/// Ethereum transaction instead, which is not built here, so they are refused.
+pub(in crate::rpc) fn sign_message(key: &Key, message: Message) -> Result<SignedMessage> {
+    anyhow::ensure!(
+        *key.key_info.key_type() != SignatureType::Delegated,
+        "signing messages from delegated addresses is not supported, as they must be signed as Ethereum transactions"
+    );
+    let sig = crate::key_management::sign(
+        *key.key_info.key_type(),
+        key.key_info.private_key(),
+        message.cid()?.to_bytes().as_slice(),
+    )?;
+    Ok(SignedMessage::new_unchecked(message, sig))
+}
+
 /// Validates whether a given string can be decoded as a well-formed address
 pub(in crate::rpc) async fn wallet_validate_address(
     params: Params<'_>,
@@ -223,9 +296,132 @@ pub async fn wallet_delete<DB: Blockstore>(
     Ok(())
 }
 
+/// Watch an Address without storing any key material for it
+pub async fn wallet_add_watch<DB: Blockstore>(
+    params: Params<'_>,
+    data: Ctx<DB>,
+) -> Result<(), JsonRpcError> {
+    let LotusJson((address,)): LotusJson<(Address,)> = params.parse()?;
+
+    let mut keystore = data.keystore.write().await;
+    crate::key_management::add_watch_addr(&address, &mut keystore)?;
+    Ok(())
+}
+
+/// Stop watching an Address
+pub async fn wallet_remove_watch<DB: Blockstore>(
+    params: Params<'_>,
+    data: Ctx<DB>,
+) -> Result<(), JsonRpcError> {
+    let LotusJson((address,)): LotusJson<(Address,)> = params.parse()?;
+
+    let mut keystore = data.keystore.write().await;
+    crate::key_management::remove_watch_addr(&address, &mut keystore)?;
+    Ok(())
+}
+
+/// List all watch-only Addresses in the Wallet
+pub async fn wallet_list_watch<DB: Blockstore>(
+    _params: Params<'_>,
+    data: Ctx<DB>,
+) -> Result<LotusJson<Vec<Address>>, JsonRpcError> {
+    let keystore = data.keystore.read().await;
+    Ok(crate::key_management::list_watch_addrs(&keystore).into())
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::lotus_json::LotusJson;
+    use crate::rpc::RPCState;
+    use crate::shim::{address::Address, message::Message};
     use crate::{shim::crypto::SignatureType, KeyStore};
+    use jsonrpsee::types::Params;
+    use std::sync::Arc;
+
+    async fn sign_and_verify(sig_type: SignatureType) {
+        let key = crate::key_management::generate_key(sig_type).unwrap();
+        let message = Message {
+            from: key.address,
+            to: Address::new_id(1000),
+            ..Default::default()
+        };
+        let smsg = super::sign_message(&key, message.clone()).unwrap();
+        assert_eq!(smsg.message(), &message);
+        assert_eq!(smsg.signature().signature_type(), sig_type);
+
+        let params = serde_json::to_string(&LotusJson((
+            key.address,
+            message.cid().unwrap().to_bytes(),
+            smsg.signature().clone(),
+        )))
+        .unwrap();
+        assert!(super::wallet_verify(Params::new(Some(&params)))
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn wallet_sign_message_secp256k1() {
+        sign_and_verify(SignatureType::Secp256k1).await;
+    }
+
+    #[tokio::test]
+    async fn wallet_sign_message_bls() {
+        sign_and_verify(SignatureType::Bls).await;
+    }
+
+    #[test]
+    fn wallet_sign_message_delegated_is_refused() {
+        let key = crate::key_management::generate_key(SignatureType::Delegated).unwrap();
+        let message = Message {
+            from: key.address,
+            to: Address::new_id(1000),
+            ..Default::default()
+        };
+        let err = super::sign_message(&key, message).unwrap_err();
+        assert!(err.to_string().contains("Ethereum transactions"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn wallet_sign_message_through_rpc() {
+        let state = Arc::new(Arc::new(RPCState::calibnet()));
+        let key = crate::key_management::generate_key(SignatureType::Secp256k1).unwrap();
+        {
+            let mut keystore = state.keystore.write().await;
+            keystore
+                .put(&format!("wallet-{}", key.address), key.key_info.clone())
+                .unwrap();
+            keystore.put("default", key.key_info.clone()).unwrap();
+        }
+        let message = Message {
+            from: key.address,
+            to: Address::new_id(1000),
+            sequence: 3,
+            ..Default::default()
+        };
+
+        // With an explicit address, and with the default one
+        for address in [Some(key.address), None] {
+            let params = serde_json::to_string(&LotusJson((address, message.clone()))).unwrap();
+            let LotusJson(smsg) =
+                super::wallet_sign_message(Params::new(Some(&params)), state.clone())
+                    .await
+                    .unwrap();
+            assert_eq!(smsg.message(), &message);
+            smsg.signature()
+                .verify(&message.cid().unwrap().to_bytes(), &key.address)
+                .unwrap();
+        }
+
+        // Unknown keys are reported
+        let unknown = crate::key_management::generate_key(SignatureType::Secp256k1).unwrap();
+        let params =
+            serde_json::to_string(&LotusJson((Some(unknown.address), message.clone()))).unwrap();
+        let err = super::wallet_sign_message(Params::new(Some(&params)), state)
+            .await
+            .unwrap_err();
+        assert!(err.message().contains("key not found"), "{err}");
+    }
 
     #[tokio::test]
     async fn wallet_delete_existing_key() {
@@ -269,4 +465,44 @@ mod tests {
             .unwrap()
             .is_none());
     }
+
+    #[tokio::test]
+    async fn wallet_watch_only_address() {
+        let state = Arc::new(Arc::new(RPCState::calibnet()));
+        let key = crate::key_management::generate_key(SignatureType::Secp256k1).unwrap();
+        let address = serde_json::to_string(&LotusJson((key.address,))).unwrap();
+        let address_str = serde_json::to_string(&(key.address.to_string(),)).unwrap();
+
+        super::wallet_add_watch(Params::new(Some(&address)), state.clone())
+            .await
+            .unwrap();
+
+        assert!(
+            super::wallet_has(Params::new(Some(&address_str)), state.clone())
+                .await
+                .unwrap()
+        );
+        let LotusJson(listed) = super::wallet_list(Params::new(None), state.clone())
+            .await
+            .unwrap();
+        assert_eq!(listed, vec![key.address]);
+
+        // signing and exporting are refused
+        let sign_params = serde_json::to_string(&LotusJson((key.address, vec![0u8; 32]))).unwrap();
+        let err = super::wallet_sign(Params::new(Some(&sign_params)), state.clone())
+            .await
+            .unwrap_err();
+        assert!(err.message().contains("Watch-only"), "{err}");
+        let err = super::wallet_export(Params::new(Some(&address_str)), state.clone())
+            .await
+            .unwrap_err();
+        assert!(err.message().contains("Watch-only"), "{err}");
+
+        super::wallet_remove_watch(Params::new(Some(&address)), state.clone())
+            .await
+            .unwrap();
+        assert!(!super::wallet_has(Params::new(Some(&address_str)), state)
+            .await
+            .unwrap());
+    }
 }
@@ -9,7 +9,8 @@ use crate::chain_sync::SyncState;
 pub use crate::libp2p::Multiaddr;
 use crate::libp2p::Multihash;
 use crate::lotus_json::{lotus_json_with_self, HasLotusJson, LotusJson};
-use crate::message::signed_message::SignedMessage;
+use crate::message::{signed_message::SignedMessage, Message as _};
+use crate::networks::NetworkChain;
 use crate::shim::sector::SectorInfo;
 use crate::shim::{
     address::Address,
@@ -17,7 +18,7 @@ use crate::shim::{
     deal::DealID,
     econ::TokenAmount,
     error::ExitCode,
-    executor::Receipt,
+    executor::{ApplyRet, Receipt},
     fvm_shared_latest::MethodNum,
     message::Message,
     sector::{RegisteredSealProof, SectorNumber},
@@ -39,6 +40,7 @@ use libipld_core::ipld::Ipld;
 use libp2p::PeerId;
 use nonempty::NonEmpty;
 use num_bigint::BigInt;
+use schemars::JsonSchema;
 use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 use serde_json::Value;
 
@@ -52,13 +54,16 @@ pub struct RPCSyncState {
 lotus_json_with_self!(RPCSyncState);
 
 // Chain API
-#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct BlockMessages {
     #[serde(rename = "BlsMessages", with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<Vec<Message>>")]
     pub bls_msg: Vec<Message>,
     #[serde(rename = "SecpkMessages", with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<Vec<SignedMessage>>")]
     pub secp_msg: Vec<SignedMessage>,
     #[serde(rename = "Cids", with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<Vec<Cid>>")]
     pub cids: Vec<Cid>,
 }
 
@@ -68,7 +73,7 @@ lotus_json_with_self!(BlockMessages);
 #[serde(rename_all = "PascalCase")]
 pub struct MessageSendSpec {
     #[serde(with = "crate::lotus_json")]
-    max_fee: TokenAmount,
+    pub max_fee: TokenAmount,
 }
 
 lotus_json_with_self!(MessageSendSpec);
@@ -167,6 +172,26 @@ pub struct ApiMarketDeal {
 }
 
 lotus_json_with_self!(ApiMarketDeal);
+lotus_json_with_self!(std::collections::BTreeMap<DealID, ApiMarketDeal>);
+
+/// Selects a page of the deals returned by `StateMarketDeals`, which are in
+/// the order of their IDs.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct MarketDealsFilter {
+    /// Number of the selected deals to skip
+    #[serde(default)]
+    pub offset: u64,
+    /// Maximum number of deals to return
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u64>,
+    /// Selects the deals from the first ID, inclusive, to the second one,
+    /// exclusive
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deal_id_range: Option<(DealID, DealID)>,
+}
+
+lotus_json_with_self!(MarketDealsFilter);
 
 impl From<MarketDeal> for ApiMarketDeal {
     fn from(d: MarketDeal) -> Self {
@@ -273,7 +298,7 @@ impl ApiMessage {
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
 pub struct ApiMessageLotusJson {
     cid: LotusJson<Cid>,
@@ -383,32 +408,44 @@ impl<'de> Deserialize<'de> for AddressOrEmpty {
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
 pub struct MinerInfoLotusJson {
     #[serde(with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<Address>")]
     pub owner: Address,
     #[serde(with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<Address>")]
     pub worker: Address,
+    #[schemars(with = "String")]
     pub new_worker: AddressOrEmpty,
     #[serde(with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<Vec<Address>>")]
     pub control_addresses: Vec<Address>, // Must all be ID addresses.
     pub worker_change_epoch: ChainEpoch,
     #[serde(with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<Option<String>>")]
     pub peer_id: Option<String>,
     #[serde(with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<Vec<Vec<u8>>>")]
     pub multiaddrs: Vec<Vec<u8>>,
+    #[schemars(with = "i64")]
     pub window_po_st_proof_type: fvm_shared2::sector::RegisteredPoStProof,
+    #[schemars(with = "u64")]
     pub sector_size: fvm_shared2::sector::SectorSize,
     pub window_po_st_partition_sectors: u64,
     pub consensus_fault_elapsed: ChainEpoch,
     #[serde(with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<Option<Address>>")]
     pub pending_owner_address: Option<Address>,
     #[serde(with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<Address>")]
     pub beneficiary: Address,
     #[serde(with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<BeneficiaryTerm>")]
     pub beneficiary_term: BeneficiaryTerm,
     #[serde(with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<Option<PendingBeneficiaryChange>>")]
     pub pending_beneficiary_term: Option<PendingBeneficiaryChange>,
 }
 
@@ -470,14 +507,16 @@ impl HasLotusJson for MinerInfo {
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
 pub struct BeneficiaryTermLotusJson {
     /// The total amount the current beneficiary can withdraw. Monotonic, but reset when beneficiary changes.
     #[serde(with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<TokenAmount>")]
     pub quota: TokenAmount,
     /// The amount of quota the current beneficiary has already withdrawn
     #[serde(with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<TokenAmount>")]
     pub used_quota: TokenAmount,
     /// The epoch at which the beneficiary's rights expire and revert to the owner
     pub expiration: ChainEpoch,
@@ -508,12 +547,14 @@ impl HasLotusJson for BeneficiaryTerm {
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
 pub struct PendingBeneficiaryChangeLotusJson {
     #[serde(with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<Address>")]
     pub new_beneficiary: Address,
     #[serde(with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<TokenAmount>")]
     pub new_quota: TokenAmount,
     pub new_expiration: ChainEpoch,
     pub approved_by_beneficiary: bool,
@@ -549,24 +590,27 @@ impl HasLotusJson for PendingBeneficiaryChange {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
 pub struct ApiReceipt {
     // Exit status of message execution
+    #[schemars(with = "u32")]
     pub exit_code: ExitCode,
     // `Return` value if the exit code is zero
     #[serde(rename = "Return")]
     #[serde(with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<RawBytes>")]
     pub return_data: RawBytes,
     // Non-negative value of GasUsed
     pub gas_used: u64,
     #[serde(with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<Option<Cid>>")]
     pub events_root: Option<Cid>,
 }
 
 lotus_json_with_self!(ApiReceipt);
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
 pub struct MinerPowerLotusJson {
     miner_power: LotusJson<Claim>,
@@ -625,18 +669,29 @@ pub struct DiscoverResult {
     openrpc: String,
 }
 
+impl DiscoverResult {
+    /// Names of the methods described by the document.
+    pub fn method_names(&self) -> impl Iterator<Item = &str> {
+        self.methods.iter().map(|method| method.name.as_str())
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct DiscoverMethod {
-    deprecated: bool,
-    description: String,
-    external_docs: DiscoverDocs,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    deprecated: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    external_docs: Option<DiscoverDocs>,
     name: String,
     param_structure: String,
     params: Value,
-    // Missing 'result' field. Tracking issue:
-    // https://github.com/ChainSafe/forest/issues/3585
-    summary: String,
+    #[serde(default)]
+    result: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    summary: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -654,21 +709,25 @@ pub struct DiscoverInfo {
 lotus_json_with_self!(DiscoverResult, DiscoverMethod, DiscoverDocs, DiscoverInfo);
 
 /// State of all actor implementations.
-#[derive(PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
+#[derive(PartialEq, Eq, Clone, Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
 pub struct ActorStateJson {
     #[serde(with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<Cid>")]
     /// Link to code for the actor.
     pub code: Cid,
     #[serde(with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<Cid>")]
     /// Link to the state of the actor.
     pub head: Cid,
     /// Sequence of the actor.
     pub nonce: u64,
     #[serde(with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<TokenAmount>")]
     /// Tokens available to the actor.
     pub balance: TokenAmount,
     #[serde(with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<Option<Address>>")]
     /// The actor's "delegated" address, if assigned.
     /// This field is set on actor creation and never modified.
     pub address: Option<Address>,
@@ -731,21 +790,24 @@ impl ApiActorState {
     }
 }
 
-#[derive(Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Clone, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
 pub struct SectorOnChainInfo {
     pub sector_number: SectorNumber,
 
+    #[schemars(with = "i64")]
     /// The seal proof type implies the PoSt proofs
     pub seal_proof: RegisteredSealProof,
 
     #[serde(with = "crate::lotus_json")]
     #[serde(rename = "SealedCID")]
+    #[schemars(with = "LotusJson<Cid>")]
     /// `CommR`
     pub sealed_cid: Cid,
 
     #[serde(rename = "DealIDs")]
     #[serde(with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<Vec<DealID>>")]
     pub deal_ids: Vec<DealID>,
 
     /// Epoch during which the sector proof was accepted
@@ -755,23 +817,28 @@ pub struct SectorOnChainInfo {
     pub expiration: ChainEpoch,
 
     #[serde(with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<BigInt>")]
     /// Integral of active deals over sector lifetime
     pub deal_weight: BigInt,
 
     #[serde(with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<BigInt>")]
     /// Integral of active verified deals over sector lifetime
     pub verified_deal_weight: BigInt,
 
     #[serde(with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<TokenAmount>")]
     /// Pledge collected to commit this sector
     pub initial_pledge: TokenAmount,
 
     #[serde(with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<TokenAmount>")]
     /// Expected one day projection of reward for sector computed at activation
     /// time
     pub expected_day_reward: TokenAmount,
 
     #[serde(with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<TokenAmount>")]
     /// Expected twenty day projection of reward for sector computed at
     /// activation time
     pub expected_storage_pledge: TokenAmount,
@@ -779,10 +846,12 @@ pub struct SectorOnChainInfo {
     pub replaced_sector_age: ChainEpoch,
 
     #[serde(with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<TokenAmount>")]
     pub replaced_day_reward: TokenAmount,
 
     #[serde(with = "crate::lotus_json")]
     #[serde(rename = "SectorKeyCID")]
+    #[schemars(with = "LotusJson<Option<Cid>>")]
     pub sector_key_cid: Option<Cid>,
 
     #[serde(rename = "SimpleQAPower")]
@@ -818,17 +887,19 @@ impl From<fil_actor_interface::miner::SectorOnChainInfo> for SectorOnChainInfo {
 
 lotus_json_with_self!(SectorOnChainInfo);
 
-#[derive(Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
 pub struct ApiDeadline {
     #[serde(with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<BitField>")]
     pub post_submissions: BitField,
     #[serde(with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<u64>")]
     pub disputable_proof_count: u64,
 }
 
 lotus_json_with_self!(ApiDeadline);
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "PascalCase")]
 pub struct ApiInvocResult {
     #[serde(with = "crate::lotus_json")]
@@ -859,7 +930,7 @@ impl PartialEq for ApiInvocResult {
     }
 }
 
-#[derive(Default, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct MessageGasCost {
     #[serde(with = "crate::lotus_json")]
@@ -882,6 +953,35 @@ pub struct MessageGasCost {
 
 lotus_json_with_self!(MessageGasCost);
 
+impl MessageGasCost {
+    /// Breaks down the gas costs of `message` from the result of its
+    /// application.
+    pub fn new(message: &Message, apply_ret: &ApplyRet) -> Self {
+        Self {
+            message: message.cid().ok(),
+            gas_used: TokenAmount::from_atto(apply_ret.msg_receipt().gas_used()),
+            base_fee_burn: apply_ret.base_fee_burn(),
+            over_estimation_burn: apply_ret.over_estimation_burn(),
+            miner_penalty: apply_ret.penalty(),
+            miner_tip: apply_ret.miner_tip(),
+            refund: apply_ret.refund(),
+            total_cost: message.required_funds() - &apply_ret.refund(),
+        }
+    }
+}
+
+/// The state root computed by `Filecoin.StateCompute`, and the traces of the
+/// messages applied to get it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ComputeStateOutput {
+    #[serde(with = "crate::lotus_json")]
+    pub root: Cid,
+    pub trace: Vec<ApiInvocResult>,
+}
+
+lotus_json_with_self!(ComputeStateOutput);
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct ExecutionTrace {
@@ -984,7 +1084,23 @@ pub struct CirculatingSupply {
 
 lotus_json_with_self!(CirculatingSupply);
 
-#[derive(Clone, Serialize, Deserialize, PartialEq, Eq)]
+/// Identifies the chain of a node, for clients to check that they talk to the
+/// expected one.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "PascalCase")]
+pub struct NetworkIdentity {
+    pub network: NetworkChain,
+    #[serde(with = "crate::lotus_json")]
+    #[schemars(with = "LotusJson<Cid>")]
+    pub genesis_cid: Cid,
+    pub eth_chain_id: u32,
+    /// Block delay, in seconds.
+    pub block_delay: u32,
+}
+
+lotus_json_with_self!(NetworkIdentity);
+
+#[derive(Clone, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
 pub struct MinerSectors {
     live: u64,
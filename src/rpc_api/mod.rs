@@ -3,6 +3,27 @@
 
 pub mod data_types;
 
+/// Version of the JSON-RPC API, each of which is served under its own path.
+///
+/// Later versions add methods, and may change the signature of methods
+/// served by earlier versions.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ApiVersion {
+    #[default]
+    V0,
+    V1,
+}
+
+impl ApiVersion {
+    /// Path under which this version is served, e.g. `rpc/v0`.
+    pub const fn endpoint(self) -> &'static str {
+        match self {
+            ApiVersion::V0 => "rpc/v0",
+            ApiVersion::V1 => "rpc/v1",
+        }
+    }
+}
+
 /// JSON-RPC API definitions
 
 /// Authorization API
@@ -18,17 +39,24 @@ pub mod auth_api {
     #[derive(Deserialize, Serialize)]
     pub struct AuthNewParams {
         pub perms: Vec<String>,
-        #[serde_as(as = "DurationSeconds<i64>")]
-        pub token_exp: Duration,
+        /// The token never expires if unset.
+        #[serde_as(as = "Option<DurationSeconds<i64>>")]
+        #[serde(default)]
+        pub token_exp: Option<Duration>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub label: Option<String>,
     }
     lotus_json_with_self!(AuthNewParams);
 
     pub const AUTH_VERIFY: &str = "Filecoin.AuthVerify";
+    pub const AUTH_LIST: &str = "Filecoin.AuthList";
+    pub const AUTH_REVOKE: &str = "Filecoin.AuthRevoke";
 }
 
 /// Beacon API
 pub mod beacon_api {
     pub const BEACON_GET_ENTRY: &str = "Filecoin.BeaconGetEntry";
+    pub const BEACON_GET_LATEST: &str = "Filecoin.BeaconGetLatest";
 }
 
 /// Chain API
@@ -51,21 +79,39 @@ pub mod chain_api {
 
     pub const CHAIN_EXPORT: &str = "Filecoin.ChainExport";
 
-    #[derive(Debug, Clone, Serialize, Deserialize)]
+    #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
     pub struct ChainExportParams {
         pub epoch: ChainEpoch,
         pub recent_roots: i64,
         pub output_path: PathBuf,
         #[serde(with = "crate::lotus_json")]
+        #[schemars(with = "LotusJson<ApiTipsetKey>")]
         pub tipset_keys: ApiTipsetKey,
         pub skip_checksum: bool,
         pub dry_run: bool,
+        /// Only include the messages of the tipsets whose state is exported
+        #[serde(default)]
+        pub skip_old_messages: bool,
     }
 
     lotus_json_with_self!(ChainExportParams);
 
     pub type ChainExportResult = Option<String>;
 
+    pub const CHAIN_EXPORT_STATUS: &str = "Filecoin.ChainExportStatus";
+
+    /// Progress of a running export, which walks the chain from `start_epoch`
+    /// down to genesis.
+    #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+    #[serde(rename_all = "PascalCase")]
+    pub struct ChainExportProgress {
+        pub start_epoch: ChainEpoch,
+        pub epoch: ChainEpoch,
+        pub blocks: u64,
+    }
+
+    lotus_json_with_self!(ChainExportProgress);
+
     pub const CHAIN_READ_OBJ: &str = "Filecoin.ChainReadObj";
     pub const CHAIN_HAS_OBJ: &str = "Filecoin.ChainHasObj";
     pub const CHAIN_GET_BLOCK_MESSAGES: &str = "Filecoin.ChainGetBlockMessages";
@@ -77,6 +123,9 @@ pub mod chain_api {
     pub const CHAIN_GET_TIPSET: &str = "Filecoin.ChainGetTipSet";
     pub const CHAIN_GET_PATH: &str = "Filecoin.ChainGetPath";
     pub const CHAIN_SET_HEAD: &str = "Filecoin.ChainSetHead";
+    pub const CHAIN_PRUNE: &str = "Filecoin.ChainPrune";
+    pub const CHAIN_PRUNE_STATUS: &str = "Filecoin.ChainPruneStatus";
+    pub const CHAIN_FLUSH: &str = "Filecoin.ChainFlush";
     pub const CHAIN_GET_MIN_BASE_FEE: &str = "Filecoin.ChainGetMinBaseFee";
     pub const CHAIN_GET_MESSAGES_IN_TIPSET: &str = "Filecoin.ChainGetMessagesInTipset";
     pub const CHAIN_GET_PARENT_MESSAGES: &str = "Filecoin.ChainGetParentMessages";
@@ -168,6 +217,7 @@ pub mod chain_api {
 /// Message Pool API
 pub mod mpool_api {
     pub const MPOOL_GET_NONCE: &str = "Filecoin.MpoolGetNonce";
+    pub const MPOOL_LOCAL: &str = "Filecoin.MpoolLocal";
     pub const MPOOL_PENDING: &str = "Filecoin.MpoolPending";
     pub const MPOOL_PUSH: &str = "Filecoin.MpoolPush";
     pub const MPOOL_PUSH_MESSAGE: &str = "Filecoin.MpoolPushMessage";
@@ -177,7 +227,13 @@ pub mod mpool_api {
 pub mod sync_api {
     pub const SYNC_CHECK_BAD: &str = "Filecoin.SyncCheckBad";
     pub const SYNC_MARK_BAD: &str = "Filecoin.SyncMarkBad";
+    pub const SYNC_UNMARK_BAD: &str = "Filecoin.SyncUnmarkBad";
+    pub const SYNC_UNMARK_ALL_BAD: &str = "Filecoin.SyncUnmarkAllBad";
     pub const SYNC_STATE: &str = "Filecoin.SyncState";
+    pub const SYNC_SUBMIT_BLOCK: &str = "Filecoin.SyncSubmitBlock";
+    pub const SYNC_INCOMING_BLOCKS: &str = "Filecoin.SyncIncomingBlocks";
+    pub const SYNC_CHECKPOINT: &str = "Filecoin.SyncCheckpoint";
+    pub const SYNC_CHECKPOINT_GET: &str = "Filecoin.SyncCheckpointGet";
 }
 
 /// Wallet API
@@ -190,28 +246,38 @@ pub mod wallet_api {
     pub const WALLET_LIST: &str = "Filecoin.WalletList";
     pub const WALLET_NEW: &str = "Filecoin.WalletNew";
     pub const WALLET_SET_DEFAULT: &str = "Filecoin.WalletSetDefault";
+    pub const WALLET_ROTATE_PASSPHRASE: &str = "Filecoin.WalletRotatePassphrase";
     pub const WALLET_SIGN: &str = "Filecoin.WalletSign";
+    pub const WALLET_SIGN_MESSAGE: &str = "Filecoin.WalletSignMessage";
     pub const WALLET_VALIDATE_ADDRESS: &str = "Filecoin.WalletValidateAddress";
     pub const WALLET_VERIFY: &str = "Filecoin.WalletVerify";
     pub const WALLET_DELETE: &str = "Filecoin.WalletDelete";
+    pub const WALLET_ADD_WATCH: &str = "Filecoin.WalletAddWatch";
+    pub const WALLET_REMOVE_WATCH: &str = "Filecoin.WalletRemoveWatch";
+    pub const WALLET_LIST_WATCH: &str = "Filecoin.WalletListWatch";
 }
 
 /// State API
 pub mod state_api {
     pub const STATE_CALL: &str = "Filecoin.StateCall";
     pub const STATE_REPLAY: &str = "Filecoin.StateReplay";
+    pub const STATE_COMPUTE: &str = "Filecoin.StateCompute";
     pub const STATE_NETWORK_NAME: &str = "Filecoin.StateNetworkName";
+    pub const STATE_NETWORK_IDENTITY: &str = "Filecoin.StateNetworkIdentity";
     pub const STATE_NETWORK_VERSION: &str = "Filecoin.StateNetworkVersion";
+    pub const STATE_MIGRATION_STATUS: &str = "Filecoin.StateMigrationStatus";
     pub const STATE_GET_ACTOR: &str = "Filecoin.StateGetActor";
     pub const STATE_MARKET_BALANCE: &str = "Filecoin.StateMarketBalance";
     pub const STATE_MARKET_DEALS: &str = "Filecoin.StateMarketDeals";
     pub const STATE_MINER_INFO: &str = "Filecoin.StateMinerInfo";
     pub const MINER_GET_BASE_INFO: &str = "Filecoin.MinerGetBaseInfo";
     pub const STATE_MINER_FAULTS: &str = "Filecoin.StateMinerFaults";
+    pub const STATE_MINER_FAULTS_BY_DEADLINE: &str = "Filecoin.StateMinerFaultsByDeadline";
     pub const STATE_MINER_RECOVERIES: &str = "Filecoin.StateMinerRecoveries";
     pub const STATE_MINER_POWER: &str = "Filecoin.StateMinerPower";
     pub const STATE_MINER_DEADLINES: &str = "Filecoin.StateMinerDeadlines";
     pub const STATE_MINER_PROVING_DEADLINE: &str = "Filecoin.StateMinerProvingDeadline";
+    pub const STATE_MINER_CURRENT_DEADLINE: &str = "Filecoin.StateMinerCurrentDeadline";
     pub const STATE_MINER_AVAILABLE_BALANCE: &str = "Filecoin.StateMinerAvailableBalance";
     pub const STATE_GET_RECEIPT: &str = "Filecoin.StateGetReceipt";
     pub const STATE_WAIT_MSG: &str = "Filecoin.StateWaitMsg";
@@ -271,6 +337,21 @@ pub mod net_api {
     pub const NET_AGENT_VERSION: &str = "Filecoin.NetAgentVersion";
     pub const NET_AUTO_NAT_STATUS: &str = "Filecoin.NetAutoNatStatus";
     pub const NET_VERSION: &str = "Filecoin.NetVersion";
+    pub const NET_BANDWIDTH_STATS: &str = "Filecoin.NetBandwidthStats";
+    pub const NET_BANDWIDTH_STATS_BY_PEER: &str = "Filecoin.NetBandwidthStatsByPeer";
+    pub const NET_BANDWIDTH_STATS_BY_PROTOCOL: &str = "Filecoin.NetBandwidthStatsByProtocol";
+    pub const NET_FIND_PEER: &str = "Filecoin.NetFindPeer";
+    pub const NET_PING: &str = "Filecoin.NetPing";
+    pub const NET_BLOCK_ADD: &str = "Filecoin.NetBlockAdd";
+    pub const NET_BLOCK_REMOVE: &str = "Filecoin.NetBlockRemove";
+    pub const NET_BLOCK_LIST: &str = "Filecoin.NetBlockList";
+    pub const NET_PUBSUB_SCORES: &str = "Filecoin.NetPubsubScores";
+    pub const NET_CONNECTEDNESS: &str = "Filecoin.NetConnectedness";
+    pub const NET_LIMIT: &str = "Filecoin.NetLimit";
+    pub const NET_SET_LIMIT: &str = "Filecoin.NetSetLimit";
+    pub const NET_BOOTSTRAP: &str = "Filecoin.NetBootstrap";
+    pub const NET_BOOTSTRAP_LIST: &str = "Filecoin.NetBootstrapList";
+    pub const NET_BOOTSTRAP_ADD: &str = "Filecoin.NetBootstrapAdd";
 
     #[derive(Debug, Default, Serialize, Deserialize, Clone)]
     pub struct NetInfoResult {
@@ -305,6 +386,101 @@ pub mod net_api {
     }
     lotus_json_with_self!(NatStatusResult);
 
+    /// Bandwidth usage in the shape of the Lotus `metrics.Stats` type. Rates
+    /// are in bytes per second.
+    #[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq)]
+    #[serde(rename_all = "PascalCase")]
+    pub struct BandwidthStats {
+        pub total_in: u64,
+        pub total_out: u64,
+        pub rate_in: f64,
+        pub rate_out: f64,
+    }
+    lotus_json_with_self!(BandwidthStats, ahash::HashMap<String, BandwidthStats>);
+
+    /// Peers, IP addresses and subnets denied by the connection gater, in the
+    /// shape of the Lotus `api.NetBlockList` type.
+    #[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
+    #[serde(rename_all = "PascalCase")]
+    pub struct NetBlockList {
+        #[serde(with = "crate::lotus_json", default)]
+        pub peers: Vec<String>,
+        #[serde(rename = "IPAddrs", with = "crate::lotus_json", default)]
+        pub ip_addrs: Vec<String>,
+        #[serde(rename = "IPSubnets", with = "crate::lotus_json", default)]
+        pub ip_subnets: Vec<String>,
+    }
+    lotus_json_with_self!(NetBlockList);
+
+    #[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
+    #[serde(rename_all = "PascalCase")]
+    pub struct NetBootstrapResult {
+        /// Number of bootstrap peers dialed.
+        pub attempted: u64,
+        /// Number of bootstrap peers connected within the timeout.
+        pub succeeded: u64,
+    }
+    lotus_json_with_self!(NetBootstrapResult);
+
+    /// Connection manager watermarks on the number of connected peers. Peers
+    /// are disconnected down to `ConnMgrLow` once there are more than
+    /// `ConnMgrHigh` of them.
+    #[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+    #[serde(rename_all = "PascalCase")]
+    pub struct NetLimit {
+        pub conn_mgr_low: u32,
+        pub conn_mgr_high: u32,
+    }
+    lotus_json_with_self!(NetLimit);
+
+    impl NetLimit {
+        pub fn from_target_peer_count(target_peer_count: u32) -> Self {
+            Self {
+                conn_mgr_low: target_peer_count,
+                conn_mgr_high: target_peer_count.saturating_mul(2),
+            }
+        }
+    }
+
+    /// Connectedness of a peer, with the values of the `go-libp2p`
+    /// `network.Connectedness` enum.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[repr(u32)]
+    pub enum Connectedness {
+        NotConnected = 0,
+        Connected = 1,
+        CanConnect = 2,
+        CannotConnect = 3,
+    }
+
+    /// Gossipsub score of a peer, in the shape of the Lotus `api.PubsubScore`
+    /// type.
+    #[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq)]
+    #[serde(rename_all = "PascalCase")]
+    pub struct PubsubScore {
+        #[serde(rename = "ID")]
+        pub id: String,
+        pub score: PeerScoreSnapshot,
+    }
+    lotus_json_with_self!(PubsubScore);
+
+    #[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq)]
+    #[serde(rename_all = "PascalCase")]
+    pub struct PeerScoreSnapshot {
+        pub score: f64,
+        pub topics: ahash::HashMap<String, TopicScoreSnapshot>,
+    }
+
+    #[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq)]
+    #[serde(rename_all = "PascalCase")]
+    pub struct TopicScoreSnapshot {
+        /// Nanoseconds, like Go's `time.Duration`.
+        pub time_in_mesh: i64,
+        pub first_message_deliveries: f64,
+        pub mesh_message_deliveries: f64,
+        pub invalid_message_deliveries: f64,
+    }
+
     impl NatStatusResult {
         // See <https://github.com/libp2p/go-libp2p/blob/164adb40fef9c19774eb5fe6d92afb95c67ba83c/core/network/network.go#L93>
         pub fn reachability_as_str(&self) -> &'static str {
@@ -1,22 +1,53 @@
 // Copyright 2019-2024 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use crate::auth::TokenInfo;
 use crate::rpc_api::auth_api::*;
 use chrono::Duration;
 
 use super::{ApiInfo, JsonRpcError, RpcRequest};
 
 impl ApiInfo {
-    /// Creates a new JWT Token
+    /// Creates a new JWT Token, which never expires if `token_exp` is `None`
     pub async fn auth_new(
         &self,
         perms: Vec<String>,
-        token_exp: Duration,
+        token_exp: Option<Duration>,
+        label: Option<String>,
     ) -> Result<Vec<u8>, JsonRpcError> {
-        self.call(Self::auth_new_req(perms, token_exp)).await
+        self.call(Self::auth_new_req(perms, token_exp, label)).await
     }
 
-    pub fn auth_new_req(perms: Vec<String>, token_exp: Duration) -> RpcRequest<Vec<u8>> {
-        RpcRequest::new(AUTH_NEW, AuthNewParams { perms, token_exp })
+    pub fn auth_new_req(
+        perms: Vec<String>,
+        token_exp: Option<Duration>,
+        label: Option<String>,
+    ) -> RpcRequest<Vec<u8>> {
+        RpcRequest::new(
+            AUTH_NEW,
+            AuthNewParams {
+                perms,
+                token_exp,
+                label,
+            },
+        )
+    }
+
+    /// Lists the tokens created with `auth_new`
+    pub async fn auth_list(&self) -> Result<Vec<TokenInfo>, JsonRpcError> {
+        self.call(Self::auth_list_req()).await
+    }
+
+    pub fn auth_list_req() -> RpcRequest<Vec<TokenInfo>> {
+        RpcRequest::new(AUTH_LIST, ())
+    }
+
+    /// Revokes a token created with `auth_new`
+    pub async fn auth_revoke(&self, id: String) -> Result<(), JsonRpcError> {
+        self.call(Self::auth_revoke_req(id)).await
+    }
+
+    pub fn auth_revoke_req(id: String) -> RpcRequest<()> {
+        RpcRequest::new(AUTH_REVOKE, (id,))
     }
 }
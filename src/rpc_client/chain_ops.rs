@@ -3,6 +3,7 @@
 
 use std::time::Duration;
 
+use crate::db::GcProgress;
 use crate::rpc_api::data_types::*;
 use crate::shim::message::Message;
 use crate::{
@@ -87,6 +88,30 @@ impl ApiInfo {
         RpcRequest::new(CHAIN_SET_HEAD, (new_head,))
     }
 
+    pub async fn chain_prune(&self) -> Result<(), JsonRpcError> {
+        self.call(Self::chain_prune_req()).await
+    }
+
+    pub fn chain_prune_req() -> RpcRequest<()> {
+        RpcRequest::new(CHAIN_PRUNE, ())
+    }
+
+    pub async fn chain_flush(&self) -> Result<ChainEpoch, JsonRpcError> {
+        self.call(Self::chain_flush_req()).await
+    }
+
+    pub fn chain_flush_req() -> RpcRequest<ChainEpoch> {
+        RpcRequest::new(CHAIN_FLUSH, ())
+    }
+
+    pub async fn chain_prune_status(&self) -> Result<GcProgress, JsonRpcError> {
+        self.call(Self::chain_prune_status_req()).await
+    }
+
+    pub fn chain_prune_status_req() -> RpcRequest<GcProgress> {
+        RpcRequest::new(CHAIN_PRUNE_STATUS, ())
+    }
+
     pub async fn chain_export(
         &self,
         params: ChainExportParams,
@@ -96,7 +121,15 @@ impl ApiInfo {
 
     pub fn chain_export_req(params: ChainExportParams) -> RpcRequest<ChainExportResult> {
         // snapshot export could take a few hours on mainnet
-        RpcRequest::new(CHAIN_EXPORT, params).with_timeout(Duration::MAX)
+        RpcRequest::new(CHAIN_EXPORT, (params,)).with_timeout(Duration::MAX)
+    }
+
+    pub async fn chain_export_status(&self) -> Result<Option<ChainExportProgress>, JsonRpcError> {
+        self.call(Self::chain_export_status_req()).await
+    }
+
+    pub fn chain_export_status_req() -> RpcRequest<Option<ChainExportProgress>> {
+        RpcRequest::new(CHAIN_EXPORT_STATUS, ())
     }
 
     #[allow(dead_code)]
@@ -144,7 +177,9 @@ impl ApiInfo {
         RpcRequest::new(CHAIN_GET_PARENT_MESSAGES, (block_cid,))
     }
 
-    pub fn chain_notify_req() -> RpcRequest<()> {
+    /// Opens a channel of head changes, to be sent with
+    /// [`ApiInfo::ws_subscribe`].
+    pub fn chain_notify_req() -> RpcRequest<Vec<ApiHeadChange>> {
         RpcRequest::new(CHAIN_NOTIFY, ())
     }
 
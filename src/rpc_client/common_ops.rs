@@ -10,6 +10,10 @@ use chrono::{DateTime, Utc};
 use super::{ApiInfo, JsonRpcError, RpcRequest};
 
 impl ApiInfo {
+    pub async fn version(&self) -> Result<APIVersion, JsonRpcError> {
+        self.call(Self::version_req()).await
+    }
+
     pub fn version_req() -> RpcRequest<APIVersion> {
         RpcRequest::new(VERSION, ())
     }
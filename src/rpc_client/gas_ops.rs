@@ -0,0 +1,32 @@
+// Copyright 2019-2024 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use crate::{
+    rpc_api::{
+        data_types::{ApiTipsetKey, MessageSendSpec},
+        gas_api::*,
+    },
+    shim::message::Message,
+};
+
+use super::{ApiInfo, JsonRpcError, RpcRequest};
+
+impl ApiInfo {
+    pub async fn gas_estimate_message_gas(
+        &self,
+        message: Message,
+        spec: Option<MessageSendSpec>,
+        tsk: ApiTipsetKey,
+    ) -> Result<Message, JsonRpcError> {
+        self.call(Self::gas_estimate_message_gas_req(message, spec, tsk))
+            .await
+    }
+
+    pub fn gas_estimate_message_gas_req(
+        message: Message,
+        spec: Option<MessageSendSpec>,
+        tsk: ApiTipsetKey,
+    ) -> RpcRequest<Message> {
+        RpcRequest::new(GAS_ESTIMATE_MESSAGE_GAS, (message, spec, tsk))
+    }
+}
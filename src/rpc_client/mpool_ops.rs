@@ -16,6 +16,14 @@ impl ApiInfo {
         RpcRequest::new(MPOOL_GET_NONCE, (addr,))
     }
 
+    pub async fn mpool_push(&self, message: SignedMessage) -> Result<Cid, JsonRpcError> {
+        self.call(Self::mpool_push_req(message)).await
+    }
+
+    pub fn mpool_push_req(message: SignedMessage) -> RpcRequest<Cid> {
+        RpcRequest::new(MPOOL_PUSH, (message,))
+    }
+
     pub async fn mpool_push_message(
         &self,
         message: Message,
@@ -39,4 +47,14 @@ impl ApiInfo {
     pub fn mpool_pending_req(cids: Vec<Cid>) -> RpcRequest<Vec<SignedMessage>> {
         RpcRequest::new(MPOOL_PENDING, (cids,))
     }
+
+    #[allow(unused)] // consistency
+    pub async fn mpool_local(&self) -> Result<Vec<SignedMessage>, JsonRpcError> {
+        self.call(Self::mpool_local_req()).await
+    }
+
+    #[allow(unused)] // consistency
+    pub fn mpool_local_req() -> RpcRequest<Vec<SignedMessage>> {
+        RpcRequest::new(MPOOL_LOCAL, ())
+    }
 }
@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use crate::rpc_api::{data_types::AddrInfo, net_api::*};
+use ahash::HashMap;
 
 use super::{ApiInfo, JsonRpcError, RpcRequest};
 
@@ -69,4 +70,144 @@ impl ApiInfo {
     pub fn net_version_req() -> RpcRequest<String> {
         RpcRequest::new_v1(NET_VERSION, ())
     }
+
+    #[allow(unused)] // consistency
+    pub async fn net_bandwidth_stats(&self) -> Result<BandwidthStats, JsonRpcError> {
+        self.call(Self::net_bandwidth_stats_req()).await
+    }
+
+    pub fn net_bandwidth_stats_req() -> RpcRequest<BandwidthStats> {
+        RpcRequest::new(NET_BANDWIDTH_STATS, ())
+    }
+
+    #[allow(unused)] // consistency
+    pub async fn net_bandwidth_stats_by_peer(
+        &self,
+    ) -> Result<HashMap<String, BandwidthStats>, JsonRpcError> {
+        self.call(Self::net_bandwidth_stats_by_peer_req()).await
+    }
+
+    pub fn net_bandwidth_stats_by_peer_req() -> RpcRequest<HashMap<String, BandwidthStats>> {
+        RpcRequest::new(NET_BANDWIDTH_STATS_BY_PEER, ())
+    }
+
+    #[allow(unused)] // consistency
+    pub async fn net_bandwidth_stats_by_protocol(
+        &self,
+    ) -> Result<HashMap<String, BandwidthStats>, JsonRpcError> {
+        self.call(Self::net_bandwidth_stats_by_protocol_req()).await
+    }
+
+    pub fn net_bandwidth_stats_by_protocol_req() -> RpcRequest<HashMap<String, BandwidthStats>> {
+        RpcRequest::new(NET_BANDWIDTH_STATS_BY_PROTOCOL, ())
+    }
+
+    #[allow(unused)] // consistency
+    pub async fn net_find_peer(&self, peer: String) -> Result<AddrInfo, JsonRpcError> {
+        self.call(Self::net_find_peer_req(peer)).await
+    }
+
+    pub fn net_find_peer_req(peer: String) -> RpcRequest<AddrInfo> {
+        RpcRequest::new(NET_FIND_PEER, (peer,))
+    }
+
+    #[allow(unused)] // consistency
+    pub async fn net_ping(&self, peer: String) -> Result<i64, JsonRpcError> {
+        self.call(Self::net_ping_req(peer)).await
+    }
+
+    /// The round-trip time is in nanoseconds, like Go's `time.Duration`.
+    pub fn net_ping_req(peer: String) -> RpcRequest<i64> {
+        RpcRequest::new(NET_PING, (peer,))
+    }
+
+    #[allow(unused)] // consistency
+    pub async fn net_block_add(&self, acl: NetBlockList) -> Result<(), JsonRpcError> {
+        self.call(Self::net_block_add_req(acl)).await
+    }
+
+    #[allow(unused)] // consistency
+    pub fn net_block_add_req(acl: NetBlockList) -> RpcRequest<()> {
+        RpcRequest::new(NET_BLOCK_ADD, (acl,))
+    }
+
+    #[allow(unused)] // consistency
+    pub async fn net_block_remove(&self, acl: NetBlockList) -> Result<(), JsonRpcError> {
+        self.call(Self::net_block_remove_req(acl)).await
+    }
+
+    #[allow(unused)] // consistency
+    pub fn net_block_remove_req(acl: NetBlockList) -> RpcRequest<()> {
+        RpcRequest::new(NET_BLOCK_REMOVE, (acl,))
+    }
+
+    #[allow(unused)] // consistency
+    pub async fn net_block_list(&self) -> Result<NetBlockList, JsonRpcError> {
+        self.call(Self::net_block_list_req()).await
+    }
+
+    pub fn net_block_list_req() -> RpcRequest<NetBlockList> {
+        RpcRequest::new(NET_BLOCK_LIST, ())
+    }
+
+    pub async fn net_pubsub_scores(&self) -> Result<Vec<PubsubScore>, JsonRpcError> {
+        self.call(Self::net_pubsub_scores_req()).await
+    }
+
+    pub fn net_pubsub_scores_req() -> RpcRequest<Vec<PubsubScore>> {
+        RpcRequest::new(NET_PUBSUB_SCORES, ())
+    }
+
+    #[allow(unused)] // consistency
+    pub async fn net_connectedness(&self, peer: String) -> Result<u32, JsonRpcError> {
+        self.call(Self::net_connectedness_req(peer)).await
+    }
+
+    pub fn net_connectedness_req(peer: String) -> RpcRequest<u32> {
+        RpcRequest::new(NET_CONNECTEDNESS, (peer,))
+    }
+
+    #[allow(unused)] // consistency
+    pub async fn net_limit(&self, scope: String) -> Result<NetLimit, JsonRpcError> {
+        self.call(Self::net_limit_req(scope)).await
+    }
+
+    #[allow(unused)] // consistency
+    pub fn net_limit_req(scope: String) -> RpcRequest<NetLimit> {
+        RpcRequest::new(NET_LIMIT, (scope,))
+    }
+
+    #[allow(unused)] // consistency
+    pub async fn net_set_limit(&self, scope: String, limit: NetLimit) -> Result<(), JsonRpcError> {
+        self.call(Self::net_set_limit_req(scope, limit)).await
+    }
+
+    #[allow(unused)] // consistency
+    pub fn net_set_limit_req(scope: String, limit: NetLimit) -> RpcRequest<()> {
+        RpcRequest::new(NET_SET_LIMIT, (scope, limit))
+    }
+
+    pub async fn net_bootstrap(&self) -> Result<NetBootstrapResult, JsonRpcError> {
+        self.call(Self::net_bootstrap_req()).await
+    }
+
+    pub fn net_bootstrap_req() -> RpcRequest<NetBootstrapResult> {
+        RpcRequest::new(NET_BOOTSTRAP, ())
+    }
+
+    pub async fn net_bootstrap_list(&self) -> Result<Vec<String>, JsonRpcError> {
+        self.call(Self::net_bootstrap_list_req()).await
+    }
+
+    pub fn net_bootstrap_list_req() -> RpcRequest<Vec<String>> {
+        RpcRequest::new(NET_BOOTSTRAP_LIST, ())
+    }
+
+    pub async fn net_bootstrap_add(&self, addr: String) -> Result<(), JsonRpcError> {
+        self.call(Self::net_bootstrap_add_req(addr)).await
+    }
+
+    pub fn net_bootstrap_add_req(addr: String) -> RpcRequest<()> {
+        RpcRequest::new(NET_BOOTSTRAP_ADD, (addr,))
+    }
 }
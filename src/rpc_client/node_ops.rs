@@ -11,6 +11,6 @@ impl ApiInfo {
     }
 
     pub fn node_status_req() -> RpcRequest<NodeStatus> {
-        RpcRequest::new(NODE_STATUS, ())
+        RpcRequest::new_v1(NODE_STATUS, ())
     }
 }
@@ -1,6 +1,7 @@
 // Copyright 2019-2024 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 use std::time::Duration;
 
@@ -11,6 +12,7 @@ use crate::{
         address::Address, clock::ChainEpoch, deal::DealID, econ::TokenAmount, message::Message,
         message::MethodNum, state_tree::ActorState, version::NetworkVersion,
     },
+    state_migration::MigrationStatus,
 };
 use cid::Cid;
 use fil_actor_interface::miner::{DeadlineInfo, MinerInfo, MinerPower};
@@ -57,6 +59,22 @@ impl ApiInfo {
         RpcRequest::new(STATE_NETWORK_NAME, ())
     }
 
+    pub async fn state_network_identity(&self) -> Result<NetworkIdentity, JsonRpcError> {
+        self.call(Self::state_network_identity_req()).await
+    }
+
+    pub fn state_network_identity_req() -> RpcRequest<NetworkIdentity> {
+        RpcRequest::new(STATE_NETWORK_IDENTITY, ())
+    }
+
+    pub async fn state_migration_status(&self) -> Result<Option<MigrationStatus>, JsonRpcError> {
+        self.call(Self::state_migration_status_req()).await
+    }
+
+    pub fn state_migration_status_req() -> RpcRequest<Option<MigrationStatus>> {
+        RpcRequest::new(STATE_MIGRATION_STATUS, ())
+    }
+
     pub fn state_miner_info_req(miner: Address, tsk: ApiTipsetKey) -> RpcRequest<MinerInfo> {
         RpcRequest::new(STATE_MINER_INFO, (miner, tsk))
     }
@@ -73,10 +91,47 @@ impl ApiInfo {
         RpcRequest::new(STATE_CALL, (message, tsk))
     }
 
+    pub async fn state_compute(
+        &self,
+        epoch: ChainEpoch,
+        messages: Vec<Message>,
+        tsk: ApiTipsetKey,
+    ) -> Result<ComputeStateOutput, JsonRpcError> {
+        self.call(Self::state_compute_req(epoch, messages, tsk))
+            .await
+    }
+
+    pub fn state_compute_req(
+        epoch: ChainEpoch,
+        messages: Vec<Message>,
+        tsk: ApiTipsetKey,
+    ) -> RpcRequest<ComputeStateOutput> {
+        RpcRequest::new(STATE_COMPUTE, (epoch, messages, tsk)).with_timeout(Duration::MAX)
+    }
+
+    pub async fn state_replay(
+        &self,
+        tsk: ApiTipsetKey,
+        message: Cid,
+    ) -> Result<ApiInvocResult, JsonRpcError> {
+        self.call(Self::state_replay_req(tsk, message)).await
+    }
+
+    pub fn state_replay_req(tsk: ApiTipsetKey, message: Cid) -> RpcRequest<ApiInvocResult> {
+        RpcRequest::new(STATE_REPLAY, (tsk, message)).with_timeout(Duration::MAX)
+    }
+
     pub fn state_miner_faults_req(miner: Address, tsk: ApiTipsetKey) -> RpcRequest<BitField> {
         RpcRequest::new(STATE_MINER_FAULTS, (miner, tsk))
     }
 
+    pub fn state_miner_faults_by_deadline_req(
+        miner: Address,
+        tsk: ApiTipsetKey,
+    ) -> RpcRequest<Vec<BitField>> {
+        RpcRequest::new(STATE_MINER_FAULTS_BY_DEADLINE, (miner, tsk))
+    }
+
     pub fn state_miner_recoveries_req(miner: Address, tsk: ApiTipsetKey) -> RpcRequest<BitField> {
         RpcRequest::new(STATE_MINER_RECOVERIES, (miner, tsk))
     }
@@ -194,6 +249,15 @@ impl ApiInfo {
         RpcRequest::new(STATE_SECTOR_GET_INFO, (addr, sector_no, tsk))
     }
 
+    pub async fn state_wait_msg(
+        &self,
+        msg_cid: Cid,
+        confidence: i64,
+    ) -> Result<Option<MessageLookup>, JsonRpcError> {
+        self.call(Self::state_wait_msg_req(msg_cid, confidence))
+            .await
+    }
+
     pub fn state_wait_msg_req(msg_cid: Cid, confidence: i64) -> RpcRequest<Option<MessageLookup>> {
         // This API is meant to be blocking when the message is missing from the blockstore
         RpcRequest::new(STATE_WAIT_MSG, (msg_cid, confidence)).with_timeout(Duration::MAX)
@@ -222,6 +286,16 @@ impl ApiInfo {
         RpcRequest::new(STATE_LIST_MESSAGES, (from_to, tsk, max_height))
     }
 
+    pub fn state_market_deals_req(
+        tsk: ApiTipsetKey,
+        filter: Option<MarketDealsFilter>,
+    ) -> RpcRequest<BTreeMap<DealID, ApiMarketDeal>> {
+        match filter {
+            Some(filter) => RpcRequest::new(STATE_MARKET_DEALS, (tsk, filter)),
+            None => RpcRequest::new(STATE_MARKET_DEALS, (tsk,)),
+        }
+    }
+
     pub fn state_market_storage_deal_req(
         deal_id: DealID,
         tsk: ApiTipsetKey,
@@ -1,6 +1,7 @@
 // Copyright 2019-2024 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use crate::blocks::TipsetKey;
 use crate::rpc_api::{data_types::RPCSyncState, sync_api::*};
 use cid::Cid;
 
@@ -23,6 +24,22 @@ impl ApiInfo {
         RpcRequest::new(SYNC_MARK_BAD, (cid,))
     }
 
+    pub async fn sync_unmark_bad(&self, cid: Cid) -> Result<(), JsonRpcError> {
+        self.call(Self::sync_unmark_bad_req(cid)).await
+    }
+
+    pub fn sync_unmark_bad_req(cid: Cid) -> RpcRequest<()> {
+        RpcRequest::new(SYNC_UNMARK_BAD, (cid,))
+    }
+
+    pub async fn sync_unmark_all_bad(&self) -> Result<(), JsonRpcError> {
+        self.call(Self::sync_unmark_all_bad_req()).await
+    }
+
+    pub fn sync_unmark_all_bad_req() -> RpcRequest<()> {
+        RpcRequest::new(SYNC_UNMARK_ALL_BAD, ())
+    }
+
     pub async fn sync_status(&self) -> Result<RPCSyncState, JsonRpcError> {
         self.call(Self::sync_status_req()).await
     }
@@ -30,4 +47,24 @@ impl ApiInfo {
     pub fn sync_status_req() -> RpcRequest<RPCSyncState> {
         RpcRequest::new(SYNC_STATE, ())
     }
+
+    #[allow(unused)] // consistency
+    pub async fn sync_checkpoint(&self, tsk: TipsetKey) -> Result<(), JsonRpcError> {
+        self.call(Self::sync_checkpoint_req(tsk)).await
+    }
+
+    #[allow(unused)] // consistency
+    pub fn sync_checkpoint_req(tsk: TipsetKey) -> RpcRequest<()> {
+        RpcRequest::new(SYNC_CHECKPOINT, (tsk,))
+    }
+
+    #[allow(unused)] // consistency
+    pub async fn sync_checkpoint_get(&self) -> Result<Option<TipsetKey>, JsonRpcError> {
+        self.call(Self::sync_checkpoint_get_req()).await
+    }
+
+    #[allow(unused)] // consistency
+    pub fn sync_checkpoint_get_req() -> RpcRequest<Option<TipsetKey>> {
+        RpcRequest::new(SYNC_CHECKPOINT_GET, ())
+    }
 }
@@ -4,10 +4,12 @@
 use super::{ApiInfo, JsonRpcError, RpcRequest};
 use crate::{
     key_management::KeyInfo,
+    message::SignedMessage,
     rpc_api::wallet_api::*,
     shim::{
         address::Address,
         crypto::{Signature, SignatureType},
+        message::Message,
     },
 };
 
@@ -76,6 +78,25 @@ impl ApiInfo {
         RpcRequest::new(WALLET_SET_DEFAULT, (address,))
     }
 
+    pub async fn wallet_rotate_passphrase(
+        &self,
+        old_passphrase: String,
+        new_passphrase: String,
+    ) -> Result<(), JsonRpcError> {
+        self.call(Self::wallet_rotate_passphrase_req(
+            old_passphrase,
+            new_passphrase,
+        ))
+        .await
+    }
+
+    pub fn wallet_rotate_passphrase_req(
+        old_passphrase: String,
+        new_passphrase: String,
+    ) -> RpcRequest<()> {
+        RpcRequest::new(WALLET_ROTATE_PASSPHRASE, (old_passphrase, new_passphrase))
+    }
+
     pub async fn wallet_sign(
         &self,
         address: Address,
@@ -88,6 +109,22 @@ impl ApiInfo {
         RpcRequest::new(WALLET_SIGN, (address, data))
     }
 
+    pub async fn wallet_sign_message(
+        &self,
+        address: Option<Address>,
+        message: Message,
+    ) -> Result<SignedMessage, JsonRpcError> {
+        self.call(Self::wallet_sign_message_req(address, message))
+            .await
+    }
+
+    pub fn wallet_sign_message_req(
+        address: Option<Address>,
+        message: Message,
+    ) -> RpcRequest<SignedMessage> {
+        RpcRequest::new(WALLET_SIGN_MESSAGE, (address, message))
+    }
+
     pub async fn wallet_validate_address(&self, address: String) -> Result<Address, JsonRpcError> {
         self.call(Self::wallet_validate_address_req(address)).await
     }
@@ -121,4 +158,28 @@ impl ApiInfo {
     pub fn wallet_delete_req(address: String) -> RpcRequest<()> {
         RpcRequest::new(WALLET_DELETE, (address,))
     }
+
+    pub async fn wallet_add_watch(&self, address: Address) -> Result<(), JsonRpcError> {
+        self.call(Self::wallet_add_watch_req(address)).await
+    }
+
+    pub fn wallet_add_watch_req(address: Address) -> RpcRequest<()> {
+        RpcRequest::new(WALLET_ADD_WATCH, (address,))
+    }
+
+    pub async fn wallet_remove_watch(&self, address: Address) -> Result<(), JsonRpcError> {
+        self.call(Self::wallet_remove_watch_req(address)).await
+    }
+
+    pub fn wallet_remove_watch_req(address: Address) -> RpcRequest<()> {
+        RpcRequest::new(WALLET_REMOVE_WATCH, (address,))
+    }
+
+    pub async fn wallet_list_watch(&self) -> Result<Vec<Address>, JsonRpcError> {
+        self.call(Self::wallet_list_watch_req()).await
+    }
+
+    pub fn wallet_list_watch_req() -> RpcRequest<Vec<Address>> {
+        RpcRequest::new(WALLET_LIST_WATCH, ())
+    }
 }
@@ -84,6 +84,14 @@ impl Signature {
         }
     }
 
+    /// Creates a delegated (Ethereum-style) Signature given the raw bytes.
+    pub fn new_delegated(bytes: Vec<u8>) -> Self {
+        Self {
+            sig_type: SignatureType::Delegated,
+            bytes,
+        }
+    }
+
     pub fn signature_type(&self) -> SignatureType {
         self.sig_type
     }
@@ -96,7 +104,7 @@ impl Signature {
         match self.sig_type {
             SignatureType::Bls => verify_bls_sig(&self.bytes, data, addr),
             SignatureType::Secp256k1 => verify_secp256k1_sig(&self.bytes, data, addr),
-            SignatureType::Delegated => Ok(()),
+            SignatureType::Delegated => verify_delegated_sig(&self.bytes, data, addr),
         }
     }
 
@@ -153,6 +161,52 @@ pub fn verify_bls_sig(
     fvm_shared_latest::crypto::signature::ops::verify_bls_sig(signature, data, &addr.into())
 }
 
+/// Returns `String` error if a delegated (Ethereum-style) signature is invalid.
+///
+/// The signature is a 65-byte recoverable `secp256k1` signature over the
+/// Keccak-256 digest of `data`. The recovered public key must hash to the
+/// Ethereum address embedded in the `f410` address.
+pub fn verify_delegated_sig(
+    signature: &[u8],
+    data: &[u8],
+    addr: &crate::shim::address::Address,
+) -> Result<(), String> {
+    use crate::shim::address::{Address, Payload};
+    use crate::utils::encoding::keccak_256;
+    use fvm_shared_latest::crypto::signature::SECP_SIG_LEN;
+
+    if signature.len() != SECP_SIG_LEN {
+        return Err(format!(
+            "invalid delegated signature length, expected {SECP_SIG_LEN}, got {}",
+            signature.len()
+        ));
+    }
+    let sub_address = match addr.payload() {
+        Payload::Delegated(delegated)
+            if Address::ETHEREUM_ACCOUNT_MANAGER_ACTOR.id().ok() == Some(delegated.namespace()) =>
+        {
+            delegated.subaddress()
+        }
+        _ => return Err(format!("address {addr} is not an f410 address")),
+    };
+
+    let message = libsecp256k1::Message::parse(&keccak_256(data));
+    let (recovery_id, sig) = signature
+        .split_last()
+        .ok_or_else(|| "empty delegated signature".to_string())?;
+    let sig = libsecp256k1::Signature::parse_standard_slice(sig).map_err(|e| e.to_string())?;
+    let recovery_id = libsecp256k1::RecoveryId::parse(*recovery_id).map_err(|e| e.to_string())?;
+    let public_key =
+        libsecp256k1::recover(&message, &sig, &recovery_id).map_err(|e| e.to_string())?;
+    let eth_addr = &keccak_256(&public_key.serialize()[1..])[12..];
+
+    if eth_addr == sub_address {
+        Ok(())
+    } else {
+        Err(format!("delegated signature did not match address {addr}"))
+    }
+}
+
 /// Extracts the raw replica commitment from a CID
 /// assuming that it has the correct hashing function and
 /// serialization types
@@ -0,0 +1,123 @@
+// Copyright 2019-2024 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Window `PoSt` deadline arithmetic, as done by the miner actor.
+
+use fil_actor_interface::miner;
+pub use fil_actor_interface::miner::DeadlineInfo;
+use fil_actors_shared::v10::runtime::Policy;
+
+use crate::shim::clock::ChainEpoch;
+
+/// The deadline of a miner open at `epoch`, going by its `state`. The proving
+/// period start recorded by the miner is normalized to the period `epoch` falls
+/// in, which matters for miners created mid-period, see
+/// [`miner::State::deadline_info`].
+pub fn current_deadline_info(
+    policy: &Policy,
+    state: &miner::State,
+    epoch: ChainEpoch,
+) -> DeadlineInfo {
+    state.deadline_info(policy, epoch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::MemoryDB;
+    use cid::Cid;
+    use fil_actor_miner_state::v10::{new_deadline_info, State as MinerStateV10};
+
+    fn miner_state(policy: &Policy, proving_period_start: ChainEpoch) -> miner::State {
+        miner::State::V10(
+            MinerStateV10::new(
+                policy,
+                &MemoryDB::default(),
+                Cid::default(),
+                proving_period_start,
+                0,
+            )
+            .unwrap(),
+        )
+    }
+
+    #[test]
+    fn current_deadline_info_at_boundary_epochs() {
+        let policy = Policy::mainnet();
+        // (seed, epoch, period start, index, open, close, challenge, fault cutoff)
+        let cases = [
+            (0, 0, 0, 0, 0, 60, -20, -70),
+            (0, 59, 0, 0, 0, 60, -20, -70),
+            (0, 60, 0, 1, 60, 120, 40, -10),
+            (0, 2879, 0, 47, 2820, 2880, 2800, 2750),
+            (0, 2880, 2880, 0, 2880, 2940, 2860, 2810),
+            // Created mid-period, with its first proving period in the future
+            (1000, 500, -1880, 39, 460, 520, 440, 390),
+            (1000, 999, -1880, 47, 940, 1000, 920, 870),
+            (1000, 1000, 1000, 0, 1000, 1060, 980, 930),
+            // Seeds past a proving period, or negative, are normalized
+            (5000, 3000, 2120, 14, 2960, 3020, 2940, 2890),
+            (-100, 0, -100, 1, -40, 20, -60, -110),
+        ];
+        for (seed, epoch, period_start, index, open, close, challenge, fault_cutoff) in cases {
+            let info = current_deadline_info(&policy, &miner_state(&policy, seed), epoch);
+            assert_eq!(
+                (
+                    info.current_epoch,
+                    info.period_start,
+                    info.index,
+                    info.open,
+                    info.close,
+                    info.challenge,
+                    info.fault_cutoff,
+                ),
+                (
+                    epoch,
+                    period_start,
+                    index,
+                    open,
+                    close,
+                    challenge,
+                    fault_cutoff
+                ),
+                "seed {seed}, epoch {epoch}"
+            );
+            assert!(info.is_open());
+        }
+    }
+
+    #[test]
+    fn deadline_info_past_the_last_deadline() {
+        let policy = Policy::mainnet();
+        let info: DeadlineInfo =
+            new_deadline_info(&policy, 2880, policy.wpost_period_deadlines, 3000).into();
+        assert_eq!(
+            (info.open, info.close, info.challenge, info.fault_cutoff),
+            (5760, 5760, 5760, 0)
+        );
+    }
+
+    #[test]
+    fn next_not_elapsed_deadline() {
+        let policy = Policy::mainnet();
+        // (period start, index, epoch, next period start)
+        let cases = [
+            (0, 0, 59, 0),
+            (0, 0, 60, 2880),
+            (0, 0, 2939, 2880),
+            (0, 0, 2940, 5760),
+            (0, 47, 2880, 2880),
+            (0, 47, 100_000, 97_920),
+        ];
+        for (period_start, index, epoch, next_period_start) in cases {
+            let info = DeadlineInfo::from(new_deadline_info(&policy, period_start, index, epoch))
+                .next_not_elapsed();
+            assert_eq!(
+                (info.period_start, info.index),
+                (next_period_start, index),
+                "period start {period_start}, index {index}, epoch {epoch}"
+            );
+            assert!(!info.has_elapsed());
+        }
+    }
+}
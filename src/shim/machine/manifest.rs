@@ -99,6 +99,20 @@ impl BuiltinActorManifest {
     pub fn builtin_actors(&self) -> impl ExactSizeIterator<Item = (BuiltinActor, Cid)> + '_ {
         self.builtin2cid.iter().map(|(k, v)| (*k, *v)) // std::iter::Copied doesn't play well with the tuple here
     }
+    /// The version of the actors, read from the code of the system actor, an
+    /// identity CID over e.g. `fil/13/system`
+    pub fn actors_version(&self) -> anyhow::Result<u64> {
+        let code = self.get_system();
+        ensure!(
+            code.hash().code() == u64::from(cid::multihash::Code::Identity),
+            "system actor code {code} is not an identity CID"
+        );
+        std::str::from_utf8(code.hash().digest())
+            .ok()
+            .and_then(|name| name.split('/').nth(1))
+            .and_then(|version| version.parse().ok())
+            .with_context(|| format!("no actors version in system actor code {code}"))
+    }
 }
 
 // https://github.com/ChainSafe/fil-actor-states/issues/171
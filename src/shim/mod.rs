@@ -5,6 +5,7 @@ pub mod address;
 pub mod bigint;
 pub mod clock;
 pub mod crypto;
+pub mod deadline;
 pub mod deal;
 pub mod econ;
 pub mod error;
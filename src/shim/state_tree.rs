@@ -255,7 +255,9 @@ where
                 };
                 st.for_each(inner)
             }
-            StateTree::V0(_) => bail!("StateTree::for_each not supported on old state trees"),
+            StateTree::V0(st) => {
+                st.for_each(|address, actor_state| f(address, &actor_state.into()))
+            }
         }
     }
 
@@ -70,6 +70,16 @@ where
         Ok(act)
     }
 
+    /// Visits every actor of the tree, with its ID address.
+    pub fn for_each<F>(&self, mut f: F) -> anyhow::Result<()>
+    where
+        F: FnMut(Address, &ActorStateV2) -> anyhow::Result<()>,
+    {
+        self.hamt
+            .for_each(|key, actor| f(Address::from_bytes(key)?, actor))?;
+        Ok(())
+    }
+
     /// Get an ID address from any Address
     pub fn lookup_id(&self, addr: &Address) -> anyhow::Result<Option<Address>> {
         if addr.protocol() == fvm_shared4::address::Protocol::ID {
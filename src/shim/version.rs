@@ -59,6 +59,12 @@ impl NetworkVersion {
     pub const V22: Self = Self(NetworkVersion_latest::new(22));
 }
 
+impl std::fmt::Display for NetworkVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
 impl Deref for NetworkVersion {
     type Target = NetworkVersion_latest;
     fn deref(&self) -> &Self::Target {
@@ -3,15 +3,24 @@
 
 use std::fmt::Debug;
 
+use cid::Cid;
 use thiserror::Error;
 use tokio::task::JoinError;
 
+use crate::shim::address::Address;
+
 /// State manager error
 #[derive(Debug, PartialEq, Error)]
 pub enum Error {
     /// Error originating from state
     #[error("{0}")]
     State(String),
+    /// The actor does not exist in the state tree
+    #[error("actor not found: {0}")]
+    ActorNotFound(Address),
+    /// The message is not on chain
+    #[error("message not found: {0}")]
+    MessageNotFound(Cid),
     /// Other state manager error
     #[error("{0}")]
     Other(String),
@@ -24,7 +33,11 @@ impl From<String> for Error {
 }
 impl From<anyhow::Error> for Error {
     fn from(e: anyhow::Error) -> Self {
-        Error::Other(e.to_string())
+        match e.downcast_ref() {
+            Some(Error::ActorNotFound(address)) => Error::ActorNotFound(*address),
+            Some(Error::MessageNotFound(cid)) => Error::MessageNotFound(*cid),
+            _ => Error::Other(e.to_string()),
+        }
     }
 }
 
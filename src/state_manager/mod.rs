@@ -13,6 +13,7 @@ use crate::beacon::{BeaconEntry, BeaconSchedule};
 use crate::blocks::{Tipset, TipsetKey};
 use crate::chain::{
     index::{ChainIndex, ResolveNullTipset},
+    message_index::MessageIndexEntry,
     ChainStore, HeadChange,
 };
 use crate::chain_sync::SyncConfig;
@@ -36,7 +37,7 @@ use crate::shim::{
     version::NetworkVersion,
 };
 use crate::state_manager::chain_rand::draw_randomness;
-use crate::state_migration::run_state_migrations;
+use crate::state_migration::{run_state_migrations, run_state_pre_migration};
 use ahash::{HashMap, HashMapExt};
 use anyhow::{bail, Context as _};
 use bls_signatures::{PublicKey as BlsPublicKey, Serialize as _};
@@ -63,6 +64,7 @@ use parking_lot::Mutex as SyncMutex;
 use rayon::prelude::ParallelBridge;
 use serde::{Deserialize, Serialize};
 use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::{num::NonZeroUsize, sync::Arc};
 use tokio::sync::{broadcast::error::RecvError, Mutex as TokioMutex, RwLock};
 use tracing::{debug, error, info, instrument, warn};
@@ -319,7 +321,7 @@ where
         let act = state
             .get_actor(addr)
             .map_err(|e| Error::State(e.to_string()))?
-            .ok_or_else(|| Error::State("Miner actor not found".to_string()))?;
+            .ok_or(Error::ActorNotFound(*addr))?;
 
         let ms = miner::State::load(self.blockstore(), act.code, act.state)?;
 
@@ -347,7 +349,7 @@ where
         if let Some(maddr) = addr {
             let m_pow = spas
                 .miner_power(self.blockstore(), &maddr.into())?
-                .ok_or_else(|| Error::State(format!("Miner for address {maddr} not found")))?;
+                .ok_or(Error::ActorNotFound(*maddr))?;
 
             let min_pow = spas.miner_nominal_power_meets_consensus_minimum(
                 &self.chain_config.policy,
@@ -370,7 +372,7 @@ where
     ) -> anyhow::Result<Vec<SectorOnChainInfo>> {
         let actor = self
             .get_actor(addr, *ts.parent_state())?
-            .ok_or_else(|| Error::State("Miner actor not found".to_string()))?;
+            .ok_or(Error::ActorNotFound(*addr))?;
         let state = miner::State::load(self.blockstore(), actor.code, actor.state)?;
 
         state.load_sectors(self.blockstore(), None)
@@ -404,7 +406,16 @@ where
         msg: &Message,
         rand: ChainRand<DB>,
         tipset: &Arc<Tipset>,
+        cancelled: &AtomicBool,
     ) -> Result<ApiInvocResult, Error> {
+        let check_cancelled = || {
+            if cancelled.load(Ordering::Relaxed) {
+                Err(Error::Other("call cancelled".into()))
+            } else {
+                Ok(())
+            }
+        };
+        check_cancelled()?;
         let mut msg = msg.clone();
 
         let state_cid = tipset.parent_state();
@@ -444,6 +455,7 @@ where
         )?;
 
         for m in prior_messsages {
+            check_cancelled()?;
             vm.apply_message(m)?;
         }
 
@@ -455,7 +467,7 @@ where
 
         let from_actor = state
             .get_actor(&msg.from())?
-            .ok_or_else(|| anyhow::anyhow!("actor not found"))?;
+            .ok_or(Error::ActorNotFound(msg.from()))?;
         msg.set_sequence(from_actor.sequence);
 
         // If the fee cap is set to zero, make gas free
@@ -465,6 +477,7 @@ where
         let mut msg = msg.clone();
         msg.gas_limit = IMPLICIT_MESSAGE_GAS_LIMIT as u64;
 
+        check_cancelled()?;
         let (apply_ret, duration) = vm.apply_implicit_message(&msg)?;
 
         Ok(ApiInvocResult {
@@ -479,15 +492,17 @@ where
     }
 
     /// runs the given message and returns its result without any persisted
-    /// changes.
+    /// changes. Setting `cancelled` halts the call before the next message is
+    /// applied; the message being executed runs to completion.
     pub fn call(
         self: &Arc<Self>,
         message: &Message,
         tipset: Option<Arc<Tipset>>,
+        cancelled: &AtomicBool,
     ) -> Result<ApiInvocResult, Error> {
         let ts = tipset.unwrap_or_else(|| self.cs.heaviest_tipset());
         let chain_rand = self.chain_rand(Arc::clone(&ts));
-        self.call_raw(message, chain_rand, &ts)
+        self.call_raw(message, chain_rand, &ts, cancelled)
     }
 
     /// Computes message on the given [Tipset] state, after applying other
@@ -538,7 +553,7 @@ where
             let from_actor = vm
                 .get_actor(&message.from())
                 .map_err(|e| Error::Other(format!("Could not get actor from state: {e}")))?
-                .ok_or_else(|| Error::Other("cant find actor in state tree".to_string()))?;
+                .ok_or(Error::ActorNotFound(message.from()))?;
             message.set_sequence(from_actor.sequence);
 
             vm.apply_message(message)
@@ -557,20 +572,32 @@ where
         self: &Arc<Self>,
         ts: &Arc<Tipset>,
         mcid: Cid,
-    ) -> Result<(Message, ApplyRet), Error> {
+    ) -> Result<ApiInvocResult, Error> {
         const ERROR_MSG: &str = "replay_halt";
 
         // This isn't ideal to have, since the execution is synchronous, but this needs
         // to be the case because the state transition has to be in blocking
         // thread to avoid starving executor
-        let (m_tx, m_rx) = std::sync::mpsc::channel();
         let (r_tx, r_rx) = std::sync::mpsc::channel();
+        // Halts the state transition at the next message if this future is
+        // dropped, e.g. when the RPC call times out
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let _cancel_on_drop = scopeguard::guard(cancelled.clone(), |cancelled| {
+            cancelled.store(true, Ordering::Relaxed)
+        });
         let callback = move |ctx: &MessageCallbackCtx| {
+            if cancelled.load(Ordering::Relaxed) {
+                anyhow::bail!("replay cancelled");
+            }
             match ctx.at {
                 CalledAt::Applied | CalledAt::Reward => {
                     if ctx.cid == mcid {
-                        m_tx.send(ctx.message.message().clone())?;
-                        r_tx.send(ctx.apply_ret.clone())?;
+                        r_tx.send(invoc_result(
+                            ctx.message.message(),
+                            ctx.cid,
+                            ctx.apply_ret,
+                            ctx.duration,
+                        ))?;
                         anyhow::bail!(ERROR_MSG);
                     }
                     Ok(())
@@ -579,7 +606,7 @@ where
             }
         };
         let result = self
-            .compute_tipset_state(Arc::clone(ts), Some(callback), VMTrace::NotTraced)
+            .compute_tipset_state(Arc::clone(ts), Some(callback), VMTrace::Traced)
             .await;
 
         if let Err(error_message) = result {
@@ -591,13 +618,98 @@ where
         }
 
         // Use try_recv here assuming callback execution is synchronous
-        let out_mes = m_rx
-            .try_recv()
-            .map_err(|err| Error::Other(format!("given message not found in tipset: {err}")))?;
-        let out_ret = r_rx
-            .try_recv()
-            .map_err(|err| Error::Other(format!("message did not have a return: {err}")))?;
-        Ok((out_mes, out_ret))
+        r_rx.try_recv().map_err(|_| Error::MessageNotFound(mcid))
+    }
+
+    /// Computes the state of `tipset`, runs the state migrations up to `epoch`,
+    /// and applies `messages` at `epoch` on top of it. Returns the resulting
+    /// state root, with the traces of the messages of the tipset, followed by
+    /// those of `messages`.
+    pub async fn compute_state(
+        self: &Arc<Self>,
+        epoch: ChainEpoch,
+        messages: Vec<Message>,
+        tipset: Arc<Tipset>,
+    ) -> anyhow::Result<(Cid, Vec<ApiInvocResult>)> {
+        anyhow::ensure!(
+            epoch >= tipset.epoch(),
+            "cannot compute the state at epoch {epoch} on top of the tipset at epoch {}",
+            tipset.epoch()
+        );
+        let this = Arc::clone(self);
+        tokio::task::spawn_blocking(move || this.compute_state_blocking(epoch, messages, tipset))
+            .await?
+    }
+
+    fn compute_state_blocking(
+        self: &Arc<Self>,
+        epoch: ChainEpoch,
+        messages: Vec<Message>,
+        tipset: Arc<Tipset>,
+    ) -> anyhow::Result<(Cid, Vec<ApiInvocResult>)> {
+        let (trace_tx, trace_rx) = std::sync::mpsc::channel();
+        let callback = move |ctx: &MessageCallbackCtx| {
+            trace_tx.send(invoc_result(
+                ctx.message.message(),
+                ctx.cid,
+                ctx.apply_ret,
+                ctx.duration,
+            ))?;
+            Ok(())
+        };
+        let (mut state_root, _) = self.compute_tipset_state_blocking(
+            Arc::clone(&tipset),
+            Some(callback),
+            VMTrace::Traced,
+        )?;
+        let mut traces = trace_rx.try_iter().collect::<Vec<_>>();
+
+        for epoch_i in tipset.epoch()..epoch {
+            if let Some(new_state) = run_state_migrations(
+                epoch_i,
+                self.chain_config(),
+                &self.blockstore_owned(),
+                &state_root,
+            )? {
+                state_root = new_state;
+            }
+        }
+
+        if !messages.is_empty() {
+            let genesis_info = GenesisInfo::from_chain_config(self.chain_config());
+            // FVM requires a stack size of 64MiB. The alternative is to use `ThreadedExecutor` from
+            // FVM, but that introduces some constraints, and possible deadlocks.
+            state_root = stacker::grow(64 << 20, || -> anyhow::Result<Cid> {
+                let mut vm = VM::new(
+                    ExecutionContext {
+                        heaviest_tipset: Arc::clone(&tipset),
+                        state_tree_root: state_root,
+                        epoch,
+                        rand: Box::new(self.chain_rand(Arc::clone(&tipset))),
+                        base_fee: tipset.block_headers().first().parent_base_fee.clone(),
+                        circ_supply: genesis_info.get_vm_circulating_supply(
+                            epoch,
+                            &self.blockstore_owned(),
+                            &state_root,
+                        )?,
+                        chain_config: self.chain_config().clone(),
+                        chain_index: Arc::clone(&self.chain_store().chain_index),
+                        timestamp: tipset.min_timestamp(),
+                    },
+                    &self.engine,
+                    VMTrace::Traced,
+                )?;
+                for message in messages {
+                    let cid = message.cid()?;
+                    let (apply_ret, duration) =
+                        vm.apply_message(&ChainMessage::Unsigned(message.clone()))?;
+                    traces.push(invoc_result(&message, cid, &apply_ret, duration));
+                }
+                vm.flush()
+            })?;
+        }
+
+        Ok((state_root, traces))
     }
 
     /// Checks the eligibility of the miner. This is used in the validation that
@@ -771,6 +883,7 @@ where
         mut current: Arc<Tipset>,
         message: &ChainMessage,
         look_back_limit: Option<i64>,
+        allow_replaced: bool,
     ) -> Result<Option<(Arc<Tipset>, Receipt)>, Error> {
         let message_from_address = message.from();
         let message_sequence = message.sequence();
@@ -803,7 +916,7 @@ where
                     && parent_actor_state.as_ref().unwrap().sequence <= message_sequence)
             {
                 let receipt = self
-                    .tipset_executed_message(current.as_ref(), message, true)?
+                    .tipset_executed_message(current.as_ref(), message, allow_replaced)?
                     .context("Failed to get receipt with tipset_executed_message")?;
                 return Ok(Some((current, receipt)));
             }
@@ -824,8 +937,57 @@ where
         current: Arc<Tipset>,
         message: &ChainMessage,
         look_back_limit: Option<i64>,
+        allow_replaced: bool,
     ) -> Result<Option<(Arc<Tipset>, Receipt)>, Error> {
-        self.check_search(current, message, look_back_limit)
+        if let Some(found) = self.search_message_index(&current, message, look_back_limit) {
+            return Ok(Some(found));
+        }
+        self.check_search(current, message, look_back_limit, allow_replaced)
+    }
+
+    /// Looks the message up in the message index, returning the tipset which
+    /// executed it if that's an ancestor of `current` within the look-back
+    /// limit. Any failure, e.g. the receipts having been garbage collected, is
+    /// treated as a miss, so that the caller falls back to scanning the chain.
+    fn search_message_index(
+        &self,
+        current: &Arc<Tipset>,
+        message: &ChainMessage,
+        look_back_limit: Option<i64>,
+    ) -> Option<(Arc<Tipset>, Receipt)> {
+        let lookup = || -> anyhow::Result<Option<(Arc<Tipset>, Receipt)>> {
+            let Some(entry) = MessageIndexEntry::load(self.cs.indices().as_ref(), &message.cid()?)?
+            else {
+                return Ok(None);
+            };
+            if entry.epoch > current.epoch() || entry.epoch <= look_back_limit.unwrap_or_default() {
+                return Ok(None);
+            }
+            // Entries are replaced when a message is executed again on another
+            // fork, but a fork which dropped it may still be re-orged in. That
+            // can't happen beyond finality, which bounds the walk back.
+            if current.epoch() - entry.epoch < self.chain_config.policy.chain_finality {
+                let canonical = self.cs.chain_index.tipset_by_height(
+                    entry.epoch,
+                    current.clone(),
+                    ResolveNullTipset::TakeOlder,
+                )?;
+                if canonical.key() != &entry.tipset {
+                    return Ok(None);
+                }
+            }
+            let tipset = self.cs.chain_index.load_required_tipset(&entry.tipset)?;
+            let receipt = crate::chain::get_parent_receipt(
+                self.blockstore(),
+                tipset.block_headers().first(),
+                entry.receipt_index as usize,
+            )?;
+            Ok(receipt.map(|receipt| (tipset, receipt)))
+        };
+        lookup().unwrap_or_else(|e| {
+            debug!("Message index lookup failed, scanning the chain instead: {e}");
+            None
+        })
     }
 
     /// Returns a message receipt from a given tipset and message CID.
@@ -837,15 +999,44 @@ where
             return Ok(receipt);
         }
 
-        let maybe_tuple = self.search_back_for_message(tipset, &m, None)?;
-        let message_receipt = maybe_tuple
-            .ok_or_else(|| {
-                Error::Other("Could not get receipt from search back message".to_string())
-            })?
-            .1;
+        let maybe_tuple = self.search_back_for_message(tipset, &m, None, true)?;
+        let message_receipt = maybe_tuple.ok_or(Error::MessageNotFound(msg))?.1;
         Ok(message_receipt)
     }
 
+    /// Pre-migrates the state for the upgrades as the head of the chain gets
+    /// within [`SyncConfig::pre_migration_epochs`] of them, so that the node
+    /// doesn't stall at the upgrade epochs.
+    pub async fn pre_migration_loop(self: Arc<Self>) -> anyhow::Result<()> {
+        let lookahead = self.sync_config.pre_migration_epochs;
+        if lookahead <= 0 {
+            return Ok(());
+        }
+        let mut head_changes = self.cs.publisher().subscribe();
+        loop {
+            let head = match head_changes.recv().await {
+                Ok(HeadChange::Apply(head)) => head,
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return Ok(()),
+            };
+            let this = Arc::clone(&self);
+            let pre_migration = tokio::task::spawn_blocking(move || {
+                run_state_pre_migration(
+                    head.epoch(),
+                    lookahead,
+                    this.chain_config(),
+                    &this.blockstore_owned(),
+                    head.parent_state(),
+                )
+            });
+            match pre_migration.await {
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => warn!("Failed to pre-migrate the state: {e}"),
+                Err(e) => warn!("State pre-migration panicked: {e}"),
+            }
+        }
+    }
+
     /// `WaitForMessage` blocks until a message appears on chain. It looks
     /// backwards in the chain to see if this has already happened. It
     /// guarantees that the message has been on chain for at least
@@ -875,7 +1066,7 @@ where
         let height_of_head = current_tipset.epoch();
         let task = tokio::task::spawn(async move {
             let back_tuple =
-                sm_cloned.search_back_for_message(current_tipset, &message_for_task, None)?;
+                sm_cloned.search_back_for_message(current_tipset, &message_for_task, None, true)?;
             sender
                 .send(())
                 .map_err(|e| Error::Other(format!("Could not send to channel {e:?}")))?;
@@ -972,16 +1163,18 @@ where
         from: Option<Arc<Tipset>>,
         msg_cid: Cid,
         look_back_limit: Option<i64>,
+        allow_replaced: bool,
     ) -> Result<Option<(Arc<Tipset>, Receipt)>, Error> {
         let from = from.unwrap_or_else(|| self.chain_store().heaviest_tipset());
         let message = crate::chain::get_chain_message(self.blockstore(), &msg_cid)
             .map_err(|err| Error::Other(format!("failed to load message {err:}")))?;
         let current_tipset = self.cs.heaviest_tipset();
-        let maybe_message_reciept = self.tipset_executed_message(&from, &message, true)?;
+        let maybe_message_reciept =
+            self.tipset_executed_message(&from, &message, allow_replaced)?;
         if let Some(r) = maybe_message_reciept {
             Ok(Some((from, r)))
         } else {
-            self.search_back_for_message(current_tipset, &message, look_back_limit)
+            self.search_back_for_message(current_tipset, &message, look_back_limit, allow_replaced)
         }
     }
 
@@ -1059,7 +1252,7 @@ where
     ) -> Result<MinerInfo, Error> {
         let actor = self
             .get_actor(addr, *ts.parent_state())?
-            .ok_or_else(|| Error::State("Miner actor not found".to_string()))?;
+            .ok_or(Error::ActorNotFound(*addr))?;
         let state = miner::State::load(self.blockstore(), actor.code, actor.state)?;
 
         Ok(state.info(self.blockstore())?)
@@ -1081,32 +1274,54 @@ where
         self.all_partition_sectors(addr, ts, |partition| partition.recovering_sectors().clone())
     }
 
+    /// Retrieves miner faults, by deadline.
+    pub fn miner_faults_by_deadline(
+        self: &Arc<Self>,
+        addr: &Address,
+        ts: &Arc<Tipset>,
+    ) -> Result<Vec<BitField>, Error> {
+        self.deadline_partition_sectors(addr, ts, |partition| partition.faulty_sectors().clone())
+    }
+
     fn all_partition_sectors(
         self: &Arc<Self>,
         addr: &Address,
         ts: &Arc<Tipset>,
         get_sector: impl Fn(Partition<'_>) -> BitField,
     ) -> Result<BitField, Error> {
+        let deadlines = self.deadline_partition_sectors(addr, ts, get_sector)?;
+        Ok(BitField::union(deadlines.iter()))
+    }
+
+    fn deadline_partition_sectors(
+        self: &Arc<Self>,
+        addr: &Address,
+        ts: &Arc<Tipset>,
+        get_sector: impl Fn(Partition<'_>) -> BitField,
+    ) -> Result<Vec<BitField>, Error> {
         let actor = self
             .get_actor(addr, *ts.parent_state())?
-            .ok_or_else(|| Error::State("Miner actor not found".to_string()))?;
+            .ok_or(Error::ActorNotFound(*addr))?;
 
         let state = miner::State::load(self.blockstore(), actor.code, actor.state)?;
 
-        let mut partitions = Vec::new();
+        let mut deadlines = Vec::new();
 
         state.for_each_deadline(
             &self.chain_config.policy,
             self.blockstore(),
             |_, deadline| {
+                let mut partitions = Vec::new();
                 deadline.for_each(self.blockstore(), |_, partition| {
                     partitions.push(get_sector(partition));
                     Ok(())
-                })
+                })?;
+                deadlines.push(BitField::union(partitions.iter()));
+                Ok(())
             },
         )?;
 
-        Ok(BitField::union(partitions.iter()))
+        Ok(deadlines)
     }
 
     /// Retrieves miner power.
@@ -1191,9 +1406,13 @@ where
             epoch,
         )?;
 
-        let actor = self
-            .get_actor(&addr, *tipset.parent_state())?
-            .context("miner actor does not exist")?;
+        // The miner is loaded from the lookback state, like its sectors and
+        // power. Miners created since have nothing to mine with.
+        let Some(actor) = self.get_actor(&addr, lb_state_root)? else {
+            self.get_actor(&addr, *tipset.parent_state())?
+                .context("miner actor does not exist")?;
+            return Ok(None);
+        };
 
         let miner_state = miner::State::load(self.blockstore(), actor.code, actor.state)?;
 
@@ -1330,7 +1549,9 @@ where
         addr: &Address,
         ts: &Arc<Tipset>,
     ) -> anyhow::Result<Option<DataCap>> {
-        let id = self.lookup_id(addr, ts)?.context("actor not found")?;
+        let id = self
+            .lookup_id(addr, ts)?
+            .ok_or(Error::ActorNotFound(*addr))?;
         let network_version = self.get_network_version(ts.epoch());
 
         // This is a copy of Lotus code, we need to treat all the actors below version 9
@@ -1340,7 +1561,7 @@ where
             let act = self
                 .get_actor(&Address::VERIFIED_REGISTRY_ACTOR, *ts.parent_state())
                 .map_err(|e| Error::State(e.to_string()))?
-                .ok_or_else(|| Error::State("Miner actor not found".to_string()))?;
+                .ok_or(Error::ActorNotFound(Address::VERIFIED_REGISTRY_ACTOR))?;
             let state = verifreg::State::load(self.blockstore(), act.code, act.state)?;
             return state.verified_client_data_cap(self.blockstore(), id.into());
         }
@@ -1348,7 +1569,7 @@ where
         let act = self
             .get_actor(&Address::DATACAP_TOKEN_ACTOR, *ts.parent_state())
             .map_err(|e| Error::State(e.to_string()))?
-            .ok_or_else(|| Error::State("Miner actor not found".to_string()))?;
+            .ok_or(Error::ActorNotFound(Address::DATACAP_TOKEN_ACTOR))?;
 
         let state = datacap::State::load(self.blockstore(), act.code, act.state)?;
 
@@ -1394,6 +1615,24 @@ where
     }
 }
 
+/// Gathers the result of the application of `message` for its trace.
+fn invoc_result(
+    message: &Message,
+    cid: Cid,
+    apply_ret: &ApplyRet,
+    duration: std::time::Duration,
+) -> ApiInvocResult {
+    ApiInvocResult {
+        msg: message.clone(),
+        msg_cid: cid,
+        msg_rct: Some(apply_ret.msg_receipt()),
+        error: apply_ret.failure_info().unwrap_or_default(),
+        duration: duration.as_nanos().clamp(0, u64::MAX as u128) as u64,
+        gas_cost: MessageGasCost::new(message, apply_ret),
+        execution_trace: structured::parse_events(apply_ret.exec_trace()).unwrap_or_default(),
+    }
+}
+
 pub fn validate_tipsets<DB, T>(
     genesis_timestamp: u64,
     chain_index: Arc<ChainIndex<Arc<DB>>>,
@@ -6,7 +6,12 @@
 
 use std::{num::NonZeroUsize, sync::Arc};
 
-use crate::shim::{address::Address, clock::ChainEpoch, econ::TokenAmount, state_tree::StateTree};
+use crate::networks::{ChainConfig, Height};
+use crate::shim::{
+    address::Address, clock::ChainEpoch, econ::TokenAmount, machine::BuiltinActorManifest,
+    state_tree::StateTree,
+};
+use anyhow::Context as _;
 use cid::Cid;
 use fvm_ipld_blockstore::Blockstore;
 
@@ -56,6 +61,25 @@ impl MigrationCache {
     }
 }
 
+/// Loads the manifest of the actor bundle of the `height` upgrade, which must
+/// be in the blockstore.
+pub(in crate::state_migration) fn load_new_manifest(
+    chain_config: &ChainConfig,
+    blockstore: &impl Blockstore,
+    height: Height,
+) -> anyhow::Result<BuiltinActorManifest> {
+    let new_manifest_cid = chain_config
+        .height_infos
+        .get(&height)
+        .with_context(|| format!("no height info for the {height} upgrade"))?
+        .bundle
+        .with_context(|| format!("no bundle for the {height} upgrade"))?;
+    blockstore.get(&new_manifest_cid)?.with_context(|| {
+        format!("manifest for the {height} upgrade not found in blockstore: {new_manifest_cid}")
+    })?;
+    BuiltinActorManifest::load_manifest(blockstore, &new_manifest_cid)
+}
+
 #[allow(dead_code)] // future migrations might need the fields.
 pub(in crate::state_migration) struct ActorMigrationInput {
     /// Actor's address
@@ -1,13 +1,13 @@
 // Copyright 2019-2024 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use std::num::NonZeroUsize;
 use std::sync::atomic::AtomicU64;
 use std::sync::Arc;
 
 use crate::cid_collections::CidHashMap;
 use crate::shim::{clock::ChainEpoch, state_tree::StateTree};
 use crate::state_migration::common::MigrationCache;
+use crate::state_migration::MigrationProgress;
 use cid::Cid;
 use fvm_ipld_blockstore::Blockstore;
 use parking_lot::Mutex;
@@ -73,13 +73,23 @@ impl<BS: Blockstore + Send + Sync> StateMigration<BS> {
         prior_epoch: ChainEpoch,
         actors_in: StateTree<BS>,
         mut actors_out: StateTree<BS>,
+        cache: &MigrationCache,
+        progress: &MigrationProgress,
     ) -> anyhow::Result<Cid> {
         // Checks if the migration specification is correct
         if let Some(verifier) = &self.verifier {
             verifier.verify_migration(store, &self.migrations, &actors_in)?;
         }
 
-        let cache = MigrationCache::new(NonZeroUsize::new(10_000).expect("infallible"));
+        // Counting the actors is as cheap as iterating over them for the
+        // deferred migrations below
+        let mut total_actors = 0;
+        actors_in.for_each(|_, _| {
+            total_actors += 1;
+            Ok(())
+        })?;
+        progress.set_total_actors(total_actors);
+
         let num_threads = std::env::var("FOREST_STATE_MIGRATION_THREADS")
             .ok()
             .and_then(|s| s.parse().ok())
@@ -145,6 +155,7 @@ impl<BS: Blockstore + Send + Sync> StateMigration<BS> {
             });
 
             while let Ok(job_output) = job_rx.recv() {
+                progress.add_migrated_actor();
                 if let Some(MigrationJobOutput {
                     address,
                     actor_state,
@@ -181,6 +192,7 @@ impl<BS: Blockstore + Send + Sync> StateMigration<BS> {
             if !migrator.is_deferred() {
                 return Ok(());
             }
+            progress.add_migrated_actor();
 
             let job = MigrationJob {
                 address,
@@ -219,3 +231,132 @@ impl<BS: Blockstore + Send + Sync> StateMigration<BS> {
         actors_out.flush()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroUsize;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::db::MemoryDB;
+    use crate::networks::Height;
+    use crate::shim::{
+        address::Address,
+        econ::TokenAmount,
+        state_tree::{ActorState, StateTreeVersion},
+    };
+    use crate::state_migration::{
+        common::{ActorMigration, ActorMigrationInput, ActorMigrationOutput},
+        MigrationStatus,
+    };
+    use crate::utils::db::CborStoreExt as _;
+    use cid::multihash::{Code::Identity, MultihashDigest as _};
+
+    /// Wraps the states of the actors, caching them by the wrapped state as the
+    /// miner migrations cache their sectors, and records the progress of the
+    /// migration it is part of.
+    struct WrappingMigrator {
+        new_code: Cid,
+        progress: Arc<MigrationProgress>,
+        runs: AtomicUsize,
+        seen: Mutex<Vec<MigrationStatus>>,
+    }
+
+    impl<BS: Blockstore> ActorMigration<BS> for WrappingMigrator {
+        fn migrate_state(
+            &self,
+            store: &BS,
+            input: ActorMigrationInput,
+        ) -> anyhow::Result<Option<ActorMigrationOutput>> {
+            self.seen.lock().extend(self.progress.status());
+            let new_head =
+                input
+                    .cache
+                    .get_or_insert_with(format!("head-{}", input.head), || {
+                        self.runs.fetch_add(1, Ordering::Relaxed);
+                        store.put_cbor_default(&(input.head, "migrated"))
+                    })?;
+            Ok(Some(ActorMigrationOutput {
+                new_code_cid: self.new_code,
+                new_head,
+            }))
+        }
+    }
+
+    fn code(name: &str) -> Cid {
+        Cid::new_v1(
+            fvm_ipld_encoding::IPLD_RAW,
+            Identity.digest(name.as_bytes()),
+        )
+    }
+
+    fn state_of_actors(store: &Arc<MemoryDB>, heads: &[(u64, u64)]) -> Cid {
+        let mut tree = StateTree::new(store.clone(), StateTreeVersion::V5).unwrap();
+        for (id, head) in heads {
+            let head = store.put_cbor_default(head).unwrap();
+            let actor = ActorState::new(code("old"), head, TokenAmount::default(), 0, None);
+            tree.set_actor(&Address::new_id(*id), actor).unwrap();
+        }
+        tree.flush().unwrap()
+    }
+
+    fn migrate(
+        store: &Arc<MemoryDB>,
+        migrator: &Arc<WrappingMigrator>,
+        state: &Cid,
+        cache: &MigrationCache,
+    ) -> Cid {
+        let mut migration = StateMigration::<MemoryDB>::new(None);
+        migration.add_migrator(code("old"), migrator.clone());
+        let actors_in = StateTree::new_from_root(store.clone(), state).unwrap();
+        let actors_out = StateTree::new(store.clone(), StateTreeVersion::V5).unwrap();
+        migrator.runs.store(0, Ordering::Relaxed);
+        migrator.progress.start(Height::Dragon, 0, false);
+        migration
+            .migrate_state_tree(store, 0, actors_in, actors_out, cache, &migrator.progress)
+            .unwrap()
+    }
+
+    #[test]
+    fn pre_migration_matches_direct_migration() {
+        let store = Arc::new(MemoryDB::default());
+        let migrator = Arc::new(WrappingMigrator {
+            new_code: code("new"),
+            progress: Default::default(),
+            runs: AtomicUsize::new(0),
+            seen: Default::default(),
+        });
+        let cache_size = NonZeroUsize::new(100).unwrap();
+        let before_upgrade = (100..110).map(|id| (id, id)).collect::<Vec<_>>();
+        // Two actors change and one is created before the upgrade
+        let mut at_upgrade = before_upgrade.clone();
+        at_upgrade[0].1 = 1000;
+        at_upgrade[1].1 = 1001;
+        at_upgrade.push((110, 110));
+        let before_upgrade = state_of_actors(&store, &before_upgrade);
+        let at_upgrade = state_of_actors(&store, &at_upgrade);
+
+        let direct = migrate(
+            &store,
+            &migrator,
+            &at_upgrade,
+            &MigrationCache::new(cache_size),
+        );
+        assert_eq!(migrator.runs.load(Ordering::Relaxed), 11);
+        let seen = std::mem::take(&mut *migrator.seen.lock());
+        assert_eq!(seen.len(), 11);
+        assert!(seen
+            .iter()
+            .all(|status| status.total_actors == 11 && status.migrated_actors < 11));
+        let status = migrator.progress.status().unwrap();
+        assert_eq!((status.migrated_actors, status.total_actors), (11, 11));
+        assert_eq!(status.percent_complete, 100.0);
+
+        let cache = MigrationCache::new(cache_size);
+        migrate(&store, &migrator, &before_upgrade, &cache);
+        let pre_migrated = migrate(&store, &migrator, &at_upgrade, &cache);
+        assert_eq!(pre_migrated, direct);
+        // Only the changed and created actors are migrated again
+        assert_eq!(migrator.runs.load(Ordering::Relaxed), 3);
+    }
+}
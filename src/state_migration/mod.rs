@@ -1,6 +1,7 @@
 // Copyright 2019-2024 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use std::num::NonZeroUsize;
 use std::sync::{
     atomic::{self, AtomicBool},
     Arc,
@@ -10,9 +11,14 @@ use crate::networks::{ChainConfig, Height, NetworkChain};
 use crate::shim::clock::ChainEpoch;
 use crate::shim::state_tree::StateRoot;
 use crate::utils::misc::reveal_three_trees;
+use ahash::HashMap;
 use cid::Cid;
+use common::MigrationCache;
 use fvm_ipld_blockstore::Blockstore;
 use fvm_ipld_encoding::CborStore;
+use nonzero_ext::nonzero;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
 
 pub(in crate::state_migration) mod common;
 mod nv17;
@@ -22,21 +28,40 @@ mod nv21;
 mod nv21fix;
 mod nv21fix2;
 mod nv22;
+mod progress;
 mod type_migrations;
 
-type RunMigration<DB> = fn(&ChainConfig, &Arc<DB>, &Cid, ChainEpoch) -> anyhow::Result<Cid>;
+pub use progress::{migration_progress, MigrationProgress, MigrationStatus};
 
-/// Run state migrations
-pub fn run_state_migrations<DB>(
-    epoch: ChainEpoch,
-    chain_config: &Arc<ChainConfig>,
-    db: &Arc<DB>,
-    parent_state: &Cid,
-) -> anyhow::Result<Option<Cid>>
+type RunMigration<DB> = fn(
+    &ChainConfig,
+    &Arc<DB>,
+    &Cid,
+    ChainEpoch,
+    &MigrationCache,
+    &MigrationProgress,
+) -> anyhow::Result<Cid>;
+
+/// Size of the cache of a migration run at the upgrade epoch.
+const MIGRATION_CACHE_SIZE: NonZeroUsize = nonzero!(10_000_usize);
+/// Size of the cache of a pre-migration, large enough to hold what is migrated
+/// for all the miners of mainnet.
+const PRE_MIGRATION_CACHE_SIZE: NonZeroUsize = nonzero!(1_000_000_usize);
+/// A pre-migration isn't started this close to the upgrade, as it would not
+/// complete in time to help.
+const PRE_MIGRATION_DONT_START_WITHIN: ChainEpoch = 15;
+
+/// Caches of the pre-migrations, by the upgrade they are for. A running
+/// pre-migration holds the lock of its cache, so that the migration at the
+/// upgrade epoch waits for it to complete.
+static PRE_MIGRATIONS: Lazy<Mutex<HashMap<Height, Arc<Mutex<MigrationCache>>>>> =
+    Lazy::new(Default::default);
+
+fn migrations<DB>(chain_config: &ChainConfig) -> Vec<(Height, RunMigration<DB>)>
 where
     DB: Blockstore + Send + Sync,
 {
-    let mappings: Vec<(_, RunMigration<DB>)> = match chain_config.network {
+    match chain_config.network {
         NetworkChain::Mainnet => {
             vec![
                 (Height::Shark, nv17::run_migration::<DB>),
@@ -69,7 +94,20 @@ where
                 (Height::Dragon, nv22::run_migration::<DB>),
             ]
         }
-    };
+    }
+}
+
+/// Run state migrations
+pub fn run_state_migrations<DB>(
+    epoch: ChainEpoch,
+    chain_config: &Arc<ChainConfig>,
+    db: &Arc<DB>,
+    parent_state: &Cid,
+) -> anyhow::Result<Option<Cid>>
+where
+    DB: Blockstore + Send + Sync,
+{
+    let mappings = migrations::<DB>(chain_config);
 
     // Make sure bundle is defined.
     static BUNDLE_CHECKED: AtomicBool = AtomicBool::new(false);
@@ -91,8 +129,23 @@ where
     for (height, migrate) in mappings {
         if epoch == chain_config.epoch(height) {
             tracing::info!("Running {height} migration at epoch {epoch}");
+            // Waits for the pre-migration of the upgrade, if it is running. The
+            // garbage collector keeps what it migrated for longer than the
+            // pre-migrations run ahead of the upgrades.
+            let pre_migration = PRE_MIGRATIONS.lock().remove(&height);
+            let cache = match pre_migration {
+                Some(cache) => {
+                    tracing::info!("Reusing the {height} pre-migration");
+                    let cache = cache.lock().clone();
+                    cache
+                }
+                None => MigrationCache::new(MIGRATION_CACHE_SIZE),
+            };
+            let progress = migration_progress();
+            progress.start(height, epoch, false);
+            let _finished = scopeguard::guard((), |_| progress.finish());
             let start_time = std::time::Instant::now();
-            let new_state = migrate(chain_config, db, parent_state, epoch)?;
+            let new_state = migrate(chain_config, db, parent_state, epoch, &cache, progress)?;
             let elapsed = start_time.elapsed().as_secs_f32();
             // `new_state_actors` is the Go state migration output, log for comparision
             let new_state_actors = db
@@ -115,5 +168,51 @@ where
     Ok(None)
 }
 
+/// Migrates `state`, the parent state of the tipset at `epoch`, for the next
+/// upgrade, if it is less than `lookahead` epochs away and hasn't been
+/// pre-migrated yet. The migrated state is discarded, but what the migration
+/// caches lets the migration at the upgrade epoch only migrate what changed
+/// since, as Lotus does. Returns the upgrade that was pre-migrated, if any.
+pub fn run_state_pre_migration<DB>(
+    epoch: ChainEpoch,
+    lookahead: ChainEpoch,
+    chain_config: &ChainConfig,
+    db: &Arc<DB>,
+    state: &Cid,
+) -> anyhow::Result<Option<Height>>
+where
+    DB: Blockstore + Send + Sync,
+{
+    let Some((height, migrate)) = migrations::<DB>(chain_config)
+        .into_iter()
+        .find(|(height, _)| {
+            let epochs_left = chain_config.epoch(*height) - epoch;
+            epochs_left > PRE_MIGRATION_DONT_START_WITHIN && epochs_left <= lookahead
+        })
+    else {
+        return Ok(None);
+    };
+    let mut pre_migrations = PRE_MIGRATIONS.lock();
+    if pre_migrations.contains_key(&height) {
+        return Ok(None);
+    }
+    let entry = Arc::new(Mutex::new(MigrationCache::new(PRE_MIGRATION_CACHE_SIZE)));
+    pre_migrations.insert(height, entry.clone());
+    let cache = entry.lock();
+    drop(pre_migrations);
+
+    tracing::info!("Pre-migrating the state for {height} at epoch {epoch}");
+    let progress = migration_progress();
+    progress.start(height, epoch, true);
+    let _finished = scopeguard::guard((), |_| progress.finish());
+    let start_time = std::time::Instant::now();
+    migrate(chain_config, db, state, epoch, &cache, progress)?;
+    tracing::info!(
+        "Pre-migrated the state for {height} at epoch {epoch}. Took: {}s.",
+        start_time.elapsed().as_secs_f32()
+    );
+    Ok(Some(height))
+}
+
 #[cfg(test)]
 mod tests;
@@ -17,8 +17,9 @@ use fvm_ipld_encoding::CborStore as _;
 
 use super::super::common::{
     migrators::{nil_migrator, DeferredMigrator},
-    StateMigration,
+    MigrationCache, StateMigration,
 };
+use super::super::MigrationProgress;
 use super::{
     datacap, miner, system, util::get_pending_verified_deals_and_total_size, verifier::Verifier,
     verifreg_market::VerifregMarketPostMigrator, SystemStateOld,
@@ -134,6 +135,8 @@ pub fn run_migration<DB>(
     blockstore: &Arc<DB>,
     state: &Cid,
     epoch: ChainEpoch,
+    cache: &MigrationCache,
+    progress: &MigrationProgress,
 ) -> anyhow::Result<Cid>
 where
     DB: Blockstore + Send + Sync,
@@ -171,7 +174,8 @@ where
 
     let actors_out = StateTree::new(blockstore.clone(), StateTreeVersion::V4)?;
 
-    let new_state = migration.migrate_state_tree(blockstore, epoch, actors_in, actors_out)?;
+    let new_state =
+        migration.migrate_state_tree(blockstore, epoch, actors_in, actors_out, cache, progress)?;
 
     Ok(new_state)
 }
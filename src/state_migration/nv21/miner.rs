@@ -300,6 +300,7 @@ mod tests {
         machine::{BuiltinActor, BuiltinActorManifest},
         state_tree::{ActorState, StateTree, StateTreeVersion},
     };
+    use crate::state_migration::{MigrationProgress, MIGRATION_CACHE_SIZE};
     use cid::multihash::MultihashDigest;
     use fvm_ipld_encoding::IPLD_RAW;
     use fvm_shared2::bigint::Zero;
@@ -367,11 +368,25 @@ mod tests {
         if let Some(entry) = chain_config.height_infos.get_mut(&Height::Watermelon) {
             entry.bundle = Some(new_manifest_cid);
         }
-        let new_state_cid =
-            super::super::run_migration(&chain_config, &store, &tree_root, 200).unwrap();
+        let new_state_cid = super::super::run_migration(
+            &chain_config,
+            &store,
+            &tree_root,
+            200,
+            &MigrationCache::new(MIGRATION_CACHE_SIZE),
+            &MigrationProgress::default(),
+        )
+        .unwrap();
 
-        let new_state_cid2 =
-            super::super::run_migration(&chain_config, &store, &tree_root, 200).unwrap();
+        let new_state_cid2 = super::super::run_migration(
+            &chain_config,
+            &store,
+            &tree_root,
+            200,
+            &MigrationCache::new(MIGRATION_CACHE_SIZE),
+            &MigrationProgress::default(),
+        )
+        .unwrap();
 
         assert_eq!(new_state_cid, new_state_cid2);
 
@@ -404,6 +419,71 @@ mod tests {
             .unwrap();
     }
 
+    #[test]
+    fn pre_migrated_miners_match_direct_migration() {
+        let store = Arc::new(crate::db::MemoryDB::default());
+        let (mut state_tree, manifest_old) = make_input_tree(&store);
+        let addr = Address::new_id(10000);
+        let miner_cid_old = manifest_old.get(BuiltinActor::Miner).unwrap();
+        let mut sectors =
+            ArrayOld::<fil_actor_miner_state::v11::SectorOnChainInfo, _>::new_with_bit_width(
+                &store,
+                fil_actor_miner_state::v11::SECTORS_AMT_BITWIDTH,
+            );
+        let mut set_sectors = |sectors: &mut ArrayOld<_, _>| {
+            let mut miner_state = make_base_miner_state(&store, 10000, 10100);
+            miner_state.sectors = sectors.flush().unwrap();
+            let miner_state_cid = store.put_cbor_default(&miner_state).unwrap();
+            let miner = ActorState::new(miner_cid_old, miner_state_cid, Zero::zero(), 0, None);
+            state_tree.set_actor(&addr, miner).unwrap();
+            state_tree.flush().unwrap()
+        };
+        sectors
+            .set(
+                0,
+                fil_actor_miner_state::v11::SectorOnChainInfo {
+                    simple_qa_power: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        let before_upgrade = set_sectors(&mut sectors);
+        // The miner gains a sector before the upgrade
+        sectors
+            .set(
+                1,
+                fil_actor_miner_state::v11::SectorOnChainInfo {
+                    sector_number: 1,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        let at_upgrade = set_sectors(&mut sectors);
+
+        let (new_manifest_cid, _new_manifest) = make_test_manifest(&store, "fil/12/");
+        let mut chain_config = ChainConfig::devnet();
+        if let Some(entry) = chain_config.height_infos.get_mut(&Height::Watermelon) {
+            entry.bundle = Some(new_manifest_cid);
+        }
+        let migrate = |state: &Cid, epoch, cache: &MigrationCache| {
+            super::super::run_migration(
+                &chain_config,
+                &store,
+                state,
+                epoch,
+                cache,
+                &MigrationProgress::default(),
+            )
+            .unwrap()
+        };
+
+        let cache = MigrationCache::new(MIGRATION_CACHE_SIZE);
+        migrate(&before_upgrade, 100, &cache);
+        let pre_migrated = migrate(&at_upgrade, 200, &cache);
+        let direct = migrate(&at_upgrade, 200, &MigrationCache::new(MIGRATION_CACHE_SIZE));
+        assert_eq!(pre_migrated, direct);
+    }
+
     fn make_input_tree<BS: Blockstore>(store: &Arc<BS>) -> (StateTree<BS>, BuiltinActorManifest) {
         let mut tree = StateTree::new(store.clone(), StateTreeVersion::V5).unwrap();
 
@@ -21,7 +21,10 @@ use fvm_ipld_blockstore::Blockstore;
 use fvm_ipld_encoding::CborStore;
 
 use super::{market, miner, system, verifier::Verifier, SystemStateOld};
-use crate::state_migration::common::{migrators::nil_migrator, StateMigration};
+use crate::state_migration::common::{
+    load_new_manifest, migrators::nil_migrator, MigrationCache, StateMigration,
+};
+use crate::state_migration::MigrationProgress;
 
 impl<BS: Blockstore> StateMigration<BS> {
     pub fn add_nv22_migrations(
@@ -89,32 +92,23 @@ pub fn run_migration<DB>(
     blockstore: &Arc<DB>,
     state: &Cid,
     epoch: ChainEpoch,
+    cache: &MigrationCache,
+    progress: &MigrationProgress,
 ) -> anyhow::Result<Cid>
 where
     DB: Blockstore + Send + Sync,
 {
-    let new_manifest_cid = chain_config
-        .height_infos
-        .get(&Height::Dragon)
-        .context("no height info for network version NV22")?
-        .bundle
-        .as_ref()
-        .context("no bundle for network version NV22")?;
-
-    blockstore.get(new_manifest_cid)?.context(format!(
-        "manifest for network version NV22 not found in blockstore: {new_manifest_cid}"
-    ))?;
-
     // Add migration specification verification
     let verifier = Arc::new(Verifier::default());
 
-    let new_manifest = BuiltinActorManifest::load_manifest(blockstore, new_manifest_cid)?;
+    let new_manifest = load_new_manifest(chain_config, blockstore, Height::Dragon)?;
     let mut migration = StateMigration::<DB>::new(Some(verifier));
     migration.add_nv22_migrations(blockstore, state, &new_manifest, chain_config)?;
 
     let actors_in = StateTree::new_from_root(blockstore.clone(), state)?;
     let actors_out = StateTree::new(blockstore.clone(), StateTreeVersion::V5)?;
-    let new_state = migration.migrate_state_tree(blockstore, epoch, actors_in, actors_out)?;
+    let new_state =
+        migration.migrate_state_tree(blockstore, epoch, actors_in, actors_out, cache, progress)?;
 
     Ok(new_state)
 }
@@ -0,0 +1,118 @@
+// Copyright 2019-2024 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::time::Instant;
+
+use crate::lotus_json::lotus_json_with_self;
+use crate::networks::Height;
+use crate::shim::clock::ChainEpoch;
+use parking_lot::Mutex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Progress of a running state migration.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "PascalCase")]
+pub struct MigrationStatus {
+    /// Upgrade the state is migrated for.
+    pub height: Height,
+    /// Epoch of the migrated state.
+    pub epoch: ChainEpoch,
+    /// Whether the state is migrated ahead of the upgrade, so that the
+    /// migration at the upgrade epoch only migrates what changed since.
+    pub pre_migration: bool,
+    pub migrated_actors: u64,
+    /// Number of actors to migrate, zero until they are counted.
+    pub total_actors: u64,
+    pub percent_complete: f64,
+    /// Estimated number of seconds left, unknown until actors are migrated.
+    pub eta_seconds: Option<u64>,
+}
+lotus_json_with_self!(MigrationStatus);
+
+#[derive(Debug)]
+struct Running {
+    height: Height,
+    epoch: ChainEpoch,
+    pre_migration: bool,
+    started: Instant,
+    migrated_actors: u64,
+    total_actors: u64,
+}
+
+/// Progress of the state migrations, updated by the migrations as they run.
+#[derive(Debug, Default)]
+pub struct MigrationProgress {
+    running: Mutex<Option<Running>>,
+}
+
+/// Progress of the state migrations of the node.
+static MIGRATION_PROGRESS: MigrationProgress = MigrationProgress::new();
+
+/// Returns the progress of the state migrations run by the node.
+pub fn migration_progress() -> &'static MigrationProgress {
+    &MIGRATION_PROGRESS
+}
+
+impl MigrationProgress {
+    pub const fn new() -> Self {
+        Self {
+            running: parking_lot::const_mutex(None),
+        }
+    }
+
+    /// Returns the progress of the running migration, if any.
+    pub fn status(&self) -> Option<MigrationStatus> {
+        let running = self.running.lock();
+        let running = running.as_ref()?;
+        let ratio = match running.total_actors {
+            0 => 0.0,
+            total => running.migrated_actors as f64 / total as f64,
+        };
+        let eta = (running.migrated_actors > 0).then(|| {
+            let elapsed = running.started.elapsed();
+            elapsed.mul_f64((1.0 - ratio) / ratio).as_secs()
+        });
+        Some(MigrationStatus {
+            height: running.height,
+            epoch: running.epoch,
+            pre_migration: running.pre_migration,
+            migrated_actors: running.migrated_actors,
+            total_actors: running.total_actors,
+            percent_complete: ratio * 100.0,
+            eta_seconds: eta,
+        })
+    }
+
+    pub(in crate::state_migration) fn start(
+        &self,
+        height: Height,
+        epoch: ChainEpoch,
+        pre_migration: bool,
+    ) {
+        *self.running.lock() = Some(Running {
+            height,
+            epoch,
+            pre_migration,
+            started: Instant::now(),
+            migrated_actors: 0,
+            total_actors: 0,
+        });
+    }
+
+    pub(in crate::state_migration) fn set_total_actors(&self, total_actors: u64) {
+        if let Some(running) = self.running.lock().as_mut() {
+            running.total_actors = total_actors;
+        }
+    }
+
+    pub(in crate::state_migration) fn add_migrated_actor(&self) {
+        if let Some(running) = self.running.lock().as_mut() {
+            running.migrated_actors += 1;
+        }
+    }
+
+    pub(in crate::state_migration) fn finish(&self) {
+        *self.running.lock() = None;
+    }
+}
@@ -4,10 +4,10 @@
 use crate::utils::net::global_http_client;
 use crate::utils::{retry, RetryArgs};
 use crate::{
-    daemon::bundle::load_actor_bundles,
-    networks::{ChainConfig, Height, NetworkChain},
+    daemon::bundle::{load_actor_bundle_overrides, load_actor_bundles},
+    networks::{ActorBundleOverride, ChainConfig, Height, NetworkChain},
     shim::state_tree::StateRoot,
-    state_migration::run_state_migrations,
+    state_migration::{common::load_new_manifest, run_state_migrations},
 };
 use cid::Cid;
 use futures::{AsyncWriteExt, TryStreamExt};
@@ -69,6 +69,78 @@ async fn test_nv19_state_migration_calibnet() {
     .await
 }
 
+// A bundle overriding the built-in one is resolved by the migration to its
+// actors version
+#[tokio::test]
+async fn test_actor_bundle_override() {
+    use crate::db::MemoryDB;
+    use crate::utils::cid::CidCborExt as _;
+    use crate::utils::db::car_stream::{CarBlock, CarWriter};
+    use cid::multihash::{Code::Identity, MultihashDigest as _};
+    use futures::StreamExt as _;
+    use fvm_ipld_encoding::IPLD_RAW;
+
+    let actor_list = ["system", "init", "account"]
+        .map(|name| {
+            let code = Identity.digest(format!("fil/13/{name}").as_bytes());
+            (name, Cid::new_v1(IPLD_RAW, code))
+        })
+        .to_vec();
+    let actor_list_cid = Cid::from_cbor_blake2b256(&actor_list).unwrap();
+    let manifest = Cid::from_cbor_blake2b256(&(1, actor_list_cid)).unwrap();
+    let blocks = vec![
+        CarBlock {
+            cid: actor_list_cid,
+            data: fvm_ipld_encoding::to_vec(&actor_list).unwrap(),
+        },
+        CarBlock {
+            cid: manifest,
+            data: fvm_ipld_encoding::to_vec(&(1, actor_list_cid)).unwrap(),
+        },
+    ];
+    let temp_dir = tempfile::tempdir().unwrap();
+    let path = temp_dir.path().join("bundle.car");
+    futures::stream::iter(blocks)
+        .map(std::io::Result::Ok)
+        .forward(
+            CarWriter::new_carv1(
+                nonempty::nonempty![manifest],
+                tokio::fs::File::create(&path).await.unwrap(),
+            )
+            .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let db = MemoryDB::default();
+    let mut chain_config = ChainConfig::calibnet();
+    let bundle = ActorBundleOverride {
+        manifest,
+        source: path.display().to_string(),
+    };
+    load_actor_bundle_overrides(&db, &[bundle], &mut chain_config)
+        .await
+        .unwrap();
+    assert_eq!(
+        chain_config.height_infos[&Height::Dragon].bundle,
+        Some(manifest)
+    );
+    let new_manifest = load_new_manifest(&chain_config, &db, Height::Dragon).unwrap();
+    assert_eq!(new_manifest.source_cid(), actor_list_cid);
+    assert_eq!(new_manifest.actors_version().unwrap(), 13);
+
+    // The root of the bundle must be the given manifest
+    let bundle = ActorBundleOverride {
+        manifest: actor_list_cid,
+        source: path.display().to_string(),
+    };
+    assert!(
+        load_actor_bundle_overrides(&MemoryDB::default(), &[bundle], &mut chain_config)
+            .await
+            .is_err()
+    );
+}
+
 async fn test_state_migration(
     height: Height,
     network: NetworkChain,
@@ -3,13 +3,21 @@
 
 use crate::blocks::Ticket;
 use crate::blocks::VRFProof;
+use crate::blocks::{CachingBlockHeader, RawBlockHeader, Tipset};
+use crate::db::{GarbageCollectable, MemoryDB};
 use crate::message::SignedMessage;
 use crate::shim::{
     address::Address,
+    clock::ChainEpoch,
     crypto::Signature,
     message::{Message, Message_v3},
 };
+use crate::utils::db::CborStoreExt as _;
+use ahash::HashSet;
 use base64::{prelude::BASE64_STANDARD, Engine};
+use cid::Cid;
+use fvm_ipld_blockstore::Blockstore;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 /// Returns a Ticket to be used for testing
 pub fn construct_ticket() -> Ticket {
@@ -31,6 +39,75 @@ pub fn construct_messages() -> (Message, SignedMessage) {
     (bls_messages, secp_messages)
 }
 
+/// Counts the blocks read from it
+#[derive(Default)]
+pub struct CountingStore {
+    inner: MemoryDB,
+    reads: AtomicUsize,
+}
+
+impl CountingStore {
+    /// Returns the number of blocks read since the last call
+    pub fn take_reads(&self) -> usize {
+        self.reads.swap(0, Ordering::Relaxed)
+    }
+}
+
+impl Blockstore for CountingStore {
+    fn get(&self, k: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        self.reads.fetch_add(1, Ordering::Relaxed);
+        self.inner.get(k)
+    }
+
+    fn put_keyed(&self, k: &Cid, block: &[u8]) -> anyhow::Result<()> {
+        self.inner.put_keyed(k, block)
+    }
+}
+
+impl GarbageCollectable for CountingStore {
+    fn get_keys(&self) -> anyhow::Result<HashSet<u32>> {
+        self.inner.get_keys()
+    }
+
+    fn remove_keys(&self, keys: HashSet<u32>) -> anyhow::Result<()> {
+        self.inner.remove_keys(keys)
+    }
+}
+
+/// Persists a chain of single-block tipsets on top of `parent`, at each of
+/// `epochs` in increasing order. The epochs in between are null rounds.
+/// `header` returns the block at an epoch given its parent, whose parents and
+/// epoch are then set. Returns the tipsets from the oldest.
+pub fn persist_chain(
+    db: &impl Blockstore,
+    parent: &Tipset,
+    epochs: impl IntoIterator<Item = ChainEpoch>,
+    mut header: impl FnMut(&Tipset, ChainEpoch) -> RawBlockHeader,
+) -> Vec<Tipset> {
+    let mut chain: Vec<Tipset> = vec![];
+    for epoch in epochs {
+        let parent = chain.last().unwrap_or(parent);
+        let block = CachingBlockHeader::new(RawBlockHeader {
+            parents: parent.key().clone(),
+            epoch,
+            ..header(parent, epoch)
+        });
+        db.put_cbor_default(&block).unwrap();
+        chain.push(Tipset::from(block));
+    }
+    chain
+}
+
+/// Persists a state-tree distinct to `epoch`, made of a root and a leaf.
+/// Returns their CIDs.
+pub fn persist_epoch_state(db: &impl Blockstore, epoch: ChainEpoch) -> (Cid, Cid) {
+    let leaf = db
+        .put_cbor_default(&format!("state of epoch {epoch}"))
+        .unwrap();
+    let root = db.put_cbor_default(&(epoch, leaf)).unwrap();
+    (root, leaf)
+}
+
 // Serialize macro used for testing
 #[macro_export]
 macro_rules! to_string_with {
@@ -24,13 +24,16 @@ where
             match cmd {
                 Subcommand::Backup(cmd) => cmd.run(),
                 Subcommand::Benchmark(cmd) => cmd.run().await,
+                Subcommand::State(cmd) => cmd.run().await,
                 Subcommand::StateMigration(cmd) => cmd.run().await,
                 Subcommand::Snapshot(cmd) => cmd.run().await,
                 Subcommand::Fetch(cmd) => cmd.run().await,
                 Subcommand::Archive(cmd) => cmd.run().await,
                 Subcommand::DB(cmd) => cmd.run().await,
+                Subcommand::Index(cmd) => cmd.run().await,
                 Subcommand::Car(cmd) => cmd.run().await,
                 Subcommand::Api(cmd) => cmd.run().await,
+                Subcommand::ChainConfig(cmd) => cmd.run(),
                 Subcommand::Net(cmd) => cmd.run().await,
                 Subcommand::Shed(cmd) => cmd.run().await,
             }
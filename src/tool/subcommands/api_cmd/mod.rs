@@ -0,0 +1,2389 @@
+// Copyright 2019-2024 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+mod notify;
+mod report;
+mod sampling;
+
+use crate::auth::{create_token, generate_priv_key, ADMIN, JWT_IDENTIFIER};
+use crate::blocks::{CachingBlockHeader, RawBlockHeader, Tipset, TipsetKey};
+use crate::chain::index::{ChainIndex, ResolveNullTipset};
+use crate::chain::{persist_objects, ChainStore};
+use crate::chain_sync::SyncConfig;
+use crate::chain_sync::SyncStage;
+use crate::chain_sync::TipsetValidator;
+use crate::cid_collections::CidHashSet;
+use crate::cli_shared::snapshot::TrustedVendor;
+use crate::daemon::db_util::download_to;
+use crate::db::car::ManyCar;
+use crate::db::setting_keys::OFFLINE_SNAPSHOTS_KEY;
+use crate::db::SettingsStoreExt as _;
+use crate::db::{
+    parity_db::ParityDb, parity_db_config::ParityDbConfig, BlockstoreWithReadCache, DBStatistics,
+    IndicesStore, MemoryDB, SettingsStore,
+};
+use crate::genesis::{get_network_name_from_genesis, read_genesis_header, read_network_name};
+use crate::key_management::{KeyStore, KeyStoreConfig};
+use crate::libp2p::NetworkMessage;
+use crate::lotus_json::HasLotusJson;
+use crate::message::Message as _;
+use crate::message_pool::{MessagePool, MpoolRpcProvider};
+use crate::networks::calibnet;
+use crate::networks::ChainConfig;
+use crate::networks::NetworkChain;
+use crate::rpc::{load_tls_config, start_rpc, stop_rpc, RPCState, RpcConfig};
+use crate::rpc_api::common_api::DISCOVER;
+use crate::rpc_api::data_types::{
+    ApiTipsetKey, DiscoverResult, MarketDealsFilter, MessageFilter, MessageLookup,
+};
+use crate::rpc_api::eth_api::Address as EthAddress;
+use crate::rpc_api::eth_api::*;
+use crate::rpc_client::CommunicationProtocol;
+use crate::rpc_client::{ApiInfo, JsonRpcError, RpcRequest, DEFAULT_PORT};
+use crate::shim::address::{Address, Protocol};
+use crate::shim::clock::ChainEpoch;
+use crate::shim::crypto::Signature;
+use crate::shim::deal::DealID;
+use crate::shim::econ::TokenAmount;
+use crate::shim::message::{Message, METHOD_SEND};
+use crate::shim::state_tree::StateTree;
+use crate::state_manager::StateManager;
+use crate::utils::version::FOREST_VERSION_STRING;
+use crate::Client;
+use anyhow::{bail, ensure, Context as _};
+use cid::Cid;
+use clap::{Subcommand, ValueEnum};
+use fil_actor_interface::{market, miner, power};
+use fil_actors_shared::fvm_ipld_amt::Amtv0;
+use fil_actors_shared::v10::runtime::{DomainSeparationTag, Policy};
+use futures::stream::FuturesUnordered;
+use futures::StreamExt;
+use notify::{collect_notifications, websocket_tests, NotificationLimits};
+use report::{dump_failure, format_as_csv, format_as_json, format_as_markdown, TestRecord};
+use sampling::{parse_epoch_range, sample_tipsets, TipsetSampling};
+
+use fvm_ipld_blockstore::Blockstore;
+use itertools::Itertools as _;
+use jsonrpsee::server::stop_channel;
+use jsonrpsee::types::ErrorCode;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::BTreeSet;
+use std::future::Future;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::num::NonZeroUsize;
+use std::ops::Range;
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+use tokio::{
+    net::TcpListener,
+    signal::{
+        ctrl_c,
+        unix::{signal, SignalKind},
+    },
+    sync::{mpsc, RwLock},
+    task::JoinSet,
+};
+use tokio_rustls::rustls::ServerConfig;
+use tracing::{debug, info, warn};
+
+#[derive(Debug, Subcommand)]
+pub enum ApiCommands {
+    // Serve
+    Serve {
+        /// Snapshot input paths. Supports `.car`, `.car.zst`, and `.forest.car.zst`.
+        snapshot_files: Vec<PathBuf>,
+        /// Filecoin network chain
+        #[arg(long, default_value = "mainnet")]
+        chain: NetworkChain,
+        /// RPC port. Zero lets the OS pick a free port, which is logged.
+        #[arg(long, default_value_t = DEFAULT_PORT)]
+        port: u16,
+        // Data Directory
+        #[arg(long, default_value = "offline-rpc-db")]
+        data_dir: PathBuf,
+        // Allow downloading snapshot automatically
+        #[arg(long)]
+        auto_download_snapshot: bool,
+        /// Additionally serve RPC on a Unix domain socket at this path
+        #[arg(long)]
+        unix_socket: Option<PathBuf>,
+        /// PEM encoded certificate chain to serve RPC over HTTPS/WSS
+        #[arg(long, requires = "tls_key")]
+        tls_cert: Option<PathBuf>,
+        /// PEM encoded private key matching the TLS certificate
+        #[arg(long, requires = "tls_cert")]
+        tls_key: Option<PathBuf>,
+        /// Serve Prometheus metrics at this address, e.g. 127.0.0.1:6116
+        #[arg(long)]
+        metrics_address: Option<SocketAddr>,
+        /// Serve the chain as it was at this epoch, instead of the heaviest
+        /// tipset of the snapshots
+        #[arg(long)]
+        height: Option<ChainEpoch>,
+        /// Genesis file, for networks whose genesis is not built into Forest
+        /// nor included in the snapshots
+        #[arg(long)]
+        genesis: Option<PathBuf>,
+        /// Keep the data directory when the server stops, so that a restart
+        /// with the same snapshots reuses it. Without snapshots, the ones it
+        /// was populated from are served again.
+        #[arg(long, overrides_with = "cleanup_on_exit")]
+        persist_db: bool,
+        /// Remove the data directory when the server stops, overriding an
+        /// earlier `--persist-db`. This is the default.
+        #[arg(long, overrides_with = "persist_db")]
+        cleanup_on_exit: bool,
+        /// Support the wallet and message pool methods that write, e.g.
+        /// `Filecoin.WalletNew` and `Filecoin.MpoolPush`. Keys are stored in
+        /// a keystore of the data directory, and pushed messages stay in the
+        /// local message pool: nothing is gossiped to the network.
+        #[arg(long)]
+        enable_writes: bool,
+        /// Serve the snapshots without a database: blocks written while
+        /// serving, e.g. computed states, are kept in memory and lost when
+        /// the server stops. Starts in seconds, as nothing is written to the
+        /// data directory but the admin token.
+        #[arg(long, conflicts_with = "persist_db")]
+        no_import: bool,
+        /// Save the indexes of the `.car` snapshots in this directory, so
+        /// that the next loads of the same snapshots are fast. Without it,
+        /// the snapshots are indexed in memory on every start.
+        #[arg(long)]
+        index_cache_dir: Option<PathBuf>,
+        /// Extend the chain by an empty tipset on top of the head every
+        /// given number of seconds, so that it moves as on a live network.
+        /// The state is not computed: new tipsets keep that of the head.
+        /// Refused on mainnet.
+        #[arg(long, value_name = "INTERVAL_SECS", value_parser = parse_simulation_interval)]
+        simulate_epochs: Option<Duration>,
+    },
+    /// Compare
+    Compare {
+        /// Forest address, optionally preceded by a token as in
+        /// `FULLNODE_API_INFO`, e.g. `<token>:/ip4/127.0.0.1/tcp/2345/http`
+        #[clap(long, default_value_t = ApiInfo::from_str("/ip4/127.0.0.1/tcp/2345/http").expect("infallible"))]
+        forest: ApiInfo,
+        /// Lotus address, optionally preceded by a token as in
+        /// `FULLNODE_API_INFO`, e.g. `<token>:/ip4/127.0.0.1/tcp/1234/http`
+        #[clap(long, default_value_t = ApiInfo::from_str("/ip4/127.0.0.1/tcp/1234/http").expect("infallible"))]
+        lotus: ApiInfo,
+        /// Token for the Forest node, overriding the one of `--forest`
+        #[arg(long)]
+        forest_token: Option<String>,
+        /// Token for the Lotus node, overriding the one of `--lotus`
+        #[arg(long)]
+        lotus_token: Option<String>,
+        /// Additional reference node, named after its implementation and
+        /// optionally with a token as for `--lotus`, e.g.
+        /// `venus=/ip4/127.0.0.1/tcp/3453/http`. May be repeated, in which
+        /// case the nodes disagreeing with the majority are reported as
+        /// incorrect.
+        #[arg(long = "ref", value_name = "NAME=ADDRESS", value_parser = parse_reference)]
+        references: Vec<(String, ApiInfo)>,
+        /// Snapshot input paths. Supports `.car`, `.car.zst`, and `.forest.car.zst`.
+        #[arg()]
+        snapshot_files: Vec<PathBuf>,
+        /// Filter which tests to run according to method name. Case sensitive.
+        #[arg(long, default_value = "")]
+        filter: String,
+        /// Filter file which tests to run according to method name. Case sensitive.
+        /// The file should contain one entry per line. Lines starting with `!`
+        /// are considered as rejected methods, while the others are allowed.
+        /// Empty lines and lines starting with `#` are ignored.
+        #[arg(long)]
+        filter_file: Option<PathBuf>,
+        /// Only run the tests with one of these tags, e.g. `state,miner`.
+        /// Combines with `--filter` and `--filter-file`.
+        #[arg(long, value_delimiter = ',')]
+        include_tags: Vec<String>,
+        /// Do not run the tests with one of these tags, e.g. `slow`, even if
+        /// they have an included tag
+        #[arg(long, value_delimiter = ',')]
+        exclude_tags: Vec<String>,
+        /// Report the results of each tag separately
+        #[arg(long)]
+        group_by_tag: bool,
+        /// Cancel test run on the first failure
+        #[arg(long)]
+        fail_fast: bool,
+        #[arg(short, long, default_value = "20")]
+        /// The number of tipsets to use to generate test cases.
+        n_tipsets: usize,
+        /// Sample tipsets from this epoch onwards, instead of going back
+        /// from the heaviest tipset of the snapshots
+        #[arg(long)]
+        from_epoch: Option<ChainEpoch>,
+        /// Number of epochs between sampled tipsets
+        #[arg(long, default_value = "1")]
+        stride: NonZeroUsize,
+        /// Sample tipsets in this range of epochs, end excluded, e.g.
+        /// `1420000..1420100`, ignoring `--n-tipsets`. May be repeated.
+        #[arg(long, value_name = "START..END", value_parser = parse_epoch_range, conflicts_with = "from_epoch")]
+        epoch_range: Vec<Range<ChainEpoch>>,
+        #[arg(long, value_enum, default_value_t = RunIgnored::Default)]
+        /// Behavior for tests marked as `ignored`.
+        run_ignored: RunIgnored,
+        /// Maximum number of concurrent requests
+        #[arg(long, default_value = "8")]
+        max_concurrent_requests: usize,
+        /// Format of the test report
+        #[arg(long, value_enum, default_value_t = ReportFormat::Markdown)]
+        report_format: ReportFormat,
+        /// Write the test report to this file instead of the standard output
+        #[arg(long)]
+        report_path: Option<PathBuf>,
+        /// Write the request, both responses and their differences of each
+        /// failed test to a file in this directory
+        #[arg(long)]
+        dump_dir: Option<PathBuf>,
+        /// Report the latency percentiles of each method and the slowest tests
+        #[arg(long)]
+        show_timing: bool,
+        /// Number of times failed tests are retried, unless their failure is
+        /// not transient, e.g. a missing method. Only the last attempt counts.
+        #[arg(long, default_value_t = 0)]
+        retries: u32,
+        /// Delay in seconds before retrying a failed test
+        #[arg(long, default_value_t = 1.)]
+        retry_delay: f64,
+        /// Fail if some methods registered by Forest are not covered by any
+        /// test, or if they cannot be listed
+        #[arg(long)]
+        fail_on_uncovered: bool,
+        /// Number of notifications to collect from each node in channel
+        /// tests, e.g. of `Filecoin.ChainNotify`, over WebSocket only
+        #[arg(long, default_value_t = 3)]
+        notification_count: usize,
+        /// Time in seconds after which channel tests stop collecting
+        /// notifications, even if fewer than `--notification-count`
+        #[arg(long, default_value_t = 90.)]
+        notification_duration: f64,
+    },
+}
+
+/// For more information about each flag, refer to the Forest documentation at:
+/// <https://docs.forest.chainsafe.io/rustdoc/forest_filecoin/tool/subcommands/api_cmd/enum.ApiCommands.html>
+struct ApiTestFlags {
+    filter: String,
+    filter_file: Option<PathBuf>,
+    tag_filter: TagFilter,
+    group_by_tag: bool,
+    fail_fast: bool,
+    tipset_sampling: TipsetSampling,
+    run_ignored: RunIgnored,
+    max_concurrent_requests: usize,
+    report_format: ReportFormat,
+    report_path: Option<PathBuf>,
+    dump_dir: Option<PathBuf>,
+    show_timing: bool,
+    retries: u32,
+    retry_delay: Duration,
+    fail_on_uncovered: bool,
+    notification_limits: NotificationLimits,
+}
+
+impl ApiCommands {
+    pub async fn run(self) -> anyhow::Result<()> {
+        match self {
+            Self::Serve {
+                snapshot_files,
+                chain,
+                port,
+                data_dir,
+                auto_download_snapshot,
+                unix_socket,
+                tls_cert,
+                tls_key,
+                metrics_address,
+                height,
+                genesis,
+                persist_db,
+                cleanup_on_exit,
+                enable_writes,
+                no_import,
+                index_cache_dir,
+                simulate_epochs,
+            } => {
+                ensure!(
+                    simulate_epochs.is_none() || chain.is_testnet(),
+                    "epochs cannot be simulated on {chain}"
+                );
+                let tls_config = match (tls_cert, tls_key) {
+                    (Some(cert_path), Some(key_path)) => {
+                        Some(load_tls_config(&cert_path, &key_path)?)
+                    }
+                    _ => None,
+                };
+                start_offline_server(
+                    snapshot_files,
+                    chain,
+                    port,
+                    data_dir.clone(),
+                    auto_download_snapshot,
+                    tls_config,
+                    unix_socket,
+                    metrics_address,
+                    height,
+                    genesis,
+                    persist_db && !cleanup_on_exit,
+                    enable_writes,
+                    no_import,
+                    index_cache_dir,
+                    simulate_epochs,
+                )
+                .await?;
+            }
+            Self::Compare {
+                forest,
+                lotus,
+                forest_token,
+                lotus_token,
+                references,
+                snapshot_files,
+                filter,
+                filter_file,
+                include_tags,
+                exclude_tags,
+                group_by_tag,
+                fail_fast,
+                n_tipsets,
+                from_epoch,
+                stride,
+                epoch_range,
+                run_ignored,
+                max_concurrent_requests,
+                report_format,
+                report_path,
+                dump_dir,
+                show_timing,
+                retries,
+                retry_delay,
+                fail_on_uncovered,
+                notification_count,
+                notification_duration,
+            } => {
+                let config = ApiTestFlags {
+                    filter,
+                    filter_file,
+                    tag_filter: TagFilter {
+                        include: include_tags,
+                        exclude: exclude_tags,
+                    },
+                    group_by_tag,
+                    fail_fast,
+                    tipset_sampling: TipsetSampling {
+                        n_tipsets,
+                        from_epoch,
+                        stride,
+                        epoch_ranges: epoch_range,
+                    },
+                    run_ignored,
+                    max_concurrent_requests,
+                    report_format,
+                    report_path,
+                    dump_dir,
+                    show_timing,
+                    retries,
+                    retry_delay: Duration::try_from_secs_f64(retry_delay)?,
+                    fail_on_uncovered,
+                    notification_limits: NotificationLimits {
+                        count: notification_count,
+                        duration: Duration::try_from_secs_f64(notification_duration)?,
+                    },
+                };
+
+                let mut nodes = vec![
+                    Node::new("forest", forest.set_token(forest_token)),
+                    Node::new("lotus", lotus.set_token(lotus_token)),
+                ];
+                for (name, api) in references {
+                    if nodes.iter().any(|node| node.name == name) {
+                        bail!("duplicate node name: {name}");
+                    }
+                    nodes.push(Node::new(name, api));
+                }
+
+                compare_apis(nodes, snapshot_files, config).await?
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parses a `--ref` argument, e.g. `venus=/ip4/127.0.0.1/tcp/3453/http`.
+fn parse_reference(s: &str) -> anyhow::Result<(String, ApiInfo)> {
+    let (name, address) = s
+        .split_once('=')
+        .context("expected a reference node as NAME=ADDRESS")?;
+    ensure!(!name.is_empty(), "the name of the reference node is empty");
+    Ok((name.to_lowercase(), ApiInfo::from_str(address)?))
+}
+
+/// Parses a `--simulate-epochs` interval, a positive number of seconds.
+fn parse_simulation_interval(s: &str) -> anyhow::Result<Duration> {
+    let interval = Duration::try_from_secs_f64(s.parse()?)?;
+    ensure!(!interval.is_zero(), "the interval must be positive");
+    Ok(interval)
+}
+
+/// A node whose API is compared, named after its implementation.
+#[derive(Debug, Clone)]
+struct Node {
+    name: String,
+    api: ApiInfo,
+}
+
+impl Node {
+    fn new(name: impl Into<String>, api: ApiInfo) -> Self {
+        Self {
+            name: name.into(),
+            api,
+        }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone)]
+#[clap(rename_all = "kebab_case")]
+pub enum RunIgnored {
+    Default,
+    IgnoredOnly,
+    All,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+#[clap(rename_all = "kebab_case")]
+pub enum ReportFormat {
+    /// Table of the outcomes of each method
+    Markdown,
+    /// Outcomes of each method, and details of each test
+    Json,
+    /// Details of each test
+    Csv,
+}
+
+#[derive(Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash, Serialize)]
+enum EndpointStatus {
+    // RPC method is missing
+    MissingMethod,
+    // Request isn't valid according to jsonrpc spec
+    InvalidRequest,
+    // Catch-all for errors on the node
+    InternalServerError,
+    // Unexpected JSON schema
+    InvalidJSON,
+    // Got response with the right JSON schema but it failed sanity checking
+    InvalidResponse,
+    Timeout,
+    Valid,
+}
+
+impl EndpointStatus {
+    fn from_json_error(err: JsonRpcError) -> Self {
+        match err.known_code() {
+            ErrorCode::ParseError => Self::InvalidResponse,
+            ErrorCode::OversizedRequest => Self::InvalidRequest,
+            ErrorCode::InvalidRequest => Self::InvalidRequest,
+            ErrorCode::MethodNotFound => Self::MissingMethod,
+            it if it.code() == 0 && it.message().contains("timed out") => Self::Timeout,
+            _ => {
+                tracing::debug!(?err);
+                Self::InternalServerError
+            }
+        }
+    }
+
+    /// Whether the same request could have another outcome if sent again.
+    fn is_transient(self) -> bool {
+        !matches!(
+            self,
+            Self::MissingMethod | Self::InvalidRequest | Self::InvalidJSON
+        )
+    }
+}
+
+struct RpcTest {
+    request: RpcRequest,
+    check_syntax: Arc<dyn Fn(serde_json::Value) -> bool + Send + Sync>,
+    check_semantics: Arc<dyn Fn(serde_json::Value, serde_json::Value) -> bool + Send + Sync>,
+    ignore: Option<&'static str>,
+    /// Groups of tests this one belongs to, e.g. `state` or `slow`, to select
+    /// tests with `--include-tags` and `--exclude-tags`
+    tags: &'static [&'static str],
+    /// Set for channels, whose notifications are collected in an array
+    notifications: Option<NotificationLimits>,
+    /// Sent to the reference nodes instead of `request`, for requests they
+    /// do not support, e.g. Forest extensions of a method
+    reference_request: Option<RpcRequest>,
+}
+
+impl RpcTest {
+    // Check that an endpoint exist and that both the Lotus and Forest JSON
+    // response follows the same schema.
+    fn basic<T>(request: RpcRequest<T>) -> RpcTest
+    where
+        T: HasLotusJson,
+    {
+        RpcTest {
+            request: request.lower(),
+            check_syntax: Arc::new(|value| serde_json::from_value::<T::LotusJson>(value).is_ok()),
+            check_semantics: Arc::new(|_, _| true),
+            ignore: None,
+            tags: &[],
+            notifications: None,
+            reference_request: None,
+        }
+    }
+
+    // Check that an endpoint exist, has the same JSON schema, and do custom
+    // validation over both responses.
+    fn validate<T>(
+        request: RpcRequest<T>,
+        validate: impl Fn(T, T) -> bool + Send + Sync + 'static,
+    ) -> RpcTest
+    where
+        T: HasLotusJson,
+        T::LotusJson: DeserializeOwned,
+    {
+        RpcTest::validate_as(request.lower(), validate)
+    }
+
+    // Check that a channel can be opened over WebSocket, that its
+    // notifications have the same JSON schema, and do custom validation over
+    // those collected from both nodes.
+    fn validate_notifications<T>(
+        request: RpcRequest<T>,
+        limits: NotificationLimits,
+        validate: impl Fn(Vec<T>, Vec<T>) -> bool + Send + Sync + 'static,
+    ) -> RpcTest
+    where
+        T: HasLotusJson,
+        T::LotusJson: DeserializeOwned,
+    {
+        let mut request = request.lower();
+        // Collecting is not expected to time out
+        request.set_timeout(limits.duration + request.timeout());
+        RpcTest {
+            notifications: Some(limits),
+            ..RpcTest::validate_as(request, validate)
+        }
+    }
+
+    // Like `validate`, for responses of type `T`.
+    fn validate_as<T>(
+        request: RpcRequest,
+        validate: impl Fn(T, T) -> bool + Send + Sync + 'static,
+    ) -> RpcTest
+    where
+        T: HasLotusJson,
+        T::LotusJson: DeserializeOwned,
+    {
+        RpcTest {
+            request,
+            check_syntax: Arc::new(|value| serde_json::from_value::<T::LotusJson>(value).is_ok()),
+            check_semantics: Arc::new(move |forest_json, lotus_json| {
+                serde_json::from_value::<T::LotusJson>(forest_json).is_ok_and(|forest| {
+                    serde_json::from_value::<T::LotusJson>(lotus_json).is_ok_and(|lotus| {
+                        validate(
+                            HasLotusJson::from_lotus_json(forest),
+                            HasLotusJson::from_lotus_json(lotus),
+                        )
+                    })
+                })
+            }),
+            ignore: None,
+            tags: &[],
+            notifications: None,
+            reference_request: None,
+        }
+    }
+
+    fn ignore(mut self, msg: &'static str) -> Self {
+        self.ignore = Some(msg);
+        self
+    }
+
+    // Check that an endpoint exist and that Forest returns exactly the same
+    // JSON as Lotus.
+    fn identity<T: PartialEq>(request: RpcRequest<T>) -> RpcTest
+    where
+        T: HasLotusJson,
+        T::LotusJson: DeserializeOwned,
+    {
+        RpcTest::validate(request, |forest, lotus| forest == lotus)
+    }
+
+    fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.request.set_timeout(timeout);
+        if let Some(request) = &mut self.reference_request {
+            request.set_timeout(timeout);
+        }
+        self
+    }
+
+    fn with_reference_request<T>(mut self, request: RpcRequest<T>) -> Self {
+        self.reference_request = Some(request.lower());
+        self
+    }
+
+    fn with_tags(mut self, tags: &'static [&'static str]) -> Self {
+        self.tags = tags;
+        self
+    }
+
+    async fn run(&self, nodes: &[Node], use_websocket: bool) -> TestResult {
+        let start = Instant::now();
+        let timed_call = |(i, node): (usize, &Node)| {
+            // The first node is the tested one
+            let request = match &self.reference_request {
+                Some(request) if i > 0 => request.clone(),
+                _ => self.request.clone(),
+            };
+            let api = node.api.clone();
+            async move {
+                let start = Instant::now();
+                let response = match self.notifications {
+                    Some(limits) => collect_notifications(&api, request, limits).await,
+                    None if use_websocket => api.ws_call(request).await,
+                    None => api.call(request).await,
+                };
+                (response, start.elapsed())
+            }
+        };
+        let calls = nodes.iter().enumerate().map(timed_call);
+        let results = if self.notifications.is_some() {
+            // Channels are listened to at the same time, so that the nodes
+            // notify of the same events
+            futures::future::join_all(calls).await
+        } else {
+            let mut results = vec![];
+            for call in calls {
+                results.push(call.await);
+            }
+            results
+        };
+        let (responses, durations): (Vec<_>, Vec<_>) = results.into_iter().unzip();
+        let response_jsons = responses.iter().map(response_json).collect();
+        let statuses = self.check(responses);
+        // Timed out calls are recorded as lasting the time limit
+        let durations = statuses
+            .iter()
+            .zip(durations)
+            .map(|(status, duration)| match status {
+                EndpointStatus::Timeout => self.request.timeout(),
+                _ => duration,
+            })
+            .collect();
+        TestResult {
+            statuses,
+            responses: response_jsons,
+            duration: start.elapsed(),
+            durations,
+        }
+    }
+
+    /// Checks the responses of the nodes, in their order. Responses which
+    /// pass the semantic check against each other are grouped, and the
+    /// nodes outside of the largest group are marked as incorrect. Ties are
+    /// decided against Forest, the first node, so that with a single
+    /// reference node, the reference is assumed to be correct.
+    fn check(
+        &self,
+        responses: Vec<Result<serde_json::Value, JsonRpcError>>,
+    ) -> Vec<EndpointStatus> {
+        if let Some(Err(first)) = responses.first() {
+            if responses.iter().all(|it| it.as_ref().err() == Some(first)) {
+                // All nodes have the same error, consider it as valid
+                return vec![EndpointStatus::Valid; responses.len()];
+            }
+        }
+
+        let mut statuses = vec![];
+        let mut valid = vec![];
+        for (i, response) in responses.into_iter().enumerate() {
+            statuses.push(match response {
+                Err(e) => EndpointStatus::from_json_error(e),
+                Ok(value) if (self.check_syntax)(value.clone()) => {
+                    valid.push((i, value));
+                    EndpointStatus::Valid
+                }
+                Ok(_) => EndpointStatus::InvalidJSON,
+            });
+        }
+
+        // Nodes whose responses pass the semantic check against the first
+        // response of their group
+        let mut groups: Vec<(serde_json::Value, Vec<usize>)> = vec![];
+        for (i, value) in valid {
+            let group = groups
+                .iter_mut()
+                .find(|(first, _)| (self.check_semantics)(first.clone(), value.clone()));
+            match group {
+                Some((_, nodes)) => nodes.push(i),
+                None => groups.push((value, vec![i])),
+            }
+        }
+        let majority = groups
+            .into_iter()
+            .map(|(_, nodes)| nodes)
+            .max_by_key(|nodes| {
+                let has_reference = nodes.iter().any(|i| *i > 0);
+                (nodes.len(), has_reference, Reverse(nodes.first().copied()))
+            });
+        if let Some(majority) = majority {
+            for (i, status) in statuses.iter_mut().enumerate() {
+                if *status == EndpointStatus::Valid && !majority.contains(&i) {
+                    *status = EndpointStatus::InvalidResponse;
+                }
+            }
+        }
+        statuses
+    }
+}
+
+/// Outcome of an [`RpcTest`], with the responses of the nodes in their order.
+struct TestResult {
+    statuses: Vec<EndpointStatus>,
+    /// Either `{"result": ...}` or `{"error": {"code": ..., "message": ...}}`
+    responses: Vec<serde_json::Value>,
+    duration: Duration,
+    durations: Vec<Duration>,
+}
+
+impl TestResult {
+    /// Whether the test failed, in a way that may not happen again.
+    fn is_retryable(&self) -> bool {
+        !passed(&self.statuses) && self.statuses.iter().all(|status| status.is_transient())
+    }
+}
+
+/// Runs a test until it passes, fails for good or has been retried `retries`
+/// times, returning the last result and the number of attempts.
+async fn run_with_retries<F: Future<Output = TestResult>>(
+    retries: u32,
+    delay: Duration,
+    mut run: impl FnMut() -> F,
+) -> (TestResult, u32) {
+    let mut attempts = 1;
+    loop {
+        let result = run().await;
+        if attempts > retries || !result.is_retryable() {
+            return (result, attempts);
+        }
+        tokio::time::sleep(delay).await;
+        attempts += 1;
+    }
+}
+
+fn response_json(response: &Result<serde_json::Value, JsonRpcError>) -> serde_json::Value {
+    match response {
+        Ok(result) => serde_json::json!({ "result": result }),
+        Err(e) => serde_json::json!({ "error": { "code": e.code(), "message": e.message() } }),
+    }
+}
+
+/// Tags the `tests` that are not tagged yet with `tags`.
+fn tagged(tags: &'static [&'static str], tests: Vec<RpcTest>) -> Vec<RpcTest> {
+    tests
+        .into_iter()
+        .map(|test| match test.tags {
+            [] => test.with_tags(tags),
+            _ => test,
+        })
+        .collect()
+}
+
+fn common_tests() -> Vec<RpcTest> {
+    tagged(
+        &["common"],
+        vec![
+            RpcTest::basic(ApiInfo::version_req()),
+            RpcTest::basic(ApiInfo::start_time_req()),
+            RpcTest::basic(ApiInfo::discover_req()),
+            RpcTest::basic(ApiInfo::session_req()),
+        ],
+    )
+}
+
+fn auth_tests() -> Vec<RpcTest> {
+    // Auth commands should be tested as well. Tracking issue:
+    // https://github.com/ChainSafe/forest/issues/3639
+    vec![]
+}
+
+fn beacon_tests() -> Vec<RpcTest> {
+    vec![RpcTest::identity(ApiInfo::beacon_get_entry_req(10101)).with_tags(&["beacon"])]
+}
+
+fn chain_tests() -> Vec<RpcTest> {
+    tagged(
+        &["chain"],
+        vec![
+            RpcTest::validate(ApiInfo::chain_head_req(), |forest, lotus| {
+                forest.epoch().abs_diff(lotus.epoch()) < 10
+            }),
+            RpcTest::identity(ApiInfo::chain_get_genesis_req()),
+        ],
+    )
+}
+
+fn chain_tests_with_tipset(shared_tipset: &Tipset) -> Vec<RpcTest> {
+    let shared_block = shared_tipset.min_ticket_block();
+
+    let tests = vec![
+        RpcTest::identity(ApiInfo::chain_get_block_req(*shared_block.cid())),
+        RpcTest::identity(ApiInfo::chain_get_tipset_by_height_req(
+            shared_tipset.epoch(),
+            Default::default(),
+        )),
+        RpcTest::identity(ApiInfo::chain_get_tipset_after_height_req(
+            shared_tipset.epoch(),
+            Default::default(),
+        )),
+        RpcTest::identity(ApiInfo::chain_get_tipset_req(shared_tipset.key().clone())),
+        RpcTest::identity(ApiInfo::chain_read_obj_req(*shared_block.cid())),
+        RpcTest::identity(ApiInfo::chain_has_obj_req(*shared_block.cid())),
+        RpcTest::identity(ApiInfo::chain_get_path_req(
+            shared_tipset.key().clone(),
+            shared_tipset.parents().clone(),
+        )),
+    ];
+    tagged(&["chain"], tests)
+}
+
+fn mpool_tests() -> Vec<RpcTest> {
+    vec![RpcTest::basic(ApiInfo::mpool_pending_req(vec![])).with_tags(&["mpool"])]
+}
+
+fn net_tests() -> Vec<RpcTest> {
+    let peer_id = calibnet::DEFAULT_BOOTSTRAP
+        .last()
+        .expect("No bootstrap peers found - bootstrap file is empty or corrupted")
+        .to_string()
+        .rsplit_once('/')
+        .expect("No peer id found - address is not in the expected format")
+        .1
+        .to_string();
+
+    // More net commands should be tested. Tracking issue:
+    // https://github.com/ChainSafe/forest/issues/3639
+    let tests = vec![
+        RpcTest::basic(ApiInfo::net_addrs_listen_req()),
+        RpcTest::basic(ApiInfo::net_peers_req()),
+        RpcTest::identity(ApiInfo::net_listening_req()),
+        RpcTest::basic(ApiInfo::net_agent_version_req(peer_id.clone())),
+        RpcTest::basic(ApiInfo::net_find_peer_req(peer_id.clone())),
+        RpcTest::basic(ApiInfo::net_ping_req(peer_id.clone())),
+        RpcTest::basic(ApiInfo::net_connectedness_req(peer_id)),
+        RpcTest::basic(ApiInfo::net_block_list_req()),
+        RpcTest::basic(ApiInfo::net_pubsub_scores_req()),
+        RpcTest::basic(ApiInfo::net_info_req())
+            .ignore("Not implemented in Lotus. Why do we even have this method?"),
+        RpcTest::basic(ApiInfo::net_auto_nat_status_req()),
+        RpcTest::identity(ApiInfo::net_version_req()),
+        // The numbers won't match Lotus, only the response shapes are compared
+        RpcTest::basic(ApiInfo::net_bandwidth_stats_req()),
+        RpcTest::basic(ApiInfo::net_bandwidth_stats_by_peer_req()),
+        RpcTest::basic(ApiInfo::net_bandwidth_stats_by_protocol_req()),
+    ];
+    tagged(&["net"], tests)
+}
+
+fn node_tests() -> Vec<RpcTest> {
+    vec![
+        // This is a v1 RPC call, so is sent to `/rpc/v1`
+        RpcTest::basic(ApiInfo::node_status_req()).with_tags(&["node"]),
+    ]
+}
+
+fn state_tests(shared_tipset: &Tipset) -> Vec<RpcTest> {
+    let shared_block = shared_tipset.min_ticket_block();
+    let tests = vec![
+        RpcTest::identity(ApiInfo::state_network_name_req()),
+        RpcTest::identity(ApiInfo::state_get_actor_req(
+            Address::SYSTEM_ACTOR,
+            shared_tipset.key().clone(),
+        )),
+        RpcTest::identity(ApiInfo::state_get_randomness_from_tickets_req(
+            shared_tipset.key().into(),
+            DomainSeparationTag::ElectionProofProduction,
+            shared_tipset.epoch(),
+            "dead beef".as_bytes().to_vec(),
+        )),
+        RpcTest::identity(ApiInfo::state_get_randomness_from_beacon_req(
+            shared_tipset.key().into(),
+            DomainSeparationTag::ElectionProofProduction,
+            shared_tipset.epoch(),
+            "dead beef".as_bytes().to_vec(),
+        )),
+        RpcTest::identity(ApiInfo::state_read_state_req(
+            Address::SYSTEM_ACTOR,
+            shared_tipset.key().into(),
+        )),
+        RpcTest::identity(ApiInfo::state_read_state_req(
+            Address::SYSTEM_ACTOR,
+            Default::default(),
+        )),
+        RpcTest::identity(ApiInfo::state_miner_active_sectors_req(
+            shared_block.miner_address,
+            shared_tipset.key().into(),
+        ))
+        .with_tags(&["state", "miner"]),
+        RpcTest::identity(ApiInfo::state_lookup_id_req(
+            shared_block.miner_address,
+            shared_tipset.key().into(),
+        )),
+        // This should return `Address::new_id(0xdeadbeef)`
+        RpcTest::identity(ApiInfo::state_lookup_id_req(
+            Address::new_id(0xdeadbeef),
+            shared_tipset.key().into(),
+        )),
+        RpcTest::identity(ApiInfo::state_network_version_req(
+            shared_tipset.key().into(),
+        )),
+        RpcTest::identity(ApiInfo::state_list_miners_req(shared_tipset.key().into()))
+            .with_tags(&["state", "miner"]),
+        RpcTest::identity(ApiInfo::state_sector_get_info_req(
+            shared_block.miner_address,
+            101,
+            shared_tipset.key().into(),
+        ))
+        .with_tags(&["state", "miner"]),
+        // Unpaged calls fail on networks with too many deals, like mainnet
+        RpcTest::identity(ApiInfo::state_market_deals_req(
+            shared_tipset.key().into(),
+            None,
+        ))
+        .with_timeout(Duration::from_secs(300))
+        .with_tags(&["state", "market", "slow"]),
+        validate_market_deals_page(
+            shared_tipset.key().into(),
+            MarketDealsFilter {
+                offset: 10,
+                limit: Some(100),
+                deal_id_range: None,
+            },
+        )
+        .with_timeout(Duration::from_secs(300))
+        .with_tags(&["state", "market", "slow"]),
+        validate_market_deals_page(
+            shared_tipset.key().into(),
+            MarketDealsFilter {
+                deal_id_range: Some((1000, 2000)),
+                ..Default::default()
+            },
+        )
+        .with_timeout(Duration::from_secs(300))
+        .with_tags(&["state", "market", "slow"]),
+        RpcTest::identity(ApiInfo::msig_get_available_balance_req(
+            Address::new_id(18101), // msig address id
+            shared_tipset.key().into(),
+        ))
+        .with_tags(&["msig"]),
+        RpcTest::identity(ApiInfo::msig_get_pending_req(
+            Address::new_id(18101), // msig address id
+            shared_tipset.key().into(),
+        ))
+        .with_tags(&["msig"]),
+    ];
+    tagged(&["state"], tests)
+}
+
+// This address has been funded by the calibnet faucet and the private keys
+// has been discarded. It should always have a non-zero balance.
+const KNOWN_WALLET: &str = "t1c4dkec3qhrnrsa4mccy7qntkyq2hhsma4sq7lui";
+
+fn wallet_tests() -> Vec<RpcTest> {
+    let known_wallet = Address::from_str(KNOWN_WALLET).unwrap();
+    // "Hello world!" signed with the above address:
+    let signature = "44364ca78d85e53dda5ac6f719a4f2de3261c17f58558ab7730f80c478e6d43775244e7d6855afad82e4a1fd6449490acfa88e3fcfe7c1fe96ed549c100900b400";
+    let text = "Hello world!".as_bytes().to_vec();
+    let sig_bytes = hex::decode(signature).unwrap();
+    let signature = match known_wallet.protocol() {
+        Protocol::Secp256k1 => Signature::new_secp256k1(sig_bytes),
+        Protocol::BLS => Signature::new_bls(sig_bytes),
+        _ => panic!("Invalid signature (must be bls or secp256k1)"),
+    };
+
+    let tests = vec![
+        RpcTest::identity(ApiInfo::wallet_balance_req(known_wallet.to_string())),
+        RpcTest::identity(ApiInfo::wallet_validate_address_req(
+            known_wallet.to_string(),
+        )),
+        RpcTest::identity(ApiInfo::wallet_verify_req(known_wallet, text, signature)),
+        // These methods require write access in Lotus
+        RpcTest::basic(ApiInfo::wallet_default_address_req()),
+        RpcTest::basic(ApiInfo::wallet_list_req()),
+        RpcTest::basic(ApiInfo::wallet_has_req(known_wallet.to_string())),
+    ];
+    tagged(&["wallet"], tests)
+}
+
+fn eth_tests() -> Vec<RpcTest> {
+    let tests = vec![
+        RpcTest::identity(ApiInfo::eth_accounts_req()),
+        RpcTest::validate(ApiInfo::eth_block_number_req(), |forest, lotus| {
+            fn parse_hex(inp: &str) -> i64 {
+                let without_prefix = inp.trim_start_matches("0x");
+                i64::from_str_radix(without_prefix, 16).unwrap_or_default()
+            }
+            parse_hex(&forest).abs_diff(parse_hex(&lotus)) < 10
+        }),
+        RpcTest::identity(ApiInfo::eth_chain_id_req()),
+        // There is randomness in the result of this API
+        RpcTest::basic(ApiInfo::eth_gas_price_req()),
+        RpcTest::basic(ApiInfo::eth_syncing_req()),
+        RpcTest::identity(ApiInfo::eth_get_balance_req(
+            EthAddress::from_str("0xff38c072f286e3b20b3954ca9f99c05fbecc64aa").unwrap(),
+            BlockNumberOrHash::from_predefined(Predefined::Latest),
+        )),
+        RpcTest::identity(ApiInfo::eth_get_balance_req(
+            EthAddress::from_str("0xff38c072f286e3b20b3954ca9f99c05fbecc64aa").unwrap(),
+            BlockNumberOrHash::from_predefined(Predefined::Pending),
+        )),
+    ];
+    tagged(&["eth"], tests)
+}
+
+fn eth_tests_with_tipset(shared_tipset: &Tipset) -> Vec<RpcTest> {
+    let tests = vec![
+        RpcTest::identity(ApiInfo::eth_get_balance_req(
+            EthAddress::from_str("0xff38c072f286e3b20b3954ca9f99c05fbecc64aa").unwrap(),
+            BlockNumberOrHash::from_block_number(shared_tipset.epoch()),
+        )),
+        RpcTest::identity(ApiInfo::eth_get_balance_req(
+            EthAddress::from_str("0xff000000000000000000000000000000000003ec").unwrap(),
+            BlockNumberOrHash::from_block_number(shared_tipset.epoch()),
+        )),
+    ];
+    tagged(&["eth"], tests)
+}
+
+fn gas_tests_with_tipset(shared_tipset: &Tipset) -> Vec<RpcTest> {
+    let known_wallet = Address::from_str(KNOWN_WALLET).unwrap();
+    let message = Message {
+        from: known_wallet,
+        to: known_wallet,
+        value: TokenAmount::from_atto(1),
+        method_num: METHOD_SEND,
+        ..Default::default()
+    };
+
+    let tests = vec![RpcTest::validate(
+        ApiInfo::gas_estimate_message_gas_req(message, None, shared_tipset.key().into()),
+        |forest, lotus| {
+            // Fee caps and premiums follow the chain head and premiums are
+            // noisy, only the gas limits are comparable. A limit well below
+            // the one of Lotus runs the message out of gas.
+            let fields = |msg: &Message| (msg.from, msg.to, msg.value.clone(), msg.method_num);
+            fields(&forest) == fields(&lotus)
+                && forest.gas_limit * 10 >= lotus.gas_limit * 9
+                && forest.gas_limit <= lotus.gas_limit * 2
+        },
+    )];
+    tagged(&["gas"], tests)
+}
+
+// Extract tests that use chain-specific data such as block CIDs or message
+// CIDs, from the tipsets sampled according to `sampling`.
+/// Replays a message of `tipset` there, and in the tipset found by searching
+/// for it.
+fn state_replay_tests(tipset: &Tipset, msg_cid: Cid) -> Vec<RpcTest> {
+    let tests = vec![
+        RpcTest::identity(ApiInfo::state_replay_req(tipset.key().into(), msg_cid)),
+        RpcTest::identity(ApiInfo::state_replay_req(ApiTipsetKey(None), msg_cid)),
+    ];
+    tagged(&["state", "slow"], tests)
+}
+
+fn snapshot_tests(store: Arc<ManyCar>, sampling: &TipsetSampling) -> anyhow::Result<Vec<RpcTest>> {
+    let mut tests = vec![];
+    let shared_tipset = store.heaviest_tipset()?;
+    let root_tsk = shared_tipset.key();
+    tests.extend(chain_tests_with_tipset(&shared_tipset));
+    tests.extend(state_tests(&shared_tipset));
+    tests.extend(eth_tests_with_tipset(&shared_tipset));
+    tests.extend(gas_tests_with_tipset(&shared_tipset));
+
+    // Not easily verifiable by using addresses extracted from blocks as most of those yield `null`
+    // for both Lotus and Forest. Therefore the actor addresses are hardcoded to values that allow
+    // for API compatibility verification.
+    tests.push(
+        RpcTest::identity(ApiInfo::state_verified_client_status(
+            Address::VERIFIED_REGISTRY_ACTOR,
+            shared_tipset.key().into(),
+        ))
+        .with_tags(&["state"]),
+    );
+    tests.push(
+        RpcTest::identity(ApiInfo::state_verified_client_status(
+            Address::DATACAP_TOKEN_ACTOR,
+            shared_tipset.key().into(),
+        ))
+        .with_tags(&["state"]),
+    );
+
+    let genesis = shared_tipset.genesis(&store)?;
+    let network = NetworkChain::from_genesis_or_devnet(genesis.cid(), None, || {
+        read_network_name(&store, &genesis)
+    })?;
+    let policy = ChainConfig::from_chain(&network).policy;
+
+    tests.extend(miner_base_info_tests_around_null_rounds(
+        &store,
+        &shared_tipset,
+        &policy,
+    )?);
+
+    for miner in miners_with_faults(&store, &shared_tipset, &policy, 3)? {
+        let miner_tests = vec![
+            RpcTest::identity(ApiInfo::state_miner_faults_req(
+                miner,
+                shared_tipset.key().into(),
+            )),
+            RpcTest::identity(ApiInfo::state_miner_faults_by_deadline_req(
+                miner,
+                shared_tipset.key().into(),
+            ))
+            .ignore("Not implemented in Lotus"),
+            RpcTest::identity(ApiInfo::state_miner_recoveries_req(
+                miner,
+                shared_tipset.key().into(),
+            )),
+            RpcTest::identity(ApiInfo::state_miner_sector_count_req(
+                miner,
+                shared_tipset.key().into(),
+            )),
+        ];
+        tests.extend(tagged(&["state", "miner"], miner_tests));
+    }
+
+    let mut seen = CidHashSet::default();
+    for tipset in sample_tipsets(&store, &shared_tipset, sampling)? {
+        tests.push(
+            RpcTest::identity(ApiInfo::chain_get_messages_in_tipset_req(
+                tipset.key().clone(),
+            ))
+            .with_tags(&["chain"]),
+        );
+        for block in tipset.block_headers() {
+            tests.push(
+                RpcTest::identity(ApiInfo::chain_get_block_messages_req(*block.cid()))
+                    .with_tags(&["chain"]),
+            );
+            tests.push(
+                RpcTest::identity(ApiInfo::chain_get_parent_messages_req(*block.cid()))
+                    .with_tags(&["chain"]),
+            );
+            tests.push(
+                RpcTest::identity(ApiInfo::chain_get_parent_receipts_req(*block.cid()))
+                    .with_tags(&["chain"]),
+            );
+            tests.push(
+                RpcTest::identity(ApiInfo::state_miner_active_sectors_req(
+                    block.miner_address,
+                    root_tsk.into(),
+                ))
+                .with_tags(&["state", "miner"]),
+            );
+
+            let (bls_messages, secp_messages) = crate::chain::store::block_messages(&store, block)?;
+            for msg in bls_messages {
+                if seen.insert(msg.cid()?) {
+                    tests.push(
+                        RpcTest::identity(ApiInfo::chain_get_message_req(msg.cid()?))
+                            .with_tags(&["chain"]),
+                    );
+                    tests.push(
+                        RpcTest::identity(ApiInfo::state_account_key_req(
+                            msg.from(),
+                            root_tsk.into(),
+                        ))
+                        .with_tags(&["state"]),
+                    );
+                    tests.push(
+                        RpcTest::identity(ApiInfo::state_account_key_req(
+                            msg.from(),
+                            Default::default(),
+                        ))
+                        .with_tags(&["state"]),
+                    );
+                    tests.push(
+                        RpcTest::identity(ApiInfo::state_lookup_id_req(
+                            msg.from(),
+                            root_tsk.into(),
+                        ))
+                        .with_tags(&["state"]),
+                    );
+                    tests.push(
+                        validate_message_lookup(ApiInfo::state_wait_msg_req(msg.cid()?, 0))
+                            .with_timeout(Duration::from_secs(30))
+                            .with_tags(&["state", "slow"]),
+                    );
+                    tests.push(
+                        validate_message_lookup(ApiInfo::state_search_msg_req(msg.cid()?))
+                            .ignore("Not implemented yet")
+                            .with_tags(&["state", "slow"]),
+                    );
+                    tests.push(
+                        validate_message_lookup(ApiInfo::state_search_msg_limited_req(
+                            msg.cid()?,
+                            800,
+                        ))
+                        .ignore("Not implemented yet")
+                        .with_tags(&["state", "slow"]),
+                    );
+                    tests.push(
+                        RpcTest::identity(ApiInfo::state_list_messages_req(
+                            MessageFilter {
+                                from: Some(msg.from()),
+                                to: Some(msg.to()),
+                            },
+                            root_tsk.into(),
+                            shared_tipset.epoch(),
+                        ))
+                        .with_tags(&["state", "slow"]),
+                    );
+                    tests.push(
+                        validate_message_lookup(ApiInfo::state_search_msg_req(msg.cid()?))
+                            .with_tags(&["state", "slow"]),
+                    );
+                    tests.push(
+                        validate_message_lookup(ApiInfo::state_search_msg_limited_req(
+                            msg.cid()?,
+                            800,
+                        ))
+                        .with_tags(&["state", "slow"]),
+                    );
+                    tests.extend(state_replay_tests(&tipset, msg.cid()?));
+                }
+            }
+            for msg in secp_messages {
+                if seen.insert(msg.cid()?) {
+                    tests.push(
+                        RpcTest::identity(ApiInfo::chain_get_message_req(msg.cid()?))
+                            .with_tags(&["chain"]),
+                    );
+                    tests.push(
+                        RpcTest::identity(ApiInfo::state_account_key_req(
+                            msg.from(),
+                            root_tsk.into(),
+                        ))
+                        .with_tags(&["state"]),
+                    );
+                    tests.push(
+                        RpcTest::identity(ApiInfo::state_account_key_req(
+                            msg.from(),
+                            Default::default(),
+                        ))
+                        .with_tags(&["state"]),
+                    );
+                    tests.push(
+                        RpcTest::identity(ApiInfo::state_lookup_id_req(
+                            msg.from(),
+                            root_tsk.into(),
+                        ))
+                        .with_tags(&["state"]),
+                    );
+                    tests.push(
+                        validate_message_lookup(ApiInfo::state_wait_msg_req(msg.cid()?, 0))
+                            .with_timeout(Duration::from_secs(30))
+                            .with_tags(&["state", "slow"]),
+                    );
+                    tests.push(
+                        validate_message_lookup(ApiInfo::state_search_msg_req(msg.cid()?))
+                            .with_tags(&["state", "slow"]),
+                    );
+                    tests.push(
+                        validate_message_lookup(ApiInfo::state_search_msg_limited_req(
+                            msg.cid()?,
+                            800,
+                        ))
+                        .with_tags(&["state", "slow"]),
+                    );
+                    tests.push(
+                        RpcTest::basic(ApiInfo::mpool_get_nonce_req(msg.from()))
+                            .with_tags(&["mpool"]),
+                    );
+                    tests.extend(state_replay_tests(&tipset, msg.cid()?));
+                    tests.push(
+                        RpcTest::identity(ApiInfo::state_list_messages_req(
+                            MessageFilter {
+                                from: None,
+                                to: Some(msg.to()),
+                            },
+                            root_tsk.into(),
+                            shared_tipset.epoch(),
+                        ))
+                        .with_tags(&["state", "slow"]),
+                    );
+                    tests.push(
+                        RpcTest::identity(ApiInfo::state_list_messages_req(
+                            MessageFilter {
+                                from: Some(msg.from()),
+                                to: None,
+                            },
+                            root_tsk.into(),
+                            shared_tipset.epoch(),
+                        ))
+                        .with_tags(&["state", "slow"]),
+                    );
+                    tests.push(
+                        RpcTest::identity(ApiInfo::state_list_messages_req(
+                            MessageFilter {
+                                from: None,
+                                to: None,
+                            },
+                            root_tsk.into(),
+                            shared_tipset.epoch(),
+                        ))
+                        .with_tags(&["state", "slow"]),
+                    );
+
+                    if !msg.params().is_empty() {
+                        tests.push(RpcTest::identity(ApiInfo::state_decode_params_req(
+                            msg.to(),
+                            msg.method_num(),
+                            msg.params().to_vec(),
+                            root_tsk.into(),
+                        )).ignore("Difficult to implement. Tracking issue: https://github.com/ChainSafe/forest/issues/3769").with_tags(&["state"]));
+                    }
+                }
+            }
+            tests.push(
+                RpcTest::identity(ApiInfo::state_miner_info_req(
+                    block.miner_address,
+                    tipset.key().into(),
+                ))
+                .with_tags(&["state", "miner"]),
+            );
+            tests.push(
+                RpcTest::identity(ApiInfo::state_miner_power_req(
+                    block.miner_address,
+                    tipset.key().into(),
+                ))
+                .with_tags(&["state", "miner"]),
+            );
+            tests.push(
+                RpcTest::identity(ApiInfo::state_miner_deadlines_req(
+                    block.miner_address,
+                    tipset.key().into(),
+                ))
+                .with_tags(&["state", "miner"]),
+            );
+            tests.push(
+                RpcTest::identity(ApiInfo::state_miner_proving_deadline_req(
+                    block.miner_address,
+                    tipset.key().into(),
+                ))
+                .with_tags(&["state", "miner"]),
+            );
+            tests.push(
+                RpcTest::identity(ApiInfo::state_miner_available_balance_req(
+                    block.miner_address,
+                    tipset.key().into(),
+                ))
+                .with_tags(&["state", "miner"]),
+            );
+            tests.push(
+                RpcTest::identity(ApiInfo::state_miner_faults_req(
+                    block.miner_address,
+                    tipset.key().into(),
+                ))
+                .with_tags(&["state", "miner"]),
+            );
+            tests.push(
+                RpcTest::identity(ApiInfo::miner_get_base_info_req(
+                    block.miner_address,
+                    block.epoch,
+                    tipset.key().into(),
+                ))
+                .with_tags(&["miner"]),
+            );
+            tests.push(
+                RpcTest::identity(ApiInfo::state_miner_recoveries_req(
+                    block.miner_address,
+                    tipset.key().into(),
+                ))
+                .with_tags(&["state", "miner"]),
+            );
+            tests.push(
+                RpcTest::identity(ApiInfo::state_miner_sector_count_req(
+                    block.miner_address,
+                    tipset.key().into(),
+                ))
+                .with_tags(&["state", "miner"]),
+            );
+        }
+        tests.push(
+            RpcTest::identity(ApiInfo::state_circulating_supply_req(tipset.key().into()))
+                .with_tags(&["state"]),
+        );
+        tests.push(
+            RpcTest::identity(ApiInfo::state_vm_circulating_supply_internal_req(
+                tipset.key().into(),
+            ))
+            .with_tags(&["state"]),
+        );
+
+        for block in tipset.block_headers() {
+            let (bls_messages, secp_messages) = crate::chain::store::block_messages(&store, block)?;
+            for msg in secp_messages {
+                tests.push(
+                    RpcTest::identity(ApiInfo::state_call_req(
+                        msg.message().clone(),
+                        shared_tipset.key().into(),
+                    ))
+                    .with_tags(&["state", "slow"]),
+                );
+            }
+            for msg in bls_messages {
+                tests.push(
+                    RpcTest::identity(ApiInfo::state_call_req(
+                        msg.clone(),
+                        shared_tipset.key().into(),
+                    ))
+                    .with_tags(&["state", "slow"]),
+                );
+            }
+        }
+
+        // Get deals
+        let deals = {
+            let state = StateTree::new_from_root(store.clone(), tipset.parent_state())?;
+            let actor = state
+                .get_actor(&Address::MARKET_ACTOR)?
+                .context("Market actor not found")?;
+            let market_state = market::State::load(&store, actor.code, actor.state)?;
+            let proposals = market_state.proposals(&store)?;
+            let mut deals = vec![];
+            proposals.for_each(|deal_id, _| {
+                deals.push(deal_id);
+                Ok(())
+            })?;
+            deals
+        };
+
+        // Take 5 deals from each tipset
+        for deal in deals.into_iter().take(5) {
+            tests.push(
+                RpcTest::identity(ApiInfo::state_market_storage_deal_req(
+                    deal,
+                    tipset.key().into(),
+                ))
+                .with_tags(&["state", "market"]),
+            );
+        }
+    }
+    Ok(tests)
+}
+
+/// Up to `limit` miners with faulty sectors in the state of `tipset`.
+fn miners_with_faults(
+    store: &Arc<ManyCar>,
+    tipset: &Tipset,
+    policy: &Policy,
+    limit: usize,
+) -> anyhow::Result<Vec<Address>> {
+    let state = StateTree::new_from_root(store.clone(), tipset.parent_state())?;
+    let power_actor = state
+        .get_actor(&Address::POWER_ACTOR)?
+        .context("Power actor not found")?;
+    let power_state = power::State::load(store, power_actor.code, power_actor.state)?;
+    let mut miners = vec![];
+    for miner in power_state.list_all_miners(store)? {
+        let miner = Address::from(miner);
+        let Some(actor) = state.get_actor(&miner)? else {
+            continue;
+        };
+        let miner_state = miner::State::load(store, actor.code, actor.state)?;
+        let mut faulty = false;
+        miner_state.for_each_deadline(policy, store, |_, deadline| {
+            deadline.for_each(store, |_, partition| {
+                faulty |= !partition.faulty_sectors().is_empty();
+                Ok(())
+            })
+        })?;
+        if faulty {
+            miners.push(miner);
+            if miners.len() == limit {
+                break;
+            }
+        }
+    }
+    Ok(miners)
+}
+
+/// `MinerGetBaseInfo` tests around the null rounds of the chain of `head`, for
+/// the miners of the tipsets following them: at the newest null rounds, and at
+/// the first round whose lookback epoch is a null round.
+fn miner_base_info_tests_around_null_rounds(
+    store: &Arc<ManyCar>,
+    head: &Tipset,
+    policy: &Policy,
+) -> anyhow::Result<Vec<RpcTest>> {
+    let finality = policy.chain_finality;
+    let index = ChainIndex::new(store.clone());
+    let head = Arc::new(head.clone());
+    let test = |miner, round, tsk: &TipsetKey| {
+        RpcTest::identity(ApiInfo::miner_get_base_info_req(miner, round, tsk.into()))
+            .with_tags(&["miner"])
+    };
+
+    let mut tests = vec![];
+    let (mut after_null_rounds, mut lookback_null_round) = (false, false);
+    for (child, parent) in head
+        .as_ref()
+        .clone()
+        .chain(store.clone())
+        .take(2 * finality as usize)
+        .tuple_windows()
+    {
+        if parent.epoch() + 1 == child.epoch() {
+            continue;
+        }
+        let miners = child
+            .block_headers()
+            .iter()
+            .map(|block| block.miner_address)
+            .collect_vec();
+        if !after_null_rounds {
+            after_null_rounds = true;
+            // The first null round, and the round the tipset was mined at
+            for round in [parent.epoch() + 1, child.epoch()] {
+                for miner in &miners {
+                    tests.push(test(*miner, round, parent.key()));
+                }
+            }
+        }
+        let round = parent.epoch() + 1 + finality;
+        if !lookback_null_round && round <= head.epoch() {
+            lookback_null_round = true;
+            let base =
+                index.tipset_by_height(round - 1, head.clone(), ResolveNullTipset::TakeOlder)?;
+            for miner in &miners {
+                tests.push(test(*miner, round, base.key()));
+            }
+        }
+        if after_null_rounds && lookback_null_round {
+            break;
+        }
+    }
+    Ok(tests)
+}
+
+/// Fetches the `OpenRPC` documents of both API versions of a node, which
+/// describe the methods it registers.
+async fn registered_methods(
+    api: &ApiInfo,
+    use_websocket: bool,
+) -> anyhow::Result<Vec<DiscoverResult>> {
+    let mut schemas = vec![];
+    for request in [ApiInfo::discover_req(), RpcRequest::new_v1(DISCOVER, ())] {
+        let schema = if use_websocket {
+            api.ws_call(request).await
+        } else {
+            api.call(request).await
+        };
+        schemas.push(schema.context("could not fetch the OpenRPC document of Forest")?);
+    }
+    Ok(schemas)
+}
+
+/// Methods described by the schemas for which there is no test, sorted.
+fn uncovered_methods(schemas: &[DiscoverResult], tests: &[RpcTest]) -> Vec<String> {
+    let tested = tests
+        .iter()
+        .map(|test| test.request.method_name)
+        .collect::<BTreeSet<_>>();
+    schemas
+        .iter()
+        .flat_map(DiscoverResult::method_names)
+        .filter(|method| !tested.contains(method))
+        .map(String::from)
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+fn derive_protocol(forest: &ApiInfo, lotus: &ApiInfo) -> anyhow::Result<CommunicationProtocol> {
+    let a = forest.multiaddr.clone().pop().map(|p| p.tag());
+    let b = lotus.multiaddr.clone().pop().map(|p| p.tag());
+
+    // Both `ApiInfo` should end with a supported tag of the same protocol to be valid. Whether
+    // TLS is used may differ, e.g. `/https` and `/http` are both HTTP.
+    let parse = |tag: Option<&str>| tag.and_then(|it| CommunicationProtocol::from_str(it).ok());
+    match (parse(a), parse(b)) {
+        (Some(x), Some(y)) if x == y => Ok(x),
+        _ => bail!(
+            "communication protocols mismatch: {:?} (Forest) is different from {:?} (Lotus)",
+            a,
+            b
+        ),
+    }
+}
+
+/// Compare RPC providers. The first two are labeled `forest` and `lotus`,
+/// but other nodes may be used (such as `venus`), and further reference nodes
+/// may be given. The responses of each test are grouped by agreement, the
+/// nodes outside of the largest group are marked as incorrect. On a tie, the
+/// `forest` node is assumed to be incorrect, so that with two nodes `lotus`
+/// is the reference.
+///
+/// If snapshot files are provided, these files will be used to generate
+/// additional tests.
+///
+/// Example output:
+/// ```markdown
+/// | RPC Method                        | Forest              | Lotus         | Venus         |
+/// |-----------------------------------|---------------------|---------------|---------------|
+/// | Filecoin.ChainGetBlock            | Valid               | Valid         | Valid         |
+/// | Filecoin.ChainGetGenesis          | Valid               | Valid         | Valid         |
+/// | Filecoin.ChainGetMessage (67)     | InternalServerError | Valid         | Valid         |
+/// ```
+/// The number after a method name indicates how many times an RPC call was tested.
+async fn compare_apis(
+    nodes: Vec<Node>,
+    snapshot_files: Vec<PathBuf>,
+    config: ApiTestFlags,
+) -> anyhow::Result<()> {
+    let (forest, references) = nodes.split_first().context("no nodes to compare")?;
+    // Every reference node must use the protocol of the Forest node
+    let mut communication = None;
+    for reference in references {
+        let protocol = derive_protocol(&forest.api, &reference.api)
+            .with_context(|| format!("cannot compare with {}", reference.name))?;
+        communication = Some(protocol);
+    }
+    let communication = communication.context("no reference node to compare against")?;
+
+    let mut tests = vec![];
+
+    tests.extend(common_tests());
+    tests.extend(auth_tests());
+    tests.extend(beacon_tests());
+    tests.extend(chain_tests());
+    tests.extend(mpool_tests());
+    tests.extend(net_tests());
+    tests.extend(node_tests());
+    tests.extend(wallet_tests());
+    tests.extend(eth_tests());
+
+    if !snapshot_files.is_empty() {
+        let store = Arc::new(ManyCar::try_from(snapshot_files)?);
+        tests.extend(snapshot_tests(store, &config.tipset_sampling)?);
+    }
+
+    let use_websocket = communication == CommunicationProtocol::Ws;
+    if use_websocket {
+        tests.extend(websocket_tests(config.notification_limits));
+    }
+
+    tests.sort_by_key(|test| test.request.method_name);
+
+    let uncovered = match registered_methods(&forest.api, use_websocket).await {
+        Ok(schemas) => uncovered_methods(&schemas, &tests),
+        Err(e) if !config.fail_on_uncovered => {
+            warn!("Cannot list the methods not covered by the tests: {e:#}");
+            vec![]
+        }
+        Err(e) => return Err(e),
+    };
+
+    run_tests(tests, &nodes, &uncovered, &config, use_websocket).await?;
+    ensure!(
+        !config.fail_on_uncovered || uncovered.is_empty(),
+        "{} methods are not covered by the tests",
+        uncovered.len()
+    );
+    Ok(())
+}
+
+/// Store of the snapshots, saving their indexes in `index_cache_dir` if set.
+fn new_many_car<WriterT>(writer: WriterT, index_cache_dir: Option<&Path>) -> ManyCar<WriterT> {
+    match index_cache_dir {
+        Some(dir) => ManyCar::new(writer).with_index_cache_dir(dir.to_owned()),
+        None => ManyCar::new(writer),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn start_offline_server(
+    snapshot_files: Vec<PathBuf>,
+    chain: NetworkChain,
+    rpc_port: u16,
+    rpc_data_dir: PathBuf,
+    auto_download_snapshot: bool,
+    tls_config: Option<Arc<ServerConfig>>,
+    rpc_unix_socket: Option<PathBuf>,
+    metrics_address: Option<SocketAddr>,
+    height: Option<ChainEpoch>,
+    genesis: Option<PathBuf>,
+    persist_db: bool,
+    enable_writes: bool,
+    no_import: bool,
+    index_cache_dir: Option<PathBuf>,
+    simulate_epochs: Option<Duration>,
+) -> anyhow::Result<()> {
+    info!("Configuring Offline RPC Server");
+    let client = Client::default();
+    let db_path = client.data_dir.as_path().join(rpc_data_dir);
+    // Declared first so that the database is closed by the time it runs,
+    // including when unwinding from a panic
+    let _cleanup = (!persist_db).then(|| {
+        scopeguard::guard(db_path.clone(), |db_path| {
+            info!("Cleaning offline RPC data directory: {}", db_path.display());
+            if let Err(e) = std::fs::remove_dir_all(&db_path) {
+                warn!("Failed to clean {}: {e}", db_path.display());
+            }
+        })
+    });
+    let options = OfflineServerOptions {
+        chain,
+        rpc_port,
+        db_path,
+        tls_config,
+        rpc_unix_socket,
+        metrics_address,
+        height,
+        genesis,
+        enable_writes,
+        simulate_epochs,
+        token_exp: client.token_exp,
+    };
+
+    if no_import {
+        let snapshot_files =
+            snapshot_files_or_download(snapshot_files, &options.chain, auto_download_snapshot)
+                .await?;
+        std::fs::create_dir_all(&options.db_path)?;
+        let db_writer = Arc::new(MemoryDB::default());
+        let db = Arc::new(new_many_car(db_writer.clone(), index_cache_dir.as_deref()));
+        db.read_only_files(snapshot_files.into_iter())?;
+        info!("Serving the snapshots with an in-memory write layer");
+        return serve_offline(db, db_writer, options).await;
+    }
+
+    let db_path = &options.db_path;
+    let open_db_writer = || -> anyhow::Result<_> {
+        Ok(Arc::new(BlockstoreWithReadCache::new(
+            ParityDb::open(db_path, &ParityDbConfig::default())?,
+            client.block_cache_size.as_usize(),
+        )))
+    };
+    let mut db_writer = open_db_writer()?;
+    let served = db_writer.read_obj::<ServedSnapshots>(OFFLINE_SNAPSHOTS_KEY)?;
+
+    let snapshot_files = match served.as_ref() {
+        Some(served) if snapshot_files.is_empty() && served.files.iter().all(|f| f.is_file()) => {
+            info!("Serving the snapshots the data directory was populated from");
+            served.files.clone()
+        }
+        _ => snapshot_files,
+    };
+    let snapshot_files =
+        snapshot_files_or_download(snapshot_files, &options.chain, auto_download_snapshot).await?;
+    let mut db = Arc::new(new_many_car(db_writer.clone(), index_cache_dir.as_deref()));
+    db.read_only_files(snapshot_files.iter().cloned())?;
+    let served_head = db.heaviest_tipset()?.key().clone();
+    match served {
+        Some(served) if served.head == served_head => {
+            info!("Reusing offline RPC data directory: {}", db_path.display());
+        }
+        Some(_) => {
+            warn!(
+                "Offline RPC data directory {} was populated from other snapshots, wiping it",
+                db_path.display()
+            );
+            drop((db, db_writer));
+            std::fs::remove_dir_all(db_path)?;
+            db_writer = open_db_writer()?;
+            db = Arc::new(new_many_car(db_writer.clone(), index_cache_dir.as_deref()));
+            db.read_only_files(snapshot_files.iter().cloned())?;
+        }
+        None => {}
+    }
+    db_writer.write_obj(
+        OFFLINE_SNAPSHOTS_KEY,
+        &ServedSnapshots {
+            files: snapshot_files
+                .iter()
+                .map(std::fs::canonicalize)
+                .collect::<Result<_, _>>()?,
+            head: served_head,
+        },
+    )?;
+    serve_offline(db, db_writer, options).await
+}
+
+/// Returns `snapshot_files`, or downloads the latest snapshot of `chain` if
+/// there are none.
+async fn snapshot_files_or_download(
+    snapshot_files: Vec<PathBuf>,
+    chain: &NetworkChain,
+    auto_download_snapshot: bool,
+) -> anyhow::Result<Vec<PathBuf>> {
+    if !snapshot_files.is_empty() {
+        return Ok(snapshot_files);
+    }
+    let (snapshot_url, num_bytes, path, _sha256) =
+        crate::cli_shared::snapshot::peek(TrustedVendor::default(), chain)
+            .await
+            .context("couldn't get snapshot size")?;
+    if !auto_download_snapshot {
+        warn!("Automatic snapshot download is disabled.");
+        let message = format!(
+            "Fetch a {} snapshot to the current directory? (denying will exit the program). ",
+            indicatif::HumanBytes(num_bytes)
+        );
+        let have_permission =
+            dialoguer::Confirm::with_theme(&dialoguer::theme::ColorfulTheme::default())
+                .with_prompt(message)
+                .default(false)
+                .interact()
+                .unwrap_or(false);
+        if !have_permission {
+            anyhow::bail!("No snapshot provided, exiting offline RPC setup.");
+        }
+    }
+    info!(
+        "Downloading latest snapshot for {} size {}",
+        chain,
+        indicatif::HumanBytes(num_bytes)
+    );
+    let downloaded_snapshot_path = std::env::current_dir()?.join(path);
+    download_to(&snapshot_url, &downloaded_snapshot_path).await?;
+    info!("Snapshot downloaded");
+    Ok(vec![downloaded_snapshot_path])
+}
+
+/// Settings of the offline RPC server, once its block store is set up.
+struct OfflineServerOptions {
+    chain: NetworkChain,
+    rpc_port: u16,
+    /// Data directory, where the admin token is written
+    db_path: PathBuf,
+    tls_config: Option<Arc<ServerConfig>>,
+    rpc_unix_socket: Option<PathBuf>,
+    metrics_address: Option<SocketAddr>,
+    height: Option<ChainEpoch>,
+    genesis: Option<PathBuf>,
+    enable_writes: bool,
+    simulate_epochs: Option<Duration>,
+    token_exp: chrono::Duration,
+}
+
+/// Serves the chain of the snapshots in `db`, whose blocks written while
+/// serving go to `db_writer`.
+async fn serve_offline<W>(
+    db: Arc<ManyCar<Arc<W>>>,
+    db_writer: Arc<W>,
+    options: OfflineServerOptions,
+) -> anyhow::Result<()>
+where
+    W: Blockstore + SettingsStore + IndicesStore + DBStatistics + Send + Sync + 'static,
+{
+    let OfflineServerOptions {
+        chain,
+        rpc_port,
+        db_path,
+        tls_config,
+        rpc_unix_socket,
+        metrics_address,
+        height,
+        genesis,
+        enable_writes,
+        simulate_epochs,
+        token_exp,
+    } = options;
+    if let Some(metrics_address) = metrics_address {
+        let prometheus_listener = TcpListener::bind(metrics_address)
+            .await
+            .with_context(|| format!("could not bind to {metrics_address}"))?;
+        info!(
+            "Prometheus server started at {}",
+            prometheus_listener.local_addr()?
+        );
+        tokio::spawn(crate::metrics::init_prometheus(
+            prometheus_listener,
+            db_path.clone(),
+            db_writer.clone(),
+        ));
+    }
+
+    let chain_config = Arc::new(ChainConfig {
+        genesis_file: genesis,
+        ..ChainConfig::from_chain(&chain)
+    });
+    chain_config.validate()?;
+    let sync_config = Arc::new(SyncConfig::default());
+    let genesis_header = read_genesis_header(&chain_config, &db).await?;
+    let chain_store = Arc::new(ChainStore::new(
+        db.clone(),
+        db.clone(),
+        db.clone(),
+        chain_config.clone(),
+        genesis_header.clone(),
+    )?);
+    let state_manager = Arc::new(StateManager::new(
+        chain_store.clone(),
+        chain_config,
+        sync_config,
+    )?);
+    let mut head = Arc::new(db.heaviest_tipset()?);
+    if let Some(height) = height {
+        head = chain_store
+            .chain_index
+            .tipset_by_height(height, head, ResolveNullTipset::TakeOlder)
+            .with_context(|| format!("no tipset at epoch {height} in the snapshots"))?;
+        anyhow::ensure!(
+            db.has(head.parent_state())?,
+            "the snapshots have no state for epoch {height}"
+        );
+        info!("Serving the chain at epoch {}", head.epoch());
+    }
+    state_manager.chain_store().set_heaviest_tipset(head)?;
+    if let Some(interval) = simulate_epochs {
+        info!("Simulating an epoch every {interval:?}");
+        let chain_store = chain_store.clone();
+        let block_delay = state_manager.chain_config().block_delay_secs.into();
+        tokio::spawn(async move {
+            if let Err(e) = simulate_epochs_every(&chain_store, interval, block_delay).await {
+                warn!("Stopped simulating epochs: {e:#}");
+            }
+        });
+    }
+
+    let beacon = Arc::new(
+        state_manager
+            .chain_config()
+            .get_beacon_schedule(chain_store.genesis_block_header().timestamp),
+    );
+    let (tipset_send, _) = flume::bounded(5);
+    let (incoming_blocks, _) = tokio::sync::broadcast::channel(1);
+    let network_name = get_network_name_from_genesis(&genesis_header, &state_manager)?;
+    let (message_pool, network_send) =
+        offline_message_pool(&state_manager, network_name.clone(), enable_writes)?;
+    let mut keystore = KeyStore::new(match enable_writes {
+        true => KeyStoreConfig::Persistent(db_path.clone()),
+        false => KeyStoreConfig::Memory,
+    })?;
+    if keystore.get(JWT_IDENTIFIER).is_err() {
+        keystore.put(JWT_IDENTIFIER, generate_priv_key())?;
+    }
+    let token = create_token(
+        ADMIN.iter().map(ToString::to_string).collect(),
+        keystore.get(JWT_IDENTIFIER)?.private_key(),
+        token_exp,
+    )?;
+    info!("Admin token: {token}");
+    let token_path = db_path.join(ADMIN_TOKEN_FILE);
+    std::fs::write(&token_path, token)
+        .with_context(|| format!("could not write {}", token_path.display()))?;
+
+    let rpc_state = RPCState {
+        state_manager,
+        keystore: Arc::new(RwLock::new(keystore)),
+        tokens: Default::default(),
+        mpool: Arc::new(message_pool),
+        bad_blocks: Default::default(),
+        sync_state: Arc::new(parking_lot::RwLock::new(Default::default())),
+        network_send,
+        tipset_send,
+        incoming_blocks,
+        network_name,
+        start_time: chrono::Utc::now(),
+        chain_store,
+        beacon,
+        bandwidth: Default::default(),
+        net_limits: Default::default(),
+        gc: None,
+    };
+    rpc_state.sync_state.write().set_stage(SyncStage::Idle);
+    start_offline_rpc(rpc_state, rpc_port, tls_config, rpc_unix_socket).await
+}
+
+/// Message pool of the offline server, and the sender of its messages to the
+/// network. The server has no peers: with `enable_writes`, messages pushed to
+/// the pool are dropped instead of being gossiped, and other requests are left
+/// unanswered. Otherwise pushing messages fails.
+fn offline_message_pool<DB>(
+    state_manager: &Arc<StateManager<DB>>,
+    network_name: String,
+    enable_writes: bool,
+) -> anyhow::Result<(
+    MessagePool<MpoolRpcProvider<DB>>,
+    flume::Sender<NetworkMessage>,
+)>
+where
+    DB: Blockstore + Send + Sync + 'static,
+{
+    let (network_send, network_receiver) = flume::bounded(5);
+    if enable_writes {
+        tokio::spawn(async move {
+            while let Ok(message) = network_receiver.recv_async().await {
+                if let NetworkMessage::PubsubMessage { topic, .. } = message {
+                    debug!("Not publishing a message to {topic}");
+                }
+            }
+        });
+    } else {
+        drop(network_receiver);
+    }
+    let message_pool = MessagePool::new(
+        MpoolRpcProvider::new(
+            state_manager.chain_store().publisher().clone(),
+            state_manager.clone(),
+        ),
+        network_name,
+        network_send.clone(),
+        Default::default(),
+        state_manager.chain_config().clone(),
+        &mut JoinSet::new(),
+    )?;
+    Ok((message_pool, network_send))
+}
+
+/// Sets a new empty tipset on top of the head as the heaviest one every
+/// `interval`, notifying the subscribers to head changes. Tipsets are
+/// `block_delay` seconds apart.
+async fn simulate_epochs_every<DB: Blockstore>(
+    chain_store: &ChainStore<DB>,
+    interval: Duration,
+    block_delay: u64,
+) -> anyhow::Result<()> {
+    let mut ticker = tokio::time::interval(interval);
+    // The first tick completes immediately
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+        let head = chain_store.heaviest_tipset();
+        let header = simulated_child(chain_store.blockstore(), &head, block_delay)?;
+        persist_objects(chain_store.blockstore(), std::iter::once(&header))?;
+        debug!("Simulated epoch {}", header.epoch);
+        chain_store.set_heaviest_tipset(Arc::new(Tipset::from(header)))?;
+    }
+}
+
+/// Header of a block without messages on top of `parent`, mined by the miner
+/// of its block with the smallest ticket `block_delay` seconds later. The
+/// state of the parent is not computed: the block keeps that of the parent,
+/// and has no receipts.
+fn simulated_child(
+    db: &impl Blockstore,
+    parent: &Tipset,
+    block_delay: u64,
+) -> anyhow::Result<CachingBlockHeader> {
+    let template = parent.min_ticket_block();
+    Ok(CachingBlockHeader::new(RawBlockHeader {
+        parents: parent.key().clone(),
+        // Heavier than the parent, so that the chain grows heavier
+        weight: parent.weight() + 1,
+        epoch: parent.epoch() + 1,
+        state_root: *parent.parent_state(),
+        message_receipts: Amtv0::new_from_iter(db, Vec::<Cid>::new())?,
+        messages: TipsetValidator::compute_msg_root(db, &[], &[])?,
+        bls_aggregate: None,
+        timestamp: parent.min_timestamp() + block_delay,
+        signature: None,
+        ..RawBlockHeader::clone(template)
+    }))
+}
+
+/// File of the offline RPC data directory the administrator token is written to
+const ADMIN_TOKEN_FILE: &str = "admin_token";
+
+/// Snapshots an offline RPC data directory was populated from, recorded in its
+/// settings store under [`OFFLINE_SNAPSHOTS_KEY`].
+#[derive(Serialize, Deserialize)]
+struct ServedSnapshots {
+    files: Vec<PathBuf>,
+    /// Heaviest tipset of the snapshots, which identifies them
+    head: TipsetKey,
+}
+
+pub async fn start_offline_rpc<DB>(
+    state: RPCState<DB>,
+    rpc_port: u16,
+    tls_config: Option<Arc<ServerConfig>>,
+    rpc_unix_socket: Option<PathBuf>,
+) -> anyhow::Result<()>
+where
+    DB: Blockstore + Send + Sync + 'static,
+{
+    info!("Starting offline RPC Server");
+    let rpc_address = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), rpc_port);
+    let rpc_listener = TcpListener::bind(rpc_address)
+        .await
+        .with_context(|| format!("failed to bind RPC to {rpc_address}"))?;
+    info!("RPC server listening at {}", rpc_listener.local_addr()?);
+    let forest_version = FOREST_VERSION_STRING.as_str();
+    let (shutdown_send, mut shutdown_recv) = mpsc::channel(1);
+    let mut terminate = signal(SignalKind::terminate())?;
+    let rpc_config = RpcConfig::default();
+    let drain_period = rpc_config.shutdown_drain_period;
+    let (stop_handle, server_handle) = stop_channel();
+    // Spawned so that in-flight calls are not cancelled on shutdown
+    let mut rpc_server = tokio::spawn(start_rpc(
+        state,
+        vec![rpc_listener],
+        tls_config,
+        rpc_unix_socket,
+        rpc_config,
+        forest_version,
+        shutdown_send,
+        stop_handle,
+    ));
+
+    let result = tokio::select! {
+        ret = &mut rpc_server => ret.map_err(anyhow::Error::from).and_then(|ret| ret),
+        _ = ctrl_c() => {
+            info!("Keyboard interrupt.");
+            Ok(())
+        },
+        _ = terminate.recv() => {
+            info!("Received SIGTERM.");
+            Ok(())
+        },
+        _ = shutdown_recv.recv() => {
+            info!("Client requested a shutdown.");
+            Ok(())
+        },
+    };
+    stop_rpc(server_handle, drain_period).await;
+    crate::utils::io::terminal_cleanup();
+    result
+}
+
+async fn run_tests(
+    tests: Vec<RpcTest>,
+    nodes: &[Node],
+    uncovered: &[String],
+    config: &ApiTestFlags,
+    use_websocket: bool,
+) -> anyhow::Result<()> {
+    let semaphore = Arc::new(Semaphore::new(config.max_concurrent_requests));
+    let mut futures = FuturesUnordered::new();
+
+    let filter_list = if let Some(filter_file) = &config.filter_file {
+        FilterList::new_from_file(filter_file)?
+    } else {
+        FilterList::default().allow(config.filter.clone())
+    };
+    let names = nodes
+        .iter()
+        .map(|node| node.name.clone())
+        .collect::<Arc<[_]>>();
+    let nodes = Arc::<[Node]>::from(nodes);
+
+    for test in tests.into_iter() {
+        // By default, do not run ignored tests.
+        if matches!(config.run_ignored, RunIgnored::Default) && test.ignore.is_some() {
+            continue;
+        }
+        // If in `IgnoreOnly` mode, only run ignored tests.
+        if matches!(config.run_ignored, RunIgnored::IgnoredOnly) && test.ignore.is_none() {
+            continue;
+        }
+
+        if !filter_list.authorize(test.request.method_name)
+            || !config.tag_filter.authorize(test.tags)
+        {
+            continue;
+        }
+
+        // Acquire a permit from the semaphore before spawning a test
+        let permit = semaphore.clone().acquire_owned().await?;
+        let nodes = nodes.clone();
+        let names = names.clone();
+        let dump_dir = config.dump_dir.clone();
+        let (retries, retry_delay) = (config.retries, config.retry_delay);
+        let future = tokio::spawn(async move {
+            let (result, attempts) =
+                run_with_retries(retries, retry_delay, || test.run(&nodes, use_websocket)).await;
+            drop(permit); // Release the permit after test execution
+            let record = TestRecord::new(
+                test.request.method_name,
+                test.request.params(),
+                result.statuses.clone(),
+                result.duration,
+            )
+            .with_durations(&result.durations)
+            .with_attempts(attempts)
+            .with_tags(test.tags);
+            if let (Some(dump_dir), Some(_)) = (dump_dir, record.failure) {
+                if let Err(e) =
+                    dump_failure(&dump_dir, &names, &record, test.request.params(), &result)
+                {
+                    warn!("Failed to dump {} test: {e:#}", record.method);
+                }
+            }
+            record
+        });
+
+        futures.push(future);
+    }
+
+    let mut records = vec![];
+    while let Some(Ok(record)) = futures.next().await {
+        let failed = record.failure.is_some();
+        records.push(record);
+
+        if failed && config.fail_fast {
+            break;
+        }
+    }
+
+    let report = match config.report_format {
+        ReportFormat::Markdown => format_as_markdown(
+            &names,
+            &records,
+            uncovered,
+            config.show_timing,
+            config.group_by_tag,
+        ),
+        ReportFormat::Json => format_as_json(
+            &names,
+            &records,
+            uncovered,
+            config.show_timing,
+            config.group_by_tag,
+        )?,
+        ReportFormat::Csv => {
+            // Tests are reported one per row, uncovered methods are logged
+            if !uncovered.is_empty() {
+                warn!("Methods not covered by the tests: {}", uncovered.join(", "));
+            }
+            format_as_csv(&names, &records)?
+        }
+    };
+    match &config.report_path {
+        Some(path) => std::fs::write(path, report)
+            .with_context(|| format!("could not write {}", path.display()))?,
+        None => println!("{report}"),
+    }
+
+    if records.iter().all(|record| record.failure.is_none()) {
+        Ok(())
+    } else {
+        Err(anyhow::Error::msg("Some tests failed"))
+    }
+}
+
+/// Whether the nodes answered consistently. All of them timing out is not
+/// considered to be a failure.
+fn passed(statuses: &[EndpointStatus]) -> bool {
+    [EndpointStatus::Valid, EndpointStatus::Timeout]
+        .iter()
+        .any(|expected| statuses.iter().all(|status| status == expected))
+}
+
+// Lotus does not page `StateMarketDeals`, so the page returned by Forest is
+// compared with the same page of all the deals returned by the reference nodes.
+fn validate_market_deals_page(tsk: ApiTipsetKey, filter: MarketDealsFilter) -> RpcTest {
+    let MarketDealsFilter {
+        offset,
+        limit,
+        deal_id_range,
+    } = filter.clone();
+    let (first, end) = deal_id_range.unwrap_or((0, DealID::MAX));
+    RpcTest::validate(
+        ApiInfo::state_market_deals_req(tsk.clone(), Some(filter)),
+        move |forest, lotus| {
+            let page = lotus
+                .into_iter()
+                .filter(|(deal_id, _)| (first..end).contains(deal_id))
+                .skip(offset as usize)
+                .take(limit.map_or(usize::MAX, |limit| limit as usize));
+            forest.into_iter().eq(page)
+        },
+    )
+    .with_reference_request(ApiInfo::state_market_deals_req(tsk, None))
+}
+
+fn validate_message_lookup(req: RpcRequest<Option<MessageLookup>>) -> RpcTest {
+    use libipld_core::ipld::Ipld;
+
+    RpcTest::validate(req, |mut forest, mut lotus| {
+        // FIXME: https://github.com/ChainSafe/forest/issues/3784
+        if let Some(json) = forest.as_mut() {
+            json.return_dec = Ipld::Null;
+        }
+        if let Some(json) = lotus.as_mut() {
+            json.return_dec = Ipld::Null;
+        }
+        forest == lotus
+    })
+}
+
+/// A filter list that allows or rejects RPC methods based on their name.
+#[derive(Default)]
+struct FilterList {
+    allow: Vec<String>,
+    reject: Vec<String>,
+}
+
+impl FilterList {
+    fn new_from_file(file: &Path) -> anyhow::Result<Self> {
+        let (allow, reject) = Self::create_allow_reject_list(file)?;
+        Ok(Self { allow, reject })
+    }
+
+    /// Authorize (or not) an RPC method based on its name.
+    /// If the allow list is empty, all methods are authorized, unless they are rejected.
+    fn authorize(&self, entry: &str) -> bool {
+        (self.allow.is_empty() || self.allow.iter().any(|a| entry.contains(a)))
+            && !self.reject.iter().any(|r| entry.contains(r))
+    }
+
+    fn allow(mut self, entry: String) -> Self {
+        self.allow.push(entry);
+        self
+    }
+
+    #[allow(dead_code)]
+    fn reject(mut self, entry: String) -> Self {
+        self.reject.push(entry);
+        self
+    }
+
+    /// Create a list of allowed and rejected RPC methods from a file.
+    fn create_allow_reject_list(file: &Path) -> anyhow::Result<(Vec<String>, Vec<String>)> {
+        let filter_file = std::fs::read_to_string(file)?;
+        let (reject, allow): (Vec<_>, Vec<_>) = filter_file
+            .lines()
+            .map(|line| line.trim().to_owned())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .partition(|line| line.starts_with('!'));
+
+        let reject = reject
+            .into_iter()
+            .map(|entry| entry.trim_start_matches('!').to_owned())
+            .collect::<Vec<_>>();
+
+        Ok((allow, reject))
+    }
+}
+
+/// Selects tests by their tags. Unless no tag is included, tests must have
+/// an included tag, and they must not have any excluded tag.
+#[derive(Debug, Default)]
+struct TagFilter {
+    include: Vec<String>,
+    exclude: Vec<String>,
+}
+
+impl TagFilter {
+    fn authorize(&self, tags: &[&str]) -> bool {
+        let has = |tag: &String| tags.contains(&tag.as_str());
+        (self.include.is_empty() || self.include.iter().any(has)) && !self.exclude.iter().any(has)
+    }
+}
+
+#[cfg(test)]
+mod tests;
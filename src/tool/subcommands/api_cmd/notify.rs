@@ -0,0 +1,128 @@
+// Copyright 2019-2024 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use super::RpcTest;
+use crate::blocks::{Tipset, TipsetKey};
+use crate::rpc_api::data_types::ApiHeadChange;
+use crate::rpc_client::{ApiInfo, JsonRpcError, RpcRequest};
+use crate::shim::clock::ChainEpoch;
+use futures::StreamExt;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Limits of the notifications collected from a channel
+#[derive(Debug, Clone, Copy)]
+pub(super) struct NotificationLimits {
+    pub(super) count: usize,
+    pub(super) duration: Duration,
+}
+
+/// Opens a channel and collects its notifications in an array, until there
+/// are enough of them or the time is up.
+pub(super) async fn collect_notifications(
+    api: &ApiInfo,
+    request: RpcRequest,
+    limits: NotificationLimits,
+) -> Result<serde_json::Value, JsonRpcError> {
+    let deadline = tokio::time::Instant::now() + limits.duration;
+    let mut notifications = api.ws_subscribe(request).await?;
+    let mut collected = vec![];
+    while collected.len() < limits.count {
+        match tokio::time::timeout_at(deadline, notifications.next()).await {
+            Ok(Some(notification)) => collected.push(notification?),
+            Ok(None) | Err(_) => break,
+        }
+    }
+    Ok(serde_json::Value::Array(collected))
+}
+
+// Channels are only served over WebSocket, so these tests require `--forest`
+// and `--lotus` to be WebSocket addresses.
+pub(super) fn websocket_tests(limits: NotificationLimits) -> Vec<RpcTest> {
+    vec![RpcTest::validate_notifications(
+        ApiInfo::chain_notify_req(),
+        limits,
+        |forest, lotus| match (
+            NotifiedChain::replay(&forest),
+            NotifiedChain::replay(&lotus),
+        ) {
+            (Some(forest), Some(lotus)) => forest.is_consistent_with(&lotus),
+            _ => false,
+        },
+    )
+    .with_tags(&["chain", "channel", "slow"])]
+}
+
+/// Maximum difference between the epochs of the heads notified by two nodes
+const NOTIFIED_EPOCH_TOLERANCE: ChainEpoch = 2;
+
+/// Chain followed by a node, according to the notifications of a
+/// `Filecoin.ChainNotify` channel.
+#[derive(Debug, PartialEq)]
+pub(super) struct NotifiedChain {
+    /// Epoch of the head when the channel was opened
+    pub(super) current: ChainEpoch,
+    /// Tipsets applied since then and not reverted, by epoch
+    pub(super) applied: BTreeMap<ChainEpoch, TipsetKey>,
+    /// Epoch of the head after the last notification, or of the tipset that
+    /// was reverted last
+    pub(super) head: ChainEpoch,
+}
+
+impl NotifiedChain {
+    /// Replays the notifications, which must start with the single `current`
+    /// head change. The next ones revert the head or apply tipsets on top of
+    /// it, which after a revert are children of the new head. Returns `None`
+    /// if the notifications are not consistent.
+    pub(super) fn replay(notifications: &[Vec<ApiHeadChange>]) -> Option<Self> {
+        let (first, rest) = notifications.split_first()?;
+        let [current] = first.as_slice() else {
+            return None;
+        };
+        if current.change != "current" {
+            return None;
+        }
+        let current = Tipset::new(current.headers.clone()).ok()?;
+        let mut head_key = current.key().clone();
+        // Unknown after a revert, as the head is then the parent tipset
+        let mut head_epoch = Some(current.epoch());
+        let mut chain = Self {
+            current: current.epoch(),
+            applied: BTreeMap::new(),
+            head: current.epoch(),
+        };
+        for change in rest.iter().flatten() {
+            let tipset = Tipset::new(change.headers.clone()).ok()?;
+            match change.change.as_str() {
+                "revert" if tipset.key() == &head_key => {
+                    chain.applied.remove(&tipset.epoch());
+                    head_key = tipset.parents().clone();
+                    head_epoch = None;
+                }
+                "apply"
+                    if head_epoch.map_or(tipset.parents() == &head_key, |epoch| {
+                        tipset.epoch() > epoch
+                    }) =>
+                {
+                    chain.applied.insert(tipset.epoch(), tipset.key().clone());
+                    head_key = tipset.key().clone();
+                    head_epoch = Some(tipset.epoch());
+                }
+                _ => return None,
+            }
+            chain.head = tipset.epoch();
+        }
+        Some(chain)
+    }
+
+    /// Whether both nodes followed the same chain, give or take a few epochs.
+    pub(super) fn is_consistent_with(&self, other: &Self) -> bool {
+        let close = |a: ChainEpoch, b: ChainEpoch| a.abs_diff(b) <= NOTIFIED_EPOCH_TOLERANCE as u64;
+        close(self.current, other.current)
+            && close(self.head, other.head)
+            && self
+                .applied
+                .iter()
+                .all(|(epoch, key)| other.applied.get(epoch).map_or(true, |other| other == key))
+    }
+}
@@ -0,0 +1,530 @@
+// Copyright 2019-2024 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use super::{passed, EndpointStatus, TestResult};
+use crate::utils::encoding::blake2b_256;
+use ahash::HashMap;
+use anyhow::Context as _;
+use indexmap::IndexMap;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tabled::{builder::Builder, settings::Style};
+
+/// Outcome of a single test, as reported in the JSON and CSV formats. The
+/// statuses and durations are those of the nodes, in their order.
+#[derive(Debug, Clone)]
+pub(super) struct TestRecord {
+    pub(super) method: &'static str,
+    /// Hex encoded BLAKE2b-256 digest of the JSON parameters of the request
+    pub(super) params_hash: String,
+    statuses: Vec<EndpointStatus>,
+    duration_secs: f64,
+    /// Durations of the calls, or their time limit if they timed out
+    pub(super) durations_secs: Vec<f64>,
+    /// Status of the first node that failed the test
+    pub(super) failure: Option<EndpointStatus>,
+    /// Number of times the test was run, the last of which is reported
+    attempts: u32,
+    pub(super) tags: &'static [&'static str],
+}
+
+impl TestRecord {
+    pub(super) fn new(
+        method: &'static str,
+        params: &serde_json::Value,
+        statuses: Vec<EndpointStatus>,
+        duration: Duration,
+    ) -> Self {
+        let failure = match passed(&statuses) {
+            true => None,
+            false => statuses
+                .iter()
+                .find(|status| **status != EndpointStatus::Valid)
+                .copied(),
+        };
+        Self {
+            method,
+            params_hash: hex::encode(blake2b_256(params.to_string().as_bytes())),
+            durations_secs: vec![0.; statuses.len()],
+            statuses,
+            duration_secs: duration.as_secs_f64(),
+            failure,
+            attempts: 1,
+            tags: &[],
+        }
+    }
+
+    pub(super) fn with_durations(mut self, durations: &[Duration]) -> Self {
+        self.durations_secs = durations.iter().map(Duration::as_secs_f64).collect();
+        self
+    }
+
+    pub(super) fn with_attempts(mut self, attempts: u32) -> Self {
+        self.attempts = attempts;
+        self
+    }
+
+    pub(super) fn with_tags(mut self, tags: &'static [&'static str]) -> Self {
+        self.tags = tags;
+        self
+    }
+
+    /// Duration of the slowest of the calls.
+    fn slowest_secs(&self) -> f64 {
+        self.durations_secs.iter().copied().fold(0., f64::max)
+    }
+
+    /// Fields of the record, in the order of the CSV columns. Statuses and
+    /// durations are named after the nodes, e.g. `forest` and
+    /// `forest_duration_secs`.
+    fn fields(&self, names: &[String]) -> Vec<(String, serde_json::Value)> {
+        let mut fields = vec![
+            ("method".into(), self.method.into()),
+            ("params_hash".into(), self.params_hash.clone().into()),
+        ];
+        fields.extend(
+            names
+                .iter()
+                .zip(&self.statuses)
+                .map(|(name, status)| (name.clone(), serde_json::json!(status))),
+        );
+        fields.push(("duration_secs".into(), self.duration_secs.into()));
+        fields.extend(
+            names
+                .iter()
+                .zip(&self.durations_secs)
+                .map(|(name, secs)| (format!("{name}_duration_secs"), (*secs).into())),
+        );
+        fields.push(("failure".into(), serde_json::json!(self.failure)));
+        fields.push(("attempts".into(), self.attempts.into()));
+        fields.push(("tags".into(), self.tags.into()));
+        fields
+    }
+
+    /// Fields of the record, in order, as serialized in the JSON reports.
+    fn to_json(&self, names: &[String]) -> IndexMap<String, serde_json::Value> {
+        self.fields(names).into_iter().collect()
+    }
+}
+
+/// Latency percentiles of the calls of a method to a node, in seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub(super) struct Timing {
+    pub(super) p50: f64,
+    pub(super) p95: f64,
+    pub(super) max: f64,
+}
+
+impl Timing {
+    /// Computes the nearest-rank percentiles of `durations`, if any.
+    pub(super) fn new(mut durations: Vec<f64>) -> Option<Self> {
+        durations.sort_by(f64::total_cmp);
+        let percentile = |p: f64| {
+            let rank = (p * durations.len() as f64).ceil() as usize;
+            durations.get(rank.saturating_sub(1)).copied()
+        };
+        Some(Self {
+            p50: percentile(0.5)?,
+            p95: percentile(0.95)?,
+            max: *durations.last()?,
+        })
+    }
+}
+
+impl fmt::Display for Timing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.3}s / {:.3}s / {:.3}s", self.p50, self.p95, self.max)
+    }
+}
+
+/// Latency percentiles of each method for each node, sorted by method.
+pub(super) fn method_timings(records: &[TestRecord]) -> Vec<(&'static str, Vec<Timing>)> {
+    let mut durations: BTreeMap<&'static str, Vec<Vec<f64>>> = BTreeMap::new();
+    for record in records {
+        let nodes = durations.entry(record.method).or_default();
+        nodes.resize_with(record.durations_secs.len(), Vec::new);
+        for (node, secs) in nodes.iter_mut().zip(&record.durations_secs) {
+            node.push(*secs);
+        }
+    }
+    durations
+        .into_iter()
+        .filter_map(|(method, nodes)| {
+            let timings = nodes.into_iter().map(Timing::new).collect::<Option<_>>()?;
+            Some((method, timings))
+        })
+        .collect()
+}
+
+/// Number of tests listed in the slowest tests section of the reports
+pub(super) const SLOWEST_TESTS: usize = 10;
+
+/// Returns the tests with the slowest calls to any node, slowest first.
+pub(super) fn slowest_tests(records: &[TestRecord]) -> Vec<&TestRecord> {
+    let mut slowest = records.iter().collect::<Vec<_>>();
+    slowest.sort_by(|a, b| b.slowest_secs().total_cmp(&a.slowest_secs()));
+    slowest.truncate(SLOWEST_TESTS);
+    slowest
+}
+
+/// Responses serialized to more bytes than this are truncated in dumps
+const DUMP_RESPONSE_LIMIT: usize = 64 * 1024;
+
+/// Writes the request and responses of a failed test to a file of `dir`
+/// named after the method and hash of the parameters, returning its path.
+pub(super) fn dump_failure(
+    dir: &Path,
+    names: &[String],
+    record: &TestRecord,
+    params: &serde_json::Value,
+    result: &TestResult,
+) -> anyhow::Result<PathBuf> {
+    let mut diff = vec![];
+    json_diff(
+        String::new(),
+        &result.responses.iter().map(Some).collect::<Vec<_>>(),
+        &mut diff,
+    );
+    let diff = diff
+        .into_iter()
+        .map(|(path, values)| {
+            let mut entry = serde_json::Map::new();
+            entry.insert("path".into(), path.into());
+            for (name, value) in names.iter().zip(values) {
+                let value = value.map(|it| truncate(it, DUMP_RESPONSE_LIMIT));
+                entry.insert(name.clone(), serde_json::json!(value));
+            }
+            serde_json::Value::Object(entry)
+        })
+        .collect::<Vec<_>>();
+    let mut dump = serde_json::Map::new();
+    dump.insert("method".into(), record.method.into());
+    dump.insert("params".into(), params.clone());
+    for ((name, status), response) in names.iter().zip(&record.statuses).zip(&result.responses) {
+        dump.insert(
+            name.clone(),
+            serde_json::json!({
+                "status": status,
+                "response": truncate(response, DUMP_RESPONSE_LIMIT),
+            }),
+        );
+    }
+    dump.insert("diff".into(), diff.into());
+
+    std::fs::create_dir_all(dir)?;
+    let path = dir.join(format!("{}-{}.json", record.method, record.params_hash));
+    std::fs::write(&path, serde_json::to_string_pretty(&dump)?)
+        .with_context(|| format!("could not write {}", path.display()))?;
+    Ok(path)
+}
+
+/// Collects the JSON pointers at which the values differ, with the value of
+/// each node, which is missing for keys or items not found on its side.
+pub(super) fn json_diff<'a>(
+    path: String,
+    values: &[Option<&'a serde_json::Value>],
+    diff: &mut Vec<(String, Vec<Option<&'a serde_json::Value>>)>,
+) {
+    use serde_json::Value;
+
+    let all = |f: fn(&'a Value) -> bool| values.iter().all(|value| value.is_some_and(f));
+    if all(Value::is_object) {
+        let objects = values
+            .iter()
+            .filter_map(|value| value.and_then(Value::as_object))
+            .collect::<Vec<_>>();
+        let keys = objects
+            .iter()
+            .flat_map(|object| object.keys())
+            .collect::<BTreeSet<_>>();
+        for key in keys {
+            let path = format!("{path}/{}", key.replace('~', "~0").replace('/', "~1"));
+            let values = objects
+                .iter()
+                .map(|object| object.get(key))
+                .collect::<Vec<_>>();
+            json_diff(path, &values, diff);
+        }
+    } else if all(Value::is_array) {
+        let arrays = values
+            .iter()
+            .filter_map(|value| value.and_then(Value::as_array))
+            .collect::<Vec<_>>();
+        let len = arrays
+            .iter()
+            .map(|array| array.len())
+            .max()
+            .unwrap_or_default();
+        for i in 0..len {
+            let values = arrays.iter().map(|array| array.get(i)).collect::<Vec<_>>();
+            json_diff(format!("{path}/{i}"), &values, diff);
+        }
+    } else if values.iter().any(|value| Some(value) != values.first()) {
+        diff.push((path, values.to_vec()));
+    }
+}
+
+/// Replaces values serialized to more than `limit` bytes by the beginning of
+/// their serialization, followed by a note.
+pub(super) fn truncate(value: &serde_json::Value, limit: usize) -> serde_json::Value {
+    let text = value.to_string();
+    if text.len() <= limit {
+        return value.clone();
+    }
+    let mut end = limit;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    serde_json::Value::String(format!(
+        "{}... (truncated from {} bytes)",
+        text.get(..end).unwrap_or_default(),
+        text.len()
+    ))
+}
+
+/// Counts the tests of each method by outcomes, sorted by method.
+fn count_results(records: &[TestRecord]) -> Vec<((&'static str, Vec<EndpointStatus>), u32)> {
+    let mut results = HashMap::default();
+    for record in records {
+        *results
+            .entry((record.method, record.statuses.clone()))
+            .or_insert(0u32) += 1;
+    }
+    let mut results = results.into_iter().collect::<Vec<_>>();
+    results.sort();
+    results
+}
+
+pub(super) fn format_as_json(
+    names: &[String],
+    records: &[TestRecord],
+    uncovered: &[String],
+    show_timing: bool,
+    group_by_tag: bool,
+) -> anyhow::Result<String> {
+    type Entry = IndexMap<String, serde_json::Value>;
+
+    #[derive(Serialize)]
+    struct Report<'a> {
+        methods: Vec<Entry>,
+        tests: Vec<Entry>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        timings: Option<Vec<Entry>>,
+        /// Slowest tests, slowest first
+        #[serde(skip_serializing_if = "Option::is_none")]
+        slowest: Option<Vec<Entry>>,
+        /// Outcomes of the methods, as in `methods`, for each tag
+        #[serde(skip_serializing_if = "Option::is_none")]
+        by_tag: Option<IndexMap<&'static str, Vec<Entry>>>,
+        /// Methods registered by Forest that no test covers
+        #[serde(skip_serializing_if = "<[_]>::is_empty")]
+        uncovered: &'a [String],
+    }
+
+    // Outcomes and timings are keyed by the names of the nodes
+    let entry = |method: &str, values: Vec<serde_json::Value>| {
+        let mut entry = Entry::from_iter([("method".to_string(), method.into())]);
+        entry.extend(names.iter().cloned().zip(values));
+        entry
+    };
+    let methods = |records: &[TestRecord]| {
+        count_results(records)
+            .into_iter()
+            .map(|((method, statuses), count)| {
+                let statuses = statuses.iter().map(|it| serde_json::json!(it)).collect();
+                let mut entry = entry(method, statuses);
+                entry.insert("count".into(), count.into());
+                entry
+            })
+            .collect()
+    };
+    let timings = show_timing.then(|| {
+        method_timings(records)
+            .into_iter()
+            .map(|(method, timings)| {
+                entry(
+                    method,
+                    timings.iter().map(|it| serde_json::json!(it)).collect(),
+                )
+            })
+            .collect()
+    });
+    let slowest = show_timing.then(|| {
+        slowest_tests(records)
+            .into_iter()
+            .map(|record| record.to_json(names))
+            .collect()
+    });
+    let by_tag = group_by_tag.then(|| {
+        records_by_tag(records)
+            .into_iter()
+            .map(|(tag, records)| (tag, methods(&records)))
+            .collect()
+    });
+    Ok(serde_json::to_string_pretty(&Report {
+        methods: methods(records),
+        tests: records.iter().map(|record| record.to_json(names)).collect(),
+        timings,
+        slowest,
+        by_tag,
+        uncovered,
+    })?)
+}
+
+pub(super) fn format_as_csv(names: &[String], records: &[TestRecord]) -> anyhow::Result<String> {
+    let mut writer = csv::Writer::from_writer(vec![]);
+    for (i, record) in records.iter().enumerate() {
+        let fields = record.fields(names);
+        if i == 0 {
+            writer.write_record(fields.iter().map(|(name, _)| name))?;
+        }
+        writer.write_record(fields.into_iter().map(|(_, value)| {
+            match value {
+                serde_json::Value::String(it) => it,
+                serde_json::Value::Null => String::new(),
+                // Tags
+                serde_json::Value::Array(items) => items
+                    .iter()
+                    .filter_map(serde_json::Value::as_str)
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                other => other.to_string(),
+            }
+        }))?;
+    }
+    Ok(String::from_utf8(writer.into_inner()?)?)
+}
+
+/// Names of the nodes as displayed in the Markdown reports.
+fn display_name(name: &str) -> String {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .map(|first| first.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
+}
+
+/// Table of the outcomes of the tests of each method, sorted by method.
+fn method_table(names: &[String], records: &[TestRecord], show_timing: bool) -> String {
+    let mut builder = Builder::default();
+
+    let mut header = vec!["RPC Method".to_string()];
+    header.extend(names.iter().map(|name| display_name(name)));
+    if show_timing {
+        header.extend(
+            names
+                .iter()
+                .map(|name| format!("{} p50 / p95 / max", display_name(name))),
+        );
+    }
+    builder.push_record(header);
+
+    let timings = method_timings(records)
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+    for ((method, statuses), n) in count_results(records) {
+        let mut row = vec![if n > 1 {
+            format!("{} ({})", method, n)
+        } else {
+            method.to_string()
+        }];
+        row.extend(statuses.iter().map(|status| format!("{:?}", status)));
+        if let Some(timings) = timings.get(method).filter(|_| show_timing) {
+            row.extend(timings.iter().map(Timing::to_string));
+        }
+        builder.push_record(row);
+    }
+    builder.build().with(Style::markdown()).to_string()
+}
+
+/// Tag under which the untagged tests are reported when grouping by tag
+const UNTAGGED: &str = "untagged";
+
+/// Records of each tag, sorted by tag. Tests with several tags are found
+/// under each of them.
+fn records_by_tag(records: &[TestRecord]) -> BTreeMap<&'static str, Vec<TestRecord>> {
+    let mut by_tag: BTreeMap<_, Vec<_>> = BTreeMap::new();
+    for record in records {
+        let tags = match record.tags {
+            [] => &[UNTAGGED],
+            tags => tags,
+        };
+        for tag in tags {
+            by_tag.entry(*tag).or_default().push(record.clone());
+        }
+    }
+    by_tag
+}
+
+pub(super) fn format_as_markdown(
+    names: &[String],
+    records: &[TestRecord],
+    uncovered: &[String],
+    show_timing: bool,
+    group_by_tag: bool,
+) -> String {
+    let mut report = if group_by_tag {
+        records_by_tag(records)
+            .into_iter()
+            .map(|(tag, records)| {
+                format!(
+                    "Tag `{tag}`\n\n{}",
+                    method_table(names, &records, show_timing)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    } else {
+        method_table(names, records, show_timing)
+    };
+    if records.iter().any(|record| record.attempts > 1) {
+        let mut builder = Builder::default();
+        builder.push_record(["RPC Method", "Parameters hash", "Attempts"]);
+        for record in records.iter().filter(|record| record.failure.is_some()) {
+            builder.push_record([
+                record.method.to_string(),
+                record.params_hash.clone(),
+                record.attempts.to_string(),
+            ]);
+        }
+        report.push_str(&format!(
+            "\n\nAttempts of failed tests\n\n{}",
+            builder.build().with(Style::markdown())
+        ));
+    }
+    if show_timing {
+        let mut builder = Builder::default();
+        let mut header = vec!["RPC Method".to_string(), "Parameters hash".to_string()];
+        header.extend(names.iter().map(|name| display_name(name)));
+        builder.push_record(header);
+        for record in slowest_tests(records) {
+            let mut row = vec![record.method.to_string(), record.params_hash.clone()];
+            row.extend(
+                record
+                    .durations_secs
+                    .iter()
+                    .map(|secs| format!("{secs:.3}s")),
+            );
+            builder.push_record(row);
+        }
+        report.push_str(&format!(
+            "\n\n{SLOWEST_TESTS} slowest tests\n\n{}",
+            builder.build().with(Style::markdown())
+        ));
+    }
+    if !uncovered.is_empty() {
+        let mut builder = Builder::default();
+        builder.push_record(["RPC Method"]);
+        for method in uncovered {
+            builder.push_record([method]);
+        }
+        report.push_str(&format!(
+            "\n\nUncovered methods\n\n{}",
+            builder.build().with(Style::markdown())
+        ));
+    }
+    report
+}
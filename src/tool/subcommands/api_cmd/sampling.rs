@@ -0,0 +1,89 @@
+// Copyright 2019-2024 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use crate::blocks::Tipset;
+use crate::chain::index::{ChainIndex, ResolveNullTipset};
+use crate::db::car::ManyCar;
+use crate::shim::clock::ChainEpoch;
+use anyhow::{ensure, Context as _};
+use std::collections::BTreeSet;
+use std::num::NonZeroUsize;
+use std::ops::Range;
+use std::sync::Arc;
+
+/// Parses an `--epoch-range` argument, e.g. `1420000..1420100`.
+pub(super) fn parse_epoch_range(s: &str) -> anyhow::Result<Range<ChainEpoch>> {
+    let (start, end) = s
+        .split_once("..")
+        .context("expected a range of epochs as START..END")?;
+    let range = start.parse()?..end.parse()?;
+    ensure!(!range.is_empty(), "the range of epochs {s} is empty");
+    Ok(range)
+}
+
+/// Selection of the tipsets of the snapshots from which tests are generated.
+#[derive(Debug, Clone)]
+pub(super) struct TipsetSampling {
+    pub(super) n_tipsets: usize,
+    pub(super) from_epoch: Option<ChainEpoch>,
+    pub(super) stride: NonZeroUsize,
+    pub(super) epoch_ranges: Vec<Range<ChainEpoch>>,
+}
+
+impl TipsetSampling {
+    /// Ranges of epochs to sample, given the epoch of the heaviest tipset.
+    /// Unless given, `n_tipsets` epochs are sampled from `from_epoch`, or up
+    /// to the heaviest tipset.
+    fn ranges(&self, head: ChainEpoch) -> Vec<Range<ChainEpoch>> {
+        if !self.epoch_ranges.is_empty() {
+            return self.epoch_ranges.clone();
+        }
+        let stride = self.stride.get() as ChainEpoch;
+        let span = (self.n_tipsets as ChainEpoch).saturating_mul(stride);
+        let range = match self.from_epoch {
+            Some(from) => from..from.saturating_add(span),
+            // Aligned so that the heaviest tipset is sampled
+            None => {
+                let start = head.saturating_sub(span - stride);
+                let start = if start < 0 { head % stride } else { start };
+                start..head + 1
+            }
+        };
+        vec![range]
+    }
+
+    /// Sampled epochs, sorted and without duplicates.
+    pub(super) fn epochs(&self, head: ChainEpoch) -> BTreeSet<ChainEpoch> {
+        self.ranges(head)
+            .into_iter()
+            .flat_map(|range| range.step_by(self.stride.get()))
+            .collect()
+    }
+}
+
+/// Loads the sampled tipsets, from the heaviest one down, failing if any of
+/// them is missing from the snapshots. Null rounds resolve to the previous
+/// tipset.
+pub(super) fn sample_tipsets(
+    store: &Arc<ManyCar>,
+    head: &Tipset,
+    sampling: &TipsetSampling,
+) -> anyhow::Result<Vec<Arc<Tipset>>> {
+    let index = ChainIndex::new(store.clone());
+    let head = Arc::new(head.clone());
+    let mut tipsets = vec![];
+    let mut missing = vec![];
+    for epoch in sampling.epochs(head.epoch()).into_iter().rev() {
+        match index.tipset_by_height(epoch, head.clone(), ResolveNullTipset::TakeOlder) {
+            Ok(tipset) => tipsets.push(tipset),
+            Err(_) => missing.push(epoch.to_string()),
+        }
+    }
+    ensure!(
+        missing.is_empty(),
+        "no tipsets at epochs {} in the snapshots",
+        missing.join(", ")
+    );
+    tipsets.dedup_by(|a, b| a.key() == b.key());
+    Ok(tipsets)
+}
@@ -0,0 +1,1143 @@
+// Copyright 2019-2024 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use super::notify::NotifiedChain;
+use super::report::{json_diff, method_timings, slowest_tests, truncate, Timing, SLOWEST_TESTS};
+use super::*;
+use crate::rpc_api::data_types::ApiHeadChange;
+use clap::Parser;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[test]
+fn cleanup_on_exit_overrides_persist_db() {
+    #[derive(Parser)]
+    struct Cli {
+        #[command(subcommand)]
+        command: ApiCommands,
+    }
+    let persists = |args: &[&str]| {
+        let cli = Cli::try_parse_from(["api", "serve"].iter().chain(args)).unwrap();
+        match cli.command {
+            ApiCommands::Serve {
+                persist_db,
+                cleanup_on_exit,
+                ..
+            } => persist_db && !cleanup_on_exit,
+            _ => unreachable!(),
+        }
+    };
+    assert!(!persists(&[]));
+    assert!(persists(&["--persist-db"]));
+    assert!(!persists(&["--cleanup-on-exit"]));
+    assert!(!persists(&["--persist-db", "--cleanup-on-exit"]));
+    assert!(persists(&["--cleanup-on-exit", "--persist-db"]));
+}
+
+#[test]
+fn simulation_interval_is_positive() {
+    assert_eq!(
+        parse_simulation_interval("0.5").unwrap(),
+        Duration::from_millis(500)
+    );
+    assert!(parse_simulation_interval("0").is_err());
+    assert!(parse_simulation_interval("-1").is_err());
+    assert!(parse_simulation_interval("soon").is_err());
+}
+
+#[test]
+fn test_filter_list_creation() {
+    // Create a temporary file and write some test data to it
+    let mut filter_file = tempfile::Builder::new().tempfile().unwrap();
+    let list = FilterList::new_from_file(filter_file.path()).unwrap();
+    assert!(list.allow.is_empty());
+    assert!(list.reject.is_empty());
+
+    write!(
+        filter_file,
+        r#"# This is a comment
+        !cthulhu
+        azathoth
+        !nyarlathotep
+        "#
+    )
+    .unwrap();
+
+    let list = FilterList::new_from_file(filter_file.path()).unwrap();
+    assert_eq!(list.allow, vec!["azathoth".to_string()]);
+    assert_eq!(
+        list.reject,
+        vec!["cthulhu".to_string(), "nyarlathotep".to_string()]
+    );
+
+    let list = list
+        .allow("shub-niggurath".to_string())
+        .reject("yog-sothoth".to_string());
+    assert_eq!(
+        list.allow,
+        vec!["azathoth".to_string(), "shub-niggurath".to_string()]
+    );
+}
+
+#[test]
+fn test_filter_list_authorize() {
+    let list = FilterList::default();
+    // if allow is empty, all entries are authorized
+    assert!(list.authorize("Filecoin.ChainGetBlock"));
+    assert!(list.authorize("Filecoin.StateNetworkName"));
+
+    // all entries are authorized, except the rejected ones
+    let list = list.reject("Network".to_string());
+    assert!(list.authorize("Filecoin.ChainGetBlock"));
+
+    // case-sensitive
+    assert!(list.authorize("Filecoin.StatenetworkName"));
+    assert!(!list.authorize("Filecoin.StateNetworkName"));
+
+    // if allow is not empty, only the allowed entries are authorized
+    let list = FilterList::default().allow("Chain".to_string());
+    assert!(list.authorize("Filecoin.ChainGetBlock"));
+    assert!(!list.authorize("Filecoin.StateNetworkName"));
+
+    // unless they are rejected
+    let list = list.reject("GetBlock".to_string());
+    assert!(!list.authorize("Filecoin.ChainGetBlock"));
+    assert!(list.authorize("Filecoin.ChainGetMessage"));
+
+    // reject takes precedence over allow
+    let list = FilterList::default()
+        .allow("Chain".to_string())
+        .reject("Chain".to_string());
+    assert!(!list.authorize("Filecoin.ChainGetBlock"));
+}
+
+#[test]
+fn test_tag_filter() {
+    let filter = |include: &[&str], exclude: &[&str]| TagFilter {
+        include: include.iter().map(|it| it.to_string()).collect(),
+        exclude: exclude.iter().map(|it| it.to_string()).collect(),
+    };
+    let untagged: &[&str] = &[];
+    let state = &["state"];
+    let miner = &["state", "miner"];
+    let slow = &["state", "slow"];
+
+    // Everything is authorized by default, even untagged tests
+    let list = filter(&[], &[]);
+    assert!(list.authorize(untagged));
+    assert!(list.authorize(miner));
+
+    // Any included tag is enough
+    let list = filter(&["miner", "eth"], &[]);
+    assert!(!list.authorize(untagged));
+    assert!(!list.authorize(state));
+    assert!(list.authorize(miner));
+    assert!(list.authorize(&["eth"]));
+
+    // Any excluded tag rejects a test
+    let list = filter(&[], &["slow"]);
+    assert!(list.authorize(untagged));
+    assert!(list.authorize(miner));
+    assert!(!list.authorize(slow));
+
+    // Exclusion prevails over inclusion
+    let list = filter(&["state"], &["slow"]);
+    assert!(list.authorize(state));
+    assert!(list.authorize(miner));
+    assert!(!list.authorize(slow));
+    assert!(!filter(&["slow"], &["slow"]).authorize(slow));
+
+    // Tags are matched exactly
+    assert!(!filter(&["stat"], &[]).authorize(state));
+}
+
+#[test]
+fn test_tagged() {
+    let tests = tagged(
+        &["state"],
+        vec![
+            RpcTest::identity(ApiInfo::state_network_name_req()),
+            RpcTest::identity(ApiInfo::state_network_name_req()).with_tags(&["miner"]),
+        ],
+    );
+    let tags = tests.iter().map(|test| test.tags).collect::<Vec<_>>();
+    assert_eq!(tags, [&["state"], &["miner"]]);
+}
+
+#[test]
+fn test_derive_protocol() {
+    let forest = ApiInfo::from_str("/ip4/127.0.0.1/tcp/2345/http").expect("infallible");
+    let lotus = ApiInfo::from_str("/ip4/127.0.0.1/tcp/1234/http").expect("infallible");
+    assert!(matches!(
+        derive_protocol(&forest, &lotus),
+        Ok(CommunicationProtocol::Http)
+    ));
+
+    let forest = ApiInfo::from_str("/ip4/127.0.0.1/tcp/2345/ws").expect("infallible");
+    let lotus = ApiInfo::from_str("/ip4/127.0.0.1/tcp/1234/ws").expect("infallible");
+    assert!(matches!(
+        derive_protocol(&forest, &lotus),
+        Ok(CommunicationProtocol::Ws)
+    ));
+
+    let forest = ApiInfo::from_str("/ip4/127.0.0.1/tcp/2345/http").expect("infallible");
+    let lotus = ApiInfo::from_str("/ip4/127.0.0.1/tcp/1234/ws").expect("infallible");
+    assert!(derive_protocol(&forest, &lotus).is_err());
+
+    let forest = ApiInfo::from_str("/ip4/127.0.0.1/tcp/2345/wss").expect("infallible");
+    let lotus = ApiInfo::from_str("/ip4/127.0.0.1/tcp/1234/wss").expect("infallible");
+    assert!(matches!(
+        derive_protocol(&forest, &lotus),
+        Ok(CommunicationProtocol::Ws)
+    ));
+
+    let forest = ApiInfo::from_str("/ip4/127.0.0.1/tcp/2345/https").expect("infallible");
+    let lotus = ApiInfo::from_str("/ip4/127.0.0.1/tcp/1234/http").expect("infallible");
+    assert!(matches!(
+        derive_protocol(&forest, &lotus),
+        Ok(CommunicationProtocol::Http)
+    ));
+
+    let forest = ApiInfo::from_str("/ip4/127.0.0.1/tcp/2345/https").expect("infallible");
+    let lotus = ApiInfo::from_str("/ip4/127.0.0.1/tcp/1234/wss").expect("infallible");
+    assert!(derive_protocol(&forest, &lotus).is_err());
+}
+
+fn forest_lotus() -> Vec<String> {
+    vec!["forest".into(), "lotus".into()]
+}
+
+fn test_records() -> Vec<TestRecord> {
+    use EndpointStatus::*;
+    let second = Duration::from_secs(1);
+    let no_params = serde_json::json!([]);
+    let id_params = serde_json::json!(["f01"]);
+    vec![
+        TestRecord::new("Filecoin.ChainHead", &no_params, vec![Valid, Valid], second),
+        TestRecord::new("Filecoin.ChainHead", &no_params, vec![Valid, Valid], second),
+        TestRecord::new(
+            "Filecoin.StateGetActor",
+            &id_params,
+            vec![MissingMethod, Valid],
+            second,
+        ),
+        TestRecord::new(
+            "Filecoin.WalletList",
+            &no_params,
+            vec![Valid, InvalidJSON],
+            second,
+        ),
+        TestRecord::new(
+            "Filecoin.EthBlockNumber",
+            &no_params,
+            vec![Timeout, Timeout],
+            second,
+        ),
+    ]
+}
+
+#[test]
+fn test_markdown_report() {
+    let report = format_as_markdown(&forest_lotus(), &test_records(), &[], false, false);
+    let rows = report
+        .lines()
+        .map(|line| {
+            line.split('|')
+                .map(str::trim)
+                .filter(|cell| !cell.is_empty())
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    // The header and its separator come first, then methods are sorted
+    assert_eq!(rows.len(), 6);
+    assert_eq!(rows[2], ["Filecoin.ChainHead (2)", "Valid", "Valid"]);
+    assert_eq!(
+        rows[4],
+        ["Filecoin.StateGetActor", "MissingMethod", "Valid"]
+    );
+}
+
+#[test]
+fn test_json_report() {
+    let report: serde_json::Value = serde_json::from_str(
+        &format_as_json(&forest_lotus(), &test_records(), &[], false, false).unwrap(),
+    )
+    .unwrap();
+
+    let methods = report["methods"].as_array().unwrap();
+    assert_eq!(methods.len(), 4);
+    assert_eq!(
+        methods.first().unwrap(),
+        &serde_json::json!({
+            "method": "Filecoin.ChainHead",
+            "forest": "Valid",
+            "lotus": "Valid",
+            "count": 2,
+        })
+    );
+
+    let tests = report["tests"].as_array().unwrap();
+    assert_eq!(tests.len(), 5);
+    let failures = tests
+        .iter()
+        .map(|test| test["failure"].as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        failures,
+        [None, None, Some("MissingMethod"), Some("InvalidJSON"), None]
+    );
+    assert_eq!(tests[0]["duration_secs"], 1.0);
+    // Tests are identified by their parameters
+    assert_eq!(tests[0]["params_hash"], tests[1]["params_hash"]);
+    assert_ne!(tests[1]["params_hash"], tests[2]["params_hash"]);
+    assert_eq!(tests[0]["params_hash"].as_str().unwrap().len(), 64);
+}
+
+/// Records of a method whose calls take `forest` and `lotus` seconds.
+fn timed_records(method: &'static str, forest: &[f64], lotus: &[f64]) -> Vec<TestRecord> {
+    forest
+        .iter()
+        .zip(lotus)
+        .map(|(forest, lotus)| {
+            TestRecord::new(
+                method,
+                &serde_json::json!([forest]),
+                vec![EndpointStatus::Valid; 2],
+                Duration::from_secs_f64(forest + lotus),
+            )
+            .with_durations(&[
+                Duration::from_secs_f64(*forest),
+                Duration::from_secs_f64(*lotus),
+            ])
+        })
+        .collect()
+}
+
+#[test]
+fn test_timing() {
+    assert_eq!(Timing::new(vec![]), None);
+    assert_eq!(
+        Timing::new(vec![2.]),
+        Some(Timing {
+            p50: 2.,
+            p95: 2.,
+            max: 2.
+        })
+    );
+    // Nearest-rank percentiles, regardless of the order of the durations
+    let durations = (1..=20).rev().map(f64::from).collect();
+    assert_eq!(
+        Timing::new(durations),
+        Some(Timing {
+            p50: 10.,
+            p95: 19.,
+            max: 20.
+        })
+    );
+}
+
+#[test]
+fn test_method_timings() {
+    let mut records = timed_records("Filecoin.StateCall", &[4., 1., 3., 2.], &[1., 1., 1., 5.]);
+    records.extend(timed_records("Filecoin.ChainHead", &[0.5], &[0.25]));
+
+    let timings = method_timings(&records);
+    assert_eq!(
+        timings,
+        [
+            (
+                "Filecoin.ChainHead",
+                vec![
+                    Timing {
+                        p50: 0.5,
+                        p95: 0.5,
+                        max: 0.5
+                    },
+                    Timing {
+                        p50: 0.25,
+                        p95: 0.25,
+                        max: 0.25
+                    }
+                ]
+            ),
+            (
+                "Filecoin.StateCall",
+                vec![
+                    Timing {
+                        p50: 2.,
+                        p95: 4.,
+                        max: 4.
+                    },
+                    Timing {
+                        p50: 1.,
+                        p95: 5.,
+                        max: 5.
+                    }
+                ]
+            ),
+        ]
+    );
+
+    // Tests are ranked by their slowest call
+    let slowest = slowest_tests(&records)
+        .into_iter()
+        .map(|record| record.durations_secs.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        slowest,
+        [[2., 5.], [4., 1.], [3., 1.], [1., 1.], [0.5, 0.25]]
+    );
+    let many = timed_records("Filecoin.StateCall", &[1.; 20], &[1.; 20]);
+    assert_eq!(slowest_tests(&many).len(), SLOWEST_TESTS);
+}
+
+#[test]
+fn test_timing_reports() {
+    let records = timed_records("Filecoin.StateCall", &[4., 1.], &[2., 3.]);
+
+    let report = format_as_markdown(&forest_lotus(), &records, &[], true, false);
+    let lines = report.lines().collect::<Vec<_>>();
+    assert!(lines[0].contains("Forest p50 / p95 / max"));
+    assert!(lines[2].contains("| 1.000s / 4.000s / 4.000s | 2.000s / 3.000s / 3.000s |"));
+    assert!(report.contains("10 slowest tests"));
+    let slowest = lines.iter().skip_while(|line| !line.contains("slowest"));
+    let rows = slowest.filter(|line| line.contains("Filecoin.StateCall"));
+    let rows = rows.map(|line| line.contains("| 4.000s | 2.000s |"));
+    assert_eq!(rows.collect::<Vec<_>>(), [true, false]);
+    // Timings are only reported on demand
+    assert!(!format_as_markdown(&forest_lotus(), &records, &[], false, false).contains("p50"));
+
+    let report: serde_json::Value =
+        serde_json::from_str(&format_as_json(&forest_lotus(), &records, &[], true, false).unwrap())
+            .unwrap();
+    assert_eq!(
+        report["timings"],
+        serde_json::json!([{
+            "method": "Filecoin.StateCall",
+            "forest": { "p50": 1.0, "p95": 4.0, "max": 4.0 },
+            "lotus": { "p50": 2.0, "p95": 3.0, "max": 3.0 },
+        }])
+    );
+    assert_eq!(report["slowest"][0]["forest_duration_secs"], 4.0);
+    let report: serde_json::Value = serde_json::from_str(
+        &format_as_json(&forest_lotus(), &records, &[], false, false).unwrap(),
+    )
+    .unwrap();
+    assert!(report.get("timings").is_none());
+}
+
+fn status_result(forest: EndpointStatus, lotus: EndpointStatus) -> TestResult {
+    TestResult {
+        statuses: vec![forest, lotus],
+        responses: vec![serde_json::Value::Null; 2],
+        duration: Duration::ZERO,
+        durations: vec![Duration::ZERO; 2],
+    }
+}
+
+/// Runs a test whose attempts have the given outcomes, the last one being
+/// repeated, returning the number of attempts and the final statuses.
+async fn run_outcomes(
+    retries: u32,
+    outcomes: &[(EndpointStatus, EndpointStatus)],
+) -> (u32, usize, (EndpointStatus, EndpointStatus)) {
+    let invocations = Arc::new(AtomicUsize::new(0));
+    let (result, attempts) = run_with_retries(retries, Duration::ZERO, || {
+        let invocation = invocations.fetch_add(1, Ordering::Relaxed);
+        let (forest, lotus) = *outcomes.get(invocation).or(outcomes.last()).unwrap();
+        async move { status_result(forest, lotus) }
+    })
+    .await;
+    (
+        attempts,
+        invocations.load(Ordering::Relaxed),
+        (result.statuses[0], result.statuses[1]),
+    )
+}
+
+#[tokio::test]
+async fn test_retries() {
+    use EndpointStatus::*;
+
+    // Passing tests are not retried
+    assert_eq!(
+        run_outcomes(3, &[(Valid, Valid)]).await,
+        (1, 1, (Valid, Valid))
+    );
+    assert_eq!(
+        run_outcomes(3, &[(Timeout, Timeout)]).await,
+        (1, 1, (Timeout, Timeout))
+    );
+    // Transient failures are retried until the test passes
+    assert_eq!(
+        run_outcomes(
+            3,
+            &[
+                (Timeout, Valid),
+                (InternalServerError, Valid),
+                (Valid, Valid)
+            ]
+        )
+        .await,
+        (3, 3, (Valid, Valid))
+    );
+    // or retries are exhausted, the last attempt being reported
+    assert_eq!(
+        run_outcomes(2, &[(InvalidResponse, Valid), (Timeout, Valid)]).await,
+        (3, 3, (Timeout, Valid))
+    );
+    // Without retries, tests are run once
+    assert_eq!(
+        run_outcomes(0, &[(Timeout, Valid), (Valid, Valid)]).await,
+        (1, 1, (Timeout, Valid))
+    );
+    // Deterministic failures are not retried
+    assert_eq!(
+        run_outcomes(3, &[(MissingMethod, Valid)]).await,
+        (1, 1, (MissingMethod, Valid))
+    );
+    assert_eq!(
+        run_outcomes(3, &[(Timeout, Valid), (Valid, InvalidJSON)]).await,
+        (2, 2, (Valid, InvalidJSON))
+    );
+}
+
+#[test]
+fn test_attempts_report() {
+    use EndpointStatus::*;
+    let records = vec![
+        TestRecord::new(
+            "Filecoin.ChainHead",
+            &serde_json::json!([]),
+            vec![Valid, Valid],
+            Duration::ZERO,
+        )
+        .with_attempts(2),
+        TestRecord::new(
+            "Filecoin.StateCall",
+            &serde_json::json!([]),
+            vec![Timeout, Valid],
+            Duration::ZERO,
+        )
+        .with_attempts(4),
+    ];
+    let report = format_as_markdown(&forest_lotus(), &records, &[], false, false);
+    assert!(report.contains("Attempts of failed tests"));
+    let row = report
+        .lines()
+        .skip_while(|line| !line.contains("Attempts"))
+        .find(|line| line.contains("Filecoin.StateCall"))
+        .unwrap();
+    assert_eq!(row.split('|').nth(3).map(str::trim), Some("4"), "{row}");
+    // Only failed tests are listed
+    assert_eq!(report.matches("Filecoin.ChainHead").count(), 1);
+
+    // Tests that were not retried are reported as before
+    assert!(
+        !format_as_markdown(&forest_lotus(), &test_records(), &[], false, false)
+            .contains("Attempts")
+    );
+
+    let report: serde_json::Value = serde_json::from_str(
+        &format_as_json(&forest_lotus(), &records, &[], false, false).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(report["tests"][1]["attempts"], 4);
+}
+
+#[test]
+fn test_csv_report() {
+    let report = format_as_csv(&forest_lotus(), &test_records()).unwrap();
+    let lines = report.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 6);
+    assert_eq!(
+        lines.first().unwrap(),
+        &"method,params_hash,forest,lotus,duration_secs,forest_duration_secs,lotus_duration_secs,failure,attempts,tags"
+    );
+    let rows = lines
+        .iter()
+        .skip(1)
+        .map(|line| {
+            let fields = line.split(',').collect::<Vec<_>>();
+            (fields[0], fields[2], fields[3], fields[7])
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        rows,
+        [
+            ("Filecoin.ChainHead", "Valid", "Valid", ""),
+            ("Filecoin.ChainHead", "Valid", "Valid", ""),
+            (
+                "Filecoin.StateGetActor",
+                "MissingMethod",
+                "Valid",
+                "MissingMethod"
+            ),
+            ("Filecoin.WalletList", "Valid", "InvalidJSON", "InvalidJSON"),
+            ("Filecoin.EthBlockNumber", "Timeout", "Timeout", ""),
+        ]
+    );
+}
+
+/// Serves `Filecoin.StateNetworkName` with the given name.
+async fn serve_network_name(name: &'static str) -> (ApiInfo, jsonrpsee::server::ServerHandle) {
+    let server = jsonrpsee::server::Server::builder()
+        .build("127.0.0.1:0")
+        .await
+        .unwrap();
+    let address = server.local_addr().unwrap();
+    let mut module = jsonrpsee::RpcModule::new(());
+    module
+        .register_method(
+            crate::rpc_api::state_api::STATE_NETWORK_NAME,
+            move |_, _| name,
+        )
+        .unwrap();
+    let api = ApiInfo::from_str(&format!(
+        "/ip4/{}/tcp/{}/http",
+        address.ip(),
+        address.port()
+    ))
+    .unwrap();
+    (api, server.start(module))
+}
+
+#[tokio::test]
+async fn test_dump_failure() {
+    let (forest, _forest_handle) = serve_network_name("calibnet").await;
+    let (lotus, _lotus_handle) = serve_network_name("mainnet").await;
+    let test = RpcTest::identity(ApiInfo::state_network_name_req());
+    let nodes = [Node::new("forest", forest), Node::new("lotus", lotus)];
+    let result = test.run(&nodes, false).await;
+    assert_eq!(
+        result.statuses,
+        [EndpointStatus::InvalidResponse, EndpointStatus::Valid]
+    );
+
+    let record = TestRecord::new(
+        test.request.method_name,
+        test.request.params(),
+        result.statuses.clone(),
+        Duration::ZERO,
+    );
+    let dump_dir = tempfile::tempdir().unwrap();
+    let names = forest_lotus();
+    let path = dump_failure(
+        dump_dir.path(),
+        &names,
+        &record,
+        test.request.params(),
+        &result,
+    )
+    .unwrap();
+    assert_eq!(
+        path.file_name().unwrap().to_str().unwrap(),
+        format!("Filecoin.StateNetworkName-{}.json", record.params_hash)
+    );
+    let dump: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+    assert_eq!(
+        dump,
+        serde_json::json!({
+            "method": "Filecoin.StateNetworkName",
+            "params": null,
+            "forest": { "status": "InvalidResponse", "response": { "result": "calibnet" } },
+            "lotus": { "status": "Valid", "response": { "result": "mainnet" } },
+            "diff": [{ "path": "/result", "forest": "calibnet", "lotus": "mainnet" }],
+        })
+    );
+}
+
+#[test]
+fn test_json_diff() {
+    let forest = serde_json::json!({ "a": [1, 2, 3], "b": { "c/d": true }, "e": null });
+    let lotus = serde_json::json!({ "a": [1, 5], "b": { "c/d": true }, "f": 0 });
+    let mut diff = vec![];
+    json_diff(String::new(), &[Some(&forest), Some(&lotus)], &mut diff);
+    let diff = diff
+        .into_iter()
+        .map(|(path, values)| (path, values.into_iter().map(Option::<&_>::cloned).collect()))
+        .collect::<Vec<(String, Vec<_>)>>();
+    assert_eq!(
+        diff,
+        [
+            ("/a/1".into(), vec![Some(2.into()), Some(5.into())]),
+            ("/a/2".into(), vec![Some(3.into()), None]),
+            ("/e".into(), vec![Some(serde_json::Value::Null), None]),
+            ("/f".into(), vec![None, Some(0.into())]),
+        ]
+    );
+
+    // Values are only compared when not all nodes agree
+    let venus = serde_json::json!({ "a": [1, 2, 3], "b": { "c/d": false }, "e": null });
+    let mut diff = vec![];
+    json_diff(
+        String::new(),
+        &[Some(&forest), Some(&forest), Some(&venus)],
+        &mut diff,
+    );
+    let paths = diff
+        .iter()
+        .map(|(path, _)| path.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(paths, ["/b/c~1d"]);
+}
+
+#[test]
+fn test_truncate() {
+    let value = serde_json::json!(["é", "abc"]);
+    assert_eq!(truncate(&value, 100), value);
+    // Truncated on a character boundary
+    assert_eq!(
+        truncate(&value, 3),
+        serde_json::Value::String(r#"["... (truncated from 12 bytes)"#.into())
+    );
+}
+
+#[test]
+fn test_majority_check() {
+    use EndpointStatus::*;
+    let test = RpcTest::identity(ApiInfo::state_network_name_req());
+    let check = |responses| test.check(responses);
+    let a = || Ok(serde_json::json!("calibnet"));
+    let b = || Ok(serde_json::json!("mainnet"));
+    let c = || Ok(serde_json::json!("butterflynet"));
+    let error = || {
+        Err(JsonRpcError::new(
+            ErrorCode::InternalError.code(),
+            "oops",
+            None,
+        ))
+    };
+
+    assert_eq!(check(vec![a(), a(), a()]), [Valid, Valid, Valid]);
+    assert_eq!(check(vec![b(), a(), a()]), [InvalidResponse, Valid, Valid]);
+    assert_eq!(check(vec![a(), a(), b()]), [Valid, Valid, InvalidResponse]);
+    // Ties are decided against Forest
+    assert_eq!(check(vec![a(), b()]), [InvalidResponse, Valid]);
+    assert_eq!(
+        check(vec![a(), b(), c()]),
+        [InvalidResponse, Valid, InvalidResponse]
+    );
+    // Identical errors are consistent
+    assert_eq!(
+        check(vec![error(), error(), error()]),
+        [Valid, Valid, Valid]
+    );
+    assert_eq!(
+        check(vec![error(), a(), a()]),
+        [InternalServerError, Valid, Valid]
+    );
+    assert_eq!(
+        check(vec![Ok(serde_json::json!(0)), a(), a()]),
+        [InvalidJSON, Valid, Valid]
+    );
+}
+
+#[test]
+fn test_parse_reference() {
+    let (name, api) = parse_reference("Venus=token:/ip4/127.0.0.1/tcp/3453/http").unwrap();
+    assert_eq!(name, "venus");
+    assert_eq!(api.token.as_deref(), Some("token"));
+    assert!(parse_reference("/ip4/127.0.0.1/tcp/3453/http").is_err());
+    assert!(parse_reference("=/ip4/127.0.0.1/tcp/3453/http").is_err());
+}
+
+#[test]
+fn test_reports_with_references() {
+    use EndpointStatus::*;
+    let names = ["forest", "lotus", "venus"].map(String::from);
+    let records = vec![TestRecord::new(
+        "Filecoin.ChainHead",
+        &serde_json::json!([]),
+        vec![Valid, Valid, InvalidResponse],
+        Duration::ZERO,
+    )
+    .with_durations(&[Duration::from_secs(1); 3])];
+    assert_eq!(records[0].failure, Some(InvalidResponse));
+
+    let report = format_as_markdown(&names, &records, &[], true, false);
+    let header = report.lines().next().unwrap();
+    assert!(header.contains("| Venus "), "{header}");
+    assert!(header.contains("| Venus p50 / p95 / max "), "{header}");
+
+    let report: serde_json::Value =
+        serde_json::from_str(&format_as_json(&names, &records, &[], true, false).unwrap()).unwrap();
+    assert_eq!(report["methods"][0]["venus"], "InvalidResponse");
+    assert_eq!(report["tests"][0]["venus_duration_secs"], 1.0);
+    assert_eq!(report["timings"][0]["venus"]["max"], 1.0);
+
+    let report = format_as_csv(&names, &records).unwrap();
+    assert_eq!(
+        report.lines().next().unwrap(),
+        "method,params_hash,forest,lotus,venus,duration_secs,forest_duration_secs,lotus_duration_secs,venus_duration_secs,failure,attempts,tags"
+    );
+}
+
+fn sampling(n_tipsets: usize, from_epoch: Option<ChainEpoch>, stride: usize) -> TipsetSampling {
+    TipsetSampling {
+        n_tipsets,
+        from_epoch,
+        stride: NonZeroUsize::new(stride).unwrap(),
+        epoch_ranges: vec![],
+    }
+}
+
+#[test]
+fn test_tipset_sampling() {
+    let epochs =
+        |sampling: TipsetSampling, head| sampling.epochs(head).into_iter().collect::<Vec<_>>();
+
+    // The last tipsets by default
+    assert_eq!(epochs(sampling(3, None, 1), 100), [98, 99, 100]);
+    assert_eq!(epochs(sampling(3, None, 5), 100), [90, 95, 100]);
+    // but not before genesis
+    assert_eq!(epochs(sampling(5, None, 3), 7), [1, 4, 7]);
+    assert!(epochs(sampling(0, None, 1), 100).is_empty());
+    // From a given epoch, regardless of the heaviest tipset
+    assert_eq!(epochs(sampling(3, Some(10), 1), 100), [10, 11, 12]);
+    assert_eq!(epochs(sampling(3, Some(10), 4), 11), [10, 14, 18]);
+
+    // Ranges ignore the number of tipsets, and may overlap
+    let sampling = TipsetSampling {
+        epoch_ranges: vec![20..30, 0..3, 23..27],
+        ..sampling(100, None, 5)
+    };
+    assert_eq!(epochs(sampling, 100), [0, 20, 23, 25]);
+}
+
+#[test]
+fn test_parse_epoch_range() {
+    assert_eq!(
+        parse_epoch_range("1420000..1420100").unwrap(),
+        1420000..1420100
+    );
+    assert!(parse_epoch_range("1420000").is_err());
+    assert!(parse_epoch_range("1420000..").is_err());
+    assert!(parse_epoch_range("20..10").is_err());
+    assert!(parse_epoch_range("20..20").is_err());
+}
+
+#[test]
+fn test_reports_by_tag() {
+    let mut records = test_records();
+    records[0].tags = &["chain"];
+    records[1].tags = &["chain", "slow"];
+    records[2].tags = &["state"];
+
+    let report = format_as_markdown(&forest_lotus(), &records, &[], false, true);
+    let groups = report
+        .lines()
+        .filter(|line| line.starts_with("Tag "))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        groups,
+        ["Tag `chain`", "Tag `slow`", "Tag `state`", "Tag `untagged`"]
+    );
+    let chain = report
+        .lines()
+        .skip_while(|line| *line != "Tag `chain`")
+        .nth(4)
+        .unwrap();
+    assert!(chain.contains("Filecoin.ChainHead (2)"), "{report}");
+    // Reports are not grouped by default
+    assert!(!format_as_markdown(&forest_lotus(), &records, &[], false, false).contains("Tag "));
+
+    let report: serde_json::Value =
+        serde_json::from_str(&format_as_json(&forest_lotus(), &records, &[], false, true).unwrap())
+            .unwrap();
+    assert_eq!(report["by_tag"]["chain"][0]["count"], 2);
+    assert_eq!(report["by_tag"]["slow"][0]["count"], 1);
+    assert_eq!(report["by_tag"]["untagged"].as_array().unwrap().len(), 2);
+    assert_eq!(
+        report["tests"][1]["tags"],
+        serde_json::json!(["chain", "slow"])
+    );
+    let report: serde_json::Value = serde_json::from_str(
+        &format_as_json(&forest_lotus(), &records, &[], false, false).unwrap(),
+    )
+    .unwrap();
+    assert!(report.get("by_tag").is_none());
+
+    let report = format_as_csv(&forest_lotus(), &records).unwrap();
+    assert!(report.lines().nth(2).unwrap().ends_with(",chain slow"));
+}
+
+#[test]
+fn test_uncovered_methods() {
+    let schema = |methods: &[&str]| -> DiscoverResult {
+        let methods = methods
+            .iter()
+            .map(|name| serde_json::json!({ "name": name, "paramStructure": "by-position", "params": [] }))
+            .collect::<Vec<_>>();
+        serde_json::from_value(serde_json::json!({
+            "info": { "title": "forest", "version": "0.0.0" },
+            "methods": methods,
+            "openrpc": "1.2.6",
+        }))
+        .unwrap()
+    };
+    let schemas = [
+        schema(&[
+            "Filecoin.ChainHead",
+            "Filecoin.StateCall",
+            "Filecoin.Version",
+        ]),
+        schema(&[
+            "Filecoin.ChainHead",
+            "Filecoin.StateCall",
+            "Filecoin.NodeStatus",
+        ]),
+    ];
+    let tests = [
+        RpcTest::basic(ApiInfo::version_req()),
+        RpcTest::validate(ApiInfo::chain_head_req(), |_, _| true),
+        RpcTest::basic(ApiInfo::discover_req()),
+    ];
+    // Methods of either API version, once each
+    assert_eq!(
+        uncovered_methods(&schemas, &tests),
+        ["Filecoin.NodeStatus", "Filecoin.StateCall"]
+    );
+
+    let uncovered = uncovered_methods(&schemas, &[]);
+    assert_eq!(uncovered.len(), 4);
+    let report = format_as_markdown(&forest_lotus(), &test_records(), &uncovered, false, false);
+    let section = report
+        .lines()
+        .skip_while(|line| *line != "Uncovered methods")
+        .collect::<Vec<_>>();
+    assert_eq!(section.len(), 2 + 2 + uncovered.len(), "{report}");
+    assert!(section.iter().any(|line| line.contains("Filecoin.Version")));
+    assert!(
+        !format_as_markdown(&forest_lotus(), &test_records(), &[], false, false)
+            .contains("Uncovered")
+    );
+
+    let report: serde_json::Value = serde_json::from_str(
+        &format_as_json(&forest_lotus(), &test_records(), &uncovered, false, false).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(report["uncovered"][0], "Filecoin.ChainHead");
+}
+
+/// Header of a tipset of a single block, at `epoch` on top of `parent`.
+fn header(parent: Option<&CachingBlockHeader>, epoch: ChainEpoch) -> CachingBlockHeader {
+    // Use a static counter to give all headers a unique timestamp
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let raw = RawBlockHeader {
+        epoch,
+        timestamp: COUNTER.fetch_add(1, Ordering::Relaxed) as u64,
+        ..Default::default()
+    };
+    CachingBlockHeader::new(match parent {
+        Some(parent) => RawBlockHeader {
+            parents: Tipset::from(parent).key().clone(),
+            ..raw
+        },
+        None => raw,
+    })
+}
+
+#[test]
+fn test_simulated_child() {
+    let db = crate::db::MemoryDB::default();
+    let parent = Tipset::from(header(None, 10));
+    let child = simulated_child(&db, &parent, 30).unwrap();
+    assert_eq!(child.parents, *parent.key());
+    assert_eq!(child.epoch, 11);
+    assert_eq!(child.timestamp, parent.min_timestamp() + 30);
+    assert!(child.weight > *parent.weight());
+    assert_eq!(child.state_root, *parent.parent_state());
+    // The messages of the child can be loaded
+    let (bls_messages, secp_messages) = crate::chain::store::block_messages(&db, &child).unwrap();
+    assert!(bls_messages.is_empty() && secp_messages.is_empty());
+}
+
+fn head_change(change: &str, header: &CachingBlockHeader) -> ApiHeadChange {
+    ApiHeadChange {
+        change: change.into(),
+        headers: vec![header.clone()],
+    }
+}
+
+#[test]
+fn test_notified_chain() {
+    let key = |header: &CachingBlockHeader| Tipset::from(header).key().clone();
+    let g = header(None, 10);
+    let a = header(Some(&g), 11);
+    let b = header(Some(&a), 12);
+    // A fork of `a`, and a child of `g` after null rounds
+    let c = header(Some(&g), 11);
+    let d = header(Some(&g), 14);
+    let current = || vec![head_change("current", &g)];
+    let apply = |header| vec![head_change("apply", header)];
+
+    let chain = NotifiedChain::replay(&[current(), apply(&a), apply(&b)]).unwrap();
+    assert_eq!(
+        chain,
+        NotifiedChain {
+            current: 10,
+            applied: BTreeMap::from([(11, key(&a)), (12, key(&b))]),
+            head: 12,
+        }
+    );
+    // Reorganizations revert the head before applying the new tipsets
+    let reorg = vec![head_change("revert", &a), head_change("apply", &c)];
+    let forked = NotifiedChain::replay(&[current(), apply(&a), reorg]).unwrap();
+    assert_eq!(forked.applied, BTreeMap::from([(11, key(&c))]));
+    assert_eq!(
+        NotifiedChain::replay(&[current(), apply(&d)]).unwrap().head,
+        14
+    );
+
+    // The first notification is the current head only
+    assert_eq!(NotifiedChain::replay(&[]), None);
+    assert_eq!(NotifiedChain::replay(&[apply(&a)]), None);
+    let currents = vec![head_change("current", &g), head_change("current", &a)];
+    assert_eq!(NotifiedChain::replay(&[currents]), None);
+    // Only the head can be reverted
+    let revert = vec![head_change("revert", &b)];
+    assert_eq!(NotifiedChain::replay(&[current(), apply(&a), revert]), None);
+    // Applied tipsets are newer than the head
+    assert_eq!(
+        NotifiedChain::replay(&[current(), apply(&b), apply(&a)]),
+        None
+    );
+    // or, after a revert, its children
+    let reorg = vec![head_change("revert", &a), head_change("apply", &b)];
+    assert_eq!(NotifiedChain::replay(&[current(), apply(&a), reorg]), None);
+    let unknown = vec![head_change("rollback", &a)];
+    assert_eq!(NotifiedChain::replay(&[current(), unknown]), None);
+
+    // Nodes may be notified of a few more tipsets than others
+    let short = NotifiedChain::replay(&[current(), apply(&a)]).unwrap();
+    assert!(chain.is_consistent_with(&short));
+    assert!(short.is_consistent_with(&chain));
+    assert!(!forked.is_consistent_with(&chain));
+    let late = NotifiedChain::replay(&[vec![head_change("current", &d)]]).unwrap();
+    assert!(!late.is_consistent_with(&short));
+}
+
+#[test]
+fn test_chain_notify_check() {
+    use EndpointStatus::*;
+    let limits = NotificationLimits {
+        count: 3,
+        duration: Duration::from_secs(1),
+    };
+    let test = websocket_tests(limits).into_iter().next().unwrap();
+    assert_eq!(test.request.method_name, "Filecoin.ChainNotify");
+
+    let g = header(None, 10);
+    let a = header(Some(&g), 11);
+    let notifications = |changes: &[(&str, &CachingBlockHeader)]| {
+        let notifications = changes
+            .iter()
+            .map(|(change, header)| vec![head_change(change, header)])
+            .collect::<Vec<_>>();
+        Ok(serde_json::to_value(notifications.into_lotus_json()).unwrap())
+    };
+    let valid = || notifications(&[("current", &g), ("apply", &a)]);
+    assert_eq!(test.check(vec![valid(), valid()]), [Valid, Valid]);
+    assert_eq!(
+        test.check(vec![notifications(&[("apply", &a)]), valid()]),
+        [InvalidResponse, Valid]
+    );
+    assert_eq!(
+        test.check(vec![Ok(serde_json::json!([{}])), valid()]),
+        [InvalidJSON, Valid]
+    );
+}
+
+// With writes enabled, a message pushed to the pool of the offline server is
+// pending and stored, although it is not gossiped.
+#[tokio::test]
+async fn offline_message_pool_keeps_pushed_messages() {
+    use crate::blocks::RawBlockHeader;
+    use crate::chain::get_chain_message;
+    use crate::chain_sync::TipsetValidator;
+    use crate::key_management::Wallet;
+    use crate::message::{ChainMessage, SignedMessage};
+    use crate::shim::crypto::SignatureType;
+    use crate::shim::state_tree::{ActorState, StateTreeVersion};
+    use crate::utils::db::CborStoreExt as _;
+
+    let db = Arc::new(MemoryDB::default());
+    let mut wallet = Wallet::new(KeyStore::new(KeyStoreConfig::Memory).unwrap());
+    let from = wallet.generate_addr(SignatureType::Secp256k1).unwrap();
+
+    // A genesis state in which the sender is a funded account
+    let mut init_state = fil_actor_init_state::v10::State::new(&db, "offline".into()).unwrap();
+    let id = init_state.map_address_to_new_id(&db, &from.into()).unwrap();
+    let mut state_tree = StateTree::new(db.clone(), StateTreeVersion::V5).unwrap();
+    // Addresses are resolved whatever the code of the init actor
+    let init_actor = ActorState::new(
+        Cid::default(),
+        db.put_cbor_default(&init_state).unwrap(),
+        TokenAmount::default(),
+        0,
+        None,
+    );
+    state_tree
+        .set_actor(&Address::INIT_ACTOR, init_actor)
+        .unwrap();
+    // calibnet account actor version 10
+    let account_actor = ActorState::new(
+        Cid::try_from("bafk2bzaceavfgpiw6whqigmskk74z4blm22nwjfnzxb4unlqz2e4wg3c5ujpw").unwrap(),
+        db.put_cbor_default(&()).unwrap(),
+        TokenAmount::from_whole(10),
+        0,
+        None,
+    );
+    state_tree
+        .set_actor(&Address::new_id(id), account_actor)
+        .unwrap();
+    let genesis = CachingBlockHeader::new(RawBlockHeader {
+        state_root: state_tree.flush().unwrap(),
+        messages: TipsetValidator::compute_msg_root(&db, &[], &[]).unwrap(),
+        timestamp: 1,
+        ..Default::default()
+    });
+    db.put_cbor_default(&genesis).unwrap();
+
+    let chain_config = Arc::new(ChainConfig::calibnet());
+    let chain_store = Arc::new(
+        ChainStore::new(
+            db.clone(),
+            db.clone(),
+            db.clone(),
+            chain_config.clone(),
+            genesis,
+        )
+        .unwrap(),
+    );
+    let state_manager = Arc::new(
+        StateManager::new(chain_store, chain_config, Arc::new(SyncConfig::default())).unwrap(),
+    );
+
+    let message: Message = crate::shim::message::Message_v3 {
+        to: Address::new_id(99).into(),
+        from: from.into(),
+        gas_limit: 1_000_000,
+        gas_fee_cap: TokenAmount::from_atto(100_000).into(),
+        gas_premium: TokenAmount::from_atto(1_000).into(),
+        ..Default::default()
+    }
+    .into();
+    let signature = wallet
+        .sign(&from, &message.cid().unwrap().to_bytes())
+        .unwrap();
+    let signed = SignedMessage::new_unchecked(message, signature);
+
+    let (message_pool, _) = offline_message_pool(&state_manager, "offline".into(), true).unwrap();
+    let cid = message_pool.push(signed.clone()).await.unwrap();
+    assert_eq!(cid, signed.cid().unwrap());
+    let (pending, _) = message_pool.pending().unwrap();
+    assert_eq!(pending, vec![signed.clone()]);
+    assert!(matches!(
+        get_chain_message(&db, &cid).unwrap(),
+        ChainMessage::Signed(stored) if stored == signed
+    ));
+}
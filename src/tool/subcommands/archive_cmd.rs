@@ -29,14 +29,15 @@
 use crate::blocks::Tipset;
 use crate::chain::{
     index::{ChainIndex, ResolveNullTipset},
-    ChainEpochDelta,
+    ChainEpochDelta, ExportOptions,
 };
 use crate::cid_collections::CidHashSet;
 use crate::cli_shared::{snapshot, snapshot::TrustedVendor};
 use crate::db::car::ManyCar;
 use crate::db::car::{AnyCar, RandomAccessFileReader};
+use crate::genesis::read_network_name;
 use crate::interpreter::VMTrace;
-use crate::ipld::{stream_graph, unordered_stream_graph};
+use crate::ipld::stream_graph;
 use crate::networks::{butterflynet, calibnet, mainnet, ChainConfig, NetworkChain};
 use crate::shim::address::CurrentNetwork;
 use crate::shim::clock::{ChainEpoch, EPOCHS_IN_DAY, EPOCH_DURATION_SECONDS};
@@ -53,7 +54,7 @@ use fvm_ipld_blockstore::Blockstore;
 use indicatif::ProgressIterator;
 use itertools::Itertools;
 use sha2::Sha256;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::io::{AsyncWriteExt, BufWriter};
 use tracing::info;
@@ -97,6 +98,36 @@ pub enum ArchiveCommands {
         /// Path to snapshot file.
         #[arg(required = true)]
         snapshot_files: Vec<PathBuf>,
+        /// Print the epochs that have a state-root or messages in the
+        /// snapshots instead
+        #[arg(long)]
+        coverage: bool,
+    },
+    /// Export the values reachable from the tipset at `--to` that are not
+    /// reachable from the tipset at `--from`. Merged with a snapshot at
+    /// `--from`, the resulting diff snapshot has the chain and state-roots of
+    /// `--to`. This is `export --diff` with the same depth for both tipsets.
+    ExportDiff {
+        /// Snapshot input paths. Supports `.car`, `.car.zst`, and `.forest.car.zst`.
+        #[arg(required = true)]
+        snapshot_files: Vec<PathBuf>,
+        /// Diff snapshot output filename or directory. Defaults to
+        /// `./forest_snapshot_{chain}_{year}-{month}-{day}_height_{epoch}.car.zst`.
+        #[arg(short, long, default_value = ".", verbatim_doc_comment)]
+        output_path: PathBuf,
+        /// Epoch of the base snapshot.
+        #[arg(long)]
+        from: ChainEpoch,
+        /// Epoch of the exported tipset. Defaults to the heaviest tipset of the
+        /// input.
+        #[arg(long)]
+        to: Option<ChainEpoch>,
+        /// How many state-roots the base and exported snapshots include.
+        #[arg(short, long, default_value_t = 2000)]
+        depth: ChainEpochDelta,
+        /// Overwrite output file without prompting.
+        #[arg(long, default_value_t = false)]
+        force: bool,
     },
     /// Merge snapshot archives into a single file. The output snapshot refers
     /// to the heaviest tipset in the input set.
@@ -163,7 +194,34 @@ impl ArchiveCommands {
             }
             Self::Checkpoints {
                 snapshot_files: snapshot,
+                coverage: false,
             } => print_checkpoints(snapshot),
+            Self::Checkpoints {
+                snapshot_files,
+                coverage: true,
+            } => print_coverage(snapshot_files),
+            Self::ExportDiff {
+                snapshot_files,
+                output_path,
+                from,
+                to,
+                depth,
+                force,
+            } => {
+                let store = ManyCar::try_from(snapshot_files)?;
+                let heaviest_tipset = store.heaviest_tipset()?;
+                do_export(
+                    store,
+                    heaviest_tipset,
+                    output_path,
+                    to,
+                    depth,
+                    Some(from),
+                    Some(depth),
+                    force,
+                )
+                .await
+            }
             Self::Merge {
                 snapshot_files,
                 output_path,
@@ -330,6 +388,77 @@ fn list_checkpoints(
     })
 }
 
+/// Epochs of the tipsets of a snapshot, and the ranges of these epochs that
+/// have a state-root or messages, most recent first.
+#[derive(Debug, PartialEq)]
+struct Coverage {
+    head: ChainEpoch,
+    state_roots: Vec<(ChainEpoch, ChainEpoch)>,
+    messages: Vec<(ChainEpoch, ChainEpoch)>,
+}
+
+impl std::fmt::Display for Coverage {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let ranges = |ranges: &[(ChainEpoch, ChainEpoch)]| {
+            ranges
+                .iter()
+                .map(|(low, high)| format!("{low}..={high}"))
+                .join(", ")
+        };
+        writeln!(f, "Tipsets:       0..={}", self.head)?;
+        writeln!(f, "State-roots:   {}", ranges(&self.state_roots))?;
+        write!(f, "Messages sets: {}", ranges(&self.messages))
+    }
+}
+
+impl Coverage {
+    // Like `ArchiveInfo`, a tipset is considered to have a state-root when
+    // the state it is computed on is available.
+    fn from_store(store: impl Blockstore, root: Tipset) -> anyhow::Result<Self> {
+        fn extend(ranges: &mut Vec<(ChainEpoch, ChainEpoch)>, contiguous: bool, epoch: ChainEpoch) {
+            match ranges.last_mut() {
+                Some((low, _)) if contiguous => *low = epoch,
+                _ => ranges.push((epoch, epoch)),
+            }
+        }
+
+        let head = root.epoch();
+        let (mut state_roots, mut messages) = (vec![], vec![]);
+        let (mut had_state_root, mut had_messages) = (false, false);
+        for tipset in root.chain(&store) {
+            let has_state_root = store.has(tipset.parent_state())?;
+            if has_state_root {
+                extend(&mut state_roots, had_state_root, tipset.epoch());
+            }
+            let has_messages = store.has(&tipset.min_ticket_block().messages)?;
+            if has_messages {
+                extend(&mut messages, had_messages, tipset.epoch());
+            }
+            (had_state_root, had_messages) = (has_state_root, has_messages);
+        }
+        Ok(Coverage {
+            head,
+            state_roots,
+            messages,
+        })
+    }
+}
+
+// Print the heaviest tipset of each snapshot, and which epochs are covered by
+// the snapshots together, e.g. a lite snapshot and the diff snapshots on top
+// of it.
+fn print_coverage(snapshot_files: Vec<PathBuf>) -> anyhow::Result<()> {
+    for path in &snapshot_files {
+        let car = AnyCar::try_from(path.as_path())
+            .with_context(|| format!("couldn't read input CAR file {}", path.display()))?;
+        println!("{}: {}", path.display(), car.heaviest_tipset()?.epoch());
+    }
+    let store = ManyCar::try_from(snapshot_files).context("couldn't read input CAR file")?;
+    let root = store.heaviest_tipset()?;
+    println!("{}", Coverage::from_store(&store, root)?);
+    Ok(())
+}
+
 // This does nothing if the output path is a file. If it is a directory - it produces the following:
 // `./forest_snapshot_{chain}_{year}-{month}-{day}_height_{epoch}.car.zst`.
 fn build_output_path(
@@ -368,7 +497,9 @@ async fn do_export(
     let store = Arc::new(store);
 
     let genesis = ts.genesis(&store)?;
-    let network = NetworkChain::from_genesis_or_devnet_placeholder(genesis.cid());
+    let network = NetworkChain::from_genesis_or_devnet(genesis.cid(), None, || {
+        read_network_name(&store, &genesis)
+    })?;
 
     let epoch = epoch_option.unwrap_or(ts.epoch());
 
@@ -388,38 +519,10 @@ async fn do_export(
         .tipset_by_height(epoch, ts, ResolveNullTipset::TakeOlder)
         .context("unable to get a tipset at given height")?;
 
-    let seen = if let Some(diff) = diff {
-        let diff_ts: Arc<Tipset> = index
-            .tipset_by_height(diff, ts.clone(), ResolveNullTipset::TakeOlder)
-            .context("diff epoch must be smaller than target epoch")?;
-        let diff_ts: &Tipset = &diff_ts;
-        let diff_limit = diff_depth.map(|depth| diff_ts.epoch() - depth).unwrap_or(0);
-        let mut stream = unordered_stream_graph(
-            store.clone(),
-            diff_ts.clone().chain(store.clone()),
-            diff_limit,
-        );
-        while stream.try_next().await?.is_some() {}
-        stream.into_seen()
-    } else {
-        CidHashSet::default()
-    };
-
     let output_path = build_output_path(network.to_string(), genesis.timestamp, epoch, output_path);
 
-    if !force && output_path.exists() {
-        let have_permission = Confirm::with_theme(&ColorfulTheme::default())
-            .with_prompt(format!(
-                "{} will be overwritten. Continue?",
-                output_path.to_string_lossy()
-            ))
-            .default(false)
-            .interact()
-            // e.g not a tty (or some other error), so haven't got permission.
-            .unwrap_or(false);
-        if !have_permission {
-            return Ok(());
-        }
+    if !force && !confirm_overwrite(&output_path) {
+        return Ok(());
     }
 
     let writer = tokio::fs::File::create(&output_path)
@@ -445,11 +548,63 @@ async fn do_export(
     pb.enable_steady_tick(std::time::Duration::from_secs_f32(0.1));
     let writer = pb.wrap_async_write(writer);
 
-    crate::chain::export::<Sha256>(store.clone(), &ts, depth, writer, seen, true).await?;
+    export_with_diff(store, ts, depth, diff, diff_depth, writer).await
+}
+
+/// Export the snapshot of `ts` with state-roots for `depth` epochs. With a
+/// `diff` epoch, the values reachable from the tipset at that epoch (with
+/// state-roots for `diff_depth` epochs) are left out.
+async fn export_with_diff(
+    store: Arc<impl Blockstore + Send + Sync + 'static>,
+    ts: Arc<Tipset>,
+    depth: ChainEpochDelta,
+    diff: Option<ChainEpoch>,
+    diff_depth: Option<ChainEpochDelta>,
+    writer: impl tokio::io::AsyncWrite + Unpin,
+) -> anyhow::Result<()> {
+    let index = ChainIndex::new(&store);
+    let seen = if let Some(diff) = diff {
+        let diff_ts: Arc<Tipset> = index
+            .tipset_by_height(diff, ts.clone(), ResolveNullTipset::TakeOlder)
+            .context("diff epoch must be smaller than target epoch")?;
+        let diff_ts: &Tipset = &diff_ts;
+        let diff_limit = diff_depth.map(|depth| diff_ts.epoch() - depth).unwrap_or(0);
+        let mut stream = stream_graph(&store, diff_ts.clone().chain(&store), diff_limit);
+        while stream.try_next().await?.is_some() {}
+        stream.into_seen()
+    } else {
+        CidHashSet::default()
+    };
+
+    crate::chain::export::<Sha256>(
+        store,
+        &ts,
+        depth,
+        writer,
+        ExportOptions {
+            skip_checksum: true,
+            seen,
+            ..Default::default()
+        },
+    )
+    .await?;
 
     Ok(())
 }
 
+fn confirm_overwrite(output_path: &Path) -> bool {
+    !output_path.exists()
+        || Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!(
+                "{} will be overwritten. Continue?",
+                output_path.to_string_lossy()
+            ))
+            .default(false)
+            .interact()
+            // e.g not a tty (or some other error), so haven't got permission.
+            .unwrap_or(false)
+}
+
 // TODO(lemmih): https://github.com/ChainSafe/forest/issues/3347
 //               Testing with diff snapshots can be significantly improved
 /// Merge a set of snapshots (diff snapshots or lite snapshots). The output
@@ -465,19 +620,8 @@ async fn merge_snapshots(
     let heaviest_tipset = store.heaviest_tipset()?;
     let roots = heaviest_tipset.key().to_cids();
 
-    if !force && output_path.exists() {
-        let have_permission = Confirm::with_theme(&ColorfulTheme::default())
-            .with_prompt(format!(
-                "{} will be overwritten. Continue?",
-                output_path.to_string_lossy()
-            ))
-            .default(false)
-            .interact()
-            // e.g not a tty (or some other error), so haven't got permission.
-            .unwrap_or(false);
-        if !have_permission {
-            return Ok(());
-        }
+    if !force && !confirm_overwrite(&output_path) {
+        return Ok(());
     }
 
     let mut writer = BufWriter::new(tokio::fs::File::create(&output_path).await.context(
@@ -526,7 +670,9 @@ async fn show_tipset_diff(
     }
 
     let genesis = heaviest_tipset.genesis(&store)?;
-    let network = NetworkChain::from_genesis_or_devnet_placeholder(genesis.cid());
+    let network = NetworkChain::from_genesis_or_devnet(genesis.cid(), None, || {
+        read_network_name(&store, &genesis)
+    })?;
 
     let timestamp = genesis.timestamp;
     let chain_index = ChainIndex::new(Arc::clone(&store));
@@ -584,8 +730,15 @@ async fn show_tipset_diff(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::blocks::{CachingBlockHeader, RawBlockHeader, TipsetKey};
+    use crate::chain_sync::TipsetValidator;
     use crate::db::car::AnyCar;
+    use crate::db::MemoryDB;
+    use crate::test_utils::{persist_chain, persist_epoch_state};
     use crate::utils::db::car_stream::CarStream;
+    use crate::utils::db::CborStoreExt as _;
+    use ahash::HashSet;
+    use nonempty::nonempty;
     use tempfile::TempDir;
     use tokio::io::BufReader;
 
@@ -644,4 +797,74 @@ mod tests {
         assert_eq!(info.network, "mainnet");
         assert_eq!(info.epoch, 0);
     }
+
+    const SNAPSHOT: &str = "test-snapshots/chain4.car";
+
+    // A chain on top of a genesis up to `head`, in which every tipset has its
+    // own state-root linking to a leaf. Returns the heaviest tipset and the
+    // header, state-root and leaf of each epoch.
+    fn chain_with_states(db: &MemoryDB, head: ChainEpoch) -> (Tipset, Vec<[Cid; 3]>) {
+        let messages = TipsetValidator::compute_msg_root(db, &[], &[]).unwrap();
+        let (state_root, leaf) = persist_epoch_state(db, 0);
+        let mut states = vec![(state_root, leaf)];
+        let genesis = CachingBlockHeader::new(RawBlockHeader {
+            parents: TipsetKey::from(nonempty![db.put_cbor_default(&"genesis parent").unwrap()]),
+            state_root,
+            messages,
+            ..Default::default()
+        });
+        db.put_cbor_default(&genesis).unwrap();
+        let genesis = Tipset::from(genesis);
+        let chain = persist_chain(db, &genesis, 1..=head, |_, epoch| {
+            let (state_root, leaf) = persist_epoch_state(db, epoch);
+            states.push((state_root, leaf));
+            RawBlockHeader {
+                state_root,
+                messages,
+                ..Default::default()
+            }
+        });
+        let blocks = std::iter::once(&genesis)
+            .chain(&chain)
+            .zip(states)
+            .map(|(tipset, (state_root, leaf))| {
+                [*tipset.min_ticket_block().cid(), state_root, leaf]
+            })
+            .collect();
+        (chain.last().unwrap_or(&genesis).clone(), blocks)
+    }
+
+    #[tokio::test]
+    async fn export_diff() {
+        let db = Arc::new(MemoryDB::default());
+        let (head, blocks) = chain_with_states(&db, 9);
+        let mut car = vec![];
+        export_with_diff(db, Arc::new(head), 6, Some(5), Some(6), &mut car)
+            .await
+            .unwrap();
+        let exported: HashSet<Cid> = CarStream::new(BufReader::new(car.as_slice()))
+            .await
+            .unwrap()
+            .map_ok(|block| block.cid)
+            .try_collect()
+            .await
+            .unwrap();
+
+        // The headers after epoch 5, and the state-roots of the last 6 epochs
+        // except those of epochs 4 and 5, which are within the depth of epoch
+        // 5. The messages root is shared by all blocks.
+        let expected: HashSet<Cid> = blocks[6..].iter().flatten().copied().collect();
+        assert_eq!(exported, expected);
+    }
+
+    #[test]
+    fn coverage() {
+        let store = ManyCar::try_from(vec![PathBuf::from(SNAPSHOT)]).unwrap();
+        let head = store.heaviest_tipset().unwrap();
+        let coverage = Coverage::from_store(&store, head).unwrap();
+        // Only the first tipsets of the test snapshot have a state
+        assert_eq!(coverage.head, 3);
+        assert_eq!(coverage.state_roots, vec![(0, 1)]);
+        assert_eq!(coverage.messages, vec![(0, 3)]);
+    }
 }
@@ -4,33 +4,51 @@
 use std::path::{Path, PathBuf};
 
 use anyhow::Context;
-use clap::Subcommand;
-use futures::{StreamExt, TryStreamExt};
+use cid::Cid;
+use clap::{Subcommand, ValueEnum};
+use futures::{SinkExt as _, Stream, StreamExt, TryStreamExt};
 use fvm_ipld_blockstore::Blockstore;
 use indicatif::{ProgressBar, ProgressStyle};
 use itertools::Itertools;
 use nonempty::NonEmpty;
 use tokio::{
     fs::File,
-    io::{AsyncWriteExt, BufReader},
+    io::{AsyncWrite, AsyncWriteExt, BufReader},
 };
 
-use crate::db::car::ForestCar;
+use crate::db::car::{forest, ForestCar};
 use crate::utils::db::{
-    car_stream::CarStream,
+    car_stream::{CarBlock, CarStream, CarWriter},
     car_util::{dedup_block_stream, merge_car_streams},
 };
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum MergeFormat {
+    /// A plain CAR, zstd compressed when the output ends with `.zst`
+    Carv1,
+    /// A `.forest.car.zst` file, with an index of its zstd frames
+    Forest,
+}
+
 #[derive(Debug, Subcommand)]
 pub enum CarCommands {
-    /// Concatenate two or more CAR files into a single archive
-    Concat {
+    /// Merge two or more CAR files into a single archive. The roots of all
+    /// inputs are kept, and blocks present in several inputs are written once.
+    #[command(alias = "concat")]
+    Merge {
         /// A list of CAR file paths. A CAR file can be a plain CAR, a zstd compressed CAR
         /// or a `.forest.car.zst` file
+        #[arg(required = true)]
         car_files: Vec<PathBuf>,
-        /// The output `.forest.car.zst` file path
+        /// The output file path
         #[arg(short, long)]
         output: PathBuf,
+        /// Format of the output
+        #[arg(long, value_enum, default_value_t = MergeFormat::Forest)]
+        format: MergeFormat,
+        /// zstd compression level of the output
+        #[arg(long, default_value_t = forest::DEFAULT_FOREST_CAR_COMPRESSION_LEVEL)]
+        compression_level: u16,
     },
     /// Check the validity of a CAR archive. For Filecoin-specific checks, see
     /// `forest-tool snapshot validate`.
@@ -49,31 +67,12 @@ pub enum CarCommands {
 impl CarCommands {
     pub async fn run(self) -> anyhow::Result<()> {
         match self {
-            Self::Concat { car_files, output } => {
-                let car_streams: Vec<_> = futures::stream::iter(car_files)
-                    .then(tokio::fs::File::open)
-                    .map_ok(tokio::io::BufReader::new)
-                    .and_then(CarStream::new)
-                    .try_collect()
-                    .await?;
-
-                let all_roots = NonEmpty::from_vec(
-                    car_streams
-                        .iter()
-                        .flat_map(|it| it.header.roots.iter())
-                        .unique()
-                        .cloned()
-                        .collect_vec(),
-                )
-                .context("car roots cannot be empty")?;
-
-                let frames = crate::db::car::forest::Encoder::compress_stream_default(
-                    dedup_block_stream(merge_car_streams(car_streams)).map_err(anyhow::Error::from),
-                );
-                let mut writer = tokio::io::BufWriter::new(tokio::fs::File::create(&output).await?);
-                crate::db::car::forest::Encoder::write(&mut writer, all_roots, frames).await?;
-                writer.flush().await?;
-            }
+            Self::Merge {
+                car_files,
+                output,
+                format,
+                compression_level,
+            } => merge(&car_files, &output, format, compression_level).await?,
             Self::Validate {
                 car_file,
                 ignore_block_validity,
@@ -84,6 +83,75 @@ impl CarCommands {
     }
 }
 
+/// Streams the blocks of all CAR files, in order, into the output. Only the
+/// CIDs of the blocks seen so far are kept in memory, so inputs of any size
+/// can be merged. Blocks that do not match their CID are rejected.
+async fn merge(
+    car_files: &[PathBuf],
+    output: &Path,
+    format: MergeFormat,
+    compression_level: u16,
+) -> anyhow::Result<()> {
+    let car_streams: Vec<_> = futures::stream::iter(car_files)
+        .then(File::open)
+        .map_ok(BufReader::new)
+        .and_then(CarStream::new)
+        .try_collect()
+        .await?;
+
+    let all_roots = NonEmpty::from_vec(
+        car_streams
+            .iter()
+            .flat_map(|it| it.header.roots.iter())
+            .unique()
+            .cloned()
+            .collect_vec(),
+    )
+    .context("car roots cannot be empty")?;
+
+    let blocks = dedup_block_stream(merge_car_streams(car_streams))
+        .map_err(anyhow::Error::from)
+        .and_then(|block| async move {
+            anyhow::ensure!(block.valid(), "CID/Block mismatch for block: {}", block.cid);
+            Ok(block)
+        });
+
+    let mut writer = tokio::io::BufWriter::new(File::create(output).await?);
+    match format {
+        MergeFormat::Forest => {
+            let frames = forest::Encoder::compress_stream(
+                forest::DEFAULT_FOREST_CAR_FRAME_SIZE,
+                compression_level,
+                blocks,
+            );
+            forest::Encoder::write(&mut writer, all_roots, frames).await?;
+        }
+        MergeFormat::Carv1 if output.extension().is_some_and(|it| it == "zst") => {
+            let encoder = async_compression::tokio::write::ZstdEncoder::with_quality(
+                &mut writer,
+                async_compression::Level::Precise(compression_level.into()),
+            );
+            write_carv1(encoder, all_roots, blocks).await?;
+        }
+        MergeFormat::Carv1 => write_carv1(&mut writer, all_roots, blocks).await?,
+    }
+    writer.flush().await?;
+    Ok(())
+}
+
+async fn write_carv1(
+    writer: impl AsyncWrite + Unpin,
+    roots: NonEmpty<Cid>,
+    blocks: impl Stream<Item = anyhow::Result<CarBlock>>,
+) -> anyhow::Result<()> {
+    let mut car_writer = CarWriter::new_carv1(roots, writer)?;
+    blocks
+        .forward((&mut car_writer).sink_map_err(anyhow::Error::from))
+        .await?;
+    car_writer.close().await?;
+    Ok(())
+}
+
 /// At present, three properties are checked:
 /// - The CAR file is syntactically valid and all blocks can be streamed.
 /// - Each block CID is checked against the hash of the block.
@@ -125,12 +193,14 @@ async fn validate(
 
 #[cfg(test)]
 mod tests {
-    use super::validate;
+    use super::{merge, validate, MergeFormat};
     use crate::db::car::forest;
+    use crate::db::car::ForestCar;
     use crate::networks::{calibnet, mainnet};
-    use crate::utils::db::car_stream::CarBlock;
+    use crate::utils::db::car_stream::{CarBlock, CarStream, CarWriter};
     use cid::multihash::{Code, MultihashDigest};
     use cid::Cid;
+    use futures::SinkExt as _;
     use futures::{stream::iter, StreamExt, TryStreamExt};
     use nonempty::{nonempty, NonEmpty};
     use std::io::Write;
@@ -254,4 +324,73 @@ mod tests {
         // Ignoring index validity should make the test pass.
         assert!(validate(&temp_path, false, true).await.is_ok());
     }
+
+    async fn create_carv1_file(roots: NonEmpty<Cid>, car_blocks: Vec<CarBlock>) -> TempPath {
+        let temp_path = Builder::new().tempfile().unwrap().into_temp_path();
+        let file = tokio::fs::File::create(&temp_path).await.unwrap();
+        let mut writer = CarWriter::new_carv1(roots, file).unwrap();
+        writer
+            .send_all(&mut iter(car_blocks).map(Ok))
+            .await
+            .unwrap();
+        writer.close().await.unwrap();
+        temp_path
+    }
+
+    async fn read_car_file(path: &std::path::Path) -> (NonEmpty<Cid>, Vec<Cid>) {
+        let file = tokio::io::BufReader::new(tokio::fs::File::open(path).await.unwrap());
+        let stream = CarStream::new(file).await.unwrap();
+        let roots = stream.header.roots.clone();
+        let cids = stream
+            .map_ok(|block| block.cid)
+            .try_collect()
+            .await
+            .unwrap();
+        (roots, cids)
+    }
+
+    // Two CAR files sharing a root and a block are merged into each format
+    #[tokio::test]
+    async fn merge_overlapping_cars() {
+        let blocks = ["a", "b", "c", "d"].map(valid_block);
+        let cids = blocks.clone().map(|block| block.cid);
+        let first = create_carv1_file(nonempty![cids[0]], blocks[..3].to_vec()).await;
+        let second = create_carv1_file(nonempty![cids[3], cids[0]], blocks[2..].to_vec()).await;
+        let inputs = [first.to_path_buf(), second.to_path_buf()];
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        for (format, name) in [
+            (MergeFormat::Forest, "merged.forest.car.zst"),
+            (MergeFormat::Carv1, "merged.car.zst"),
+            (MergeFormat::Carv1, "merged.car"),
+        ] {
+            let output = temp_dir.path().join(name);
+            merge(&inputs, &output, format, 3).await.unwrap();
+            let (roots, merged) = read_car_file(&output).await;
+            assert_eq!(roots, nonempty![cids[0], cids[3]], "{name}");
+            assert_eq!(merged, cids, "{name}");
+            assert_eq!(
+                ForestCar::try_from(output.as_path()).is_ok(),
+                format == MergeFormat::Forest,
+                "{name}"
+            );
+            assert!(validate(&output, false, true).await.is_ok(), "{name}");
+        }
+    }
+
+    #[tokio::test]
+    async fn merge_invalid_blocks() {
+        let first = create_carv1_file(
+            nonempty![valid_block("a").cid],
+            vec![valid_block("a"), invalid_block("b")],
+        )
+        .await;
+        let temp_dir = tempfile::tempdir().unwrap();
+        let output = temp_dir.path().join("merged.car");
+        assert!(
+            merge(&[first.to_path_buf()], &output, MergeFormat::Carv1, 3)
+                .await
+                .is_err()
+        );
+    }
 }
@@ -0,0 +1,46 @@
+// Copyright 2019-2024 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use crate::networks::{ChainConfig, NetworkChain};
+use clap::Subcommand;
+use std::path::PathBuf;
+
+#[derive(Debug, Subcommand)]
+pub enum ChainConfigCommands {
+    /// Print the resolved chain configuration and check it for
+    /// inconsistencies
+    Validate {
+        /// Filecoin network chain
+        #[arg(long, default_value = "mainnet", conflicts_with = "chain_spec")]
+        chain: NetworkChain,
+        /// Chain spec file of a custom network, in TOML or JSON
+        #[arg(long)]
+        chain_spec: Option<PathBuf>,
+    },
+}
+
+impl ChainConfigCommands {
+    pub fn run(self) -> anyhow::Result<()> {
+        match self {
+            Self::Validate { chain, chain_spec } => {
+                let config = match chain_spec {
+                    Some(path) => ChainConfig::from_file_unvalidated(path)?,
+                    None => ChainConfig::from_chain(&chain),
+                };
+                println!("{}", toml::to_string_pretty(&config)?);
+                let violations = config.violations();
+                for violation in &violations {
+                    println!("violation: {violation}");
+                }
+                anyhow::ensure!(
+                    violations.is_empty(),
+                    "the {} chain configuration has {} violation(s)",
+                    config.network,
+                    violations.len()
+                );
+                println!("The {} chain configuration is valid", config.network);
+                Ok(())
+            }
+        }
+    }
+}
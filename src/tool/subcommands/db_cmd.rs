@@ -1,13 +1,19 @@
 // Copyright 2019-2024 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
+use crate::blocks::{Tipset, TipsetKey};
+use crate::chain_sync::Checkpoint;
 use crate::cli::subcommands::prompt_confirm;
 use crate::cli_shared::{chain_path, read_config};
-use crate::db::db_engine::db_root;
+use crate::db::db_engine::{db_root, Db, DbConfig};
+use crate::db::parity_db::ColumnStats;
+use crate::db::{setting_keys::HEAD_KEY, SettingsStore as _, SettingsStoreExt as _};
 use crate::networks::NetworkChain;
+use crate::shim::clock::ChainEpoch;
 use clap::Subcommand;
+use human_repr::HumanCount as _;
 use tracing::error;
 
 #[derive(Debug, Subcommand)]
@@ -21,6 +27,16 @@ pub enum DBCommands {
         #[arg(long)]
         chain: Option<NetworkChain>,
     },
+    /// Apply and remove the write-ahead logs of the database, and report the
+    /// space reclaimed
+    Compact {
+        /// Optional TOML file containing forest daemon configuration
+        #[arg(short, long)]
+        config: Option<PathBuf>,
+        /// Optional chain, will override the chain section of configuration file if used
+        #[arg(long)]
+        chain: Option<NetworkChain>,
+    },
     /// DB destruction
     Destroy {
         /// Answer yes to all forest-cli yes/no questions without prompting
@@ -39,14 +55,23 @@ impl DBCommands {
     pub async fn run(&self) -> anyhow::Result<()> {
         match self {
             Self::Stats { config, chain } => {
-                use human_repr::HumanCount;
+                let (_, config) = read_config(config.as_ref(), chain.clone())?;
 
+                let dir = db_root(&chain_path(&config))?;
+                println!("{}", DbStats::read(&dir, config.db_config())?);
+                Ok(())
+            }
+            Self::Compact { config, chain } => {
                 let (_, config) = read_config(config.as_ref(), chain.clone())?;
 
                 let dir = db_root(&chain_path(&config))?;
-                println!("Database path: {}", dir.display());
-                let size = fs_extra::dir::get_size(dir).unwrap_or_default();
-                println!("Database size: {}", size.human_count_bytes());
+                let (before, after) = compact(&dir, config.db_config())?;
+                println!(
+                    "Reclaimed {} ({} -> {})",
+                    before.saturating_sub(after).human_count_bytes(),
+                    before.human_count_bytes(),
+                    after.human_count_bytes()
+                );
                 Ok(())
             }
             Self::Destroy {
@@ -64,7 +89,12 @@ impl DBCommands {
                     );
                     return Ok(());
                 }
-                println!("Deleting {}", dir.display());
+                ensure_unlocked(&db_root(&dir)?, config.db_config())?;
+                println!(
+                    "Deleting {} ({})",
+                    dir.display(),
+                    dir_size(&dir).human_count_bytes()
+                );
                 if !force && !prompt_confirm() {
                     println!("Aborted.");
                     return Ok(());
@@ -83,3 +113,186 @@ impl DBCommands {
         }
     }
 }
+
+#[derive(Debug, thiserror::Error)]
+#[error("the database at {} is in use, stop the Forest daemon first", .0.display())]
+struct DbInUse(PathBuf);
+
+/// Opens an existing database. The daemon holds a lock on the database while
+/// it runs, in which case [`DbInUse`] is returned.
+pub(super) fn open_unlocked(dir: &Path, config: &DbConfig) -> anyhow::Result<Db> {
+    anyhow::ensure!(dir.is_dir(), "no database found at {}", dir.display());
+    Db::open(dir, config).map_err(|e| match e.downcast_ref::<parity_db::Error>() {
+        Some(parity_db::Error::Locked(_)) => DbInUse(dir.to_owned()).into(),
+        _ => e,
+    })
+}
+
+/// Fails if the database is in use. Databases that cannot be opened for other
+/// reasons, e.g. an old format, are not in use.
+fn ensure_unlocked(dir: &Path, config: &DbConfig) -> anyhow::Result<()> {
+    match open_unlocked(dir, config) {
+        Err(e) if e.is::<DbInUse>() => Err(e),
+        _ => Ok(()),
+    }
+}
+
+fn dir_size(dir: &Path) -> u64 {
+    fs_extra::dir::get_size(dir).unwrap_or_default()
+}
+
+#[derive(Debug)]
+struct DbStats {
+    path: PathBuf,
+    size: u64,
+    columns: Vec<ColumnStats>,
+    head: Option<(TipsetKey, Option<ChainEpoch>)>,
+    checkpoint: Option<Checkpoint>,
+    setting_keys: Vec<String>,
+}
+
+impl DbStats {
+    fn read(dir: &Path, config: &DbConfig) -> anyhow::Result<Self> {
+        let db = open_unlocked(dir, config)?;
+        let head = db
+            .read_obj::<TipsetKey>(HEAD_KEY)?
+            .map(|key| {
+                let epoch = Tipset::load(&db, &key)?.map(|tipset| tipset.epoch());
+                anyhow::Ok((key, epoch))
+            })
+            .transpose()?;
+        Ok(DbStats {
+            path: dir.to_owned(),
+            size: dir_size(dir),
+            columns: db.column_stats(dir)?,
+            head,
+            checkpoint: Checkpoint::load(&db)?,
+            setting_keys: db.setting_keys()?,
+        })
+    }
+}
+
+impl std::fmt::Display for DbStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "Database path: {}", self.path.display())?;
+        writeln!(f, "Database size: {}", self.size.human_count_bytes())?;
+        writeln!(f, "Columns:")?;
+        for column in &self.columns {
+            writeln!(
+                f,
+                "  {:<24} {:>12} values {:>12}",
+                column.name,
+                column.values,
+                column.size.human_count_bytes().to_string()
+            )?;
+        }
+        match &self.head {
+            Some((key, Some(epoch))) => writeln!(f, "Head:          {epoch} {key}")?,
+            Some((key, None)) => writeln!(f, "Head:          missing tipset {key}")?,
+            None => writeln!(f, "Head:          none")?,
+        }
+        match &self.checkpoint {
+            Some(checkpoint) => writeln!(
+                f,
+                "Checkpoint:    {} {}",
+                checkpoint.epoch(),
+                checkpoint.key()
+            )?,
+            None => writeln!(f, "Checkpoint:    none")?,
+        }
+        write!(f, "Settings:      {}", self.setting_keys.join(", "))
+    }
+}
+
+/// `ParityDb` has no explicit compaction. Opening the database applies its
+/// write-ahead logs to the value tables and removes them, which is what
+/// reclaims space after the daemon has stopped.
+fn compact(dir: &Path, config: &DbConfig) -> anyhow::Result<(u64, u64)> {
+    let before = dir_size(dir);
+    drop(open_unlocked(dir, config)?);
+    Ok((before, dir_size(dir)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::ChainStore;
+    use crate::networks::{mainnet, ChainConfig};
+    use crate::utils::db::car_util::load_car;
+    use std::sync::Arc;
+
+    /// Creates a database with the mainnet genesis as its head and checkpoint.
+    async fn populate(dir: &Path) {
+        let db = Arc::new(Db::open(dir, &DbConfig::default()).unwrap());
+        let header = load_car(&db, mainnet::DEFAULT_GENESIS).await.unwrap();
+        let genesis = Tipset::load_required(&db, &header.roots.into()).unwrap();
+        let chain_store = ChainStore::new(
+            db.clone(),
+            db.clone(),
+            db.clone(),
+            Arc::new(ChainConfig::mainnet()),
+            genesis.min_ticket_block().clone(),
+        )
+        .unwrap();
+        Checkpoint::from(&*chain_store.heaviest_tipset())
+            .save(&db)
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn stats() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path().join("db");
+        populate(&dir).await;
+
+        let stats = DbStats::read(&dir, &DbConfig::default()).unwrap();
+        assert!(stats.size > 0);
+        let values = stats
+            .columns
+            .iter()
+            .map(|column| (column.name.as_str(), column.values))
+            .collect::<Vec<_>>();
+        assert!(values[0].1 > 0, "{values:?}");
+        assert_eq!(values[2], ("Settings", 2), "{values:?}");
+        assert_eq!(stats.head.as_ref().unwrap().1, Some(0));
+        assert_eq!(stats.checkpoint.as_ref().unwrap().epoch(), 0);
+        assert_eq!(
+            stats.checkpoint.as_ref().unwrap().key(),
+            &stats.head.as_ref().unwrap().0
+        );
+        assert!(stats.setting_keys.contains(&HEAD_KEY.to_owned()));
+    }
+
+    #[tokio::test]
+    async fn compact_keeps_values() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path().join("db");
+        populate(&dir).await;
+
+        let values = |dir| {
+            DbStats::read(dir, &DbConfig::default())
+                .unwrap()
+                .columns
+                .iter()
+                .map(|column| column.values)
+                .collect::<Vec<_>>()
+        };
+        let before = values(&dir);
+        compact(&dir, &DbConfig::default()).unwrap();
+        assert_eq!(values(&dir), before);
+    }
+
+    #[tokio::test]
+    async fn refuse_locked_db() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path().join("db");
+        populate(&dir).await;
+
+        let _daemon_db = Db::open(&dir, &DbConfig::default()).unwrap();
+        let config = DbConfig::default();
+        assert!(DbStats::read(&dir, &config).unwrap_err().is::<DbInUse>());
+        assert!(compact(&dir, &config).unwrap_err().is::<DbInUse>());
+        assert!(ensure_unlocked(&dir, &config).unwrap_err().is::<DbInUse>());
+        assert!(DbStats::read(&temp_dir.path().join("missing"), &config).is_err());
+    }
+}
@@ -0,0 +1,218 @@
+// Copyright 2019-2024 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use super::db_cmd::open_unlocked;
+use crate::blocks::{Tipset, TipsetKey};
+use crate::chain::index::{ChainIndex, ResolveNullTipset};
+use crate::chain::message_index::index_messages;
+use crate::cli_shared::{chain_path, read_config};
+use crate::db::car::ManyCar;
+use crate::db::db_engine::db_root;
+use crate::db::{setting_keys::HEAD_KEY, IndicesStore, SettingsStoreExt as _};
+use crate::networks::NetworkChain;
+use crate::shim::clock::ChainEpoch;
+use anyhow::Context as _;
+use clap::Subcommand;
+use fvm_ipld_blockstore::Blockstore;
+
+#[derive(Debug, Subcommand)]
+pub enum IndexCommands {
+    /// Index the messages of the chain stored in the database, so that the
+    /// daemon finds old messages without scanning the chain. Tipsets synced by
+    /// the daemon are indexed as they are validated.
+    BackfillMessages {
+        /// Optional TOML file containing forest daemon configuration
+        #[arg(short, long)]
+        config: Option<PathBuf>,
+        /// Optional chain, will override the chain section of configuration file if used
+        #[arg(long)]
+        chain: Option<NetworkChain>,
+        /// Snapshot files to read the chain from, starting at their heaviest
+        /// tipset. The index is written to the database either way.
+        #[arg(long)]
+        snapshot: Vec<PathBuf>,
+        /// Epoch to index back from. Defaults to the head of the chain.
+        #[arg(long)]
+        from: Option<ChainEpoch>,
+        /// Epoch to index back to (exclusive)
+        #[arg(long, default_value_t = 0)]
+        to: ChainEpoch,
+    },
+}
+
+impl IndexCommands {
+    pub async fn run(self) -> anyhow::Result<()> {
+        match self {
+            Self::BackfillMessages {
+                config,
+                chain,
+                snapshot,
+                from,
+                to,
+            } => {
+                let (_, config) = read_config(config.as_ref(), chain)?;
+                let db = Arc::new(open_unlocked(
+                    &db_root(&chain_path(&config))?,
+                    config.db_config(),
+                )?);
+                let store = ManyCar::new(db.clone());
+                let head = if snapshot.is_empty() {
+                    let head = db
+                        .read_obj::<TipsetKey>(HEAD_KEY)?
+                        .context("the database has no head tipset")?;
+                    Tipset::load_required(&store, &head)?
+                } else {
+                    store.read_only_files(snapshot.into_iter())?;
+                    store.heaviest_tipset()?
+                };
+                let head = match from {
+                    Some(from) => ChainIndex::new(&store).tipset_by_height(
+                        from,
+                        Arc::new(head),
+                        ResolveNullTipset::TakeOlder,
+                    )?,
+                    None => Arc::new(head),
+                };
+
+                let (tipsets, messages) =
+                    backfill_messages(&store, db.as_ref(), head.as_ref(), to)?;
+                println!("Indexed {messages} messages executed by {tipsets} tipsets");
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Indexes the messages executed by `head` and its ancestors above epoch `to`.
+/// Returns the number of indexed tipsets and messages.
+fn backfill_messages(
+    db: &impl Blockstore,
+    indices: &(impl IndicesStore + ?Sized),
+    head: &Tipset,
+    to: ChainEpoch,
+) -> anyhow::Result<(usize, usize)> {
+    let pb = indicatif::ProgressBar::new(head.epoch().saturating_sub(to).max(0) as u64).with_style(
+        indicatif::ProgressStyle::with_template(
+            "{spinner} indexed epoch {msg} {wide_bar} {pos}/{len} in {elapsed}",
+        )
+        .expect("indicatif template must be valid"),
+    );
+    let (mut tipsets, mut messages) = (0, 0);
+    for tipset in head.clone().chain(db).take_while(|ts| ts.epoch() > to) {
+        messages += index_messages(db, indices, &tipset)
+            .with_context(|| format!("failed to index epoch {}", tipset.epoch()))?;
+        tipsets += 1;
+        pb.set_message(tipset.epoch().to_string());
+        pb.set_position((head.epoch() - tipset.epoch() + 1) as u64);
+    }
+    pb.finish_and_clear();
+    Ok((tipsets, messages))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::{CachingBlockHeader, RawBlockHeader};
+    use crate::chain::ChainStore;
+    use crate::chain_sync::{SyncConfig, TipsetValidator};
+    use crate::db::MemoryDB;
+    use crate::message_pool::test_provider::{mock_block, mock_block_with_parents};
+    use crate::networks::ChainConfig;
+    use crate::shim::{address::Address, message::Message};
+    use crate::state_manager::StateManager;
+    use crate::test_utils::{persist_chain, CountingStore};
+    use crate::utils::db::CborStoreExt;
+    use cid::Cid;
+    use fil_actors_shared::fvm_ipld_amt::Amtv0 as Amt;
+    use fvm_shared4::{error::ExitCode, receipt::Receipt};
+
+    const CHAIN_LENGTH: ChainEpoch = 100;
+
+    // Builds a chain of `CHAIN_LENGTH` epochs on top of a genesis block, each
+    // including one message, whose receipt reports the epoch it was included at
+    // as the gas used. Returns the genesis block, the head and the messages.
+    fn chain_with_messages(db: &CountingStore) -> (CachingBlockHeader, Tipset, Vec<Cid>) {
+        let empty = TipsetValidator::compute_msg_root(db, &[], &[]).unwrap();
+        let genesis = CachingBlockHeader::new(RawBlockHeader {
+            messages: empty,
+            timestamp: 7777,
+            ..mock_block(1, 1).into_raw()
+        });
+        db.put_cbor_default(&genesis).unwrap();
+
+        let mut messages = vec![];
+        let chain = persist_chain(
+            db,
+            &Tipset::from(&genesis),
+            1..=CHAIN_LENGTH,
+            |parent, epoch| {
+                let message: Message = fvm_shared4::message::Message {
+                    from: Address::new_id(100).into(),
+                    to: Address::new_id(101).into(),
+                    sequence: epoch as u64,
+                    ..Default::default()
+                }
+                .into();
+                let receipts = (epoch > 1).then(|| Receipt {
+                    exit_code: ExitCode::OK,
+                    return_data: Default::default(),
+                    gas_used: epoch as u64 - 1,
+                    events_root: None,
+                });
+                messages.push(db.put_cbor_default(&message).unwrap());
+                RawBlockHeader {
+                    messages: TipsetValidator::compute_msg_root(db, &[message], &[]).unwrap(),
+                    message_receipts: Amt::new_from_iter(db, receipts).unwrap(),
+                    ..mock_block_with_parents(parent, 1, 1).into_raw()
+                }
+            },
+        );
+        (genesis, chain.last().unwrap().clone(), messages)
+    }
+
+    #[tokio::test]
+    async fn backfilled_messages_found_without_scanning() {
+        let db = Arc::new(CountingStore::default());
+        let indices = Arc::new(MemoryDB::default());
+        let (genesis, head, messages) = chain_with_messages(&db);
+
+        // The message of the head hasn't been executed yet
+        let (tipsets, indexed) =
+            backfill_messages(db.as_ref(), indices.as_ref(), &head, 0).unwrap();
+        assert_eq!(tipsets, CHAIN_LENGTH as usize);
+        assert_eq!(indexed, CHAIN_LENGTH as usize - 1);
+
+        let mut chain_config = ChainConfig::default();
+        chain_config.policy.chain_finality = 10;
+        let chain_config = Arc::new(chain_config);
+        let chain_store = Arc::new(
+            ChainStore::new(
+                db.clone(),
+                Arc::new(MemoryDB::default()),
+                indices,
+                chain_config.clone(),
+                genesis,
+            )
+            .unwrap(),
+        );
+        chain_store.set_heaviest_tipset(Arc::new(head)).unwrap();
+        let state_manager = Arc::new(
+            StateManager::new(chain_store, chain_config, Arc::new(SyncConfig::default())).unwrap(),
+        );
+
+        db.take_reads();
+        let (tipset, receipt) = state_manager
+            .search_for_message(None, messages[0], None, true)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(tipset.epoch(), 2);
+        assert_eq!(receipt.gas_used(), 1);
+        // Scanning the chain would read at least a block per epoch
+        let reads = db.take_reads();
+        assert!(reads <= 10, "{reads} blocks read");
+    }
+}
@@ -6,11 +6,14 @@ mod archive_cmd;
 mod backup_cmd;
 mod benchmark_cmd;
 mod car_cmd;
+mod chain_config_cmd;
 mod db_cmd;
 mod fetch_params_cmd;
+mod index_cmd;
 mod net_cmd;
 mod shed_cmd;
 mod snapshot_cmd;
+mod state_cmd;
 mod state_migration_cmd;
 
 use crate::cli_shared::cli::HELP_MESSAGE;
@@ -29,6 +32,7 @@ pub struct Cli {
 
 /// forest-tool sub-commands
 #[derive(clap::Subcommand)]
+#[allow(clippy::large_enum_variant)]
 pub enum Subcommand {
     /// Create and restore backups
     #[command(subcommand)]
@@ -38,6 +42,10 @@ pub enum Subcommand {
     #[command(subcommand)]
     Benchmark(benchmark_cmd::BenchmarkCommands),
 
+    /// State inspection tools
+    #[command(subcommand)]
+    State(state_cmd::StateCommands),
+
     /// State migration tools
     #[command(subcommand)]
     StateMigration(state_migration_cmd::StateMigrationCommands),
@@ -58,6 +66,10 @@ pub enum Subcommand {
     #[command(subcommand)]
     DB(db_cmd::DBCommands),
 
+    /// Manage the indices of the chain
+    #[command(subcommand)]
+    Index(index_cmd::IndexCommands),
+
     /// Utilities for manipulating CAR files
     #[command(subcommand)]
     Car(car_cmd::CarCommands),
@@ -66,6 +78,10 @@ pub enum Subcommand {
     #[command(subcommand)]
     Api(api_cmd::ApiCommands),
 
+    /// Inspect chain configurations
+    #[command(subcommand)]
+    ChainConfig(chain_config_cmd::ChainConfigCommands),
+
     /// Network utilities
     #[command(subcommand)]
     Net(net_cmd::NetCommands),
@@ -3,7 +3,10 @@
 
 use std::path::PathBuf;
 
-use crate::{libp2p::keypair::get_keypair, rpc_client::ApiInfo};
+use crate::{
+    libp2p::keypair::get_keypair,
+    rpc_client::{ApiInfo, ApiVersion},
+};
 use anyhow::Context as _;
 use base64::{prelude::BASE64_STANDARD, Engine};
 use clap::Subcommand;
@@ -59,6 +62,7 @@ impl ShedCommands {
                 let client = ApiInfo {
                     multiaddr: host,
                     token: None,
+                    version: ApiVersion::default(),
                 };
                 let head = client.chain_head().await?;
                 let end_height = match height {
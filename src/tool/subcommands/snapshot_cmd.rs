@@ -2,30 +2,37 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use super::*;
-use crate::blocks::Tipset;
+use crate::blocks::{Tipset, TipsetKey};
 use crate::chain::index::{ChainIndex, ResolveNullTipset};
+use crate::cid_collections::CidHashSet;
+use crate::cli_shared::cli::OutputFormat;
 use crate::cli_shared::snapshot;
 use crate::daemon::bundle::load_actor_bundles;
 use crate::db::car::forest::DEFAULT_FOREST_CAR_FRAME_SIZE;
 use crate::db::car::{AnyCar, ManyCar};
+use crate::genesis::read_network_name;
 use crate::interpreter::{MessageCallbackCtx, VMTrace};
 use crate::ipld::stream_chain;
-use crate::networks::{butterflynet, calibnet, mainnet, ChainConfig, NetworkChain};
-use crate::shim::address::CurrentNetwork;
+use crate::lotus_json::LotusJson;
+use crate::networks::{ChainConfig, NetworkChain};
+use crate::shim::address::{Address, CurrentNetwork};
 use crate::shim::clock::ChainEpoch;
 use crate::shim::fvm_shared_latest::address::Network;
 use crate::shim::machine::MultiEngine;
+use crate::shim::state_tree::{ActorState, StateTree};
 use crate::state_manager::apply_block_messages;
 use crate::utils::db::car_stream::CarStream;
 use crate::utils::proofs_api::paramfetch::ensure_params_downloaded;
 use anyhow::{bail, Context as _};
-use cid::Cid;
 use clap::Subcommand;
 use dialoguer::{theme::ColorfulTheme, Confirm};
 use futures::TryStreamExt;
 use fvm_ipld_blockstore::Blockstore;
 use indicatif::{ProgressBar, ProgressStyle};
-use std::path::PathBuf;
+use itertools::Itertools as _;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::fs::File;
 use tokio::io::AsyncWriteExt;
@@ -45,6 +52,9 @@ pub enum SnapshotCommands {
     },
 
     /// Validates the snapshot.
+    ///
+    /// Exits with code 3 if the snapshot is corrupted, and with code 4 if it
+    /// is truncated.
     Validate {
         /// Number of recent epochs to scan for broken links
         #[arg(long, default_value_t = 2000)]
@@ -61,6 +71,25 @@ pub enum SnapshotCommands {
         snapshot_files: Vec<PathBuf>,
     },
 
+    /// Compares the chains and the state coverage of two snapshots.
+    Diff {
+        /// Path to a snapshot CAR, which may be zstd compressed
+        a: PathBuf,
+        /// Path to the snapshot CAR to compare it with
+        b: PathBuf,
+        /// Also list the actors whose state differs at a shared epoch
+        #[arg(long)]
+        deep: bool,
+        /// Epoch to compare the actors at. Defaults to the most recent epoch
+        /// with state-roots in both snapshots.
+        #[arg(long, requires = "deep")]
+        epoch: Option<ChainEpoch>,
+        /// Output format, a summary of the differences or every difference as
+        /// JSON
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+
     /// Make this snapshot suitable for use as a compressed car-backed blockstore.
     Compress {
         /// Input CAR file, in `.car`, `.car.zst`, or `.forest.car.zst` format.
@@ -130,15 +159,44 @@ impl SnapshotCommands {
                 check_stateroots,
                 snapshot_files,
             } => {
-                let store = ManyCar::try_from(snapshot_files)?;
-                validate_with_blockstore(
-                    store.heaviest_tipset()?,
-                    Arc::new(store),
-                    check_links,
-                    check_network,
-                    check_stateroots,
-                )
-                .await
+                let result = async {
+                    verify_blocks(&snapshot_files).await?;
+                    let store = ManyCar::try_from(snapshot_files)?;
+                    let root = store
+                        .heaviest_tipset()
+                        .map_err(|e| SnapshotDamage::Corrupted(format!("invalid roots: {e}")))?;
+                    validate_with_blockstore(
+                        root,
+                        Arc::new(store),
+                        check_links,
+                        check_network,
+                        check_stateroots,
+                    )
+                    .await
+                }
+                .await;
+                if let Some(damage) = result
+                    .as_ref()
+                    .err()
+                    .and_then(|e| e.downcast_ref::<SnapshotDamage>())
+                {
+                    cli_error_and_die(damage.to_string(), damage.exit_code());
+                }
+                result
+            }
+            Self::Diff {
+                a,
+                b,
+                deep,
+                epoch,
+                format,
+            } => {
+                let diff = diff_snapshots(&a, &b, deep, epoch).await?;
+                match format {
+                    OutputFormat::Text => print!("{diff}"),
+                    OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&diff)?),
+                }
+                Ok(())
             }
             Self::Compress {
                 source,
@@ -211,10 +269,74 @@ impl SnapshotCommands {
     }
 }
 
+/// Damage found in a snapshot, told apart by the exit code of
+/// `forest-tool snapshot validate`.
+#[derive(Debug, thiserror::Error)]
+enum SnapshotDamage {
+    #[error("snapshot is corrupted: {0}")]
+    Corrupted(String),
+    #[error("snapshot is truncated: {0}")]
+    Truncated(String),
+}
+
+impl SnapshotDamage {
+    fn exit_code(&self) -> i32 {
+        match self {
+            Self::Corrupted(_) => 3,
+            Self::Truncated(_) => 4,
+        }
+    }
+
+    fn from_read_error(path: &Path, error: std::io::Error) -> Self {
+        let message = format!("{}: {error}", path.display());
+        match error.kind() {
+            std::io::ErrorKind::UnexpectedEof => Self::Truncated(message),
+            _ => Self::Corrupted(message),
+        }
+    }
+}
+
+// Every block is hashed and checked against its CID, reading the snapshots to
+// their end. A snapshot ending in the middle of a block or a zstd frame was
+// truncated, e.g. by an interrupted download.
+async fn verify_blocks(snapshot_files: &[PathBuf]) -> anyhow::Result<()> {
+    let pb = validation_spinner("Verifying block CIDs:").with_finish(
+        indicatif::ProgressFinish::AbandonWithMessage("❌ Invalid blocks!".into()),
+    );
+    let mut count = 0u64;
+    for path in snapshot_files {
+        let file = tokio::io::BufReader::new(File::open(path).await?);
+        let mut blocks = CarStream::new(file)
+            .await
+            .map_err(|e| SnapshotDamage::from_read_error(path, e))?;
+        while let Some(block) = blocks
+            .try_next()
+            .await
+            .map_err(|e| SnapshotDamage::from_read_error(path, e))?
+        {
+            if !block.valid() {
+                return Err(SnapshotDamage::Corrupted(format!(
+                    "{}: block {} does not match its CID",
+                    path.display(),
+                    block.cid
+                ))
+                .into());
+            }
+            count += 1;
+            if count % 10_000 == 0 {
+                pb.set_message(format!("{count} blocks"));
+            }
+        }
+    }
+    pb.finish_with_message("✅ verified!");
+    Ok(())
+}
+
 // Check the validity of a snapshot by looking at IPLD links, the genesis block,
 // and message output. More checks may be added in the future.
 //
 // If the snapshot is valid, the output should look like this:
+//     Verifying block CIDs:          ✅ verified!
 //     Checking IPLD integrity:       ✅ verified!
 //     Identifying genesis block:     ✅ found!
 //     Verifying network identity:    ✅ verified!
@@ -223,6 +345,7 @@ impl SnapshotCommands {
 //
 // If we receive a mainnet snapshot but expect a calibnet snapshot, the output
 // should look like this:
+//     Verifying block CIDs:          ✅ verified!
 //     Checking IPLD integrity:       ✅ verified!
 //     Identifying genesis block:     ✅ found!
 //     Verifying network identity:    ❌ wrong!
@@ -267,7 +390,8 @@ where
 // The Filecoin block chain is a DAG of Ipld nodes. The complete graph isn't
 // required to sync to the network and snapshot files usually disgard data after
 // 2000 epochs. Validity can be verified by ensuring there are no bad IPLD or
-// broken links in the N most recent epochs.
+// broken links in the N most recent epochs, and that the headers of these
+// epochs link to their parents.
 async fn validate_ipld_links<DB>(ts: Tipset, db: &DB, epochs: u32) -> anyhow::Result<()>
 where
     DB: Blockstore + Send + Sync,
@@ -278,6 +402,8 @@ where
         indicatif::ProgressFinish::AbandonWithMessage("❌ Invalid IPLD data!".into()),
     );
 
+    let mut last_epoch: Option<ChainEpoch> = None;
+    let mut bad_link = None;
     let tipsets = ts.chain(db).inspect(|tipset| {
         let height = tipset.epoch();
         if height - epoch_limit >= 0 {
@@ -285,9 +411,28 @@ where
         } else {
             pb.set_message(format!("{} remaining epochs (spine)", height));
         }
+        if last_epoch.is_some_and(|child| height >= child) {
+            bad_link.get_or_insert(height);
+        }
+        last_epoch = Some(height);
     });
     let mut stream = stream_chain(&db, tipsets, epoch_limit);
-    while stream.try_next().await?.is_some() {}
+    while let Some(block) = stream.try_next().await.transpose() {
+        block.map_err(|e| SnapshotDamage::Corrupted(e.to_string()))?;
+    }
+    drop(stream);
+
+    if let Some(height) = bad_link {
+        bail!(SnapshotDamage::Corrupted(format!(
+            "tipset at epoch {height} is not older than its child"
+        )));
+    }
+    // The spine ends early if the parents of a tipset are missing
+    if let Some(last_epoch) = last_epoch.filter(|&epoch| epoch > 0 && epoch > epoch_limit) {
+        bail!(SnapshotDamage::Corrupted(format!(
+            "parents of the tipset at epoch {last_epoch} are missing"
+        )));
+    }
 
     pb.finish_with_message("✅ verified!");
     Ok(())
@@ -303,20 +448,9 @@ fn query_network(ts: &Tipset, db: impl Blockstore) -> anyhow::Result<NetworkChai
         indicatif::ProgressFinish::AbandonWithMessage("✅ found!".into()),
     );
 
-    fn match_genesis_block(block_cid: Cid) -> anyhow::Result<NetworkChain> {
-        if block_cid == *calibnet::GENESIS_CID {
-            Ok(NetworkChain::Calibnet)
-        } else if block_cid == *mainnet::GENESIS_CID {
-            Ok(NetworkChain::Mainnet)
-        } else if block_cid == *butterflynet::GENESIS_CID {
-            Ok(NetworkChain::Butterflynet)
-        } else {
-            bail!("Unrecognizable genesis block");
-        }
-    }
-
     if let Ok(genesis_block) = ts.genesis(db) {
-        return match_genesis_block(*genesis_block.cid());
+        return NetworkChain::from_genesis(genesis_block.cid())
+            .context("Unrecognizable genesis block");
     }
 
     pb.finish_with_message("❌ No valid genesis block!");
@@ -388,6 +522,208 @@ where
     Ok(())
 }
 
+/// How two snapshots differ, as reported by `forest-tool snapshot diff`.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct SnapshotDiff {
+    a: SnapshotSummary,
+    b: SnapshotSummary,
+    /// Epochs with state-roots in both snapshots
+    shared_state_roots: Option<(ChainEpoch, ChainEpoch)>,
+    blocks_only_in_a: usize,
+    blocks_only_in_b: usize,
+    blocks_in_both: usize,
+    actors: Option<ActorsDiff>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct SnapshotSummary {
+    head_epoch: ChainEpoch,
+    head: LotusJson<TipsetKey>,
+    /// The most recent epochs whose tipsets have a state-root
+    state_roots: Option<(ChainEpoch, ChainEpoch)>,
+}
+
+/// The actors whose state differs at an epoch.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct ActorsDiff {
+    epoch: ChainEpoch,
+    changed: Vec<String>,
+    only_in_a: Vec<String>,
+    only_in_b: Vec<String>,
+}
+
+impl std::fmt::Display for SnapshotDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let range = |range: Option<(ChainEpoch, ChainEpoch)>| match range {
+            Some((from, to)) => format!("epochs {from} to {to}"),
+            None => "none".to_string(),
+        };
+        for (name, summary) in [("A", &self.a), ("B", &self.b)] {
+            writeln!(
+                f,
+                "{name}: head at epoch {} ({})",
+                summary.head_epoch,
+                summary.head.0.to_cids().iter().join(", ")
+            )?;
+            writeln!(f, "   state-roots: {}", range(summary.state_roots))?;
+        }
+        writeln!(f, "Shared state-roots: {}", range(self.shared_state_roots))?;
+        writeln!(f, "Blocks only in A:   {}", self.blocks_only_in_a)?;
+        writeln!(f, "Blocks only in B:   {}", self.blocks_only_in_b)?;
+        writeln!(f, "Blocks in both:     {}", self.blocks_in_both)?;
+        if let Some(actors) = &self.actors {
+            writeln!(f, "Actors at epoch {}:", actors.epoch)?;
+            for (label, addresses) in [
+                ("changed", &actors.changed),
+                ("only in A", &actors.only_in_a),
+                ("only in B", &actors.only_in_b),
+            ] {
+                for address in addresses {
+                    writeln!(f, "  {address} {label}")?;
+                }
+            }
+            if actors.changed.is_empty()
+                && actors.only_in_a.is_empty()
+                && actors.only_in_b.is_empty()
+            {
+                writeln!(f, "  no differences")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+async fn diff_snapshots(
+    a: &Path,
+    b: &Path,
+    deep: bool,
+    epoch: Option<ChainEpoch>,
+) -> anyhow::Result<SnapshotDiff> {
+    let store_a = Arc::new(ManyCar::try_from(vec![a.to_path_buf()])?);
+    let store_b = Arc::new(ManyCar::try_from(vec![b.to_path_buf()])?);
+    let head_a = store_a.heaviest_tipset()?;
+    let head_b = store_b.heaviest_tipset()?;
+    let summary = |head: &Tipset, store| {
+        anyhow::Ok(SnapshotSummary {
+            head_epoch: head.epoch(),
+            head: LotusJson(head.key().clone()),
+            state_roots: state_root_range(head, store)?,
+        })
+    };
+    let a_summary = summary(&head_a, &store_a)?;
+    let b_summary = summary(&head_b, &store_b)?;
+    let shared_state_roots = match (a_summary.state_roots, b_summary.state_roots) {
+        (Some((from_a, to_a)), Some((from_b, to_b))) => {
+            Some((from_a.max(from_b), to_a.min(to_b))).filter(|(from, to)| from <= to)
+        }
+        _ => None,
+    };
+
+    let cids_a = read_cids(a).await?;
+    let cids_b = read_cids(b).await?;
+    let (blocks_in_a, blocks_in_b) = (cids_a.len(), cids_b.len());
+    let blocks_in_both = cids_b
+        .into_iter()
+        .filter(|cid| cids_a.contains(cid))
+        .count();
+
+    let actors = match deep {
+        true => {
+            let epoch = epoch
+                .or(shared_state_roots.map(|(_, to)| to))
+                .context("the snapshots have no state-roots at a shared epoch")?;
+            Some(diff_actors(
+                epoch,
+                read_actors(epoch, head_a, &store_a)?,
+                read_actors(epoch, head_b, &store_b)?,
+            ))
+        }
+        false => None,
+    };
+
+    Ok(SnapshotDiff {
+        a: a_summary,
+        b: b_summary,
+        shared_state_roots,
+        blocks_only_in_a: blocks_in_a - blocks_in_both,
+        blocks_only_in_b: blocks_in_b - blocks_in_both,
+        blocks_in_both,
+        actors,
+    })
+}
+
+/// The most recent range of epochs whose tipsets all have a state-root in the
+/// store.
+fn state_root_range(
+    head: &Tipset,
+    store: impl Blockstore,
+) -> anyhow::Result<Option<(ChainEpoch, ChainEpoch)>> {
+    let mut range: Option<(ChainEpoch, ChainEpoch)> = None;
+    for tipset in head.clone().chain(&store) {
+        match (store.has(tipset.parent_state())?, &mut range) {
+            (true, Some((oldest, _))) => *oldest = tipset.epoch(),
+            (true, None) => range = Some((tipset.epoch(), tipset.epoch())),
+            (false, Some(_)) => break,
+            (false, None) => {}
+        }
+    }
+    Ok(range)
+}
+
+async fn read_cids(snapshot: &Path) -> anyhow::Result<CidHashSet> {
+    let file = tokio::io::BufReader::new(File::open(snapshot).await?);
+    CarStream::new(file)
+        .await?
+        .map_ok(|block| block.cid)
+        .try_collect()
+        .await
+        .with_context(|| format!("failed to read {}", snapshot.display()))
+}
+
+fn read_actors<DB: Blockstore>(
+    epoch: ChainEpoch,
+    head: Tipset,
+    store: &Arc<DB>,
+) -> anyhow::Result<BTreeMap<Address, ActorState>> {
+    let tipset = ChainIndex::new(Arc::clone(store)).tipset_by_height(
+        epoch,
+        Arc::new(head),
+        ResolveNullTipset::TakeOlder,
+    )?;
+    let state_tree = StateTree::new_from_root(Arc::clone(store), tipset.parent_state())?;
+    let mut actors = BTreeMap::new();
+    state_tree.for_each(|address, actor| {
+        actors.insert(address, actor.clone());
+        Ok(())
+    })?;
+    Ok(actors)
+}
+
+fn diff_actors(
+    epoch: ChainEpoch,
+    a: BTreeMap<Address, ActorState>,
+    mut b: BTreeMap<Address, ActorState>,
+) -> ActorsDiff {
+    let mut diff = ActorsDiff {
+        epoch,
+        changed: vec![],
+        only_in_a: vec![],
+        only_in_b: vec![],
+    };
+    for (address, actor) in a {
+        match b.remove(&address) {
+            Some(other) if other != actor => diff.changed.push(address.to_string()),
+            Some(_) => {}
+            None => diff.only_in_a.push(address.to_string()),
+        }
+    }
+    diff.only_in_b = b.into_keys().map(|address| address.to_string()).collect();
+    diff
+}
+
 fn validation_spinner(prefix: &'static str) -> indicatif::ProgressBar {
     let pb = indicatif::ProgressBar::new_spinner()
         .with_style(
@@ -407,7 +743,9 @@ fn print_computed_state(snapshot: PathBuf, epoch: ChainEpoch, json: bool) -> any
     let ts = store.heaviest_tipset()?;
 
     let genesis = ts.genesis(&store)?;
-    let network = NetworkChain::from_genesis_or_devnet_placeholder(genesis.cid());
+    let network = NetworkChain::from_genesis_or_devnet(genesis.cid(), None, || {
+        read_network_name(&store, &genesis)
+    })?;
 
     let timestamp = genesis.timestamp;
     let chain_index = ChainIndex::new(Arc::clone(&store));
@@ -512,3 +850,90 @@ mod structured {
         }))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipld::stream_graph;
+    use crate::utils::db::car_stream::CarWriter;
+    use cid::Cid;
+    use futures::StreamExt as _;
+
+    const SNAPSHOT: &str = "test-snapshots/chain4.car";
+
+    /// Copies the block headers of a snapshot, and the genesis state.
+    async fn prune(snapshot: &Path, output: &Path) {
+        let store = ManyCar::try_from(vec![snapshot.to_path_buf()]).unwrap();
+        let head = store.heaviest_tipset().unwrap();
+        let writer = CarWriter::new_carv1(
+            head.key().to_cids(),
+            tokio::fs::File::create(output).await.unwrap(),
+        )
+        .unwrap();
+        stream_graph(&store, head.clone().chain(&store), head.epoch())
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+            .forward(writer)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn diff_pruned_copy() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let pruned = temp_dir.path().join("pruned.car");
+        prune(SNAPSHOT.as_ref(), &pruned).await;
+
+        let diff = diff_snapshots(SNAPSHOT.as_ref(), &pruned, false, None)
+            .await
+            .unwrap();
+        assert_eq!(diff.a.head.0, diff.b.head.0);
+        // The test snapshot only has the state of its first tipsets, and the
+        // genesis messages leave the genesis state as it is
+        assert_eq!(diff.a.state_roots, Some((0, 1)));
+        assert_eq!(diff.b.state_roots, Some((0, 1)));
+        assert_eq!(diff.shared_state_roots, Some((0, 1)));
+        assert!(diff.blocks_only_in_a > 0);
+        assert_eq!(diff.blocks_only_in_b, 0);
+        assert!(diff.blocks_in_both > 0);
+        assert!(diff.actors.is_none());
+
+        let actors = diff_snapshots(SNAPSHOT.as_ref(), &pruned, true, None)
+            .await
+            .unwrap()
+            .actors
+            .unwrap();
+        assert_eq!(actors.epoch, 1);
+        assert!(actors.changed.is_empty());
+        assert!(actors.only_in_a.is_empty());
+        assert!(actors.only_in_b.is_empty());
+        assert!(diff_snapshots(SNAPSHOT.as_ref(), &pruned, true, Some(3))
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn diff_actors_by_address() {
+        let actor = |balance| {
+            ActorState::new(
+                Cid::default(),
+                Cid::default(),
+                crate::shim::econ::TokenAmount::from_atto(balance),
+                0,
+                None,
+            )
+        };
+        let a = BTreeMap::from([
+            (Address::new_id(1), actor(1)),
+            (Address::new_id(2), actor(2)),
+        ]);
+        let b = BTreeMap::from([
+            (Address::new_id(1), actor(1)),
+            (Address::new_id(2), actor(3)),
+            (Address::new_id(3), actor(3)),
+        ]);
+        let diff = diff_actors(10, a, b);
+        assert_eq!(diff.changed, [Address::new_id(2).to_string()]);
+        assert!(diff.only_in_a.is_empty());
+        assert_eq!(diff.only_in_b, [Address::new_id(3).to_string()]);
+    }
+}
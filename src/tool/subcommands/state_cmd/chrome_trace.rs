@@ -0,0 +1,208 @@
+// Copyright 2019-2024 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Converts execution traces to the Chrome trace event format, which Perfetto
+//! and `chrome://tracing` open as flame graphs.
+//!
+//! Each call is a slice lasting as many time units as the gas it used, its own
+//! and that of its subcalls. The subcalls of a call follow its own gas charges,
+//! in order.
+
+use crate::rpc_api::data_types::ExecutionTrace;
+use crate::shim::address::Address;
+use ahash::HashMap;
+use serde::Serialize;
+use serde_json::json;
+
+/// A complete event, i.e. a slice, of the Chrome trace event format.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TraceEvent {
+    pub name: String,
+    pub cat: &'static str,
+    pub ph: &'static str,
+    /// Gas used before the call, by the calls it is nested in or follows
+    pub ts: u64,
+    /// Gas used by the call and its subcalls
+    pub dur: u64,
+    pub pid: u32,
+    pub tid: u32,
+    pub args: serde_json::Value,
+}
+
+/// The Chrome trace event JSON of `trace`.
+pub fn chrome_trace(trace: &ExecutionTrace) -> serde_json::Value {
+    json!({
+        "traceEvents": trace_events(trace),
+        "displayTimeUnit": "ns",
+    })
+}
+
+/// A slice for each call of `trace`, callers first.
+pub fn trace_events(trace: &ExecutionTrace) -> Vec<TraceEvent> {
+    let mut events = vec![];
+    push_events(&mut events, trace, 0);
+    events
+}
+
+/// Pushes the slices of `trace` starting at `ts`.
+fn push_events(events: &mut Vec<TraceEvent>, trace: &ExecutionTrace, ts: u64) {
+    let own_gas = own_gas(trace);
+    events.push(TraceEvent {
+        name: format!("{} method {}", trace.msg.to, trace.msg.method),
+        cat: "call",
+        ph: "X",
+        ts,
+        dur: total_gas(trace),
+        pid: 1,
+        tid: 1,
+        args: json!({
+            "from": trace.msg.from.to_string(),
+            "to": trace.msg.to.to_string(),
+            "method": trace.msg.method,
+            "value": trace.msg.value.to_string(),
+            "exit_code": trace.msg_rct.exit_code.value(),
+            "own_gas": own_gas,
+        }),
+    });
+    let mut ts = ts + own_gas;
+    for subcall in &trace.subcalls {
+        push_events(events, subcall, ts);
+        ts += total_gas(subcall);
+    }
+}
+
+fn total_gas(trace: &ExecutionTrace) -> u64 {
+    own_gas(trace) + trace.subcalls.iter().map(total_gas).sum::<u64>()
+}
+
+fn own_gas(trace: &ExecutionTrace) -> u64 {
+    trace
+        .gas_charges
+        .iter()
+        .map(|charge| charge.total_gas)
+        .sum()
+}
+
+/// The gas used by the calls to an actor, not counting their subcalls.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActorGas {
+    pub actor: Address,
+    pub calls: u64,
+    pub gas: u64,
+}
+
+/// The gas used by each actor called in `trace`, the most used first.
+pub fn gas_by_actor(trace: &ExecutionTrace) -> Vec<ActorGas> {
+    fn add(actors: &mut HashMap<Address, ActorGas>, trace: &ExecutionTrace) {
+        let actor = actors.entry(trace.msg.to).or_insert(ActorGas {
+            actor: trace.msg.to,
+            calls: 0,
+            gas: 0,
+        });
+        actor.calls += 1;
+        actor.gas += own_gas(trace);
+        for subcall in &trace.subcalls {
+            add(actors, subcall);
+        }
+    }
+    let mut actors = HashMap::default();
+    add(&mut actors, trace);
+    let mut actors: Vec<_> = actors.into_values().collect();
+    actors.sort_by(|a, b| {
+        b.gas
+            .cmp(&a.gas)
+            .then_with(|| a.actor.to_string().cmp(&b.actor.to_string()))
+    });
+    actors
+}
+
+/// A table of the gas used by each actor, with its share of the total.
+pub fn render_gas_summary(actors: &[ActorGas]) -> String {
+    let total: u64 = actors.iter().map(|actor| actor.gas).sum();
+    let mut text = format!(
+        "{:<24} {:>8} {:>16} {:>7}\n",
+        "Actor", "Calls", "Gas", "Share"
+    );
+    for actor in actors {
+        let share = match total {
+            0 => 0.0,
+            total => actor.gas as f64 * 100.0 / total as f64,
+        };
+        text += &format!(
+            "{:<24} {:>8} {:>16} {:>6.1}%\n",
+            actor.actor.to_string(),
+            actor.calls,
+            actor.gas,
+            share
+        );
+    }
+    text += &format!("{:<24} {:>8} {:>16}\n", "Total", "", total);
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lotus_json::HasLotusJson;
+    use crate::rpc_api::data_types::ApiInvocResult;
+
+    fn trace() -> ExecutionTrace {
+        let json = include_str!("replay_output.json");
+        ApiInvocResult::from_lotus_json(serde_json::from_str(json).unwrap())
+            .execution_trace
+            .unwrap()
+    }
+
+    #[test]
+    fn calls_last_as_long_as_their_gas() {
+        let slices = trace_events(&trace())
+            .into_iter()
+            .map(|event| (event.name, event.ts, event.dur))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            slices,
+            [
+                ("f04 method 2".into(), 0, 1550),
+                ("f01 method 2".into(), 1300, 250),
+                ("f0100 method 0".into(), 1500, 50),
+                ("f099 method 3".into(), 1550, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn calls_are_complete_events() {
+        let json = chrome_trace(&trace());
+        let event = &json["traceEvents"][3];
+        assert_eq!(event["ph"], "X");
+        assert_eq!(event["args"]["from"], "f04");
+        assert_eq!(event["args"]["exit_code"], 16);
+        assert_eq!(json["traceEvents"][0]["args"]["own_gas"], 1300);
+    }
+
+    #[test]
+    fn gas_is_summed_by_actor() {
+        let actors = gas_by_actor(&trace());
+        let summary = actors
+            .iter()
+            .map(|actor| (actor.actor.to_string(), actor.calls, actor.gas))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            [
+                ("f04".into(), 1, 1300),
+                ("f01".into(), 1, 200),
+                ("f0100".into(), 1, 50),
+                ("f099".into(), 1, 0),
+            ]
+        );
+        let table = render_gas_summary(&actors);
+        let rows = table
+            .lines()
+            .map(|line| line.split_whitespace().collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(rows[0], ["Actor", "Calls", "Gas", "Share"]);
+        assert_eq!(rows[1], ["f04", "1", "1300", "83.9%"]);
+        assert_eq!(rows[5], ["Total", "1550"]);
+    }
+}
@@ -0,0 +1,53 @@
+// Copyright 2019-2024 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+mod chrome_trace;
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
+use std::str::FromStr as _;
+
+use crate::rpc_api::data_types::ApiTipsetKey;
+use crate::rpc_client::ApiInfo;
+use anyhow::Context as _;
+use cid::Cid;
+use clap::Subcommand;
+
+#[derive(Debug, Subcommand)]
+pub enum StateCommands {
+    /// Replay a message with `Filecoin.StateReplay` and export its gas trace
+    /// in the Chrome trace event format, which Perfetto and `chrome://tracing`
+    /// open as a flame graph. Prints the gas used by each actor.
+    ReplayTrace {
+        /// CID of the message to replay
+        #[arg(long)]
+        message: Cid,
+        /// File to write the trace to
+        #[arg(long)]
+        out: PathBuf,
+        /// Node to replay the message on
+        #[arg(long, default_value_t = ApiInfo::from_str("/ip4/127.0.0.1/tcp/2345/http").expect("infallible"))]
+        node: ApiInfo,
+    },
+}
+
+impl StateCommands {
+    pub async fn run(self) -> anyhow::Result<()> {
+        match self {
+            Self::ReplayTrace { message, out, node } => {
+                let result = node.state_replay(ApiTipsetKey(None), message).await?;
+                let trace = result
+                    .execution_trace
+                    .with_context(|| format!("no execution trace for message {message}"))?;
+                let writer = BufWriter::new(File::create(&out)?);
+                serde_json::to_writer(writer, &chrome_trace::chrome_trace(&trace))?;
+                print!(
+                    "{}",
+                    chrome_trace::render_gas_summary(&chrome_trace::gas_by_actor(&trace))
+                );
+                Ok(())
+            }
+        }
+    }
+}
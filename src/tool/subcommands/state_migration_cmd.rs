@@ -1,7 +1,8 @@
 // Copyright 2019-2024 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use crate::networks::generate_actor_bundle;
+use crate::cli_shared::cli::Config;
+use crate::networks::{generate_actor_bundle, verify_actor_bundles, ActorBundleOptions};
 use std::path::PathBuf;
 
 #[derive(Debug, clap::Subcommand)]
@@ -10,15 +11,51 @@ pub enum StateMigrationCommands {
     ActorBundle {
         #[arg(default_value = "actor_bundles.car.zst")]
         output: PathBuf,
+        /// Download and verify the bundles against their recorded checksums,
+        /// without writing the merged bundle
+        #[arg(long)]
+        verify_only: bool,
+        /// Directory in which downloaded bundles are kept, defaults to
+        /// `actor_bundles` in the Forest data directory
+        #[arg(long)]
+        cache_dir: Option<PathBuf>,
+        /// Only use the bundles of the cache, failing if any is missing
+        #[arg(long)]
+        offline: bool,
     },
 }
 
 impl StateMigrationCommands {
     pub async fn run(self) -> anyhow::Result<()> {
         match self {
-            Self::ActorBundle { output } => {
-                generate_actor_bundle(&output).await?;
-                println!("Wrote the actors bundle to {}", output.display());
+            Self::ActorBundle {
+                verify_only: true, ..
+            } => {
+                for (info, sha256) in verify_actor_bundles().await? {
+                    let status = if info.sha256.is_some() {
+                        "verified"
+                    } else {
+                        "no recorded checksum"
+                    };
+                    println!("{} {} {sha256} ({status})", info.network, info.manifest);
+                }
+                Ok(())
+            }
+            Self::ActorBundle {
+                output,
+                cache_dir,
+                offline,
+                ..
+            } => {
+                let options = ActorBundleOptions {
+                    output,
+                    cache_dir: cache_dir
+                        .unwrap_or_else(|| Config::default().client.data_dir.join("actor_bundles")),
+                    offline,
+                };
+                let sha256 = generate_actor_bundle(&options).await?;
+                println!("Wrote the actors bundle to {}", options.output.display());
+                println!("sha256: {sha256}");
                 Ok(())
             }
         }
@@ -17,8 +17,8 @@ use std::io;
 use std::pin::Pin;
 use std::task::{Context, Poll};
 use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncWrite};
-use tokio_util::codec::Encoder;
 use tokio_util::codec::FramedRead;
+use tokio_util::codec::{Decoder, Encoder};
 use tokio_util::either::Either;
 use unsigned_varint::codec::UviBytes;
 
@@ -78,7 +78,7 @@ pin_project! {
     /// automatically be decompressed.
     pub struct CarStream<ReaderT> {
         #[pin]
-        reader: FramedRead<Either<ReaderT, ZstdDecoder<ReaderT>>, UviBytes>,
+        reader: FramedRead<Either<ReaderT, ZstdDecoder<ReaderT>>, UviFrames>,
         pub header: CarHeader,
         first_block: Option<CarBlock>,
     }
@@ -97,9 +97,9 @@ impl<ReaderT: AsyncBufRead + Unpin> CarStream<ReaderT> {
         let mut reader = if is_compressed {
             let mut zstd = ZstdDecoder::new(reader);
             zstd.multiple_members(true);
-            FramedRead::new(Either::Right(zstd), UviBytes::default())
+            FramedRead::new(Either::Right(zstd), UviFrames)
         } else {
-            FramedRead::new(Either::Left(reader), UviBytes::default())
+            FramedRead::new(Either::Left(reader), UviFrames)
         };
         let header = read_header(&mut reader)
             .await
@@ -191,8 +191,50 @@ impl<W: AsyncWrite> Sink<CarBlock> for CarWriter<W> {
     }
 }
 
+/// Decodes varint-prefixed frames like [`UviBytes`], but reports a frame cut
+/// off by the end of the input as [`io::ErrorKind::UnexpectedEof`], so that
+/// truncated CAR files are told apart from invalid ones.
+struct UviFrames;
+
+impl Decoder for UviFrames {
+    type Item = BytesMut;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> io::Result<Option<BytesMut>> {
+        let (len, prefix_len) = match unsigned_varint::decode::usize(src) {
+            Ok((len, rest)) => (len, src.len() - rest.len()),
+            Err(unsigned_varint::decode::Error::Insufficient) => return Ok(None),
+            Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+        };
+        // The limit of `UviBytes`, which keeps corrupted lengths from being
+        // allocated
+        if len > 128 * 1024 * 1024 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "block is too large",
+            ));
+        }
+        if src.len() < prefix_len + len {
+            src.reserve(prefix_len + len - src.len());
+            return Ok(None);
+        }
+        src.advance(prefix_len);
+        Ok(Some(src.split_to(len)))
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> io::Result<Option<BytesMut>> {
+        match self.decode(src)? {
+            None if !src.is_empty() => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "the last block is truncated",
+            )),
+            frame => Ok(frame),
+        }
+    }
+}
+
 async fn read_header<ReaderT: AsyncRead + Unpin>(
-    framed_reader: &mut FramedRead<ReaderT, UviBytes>,
+    framed_reader: &mut FramedRead<ReaderT, UviFrames>,
 ) -> Option<CarHeader> {
     let header = from_slice_with_fallback::<CarHeader>(&framed_reader.next().await?.ok()?).ok()?;
     if header.version != 1 {
@@ -234,6 +276,14 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn stream_truncated() {
+        let car = calibnet::DEFAULT_GENESIS;
+        let stream = CarStream::new(&car[..car.len() - 1]).await.unwrap();
+        let error = stream.try_collect::<Vec<_>>().await.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
     #[tokio::test]
     async fn stream_mainnet_genesis() {
         let stream = CarStream::new(mainnet::DEFAULT_GENESIS).await.unwrap();
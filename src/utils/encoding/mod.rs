@@ -96,6 +96,13 @@ pub fn blake2b_256(ingest: &[u8]) -> [u8; 32] {
     ret
 }
 
+/// Generates Keccak-256 hash of fixed 32 bytes size, as used by Ethereum.
+pub fn keccak_256(ingest: &[u8]) -> [u8; 32] {
+    use sha3::{Digest, Keccak256};
+
+    Keccak256::digest(ingest).into()
+}
+
 pub fn prover_id_from_u64(id: u64) -> ProverId {
     let mut prover_id = ProverId::default();
     let prover_bytes = Address::new_id(id).payload().to_raw_bytes();
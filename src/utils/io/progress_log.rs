@@ -87,17 +87,35 @@ impl<S> WithProgress<S> {
         self.progress.item_type = ItemType::Bytes;
         self
     }
+
+    /// Starts from `completed` items, e.g. when resuming a download. The speed
+    /// and ETA only account for the items processed since.
+    pub fn resumed_at(mut self, completed: u64) -> Self {
+        self.progress.completed_items = completed;
+        self.progress.initial_items = completed;
+        self.progress.last_logged_items = completed;
+        self
+    }
+
+    /// Also logs the estimated time left, from the average speed so far.
+    pub fn with_eta(mut self) -> Self {
+        self.progress.show_eta = true;
+        self
+    }
 }
 
 #[derive(Debug, Clone)]
 struct Progress {
     completed_items: u64,
     total_items: Option<u64>,
+    /// Items already completed when the progress started
+    initial_items: u64,
     last_logged_items: u64,
     start: Instant,
     last_logged: Instant,
     message: String,
     item_type: ItemType,
+    show_eta: bool,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -111,12 +129,14 @@ impl Progress {
         let now = Instant::now();
         Self {
             completed_items: 0,
+            initial_items: 0,
             last_logged_items: 0,
             total_items: None,
             start: now,
             last_logged: now,
             message: message.into(),
             item_type: ItemType::Items,
+            show_eta: false,
         }
     }
 
@@ -176,7 +196,21 @@ impl Progress {
             ItemType::Items => format!("{diff:.0} items/s"),
         };
 
-        format!("{message} {at}{total}, {speed}, elapsed time: {elapsed_duration}")
+        let eta = match self.total_items {
+            Some(total) if self.show_eta && total > 0 => {
+                let done = self.completed_items.saturating_sub(self.initial_items) as f64;
+                let left = total.saturating_sub(self.completed_items) as f64;
+                if done > 0.0 {
+                    let eta = Duration::from_secs((left * elapsed_secs / done) as u64);
+                    format!(", eta: {}", format_duration(eta))
+                } else {
+                    ", eta: unknown".into()
+                }
+            }
+            _ => String::new(),
+        };
+
+        format!("{message} {at}{total}, {speed}, elapsed time: {elapsed_duration}{eta}")
     }
 
     fn emit_log_if_required(&mut self) {
@@ -249,6 +283,30 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_progress_msg_eta() {
+        let mut progress = Progress::new("test");
+        let now = progress.start;
+        progress.item_type = ItemType::Bytes;
+        progress.show_eta = true;
+        progress.total_items = Some(1024 * 1024 * 1024);
+        // Resumed at 512MiB
+        progress.completed_items = 1024 * 1024 * 512;
+        progress.initial_items = 1024 * 1024 * 512;
+        progress.last_logged_items = 1024 * 1024 * 512;
+        assert_eq!(
+            progress.msg(now + Duration::from_secs(1)),
+            "test 512 MiB / 1 GiB, 50%, 0 B/s, elapsed time: 1s, eta: unknown"
+        );
+
+        // 128MiB in 10s since resuming, 384MiB left
+        progress.set(1024 * 1024 * 640);
+        assert_eq!(
+            progress.msg(now + Duration::from_secs(10)),
+            "test 640 MiB / 1 GiB, 62%, 12.8 MiB/s, elapsed time: 10s, eta: 30s"
+        );
+    }
+
     #[test]
     fn test_progress_msg_items() {
         let mut progress = Progress::new("test");
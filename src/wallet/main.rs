@@ -23,12 +23,16 @@ where
         .enable_all()
         .build()?
         .block_on(async {
-            let name = api.state_network_name().await?;
-            let chain = NetworkChain::from_str(&name)?;
+            // Nodes that cannot identify their network, e.g. Lotus ones, only
+            // report its name
+            let chain = match api.state_network_identity().await {
+                Ok(identity) => identity.network,
+                Err(_) => NetworkChain::from_str(&api.state_network_name().await?)?,
+            };
             if chain.is_testnet() {
                 CurrentNetwork::set_global(Network::Testnet);
             }
             // Run command
-            cmd.run(api).await
+            cmd.run(api, opts.output).await
         })
 }
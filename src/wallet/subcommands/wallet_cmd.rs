@@ -1,19 +1,21 @@
 // Copyright 2019-2024 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use std::{
-    path::PathBuf,
-    str::{self, FromStr},
-};
+use std::{path::PathBuf, str::FromStr};
 
-use crate::lotus_json::LotusJson;
 use crate::shim::{
     address::{Protocol, StrictAddress},
     crypto::{Signature, SignatureType},
     econ::TokenAmount,
 };
 use crate::utils::io::read_file_to_string;
-use crate::{key_management::KeyInfo, rpc_client::ApiInfo};
+use crate::{
+    key_management::{
+        decode_key_info_hex, encode_key_info_hex, FOREST_KEYSTORE_NEW_PHRASE_ENV,
+        FOREST_KEYSTORE_PHRASE_ENV,
+    },
+    rpc_client::ApiInfo,
+};
 use anyhow::Context as _;
 use base64::{prelude::BASE64_STANDARD, Engine};
 use clap::{arg, Subcommand};
@@ -21,19 +23,25 @@ use dialoguer::{theme::ColorfulTheme, Password};
 use num::BigInt;
 
 use crate::cli::humantoken::TokenAmountPretty as _;
+use crate::cli_shared::cli::OutputFormat;
 
 #[derive(Debug, Subcommand)]
 pub enum WalletCommands {
     /// Create a new wallet
     New {
-        /// The signature type to use. One of SECP256k1, or BLS
+        /// The signature type to use. One of SECP256k1, BLS or delegated
         #[arg(default_value = "secp256k1")]
         signature_type: String,
     },
     /// Get account balance
     Balance {
         /// The address of the account to check
-        address: String,
+        #[arg(required_unless_present = "all")]
+        address: Option<String>,
+        /// List the balances of all addresses of the wallet, largest first,
+        /// and their total
+        #[arg(long, conflicts_with = "address")]
+        all: bool,
     },
     /// Get the default address of the wallet
     Default,
@@ -47,12 +55,18 @@ pub enum WalletCommands {
         /// The key to check
         key: String,
     },
-    /// Import keys from existing wallet
+    /// Import keys from existing wallet. Accepts the output of `export` (also
+    /// from Lotus), or a hex encoded raw private key when `--key-type` is set
     Import {
         /// The path to the private key
         path: Option<String>,
+        /// The type of a raw private key. One of secp256k1, bls or delegated
+        #[arg(long)]
+        key_type: Option<SignatureType>,
     },
-    /// List addresses of the wallet
+    /// List addresses of the wallet. With `--output json`, prints an array
+    /// of objects with the `Address`, whether it is the `Default` one, and
+    /// its `Balance` in attoFIL
     List {
         /// Output is rounded to 4 significant figures by default.
         /// Do not round
@@ -71,9 +85,8 @@ pub enum WalletCommands {
     },
     /// Sign a message
     Sign {
-        /// The hex encoded message to sign
-        #[arg(short)]
-        message: String,
+        #[command(flatten)]
+        message: MessageInput,
         /// The address to be used to sign the message
         #[arg(short)]
         address: String,
@@ -89,9 +102,8 @@ pub enum WalletCommands {
         /// The address used to sign the message
         #[arg(short)]
         address: String,
-        /// The message to verify
-        #[arg(short)]
-        message: String,
+        #[command(flatten)]
+        message: MessageInput,
         /// The signature of the message to verify
         #[arg(short)]
         signature: String,
@@ -101,14 +113,77 @@ pub enum WalletCommands {
         /// The address of the wallet to delete
         address: String,
     },
+    /// Re-encrypt the keystore with a new passphrase. The current and new
+    /// passphrases are read from `FOREST_KEYSTORE_PHRASE` and
+    /// `FOREST_KEYSTORE_NEW_PHRASE` respectively, or prompted for if unset
+    RotatePassphrase,
+    /// Manage watch-only addresses, which are listed by the wallet but have no
+    /// private key on the node
+    #[command(subcommand)]
+    Watch(WatchCommands),
+}
+
+/// A message to sign or verify, given on the command line or as a file.
+#[derive(Debug, clap::Args)]
+pub struct MessageInput {
+    /// The message, hex encoded unless `--base64` is set
+    #[arg(short, required_unless_present = "file")]
+    message: Option<String>,
+    /// Read the message from a file instead. Files are streamed, and their
+    /// BLAKE2b-256 digest stands in for their contents
+    #[arg(long, conflicts_with = "message")]
+    file: Option<PathBuf>,
+    /// The message is hex encoded (the default)
+    #[arg(long, conflicts_with_all = ["base64", "file"])]
+    hex: bool,
+    /// The message is base64 encoded
+    #[arg(long, conflicts_with = "file")]
+    base64: bool,
+}
+
+impl MessageInput {
+    fn into_bytes(self) -> anyhow::Result<Vec<u8>> {
+        match (self.message, self.file) {
+            (_, Some(path)) => {
+                let mut file = std::fs::File::open(&path)
+                    .with_context(|| format!("could not open {}", path.display()))?;
+                let mut hasher = blake2b_simd::Params::new().hash_length(32).to_state();
+                std::io::copy(&mut file, &mut hasher)
+                    .with_context(|| format!("could not read {}", path.display()))?;
+                Ok(hasher.finalize().as_bytes().to_vec())
+            }
+            (Some(message), None) if self.base64 => BASE64_STANDARD
+                .decode(message)
+                .context("Message has to be a base64 string"),
+            (Some(message), None) => hex::decode(message).context("Message has to be a hex string"),
+            (None, None) => anyhow::bail!("a message or a file is required"),
+        }
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum WatchCommands {
+    /// Watch an address without importing its private key
+    Add {
+        /// The address to watch
+        address: String,
+    },
+    /// Stop watching an address
+    Remove {
+        /// The address to stop watching
+        address: String,
+    },
+    /// List watched addresses
+    List,
 }
 
 impl WalletCommands {
-    pub async fn run(self, api: ApiInfo) -> anyhow::Result<()> {
+    pub async fn run(self, api: ApiInfo, output: OutputFormat) -> anyhow::Result<()> {
         match self {
             Self::New { signature_type } => {
                 let signature_type = match signature_type.to_lowercase().as_str() {
                     "secp256k1" => SignatureType::Secp256k1,
+                    "delegated" => SignatureType::Delegated,
                     _ => SignatureType::Bls,
                 };
 
@@ -116,9 +191,30 @@ impl WalletCommands {
                 println!("{response}");
                 Ok(())
             }
-            Self::Balance { address } => {
-                let response = api.wallet_balance(address.to_string()).await?;
-                println!("{response}");
+            Self::Balance { address, all } => {
+                if !all {
+                    let address = address.context("an address is required")?;
+                    let response = api.wallet_balance(address).await?;
+                    println!("{response}");
+                    return Ok(());
+                }
+
+                let mut balances = Vec::new();
+                for address in api.wallet_list().await? {
+                    let balance = api.wallet_balance(address.to_string()).await?;
+                    balances.push((address, TokenAmount::from_atto(balance.parse::<BigInt>()?)));
+                }
+                balances.sort_by(|(_, a), (_, b)| b.cmp(a));
+
+                let total = balances
+                    .iter()
+                    .fold(TokenAmount::default(), |total, (_, balance)| {
+                        total + balance
+                    });
+                for (address, balance) in &balances {
+                    println!("{:41}  {}", address.to_string(), balance.pretty());
+                }
+                println!("{:41}  {}", "Total", total.pretty());
                 Ok(())
             }
             Self::Default => {
@@ -132,8 +228,7 @@ impl WalletCommands {
             Self::Export { address } => {
                 let response = api.wallet_export(address.to_string()).await?;
 
-                let encoded_key = serde_json::to_string(&LotusJson(response))?;
-                println!("{}", hex::encode(encoded_key));
+                println!("{}", encode_key_info_hex(&response));
                 Ok(())
             }
             Self::Has { key } => {
@@ -146,7 +241,41 @@ impl WalletCommands {
                 println!("deleted {address}.");
                 Ok(())
             }
-            Self::Import { path } => {
+            Self::RotatePassphrase => {
+                let old_passphrase = match std::env::var(FOREST_KEYSTORE_PHRASE_ENV) {
+                    Ok(passphrase) => passphrase,
+                    Err(_) => {
+                        tokio::task::spawn_blocking(|| {
+                            Password::with_theme(&ColorfulTheme::default())
+                                .allow_empty_password(true)
+                                .with_prompt("Enter the current keystore passphrase")
+                                .interact()
+                        })
+                        .await??
+                    }
+                };
+                let new_passphrase = match std::env::var(FOREST_KEYSTORE_NEW_PHRASE_ENV) {
+                    Ok(passphrase) => passphrase,
+                    Err(_) => {
+                        tokio::task::spawn_blocking(|| {
+                            Password::with_theme(&ColorfulTheme::default())
+                                .with_prompt("Enter the new keystore passphrase")
+                                .with_confirmation(
+                                    "Confirm the new keystore passphrase",
+                                    "Error: the passphrases do not match.",
+                                )
+                                .interact()
+                        })
+                        .await??
+                    }
+                };
+
+                api.wallet_rotate_passphrase(old_passphrase, new_passphrase)
+                    .await?;
+                println!("Keystore passphrase changed");
+                Ok(())
+            }
+            Self::Import { path, key_type } => {
                 let key = match path {
                     Some(path) => read_file_to_string(&PathBuf::from(path))?,
                     _ => {
@@ -160,14 +289,7 @@ impl WalletCommands {
                     }
                 };
 
-                let key = key.trim();
-
-                let decoded_key = hex::decode(key).context("Key must be hex encoded")?;
-
-                let key_str = str::from_utf8(&decoded_key)?;
-
-                let LotusJson(key) = serde_json::from_str::<LotusJson<KeyInfo>>(key_str)
-                    .context("invalid key format")?;
+                let key = decode_key_info_hex(&key, key_type)?;
 
                 let key = api.wallet_import(vec![key]).await?;
 
@@ -182,6 +304,21 @@ impl WalletCommands {
 
                 let default = api.wallet_default_address().await?;
 
+                if output == OutputFormat::Json {
+                    let mut wallets = Vec::with_capacity(response.len());
+                    for address in response {
+                        let addr = address.to_string();
+                        let balance = api.wallet_balance(addr.clone()).await?;
+                        wallets.push(serde_json::json!({
+                            "Default": default.as_ref() == Some(&addr),
+                            "Address": addr,
+                            "Balance": balance,
+                        }));
+                    }
+                    println!("{}", serde_json::to_string_pretty(&wallets)?);
+                    return Ok(());
+                }
+
                 let (title_address, title_default_mark, title_balance) =
                     ("Address", "Default", "Balance");
                 println!("{title_address:41} {title_default_mark:7} {title_balance}");
@@ -214,6 +351,7 @@ impl WalletCommands {
                 }
                 Ok(())
             }
+            Self::Watch(cmd) => cmd.run(api).await,
             Self::SetDefault { key } => {
                 let StrictAddress(key) = StrictAddress::from_str(&key)
                     .with_context(|| format!("Invalid address: {key}"))?;
@@ -225,8 +363,7 @@ impl WalletCommands {
                 let StrictAddress(address) = StrictAddress::from_str(&address)
                     .with_context(|| format!("Invalid address: {address}"))?;
 
-                let message = hex::decode(message).context("Message has to be a hex string")?;
-                let message = BASE64_STANDARD.encode(message);
+                let message = BASE64_STANDARD.encode(message.into_bytes()?);
 
                 let response = api.wallet_sign(address, message.into_bytes()).await?;
                 println!("{}", hex::encode(response.bytes()));
@@ -249,11 +386,12 @@ impl WalletCommands {
                 let signature = match address.protocol() {
                     Protocol::Secp256k1 => Signature::new_secp256k1(sig_bytes),
                     Protocol::BLS => Signature::new_bls(sig_bytes),
-                    _ => anyhow::bail!("Invalid signature (must be bls or secp256k1)"),
+                    Protocol::Delegated => Signature::new_delegated(sig_bytes),
+                    _ => anyhow::bail!("Invalid signature (must be bls, secp256k1 or delegated)"),
                 };
-                let msg = hex::decode(message).context("Message has to be a hex string")?;
-
-                let response = api.wallet_verify(address, msg, signature).await?;
+                let response = api
+                    .wallet_verify(address, message.into_bytes()?, signature)
+                    .await?;
 
                 println!("{response}");
                 Ok(())
@@ -261,3 +399,32 @@ impl WalletCommands {
         }
     }
 }
+
+impl WatchCommands {
+    pub async fn run(self, api: ApiInfo) -> anyhow::Result<()> {
+        match self {
+            Self::Add { address } => {
+                let StrictAddress(address) = StrictAddress::from_str(&address)
+                    .with_context(|| format!("Invalid address: {address}"))?;
+
+                api.wallet_add_watch(address).await?;
+                println!("watching {address}.");
+                Ok(())
+            }
+            Self::Remove { address } => {
+                let StrictAddress(address) = StrictAddress::from_str(&address)
+                    .with_context(|| format!("Invalid address: {address}"))?;
+
+                api.wallet_remove_watch(address).await?;
+                println!("stopped watching {address}.");
+                Ok(())
+            }
+            Self::List => {
+                for address in api.wallet_list_watch().await? {
+                    println!("{address}");
+                }
+                Ok(())
+            }
+        }
+    }
+}
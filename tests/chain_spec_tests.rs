@@ -0,0 +1,114 @@
+// Copyright 2019-2024 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+pub mod common;
+
+use crate::common::{create_tmp_config, daemon, CommonArgs, CommonEnv};
+
+use predicates::prelude::*;
+use std::path::{Path, PathBuf};
+
+/// Writes a spec of a 2k devnet to `dir`, next to its genesis. The genesis of
+/// calibnet stands in for a devnet one.
+fn write_2k_spec(dir: &Path, extra: &str) -> PathBuf {
+    std::fs::copy(
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("src/networks/calibnet/genesis.car"),
+        dir.join("genesis.car"),
+    )
+    .unwrap();
+    let spec = dir.join("2k.toml");
+    std::fs::write(
+        &spec,
+        format!(
+            r#"
+genesis_file = "genesis.car"
+block_delay_secs = 4
+eth_chain_id = 31415926
+{extra}
+
+[network]
+type = "devnet"
+name = "2k"
+
+[policy]
+pre_commit_challenge_delay = 10
+"#
+        ),
+    )
+    .unwrap();
+    spec
+}
+
+// The daemon boots a devnet from a spec file, which takes precedence over the
+// chain of the configuration
+#[test]
+fn daemon_boots_devnet_from_chain_spec() {
+    let (config_file, data_dir) = create_tmp_config();
+    let spec = write_2k_spec(data_dir.path(), "");
+
+    daemon()
+        .common_env()
+        .common_args()
+        .arg("--config")
+        .arg(config_file)
+        .arg("--encrypt-keystore")
+        .arg("false")
+        .arg("--chain")
+        .arg("calibnet")
+        .arg("--chain-spec")
+        .arg(spec)
+        .assert()
+        .success();
+
+    assert!(data_dir.path().join("2k").exists());
+    assert!(!data_dir.path().join("calibnet").exists());
+}
+
+#[test]
+fn daemon_rejects_mismatching_genesis() {
+    let (config_file, data_dir) = create_tmp_config();
+    let spec = write_2k_spec(
+        data_dir.path(),
+        r#"genesis_cid = "bafy2bzacecnamqgqmifpluoeldx7zzglxcljo6oja4vrmtj7432rphldpdmm2""#,
+    );
+
+    daemon()
+        .common_env()
+        .common_args()
+        .arg("--config")
+        .arg(config_file)
+        .arg("--encrypt-keystore")
+        .arg("false")
+        .arg("--chain-spec")
+        .arg(spec)
+        .assert()
+        .failure();
+}
+
+#[test]
+fn daemon_rejects_invalid_chain_spec() {
+    let (config_file, data_dir) = create_tmp_config();
+    let spec = write_2k_spec(
+        data_dir.path(),
+        r#"
+[height_infos.Thunder]
+epoch = 10
+
+[height_infos.Watermelon]
+epoch = 10
+"#,
+    );
+
+    daemon()
+        .common_env()
+        .common_args()
+        .arg("--config")
+        .arg(config_file)
+        .arg("--encrypt-keystore")
+        .arg("false")
+        .arg("--chain-spec")
+        .arg(spec)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("both scheduled at epoch 10"));
+}
@@ -1,7 +1,13 @@
 // Copyright 2019-2024 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use std::path::PathBuf;
+use std::ffi::OsStr;
+use std::io::{BufRead as _, BufReader};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::process::{Child, ExitStatus, Stdio};
+use std::sync::mpsc;
+use std::time::Duration;
 
 use assert_cmd::Command;
 use tempfile::TempDir;
@@ -14,6 +20,10 @@ pub fn tool() -> Command {
     Command::cargo_bin("forest-tool").unwrap()
 }
 
+pub fn wallet() -> Command {
+    Command::cargo_bin("forest-wallet").unwrap()
+}
+
 pub fn daemon() -> Command {
     Command::cargo_bin("forest").unwrap()
 }
@@ -64,3 +74,143 @@ type = "calibnet"
 
     (config_file, temp_dir)
 }
+
+/// An offline RPC server started by `forest-tool api serve`, killed when
+/// dropped.
+pub struct OfflineServer {
+    pub child: Child,
+    /// Address the RPC server listens at
+    pub rpc_address: SocketAddr,
+    /// Address the Prometheus server listens at, if requested with
+    /// `--metrics-address`
+    pub metrics_address: Option<SocketAddr>,
+    data_dir: PathBuf,
+}
+
+impl OfflineServer {
+    /// Serves the calibnet test snapshot on a port picked by the OS, with
+    /// further `args`. The addresses are read from the logs of the server.
+    pub fn start<S: AsRef<OsStr>>(data_dir: &Path, args: impl IntoIterator<Item = S>) -> Self {
+        Self::start_on("calibnet", data_dir, args)
+    }
+
+    /// Serves the calibnet test snapshot as the given `chain`, like
+    /// [`OfflineServer::start`].
+    pub fn start_on<S: AsRef<OsStr>>(
+        chain: &str,
+        data_dir: &Path,
+        args: impl IntoIterator<Item = S>,
+    ) -> Self {
+        Self::serve(
+            Path::new("test-snapshots/chain4.car"),
+            chain,
+            data_dir,
+            args,
+        )
+    }
+
+    /// Serves the given snapshot as the given `chain`, like
+    /// [`OfflineServer::start`].
+    pub fn serve<S: AsRef<OsStr>>(
+        snapshot: &Path,
+        chain: &str,
+        data_dir: &Path,
+        args: impl IntoIterator<Item = S>,
+    ) -> Self {
+        Self::spawn(Some(snapshot), chain, data_dir, args)
+    }
+
+    /// Serves the snapshots a data directory persisted with `--persist-db` was
+    /// populated from, like [`OfflineServer::start`].
+    pub fn resume<S: AsRef<OsStr>>(data_dir: &Path, args: impl IntoIterator<Item = S>) -> Self {
+        Self::spawn(None, "calibnet", data_dir, args)
+    }
+
+    fn spawn<S: AsRef<OsStr>>(
+        snapshot: Option<&Path>,
+        chain: &str,
+        data_dir: &Path,
+        args: impl IntoIterator<Item = S>,
+    ) -> Self {
+        let mut child = std::process::Command::new(assert_cmd::cargo::cargo_bin("forest-tool"))
+            .args(["api", "serve"])
+            .args(snapshot)
+            .args(["--chain", chain, "--port", "0"])
+            .arg("--data-dir")
+            .arg(data_dir)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        let logs = BufReader::new(child.stderr.take().unwrap());
+        // Keeps draining the logs so the server never blocks on writing them
+        std::thread::spawn(move || {
+            for line in logs.lines().map_while(Result::ok) {
+                for (prefix, is_rpc) in [
+                    ("RPC server listening at ", true),
+                    ("Prometheus server started at ", false),
+                ] {
+                    if let Some((_, rest)) = line.split_once(prefix) {
+                        let address = rest
+                            .split_whitespace()
+                            .next()
+                            .and_then(|it| it.parse().ok());
+                        tx.send((is_rpc, address)).ok();
+                    }
+                }
+            }
+        });
+
+        let mut metrics_address = None;
+        let rpc_address = loop {
+            match rx.recv_timeout(Duration::from_secs(60)) {
+                Ok((true, Some(address))) => break address,
+                Ok((false, address)) => metrics_address = address,
+                _ => {
+                    child.kill().ok();
+                    panic!("RPC server did not start");
+                }
+            }
+        };
+        Self {
+            child,
+            rpc_address,
+            metrics_address,
+            data_dir: data_dir.to_owned(),
+        }
+    }
+}
+
+impl OfflineServer {
+    /// Reads the admin token the server wrote to its data directory.
+    pub fn admin_token(&self) -> String {
+        std::fs::read_to_string(self.data_dir.join("admin_token")).unwrap()
+    }
+
+    /// Returns the `FULLNODE_API_INFO` with which clients connect to the
+    /// server as an admin.
+    pub fn api_info(&self) -> String {
+        format!(
+            "{}:/ip4/127.0.0.1/tcp/{}/http",
+            self.admin_token(),
+            self.rpc_address.port()
+        )
+    }
+
+    /// Shuts the server down gracefully, returning how it exited.
+    pub fn stop(mut self) -> ExitStatus {
+        unsafe { libc::kill(self.child.id() as libc::pid_t, libc::SIGTERM) };
+        self.child.wait().unwrap()
+    }
+}
+
+impl Drop for OfflineServer {
+    fn drop(&mut self) {
+        self.child.kill().ok();
+        self.child.wait().ok();
+    }
+}
@@ -0,0 +1,70 @@
+// Copyright 2019-2024 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+pub mod common;
+
+use crate::common::{cli, OfflineServer};
+
+use std::net::TcpListener;
+
+fn stdout(output: &std::process::Output) -> &str {
+    std::str::from_utf8(&output.stdout).unwrap()
+}
+
+// The offline server answers RPC calls, so it is live, but it serves a
+// snapshot far behind the wall clock and never syncs, so it is not ready.
+#[test]
+fn healthcheck_offline_server() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let data_dir = temp_dir.path().join("db");
+    let server = OfflineServer::start(&data_dir, [] as [&str; 0]);
+    let api_info = server.api_info();
+
+    let live = cli()
+        .env("FULLNODE_API_INFO", &api_info)
+        .args(["healthcheck", "live"])
+        .assert()
+        .success();
+    let live = stdout(live.get_output());
+    assert!(live.starts_with("ok: RPC server responding"), "{live}");
+    assert_eq!(live.lines().count(), 1);
+
+    cli()
+        .env("FULLNODE_API_INFO", &api_info)
+        .args(["healthcheck", "live", "--quiet"])
+        .assert()
+        .success()
+        .stdout("");
+
+    let ready = cli()
+        .env("FULLNODE_API_INFO", &api_info)
+        .args(["healthcheck", "ready"])
+        .assert()
+        .code(1);
+    let ready = stdout(ready.get_output());
+    assert!(ready.starts_with("unhealthy: sync stage is"), "{ready}");
+    assert_eq!(ready.lines().count(), 1);
+}
+
+#[test]
+fn healthcheck_without_server() {
+    // Nothing listens on a port that was free a moment ago
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let api_info = format!("/ip4/127.0.0.1/tcp/{port}/http");
+    for probe in ["live", "ready"] {
+        let output = cli()
+            .env("FULLNODE_API_INFO", &api_info)
+            .args(["healthcheck", probe, "--timeout", "1"])
+            .assert()
+            .code(1);
+        let output = stdout(output.get_output());
+        assert!(
+            output.starts_with("unhealthy: RPC server not responding"),
+            "{output}"
+        );
+    }
+}
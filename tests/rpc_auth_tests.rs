@@ -0,0 +1,58 @@
+// Copyright 2019-2024 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+pub mod common;
+
+use crate::common::OfflineServer;
+
+use jsonrpsee::core::client::ClientT as _;
+use jsonrpsee::rpc_params;
+use jsonrpsee::ws_client::{HeaderMap, HeaderValue, WsClientBuilder};
+use std::net::SocketAddr;
+
+async fn wallet_list(address: SocketAddr, token: Option<&str>) -> serde_json::Value {
+    let request = reqwest::Client::new()
+        .post(format!("http://{address}/rpc/v0"))
+        .json(&serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 0,
+            "method": "Filecoin.WalletList",
+            "params": [],
+        }));
+    let request = match token {
+        Some(token) => request.bearer_auth(token),
+        None => request,
+    };
+    request.send().await.unwrap().json().await.unwrap()
+}
+
+// The offline server issues an administrator token, which grants access to
+// the methods requiring write access over HTTP and WebSocket.
+#[tokio::test]
+async fn offline_rpc_admin_token() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let data_dir = temp_dir.path().join("db");
+    let server = OfflineServer::start(&data_dir, [] as [&str; 0]);
+    let token = std::fs::read_to_string(data_dir.join("admin_token")).unwrap();
+
+    let response = wallet_list(server.rpc_address, None).await;
+    assert!(response["error"].is_object(), "{response}");
+    let response = wallet_list(server.rpc_address, Some(&token)).await;
+    // The wallet is empty, so its `null` list is not checked
+    assert!(response.get("error").is_none(), "{response}");
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        "Authorization",
+        HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+    );
+    let client = WsClientBuilder::default()
+        .set_headers(headers)
+        .build(format!("ws://{}/rpc/v0", server.rpc_address))
+        .await
+        .unwrap();
+    client
+        .request::<serde_json::Value, _>("Filecoin.WalletList", rpc_params![])
+        .await
+        .unwrap();
+}
@@ -0,0 +1,197 @@
+// Copyright 2019-2024 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+pub mod common;
+
+use crate::common::OfflineServer;
+
+use jsonrpsee::client_transport::ws::{
+    EitherStream, Receiver, Sender, Url, WsTransportClientBuilder,
+};
+use jsonrpsee::core::client::{ReceivedMessage, TransportReceiverT as _, TransportSenderT as _};
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use tokio_util::compat::Compat;
+
+/// A raw WebSocket session, as the channel protocol is not understood by
+/// `jsonrpsee` clients.
+struct Session {
+    sender: Sender<Compat<EitherStream>>,
+    receiver: Receiver<Compat<EitherStream>>,
+}
+
+impl Session {
+    async fn send(&mut self, id: Value, method: &str, params: Value) {
+        let request = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        self.sender.send(request.to_string()).await.unwrap();
+    }
+
+    /// Returns the next message matching `filter`, skipping the others.
+    async fn recv(&mut self, filter: impl Fn(&Value) -> bool) -> Value {
+        tokio::time::timeout(Duration::from_secs(30), async {
+            loop {
+                let ReceivedMessage::Text(text) = self.receiver.receive().await.unwrap() else {
+                    continue;
+                };
+                let msg: Value = serde_json::from_str(&text).unwrap();
+                if filter(&msg) {
+                    return msg;
+                }
+            }
+        })
+        .await
+        .expect("no matching message")
+    }
+
+    async fn call(&mut self, id: Value, method: &str, params: Value) -> Value {
+        self.send(id.clone(), method, params).await;
+        self.recv(|msg| msg.get("id") == Some(&id)).await
+    }
+}
+
+async fn active_channels(client: &reqwest::Client, metrics_address: SocketAddr) -> f64 {
+    let metrics = client
+        .get(format!("http://{metrics_address}/metrics"))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    metrics
+        .lines()
+        .find_map(|line| line.strip_prefix("rpc_active_channels "))
+        .map(|value| value.parse().unwrap())
+        .unwrap_or_default()
+}
+
+async fn wait_for_channels(client: &reqwest::Client, metrics_address: SocketAddr, expected: f64) {
+    let deadline = Instant::now() + Duration::from_secs(30);
+    while active_channels(client, metrics_address).await != expected {
+        assert!(
+            Instant::now() < deadline,
+            "timed out waiting for {expected} active channels"
+        );
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+// Cancelling a `ChainNotify` channel over WebSocket ends its notification task
+// on the server, and only the connection that opened it can cancel it.
+#[tokio::test(flavor = "multi_thread")]
+async fn cancel_ends_channel() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let server = OfflineServer::start(
+        &temp_dir.path().join("db"),
+        ["--metrics-address", "127.0.0.1:0"],
+    );
+    let (rpc_address, metrics_address) = (server.rpc_address, server.metrics_address.unwrap());
+    let connect = || async move {
+        let url = Url::parse(&format!("ws://{rpc_address}/rpc/v0")).unwrap();
+        let (sender, receiver) = WsTransportClientBuilder::default()
+            .build(url)
+            .await
+            .unwrap();
+        Session { sender, receiver }
+    };
+    let client = reqwest::Client::new();
+    let mut session = connect().await;
+    let mut other = connect().await;
+
+    let head = session
+        .call(json!(1), "Filecoin.ChainHead", json!([]))
+        .await;
+    assert!(head["result"]["Cids"].is_array(), "{head}");
+
+    let opened = session
+        .call(json!(2), "Filecoin.ChainNotify", json!([]))
+        .await;
+    let channel_id = opened["result"].clone();
+    assert!(channel_id.is_u64(), "{opened}");
+    let current = session
+        .recv(|msg| msg["method"] == "xrpc.ch.val" && msg["params"][0] == channel_id)
+        .await;
+    assert_eq!(current["params"][1][0]["Type"], "current");
+    wait_for_channels(&client, metrics_address, 1.).await;
+
+    // The same request id on another connection refers to another channel
+    let cancelled = other.call(json!(null), "xrpc.cancel", json!([2])).await;
+    assert!(cancelled["error"].is_object(), "{cancelled}");
+    assert_eq!(active_channels(&client, metrics_address).await, 1.);
+
+    let cancelled = session.call(json!(null), "xrpc.cancel", json!([2])).await;
+    assert_eq!(cancelled["result"]["method"], "xrpc.ch.close");
+    assert_eq!(cancelled["result"]["params"][0], channel_id);
+    wait_for_channels(&client, metrics_address, 0.).await;
+
+    // The channel is gone, so it cannot be cancelled twice
+    let cancelled = session.call(json!(null), "xrpc.cancel", json!([2])).await;
+    assert!(cancelled["error"].is_object(), "{cancelled}");
+
+    // The connection remains usable
+    let head = session
+        .call(json!(3), "Filecoin.ChainHead", json!([]))
+        .await;
+    assert!(head["result"]["Cids"].is_array(), "{head}");
+}
+
+/// Returns the next head change notified on `channel_id`.
+async fn next_change(session: &mut Session, channel_id: &Value) -> Value {
+    let notification = session
+        .recv(|msg| msg["method"] == "xrpc.ch.val" && msg["params"][0] == *channel_id)
+        .await;
+    notification["params"][1][0].clone()
+}
+
+// With `--simulate-epochs`, the head of the offline server moves, and
+// `ChainNotify` subscribers are notified of the new tipsets.
+#[tokio::test(flavor = "multi_thread")]
+async fn simulated_epochs_are_notified() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let server = OfflineServer::start(&temp_dir.path().join("db"), ["--simulate-epochs", "0.2"]);
+    let url = Url::parse(&format!("ws://{}/rpc/v0", server.rpc_address)).unwrap();
+    let (sender, receiver) = WsTransportClientBuilder::default()
+        .build(url)
+        .await
+        .unwrap();
+    let mut session = Session { sender, receiver };
+
+    let opened = session
+        .call(json!(1), "Filecoin.ChainNotify", json!([]))
+        .await;
+    let channel_id = opened["result"].clone();
+    let current = next_change(&mut session, &channel_id).await;
+    assert_eq!(current["Type"], "current");
+    let epoch = current["Val"][0]["Height"].as_i64().unwrap();
+    for expected in epoch + 1..epoch + 3 {
+        let applied = next_change(&mut session, &channel_id).await;
+        assert_eq!(applied["Type"], "apply");
+        assert_eq!(applied["Val"][0]["Height"], expected);
+    }
+
+    let head = session
+        .call(json!(2), "Filecoin.ChainHead", json!([]))
+        .await;
+    let head = &head["result"];
+    assert!(head["Height"].as_i64().unwrap() >= epoch + 2, "{head}");
+    // Simulated tipsets are children of the previous head
+    let parent = session
+        .call(
+            json!(3),
+            "Filecoin.ChainGetTipSetByHeight",
+            json!([epoch, null]),
+        )
+        .await;
+    let child = session
+        .call(
+            json!(4),
+            "Filecoin.ChainGetTipSetByHeight",
+            json!([epoch + 1, null]),
+        )
+        .await;
+    assert_eq!(
+        child["result"]["Blocks"][0]["Parents"],
+        parent["result"]["Cids"]
+    );
+}
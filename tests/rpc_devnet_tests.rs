@@ -0,0 +1,86 @@
+// Copyright 2019-2024 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+pub mod common;
+
+use crate::common::{tool, OfflineServer};
+
+use predicates::prelude::*;
+use std::net::SocketAddr;
+use std::path::Path;
+
+/// Name of the devnet, unlike the network name of the genesis state of the
+/// test snapshot, `testnetnet`
+const DEVNET: &str = "2k-devnet";
+
+async fn state_network_name(client: &reqwest::Client, address: SocketAddr) -> String {
+    let response: serde_json::Value = client
+        .post(format!("http://{address}/rpc/v0"))
+        .json(&serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 0,
+            "method": "Filecoin.StateNetworkName",
+            "params": [],
+        }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    response["result"]
+        .as_str()
+        .unwrap_or_else(|| panic!("{response}"))
+        .to_owned()
+}
+
+// The offline server serves a devnet from its genesis file, which is kept in
+// the data directory for later runs. The test snapshot is built on the genesis
+// of mainnet, which stands in for that of the devnet. The network is named as
+// configured rather than after the genesis state.
+#[tokio::test]
+async fn offline_rpc_serves_devnet_genesis() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let data_dir = temp_dir.path().join("db");
+    let genesis = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/networks/mainnet/genesis.car");
+    let client = reqwest::Client::new();
+
+    // Without a genesis file, the devnet cannot be served
+    tool()
+        .args([
+            "api",
+            "serve",
+            "test-snapshots/chain4.car",
+            "--chain",
+            DEVNET,
+        ])
+        .arg("--data-dir")
+        .arg(temp_dir.path().join("no-genesis"))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("no genesis for devnet"));
+
+    {
+        let server = OfflineServer::start_on(
+            DEVNET,
+            &data_dir,
+            [
+                "--persist-db".as_ref(),
+                "--height".as_ref(),
+                "0".as_ref(),
+                "--genesis".as_ref(),
+                genesis.as_os_str(),
+            ],
+        );
+        assert_eq!(
+            state_network_name(&client, server.rpc_address).await,
+            DEVNET
+        );
+    }
+
+    let server = OfflineServer::start_on(DEVNET, &data_dir, ["--persist-db", "--height", "0"]);
+    assert_eq!(
+        state_network_name(&client, server.rpc_address).await,
+        DEVNET
+    );
+}
@@ -0,0 +1,48 @@
+// Copyright 2019-2024 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+pub mod common;
+
+use crate::common::OfflineServer;
+
+use std::net::SocketAddr;
+
+async fn head_epoch(client: &reqwest::Client, address: SocketAddr) -> i64 {
+    let response: serde_json::Value = client
+        .post(format!("http://{address}/rpc/v0"))
+        .json(&serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 0,
+            "method": "Filecoin.ChainHead",
+            "params": [],
+        }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    response["result"]["Height"]
+        .as_i64()
+        .unwrap_or_else(|| panic!("{response}"))
+}
+
+// The offline server can serve the chain as it was at an earlier epoch of the
+// snapshot.
+#[tokio::test]
+async fn offline_rpc_serves_height() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let client = reqwest::Client::new();
+    let head = {
+        let server = OfflineServer::start(&temp_dir.path().join("db"), [] as [&str; 0]);
+        head_epoch(&client, server.rpc_address).await
+    };
+
+    // The test snapshot is short, and only has the state of its first tipsets
+    let height = head - 2;
+    let server = OfflineServer::start(
+        &temp_dir.path().join("db"),
+        ["--height".to_string(), height.to_string()],
+    );
+    assert_eq!(head_epoch(&client, server.rpc_address).await, height);
+}
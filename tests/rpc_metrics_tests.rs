@@ -0,0 +1,77 @@
+// Copyright 2019-2024 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+pub mod common;
+
+use crate::common::OfflineServer;
+
+use std::net::SocketAddr;
+
+async fn call(client: &reqwest::Client, address: SocketAddr, method: &str) {
+    client
+        .post(format!("http://{address}/rpc/v0"))
+        .json(&serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 0,
+            "method": method,
+            "params": [],
+        }))
+        .send()
+        .await
+        .unwrap();
+}
+
+/// Returns the sum of the series starting with `prefix`, which is zero if none
+/// has been recorded yet.
+async fn scrape(client: &reqwest::Client, address: SocketAddr, prefix: &str) -> f64 {
+    let metrics = client
+        .get(format!("http://{address}/metrics"))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    metrics
+        .lines()
+        .filter(|line| line.starts_with(prefix))
+        .filter_map(|line| line.rsplit_once(' ')?.1.parse::<f64>().ok())
+        .sum()
+}
+
+// Calling methods through the offline server moves the RPC metrics exposed by
+// the Prometheus endpoint.
+#[tokio::test]
+async fn offline_rpc_metrics() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let server = OfflineServer::start(
+        &temp_dir.path().join("db"),
+        ["--metrics-address", "127.0.0.1:0"],
+    );
+    let (rpc_address, metrics_address) = (server.rpc_address, server.metrics_address.unwrap());
+
+    let client = reqwest::Client::new();
+    let success = r#"rpc_method_success_total{method="Filecoin.Version"}"#;
+    let latency = r#"rpc_method_time_count{method="Filecoin.Version"}"#;
+    // Unregistered methods are not used as label values
+    let failure = r#"rpc_method_failure_total{method="unknown","#;
+
+    let before = [
+        scrape(&client, metrics_address, success).await,
+        scrape(&client, metrics_address, latency).await,
+        scrape(&client, metrics_address, failure).await,
+    ];
+    call(&client, rpc_address, "Filecoin.Version").await;
+    call(&client, rpc_address, "Filecoin.DoesNotExist").await;
+    let after = [
+        scrape(&client, metrics_address, success).await,
+        scrape(&client, metrics_address, latency).await,
+        scrape(&client, metrics_address, failure).await,
+    ];
+
+    assert_eq!(after, before.map(|it| it + 1.));
+    assert_eq!(
+        scrape(&client, metrics_address, "rpc_inflight_requests ").await,
+        0.
+    );
+}
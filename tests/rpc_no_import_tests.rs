@@ -0,0 +1,67 @@
+// Copyright 2019-2024 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+pub mod common;
+
+use crate::common::OfflineServer;
+
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+async fn call(address: SocketAddr, token: &str, method: &str) -> serde_json::Value {
+    let response: serde_json::Value = reqwest::Client::new()
+        .post(format!("http://{address}/rpc/v0"))
+        .bearer_auth(token)
+        .json(&serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 0,
+            "method": method,
+            "params": [],
+        }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(response.get("error").is_none(), "{response}");
+    response["result"].clone()
+}
+
+// With `--no-import`, blocks written while serving go to memory: neither the
+// snapshot nor the data directory is written to, but for the admin token.
+#[tokio::test]
+async fn offline_rpc_no_import() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let snapshot = temp_dir.path().join("chain4.car");
+    std::fs::copy("test-snapshots/chain4.car", &snapshot).unwrap();
+    let original = std::fs::read(&snapshot).unwrap();
+    let data_dir = temp_dir.path().join("db");
+
+    let server = OfflineServer::serve(
+        &snapshot,
+        "calibnet",
+        &data_dir,
+        ["--no-import", "--simulate-epochs", "0.2"],
+    );
+    let token = server.admin_token();
+    let address = server.rpc_address;
+
+    // The simulated tipsets are written to the block store
+    let height = call(address, &token, "Filecoin.ChainHead").await["Height"].clone();
+    let deadline = Instant::now() + Duration::from_secs(60);
+    while call(address, &token, "Filecoin.ChainHead").await["Height"] == height {
+        assert!(Instant::now() < deadline, "the head didn't move");
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    call(address, &token, "Filecoin.ChainFlush").await;
+
+    let entries: Vec<_> = std::fs::read_dir(&data_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    assert_eq!(entries, ["admin_token"]);
+    assert!(server.stop().success());
+    assert_eq!(std::fs::read(&snapshot).unwrap(), original);
+    assert!(!data_dir.exists());
+}
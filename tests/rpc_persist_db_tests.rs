@@ -0,0 +1,52 @@
+// Copyright 2019-2024 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+pub mod common;
+
+use crate::common::OfflineServer;
+
+use std::net::SocketAddr;
+
+async fn chain_head(address: SocketAddr) -> serde_json::Value {
+    let response: serde_json::Value = reqwest::Client::new()
+        .post(format!("http://{address}/rpc/v0"))
+        .json(&serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 0,
+            "method": "Filecoin.ChainHead",
+            "params": [],
+        }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    response["result"].clone()
+}
+
+// A data directory persisted by the offline server is reused on restart, and
+// removed once the server is no longer asked to persist it.
+#[tokio::test]
+async fn offline_rpc_persists_db() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let data_dir = temp_dir.path().join("db");
+
+    let server = OfflineServer::start(&data_dir, ["--persist-db"]);
+    let head = chain_head(server.rpc_address).await;
+    assert!(head["Cids"].is_array(), "{head}");
+    assert!(server.stop().success());
+    assert!(data_dir.is_dir());
+
+    // Without snapshots, the server could only start by reusing the data
+    // directory, as it cannot download one
+    let server = OfflineServer::resume(&data_dir, ["--persist-db"]);
+    assert_eq!(chain_head(server.rpc_address).await, head);
+    assert!(server.stop().success());
+    assert!(data_dir.is_dir());
+
+    let server = OfflineServer::start(&data_dir, ["--cleanup-on-exit"]);
+    assert_eq!(chain_head(server.rpc_address).await, head);
+    assert!(server.stop().success());
+    assert!(!data_dir.exists());
+}
@@ -0,0 +1,147 @@
+// Copyright 2019-2024 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+pub mod common;
+
+use crate::common::OfflineServer;
+
+use std::ffi::CString;
+use std::net::{SocketAddr, TcpStream};
+use std::os::unix::ffi::OsStrExt as _;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+fn mkfifo(path: &Path) {
+    let path = CString::new(path.as_os_str().as_bytes()).unwrap();
+    assert_eq!(unsafe { libc::mkfifo(path.as_ptr(), 0o600) }, 0);
+}
+
+fn wait_until(what: &str, mut condition: impl FnMut() -> bool) {
+    let deadline = Instant::now() + Duration::from_secs(60);
+    while !condition() {
+        assert!(Instant::now() < deadline, "timed out waiting for {what}");
+        std::thread::sleep(Duration::from_millis(100));
+    }
+}
+
+async fn inflight_requests(client: &reqwest::Client, metrics_address: SocketAddr) -> f64 {
+    let metrics = client
+        .get(format!("http://{metrics_address}/metrics"))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    metrics
+        .lines()
+        .find_map(|line| line.strip_prefix("rpc_inflight_requests "))
+        .map(|value| value.parse().unwrap())
+        .unwrap_or_default()
+}
+
+// A call in progress when the offline server is asked to shut down is allowed
+// to complete before the server exits.
+#[tokio::test(flavor = "multi_thread")]
+async fn offline_rpc_drains_calls_on_shutdown() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let mut server = OfflineServer::start(
+        &temp_dir.path().join("db"),
+        ["--metrics-address", "127.0.0.1:0"],
+    );
+    let (rpc_address, metrics_address) = (server.rpc_address, server.metrics_address.unwrap());
+
+    // The export blocks on opening the pipe until it is read, which makes the
+    // call as slow as needed
+    let fifo = temp_dir.path().join("export.car");
+    mkfifo(&fifo);
+    let client = reqwest::Client::new();
+    let export = tokio::spawn(
+        client
+            .post(format!("http://{rpc_address}/rpc/v0"))
+            .json(&serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 0,
+                "method": "Filecoin.ChainExport",
+                "params": [{
+                    "epoch": 0,
+                    "recent_roots": 900,
+                    "output_path": fifo,
+                    "tipset_keys": null,
+                    "skip_checksum": false,
+                    "dry_run": false,
+                }],
+            }))
+            .send(),
+    );
+    while inflight_requests(&client, metrics_address).await < 1. {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    unsafe { libc::kill(server.child.id() as libc::pid_t, libc::SIGTERM) };
+    wait_until("the RPC server to stop accepting connections", || {
+        TcpStream::connect(rpc_address).is_err()
+    });
+    assert!(server.child.try_wait().unwrap().is_none());
+
+    let exported = tokio::task::spawn_blocking(move || std::fs::read(fifo).unwrap());
+    let response: serde_json::Value = export.await.unwrap().unwrap().json().await.unwrap();
+    assert!(response["result"].is_string(), "{response}");
+    assert!(!exported.await.unwrap().is_empty());
+
+    let mut status = None;
+    wait_until("the RPC server to exit", || {
+        status = server.child.try_wait().unwrap();
+        status.is_some()
+    });
+    assert!(status.unwrap().success());
+}
+
+fn cli(server: &OfflineServer) -> assert_cmd::Command {
+    let mut cli = common::cli();
+    cli.env("FULLNODE_API_INFO", server.api_info());
+    cli
+}
+
+// `forest-cli shutdown` makes the offline server exit gracefully, once the
+// head is flushed with `chain flush`.
+#[test]
+fn offline_rpc_flush_and_shutdown() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let data_dir = temp_dir.path().join("db");
+    let mut server = OfflineServer::start(&data_dir, [] as [&str; 0]);
+
+    let head: serde_json::Value = serde_json::from_slice(
+        &cli(&server)
+            .args(["chain", "head", "--format", "json"])
+            .assert()
+            .success()
+            .get_output()
+            .stdout,
+    )
+    .unwrap();
+    cli(&server)
+        .args(["chain", "flush"])
+        .assert()
+        .success()
+        .stdout(format!("Persisted head at epoch {}\n", head["Height"]));
+
+    // Declining the prompt leaves the server running
+    cli(&server)
+        .arg("shutdown")
+        .write_stdin("n\n")
+        .assert()
+        .success();
+    assert!(server.child.try_wait().unwrap().is_none());
+
+    cli(&server)
+        .args(["shutdown", "--force"])
+        .assert()
+        .success();
+    let mut status = None;
+    wait_until("the RPC server to exit", || {
+        status = server.child.try_wait().unwrap();
+        status.is_some()
+    });
+    assert!(status.unwrap().success());
+}
@@ -0,0 +1,46 @@
+// Copyright 2019-2024 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+pub mod common;
+
+use std::os::unix::fs::{FileTypeExt as _, PermissionsExt as _};
+use std::time::{Duration, Instant};
+
+use predicates::prelude::*;
+
+use crate::common::{cli, OfflineServer};
+
+// The offline RPC server serves the API over a Unix socket that only the
+// current user may access, and removes it on shutdown.
+#[test]
+fn offline_rpc_over_unix_socket() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let socket = temp_dir.path().join("forest.sock");
+
+    let mut server = OfflineServer::start(
+        &temp_dir.path().join("db"),
+        ["--unix-socket".as_ref(), socket.as_os_str()],
+    );
+
+    // The socket is bound after the TCP listener
+    let deadline = Instant::now() + Duration::from_secs(60);
+    while !socket.exists() {
+        assert!(Instant::now() < deadline, "RPC socket was not created");
+        std::thread::sleep(Duration::from_millis(100));
+    }
+
+    let metadata = std::fs::metadata(&socket).unwrap();
+    assert!(metadata.file_type().is_socket());
+    assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+
+    cli()
+        .env("FULLNODE_API_INFO", format!("/unix{}", socket.display()))
+        .args(["attach", "--exec", "version()"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("APIVersion"));
+
+    unsafe { libc::kill(server.child.id() as libc::pid_t, libc::SIGTERM) };
+    assert!(server.child.wait().unwrap().success());
+    assert!(!socket.exists());
+}
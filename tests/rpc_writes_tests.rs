@@ -0,0 +1,117 @@
+// Copyright 2019-2024 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+pub mod common;
+
+use crate::common::OfflineServer;
+
+use std::net::SocketAddr;
+
+async fn call(
+    address: SocketAddr,
+    token: &str,
+    method: &str,
+    params: serde_json::Value,
+) -> serde_json::Value {
+    reqwest::Client::new()
+        .post(format!("http://{address}/rpc/v0"))
+        .bearer_auth(token)
+        .json(&serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 0,
+            "method": method,
+            "params": params,
+        }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap()
+}
+
+// With `--enable-writes`, the offline server creates keys in a keystore of its
+// data directory, signs with them, and accepts messages to push.
+#[tokio::test]
+async fn offline_rpc_enable_writes() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let data_dir = temp_dir.path().join("db");
+    let args = ["--enable-writes", "--persist-db"];
+
+    let server = OfflineServer::start(&data_dir, args);
+    let token = server.admin_token();
+    let response = call(
+        server.rpc_address,
+        &token,
+        "Filecoin.WalletNew",
+        serde_json::json!(["secp256k1"]),
+    )
+    .await;
+    let address = response["result"].clone();
+    assert!(address.is_string(), "{response}");
+
+    let message = serde_json::json!({
+        "Version": 0,
+        "To": "f099",
+        "From": address,
+        "Nonce": 0,
+        "Value": "0",
+        "GasLimit": 1000000,
+        "GasFeeCap": "100000",
+        "GasPremium": "1000",
+        "Method": 0,
+        "Params": null,
+    });
+    let response = call(
+        server.rpc_address,
+        &token,
+        "Filecoin.WalletSignMessage",
+        serde_json::json!([address, message]),
+    )
+    .await;
+    let signed = response["result"].clone();
+    assert_eq!(signed["Signature"]["Type"], 1, "{response}");
+
+    // The new key has no funds, and the test snapshot has no usable state at
+    // its head, so the message pool rejects the message
+    let response = call(
+        server.rpc_address,
+        &token,
+        "Filecoin.MpoolPush",
+        serde_json::json!([signed]),
+    )
+    .await;
+    assert!(response["error"].is_object(), "{response}");
+    let response = call(
+        server.rpc_address,
+        &token,
+        "Filecoin.MpoolPending",
+        serde_json::json!([null]),
+    )
+    .await;
+    assert!(response["result"].is_null(), "{response}");
+    assert!(server.stop().success());
+
+    // Keys are kept across restarts, along with the data directory
+    let server = OfflineServer::resume(&data_dir, args);
+    let response = call(
+        server.rpc_address,
+        &server.admin_token(),
+        "Filecoin.WalletList",
+        serde_json::json!([]),
+    )
+    .await;
+    assert_eq!(response["result"], serde_json::json!([address]));
+    assert!(server.stop().success());
+
+    // Without `--enable-writes`, keys only live in memory
+    let server = OfflineServer::resume(&data_dir, ["--cleanup-on-exit"]);
+    let response = call(
+        server.rpc_address,
+        &server.admin_token(),
+        "Filecoin.WalletList",
+        serde_json::json!([]),
+    )
+    .await;
+    assert!(response["result"].is_null(), "{response}");
+}
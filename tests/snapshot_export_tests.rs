@@ -0,0 +1,79 @@
+// Copyright 2019-2024 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+pub mod common;
+
+use crate::common::{cli, OfflineServer};
+
+use assert_cmd::Command;
+use std::path::Path;
+
+/// Network name of the genesis state of the test snapshot
+const DEVNET: &str = "testnetnet";
+
+/// Starts an offline devnet server for the snapshot, returning it along with
+/// the `forest-cli` commands to run against it.
+fn serve(snapshot: &Path, data_dir: &Path, args: &[&str]) -> (OfflineServer, impl Fn() -> Command) {
+    let genesis = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/networks/mainnet/genesis.car");
+    let server = OfflineServer::serve(
+        snapshot,
+        DEVNET,
+        data_dir,
+        ["--genesis".as_ref(), genesis.as_os_str()]
+            .into_iter()
+            .chain(args.iter().map(|arg| arg.as_ref())),
+    );
+    let api_info = server.api_info();
+    let cli = move || {
+        let mut cli = cli();
+        cli.env("FULLNODE_API_INFO", &api_info);
+        cli
+    };
+    (server, cli)
+}
+
+fn json(command: &mut Command) -> serde_json::Value {
+    serde_json::from_slice(&command.assert().success().get_output().stdout).unwrap()
+}
+
+// A tipset below the head, given by its epoch or its key, is exported and the
+// snapshot is served with that tipset as its head. Only the first tipsets of
+// the test snapshot have a state in the devnet.
+#[test]
+fn snapshot_export_at_tipset() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let (_server, cli) = serve(
+        Path::new("test-snapshots/chain4.car"),
+        &temp_dir.path().join("db"),
+        &["--height", "1"],
+    );
+    let genesis = json(cli().args(["chain", "genesis"]));
+    assert_eq!(genesis["Height"], 0);
+    let genesis_cid = genesis["Cids"][0]["/"].as_str().unwrap().to_owned();
+
+    let by_epoch = temp_dir.path().join("by_epoch.forest.car.zst");
+    cli()
+        .args(["snapshot", "export", "--tipset", "0", "-o"])
+        .arg(&by_epoch)
+        .assert()
+        .success();
+    assert!(by_epoch.with_extension("sha256sum").exists());
+
+    let by_key = temp_dir.path().join("by_key.forest.car.zst");
+    cli()
+        .args(["snapshot", "export", "--skip-checksum", "--tipset"])
+        .arg(format!("@{genesis_cid}"))
+        .args(["--skip-old-messages", "false", "-o"])
+        .arg(&by_key)
+        .assert()
+        .success();
+    assert_eq!(
+        std::fs::read(&by_epoch).unwrap(),
+        std::fs::read(&by_key).unwrap()
+    );
+
+    let (_server, cli) = serve(&by_key, &temp_dir.path().join("reimported"), &[]);
+    let head = json(cli().args(["chain", "head", "--format", "json"]));
+    assert_eq!(head["Height"], 0);
+    assert_eq!(head["Cids"][0]["/"], genesis_cid.as_str());
+}
@@ -21,9 +21,10 @@ fn export_empty_archive() {
         .arg(&temp_file)
         .assert()
         .failure()
-        .stderr(predicate::eq(
-            "Error: input not recognized as any kind of CAR data (.car, .car.zst, .forest.car)\n",
-        ));
+        .stderr(predicate::eq(format!(
+            "Error: couldn't load {}\n\nCaused by:\n    input not recognized as any kind of CAR data (.car, .car.zst, .forest.car)\n",
+            temp_file.display()
+        )));
 }
 
 // Running `forest-tool state-migration actor-bundle` may not fail.
@@ -237,3 +238,99 @@ fn keypair_conversion_roundtrip() {
     let keypair_decoded = BASE64_STANDARD.decode(keypair_encoded).unwrap();
     assert_eq!(keypair, keypair_decoded);
 }
+
+// Epochs are only simulated on test networks
+#[test]
+fn api_serve_refuses_simulated_epochs_on_mainnet() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    tool()
+        .args([
+            "api",
+            "serve",
+            "--chain",
+            "mainnet",
+            "--simulate-epochs",
+            "1",
+        ])
+        .arg("--data-dir")
+        .arg(temp_dir.path().join("db"))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "epochs cannot be simulated on mainnet",
+        ));
+}
+
+#[test]
+fn chain_config_validate_reports_violations() {
+    tool()
+        .args(["chain-config", "validate", "--chain", "calibnet"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "The calibnet chain configuration is valid",
+        ));
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let spec = temp_dir.path().join("2k.toml");
+    std::fs::write(
+        &spec,
+        r#"
+[network]
+type = "devnet"
+name = "2k"
+
+[height_infos.Dragon]
+epoch = 10
+
+[height_infos.Thunder]
+epoch = 100
+"#,
+    )
+    .unwrap();
+    tool()
+        .args(["chain-config", "validate", "--chain-spec"])
+        .arg(&spec)
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains(
+            "violation: upgrade Dragon at epoch 10 precedes the Thunder upgrade at epoch 100",
+        ));
+}
+
+// Every block of a snapshot is checked against its CID, and the exit code
+// tells a corrupted snapshot from a truncated one. The test snapshot is built
+// on the genesis of mainnet.
+#[test]
+fn snapshot_validate_detects_damage() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let validate = |snapshot: &std::path::Path| {
+        let mut validate = tool();
+        validate
+            .args(["snapshot", "validate", "--check-links", "0"])
+            .args(["--check-stateroots", "0", "--check-network", "mainnet"])
+            .arg(snapshot);
+        validate.assert()
+    };
+    validate("test-snapshots/chain4.car".as_ref()).success();
+    validate("test-snapshots/chain4.car.zst".as_ref()).success();
+
+    for source in ["test-snapshots/chain4.car", "test-snapshots/chain4.car.zst"] {
+        let car = std::fs::read(source).unwrap();
+
+        let truncated = temp_dir.path().join("truncated.car");
+        std::fs::write(&truncated, &car[..car.len() * 2 / 3]).unwrap();
+        validate(&truncated)
+            .code(4)
+            .stderr(predicate::str::contains("snapshot is truncated"));
+    }
+
+    let mut car = std::fs::read("test-snapshots/chain4.car").unwrap();
+    let len = car.len();
+    car[len * 2 / 3] ^= 0xff;
+    let corrupted = temp_dir.path().join("corrupted.car");
+    std::fs::write(&corrupted, car).unwrap();
+    validate(&corrupted)
+        .code(3)
+        .stderr(predicate::str::contains("snapshot is corrupted"));
+}
@@ -0,0 +1,101 @@
+// Copyright 2019-2024 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+pub mod common;
+
+use crate::common::{wallet, OfflineServer};
+
+use assert_cmd::Command;
+use fvm_shared4::address::{set_current_network, Network};
+use predicates::prelude::*;
+use std::path::Path;
+
+fn output(command: &mut Command) -> String {
+    let output = command.assert().success().get_output().stdout.clone();
+    String::from_utf8(output).unwrap().trim().to_owned()
+}
+
+/// Starts an offline server with writes enabled, returning it along with the
+/// `forest-wallet` commands to run against it.
+fn serve(data_dir: &Path, args: &[&str]) -> (OfflineServer, impl Fn() -> Command) {
+    let server = OfflineServer::start(data_dir, ["--enable-writes"].iter().chain(args));
+    let api_info = server.api_info();
+    let wallet = move || {
+        let mut wallet = wallet();
+        wallet.env("FULLNODE_API_INFO", &api_info);
+        wallet
+    };
+    (server, wallet)
+}
+
+// Files and encoded messages are signed with the keys of an offline server.
+#[test]
+fn wallet_sign_and_verify() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let (_server, wallet) = serve(&temp_dir.path().join("db"), &[]);
+
+    // The offline server formats addresses for mainnet, while the wallet
+    // expects those of the calibnet snapshot it serves
+    let address = Network::Mainnet
+        .parse_address(&output(wallet().arg("new")))
+        .unwrap();
+    set_current_network(Network::Testnet);
+    let address = address.to_string();
+
+    let file = temp_dir.path().join("release.tar.gz");
+    std::fs::write(&file, vec![7; 3 << 20]).unwrap();
+    let signature = output(wallet().args(["sign", "-a", &address, "--file"]).arg(&file));
+    let verify = |file: &Path| {
+        output(
+            wallet()
+                .args(["verify", "-a", &address, "-s", &signature, "--file"])
+                .arg(file),
+        )
+    };
+    assert_eq!(verify(&file), "true");
+    std::fs::write(&file, vec![7; (3 << 20) + 1]).unwrap();
+    assert_eq!(verify(&file), "false");
+
+    // The same message, hex or base64 encoded
+    let signature = output(wallet().args(["sign", "-a", &address, "-m", "68656c6c6f"]));
+    let verify = |encoding: &str, message: &str| {
+        output(wallet().args([
+            "verify", "-a", &address, "-s", &signature, encoding, "-m", message,
+        ]))
+    };
+    assert_eq!(verify("--hex", "68656c6c6f"), "true");
+    assert_eq!(verify("--base64", "aGVsbG8="), "true");
+    assert_eq!(verify("--base64", "aGVsbG8h"), "false");
+    wallet()
+        .args(["sign", "-a", &address, "--base64", "-m", "not base64!"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("base64"));
+    wallet()
+        .args(["sign", "-a", &address, "-m", "00", "--file"])
+        .arg(&file)
+        .assert()
+        .failure();
+}
+
+// The balances of all addresses of the wallet are listed largest first. Only
+// the genesis state of the test snapshot is usable, and its state tree cannot
+// look up key addresses, so actors of the genesis are watched instead.
+#[test]
+fn wallet_balance_all() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let (_server, wallet) = serve(&temp_dir.path().join("db"), &["--height", "0"]);
+
+    for address in ["t00", "t02", "t099"] {
+        wallet().args(["watch", "add", address]).assert().success();
+    }
+    let balances = output(wallet().args(["balance", "--all"]));
+    let lines = balances.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 4, "{balances}");
+    // The reward actor holds the funds to be mined
+    assert!(lines[0].starts_with("t02 "), "{balances}");
+    assert!(lines[3].starts_with("Total "), "{balances}");
+    assert!(!lines[3].ends_with(" 0 FIL"), "{balances}");
+
+    wallet().arg("balance").assert().failure();
+}